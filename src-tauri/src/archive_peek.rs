@@ -0,0 +1,320 @@
+// src-tauri/src/archive_peek.rs
+// Zip listings before download: peek_archive reads just the end of a shared .zip (the
+// end-of-central-directory record and the central directory after it) with Range requests and
+// returns the names and sizes inside, so a large archive can be looked into before it's accepted.
+// Works on local files too (one we serve, or a finished download).
+
+use crate::commands::AppState;
+use crate::download_policy;
+use crate::http_client;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use tauri::{AppHandle, Manager, Runtime};
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const EOCD_LEN: u64 = 22;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_LOCATOR_LEN: u64 = 20;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_EOCD_LEN: u64 = 56;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const CENTRAL_HEADER_LEN: usize = 46;
+/// The archive comment can be up to 64 KiB, so the EOCD is somewhere in this much of the tail
+const MAX_TAIL: u64 = EOCD_LEN + 0xFFFF + ZIP64_LOCATOR_LEN;
+/// Central directories larger than this aren't fetched (that's a few hundred thousand entries)
+const MAX_DIRECTORY_BYTES: u64 = 32 * 1024 * 1024;
+/// Entries listed; the totals still cover all of them
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// Uncompressed size
+    pub size: u64,
+    pub compressed_size: u64,
+    pub is_dir: bool,
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveListing {
+    pub entries: Vec<ArchiveEntry>,
+    /// Files in the archive (directories not counted)
+    pub file_count: u64,
+    /// What it takes on disk once extracted
+    pub total_size: u64,
+    /// Size of the archive itself
+    pub archive_size: u64,
+    /// More entries than listed
+    pub truncated: bool,
+}
+
+fn u16_at(buf: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([buf[pos], buf[pos + 1]])
+}
+
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap())
+}
+
+/// Where the central directory is and how many entries it holds: (offset, size, entries)
+fn locate_directory(
+    len: u64,
+    read_at: &mut impl FnMut(u64, u64) -> Result<Vec<u8>, String>,
+) -> Result<(u64, u64, u64), String> {
+    if len < EOCD_LEN {
+        return Err("Not a zip archive".to_string());
+    }
+    let tail_start = len.saturating_sub(MAX_TAIL);
+    let tail = read_at(tail_start, len - tail_start)?;
+    // Last signature whose comment runs exactly to the end; a comment may contain the signature
+    let eocd = (0..=tail.len() - EOCD_LEN as usize)
+        .rev()
+        .find(|&i| {
+            u32_at(&tail, i) == EOCD_SIGNATURE
+                && i + EOCD_LEN as usize + u16_at(&tail, i + 20) as usize == tail.len()
+        })
+        .ok_or("Not a zip archive")?;
+    let entries = u16_at(&tail, eocd + 10) as u64;
+    let size = u32_at(&tail, eocd + 12) as u64;
+    let offset = u32_at(&tail, eocd + 16) as u64;
+    if entries != 0xFFFF && size != 0xFFFF_FFFF && offset != 0xFFFF_FFFF {
+        return Ok((offset, size, entries));
+    }
+
+    // ZIP64: the real values are in a second record the locator before the EOCD points to
+    let locator = eocd
+        .checked_sub(ZIP64_LOCATOR_LEN as usize)
+        .filter(|&l| u32_at(&tail, l) == ZIP64_LOCATOR_SIGNATURE)
+        .ok_or("Damaged zip64 archive")?;
+    let record_offset = u64_at(&tail, locator + 8);
+    if record_offset
+        .checked_add(ZIP64_EOCD_LEN)
+        .is_none_or(|end| end > len)
+    {
+        return Err("Damaged zip64 archive".to_string());
+    }
+    let record = read_at(record_offset, ZIP64_EOCD_LEN)?;
+    if u32_at(&record, 0) != ZIP64_EOCD_SIGNATURE {
+        return Err("Damaged zip64 archive".to_string());
+    }
+    Ok((
+        u64_at(&record, 48),
+        u64_at(&record, 40),
+        u64_at(&record, 32),
+    ))
+}
+
+/// Sizes from the zip64 extra field, for the ones the header marks as 0xFFFFFFFF
+fn zip64_sizes(extra: &[u8], mut size: u64, mut compressed: u64) -> (u64, u64) {
+    let mut pos = 0;
+    while pos + 4 <= extra.len() {
+        let (id, field_len) = (u16_at(extra, pos), u16_at(extra, pos + 2) as usize);
+        let field = &extra[pos + 4..(pos + 4 + field_len).min(extra.len())];
+        if id == 0x0001 {
+            let mut at = 0;
+            if size == 0xFFFF_FFFF && field.len() >= at + 8 {
+                size = u64_at(field, at);
+                at += 8;
+            }
+            if compressed == 0xFFFF_FFFF && field.len() >= at + 8 {
+                compressed = u64_at(field, at);
+            }
+            break;
+        }
+        pos += 4 + field_len;
+    }
+    (size, compressed)
+}
+
+/// List a zip of `len` bytes, reading it only through `read_at(offset, count)`
+pub fn list(
+    len: u64,
+    mut read_at: impl FnMut(u64, u64) -> Result<Vec<u8>, String>,
+) -> Result<ArchiveListing, String> {
+    let (offset, size, count) = locate_directory(len, &mut read_at)?;
+    if offset.checked_add(size).is_none_or(|end| end > len) {
+        return Err("Damaged zip archive".to_string());
+    }
+    if size > MAX_DIRECTORY_BYTES {
+        return Err("The archive lists too many files to preview".to_string());
+    }
+    let directory = read_at(offset, size)?;
+
+    let mut listing = ArchiveListing {
+        entries: Vec::new(),
+        file_count: 0,
+        total_size: 0,
+        archive_size: len,
+        truncated: false,
+    };
+    let mut pos = 0;
+    for _ in 0..count {
+        if pos + CENTRAL_HEADER_LEN > directory.len()
+            || u32_at(&directory, pos) != CENTRAL_HEADER_SIGNATURE
+        {
+            return Err("Damaged zip archive".to_string());
+        }
+        let flags = u16_at(&directory, pos + 8);
+        let name_len = u16_at(&directory, pos + 28) as usize;
+        let extra_len = u16_at(&directory, pos + 30) as usize;
+        let comment_len = u16_at(&directory, pos + 32) as usize;
+        let name_start = pos + CENTRAL_HEADER_LEN;
+        let next = name_start + name_len + extra_len + comment_len;
+        if next > directory.len() {
+            return Err("Damaged zip archive".to_string());
+        }
+        let name =
+            String::from_utf8_lossy(&directory[name_start..name_start + name_len]).into_owned();
+        let (size, compressed_size) = zip64_sizes(
+            &directory[name_start + name_len..name_start + name_len + extra_len],
+            u32_at(&directory, pos + 24) as u64,
+            u32_at(&directory, pos + 20) as u64,
+        );
+        let is_dir = name.ends_with('/');
+        if !is_dir {
+            listing.file_count += 1;
+            listing.total_size = listing.total_size.saturating_add(size);
+        }
+        if listing.entries.len() < MAX_ENTRIES {
+            listing.entries.push(ArchiveEntry {
+                name,
+                size,
+                compressed_size,
+                is_dir,
+                encrypted: flags & 1 != 0,
+            });
+        } else {
+            listing.truncated = true;
+        }
+        pos = next;
+    }
+    Ok(listing)
+}
+
+fn list_local(path: &str) -> Result<ArchiveListing, String> {
+    let mut file = File::open(path).map_err(|e| format!("Can't open {}: {}", path, e))?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    list(len, |offset, count| {
+        let mut buf = vec![0; count as usize];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut buf))
+            .map_err(|e| e.to_string())?;
+        Ok(buf)
+    })
+}
+
+fn list_remote(url: &str) -> Result<ArchiveListing, String> {
+    let len =
+        download_policy::remote_size(url).ok_or("The sender didn't say how large the file is")?;
+    let client = http_client::client();
+    list(len, |offset, count| {
+        // "bytes=n-(n-1)" would be malformed; nothing to fetch anyway
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let response = client
+            .get(url)
+            .header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", offset, offset + count - 1),
+            )
+            .send()
+            .map_err(|e| format!("Fetching the archive failed: {}", e))?;
+        // A 200 would be the whole archive, which is what peeking is meant to avoid
+        if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Err(format!(
+                "The sender doesn't support partial downloads ({})",
+                response.status()
+            ));
+        }
+        let data = response.bytes().map_err(|e| e.to_string())?;
+        if data.len() as u64 != count {
+            return Err("The sender returned a different part of the archive".to_string());
+        }
+        Ok(data.to_vec())
+    })
+}
+
+// ============ COMMANDS ============
+
+/// What's inside a zip without downloading it. Pass the file message's `url` (with the
+/// sender's address filled in), or a `file_id`: a file we serve, or a download (one awaiting
+/// approval is peeked at over the network, a finished one read from disk).
+#[tauri::command]
+pub async fn peek_archive<R: Runtime>(
+    app: AppHandle<R>,
+    url: Option<String>,
+    file_id: Option<String>,
+) -> Result<ArchiveListing, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Some(url) = url {
+            return list_remote(&url);
+        }
+        let file_id = file_id.ok_or("Give a url or a file id")?;
+        let state = app.state::<AppState>();
+        if let Some(path) = state.file_server.file_path(&file_id) {
+            return list_local(&path.to_string_lossy());
+        }
+        let download = state
+            .db
+            .get_download(&file_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("File not found")?;
+        match (download.path, download.url) {
+            (Some(path), _) if download.status == "complete" => list_local(&path),
+            (_, Some(url)) => list_remote(&url),
+            _ => Err("File not found".to_string()),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_listing_reads_only_the_directory() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("photos/", options).unwrap();
+        writer.start_file("photos/beach.jpg", options).unwrap();
+        writer.write_all(&[7u8; 5000]).unwrap();
+        writer.start_file("readme.txt", options).unwrap();
+        writer.write_all(b"hello").unwrap();
+        writer.set_comment("made with PK\x05\x06 in the comment");
+        let data = writer.finish().unwrap().into_inner();
+
+        let mut reads = 0;
+        let listing = list(data.len() as u64, |offset, count| {
+            reads += 1;
+            Ok(data[offset as usize..(offset + count) as usize].to_vec())
+        })
+        .unwrap();
+
+        let names: Vec<_> = listing.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["photos/", "photos/beach.jpg", "readme.txt"]);
+        assert!(listing.entries[0].is_dir);
+        assert_eq!(listing.entries[1].size, 5000);
+        assert!(listing.entries[1].compressed_size < 5000);
+        assert_eq!((listing.file_count, listing.total_size), (2, 5005));
+        assert!(!listing.truncated);
+        // The tail, then the directory
+        assert_eq!(reads, 2);
+
+        assert!(list(5, |_, _| Ok(vec![0; 5])).is_err());
+        assert!(list(data.len() as u64 - 1, |offset, count| {
+            Ok(data[offset as usize..(offset + count) as usize].to_vec())
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,320 @@
+// src-tauri/src/auto_reply.rs
+// Rule-based auto-responder: answers incoming direct messages that match a user-defined
+// rule (trigger pattern, schedule, target peers) with a templated reply
+
+use crate::commands::{send_direct_message, AppState};
+use crate::db::{generate_id, now, AutoReplyRule};
+use crate::window_manager;
+use chrono::{DateTime, Datelike, Local, NaiveTime};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const MATCH_MODES: [&str; 4] = ["any", "contains", "exact", "starts_with"];
+/// Shortest cooldown a rule can have; without one two responders answer each other forever
+const MIN_COOLDOWN_MINUTES: i64 = 1;
+
+// (rule id, peer id) -> when we last auto-replied; keeps two responders from ping-ponging
+static LAST_REPLIES: Mutex<Option<HashMap<(String, String), Instant>>> = Mutex::new(None);
+
+#[derive(Deserialize)]
+pub struct AutoReplyRuleInput {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub pattern: Option<String>,
+    pub match_mode: Option<String>,
+    pub peer_ids: Option<Vec<String>>,
+    pub days: Option<Vec<u32>>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub active_from: Option<String>,
+    pub active_until: Option<String>,
+    pub response: String,
+    pub cooldown_minutes: Option<i64>,
+    pub enabled: Option<bool>,
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+fn parse_datetime(value: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|d| d.with_timezone(&Local))
+}
+
+fn pattern_matches(rule: &AutoReplyRule, content: &str) -> bool {
+    let pattern = rule.pattern.trim().to_lowercase();
+    let content = content.trim().to_lowercase();
+    if pattern.is_empty() {
+        return true;
+    }
+    match rule.match_mode.as_str() {
+        "exact" => content == pattern,
+        "starts_with" => content.starts_with(&pattern),
+        "any" => true,
+        _ => content.contains(&pattern),
+    }
+}
+
+fn in_schedule(rule: &AutoReplyRule, at: DateTime<Local>) -> bool {
+    if let Some(from) = rule.active_from.as_deref().and_then(parse_datetime) {
+        if at < from {
+            return false;
+        }
+    }
+    if let Some(until) = rule.active_until.as_deref().and_then(parse_datetime) {
+        if at > until {
+            return false;
+        }
+    }
+    if !rule.days.is_empty() && !rule.days.contains(&at.weekday().number_from_monday()) {
+        return false;
+    }
+    let start = rule.start_time.as_deref().and_then(parse_time);
+    let end = rule.end_time.as_deref().and_then(parse_time);
+    let time = at.time();
+    match (start, end) {
+        // Windows like 18:00-09:00 wrap past midnight
+        (Some(s), Some(e)) if s > e => time >= s || time < e,
+        (Some(s), Some(e)) => time >= s && time < e,
+        (Some(s), None) => time >= s,
+        (None, Some(e)) => time < e,
+        (None, None) => true,
+    }
+}
+
+/// Fill {name}, {message} and {until} in a response template
+fn render(rule: &AutoReplyRule, sender_name: &str, content: &str) -> String {
+    let until = rule
+        .active_until
+        .as_deref()
+        .and_then(parse_datetime)
+        .map(|d| d.format("%A %-d %B").to_string())
+        .unwrap_or_default();
+    rule.response
+        .replace("{name}", sender_name)
+        .replace("{message}", content)
+        .replace("{until}", &until)
+}
+
+/// First enabled rule that applies to this message, with its rendered reply
+fn pick_reply<'a>(
+    rules: &'a [AutoReplyRule],
+    peer_id: &str,
+    sender_name: &str,
+    content: &str,
+    at: DateTime<Local>,
+) -> Option<(&'a AutoReplyRule, String)> {
+    rules
+        .iter()
+        .filter(|r| r.enabled && !r.response.trim().is_empty())
+        .filter(|r| r.peer_ids.is_empty() || r.peer_ids.iter().any(|p| p == peer_id))
+        .filter(|r| pattern_matches(r, content) && in_schedule(r, at))
+        .map(|r| (r, render(r, sender_name, content)))
+        .next()
+}
+
+fn on_cooldown(rule: &AutoReplyRule, peer_id: &str) -> bool {
+    let mut guard = LAST_REPLIES.lock().unwrap();
+    let last_replies = guard.get_or_insert_with(HashMap::new);
+    let key = (rule.id.clone(), peer_id.to_string());
+    let cooldown = Duration::from_secs(rule.cooldown_minutes.max(MIN_COOLDOWN_MINUTES) as u64 * 60);
+    if let Some(last) = last_replies.get(&key) {
+        if last.elapsed() < cooldown {
+            return true;
+        }
+    }
+    last_replies.insert(key, Instant::now());
+    false
+}
+
+/// Called from the signaling receive path for each incoming direct text message
+pub fn handle_incoming<R: Runtime>(
+    app: &AppHandle<R>,
+    peer_id: &str,
+    sender_name: &str,
+    content: &str,
+) {
+    let state = app.state::<AppState>();
+    // Never answer our own linked devices
+    if matches!(state.db.get_linked_device(peer_id), Ok(Some(_))) {
+        return;
+    }
+    let rules = match state.db.get_auto_reply_rules() {
+        Ok(rules) if !rules.is_empty() => rules,
+        _ => return,
+    };
+    let Some((rule, reply)) = pick_reply(&rules, peer_id, sender_name, content, Local::now())
+    else {
+        return;
+    };
+    if on_cooldown(rule, peer_id) {
+        return;
+    }
+    match send_direct_message(&state, peer_id, reply, "text", true) {
+        Ok(message) => {
+            info!("Auto-replied to {} ({})", sender_name, rule.name);
+            let _ = app.emit("chat-message-sent", &message);
+            window_manager::route_to_chat_window(app, &state.chat_windows, peer_id, &message);
+        }
+        Err(e) => warn!("Auto-reply failed: {}", e),
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_auto_reply_rules(state: State<AppState>) -> Result<Vec<AutoReplyRule>, String> {
+    state.db.get_auto_reply_rules().map_err(|e| e.to_string())
+}
+
+/// Create a rule, or replace it when `id` is set
+#[tauri::command]
+pub fn save_auto_reply_rule(
+    state: State<AppState>,
+    input: AutoReplyRuleInput,
+) -> Result<AutoReplyRule, String> {
+    if input.response.trim().is_empty() {
+        return Err("Reply text is empty".to_string());
+    }
+    let match_mode = input.match_mode.unwrap_or_else(|| "contains".into());
+    if !MATCH_MODES.contains(&match_mode.as_str()) {
+        return Err(format!("Unknown match mode: {}", match_mode));
+    }
+    for t in [&input.start_time, &input.end_time].into_iter().flatten() {
+        parse_time(t).ok_or(format!("Invalid time (expected HH:MM): {}", t))?;
+    }
+    for d in [&input.active_from, &input.active_until]
+        .into_iter()
+        .flatten()
+    {
+        parse_datetime(d).ok_or(format!("Invalid date: {}", d))?;
+    }
+    let days = input.days.unwrap_or_default();
+    if days.iter().any(|d| !(1..=7).contains(d)) {
+        return Err("Days must be 1 (Monday) to 7 (Sunday)".to_string());
+    }
+
+    let created_at = match &input.id {
+        Some(id) => state
+            .db
+            .get_auto_reply_rules()
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|r| &r.id == id)
+            .map(|r| r.created_at)
+            .ok_or("Rule not found")?,
+        None => now(),
+    };
+    let rule = AutoReplyRule {
+        id: input.id.unwrap_or_else(generate_id),
+        name: input.name.unwrap_or_default(),
+        pattern: input.pattern.unwrap_or_default(),
+        match_mode,
+        peer_ids: input.peer_ids.unwrap_or_default(),
+        days,
+        start_time: input.start_time.filter(|t| !t.is_empty()),
+        end_time: input.end_time.filter(|t| !t.is_empty()),
+        active_from: input.active_from.filter(|t| !t.is_empty()),
+        active_until: input.active_until.filter(|t| !t.is_empty()),
+        response: input.response,
+        cooldown_minutes: input
+            .cooldown_minutes
+            .unwrap_or(60)
+            .max(MIN_COOLDOWN_MINUTES),
+        enabled: input.enabled.unwrap_or(true),
+        created_at,
+    };
+    state
+        .db
+        .save_auto_reply_rule(&rule)
+        .map_err(|e| e.to_string())?;
+    Ok(rule)
+}
+
+#[tauri::command]
+pub fn delete_auto_reply_rule(state: State<AppState>, id: String) -> Result<(), String> {
+    state
+        .db
+        .delete_auto_reply_rule(&id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_auto_reply_rule_enabled(
+    state: State<AppState>,
+    id: String,
+    enabled: bool,
+) -> Result<(), String> {
+    state
+        .db
+        .set_auto_reply_rule_enabled(&id, enabled)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn rule(pattern: &str, mode: &str) -> AutoReplyRule {
+        AutoReplyRule {
+            id: "r1".into(),
+            name: "PTO".into(),
+            pattern: pattern.into(),
+            match_mode: mode.into(),
+            peer_ids: vec![],
+            days: vec![],
+            start_time: None,
+            end_time: None,
+            active_from: None,
+            active_until: None,
+            response: "Hi {name}, I'm away".into(),
+            cooldown_minutes: 60,
+            enabled: true,
+            created_at: now(),
+        }
+    }
+
+    #[test]
+    fn test_matches_patterns_and_peers() {
+        let at = Local::now();
+        let mut r = rule("urgent", "contains");
+        assert!(pick_reply(&[r.clone()], "p1", "Ann", "This is URGENT", at).is_some());
+        assert!(pick_reply(&[r.clone()], "p1", "Ann", "hello", at).is_none());
+        r.peer_ids = vec!["p2".into()];
+        assert!(pick_reply(&[r], "p1", "Ann", "urgent", at).is_none());
+
+        let (_, reply) = pick_reply(&[rule("", "any")], "p1", "Ann", "hey", at).unwrap();
+        assert_eq!(reply, "Hi Ann, I'm away");
+    }
+
+    #[test]
+    fn test_zero_cooldown_still_holds_for_a_minute() {
+        let mut r = rule("", "any");
+        r.id = "no-cooldown".into();
+        r.cooldown_minutes = 0;
+        assert!(!on_cooldown(&r, "p1"));
+        assert!(on_cooldown(&r, "p1"));
+        assert!(!on_cooldown(&r, "p2"));
+    }
+
+    #[test]
+    fn test_respects_overnight_window_and_days() {
+        let mut r = rule("", "any");
+        r.start_time = Some("18:00".into());
+        r.end_time = Some("09:00".into());
+        // 2024-01-01 is a Monday
+        let late = Local.with_ymd_and_hms(2024, 1, 1, 23, 0, 0).unwrap();
+        let noon = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        assert!(in_schedule(&r, late));
+        assert!(!in_schedule(&r, noon));
+        r.days = vec![6, 7];
+        assert!(!in_schedule(&r, late));
+    }
+}
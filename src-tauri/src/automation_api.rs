@@ -0,0 +1,436 @@
+// src-tauri/src/automation_api.rs
+// Opt-in localhost HTTP + WebSocket API so scripts and other desktop tools can
+// send messages, list peers and follow incoming messages
+
+use crate::commands::{post_group_message, send_direct_message, AppState};
+use crate::db::Database;
+use crate::window_manager;
+use base64::Engine;
+use crossbeam_channel::{RecvTimeoutError, Sender};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tracing::{info, warn};
+use tungstenite::protocol::Role;
+use tungstenite::WebSocket;
+
+pub(crate) const DEFAULT_PORT: u16 = 48080;
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+// Keeps idle event streams alive and detects clients that went away
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+// Running server and the port it is bound to
+static SERVER: Mutex<Option<(Arc<Server>, u16)>> = Mutex::new(None);
+// One channel per connected /events WebSocket
+static SUBSCRIBERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutomationApiStatus {
+    pub enabled: bool,
+    pub running: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+struct SendMessageBody {
+    peer_id: String,
+    content: String,
+    message_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SendGroupMessageBody {
+    content: String,
+    message_type: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Route {
+    Peers,
+    Unread,
+    Groups,
+    SendMessage,
+    SendGroupMessage(String),
+    Events,
+    NotFound,
+}
+
+fn route(method: &Method, path: &str) -> Route {
+    let segments: Vec<&str> = path
+        .trim_start_matches("/api/v1/")
+        .trim_end_matches('/')
+        .split('/')
+        .collect();
+    if !path.starts_with("/api/v1/") {
+        return Route::NotFound;
+    }
+    match (method, segments.as_slice()) {
+        (Method::Get, ["peers"]) => Route::Peers,
+        (Method::Get, ["unread"]) => Route::Unread,
+        (Method::Get, ["groups"]) => Route::Groups,
+        (Method::Get, ["events"]) => Route::Events,
+        (Method::Post, ["messages"]) => Route::SendMessage,
+        (Method::Post, ["groups", id, "messages"]) if !id.is_empty() => {
+            Route::SendGroupMessage(id.to_string())
+        }
+        _ => Route::NotFound,
+    }
+}
+
+/// Token from an `Authorization: Bearer` header, or `?token=` for clients that can't set
+/// headers on a WebSocket handshake
+fn request_token(authorization: Option<&str>, url: &str) -> Option<String> {
+    if let Some(token) = authorization.and_then(|v| v.trim().strip_prefix("Bearer ")) {
+        return Some(token.trim().to_string());
+    }
+    url.split_once('?')?
+        .1
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(|t| t.to_string())
+}
+
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn is_enabled(db: &Database) -> bool {
+    db.get_setting("automation_api_enabled")
+        .ok()
+        .flatten()
+        .map(|v| v == "true")
+        .unwrap_or(false)
+}
+
+fn configured_port(db: &Database) -> u16 {
+    db.get_setting("automation_api_port")
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_PORT)
+}
+
+fn new_token(db: &Database) -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+    db.set_setting("automation_api_token", &token)
+        .map_err(|e| e.to_string())?;
+    Ok(token)
+}
+
+/// The API token, created on first use
+fn token(db: &Database) -> Result<String, String> {
+    match db
+        .get_setting("automation_api_token")
+        .map_err(|e| e.to_string())?
+    {
+        Some(token) if !token.is_empty() => Ok(token),
+        _ => new_token(db),
+    }
+}
+
+/// Port the API is listening on, if it is running
+pub(crate) fn running_port() -> Option<u16> {
+    SERVER.lock().unwrap().as_ref().map(|(_, port)| *port)
+}
+
+fn status(db: &Database) -> Result<AutomationApiStatus, String> {
+    let running = running_port();
+    Ok(AutomationApiStatus {
+        enabled: is_enabled(db),
+        running: running.is_some(),
+        port: running.unwrap_or_else(|| configured_port(db)),
+        token: token(db)?,
+    })
+}
+
+/// Push an event to every connected /events client
+pub fn publish<T: Serialize>(event: &str, data: &T) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
+    }
+    let text = serde_json::json!({ "event": event, "data": data }).to_string();
+    subscribers.retain(|tx| tx.send(text.clone()).is_ok());
+}
+
+fn json_response<T: Serialize>(code: u16, body: &T) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(serde_json::to_vec(body).unwrap_or_default())
+        .with_status_code(StatusCode(code))
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
+fn error_response(code: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(code, &serde_json::json!({ "error": message }))
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(request: &mut Request) -> Result<T, String> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err("Request body too large".to_string());
+    }
+    serde_json::from_slice(&body).map_err(|e| format!("Invalid JSON body: {}", e))
+}
+
+fn header_value(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+fn handle_request<R: Runtime>(app: &AppHandle<R>, mut request: Request) {
+    let state = app.state::<AppState>();
+    let expected = match token(&state.db) {
+        Ok(t) => t,
+        Err(e) => {
+            let _ = request.respond(error_response(500, &e));
+            return;
+        }
+    };
+    let given = request_token(
+        header_value(&request, "Authorization").as_deref(),
+        request.url(),
+    );
+    if !given.map(|t| tokens_match(&t, &expected)).unwrap_or(false) {
+        let _ = request.respond(error_response(401, "Missing or invalid token"));
+        return;
+    }
+
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+    let response = match route(request.method(), &path) {
+        Route::Peers => json_response(200, &state.discovery.get_peers()),
+        Route::Groups => match state.db.get_groups(&state.device_id()) {
+            Ok(groups) => json_response(200, &groups),
+            Err(e) => error_response(500, &e.to_string()),
+        },
+        Route::Unread => match state.db.get_unread_counts_by_peer(&state.device_id()) {
+            Ok(counts) => {
+                let total: i32 = counts.iter().map(|(_, c)| c).sum();
+                let peers: HashMap<String, i32> = counts.into_iter().collect();
+                json_response(200, &serde_json::json!({ "total": total, "peers": peers }))
+            }
+            Err(e) => error_response(500, &e.to_string()),
+        },
+        Route::SendMessage => match read_json::<SendMessageBody>(&mut request) {
+            Ok(body) if body.content.trim().is_empty() => error_response(400, "Message is empty"),
+            Ok(body) => {
+                let message_type = body.message_type.unwrap_or_else(|| "text".into());
+                match send_direct_message(&state, &body.peer_id, body.content, &message_type, false)
+                {
+                    Ok(message) => {
+                        let _ = app.emit("chat-message-sent", &message);
+                        window_manager::route_to_chat_window(
+                            app,
+                            &state.chat_windows,
+                            &body.peer_id,
+                            &message,
+                        );
+                        json_response(201, &message)
+                    }
+                    Err(e) => error_response(500, &e),
+                }
+            }
+            Err(e) => error_response(400, &e),
+        },
+        Route::SendGroupMessage(group_id) => {
+            match read_json::<SendGroupMessageBody>(&mut request) {
+                Ok(body) if body.content.trim().is_empty() => {
+                    error_response(400, "Message is empty")
+                }
+                Ok(body) => {
+                    match post_group_message(&state, &group_id, body.content, body.message_type) {
+                        Ok(message) => {
+                            let _ = app.emit("group-message-sent", &message);
+                            json_response(201, &message)
+                        }
+                        Err(e) => error_response(500, &e),
+                    }
+                }
+                Err(e) => error_response(400, &e),
+            }
+        }
+        Route::Events => {
+            open_event_stream(request);
+            return;
+        }
+        Route::NotFound => error_response(404, "Not found"),
+    };
+    let _ = request.respond(response);
+}
+
+/// Upgrade to a WebSocket and stream published events until the client disconnects
+fn open_event_stream(request: Request) {
+    let key = match header_value(&request, "Sec-WebSocket-Key") {
+        Some(k) => k,
+        None => {
+            let _ = request.respond(error_response(400, "Expected a WebSocket upgrade"));
+            return;
+        }
+    };
+    let accept = tungstenite::handshake::derive_accept_key(key.trim().as_bytes());
+    let response = Response::empty(101)
+        .with_header(Header::from_bytes(&b"Sec-WebSocket-Accept"[..], accept.as_bytes()).unwrap());
+    let stream = request.upgrade("websocket", response);
+
+    let (tx, rx) = crossbeam_channel::unbounded::<String>();
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    std::thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        loop {
+            let result = match rx.recv_timeout(PING_INTERVAL) {
+                Ok(text) => socket.send(tungstenite::Message::Text(text)),
+                Err(RecvTimeoutError::Timeout) => {
+                    socket.send(tungstenite::Message::Ping(Vec::new()))
+                }
+                // Server stopped
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                    break;
+                }
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn start<R: Runtime>(app: &AppHandle<R>, port: u16) -> Result<u16, String> {
+    let mut slot = SERVER.lock().unwrap();
+    if let Some((_, running)) = slot.as_ref() {
+        return Ok(*running);
+    }
+    // Loopback only: the API can send messages as the local user
+    let server = Server::http(("127.0.0.1", port))
+        .map_err(|e| format!("Failed to bind automation API on port {}: {}", port, e))?;
+    let server = Arc::new(server);
+    *slot = Some((server.clone(), port));
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle_request(&app, request);
+        }
+    });
+    info!("Automation API listening on 127.0.0.1:{}", port);
+    Ok(port)
+}
+
+fn stop() {
+    if let Some((server, _)) = SERVER.lock().unwrap().take() {
+        server.unblock();
+        info!("Automation API stopped");
+    }
+    // Dropping the senders ends every event stream
+    SUBSCRIBERS.lock().unwrap().clear();
+}
+
+/// Called at startup; the API stays off unless the user enabled it
+pub fn start_if_enabled<R: Runtime>(app: &AppHandle<R>, db: &Database) {
+    if !is_enabled(db) {
+        return;
+    }
+    if let Err(e) = start(app, configured_port(db)) {
+        warn!("{}", e);
+    }
+}
+
+/// Start, stop or move the API to match the settings (after update_config)
+pub fn reconfigure<R: Runtime>(app: &AppHandle<R>, db: &Database) {
+    stop();
+    if is_enabled(db) {
+        if let Err(e) = token(db) {
+            warn!("{}", e);
+            return;
+        }
+    }
+    start_if_enabled(app, db);
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_automation_api(state: State<AppState>) -> Result<AutomationApiStatus, String> {
+    status(&state.db)
+}
+
+#[tauri::command]
+pub fn set_automation_api_enabled<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    enabled: bool,
+) -> Result<AutomationApiStatus, String> {
+    if enabled {
+        token(&state.db)?;
+        start(&app, configured_port(&state.db))?;
+    } else {
+        stop();
+    }
+    state
+        .db
+        .set_setting(
+            "automation_api_enabled",
+            if enabled { "true" } else { "false" },
+        )
+        .map_err(|e| e.to_string())?;
+    status(&state.db)
+}
+
+/// Invalidate the current token; it is checked per request so this takes effect immediately
+#[tauri::command]
+pub fn regenerate_automation_token(state: State<AppState>) -> Result<AutomationApiStatus, String> {
+    new_token(&state.db)?;
+    status(&state.db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes_api_paths() {
+        assert_eq!(route(&Method::Get, "/api/v1/peers"), Route::Peers);
+        assert_eq!(route(&Method::Get, "/api/v1/unread/"), Route::Unread);
+        assert_eq!(route(&Method::Post, "/api/v1/messages"), Route::SendMessage);
+        assert_eq!(
+            route(&Method::Post, "/api/v1/groups/g1/messages"),
+            Route::SendGroupMessage("g1".into())
+        );
+        assert_eq!(route(&Method::Post, "/api/v1/peers"), Route::NotFound);
+        assert_eq!(route(&Method::Get, "/peers"), Route::NotFound);
+    }
+
+    #[test]
+    fn test_reads_token_from_header_or_query() {
+        assert_eq!(
+            request_token(Some("Bearer abc"), "/api/v1/peers"),
+            Some("abc".into())
+        );
+        assert_eq!(
+            request_token(None, "/api/v1/events?x=1&token=xyz"),
+            Some("xyz".into())
+        );
+        assert_eq!(request_token(Some("Basic abc"), "/api/v1/peers"), None);
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abc", "abd"));
+    }
+}
@@ -0,0 +1,134 @@
+// src-tauri/src/avatar.rs
+// Avatar image processing: whatever the user picks (often a multi-megabyte photo) is
+// center-cropped to a square, scaled down to AVATAR_SIZE and re-encoded as PNG before it's
+// stored and served to peers. Avatars are versioned by a hash of their content, so peers
+// only download one again when it actually changed.
+
+use crate::crypto::generate_checksum;
+use crate::file_server::FileServer;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+pub const AVATAR_SIZE: u32 = 256;
+const MAX_INPUT_BYTES: usize = 20 * 1024 * 1024;
+
+/// Decode a data URL or bare base64 string
+pub fn decode_image_data(image_data: &str) -> Result<Vec<u8>, String> {
+    let b64 = image_data
+        .split_once("base64,")
+        .map(|(_, b)| b)
+        .unwrap_or(image_data);
+    base64::engine::general_purpose::STANDARD
+        .decode(b64.trim())
+        .map_err(|e| format!("Invalid avatar data: {}", e))
+}
+
+fn crop_square(img: DynamicImage) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    let side = w.min(h);
+    if w == h {
+        return img;
+    }
+    img.crop_imm((w - side) / 2, (h - side) / 2, side, side)
+}
+
+/// Crop, resize and re-encode an uploaded avatar. Returns PNG bytes.
+pub fn process(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.len() > MAX_INPUT_BYTES {
+        return Err(format!(
+            "Avatar image is too large ({} MB max)",
+            MAX_INPUT_BYTES / (1024 * 1024)
+        ));
+    }
+    let img = image::load_from_memory(bytes).map_err(|e| format!("Unsupported image: {}", e))?;
+    let mut img = crop_square(img);
+    if img.width() > AVATAR_SIZE {
+        img = img.resize_exact(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+    }
+    let mut out = Vec::new();
+    img.write_to(&mut Cursor::new(&mut out), ImageOutputFormat::Png)
+        .map_err(|e| format!("Encoding avatar failed: {}", e))?;
+    Ok(out)
+}
+
+/// Version of an avatar: SHA-256 of the image bytes
+pub fn content_hash(bytes: &[u8]) -> String {
+    generate_checksum(bytes)
+}
+
+/// File server id our own avatar (and cached peer avatars) are served under
+pub fn file_id(device_id: &str) -> String {
+    format!("avatar_{}", device_id)
+}
+
+/// Where downloaded peer avatars are cached (Documents/Pingo/avatars on Windows and macOS)
+pub fn cache_dir() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        let docs = std::env::var("USERPROFILE")
+            .map(|p| PathBuf::from(p).join("Documents"))
+            .unwrap_or_else(|_| PathBuf::from("."));
+        docs.join("Pingo").join("avatars")
+    } else if cfg!(target_os = "macos") {
+        let home = std::env::var("HOME").unwrap_or_default();
+        PathBuf::from(home).join("Documents/Pingo/avatars")
+    } else {
+        let home = std::env::var("HOME").unwrap_or_default();
+        PathBuf::from(home).join(".local/share/Pingo/avatars")
+    }
+}
+
+pub fn cache_path(device_id: &str) -> PathBuf {
+    cache_dir().join(format!("user_{}.png", device_id))
+}
+
+/// Serve a cached peer avatar from our file server; None if it isn't cached
+pub fn cached_url(file_server: &FileServer, device_id: &str) -> Option<String> {
+    let path = cache_path(device_id);
+    if !path.exists() {
+        return None;
+    }
+    let id = file_id(device_id);
+    let name = format!("user_{}.png", device_id);
+    file_server.register_file(&id, &path, &name);
+    Some(file_server.local_url(&id))
+}
+
+/// Hash of our own processed avatar, if we have one
+pub fn local_hash(file_server: &FileServer, device_id: &str) -> Option<String> {
+    let path = file_server
+        .get_storage_dir()
+        .join(format!("{}.png", file_id(device_id)));
+    std::fs::read(path).ok().map(|bytes| content_hash(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    #[test]
+    fn test_process_crops_and_shrinks() {
+        let photo = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(800, 400, Rgb([200, 10, 10])));
+        let mut jpeg = Vec::new();
+        photo
+            .write_to(&mut Cursor::new(&mut jpeg), ImageOutputFormat::Jpeg(90))
+            .unwrap();
+
+        let png = process(&jpeg).unwrap();
+        let avatar = image::load_from_memory(&png).unwrap();
+        assert_eq!(avatar.dimensions(), (AVATAR_SIZE, AVATAR_SIZE));
+        assert!(png.starts_with(b"\x89PNG"));
+        // Same input, same version; the hash is what peers compare
+        assert_eq!(content_hash(&process(&jpeg).unwrap()), content_hash(&png));
+
+        assert!(process(b"not an image").is_err());
+        let data_url = format!(
+            "data:image/jpeg;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(&jpeg)
+        );
+        assert_eq!(decode_image_data(&data_url).unwrap(), jpeg);
+    }
+}
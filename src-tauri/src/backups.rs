@@ -0,0 +1,398 @@
+// src-tauri/src/backups.rs
+// Scheduled backups: daily or weekly, a background task copies the live database (messages,
+// contacts and the settings table) with SQLite's online backup API into a folder of its own
+// under the chosen destination, next to a settings.json with the typed settings for reference.
+// Only the newest `retention` backups are kept. Every run lands in backup_history and ends
+// with a notification.
+
+use crate::backup_targets;
+use crate::commands::AppState;
+use crate::config;
+use crate::db::{generate_id, now, BackupRecord, Database};
+use crate::notifications;
+use crate::tray;
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "backup_schedule";
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Nothing runs right at startup; the app has enough to do then
+const STARTUP_DELAY: Duration = Duration::from_secs(2 * 60);
+/// A failed scheduled backup is tried again after this long, not on every check
+const RETRY_AFTER_HOURS: i64 = 6;
+/// Backup folders are named <prefix><timestamp>; retention only ever deletes these
+pub(crate) const FOLDER_PREFIX: &str = "pingo-backup-";
+pub(crate) const DB_FILE: &str = "pingo.db";
+/// Everything in a backup folder
+pub(crate) const BACKUP_FILES: [&str; 2] = [DB_FILE, "settings.json"];
+
+/// One backup at a time, whether scheduled or asked for
+static RUNNING: Mutex<()> = Mutex::new(());
+
+/// Hold off backups (e.g. while a restore swaps the database file)
+pub(crate) fn lock() -> MutexGuard<'static, ()> {
+    RUNNING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frequency {
+    #[default]
+    Daily,
+    Weekly,
+}
+
+impl Frequency {
+    fn interval(self) -> ChronoDuration {
+        match self {
+            Frequency::Daily => ChronoDuration::days(1),
+            Frequency::Weekly => ChronoDuration::weeks(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackupConfig {
+    pub enabled: bool,
+    #[serde(default)]
+    pub frequency: Frequency,
+    /// Backups kept; older ones are deleted after each successful run
+    #[serde(default = "default_retention")]
+    pub retention: usize,
+    /// Folder the backups go into; empty for "backups" in the app's data folder
+    #[serde(default)]
+    pub destination: String,
+}
+
+fn default_retention() -> usize {
+    7
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            enabled: false,
+            frequency: Frequency::Daily,
+            retention: default_retention(),
+            destination: String::new(),
+        }
+    }
+}
+
+pub fn load(db: &Database) -> BackupConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn destination(config: &BackupConfig) -> PathBuf {
+    if config.destination.trim().is_empty() {
+        Database::get_db_path()
+            .parent()
+            .map(|dir| dir.join("backups"))
+            .unwrap_or_else(|| PathBuf::from("backups"))
+    } else {
+        PathBuf::from(config.destination.trim())
+    }
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Whether a scheduled backup should run at `at`, given the history (newest first)
+fn is_due(config: &BackupConfig, history: &[BackupRecord], at: DateTime<Utc>) -> bool {
+    if !config.enabled {
+        return false;
+    }
+    let last_ok = history
+        .iter()
+        .find(|b| b.status == "ok")
+        .and_then(|b| parse_time(&b.started_at));
+    if last_ok.is_some_and(|t| at - t < config.frequency.interval()) {
+        return false;
+    }
+    let last_attempt = history.first().and_then(|b| parse_time(&b.started_at));
+    last_attempt.is_none_or(|t| at - t >= ChronoDuration::hours(RETRY_AFTER_HOURS))
+}
+
+/// Write one backup into a new folder under `dest`; returns the folder and its size
+fn write_backup(db: &Database, dest: &Path) -> Result<(PathBuf, u64), String> {
+    // Timestamped so they sort, with a suffix so two in the same second don't share a folder
+    let folder = dest.join(format!(
+        "{}{}-{}",
+        FOLDER_PREFIX,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        &generate_id()[..8]
+    ));
+    std::fs::create_dir_all(dest)
+        .and_then(|_| std::fs::create_dir(&folder))
+        .map_err(|e| format!("Can't create {}: {}", folder.display(), e))?;
+    let result = (|| {
+        let db_file = folder.join(DB_FILE);
+        db.backup_to(&db_file)
+            .map_err(|e| format!("Copying the database failed: {}", e))?;
+        let settings =
+            serde_json::to_string_pretty(&config::load(db)).map_err(|e| e.to_string())?;
+        let settings_file = folder.join(BACKUP_FILES[1]);
+        std::fs::write(&settings_file, settings)
+            .map_err(|e| format!("Writing settings failed: {}", e))?;
+        let size = [db_file, settings_file]
+            .iter()
+            .filter_map(|f| std::fs::metadata(f).ok())
+            .map(|m| m.len())
+            .sum();
+        Ok(size)
+    })();
+    match result {
+        Ok(size) => Ok((folder, size)),
+        Err(e) => {
+            // No half-written backup is left to be mistaken for a good one
+            let _ = std::fs::remove_dir_all(&folder);
+            Err(e)
+        }
+    }
+}
+
+/// Delete all but the newest `keep` backup folders in `dest`
+fn prune(dest: &Path, keep: usize) -> usize {
+    let Ok(entries) = std::fs::read_dir(dest) else {
+        return 0;
+    };
+    let mut folders: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with(FOLDER_PREFIX))
+        })
+        .collect();
+    // Timestamped names sort oldest first
+    folders.sort();
+    let excess = folders.len().saturating_sub(keep.max(1));
+    let mut removed = 0;
+    for folder in folders.into_iter().take(excess) {
+        match std::fs::remove_dir_all(&folder) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Removing old backup {} failed: {}", folder.display(), e),
+        }
+    }
+    removed
+}
+
+/// Run a backup now, record it, notify and emit "backup-finished"
+fn run<R: Runtime>(app: &AppHandle<R>, db: &Database, trigger: &str) -> BackupRecord {
+    let _running = lock();
+    let config = load(db);
+    let dest = destination(&config);
+    let started_at = now();
+    let result = write_backup(db, &dest);
+    let mut record = BackupRecord {
+        id: generate_id(),
+        started_at,
+        finished_at: now(),
+        trigger: trigger.to_string(),
+        status: "ok".to_string(),
+        path: None,
+        size: 0,
+        error: None,
+        remote: None,
+        remote_error: None,
+    };
+    let mut body = match result {
+        Ok((folder, size)) => {
+            let removed = prune(&dest, config.retention);
+            info!(
+                "Backup written to {} ({} bytes, {} old ones removed)",
+                folder.display(),
+                size,
+                removed
+            );
+            record.path = Some(folder.to_string_lossy().into_owned());
+            record.size = size as i64;
+            match backup_targets::upload(db, &folder, config.retention) {
+                Some(Ok(location)) => record.remote = Some(location),
+                Some(Err(e)) => {
+                    warn!("Uploading the backup failed: {}", e);
+                    record.remote_error = Some(e);
+                }
+                None => {}
+            }
+            format!("Saved to {}", folder.display())
+        }
+        Err(e) => {
+            warn!("Backup failed: {}", e);
+            record.status = "failed".to_string();
+            record.error = Some(e.clone());
+            e
+        }
+    };
+    if let Some(e) = &record.remote_error {
+        body = format!("{}, but uploading it failed: {}", body, e);
+    }
+    record.finished_at = now();
+    if let Err(e) = db.add_backup_record(&record) {
+        warn!("Recording the backup failed: {}", e);
+    }
+    // Failures always get through; a routine success respects do-not-disturb
+    let quiet = tray::is_muted() || notifications::is_do_not_disturb(db);
+    let failed = record.status != "ok" || record.remote_error.is_some();
+    if failed || !quiet {
+        let title = if !failed {
+            "Backup complete"
+        } else if record.status == "ok" {
+            "Backup upload failed"
+        } else {
+            "Backup failed"
+        };
+        if let Err(e) = notifications::show_plain(app, title, &body) {
+            warn!("Failed to show backup notification: {}", e);
+        }
+    }
+    let _ = app.emit("backup-finished", &record);
+    record
+}
+
+/// Check for a due backup every few minutes for the life of the app
+pub fn start_scheduler<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || {
+        thread::sleep(STARTUP_DELAY);
+        loop {
+            let state = app.state::<AppState>();
+            let config = load(&state.db);
+            let history = state.db.get_backup_history(20).unwrap_or_default();
+            if is_due(&config, &history, Utc::now()) {
+                run(&app, &state.db, "scheduled");
+            }
+            thread::sleep(CHECK_INTERVAL);
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_backup_config(state: State<AppState>) -> BackupConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_backup_config(state: State<AppState>, config: BackupConfig) -> Result<(), String> {
+    if config.retention == 0 {
+        return Err("Keep at least one backup".to_string());
+    }
+    let dest = destination(&config);
+    std::fs::create_dir_all(&dest)
+        .map_err(|e| format!("Can't use {} for backups: {}", dest.display(), e))?;
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!(
+        "Scheduled backups enabled: {} ({:?}, keep {})",
+        config.enabled, config.frequency, config.retention
+    );
+    Ok(())
+}
+
+/// Back up now, whether or not scheduled backups are on
+#[tauri::command]
+pub async fn run_backup_now<R: Runtime>(app: AppHandle<R>) -> Result<BackupRecord, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        run(&app, &state.db, "manual")
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Past backup runs, newest first
+#[tauri::command]
+pub fn get_backup_history(
+    state: State<AppState>,
+    limit: Option<i64>,
+) -> Result<Vec<BackupRecord>, String> {
+    state
+        .db
+        .get_backup_history(limit.unwrap_or(50))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(started_at: DateTime<Utc>, status: &str) -> BackupRecord {
+        BackupRecord {
+            id: generate_id(),
+            started_at: started_at.to_rfc3339(),
+            finished_at: started_at.to_rfc3339(),
+            trigger: "scheduled".to_string(),
+            status: status.to_string(),
+            path: None,
+            size: 0,
+            error: None,
+            remote: None,
+            remote_error: None,
+        }
+    }
+
+    #[test]
+    fn test_schedule_and_retention() {
+        let at = Utc::now();
+        let config = BackupConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        assert!(is_due(&config, &[], at));
+        assert!(!is_due(&BackupConfig::default(), &[], at));
+        let yesterday_ok = [record(at - ChronoDuration::hours(25), "ok")];
+        assert!(is_due(&config, &yesterday_ok, at));
+        let weekly = BackupConfig {
+            frequency: Frequency::Weekly,
+            ..config.clone()
+        };
+        assert!(!is_due(&weekly, &yesterday_ok, at));
+        // Failed an hour ago: not retried yet
+        let failed = record(at - ChronoDuration::hours(1), "failed");
+        assert!(!is_due(&config, &[failed, yesterday_ok[0].clone()], at));
+
+        let dest = std::env::temp_dir().join(format!("pingo_backups_{}", generate_id()));
+        let db = Database::new_in_memory().unwrap();
+        db.set_setting("log_level", "debug").unwrap();
+        let (folder, size) = write_backup(&db, &dest).unwrap();
+        assert!(size > 0);
+        let copy = rusqlite::Connection::open(folder.join(DB_FILE)).unwrap();
+        let level: String = copy
+            .query_row(
+                "SELECT value FROM settings WHERE key='log_level'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(level, "debug");
+
+        for stamp in ["20240101-000000", "20240102-000000"] {
+            std::fs::create_dir_all(dest.join(format!("{}{}", FOLDER_PREFIX, stamp))).unwrap();
+        }
+        std::fs::create_dir_all(dest.join("unrelated")).unwrap();
+        assert_eq!(prune(&dest, 2), 1);
+        assert!(folder.exists());
+        assert!(!dest.join("pingo-backup-20240101-000000").exists());
+        assert!(dest.join("unrelated").exists());
+        let _ = std::fs::remove_dir_all(&dest);
+    }
+}
@@ -0,0 +1,287 @@
+// src-tauri/src/bulk_messages.rs
+// Batch message operations for multi-select in the chat view: delete, mark read and forward
+// many messages in one DB transaction, with a single "messages-bulk-updated" event instead
+// of one command and one event per message. Also broadcasts a text to every contact with a tag.
+
+use crate::commands::{self, AppState};
+use crate::contact_tags;
+use crate::db::{generate_id, now, Message};
+use crate::delivery_status::{self, MessageStatus};
+use crate::file_server::FileServer;
+use crate::pin_pairing;
+use crate::signaling::SignalingMessage;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+const MAX_BATCH: usize = 1000;
+const MAX_BROADCAST_RECIPIENTS: usize = 200;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkResult {
+    pub action: String,
+    pub requested: usize,
+    pub affected: usize,
+    /// Forwarded copies (forward only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<Message>,
+    /// Recipients left out because PIN pairing is required and they aren't paired (broadcast only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+fn check_batch(ids: &[String]) -> Result<(), String> {
+    if ids.is_empty() {
+        return Err("No messages selected".to_string());
+    }
+    if ids.len() > MAX_BATCH {
+        return Err(format!("At most {} messages at a time", MAX_BATCH));
+    }
+    Ok(())
+}
+
+fn finish<R: Runtime>(app: &AppHandle<R>, result: BulkResult) -> BulkResult {
+    info!(
+        "Bulk {}: {} of {} messages",
+        result.action, result.affected, result.requested
+    );
+    let _ = app.emit("messages-bulk-updated", &result);
+    result
+}
+
+/// A forwarded attachment is served by us now: point the file message at our file server
+/// when we have the file, otherwise leave it pointing where it did
+fn rehost(file_server: &FileServer, message: &Message) -> String {
+    if !matches!(message.message_type.as_str(), "image" | "video" | "file") {
+        return message.content.clone();
+    }
+    let Ok(mut info) = serde_json::from_str::<serde_json::Value>(&message.content) else {
+        return message.content.clone();
+    };
+    let held = info
+        .get("fileId")
+        .and_then(|v| v.as_str())
+        .is_some_and(|id| file_server.get_file_url(id).is_some());
+    if !held {
+        return message.content.clone();
+    }
+    let token = info["fileId"].as_str().map(|id| file_server.token(id));
+    info["port"] = serde_json::json!(file_server.get_port());
+    info["token"] = serde_json::json!(token);
+    info.to_string()
+}
+
+/// Send stored messages to `target` in order. Stops at the first failure; the rest go out with
+/// the other pending messages when the peer is back.
+fn relay(state: &AppState, target: &str, messages: &[Message]) {
+    let local_id = state.device_id();
+    let sender_name = state
+        .db
+        .get_user(&local_id)
+        .ok()
+        .flatten()
+        .map(|u| u.username)
+        .unwrap_or_default();
+    for m in messages {
+        let relay = SignalingMessage::ChatMessage {
+            from: local_id.clone(),
+            to: target.to_string(),
+            id: m.id.clone(),
+            content: m.content.clone(),
+            message_type: m.message_type.clone(),
+            sender_name: sender_name.clone(),
+            timestamp: m.created_at.clone(),
+            lamport: m.lamport,
+            auto_reply: false,
+        };
+        if let Err(e) = delivery_status::track_send(state, &m.id, || {
+            commands::send_to_peer(state, target, &relay)
+        }) {
+            warn!("Message {} to {} queued: {}", m.id, target, e);
+            break;
+        }
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn delete_messages<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    ids: Vec<String>,
+) -> Result<BulkResult, String> {
+    check_batch(&ids)?;
+    let affected = state.db.delete_messages(&ids).map_err(|e| e.to_string())?;
+    Ok(finish(
+        &app,
+        BulkResult {
+            action: "delete".to_string(),
+            requested: ids.len(),
+            affected,
+            messages: Vec::new(),
+            skipped: Vec::new(),
+        },
+    ))
+}
+
+#[tauri::command]
+pub fn mark_messages_read<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    ids: Vec<String>,
+) -> Result<BulkResult, String> {
+    check_batch(&ids)?;
+    let affected = state
+        .db
+        .mark_messages_read(&ids)
+        .map_err(|e| e.to_string())?;
+    Ok(finish(
+        &app,
+        BulkResult {
+            action: "read".to_string(),
+            requested: ids.len(),
+            affected,
+            messages: Vec::new(),
+            skipped: Vec::new(),
+        },
+    ))
+}
+
+/// Copy messages into the conversation with `target` (oldest first) and relay them. Copies
+/// the peer doesn't get now are delivered with the other pending messages when it's back.
+#[tauri::command]
+pub fn forward_messages<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    ids: Vec<String>,
+    target: String,
+) -> Result<BulkResult, String> {
+    check_batch(&ids)?;
+    pin_pairing::check_outgoing(&state, &target)?;
+    let local_id = state.device_id();
+    let mut originals = Vec::new();
+    for id in &ids {
+        if let Some(m) = state.db.get_message(id).map_err(|e| e.to_string())? {
+            originals.push(m);
+        }
+    }
+    originals.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+    let clock = state.db.next_lamport().map_err(|e| e.to_string())?;
+    let copies: Vec<Message> = originals
+        .iter()
+        .zip(0..)
+        .map(|(m, i)| Message {
+            id: generate_id(),
+            sender_id: local_id.clone(),
+            receiver_id: target.clone(),
+            content: rehost(&state.file_server, m),
+            message_type: m.message_type.clone(),
+            file_path: m.file_path.clone(),
+            is_read: false,
+            is_delivered: false,
+            created_at: now(),
+            lamport: clock.saturating_add(i),
+            status: MessageStatus::Queued,
+        })
+        .collect();
+    state
+        .db
+        .import_messages(&copies)
+        .map_err(|e| e.to_string())?;
+
+    relay(&state, &target, &copies);
+
+    Ok(finish(
+        &app,
+        BulkResult {
+            action: "forward".to_string(),
+            requested: ids.len(),
+            affected: copies.len(),
+            messages: copies,
+            skipped: Vec::new(),
+        },
+    ))
+}
+
+/// Send a text to every contact tagged `tag`, as a separate direct message in each conversation.
+/// Contacts that need PIN pairing first are skipped and listed in the result.
+#[tauri::command]
+pub fn broadcast_to_tag<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    tag: String,
+    content: String,
+) -> Result<BulkResult, String> {
+    if content.trim().is_empty() {
+        return Err("Message is empty".to_string());
+    }
+    let local_id = state.device_id();
+    let mut recipients: Vec<String> = contact_tags::tagged_user_ids(&state.db, &tag)?
+        .into_iter()
+        .filter(|id| *id != local_id)
+        .collect();
+    if recipients.is_empty() {
+        return Err(format!("No contacts are tagged {}", tag.trim()));
+    }
+    if recipients.len() > MAX_BROADCAST_RECIPIENTS {
+        return Err(format!(
+            "At most {} recipients at a time",
+            MAX_BROADCAST_RECIPIENTS
+        ));
+    }
+    recipients.sort();
+    let (recipients, skipped): (Vec<String>, Vec<String>) = recipients
+        .into_iter()
+        .partition(|id| pin_pairing::check_outgoing(&state, id).is_ok());
+    if !skipped.is_empty() {
+        warn!(
+            "Broadcast to {} skips {} unpaired contacts",
+            tag.trim(),
+            skipped.len()
+        );
+    }
+    if recipients.is_empty() {
+        return Err(format!(
+            "None of the contacts tagged {} are paired",
+            tag.trim()
+        ));
+    }
+
+    let lamport = state.db.next_lamport().map_err(|e| e.to_string())?;
+    let messages: Vec<Message> = recipients
+        .iter()
+        .map(|target| Message {
+            id: generate_id(),
+            sender_id: local_id.clone(),
+            receiver_id: target.clone(),
+            content: content.clone(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: false,
+            created_at: now(),
+            lamport,
+            status: MessageStatus::Queued,
+        })
+        .collect();
+    state
+        .db
+        .import_messages(&messages)
+        .map_err(|e| e.to_string())?;
+    for m in &messages {
+        relay(&state, &m.receiver_id, std::slice::from_ref(m));
+    }
+
+    Ok(finish(
+        &app,
+        BulkResult {
+            action: "broadcast".to_string(),
+            requested: recipients.len() + skipped.len(),
+            affected: messages.len(),
+            messages,
+            skipped,
+        },
+    ))
+}
@@ -0,0 +1,208 @@
+// src-tauri/src/channels.rs
+// Announcement channels: groups of kind "channel" where only admins post. Both ends enforce
+// it: post_group_message refuses a non-admin, and an incoming channel message from someone who
+// isn't an admin in our copy of the member list is dropped. Members get new channels muted by
+// default, and channel messages arrive as "channel-message-received" rather than
+// "group-message-received".
+
+use crate::chat_settings;
+use crate::commands::{self, AppState};
+use crate::db::{Database, Group};
+use crate::signaling::SignalingMessage;
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+pub const KIND_GROUP: &str = "group";
+pub const KIND_CHANNEL: &str = "channel";
+pub const ROLE_ADMIN: &str = "admin";
+pub const ROLE_MEMBER: &str = "member";
+
+/// "group" or "channel"; None means a plain group
+pub fn validate_kind(kind: Option<&str>) -> Result<String, String> {
+    match kind.unwrap_or(KIND_GROUP) {
+        KIND_GROUP => Ok(KIND_GROUP.to_string()),
+        KIND_CHANNEL => Ok(KIND_CHANNEL.to_string()),
+        other => Err(format!("Unknown group kind: {}", other)),
+    }
+}
+
+pub fn is_channel(db: &Database, group_id: &str) -> bool {
+    db.get_group(group_id)
+        .ok()
+        .flatten()
+        .is_some_and(|g| g.kind == KIND_CHANNEL)
+}
+
+pub fn is_admin(db: &Database, group_id: &str, user_id: &str) -> bool {
+    db.get_group_member_role(group_id, user_id)
+        .ok()
+        .flatten()
+        .as_deref()
+        == Some(ROLE_ADMIN)
+}
+
+/// Whether `user_id` may post in the group: anyone in a plain group, only admins in a channel
+pub fn can_post(db: &Database, group_id: &str, user_id: &str) -> bool {
+    !is_channel(db, group_id) || is_admin(db, group_id, user_id)
+}
+
+/// Members other than `except` who are admins, for GroupCreated
+pub fn admin_ids(db: &Database, group_id: &str, except: &str) -> Vec<String> {
+    db.get_group_members(group_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|m| m.role == ROLE_ADMIN && m.user_id != except)
+        .map(|m| m.user_id)
+        .collect()
+}
+
+/// Defaults for a channel we were just added to: muted unless the user already chose otherwise
+pub fn apply_member_defaults(db: &Database, group: &Group) {
+    if group.kind != KIND_CHANNEL {
+        return;
+    }
+    if chat_settings::notification_override(db, &group.id) == chat_settings::NOTIFY_DEFAULT {
+        if let Err(e) = chat_settings::set(
+            db,
+            &group.id,
+            "notifications",
+            Some(chat_settings::NOTIFY_MUTED.to_string()),
+        ) {
+            warn!("Muting channel {} failed: {}", group.id, e);
+        }
+    }
+}
+
+/// Event the webview gets for an incoming message in this group
+pub fn message_event(db: &Database, group_id: &str) -> &'static str {
+    if is_channel(db, group_id) {
+        "channel-message-received"
+    } else {
+        "group-message-received"
+    }
+}
+
+/// A role change from another member; only honoured when that member is an admin here
+pub fn handle_role_changed<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    from: &str,
+    group_id: &str,
+    user_id: &str,
+    role: &str,
+) {
+    if !is_admin(db, group_id, from) {
+        warn!(
+            "Ignoring role change in {} from non-admin {}",
+            group_id, from
+        );
+        return;
+    }
+    if role != ROLE_ADMIN && role != ROLE_MEMBER {
+        return;
+    }
+    if let Ok(true) = db.set_group_member_role(group_id, user_id, role) {
+        let _ = app.emit(
+            "group-role-changed",
+            serde_json::json!({ "group_id": group_id, "user_id": user_id, "role": role }),
+        );
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Make a member an admin ("admin") or take it away ("member"); admins only. In a channel
+/// this decides who can post.
+#[tauri::command]
+pub fn set_group_role<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    group_id: String,
+    user_id: String,
+    role: String,
+) -> Result<(), String> {
+    if role != ROLE_ADMIN && role != ROLE_MEMBER {
+        return Err(format!("Unknown role: {}", role));
+    }
+    let local_id = state.device_id();
+    if !is_admin(&state.db, &group_id, &local_id) {
+        return Err("Only admins can change roles".to_string());
+    }
+    let members = state
+        .db
+        .get_group_members(&group_id)
+        .map_err(|e| e.to_string())?;
+    if !members.iter().any(|m| m.user_id == user_id) {
+        return Err("Not a member of this group".to_string());
+    }
+    let admins = members.iter().filter(|m| m.role == ROLE_ADMIN).count();
+    if role == ROLE_MEMBER && admins <= 1 && is_admin(&state.db, &group_id, &user_id) {
+        return Err("A group needs at least one admin".to_string());
+    }
+    state
+        .db
+        .set_group_member_role(&group_id, &user_id, &role)
+        .map_err(|e| e.to_string())?;
+    info!("{} is now {} in {}", user_id, role, group_id);
+
+    for m in members.iter().filter(|m| m.user_id != local_id) {
+        let msg = SignalingMessage::GroupRoleChanged {
+            from: local_id.clone(),
+            to: m.user_id.clone(),
+            group_id: group_id.clone(),
+            user_id: user_id.clone(),
+            role: role.clone(),
+        };
+        if let Err(e) = commands::send_to_peer(&state, &m.user_id, &msg) {
+            warn!("Role change to {} not sent: {}", m.user_id, e);
+        }
+    }
+    let _ = app.emit(
+        "group-role-changed",
+        serde_json::json!({ "group_id": group_id, "user_id": user_id, "role": role }),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{now, GroupMember};
+
+    #[test]
+    fn test_only_admins_post_in_channels() {
+        let db = Database::new_in_memory().unwrap();
+        for (id, kind) in [("g1", KIND_GROUP), ("c1", KIND_CHANNEL)] {
+            db.create_group(&Group {
+                id: id.to_string(),
+                name: id.to_string(),
+                created_by: "alice".to_string(),
+                avatar_color: None,
+                created_at: now(),
+                kind: kind.to_string(),
+            })
+            .unwrap();
+            for (user, role) in [("alice", ROLE_ADMIN), ("bob", ROLE_MEMBER)] {
+                db.add_group_member(&GroupMember {
+                    group_id: id.to_string(),
+                    user_id: user.to_string(),
+                    username: user.to_string(),
+                    role: role.to_string(),
+                    joined_at: now(),
+                })
+                .unwrap();
+            }
+        }
+        assert!(can_post(&db, "g1", "bob"));
+        assert!(can_post(&db, "c1", "alice"));
+        assert!(!can_post(&db, "c1", "bob"));
+        assert!(!can_post(&db, "c1", "mallory"));
+
+        apply_member_defaults(&db, &db.get_group("c1").unwrap().unwrap());
+        assert_eq!(
+            chat_settings::notification_override(&db, "c1"),
+            chat_settings::NOTIFY_MUTED
+        );
+        assert!(validate_kind(Some("forum")).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+// src-tauri/src/chat_folders.rs
+// Chat folders: user-defined folders (Work, Friends, Alerts) that direct chats and groups are
+// filed into, so a long sidebar can be narrowed down. A chat can sit in several folders. Each
+// folder's unread total rides along with the per-peer unread counts.
+
+use crate::commands::AppState;
+use crate::db::{ChatFolder, Database, FolderChat};
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+const MAX_FOLDER_NAME_CHARS: usize = 32;
+const MAX_FOLDERS: usize = 50;
+
+/// A folder with how many chats it holds and their unread total
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderSummary {
+    #[serde(flatten)]
+    pub folder: ChatFolder,
+    pub chats: usize,
+    pub unread: i32,
+}
+
+/// Unread total per folder, from the per-peer unread counts. Groups have no read state, so
+/// only direct chats count.
+pub fn unread_by_folder(db: &Database, by_peer: &HashMap<String, i32>) -> HashMap<String, i32> {
+    let mut totals = HashMap::new();
+    for (folder_id, chat_id) in db.get_folder_assignments().unwrap_or_default() {
+        let total = totals.entry(folder_id).or_insert(0);
+        *total += by_peer.get(&chat_id).copied().unwrap_or(0);
+    }
+    totals
+}
+
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Folder name can't be empty".to_string());
+    }
+    if name.chars().count() > MAX_FOLDER_NAME_CHARS {
+        return Err(format!(
+            "Folder names are at most {} characters",
+            MAX_FOLDER_NAME_CHARS
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_color(color: Option<String>) -> Result<Option<String>, String> {
+    let color = color
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty());
+    if let Some(c) = &color {
+        if c.len() != 7 || !c.starts_with('#') || !c[1..].chars().all(|ch| ch.is_ascii_hexdigit()) {
+            return Err("Color must look like #RRGGBB".to_string());
+        }
+    }
+    Ok(color)
+}
+
+/// Duplicate names hit the UNIQUE constraint; say so rather than echo SQLite
+fn name_error(e: rusqlite::Error, name: &str) -> String {
+    if e.to_string().contains("UNIQUE") {
+        format!("A folder named \"{}\" already exists", name)
+    } else {
+        e.to_string()
+    }
+}
+
+fn summaries(state: &AppState) -> Result<Vec<FolderSummary>, String> {
+    let folders = state.db.get_chat_folders().map_err(|e| e.to_string())?;
+    let by_peer: HashMap<String, i32> = state
+        .db
+        .get_unread_counts_by_peer(&state.device_id())
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+    let unread = unread_by_folder(&state.db, &by_peer);
+    let mut chats: HashMap<String, usize> = HashMap::new();
+    for (folder_id, _) in state.db.get_folder_assignments().unwrap_or_default() {
+        *chats.entry(folder_id).or_insert(0) += 1;
+    }
+    Ok(folders
+        .into_iter()
+        .map(|folder| FolderSummary {
+            chats: chats.get(&folder.id).copied().unwrap_or(0),
+            unread: unread.get(&folder.id).copied().unwrap_or(0),
+            folder,
+        })
+        .collect())
+}
+
+/// Tell every window the folders changed, with the new list
+fn emit_changed<R: Runtime>(app: &AppHandle<R>, state: &AppState) {
+    if let Ok(folders) = summaries(state) {
+        let _ = app.emit("chat-folders-changed", folders);
+    }
+}
+
+fn require_folder(db: &Database, folder_id: &str) -> Result<ChatFolder, String> {
+    db.get_chat_folder(folder_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Folder not found".to_string())
+}
+
+// ============ COMMANDS ============
+
+/// Every folder with its chat count and unread total
+#[tauri::command]
+pub fn get_chat_folders(state: State<AppState>) -> Result<Vec<FolderSummary>, String> {
+    summaries(&state)
+}
+
+#[tauri::command]
+pub fn create_chat_folder<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    name: String,
+    color: Option<String>,
+) -> Result<ChatFolder, String> {
+    let name = validate_name(&name)?;
+    let color = validate_color(color)?;
+    let count = state
+        .db
+        .get_chat_folders()
+        .map_err(|e| e.to_string())?
+        .len();
+    if count >= MAX_FOLDERS {
+        return Err(format!("At most {} folders", MAX_FOLDERS));
+    }
+    let folder = state
+        .db
+        .create_chat_folder(&name, color.as_deref())
+        .map_err(|e| name_error(e, &name))?;
+    emit_changed(&app, &state);
+    Ok(folder)
+}
+
+/// Rename a folder or change its color
+#[tauri::command]
+pub fn update_chat_folder<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    folder_id: String,
+    name: String,
+    color: Option<String>,
+) -> Result<(), String> {
+    let name = validate_name(&name)?;
+    let color = validate_color(color)?;
+    let updated = state
+        .db
+        .update_chat_folder(&folder_id, &name, color.as_deref())
+        .map_err(|e| name_error(e, &name))?;
+    if !updated {
+        return Err("Folder not found".to_string());
+    }
+    emit_changed(&app, &state);
+    Ok(())
+}
+
+/// Delete a folder; its chats stay, they're just no longer filed there
+#[tauri::command]
+pub fn delete_chat_folder<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    folder_id: String,
+) -> Result<bool, String> {
+    let deleted = state
+        .db
+        .delete_chat_folder(&folder_id)
+        .map_err(|e| e.to_string())?;
+    if deleted {
+        emit_changed(&app, &state);
+    }
+    Ok(deleted)
+}
+
+/// File a chat (peer device_id or group_id) in a folder
+#[tauri::command]
+pub fn assign_chat_to_folder<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    chat_id: String,
+    folder_id: String,
+) -> Result<(), String> {
+    if chat_id.is_empty() {
+        return Err("No chat given".to_string());
+    }
+    require_folder(&state.db, &folder_id)?;
+    state
+        .db
+        .add_chat_to_folder(&folder_id, &chat_id)
+        .map_err(|e| e.to_string())?;
+    emit_changed(&app, &state);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_chat_from_folder<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    chat_id: String,
+    folder_id: String,
+) -> Result<bool, String> {
+    let removed = state
+        .db
+        .remove_chat_from_folder(&folder_id, &chat_id)
+        .map_err(|e| e.to_string())?;
+    if removed {
+        emit_changed(&app, &state);
+    }
+    Ok(removed)
+}
+
+/// The chats filed in a folder, with their unread counts
+#[tauri::command]
+pub fn get_chats_by_folder(
+    state: State<AppState>,
+    folder_id: String,
+) -> Result<Vec<FolderChat>, String> {
+    require_folder(&state.db, &folder_id)?;
+    let by_peer: HashMap<String, i32> = state
+        .db
+        .get_unread_counts_by_peer(&state.device_id())
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+    let mut chats = state
+        .db
+        .get_folder_chats(&folder_id)
+        .map_err(|e| e.to_string())?;
+    for chat in &mut chats {
+        chat.unread = by_peer.get(&chat.chat_id).copied().unwrap_or(0);
+    }
+    Ok(chats)
+}
+
+/// Folder ids per chat, for badges on sidebar entries
+#[tauri::command]
+pub fn get_chat_folder_assignments(
+    state: State<AppState>,
+) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut by_chat: HashMap<String, Vec<String>> = HashMap::new();
+    let assignments = state
+        .db
+        .get_folder_assignments()
+        .map_err(|e| e.to_string())?;
+    for (folder_id, chat_id) in assignments {
+        by_chat.entry(chat_id).or_default().push(folder_id);
+    }
+    Ok(by_chat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unread_by_folder_sums_member_chats() {
+        let db = Database::new_in_memory().unwrap();
+        let work = db.create_chat_folder("Work", None).unwrap();
+        let friends = db.create_chat_folder("Friends", None).unwrap();
+        assert!(db.create_chat_folder("work", None).is_err());
+        db.add_chat_to_folder(&work.id, "alice").unwrap();
+        db.add_chat_to_folder(&work.id, "bob").unwrap();
+        db.add_chat_to_folder(&friends.id, "bob").unwrap();
+
+        let by_peer = HashMap::from([("alice".to_string(), 2), ("bob".to_string(), 3)]);
+        let totals = unread_by_folder(&db, &by_peer);
+        assert_eq!(totals[&work.id], 5);
+        assert_eq!(totals[&friends.id], 3);
+
+        db.delete_chat_folder(&work.id).unwrap();
+        assert_eq!(db.get_folder_assignments().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,166 @@
+// src-tauri/src/chat_settings.rs
+// Per-conversation settings: a nickname, accent color and wallpaper for the chat, and a
+// notification override ("all" rings even during do-not-disturb, "muted" never rings). Stored
+// in the chat_settings table so every window and the notification code see the same values.
+
+use crate::commands::AppState;
+use crate::db::{now, ChatSettings, Database};
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+pub const NOTIFY_DEFAULT: &str = "default";
+pub const NOTIFY_ALL: &str = "all";
+pub const NOTIFY_MUTED: &str = "muted";
+
+const MAX_NICKNAME_CHARS: usize = 40;
+// A preset name, a file id or a local file URL; not the image itself
+const MAX_WALLPAPER_LEN: usize = 2048;
+
+/// The chat's settings, defaults when nothing was set
+pub fn load(db: &Database, chat_id: &str) -> ChatSettings {
+    db.get_chat_settings(chat_id)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| ChatSettings {
+            chat_id: chat_id.to_string(),
+            notifications: NOTIFY_DEFAULT.to_string(),
+            ..ChatSettings::default()
+        })
+}
+
+/// The chat's notification override: NOTIFY_DEFAULT, NOTIFY_ALL or NOTIFY_MUTED
+pub fn notification_override(db: &Database, chat_id: &str) -> String {
+    load(db, chat_id).notifications
+}
+
+fn is_hex_color(value: &str) -> bool {
+    value.len() == 7 && value.starts_with('#') && value[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Set one setting on `settings`; None (or an empty value) goes back to the default
+fn apply(settings: &mut ChatSettings, key: &str, value: Option<String>) -> Result<(), String> {
+    let value = value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    match key {
+        "nickname" => {
+            if value
+                .as_ref()
+                .is_some_and(|v| v.chars().count() > MAX_NICKNAME_CHARS)
+            {
+                return Err(format!(
+                    "Nickname can be at most {} characters",
+                    MAX_NICKNAME_CHARS
+                ));
+            }
+            settings.nickname = value;
+        }
+        "color" => {
+            if value.as_deref().is_some_and(|v| !is_hex_color(v)) {
+                return Err("Color must look like #RRGGBB".to_string());
+            }
+            settings.color = value.map(|v| v.to_lowercase());
+        }
+        "wallpaper" => {
+            if value.as_ref().is_some_and(|v| v.len() > MAX_WALLPAPER_LEN) {
+                return Err("Wallpaper reference is too long".to_string());
+            }
+            settings.wallpaper = value;
+        }
+        "notifications" => {
+            let value = value.unwrap_or_else(|| NOTIFY_DEFAULT.to_string());
+            if ![NOTIFY_DEFAULT, NOTIFY_ALL, NOTIFY_MUTED].contains(&value.as_str()) {
+                return Err(format!("Unknown notification setting: {}", value));
+            }
+            settings.notifications = value;
+        }
+        _ => return Err(format!("Unknown chat setting: {}", key)),
+    }
+    Ok(())
+}
+
+/// Change one setting for a chat and store the result
+pub fn set(
+    db: &Database,
+    chat_id: &str,
+    key: &str,
+    value: Option<String>,
+) -> Result<ChatSettings, String> {
+    if chat_id.is_empty() {
+        return Err("No chat given".to_string());
+    }
+    let mut settings = load(db, chat_id);
+    apply(&mut settings, key, value)?;
+    settings.updated_at = now();
+    let is_default = settings.nickname.is_none()
+        && settings.color.is_none()
+        && settings.wallpaper.is_none()
+        && settings.notifications == NOTIFY_DEFAULT;
+    if is_default {
+        db.delete_chat_settings(chat_id)
+    } else {
+        db.save_chat_settings(&settings)
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(settings)
+}
+
+// ============ COMMANDS ============
+
+/// Change one of a chat's settings (nickname, color, wallpaper, notifications); a null value
+/// resets it
+#[tauri::command]
+pub fn set_chat_setting<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    chat_id: String,
+    key: String,
+    value: Option<String>,
+) -> Result<ChatSettings, String> {
+    let settings = set(&state.db, &chat_id, &key, value)?;
+    let _ = app.emit("chat-settings-changed", &settings);
+    Ok(settings)
+}
+
+/// Settings for one chat, or for every chat that has any when `chat_id` is omitted
+#[tauri::command]
+pub fn get_chat_settings(
+    state: State<AppState>,
+    chat_id: Option<String>,
+) -> Result<Vec<ChatSettings>, String> {
+    match chat_id {
+        Some(chat_id) => Ok(vec![load(&state.db, &chat_id)]),
+        None => state.db.get_all_chat_settings().map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_validates_and_resets() {
+        let db = Database::new_in_memory().unwrap();
+        assert_eq!(notification_override(&db, "peer-1"), NOTIFY_DEFAULT);
+
+        let s = set(&db, "peer-1", "color", Some("#A1B2C3".to_string())).unwrap();
+        assert_eq!(s.color.as_deref(), Some("#a1b2c3"));
+        assert!(set(&db, "peer-1", "color", Some("red".to_string())).is_err());
+        assert!(set(&db, "peer-1", "notifications", Some("loud".to_string())).is_err());
+        assert!(set(&db, "peer-1", "font", Some("big".to_string())).is_err());
+
+        set(
+            &db,
+            "peer-1",
+            "notifications",
+            Some(NOTIFY_MUTED.to_string()),
+        )
+        .unwrap();
+        assert_eq!(notification_override(&db, "peer-1"), NOTIFY_MUTED);
+        assert_eq!(load(&db, "peer-1").color.as_deref(), Some("#a1b2c3"));
+
+        // Back to all defaults: nothing left stored
+        set(&db, "peer-1", "color", None).unwrap();
+        set(&db, "peer-1", "notifications", None).unwrap();
+        assert!(db.get_chat_settings("peer-1").unwrap().is_none());
+    }
+}
@@ -0,0 +1,182 @@
+// src-tauri/src/chat_stats.rs
+// Usage insights for one conversation or group, computed from the local DB: message counts
+// by direction, busiest hours and weekdays (local time), media counts and how long each side
+// usually takes to answer.
+
+use crate::commands::AppState;
+use crate::db::ActivityRow;
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tauri::State;
+
+/// Longer gaps are a new conversation, not a slow reply
+const MAX_RESPONSE_GAP_SECS: i64 = 12 * 60 * 60;
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct ChatStats {
+    pub chat_id: String,
+    pub is_group: bool,
+    pub total: usize,
+    pub sent: usize,
+    pub received: usize,
+    /// Non-text messages by type (image, video, file, …)
+    pub media: BTreeMap<String, usize>,
+    /// Messages per hour of day, 0–23
+    pub by_hour: Vec<usize>,
+    /// Messages per weekday, Monday first
+    pub by_weekday: Vec<usize>,
+    pub busiest_hour: Option<u32>,
+    pub busiest_weekday: Option<String>,
+    pub first_message_at: Option<String>,
+    pub last_message_at: Option<String>,
+    /// How long we take to answer them, in seconds
+    pub avg_response_secs_mine: Option<f64>,
+    /// How long they take to answer us, in seconds
+    pub avg_response_secs_theirs: Option<f64>,
+}
+
+fn busiest(counts: &[usize]) -> Option<usize> {
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, &n)| n > 0)
+        .max_by_key(|(i, &n)| (n, std::cmp::Reverse(*i)))
+        .map(|(i, _)| i)
+}
+
+fn average(samples: &[i64]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    Some(samples.iter().sum::<i64>() as f64 / samples.len() as f64)
+}
+
+pub fn compute<Tz: TimeZone>(rows: &[ActivityRow], local_id: &str, tz: &Tz) -> ChatStats {
+    let mut stats = ChatStats {
+        by_hour: vec![0; 24],
+        by_weekday: vec![0; 7],
+        ..Default::default()
+    };
+    let mut mine = Vec::new();
+    let mut theirs = Vec::new();
+    let mut previous: Option<(&str, DateTime<Tz>)> = None;
+
+    for row in rows {
+        stats.total += 1;
+        let from_me = row.sender_id == local_id;
+        if from_me {
+            stats.sent += 1;
+        } else {
+            stats.received += 1;
+        }
+        if row.message_type != "text" {
+            *stats.media.entry(row.message_type.clone()).or_default() += 1;
+        }
+        let Ok(at) = DateTime::parse_from_rfc3339(&row.created_at) else {
+            continue;
+        };
+        let at = at.with_timezone(tz);
+        stats.by_hour[at.hour() as usize] += 1;
+        stats.by_weekday[at.weekday().num_days_from_monday() as usize] += 1;
+
+        if let Some((prev_sender, prev_at)) = &previous {
+            let gap = (at.clone() - prev_at.clone()).num_seconds();
+            let answered = (*prev_sender == local_id) != from_me;
+            if answered && (0..=MAX_RESPONSE_GAP_SECS).contains(&gap) {
+                if from_me {
+                    mine.push(gap);
+                } else {
+                    theirs.push(gap);
+                }
+            }
+        }
+        previous = Some((&row.sender_id, at));
+    }
+
+    stats.busiest_hour = busiest(&stats.by_hour).map(|h| h as u32);
+    stats.busiest_weekday = busiest(&stats.by_weekday).map(|d| WEEKDAYS[d].to_string());
+    stats.first_message_at = rows.first().map(|r| r.created_at.clone());
+    stats.last_message_at = rows.last().map(|r| r.created_at.clone());
+    stats.avg_response_secs_mine = average(&mine);
+    stats.avg_response_secs_theirs = average(&theirs);
+    stats
+}
+
+// ============ COMMANDS ============
+
+/// Statistics for a direct chat (`peer_id`) or a group (`group_id`)
+#[tauri::command]
+pub fn get_chat_stats(
+    state: State<AppState>,
+    peer_id: Option<String>,
+    group_id: Option<String>,
+) -> Result<ChatStats, String> {
+    let local_id = state.device_id();
+    let (chat_id, is_group, rows) = match (peer_id, group_id) {
+        (Some(peer), None) => {
+            let rows = state
+                .db
+                .get_conversation_activity(&local_id, &peer)
+                .map_err(|e| e.to_string())?;
+            (peer, false, rows)
+        }
+        (None, Some(group)) => {
+            let rows = state
+                .db
+                .get_group_activity(&group)
+                .map_err(|e| e.to_string())?;
+            (group, true, rows)
+        }
+        _ => return Err("Pass either peer_id or group_id".to_string()),
+    };
+    Ok(ChatStats {
+        chat_id,
+        is_group,
+        ..compute(&rows, &local_id, &Local)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn row(sender: &str, message_type: &str, at: &str) -> ActivityRow {
+        ActivityRow {
+            sender_id: sender.to_string(),
+            message_type: message_type.to_string(),
+            created_at: at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_compute_counts_and_response_times() {
+        // 2026-03-02 is a Monday
+        let rows = vec![
+            row("peer", "text", "2026-03-02T09:00:00+00:00"),
+            row("me", "text", "2026-03-02T09:01:00+00:00"),
+            row("me", "image", "2026-03-02T09:02:00+00:00"),
+            row("peer", "text", "2026-03-02T09:05:00+00:00"),
+            // A day later: not a reply
+            row("me", "file", "2026-03-03T15:00:00+00:00"),
+        ];
+        let stats = compute(&rows, "me", &Utc);
+        assert_eq!((stats.total, stats.sent, stats.received), (5, 3, 2));
+        assert_eq!(stats.media.get("image"), Some(&1));
+        assert_eq!(stats.media.get("file"), Some(&1));
+        assert_eq!(stats.busiest_hour, Some(9));
+        assert_eq!(stats.busiest_weekday.as_deref(), Some("Monday"));
+        assert_eq!(stats.avg_response_secs_mine, Some(60.0));
+        assert_eq!(stats.avg_response_secs_theirs, Some(180.0));
+    }
+}
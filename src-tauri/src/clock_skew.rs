@@ -0,0 +1,313 @@
+// src-tauri/src/clock_skew.rs
+// Clock skew: messages are ordered by the sender's own timestamp, so a peer whose clock is off
+// files its messages in the past or the future. Every few minutes each known peer is pinged a few
+// times; its Pong carries its clock, and the reply with the shortest round trip gives the offset
+// (NTP style), which is kept in peer_clock. Incoming messages from a peer that's off by more than
+// the tolerance have their timestamp shifted onto our clock, and nothing is filed later than it
+// arrived. A peer newly found to be off raises "peer-clock-skew" so the UI can flag it. The Pong
+// clock isn't authenticated, so offsets beyond MAX_OFFSET_MS are ignored rather than applied.
+
+use crate::commands::AppState;
+use crate::db::{now, Database, PeerClock};
+use crate::signaling::SignalingServer;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{debug, warn};
+
+const SETTING_KEY: &str = "clock_skew";
+const MEASURE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const STARTUP_DELAY: Duration = Duration::from_secs(30);
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// Pings per measurement; the quickest reply is the most accurate
+const SAMPLES: usize = 3;
+/// Offsets beyond a year are a broken or lying clock, not skew
+const MAX_OFFSET_MS: i64 = 365 * 24 * 60 * 60 * 1000;
+const MAX_TOLERANCE_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClockSkewConfig {
+    /// Shift incoming timestamps of skewed peers onto our clock
+    pub compensate: bool,
+    /// Offsets up to this are left alone (and the peer isn't flagged)
+    #[serde(default = "default_tolerance")]
+    pub tolerance_secs: u64,
+}
+
+fn default_tolerance() -> u64 {
+    5
+}
+
+impl ClockSkewConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.tolerance_secs > MAX_TOLERANCE_SECS {
+            return Err(format!(
+                "The tolerance can be at most {} seconds",
+                MAX_TOLERANCE_SECS
+            ));
+        }
+        Ok(())
+    }
+
+    /// The tolerance, bounded even for a config stored before validation
+    fn tolerance_ms(&self) -> u64 {
+        self.tolerance_secs.min(MAX_TOLERANCE_SECS) * 1000
+    }
+}
+
+impl Default for ClockSkewConfig {
+    fn default() -> Self {
+        ClockSkewConfig {
+            compensate: true,
+            tolerance_secs: default_tolerance(),
+        }
+    }
+}
+
+/// A peer's last measurement and whether it's outside the tolerance
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerClockInfo {
+    #[serde(flatten)]
+    pub clock: PeerClock,
+    pub skewed: bool,
+}
+
+pub fn load(db: &Database) -> ClockSkewConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn is_skewed(config: &ClockSkewConfig, offset_ms: i64) -> bool {
+    offset_ms.unsigned_abs() > config.tolerance_ms()
+}
+
+fn info(config: &ClockSkewConfig, clock: PeerClock) -> PeerClockInfo {
+    PeerClockInfo {
+        skewed: is_skewed(config, clock.offset_ms),
+        clock,
+    }
+}
+
+/// The sender's `timestamp` on our clock, given its measured offset (None if it was never
+/// measured). None when it can stay as it is.
+fn adjust(
+    config: &ClockSkewConfig,
+    timestamp: &str,
+    offset_ms: Option<i64>,
+    received_at: DateTime<Utc>,
+) -> Option<String> {
+    // Unreadable timestamps would sort anywhere; file them as they arrive
+    let Ok(sent) = DateTime::parse_from_rfc3339(timestamp) else {
+        return Some(received_at.to_rfc3339());
+    };
+    let mut sent = sent.with_timezone(&Utc);
+    let shifted = offset_ms
+        .filter(|&o| o.unsigned_abs() <= MAX_OFFSET_MS as u64 && is_skewed(config, o))
+        .and_then(|o| sent.checked_sub_signed(ChronoDuration::milliseconds(o)));
+    if let Some(adjusted) = shifted {
+        sent = adjusted;
+    }
+    // Nothing was sent after it arrived, whatever the clock said
+    let late = sent - received_at > ChronoDuration::milliseconds(config.tolerance_ms() as i64);
+    if late {
+        Some(received_at.to_rfc3339())
+    } else if shifted.is_some() {
+        Some(sent.to_rfc3339())
+    } else {
+        None
+    }
+}
+
+/// Timestamp to file an incoming message from `peer_id` under
+pub fn normalize(db: &Database, peer_id: &str, timestamp: &str) -> String {
+    let config = load(db);
+    if !config.compensate {
+        return timestamp.to_string();
+    }
+    let offset = db
+        .get_peer_clock(peer_id)
+        .ok()
+        .flatten()
+        .map(|c| c.offset_ms);
+    match adjust(&config, timestamp, offset, Utc::now()) {
+        Some(adjusted) => {
+            debug!(
+                "Timestamp from {} moved from {} to {}",
+                peer_id, timestamp, adjusted
+            );
+            adjusted
+        }
+        None => timestamp.to_string(),
+    }
+}
+
+/// Ping `peer_id` a few times and keep the offset from the quickest reply. None when the peer
+/// doesn't answer or is too old to report its clock.
+fn measure<R: Runtime>(
+    app: &AppHandle<R>,
+    signaling: &SignalingServer,
+    db: &Database,
+    peer_id: &str,
+    addr: SocketAddr,
+) -> Option<PeerClock> {
+    let best = (0..SAMPLES)
+        .filter_map(|_| signaling.ping(addr, PING_TIMEOUT).ok())
+        .filter_map(|reply| reply.clock_offset_ms.map(|offset| (reply.rtt, offset)))
+        .filter(|(_, offset)| offset.unsigned_abs() <= MAX_OFFSET_MS as u64)
+        .min_by_key(|(rtt, _)| *rtt)?;
+    let clock = PeerClock {
+        peer_id: peer_id.to_string(),
+        offset_ms: best.1,
+        rtt_ms: best.0.as_millis() as i64,
+        measured_at: now(),
+    };
+    let config = load(db);
+    let was_skewed = db
+        .get_peer_clock(peer_id)
+        .ok()
+        .flatten()
+        .is_some_and(|c| is_skewed(&config, c.offset_ms));
+    if let Err(e) = db.set_peer_clock(&clock) {
+        warn!("Failed to store clock offset of {}: {}", peer_id, e);
+    }
+    if is_skewed(&config, clock.offset_ms) && !was_skewed {
+        warn!(
+            "Clock of {} is {} ms off from ours",
+            peer_id, clock.offset_ms
+        );
+        let _ = app.emit("peer-clock-skew", &info(&config, clock.clone()));
+    }
+    Some(clock)
+}
+
+/// Measure every known peer now and then for the life of the app
+pub fn start_scheduler<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || {
+        thread::sleep(STARTUP_DELAY);
+        loop {
+            let state = app.state::<AppState>();
+            if state.signaling.is_running() {
+                for (peer_id, addr) in state.signaling.peer_addresses() {
+                    measure(&app, &state.signaling, &state.db, &peer_id, addr);
+                }
+            }
+            thread::sleep(MEASURE_INTERVAL);
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+/// Last measured clock offset of every peer that reported one
+#[tauri::command]
+pub fn get_peer_clocks(state: State<AppState>) -> Result<Vec<PeerClockInfo>, String> {
+    let config = load(&state.db);
+    let clocks = state.db.get_peer_clocks().map_err(|e| e.to_string())?;
+    Ok(clocks.into_iter().map(|c| info(&config, c)).collect())
+}
+
+/// Measure one peer's clock now
+#[tauri::command]
+pub async fn measure_peer_clock<R: Runtime>(
+    app: AppHandle<R>,
+    peer_id: String,
+) -> Result<PeerClockInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        let addr = state
+            .signaling
+            .get_peer(&peer_id)
+            .map(|p| p.address)
+            .ok_or("Peer not registered")?;
+        let clock = measure(&app, &state.signaling, &state.db, &peer_id, addr)
+            .ok_or("The peer didn't report its clock")?;
+        Ok(info(&load(&state.db), clock))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_clock_skew_config(state: State<AppState>) -> ClockSkewConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_clock_skew_config(
+    state: State<AppState>,
+    config: ClockSkewConfig,
+) -> Result<(), String> {
+    config.validate()?;
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adjust_timestamps() {
+        let config = ClockSkewConfig::default();
+        let received = DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        // In tolerance: untouched
+        assert_eq!(
+            adjust(&config, "2024-03-01T11:59:59Z", Some(2_000), received),
+            None
+        );
+        // Peer ten minutes fast: shifted back
+        assert_eq!(
+            adjust(&config, "2024-03-01T12:09:58Z", Some(600_000), received),
+            Some("2024-03-01T11:59:58+00:00".to_string())
+        );
+        // Peer an hour slow
+        assert_eq!(
+            adjust(&config, "2024-03-01T11:00:00Z", Some(-3_600_000), received),
+            Some("2024-03-01T12:00:00+00:00".to_string())
+        );
+        // Never measured but from the future: filed as it arrived
+        assert_eq!(
+            adjust(&config, "2030-01-01T00:00:00Z", None, received),
+            Some(received.to_rfc3339())
+        );
+        assert_eq!(
+            adjust(&config, "yesterday", None, received),
+            Some(received.to_rfc3339())
+        );
+        assert!(is_skewed(&config, -6_000));
+        assert!(!is_skewed(&config, 5_000));
+
+        // Absurd offsets (a lying Pong) are ignored instead of overflowing the timestamp
+        assert_eq!(
+            adjust(&config, "2024-03-01T11:59:59Z", Some(i64::MAX), received),
+            None
+        );
+        assert_eq!(
+            adjust(
+                &config,
+                "2024-03-01T11:59:59Z",
+                Some(10_000_000_000_000_000),
+                received
+            ),
+            None
+        );
+        let huge = ClockSkewConfig {
+            tolerance_secs: u64::MAX,
+            ..config
+        };
+        assert!(huge.validate().is_err());
+        assert!(!is_skewed(&huge, 60_000));
+    }
+}
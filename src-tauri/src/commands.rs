@@ -1,27 +1,80 @@
 // src-tauri/src/commands.rs
 // IPC Commands exposed to frontend
 
-use crate::crypto::{generate_device_id, CryptoManager, EncryptedEnvelope};
+use crate::auto_reply;
+use crate::automation_api;
+use crate::avatar;
+use crate::channels;
+use crate::clock_skew;
+use crate::config;
+use crate::contact_tags;
+use crate::conversation_clear;
+use crate::crypto::{
+    decrypt_with_passphrase, encrypt_with_passphrase, generate_device_id, CryptoManager,
+    EncryptedEnvelope, PassphraseEnvelope,
+};
 use crate::db::{
     generate_id, now, Database, Group, GroupMember, GroupMessage, LastMessageInfo, Message, Note,
-    Settings, User,
+    NoteAttachment, ProfileFields, Settings, SharedMediaCount, SharedMediaFilter, User,
 };
-use crate::discovery::{DiscoveryEvent, DiscoveryManager, PeerInfo};
-use crate::file_server::FileServer;
+use crate::db_recovery;
+use crate::deleted_messages;
+use crate::delivery_retry;
+use crate::delivery_status::{self, MessageStatus};
+use crate::device_sync;
+use crate::discovery::{DiscoveryEvent, DiscoveryManager, PeerEventCoalescer, PeerInfo};
+use crate::disk_space;
+use crate::download_folders;
+use crate::download_policy::{self, PolicyAction};
+use crate::download_pool;
+use crate::downloads;
+use crate::event_bus::EventBus;
+use crate::file_index;
+use crate::file_requests;
+use crate::file_send;
+use crate::file_server::{self, FileServer};
 use crate::file_transfer::{FileChunk, FileMetadata, FileTransferManager, TransferProgress};
-use crate::signaling::{SignalingMessage, SignalingServer};
+use crate::http_client;
+use crate::image_compression;
+use crate::image_metadata;
+use crate::link_snapshots;
+use crate::meeting;
+use crate::meeting_history;
+use crate::meeting_recording;
+use crate::meeting_roster::MeetingRosters;
+use crate::meeting_schedule;
+use crate::message_requests;
+use crate::note_sharing;
+use crate::notifications::{self, NotificationTarget};
+use crate::ocr;
+use crate::pairing;
+use crate::pin_pairing;
+use crate::previews;
+use crate::profiles;
+use crate::remote_control;
+use crate::scan;
+use crate::session_store;
+use crate::shred;
+use crate::signaling::{
+    clock_ms, DedupWindow, PeerCapabilities, SignalingMessage, SignalingServer,
+};
+use crate::status;
+use crate::transcription;
 use crate::tray;
+use crate::trust;
+use crate::webhooks;
+use crate::whiteboard;
+use crate::window_manager::{self, ChatWindows};
 
 use base64::Engine;
 use serde::{Deserialize, Serialize};
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::sync::OnceLock;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tauri_plugin_opener::OpenerExt;
+use tracing::{debug, error, info, warn};
 
 /// App state containing all managers
 pub struct AppState {
@@ -31,73 +84,86 @@ pub struct AppState {
     pub signaling: Arc<SignalingServer>,
     pub file_transfer: Arc<FileTransferManager>,
     pub file_server: Arc<FileServer>,
-    pub device_id: String,
+    pub chat_windows: Arc<ChatWindows>,
+    pub meeting_rosters: Arc<MeetingRosters>,
+    pub events: Arc<EventBus>,
+    // Swapped on profile switch; read through device_id()
+    pub(crate) device_id: RwLock<String>,
 }
 
 impl AppState {
     pub fn new() -> Result<Self, String> {
-        let db = Database::new().map_err(|e| e.to_string())?;
-
-        let device_id = match db.get_setting("device_id") {
-            Ok(Some(id)) if !id.is_empty() => {
-                println!(
-                    "[Pingo] Loaded persisted device_id: {}",
-                    &id[..8.min(id.len())]
-                );
-                id
-            }
-            _ => {
-                let new_id = generate_device_id();
-                db.set_setting("device_id", &new_id)
-                    .map_err(|e| e.to_string())?;
-                println!(
-                    "[Pingo] Generated new device_id: {}",
-                    &new_id[..8.min(new_id.len())]
-                );
-                new_id
-            }
-        };
+        let db = Arc::new(db_recovery::open()?);
+        let device_id = load_or_create_device_id(&db)?;
+        let events = Arc::new(EventBus::new());
+        db.set_change_listener(events.data_listener());
+        let file_transfer = FileTransferManager::new();
+        file_transfer.set_progress_listener(events.progress_listener());
 
         Ok(AppState {
-            db: Arc::new(db),
             discovery: Arc::new(DiscoveryManager::new()),
-            crypto: Arc::new(CryptoManager::new()),
+            crypto: Arc::new({
+                let crypto = CryptoManager::new();
+                crypto.set_device_id(&device_id);
+                crypto.set_session_listener(session_store::listener(db.clone()));
+                crypto
+            }),
+            db,
             signaling: Arc::new(SignalingServer::new(device_id.clone())),
-            file_transfer: Arc::new(FileTransferManager::new()),
+            file_transfer: Arc::new(file_transfer),
             file_server: Arc::new(FileServer::new()),
-            device_id,
+            chat_windows: Arc::new(ChatWindows::new()),
+            meeting_rosters: Arc::new(MeetingRosters::new()),
+            events,
+            device_id: RwLock::new(device_id),
         })
     }
+
+    /// This device's id for the active profile
+    pub fn device_id(&self) -> String {
+        self.device_id.read().unwrap().clone()
+    }
+
+    /// Stop discovery and signaling and drop peer sessions and shared files, so nothing from
+    /// the network reaches the database while it's swapped out
+    pub fn stop_networking(&self) {
+        self.discovery.stop();
+        self.signaling.stop();
+        self.crypto.clear_sessions();
+        self.file_server.clear_registered_files();
+    }
+
+    /// Re-point every service at the active profile (see profiles::switch_profile).
+    /// Networking is stopped here and restarted by the frontend's next init_app.
+    pub fn reload_profile(&self) -> Result<(), String> {
+        self.stop_networking();
+        self.db.reopen().map_err(|e| e.to_string())?;
+        let device_id = load_or_create_device_id(&self.db)?;
+        self.signaling.set_device_id(&device_id);
+        self.crypto.set_device_id(&device_id);
+        *self.device_id.write().unwrap() = device_id;
+        self.file_transfer
+            .set_downloads_dir(profiles::downloads_dir(&profiles::active_profile()));
+        http_client::configure(&self.db);
+        Ok(())
+    }
 }
 
-// Throttle file writes to avoid unbounded growth during noisy periods (e.g., presence updates).
-// We still print to stdout every time for debugging, but only write to disk at most once per second.
-static LAST_DEV_LOG_WRITE: OnceLock<AtomicU64> = OnceLock::new();
-
-fn dev_log(msg: &str) {
-    let ts = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_secs())
-        .unwrap_or(0);
-    let s = format!("[{}] [Pingo] {}", ts, msg);
-    println!("{}", s);
-
-    let last_write = LAST_DEV_LOG_WRITE.get_or_init(|| AtomicU64::new(0));
-    let prev = last_write.load(Ordering::Relaxed);
-    if ts.saturating_sub(prev) >= 1 {
-        // safe to write to file — place log in the app data directory (same place as DB)
-        last_write.store(ts, Ordering::Relaxed);
-        // Determine a stable app-local location to avoid touching the source tree and
-        // triggering dev rebuilds when the log file changes.
-        let log_dir = Database::get_db_path()
-            .parent()
-            .map(|p| p.to_path_buf())
-            .unwrap_or_else(|| PathBuf::from("."));
-        let log_path = log_dir.join("pingo_dev_log.txt");
-        // Ensure directory exists
-        let _ = std::fs::create_dir_all(&log_dir);
-        if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(log_path) {
-            let _ = writeln!(f, "{}", s);
+fn load_or_create_device_id(db: &Database) -> Result<String, String> {
+    match db.get_setting("device_id") {
+        Ok(Some(id)) if !id.is_empty() => {
+            info!("Loaded persisted device_id: {}", &id[..8.min(id.len())]);
+            Ok(id)
+        }
+        _ => {
+            let new_id = generate_device_id();
+            db.set_setting("device_id", &new_id)
+                .map_err(|e| e.to_string())?;
+            info!(
+                "Generated new device_id: {}",
+                &new_id[..8.min(new_id.len())]
+            );
+            Ok(new_id)
         }
     }
 }
@@ -112,49 +178,58 @@ pub struct InitResult {
 
 #[tauri::command]
 pub fn init_app(state: State<AppState>) -> Result<InitResult, String> {
-    dev_log("init_app started");
+    info!("init_app started");
     // spawn a one-shot watchdog to detect unusually long init
-    let watchdog_device = state.device_id.clone();
+    let watchdog_device = state.device_id();
     std::thread::spawn(move || {
         std::thread::sleep(std::time::Duration::from_secs(10));
-        dev_log(&format!(
+        warn!(
             "init_app still running after 10s for device {}",
             watchdog_device
-        ));
+        );
     });
 
-    let public_key = state.crypto.generate_keypair();
+    session_store::load_identity(&state.crypto)?;
+    let public_key = session_store::load_keypair(&state.db, &state.crypto)?;
+    session_store::restore_sessions(&state.db, &state.crypto);
     state
         .db
         .set_setting("public_key", &public_key)
         .map_err(|e| e.to_string())?;
 
-    // Start file server with retry
-    let file_port = state.file_server.start(18080).unwrap_or(0);
+    // Start file server with retry (already running when re-initialising after a profile switch)
+    let file_port = match state.file_server.get_port() {
+        0 => state
+            .file_server
+            .start(config::load(&state.db).file_server_port)
+            .unwrap_or(0),
+        port => port,
+    };
     if file_port == 0 {
-        dev_log("ERROR: File server failed to start on any port!");
+        error!("File server failed to start on any port");
         return Err("File server failed to start".to_string());
     }
-    dev_log(&format!(
-        "File server started successfully on port {}",
-        file_port
-    ));
+    info!("File server started successfully on port {}", file_port);
+    register_note_attachments(&state);
+    image_compression::register_originals(&state);
+    publish_own_avatar(&state);
+    download_folders::migrate(&state.db, &state.file_transfer.get_downloads_dir());
 
     // Add a small delay to ensure the server thread has time to bind
     std::thread::sleep(std::time::Duration::from_millis(100));
 
     let existing_user = state
         .db
-        .get_user(&state.device_id)
+        .get_user(&state.device_id())
         .map_err(|e| e.to_string())?;
     if existing_user.is_none() {
         let hostname = hostname::get()
             .map(|h| h.to_string_lossy().to_string())
             .unwrap_or_else(|_| "Pingo User".to_string());
         let user = User {
-            id: state.device_id.clone(),
+            id: state.device_id(),
             username: hostname,
-            device_id: state.device_id.clone(),
+            device_id: state.device_id(),
             public_key: Some(public_key.clone()),
             avatar_path: None,
             bio: Some(String::new()),
@@ -162,6 +237,7 @@ pub fn init_app(state: State<AppState>) -> Result<InitResult, String> {
             last_seen: Some(now()),
             is_online: true,
             created_at: now(),
+            profile: ProfileFields::default(),
         };
         state.db.create_user(&user).map_err(|e| e.to_string())?;
     } else {
@@ -172,12 +248,9 @@ pub fn init_app(state: State<AppState>) -> Result<InitResult, String> {
         state.db.create_user(&user).map_err(|e| e.to_string())?;
     }
 
-    dev_log(&format!(
-        "init_app complete. device_id={}",
-        &state.device_id
-    ));
+    info!("init_app complete. device_id={}", state.device_id());
     Ok(InitResult {
-        device_id: state.device_id.clone(),
+        device_id: state.device_id(),
         public_key,
         db_path: Database::get_db_path().to_string_lossy().to_string(),
         downloads_path: state
@@ -196,6 +269,9 @@ pub struct CreateUserInput {
     pub avatar_path: Option<String>,
     pub bio: Option<String>,
     pub designation: Option<String>,
+    /// Only fields that are set change; send "" to clear one
+    #[serde(flatten)]
+    pub profile: ProfileFields,
 }
 
 #[tauri::command]
@@ -203,12 +279,16 @@ pub fn create_user(state: State<AppState>, input: CreateUserInput) -> Result<Use
     // Load existing user to preserve fields not being updated
     let existing = state
         .db
-        .get_user(&state.device_id)
+        .get_user(&state.device_id())
         .map_err(|e| e.to_string())?;
+    let previous = existing
+        .as_ref()
+        .map(|u| u.profile.clone())
+        .unwrap_or_default();
     let user = User {
-        id: state.device_id.clone(),
+        id: state.device_id(),
         username: input.username,
-        device_id: state.device_id.clone(),
+        device_id: state.device_id(),
         public_key: state.crypto.get_public_key(),
         avatar_path: input
             .avatar_path
@@ -222,6 +302,7 @@ pub fn create_user(state: State<AppState>, input: CreateUserInput) -> Result<Use
         last_seen: Some(now()),
         is_online: true,
         created_at: existing.map(|u| u.created_at).unwrap_or_else(now),
+        profile: input.profile.or(previous).normalized(),
     };
     state.db.create_user(&user).map_err(|e| e.to_string())?;
     Ok(user)
@@ -232,23 +313,25 @@ pub fn get_user(state: State<AppState>, id: String) -> Result<Option<User>, Stri
     state.db.get_user(&id).map_err(|e| e.to_string())
 }
 
+/// Every contact; only those tagged `tag` when one is given
 #[tauri::command]
-pub fn get_all_users(state: State<AppState>) -> Result<Vec<User>, String> {
-    state.db.get_all_users().map_err(|e| e.to_string())
+pub fn get_all_users(state: State<AppState>, tag: Option<String>) -> Result<Vec<User>, String> {
+    let users = state.db.get_all_users().map_err(|e| e.to_string())?;
+    contact_tags::filter_users(&state.db, users, tag.as_deref())
 }
 
 #[tauri::command]
 pub fn get_local_user(state: State<AppState>) -> Result<Option<User>, String> {
     state
         .db
-        .get_user(&state.device_id)
+        .get_user(&state.device_id())
         .map_err(|e| e.to_string())
 }
 
 #[allow(dead_code)]
 #[tauri::command]
 pub fn append_dev_log(message: String) -> Result<(), String> {
-    dev_log(&message);
+    info!(target: "pingo_lib::frontend", "{}", message);
     Ok(())
 }
 
@@ -266,7 +349,7 @@ pub struct SendMessageInput {
 pub fn send_message(state: State<AppState>, input: SendMessageInput) -> Result<Message, String> {
     let message = Message {
         id: generate_id(),
-        sender_id: state.device_id.clone(),
+        sender_id: state.device_id(),
         receiver_id: input.receiver_id,
         content: input.content,
         message_type: input.message_type.unwrap_or_else(|| "text".into()),
@@ -274,11 +357,16 @@ pub fn send_message(state: State<AppState>, input: SendMessageInput) -> Result<M
         is_read: false,
         is_delivered: false,
         created_at: now(),
+        lamport: state.db.next_lamport().map_err(|e| e.to_string())?,
+        status: MessageStatus::Queued,
     };
     state
         .db
         .create_message(&message)
         .map_err(|e| e.to_string())?;
+    if message.message_type == "text" {
+        link_snapshots::queue(&state, &message.id, &message.content, false);
+    }
     Ok(message)
 }
 
@@ -290,7 +378,7 @@ pub fn get_messages(
 ) -> Result<Vec<Message>, String> {
     state
         .db
-        .get_messages_between(&state.device_id, &peer_id, limit.unwrap_or(100))
+        .get_messages_between(&state.device_id(), &peer_id, limit.unwrap_or(100))
         .map_err(|e| e.to_string())
 }
 
@@ -304,7 +392,7 @@ pub fn get_messages_paginated(
     state
         .db
         .get_messages_paginated(
-            &state.device_id,
+            &state.device_id(),
             &peer_id,
             before.as_deref(),
             limit.unwrap_or(50),
@@ -320,7 +408,7 @@ pub fn get_new_messages_since(
 ) -> Result<Vec<Message>, String> {
     state
         .db
-        .get_new_messages_since(&state.device_id, &peer_id, &since)
+        .get_new_messages_since(&state.device_id(), &peer_id, &since)
         .map_err(|e| e.to_string())
 }
 
@@ -336,16 +424,26 @@ pub fn mark_message_read(state: State<AppState>, message_id: String) -> Result<(
 pub fn mark_messages_read_from_peer(state: State<AppState>, peer_id: String) -> Result<(), String> {
     state
         .db
-        .mark_messages_read_from_peer(&state.device_id, &peer_id)
+        .mark_messages_read_from_peer(&state.device_id(), &peer_id)
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub fn mark_message_delivered(state: State<AppState>, message_id: String) -> Result<(), String> {
-    state
+    delivery_status::try_update(&state, &message_id, MessageStatus::Delivered).map(|_| ())
+}
+
+/// Delivery state of a direct message
+#[tauri::command]
+pub fn get_message_status(
+    state: State<AppState>,
+    message_id: String,
+) -> Result<Option<MessageStatus>, String> {
+    Ok(state
         .db
-        .mark_message_delivered(&message_id)
-        .map_err(|e| e.to_string())
+        .get_message(&message_id)
+        .map_err(|e| e.to_string())?
+        .map(|m| m.status))
 }
 
 #[tauri::command]
@@ -355,36 +453,66 @@ pub fn get_undelivered_messages_for_peer(
 ) -> Result<Vec<Message>, String> {
     state
         .db
-        .get_undelivered_messages_for_peer(&state.device_id, &peer_id)
+        .get_undelivered_messages_for_peer(
+            &state.device_id(),
+            &peer_id,
+            delivery_retry::load(&state.db).exhausted_after(),
+        )
         .map_err(|e| e.to_string())
 }
 
+/// Polling fallback: counts are pushed as "backend-event" { kind: "unread_counts" }
 #[tauri::command]
 pub fn get_unread_count(state: State<AppState>) -> Result<i32, String> {
     state
         .db
-        .get_unread_count(&state.device_id)
+        .get_unread_count(&state.device_id())
         .map_err(|e| e.to_string())
 }
 
+/// Polling fallback: counts are pushed as "backend-event" { kind: "unread_counts" }
 #[tauri::command]
 pub fn get_unread_count_from_peer(state: State<AppState>, peer_id: String) -> Result<i32, String> {
     state
         .db
-        .get_unread_count_from_peer(&state.device_id, &peer_id)
+        .get_unread_count_from_peer(&state.device_id(), &peer_id)
         .map_err(|e| e.to_string())
 }
 
+/// Initial load; later changes are pushed as "backend-event" { kind: "last_messages" }
 #[tauri::command]
 pub fn get_last_messages(state: State<AppState>) -> Result<Vec<LastMessageInfo>, String> {
     state
         .db
-        .get_last_messages(&state.device_id)
+        .get_last_messages(&state.device_id())
         .map_err(|e| e.to_string())
 }
 
 // ============ DISCOVERY COMMANDS ============
 
+/// How often an unchanged peer's last_seen is written back while its hellos keep coming
+const LAST_SEEN_REFRESH: Duration = Duration::from_secs(60);
+
+/// Send a delivery acknowledgement back to the sender so they can mark the message as
+/// delivered in their local DB/UI. This avoids marking delivery based purely on UDP send success.
+fn send_delivery_ack(signaling: &SignalingServer, local_id: &str, to: &str, message_id: &str) {
+    let ack = SignalingMessage::DeliveryAck {
+        from: local_id.to_string(),
+        to: to.to_string(),
+        message_id: message_id.to_string(),
+    };
+    let _ = signaling.send_message(to, &ack);
+}
+
+/// Keep the identity key a peer announced (discovery already checked it against any pin)
+fn pin_identity(db: &Database, peer: &PeerInfo) {
+    if let Some(identity) = &peer.identity {
+        if let Err(e) = db.pin_identity(&peer.device_id, &identity.identity_key) {
+            warn!("Pinning the identity of {} failed: {}", peer.device_id, e);
+        }
+    }
+}
+
 #[tauri::command]
 pub fn start_discovery<R: Runtime>(
     app: AppHandle<R>,
@@ -396,24 +524,67 @@ pub fn start_discovery<R: Runtime>(
         .crypto
         .get_public_key()
         .ok_or("Public key not initialized")?;
-    if state
+    state
         .discovery
-        .start(state.device_id.clone(), username, port, public_key)?
-    {
+        .set_avatar_hash(avatar::local_hash(&state.file_server, &state.device_id()));
+    state
+        .discovery
+        .set_status(state.db.get_user_status(&state.device_id()).ok().flatten());
+    state.discovery.set_visible(status::load_visibility(&state));
+    state.discovery.set_identity_pins(
+        state
+            .db
+            .get_identity_pins()
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+    );
+    if state.discovery.start(
+        state.device_id(),
+        username,
+        port,
+        public_key,
+        state.crypto.identity_proof(),
+    )? {
         let discovery = Arc::clone(&state.discovery);
         let db = Arc::clone(&state.db);
         let signaling = Arc::clone(&state.signaling);
+        let crypto = Arc::clone(&state.crypto);
+        let local_device_id = state.device_id();
         let app_clone = app.clone();
 
         std::thread::spawn(move || {
             let receiver = discovery.get_event_receiver();
+            // Avatar versions we've already told the UI about, so each change is reported once
+            let mut announced_avatars: HashMap<String, String> = HashMap::new();
+            let mut check_avatar = |peer: &PeerInfo| {
+                let Some(hash) = &peer.avatar_hash else {
+                    return;
+                };
+                let stored = db.get_user_avatar_hash(&peer.device_id).ok().flatten();
+                if stored.as_ref() != Some(hash)
+                    && announced_avatars.get(&peer.device_id) != Some(hash)
+                {
+                    announced_avatars.insert(peer.device_id.clone(), hash.clone());
+                    let _ = app_clone.emit(
+                        "peer-avatar-changed",
+                        serde_json::json!({ "device_id": peer.device_id, "avatar_hash": hash }),
+                    );
+                }
+            };
+            // Hellos arrive every few seconds per peer; only changes reach the DB and the UI
+            let mut coalescer = PeerEventCoalescer::new();
+            let mut last_written: HashMap<String, Instant> = HashMap::new();
             loop {
                 if !discovery.is_running() {
                     break;
                 }
-                match receiver.recv_timeout(std::time::Duration::from_millis(500)) {
+                let wait = coalescer.wait(Instant::now(), Duration::from_millis(500));
+                match receiver.recv_timeout(wait) {
                     Ok(event) => match event {
                         DiscoveryEvent::PeerDiscovered { ref peer } => {
+                            coalescer.discovered(peer);
+                            last_written.insert(peer.device_id.clone(), Instant::now());
                             let _ = db.upsert_peer_as_user(
                                 &peer.device_id,
                                 &peer.username,
@@ -425,29 +596,70 @@ pub fn start_discovery<R: Runtime>(
                                 &peer.ip_address,
                                 peer.port,
                             );
+                            signaling.set_peer_compression(&peer.device_id, peer.compression);
+                            pin_identity(&db, peer);
+                            trust::on_peer_seen(&db, &crypto, peer);
+                            // A linked device came online: catch up on what it has
+                            if db
+                                .get_linked_device(&peer.device_id)
+                                .ok()
+                                .flatten()
+                                .is_some()
+                            {
+                                device_sync::request_sync(
+                                    &db,
+                                    &signaling,
+                                    &local_device_id,
+                                    &peer.device_id,
+                                );
+                            }
                             let _ = app_clone.emit("peer-discovered", peer);
+                            check_avatar(peer);
                         }
                         DiscoveryEvent::PeerUpdated { ref peer } => {
-                            let _ = db.upsert_peer_as_user(
-                                &peer.device_id,
-                                &peer.username,
-                                Some(&peer.public_key),
-                            );
-                            let _ = signaling.register_peer(
-                                &peer.device_id,
-                                &peer.ip_address,
-                                peer.port,
-                            );
-                            let _ = app_clone.emit("peer-updated", peer);
+                            let changed = coalescer.updated(peer, Instant::now());
+                            // Unchanged hellos only keep last_seen fresh, once a minute
+                            let stale = last_written
+                                .get(&peer.device_id)
+                                .is_none_or(|at| at.elapsed() >= LAST_SEEN_REFRESH);
+                            if changed || stale {
+                                last_written.insert(peer.device_id.clone(), Instant::now());
+                                let _ = db.upsert_peer_as_user(
+                                    &peer.device_id,
+                                    &peer.username,
+                                    Some(&peer.public_key),
+                                );
+                                let _ = signaling.register_peer(
+                                    &peer.device_id,
+                                    &peer.ip_address,
+                                    peer.port,
+                                );
+                                signaling.set_peer_compression(&peer.device_id, peer.compression);
+                                pin_identity(&db, peer);
+                            }
+                            if changed {
+                                // A rotated session key replaces the session made with the old one
+                                trust::on_peer_seen(&db, &crypto, peer);
+                                check_avatar(peer);
+                            }
                         }
                         DiscoveryEvent::PeerLost { device_id } => {
-                            let _ = app_clone
-                                .emit("peer-lost", serde_json::json!({ "device_id": device_id }));
+                            last_written.remove(&device_id);
+                            if coalescer.lost(&device_id) {
+                                let _ = app_clone.emit(
+                                    "peer-lost",
+                                    serde_json::json!({ "device_id": device_id }),
+                                );
+                            }
                         }
                     },
                     Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
                     Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
                 }
+                let batch = coalescer.take_due(Instant::now());
+                if !batch.is_empty() {
+                    let _ = app_clone.emit("peers-updated", &batch);
+                }
             }
         });
     }
@@ -472,26 +684,70 @@ pub fn get_online_peers(state: State<AppState>) -> Vec<PeerInfo> {
 
 // ============ SIGNALING COMMANDS ============
 
+/// Repeats of a message within this long are dropped (see DedupWindow)
+const DEDUP_WINDOW: Duration = Duration::from_secs(30);
+
 #[tauri::command]
 pub fn start_signaling<R: Runtime>(
     app: AppHandle<R>,
     state: State<AppState>,
     port: Option<u16>,
 ) -> Result<u16, String> {
-    let actual_port = state.signaling.start(port.unwrap_or(45678))?;
+    let actual_port = state
+        .signaling
+        .start(port.unwrap_or_else(|| config::load(&state.db).signaling_port))?;
     let signaling = Arc::clone(&state.signaling);
     let db = Arc::clone(&state.db);
-    let local_device_id = state.device_id.clone();
+    let chat_windows = Arc::clone(&state.chat_windows);
+    let file_server = Arc::clone(&state.file_server);
+    let file_transfer = Arc::clone(&state.file_transfer);
+    let local_device_id = state.device_id();
     let app_clone = app.clone();
 
+    let generation = signaling.generation();
+
     std::thread::spawn(move || {
         let receiver = signaling.get_event_receiver();
-        dev_log(&format!(
-            "Signaling event forwarder started on port {}",
-            actual_port
-        ));
+        let mut recent = DedupWindow::new(DEDUP_WINDOW);
+        info!("Signaling event forwarder started on port {}", actual_port);
         loop {
+            // A restart (e.g. after a profile switch) spawns a new forwarder with fresh state
+            if !signaling.is_running() || signaling.generation() != generation {
+                break;
+            }
             match receiver.recv_timeout(std::time::Duration::from_millis(500)) {
+                // Older versions sent meeting chat as prefixed direct messages; it now has its
+                // own MeetingChat messages and never goes into the chats
+                Ok(SignalingMessage::ChatMessage { content, .. })
+                    if content.starts_with("[MEETING_CHAT]") =>
+                {
+                    debug!("Dropped meeting chat sent as a direct message");
+                }
+                // PIN pairing required: first contact from unpaired peers is dropped
+                Ok(msg) if pin_pairing::blocks(&app_clone.state::<AppState>(), &msg) => {}
+                // Meeting offers, answers and candidates only from peers in the meeting
+                Ok(msg)
+                    if !app_clone
+                        .state::<AppState>()
+                        .meeting_rosters
+                        .admits_incoming(&local_device_id, &msg) => {}
+                // Privacy mode: unknown senders' messages wait in message requests
+                Ok(msg)
+                    if message_requests::hold(
+                        &app_clone,
+                        &db,
+                        &signaling,
+                        &local_device_id,
+                        &msg,
+                    ) => {}
+                // The same message again (retry, TCP fallback, relay): already handled. A chat
+                // message is acknowledged again, since the sender evidently missed our ack.
+                Ok(msg) if !recent.first_sighting(&msg, Instant::now()) => {
+                    if let SignalingMessage::ChatMessage { from, id, .. } = &msg {
+                        send_delivery_ack(&signaling, &local_device_id, from, id);
+                    }
+                    debug!("Dropped a duplicate signaling message");
+                }
                 Ok(msg) => match &msg {
                     SignalingMessage::ChatMessage {
                         from,
@@ -500,9 +756,12 @@ pub fn start_signaling<R: Runtime>(
                         message_type,
                         sender_name,
                         timestamp,
+                        lamport,
+                        auto_reply: is_auto_reply,
                         ..
                     } => {
-                        println!("[Pingo] Received chat message from {}", sender_name);
+                        debug!("Received chat message from {}", sender_name);
+                        let timestamp = &clock_skew::normalize(&db, from, timestamp);
 
                         // Ensure the peer exists in users table
                         let _ = db.upsert_peer_as_user(from, sender_name, None);
@@ -531,16 +790,12 @@ pub fn start_signaling<R: Runtime>(
                                                     ip, meta_port, file_id
                                                 );
                                                 match db.set_user_avatar(&from, &url) {
-                                                    Ok(_) => println!(
-                                                        "[Pingo] Resolved avatar for {}",
-                                                        from
-                                                    ),
-                                                    Err(e) => println!(
-                                                        "[Pingo] Failed to resolve avatar: {}",
-                                                        e
-                                                    ),
+                                                    Ok(_) => info!("Resolved avatar for {}", from),
+                                                    Err(e) => {
+                                                        warn!("Failed to resolve avatar: {}", e)
+                                                    }
                                                 }
-                                                let _ = app_clone.emit("peer-updated", serde_json::json!({ "device_id": from, "username": sender_name, "avatar_path": url }));
+                                                let _ = app_clone.emit("peers-updated", serde_json::json!([{ "device_id": from, "username": sender_name, "avatar_path": url }]));
                                             }
                                         }
                                     }
@@ -548,13 +803,13 @@ pub fn start_signaling<R: Runtime>(
                             }
 
                             let _ = app_clone.emit(
-                                "peer-updated",
-                                serde_json::json!({
+                                "peers-updated",
+                                serde_json::json!([{
                                     "device_id": from,
                                     "username": sender_name,
                                     "ip_address": ip,
                                     "port": port,
-                                }),
+                                }]),
                             );
                         }
 
@@ -568,27 +823,65 @@ pub fn start_signaling<R: Runtime>(
                             is_read: false,
                             is_delivered: true,
                             created_at: timestamp.clone(),
+                            lamport: *lamport,
+                            status: MessageStatus::Delivered,
                         };
                         match db.create_message(&message) {
-                            Ok(_) => println!(
-                                "[Pingo] Stored incoming message {}",
-                                &id[..8.min(id.len())]
-                            ),
-                            Err(e) => println!("[Pingo] Failed to store message: {}", e),
+                            Ok(_) => debug!("Stored incoming message {}", &id[..8.min(id.len())]),
+                            Err(e) => warn!("Failed to store message: {}", e),
                         }
 
                         // Notify frontend to load/display the message
                         let _ = app_clone.emit("chat-message-received", &message);
+                        automation_api::publish("chat-message-received", &message);
+                        webhooks::message_received(&db, &message);
+                        window_manager::route_to_chat_window(
+                            &app_clone,
+                            &chat_windows,
+                            from,
+                            &message,
+                        );
 
-                        // Send delivery acknowledgement back to the sender so they can mark the
-                        // message as delivered in their local DB/UI. This avoids marking delivery
-                        // based purely on UDP send success.
-                        let ack_msg = SignalingMessage::DeliveryAck {
-                            from: local_device_id.clone(),
-                            to: from.clone(),
-                            message_id: id.clone(),
-                        };
-                        let _ = signaling.send_message(&from, &ack_msg);
+                        notifications::notify_incoming(
+                            &app_clone,
+                            &db,
+                            NotificationTarget::Direct { peer_id: from },
+                            sender_name,
+                            message_type,
+                            content,
+                        );
+
+                        if message_type == "text" {
+                            // Never answer another responder's auto-reply
+                            if !is_auto_reply {
+                                auto_reply::handle_incoming(&app_clone, from, sender_name, content);
+                            }
+                            link_snapshots::queue(
+                                &app_clone.state::<AppState>(),
+                                id,
+                                content,
+                                true,
+                            );
+                        }
+
+                        // Pop up the quick reply window when the user isn't looking at the app
+                        window_manager::set_last_incoming_peer(from);
+                        let popup_enabled = db
+                            .get_setting("quick_reply_popup")
+                            .ok()
+                            .flatten()
+                            .map(|v| v == "true")
+                            .unwrap_or(false);
+                        if popup_enabled
+                            && !tray::is_muted()
+                            && !window_manager::is_main_window_focused(&app_clone)
+                        {
+                            if let Err(e) = window_manager::open_quick_reply(&app_clone, from) {
+                                warn!("Failed to open quick reply: {}", e);
+                            }
+                        }
+
+                        send_delivery_ack(&signaling, &local_device_id, from, id);
                     }
                     SignalingMessage::ProfileUpdate {
                         from,
@@ -598,16 +891,38 @@ pub fn start_signaling<R: Runtime>(
                         avatar_file_port,
                         bio,
                         designation,
+                        avatar_hash,
+                        profile,
+                        status,
                         ..
                     } => {
-                        println!("[Pingo] Received profile update from {}", from);
+                        info!("Received profile update from {}", from);
                         let _ = db.upsert_peer_as_user(from, username, None);
+                        let profile = profile.clone().normalized();
+                        let _ = db.set_user_profile_fields(from, &profile);
+                        let _ = db.set_user_status(from, status.as_ref());
+                        download_folders::remember_name(
+                            &file_transfer.get_downloads_dir(),
+                            from,
+                            username,
+                        );
+
+                        // Same avatar version as the one we cached: keep serving our copy
+                        let cached_avatar = avatar_hash
+                            .as_ref()
+                            .filter(|h| {
+                                db.get_user_avatar_hash(from).ok().flatten().as_ref() == Some(*h)
+                            })
+                            .and_then(|_| avatar::cached_url(&file_server, from));
 
                         // Resolve avatar URL
-                        let resolved_avatar: Option<String> = if let Some(url) = avatar_url {
+                        let resolved_avatar: Option<String> = if let Some(url) = cached_avatar {
+                            let _ = db.set_user_avatar(from, &url);
+                            Some(url)
+                        } else if let Some(url) = avatar_url {
                             match db.set_user_avatar(from, &url) {
-                                Ok(_) => println!("[Pingo] Updated avatar for {}", from),
-                                Err(e) => println!("[Pingo] Failed to set avatar: {}", e),
+                                Ok(_) => info!("Updated avatar for {}", from),
+                                Err(e) => warn!("Failed to set avatar: {}", e),
                             }
                             Some(url.clone())
                         } else if let Some(file_id) = avatar_file_id {
@@ -616,8 +931,8 @@ pub fn start_signaling<R: Runtime>(
                                 let port = avatar_file_port.unwrap_or(pc.address.port());
                                 let url = format!("http://{}:{}/file/{}", ip, port, file_id);
                                 match db.set_user_avatar(from, &url) {
-                                    Ok(_) => println!("[Pingo] Set avatar (file) for {}", from),
-                                    Err(e) => println!("[Pingo] Failed to set avatar: {}", e),
+                                    Ok(_) => info!("Set avatar (file) for {}", from),
+                                    Err(e) => warn!("Failed to set avatar: {}", e),
                                 }
                                 Some(url)
                             } else {
@@ -628,12 +943,9 @@ pub fn start_signaling<R: Runtime>(
                                 );
                                 match db.set_user_avatar(from, &placeholder) {
                                     Ok(_) => {
-                                        println!("[Pingo] Stored avatar placeholder for {}", from)
+                                        info!("Stored avatar placeholder for {}", from)
                                     }
-                                    Err(e) => println!(
-                                        "[Pingo] Failed to store avatar placeholder: {}",
-                                        e
-                                    ),
+                                    Err(e) => warn!("Failed to store avatar placeholder: {}", e),
                                 }
                                 Some(placeholder)
                             }
@@ -652,8 +964,15 @@ pub fn start_signaling<R: Runtime>(
                                 "to": local_device_id,
                                 "username": username,
                                 "avatar_url": resolved_avatar,
+                                "avatar_hash": avatar_hash,
                                 "bio": bio,
                                 "designation": designation,
+                                "email": profile.email,
+                                "phone": profile.phone,
+                                "department": profile.department,
+                                "pronouns": profile.pronouns,
+                                "timezone": profile.timezone,
+                                "status": status,
                             }),
                         );
                     }
@@ -664,24 +983,55 @@ pub fn start_signaling<R: Runtime>(
                         member_ids,
                         member_names,
                         created_at,
+                        kind,
+                        admin_ids,
                         ..
                     } => {
-                        println!("[Pingo] Received group created from {} ({})", from, id);
-                        // Create group locally and add members
-                        let group = Group {
-                            id: id.clone(),
-                            name: name.clone(),
-                            created_by: from.clone(),
-                            avatar_color: None,
-                            created_at: created_at.clone(),
-                        };
-                        match db.create_group(&group) {
-                            Ok(_) => println!("[Pingo] Stored group {}", &id[..8.min(id.len())]),
-                            Err(e) => println!("[Pingo] Failed to store group: {}", e),
+                        info!("Received group created from {} ({})", from, id);
+                        if !trust::accepts_group_invite(&db, from) {
+                            info!("Ignoring group {} from untrusted peer {}", id, from);
+                            continue;
+                        }
+                        // A group we already have is only re-sent by its admins, and only adds
+                        // members: roles are changed with GroupRoleChanged, never here
+                        let existing = db.get_group(id).ok().flatten();
+                        if existing.is_some()
+                            && db.get_group_member_role(id, from).ok().flatten().as_deref()
+                                != Some("admin")
+                        {
+                            info!("Ignoring group {} re-sent by non-admin {}", id, from);
+                            continue;
                         }
+                        let is_new = existing.is_none();
+                        let group = match existing {
+                            Some(group) => group,
+                            None => {
+                                // Create group locally and add members
+                                let group = Group {
+                                    id: id.clone(),
+                                    name: name.clone(),
+                                    created_by: from.clone(),
+                                    avatar_color: None,
+                                    created_at: created_at.clone(),
+                                    kind: channels::validate_kind(kind.as_deref())
+                                        .unwrap_or_else(|_| channels::KIND_GROUP.to_string()),
+                                };
+                                match db.create_group(&group) {
+                                    Ok(_) => info!("Stored group {}", &id[..8.min(id.len())]),
+                                    Err(e) => warn!("Failed to store group: {}", e),
+                                }
+                                group
+                            }
+                        };
                         for (i, uid) in member_ids.iter().enumerate() {
+                            if !is_new && db.get_group_member_role(id, uid).ok().flatten().is_some()
+                            {
+                                continue;
+                            }
                             let uname = member_names.get(i).cloned().unwrap_or_default();
-                            let role = if uid.as_str() == from.as_str() {
+                            let role = if is_new
+                                && (uid.as_str() == from.as_str() || admin_ids.contains(uid))
+                            {
                                 "admin".to_string()
                             } else {
                                 "member".to_string()
@@ -695,9 +1045,10 @@ pub fn start_signaling<R: Runtime>(
                             };
                             match db.add_group_member(&gm) {
                                 Ok(_) => {}
-                                Err(e) => println!("[Pingo] Failed to add group member: {}", e),
+                                Err(e) => warn!("Failed to add group member: {}", e),
                             }
                         }
+                        channels::apply_member_defaults(&db, &group);
                         let _ = app_clone.emit("group-created", &group);
                     }
                     SignalingMessage::GroupChatMessage {
@@ -708,15 +1059,24 @@ pub fn start_signaling<R: Runtime>(
                         message_type,
                         sender_name,
                         timestamp,
+                        lamport,
                         ..
                     } => {
-                        println!(
-                            "[Pingo] Received group chat message from {} in group {}",
+                        debug!(
+                            "Received group chat message from {} in group {}",
                             sender_name,
                             &group_id[..8.min(group_id.len())]
                         );
+                        if !channels::can_post(&db, group_id, from) {
+                            warn!(
+                                "Dropping post in channel {} from non-admin {}",
+                                group_id, from
+                            );
+                            continue;
+                        }
                         // Ensure the peer exists in users table
                         let _ = db.upsert_peer_as_user(&from, &sender_name, None);
+                        let timestamp = &clock_skew::normalize(&db, from, timestamp);
                         // Store as group message
                         let gmsg = GroupMessage {
                             id: id.clone(),
@@ -726,15 +1086,45 @@ pub fn start_signaling<R: Runtime>(
                             content: content.clone(),
                             message_type: message_type.clone(),
                             created_at: timestamp.clone(),
+                            lamport: *lamport,
                         };
                         match db.send_group_message(&gmsg) {
                             Ok(_) => {
-                                println!("[Pingo] Stored group message {}", &id[..8.min(id.len())])
+                                debug!("Stored group message {}", &id[..8.min(id.len())])
                             }
-                            Err(e) => println!("[Pingo] Failed to store group message: {}", e),
+                            Err(e) => warn!("Failed to store group message: {}", e),
+                        }
+                        // Emit separate events for group and channel messages
+                        let event = channels::message_event(&db, group_id);
+                        let _ = app_clone.emit(event, &gmsg);
+                        automation_api::publish(event, &gmsg);
+                        webhooks::group_message_received(&db, &gmsg);
+                        if message_type == "text" {
+                            link_snapshots::queue(
+                                &app_clone.state::<AppState>(),
+                                id,
+                                content,
+                                true,
+                            );
                         }
-                        // Emit separate event for group messages
-                        let _ = app_clone.emit("group-message-received", &gmsg);
+
+                        let group_name = db
+                            .get_groups(&local_device_id)
+                            .ok()
+                            .and_then(|gs| gs.into_iter().find(|g| &g.id == group_id))
+                            .map(|g| g.name)
+                            .unwrap_or_else(|| "Group".to_string());
+                        notifications::notify_incoming(
+                            &app_clone,
+                            &db,
+                            NotificationTarget::Group {
+                                group_id,
+                                group_name: &group_name,
+                            },
+                            sender_name,
+                            message_type,
+                            content,
+                        );
                     }
                     SignalingMessage::MeetingChatMessage {
                         from,
@@ -745,8 +1135,8 @@ pub fn start_signaling<R: Runtime>(
                         timestamp,
                         ..
                     } => {
-                        println!(
-                            "[Pingo] Received meeting chat from {} (session {})",
+                        debug!(
+                            "Received meeting chat from {} (session {})",
                             sender_name,
                             &session_id[..8.min(session_id.len())]
                         );
@@ -763,8 +1153,8 @@ pub fn start_signaling<R: Runtime>(
                         username,
                         ..
                     } => {
-                        println!(
-                            "[Pingo] Group member added: {} to group {}",
+                        info!(
+                            "Group member added: {} to group {}",
                             username,
                             &group_id[..8.min(group_id.len())]
                         );
@@ -780,14 +1170,25 @@ pub fn start_signaling<R: Runtime>(
                             "from": from, "group_id": group_id, "user_id": user_id, "username": username,
                         }));
                     }
+                    SignalingMessage::GroupRoleChanged {
+                        from,
+                        group_id,
+                        user_id,
+                        role,
+                        ..
+                    } => {
+                        channels::handle_role_changed(
+                            &app_clone, &db, from, group_id, user_id, role,
+                        );
+                    }
                     SignalingMessage::GroupMemberRemoved {
                         from,
                         group_id,
                         user_id,
                         ..
                     } => {
-                        println!(
-                            "[Pingo] Group member removed: {} from group {}",
+                        info!(
+                            "Group member removed: {} from group {}",
                             user_id,
                             &group_id[..8.min(group_id.len())]
                         );
@@ -799,6 +1200,138 @@ pub fn start_signaling<R: Runtime>(
                             }),
                         );
                     }
+                    SignalingMessage::LinkRequest { .. }
+                    | SignalingMessage::LinkAccept { .. }
+                    | SignalingMessage::SyncRequest { .. }
+                    | SignalingMessage::SyncBatch { .. }
+                    | SignalingMessage::NoteSync { .. }
+                    | SignalingMessage::SettingsSync { .. } => {
+                        device_sync::handle_message(&app_clone, &msg);
+                    }
+                    SignalingMessage::PairIntroduction { from, payload, .. } => {
+                        pairing::handle_introduction(&app_clone, from, payload);
+                    }
+                    SignalingMessage::NoteShare { from, payload, .. } => {
+                        note_sharing::handle_message(&app_clone, from, payload);
+                    }
+                    SignalingMessage::FileTransferRequest { .. } => {
+                        file_requests::handle_request(&app_clone, &msg);
+                    }
+                    SignalingMessage::FileTransferResponse { .. } => {
+                        file_requests::handle_response(&app_clone, &msg);
+                    }
+                    SignalingMessage::FileChunkData { .. }
+                    | SignalingMessage::FileTransferDone { .. }
+                    | SignalingMessage::FileTransferStatus { .. } => {
+                        file_send::handle_message(&app_clone, &msg);
+                    }
+                    SignalingMessage::PinPairRequest { .. }
+                    | SignalingMessage::PinPairResponse { .. }
+                    | SignalingMessage::PinPairResult { .. } => {
+                        pin_pairing::handle_message(&app_clone, &msg);
+                    }
+                    SignalingMessage::NativeMeetingOffer { .. }
+                    | SignalingMessage::NativeMeetingAnswer { .. }
+                    | SignalingMessage::NativeMeetingCandidate { .. }
+                    | SignalingMessage::NativeMeetingLeave { .. } => {
+                        meeting::handle_signal(&app_clone, msg.clone());
+                    }
+                    SignalingMessage::MeetingInvite { .. }
+                    | SignalingMessage::MeetingInviteResponse { .. }
+                    | SignalingMessage::MeetingRejoinRequest { .. }
+                    | SignalingMessage::MeetingJoinRequest { .. }
+                    | SignalingMessage::MeetingJoinDecision { .. }
+                    | SignalingMessage::MeetingParticipantList { .. }
+                    | SignalingMessage::MeetingLeave { .. }
+                    | SignalingMessage::MeetingEnded { .. } => {
+                        meeting_history::record(&db, &local_device_id, &msg, false);
+                        let rosters = &app_clone.state::<AppState>().meeting_rosters;
+                        let reply = rosters.apply(&local_device_id, &msg, false);
+                        if let Some(SignalingMessage::MeetingParticipantList { to, .. }) = &reply {
+                            let _ = signaling.send_message(to, reply.as_ref().unwrap());
+                        }
+                        if let SignalingMessage::MeetingParticipantList {
+                            from, meeting_id, ..
+                        } = &msg
+                        {
+                            meeting::on_participant_list(&app_clone, from, meeting_id);
+                        }
+                        if let SignalingMessage::MeetingJoinRequest {
+                            from,
+                            meeting_id,
+                            username,
+                            ..
+                        } = &msg
+                        {
+                            if rosters.is_waiting(meeting_id, from) {
+                                let _ = app_clone.emit(
+                                    "meeting-join-request",
+                                    serde_json::json!({
+                                        "meeting_id": meeting_id, "peer_id": from, "username": username,
+                                    }),
+                                );
+                            }
+                        }
+                        let _ = app_clone.emit("signaling-message", &msg);
+                    }
+                    SignalingMessage::MeetingChat {
+                        from,
+                        meeting_id,
+                        chat,
+                        ..
+                    } => {
+                        meeting_recording::record_chat(meeting_id, from, chat);
+                        let _ = app_clone.emit("signaling-message", &msg);
+                    }
+                    SignalingMessage::ScheduledMeetingInvite { .. }
+                    | SignalingMessage::ScheduledMeetingInviteAck { .. } => {
+                        meeting_schedule::handle_message(&app_clone, &msg);
+                    }
+                    SignalingMessage::ConversationCleared { .. } => {
+                        conversation_clear::handle_message(&app_clone, &msg);
+                    }
+                    SignalingMessage::MessageDeleted {
+                        from, message_id, ..
+                    } => {
+                        deleted_messages::handle_tombstone(&app_clone, &db, from, message_id);
+                    }
+                    SignalingMessage::WhiteboardOp {
+                        from,
+                        meeting_id,
+                        op,
+                        ..
+                    } => {
+                        whiteboard::handle_message(&app_clone, from, meeting_id, op);
+                    }
+                    SignalingMessage::RemoteControlRequest { .. }
+                    | SignalingMessage::RemoteControlResponse { .. }
+                    | SignalingMessage::RemoteInput { .. }
+                    | SignalingMessage::RemoteControlEnd { .. } => {
+                        remote_control::handle_message(&app_clone, &msg);
+                    }
+                    SignalingMessage::MeetingRecording {
+                        from,
+                        meeting_id,
+                        recording,
+                        ..
+                    } => {
+                        info!("{} recording meeting {}: {}", from, meeting_id, recording);
+                        let _ = app_clone.emit(
+                            "meeting-recording-state",
+                            serde_json::json!({
+                                "meeting_id": meeting_id, "from": from, "recording": recording,
+                            }),
+                        );
+                    }
+                    SignalingMessage::Ping { from, timestamp } => {
+                        let pong = SignalingMessage::Pong {
+                            from: local_device_id.clone(),
+                            timestamp: *timestamp,
+                            file_port: Some(file_server.get_port()).filter(|p| *p != 0),
+                            clock: Some(clock_ms()),
+                        };
+                        let _ = signaling.send_message(from, &pong);
+                    }
                     _ => {
                         let _ = app_clone.emit("signaling-message", &msg);
                     }
@@ -821,12 +1354,53 @@ pub fn register_peer(
     state.signaling.register_peer(&peer_id, &ip, port)
 }
 
+/// Protocol version and features a peer reported; None for peers that predate the handshake
+#[tauri::command]
+pub fn get_peer_capabilities(state: State<AppState>, peer_id: String) -> Option<PeerCapabilities> {
+    state.signaling.peer_capabilities(&peer_id)
+}
+
 #[tauri::command]
 pub fn send_signaling_message(
     state: State<AppState>,
     peer_id: String,
-    message: SignalingMessage,
+    mut message: SignalingMessage,
 ) -> Result<(), String> {
+    state
+        .meeting_rosters
+        .check_outgoing(&state.device_id(), &message)?;
+    // Profile updates carry the directory fields and status we have on record
+    if let SignalingMessage::ProfileUpdate {
+        profile, status, ..
+    } = &mut message
+    {
+        if let Ok(Some(me)) = state.db.get_user(&state.device_id()) {
+            *profile = me.profile;
+        }
+        *status = state.db.get_user_status(&state.device_id()).ok().flatten();
+    }
+    // Profile updates pointing peers at our file server carry our avatar's version
+    if let SignalingMessage::ProfileUpdate {
+        avatar_file_id: Some(_),
+        avatar_hash: avatar_hash @ None,
+        ..
+    } = &mut message
+    {
+        *avatar_hash = avatar::local_hash(&state.file_server, &state.device_id());
+    }
+    if let SignalingMessage::MeetingChat {
+        from,
+        meeting_id,
+        chat,
+        ..
+    } = &message
+    {
+        meeting_recording::record_chat(meeting_id, from, chat);
+    }
+    meeting_history::record(&state.db, &state.device_id(), &message, true);
+    state
+        .meeting_rosters
+        .apply(&state.device_id(), &message, true);
     state.signaling.send_message(&peer_id, &message)
 }
 
@@ -846,8 +1420,11 @@ pub fn encrypt_message(
     state: State<AppState>,
     peer_id: String,
     message: String,
+    message_id: Option<String>,
 ) -> Result<EncryptedEnvelope, String> {
-    state.crypto.encrypt_message(&peer_id, &message)
+    state
+        .crypto
+        .encrypt_message(&peer_id, message_id.as_deref(), &message)
 }
 
 #[tauri::command]
@@ -855,8 +1432,11 @@ pub fn decrypt_message(
     state: State<AppState>,
     peer_id: String,
     envelope: EncryptedEnvelope,
+    message_id: Option<String>,
 ) -> Result<String, String> {
-    state.crypto.decrypt_message(&peer_id, &envelope)
+    state
+        .crypto
+        .decrypt_message(&peer_id, &envelope, message_id.as_deref())
 }
 
 #[tauri::command]
@@ -864,6 +1444,25 @@ pub fn get_public_key(state: State<AppState>) -> Option<String> {
     state.crypto.get_public_key()
 }
 
+/// Long-term identity key (the session key from get_public_key rotates)
+#[tauri::command]
+pub fn get_identity_key(state: State<AppState>) -> Option<String> {
+    state.crypto.get_identity_key()
+}
+
+/// Accept a new identity key from a device that was reinstalled; the next one it announces is
+/// pinned in place of the old one
+#[tauri::command]
+pub fn forget_identity_pin(state: State<AppState>, peer_id: String) -> Result<(), String> {
+    state
+        .db
+        .unpin_identity(&peer_id)
+        .map_err(|e| e.to_string())?;
+    state.discovery.forget_identity_pin(&peer_id);
+    info!("Forgot the identity key of {}", peer_id);
+    Ok(())
+}
+
 // ============ FILE TRANSFER COMMANDS ============
 
 #[tauri::command]
@@ -878,12 +1477,45 @@ pub fn prepare_file_send(
 }
 
 #[tauri::command]
-pub fn prepare_file_receive(
+pub fn prepare_file_receive<R: Runtime>(
+    app: AppHandle<R>,
     state: State<AppState>,
     metadata: FileMetadata,
 ) -> Result<String, String> {
-    let path = state.file_transfer.prepare_receive(&metadata)?;
-    Ok(path.to_string_lossy().to_string())
+    if !file_requests::is_accepted(&metadata.transfer_id) {
+        return Err("This file transfer hasn't been accepted".to_string());
+    }
+    begin_receive(&app, &state, &metadata)
+}
+
+/// Reserve space and a download record for an accepted incoming transfer
+pub(crate) fn begin_receive<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    metadata: &FileMetadata,
+) -> Result<String, String> {
+    disk_space::ensure_space(
+        app,
+        &state.db,
+        &state.file_transfer.get_downloads_dir(),
+        metadata.file_size,
+        &metadata.file_name,
+    )?;
+    let path = state.file_transfer.prepare_receive(metadata)?;
+    let path = path.to_string_lossy().to_string();
+    downloads::begin(
+        &state.db,
+        Some(&metadata.transfer_id),
+        downloads::Source {
+            kind: "transfer",
+            file_name: &metadata.file_name,
+            file_type: &metadata.file_type,
+            path: Some(&path),
+            size: metadata.file_size as i64,
+            ..Default::default()
+        },
+    );
+    Ok(path)
 }
 
 #[tauri::command]
@@ -897,9 +1529,12 @@ pub fn get_file_chunk(
 
 #[tauri::command]
 pub fn receive_file_chunk(state: State<AppState>, chunk: FileChunk) -> Result<bool, String> {
-    Ok(state.file_transfer.receive_chunk(&chunk)?.success)
+    // Progress reaches the UI through the transfer manager's listener
+    let ack = state.file_transfer.receive_chunk(&chunk)?;
+    Ok(ack.success)
 }
 
+/// Polling fallback: progress is pushed as "backend-event" { kind: "transfer_progress" }
 #[tauri::command]
 pub fn get_transfer_progress(
     state: State<AppState>,
@@ -914,13 +1549,53 @@ pub fn get_missing_chunks(state: State<AppState>, transfer_id: String) -> Vec<u3
 }
 
 #[tauri::command]
-pub fn complete_transfer(state: State<AppState>, transfer_id: String) -> Result<bool, String> {
-    Ok(state.file_transfer.complete_transfer(&transfer_id)?.success)
+pub fn complete_transfer<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    transfer_id: String,
+) -> Result<bool, String> {
+    finish_receive(&app, &state, &transfer_id)
+}
+
+/// Verify a fully received transfer and scan it; the download record follows the outcome
+pub(crate) fn finish_receive<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    transfer_id: &str,
+) -> Result<bool, String> {
+    let done = state.file_transfer.complete_transfer(transfer_id)?;
+    if !done.success {
+        let _ =
+            state
+                .db
+                .update_download(transfer_id, "failed", None, None, Some("Checksum mismatch"));
+        return Ok(false);
+    }
+    // Chunked files are written straight into the downloads folder; scan them there
+    let record = state.db.get_download(transfer_id).ok().flatten();
+    if let Some((path, name)) = record.and_then(|r| Some((r.path?, r.file_name))) {
+        if let Err(e) = scan::check(app, &state.db, Path::new(&path), &name, None) {
+            let _ = state
+                .db
+                .update_download(transfer_id, "quarantined", None, None, Some(&e));
+            return Ok(false);
+        }
+    }
+    let _ = state
+        .db
+        .update_download(transfer_id, "complete", None, None, None);
+    transcription::queue(app, &state.db, transfer_id);
+    ocr::queue_download(app, &state.db, transfer_id);
+    Ok(true)
 }
 
 #[tauri::command]
 pub fn cancel_transfer(state: State<AppState>, transfer_id: String) -> Result<(), String> {
-    state.file_transfer.cancel_transfer(&transfer_id)
+    state.file_transfer.cancel_transfer(&transfer_id)?;
+    let _ = state
+        .db
+        .update_download(&transfer_id, "cancelled", None, None, None);
+    Ok(())
 }
 
 // ============ SETTINGS COMMANDS ============
@@ -930,7 +1605,9 @@ pub fn set_setting(state: State<AppState>, key: String, value: String) -> Result
     state
         .db
         .set_setting(&key, &value)
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    device_sync::push_settings(&state, &[key.as_str()]);
+    Ok(())
 }
 
 #[tauri::command]
@@ -976,7 +1653,7 @@ pub fn show_window<R: Runtime>(app: AppHandle<R>) -> Result<(), String> {
 
 #[tauri::command]
 pub fn get_device_id(state: State<AppState>) -> String {
-    state.device_id.clone()
+    state.device_id()
 }
 
 #[tauri::command]
@@ -1030,9 +1707,13 @@ pub fn restart_discovery(
         .crypto
         .get_public_key()
         .ok_or("Public key not initialized")?;
-    state
-        .discovery
-        .start(state.device_id.clone(), username, port, pk)?;
+    state.discovery.start(
+        state.device_id(),
+        username,
+        port,
+        pk,
+        state.crypto.identity_proof(),
+    )?;
     Ok(())
 }
 
@@ -1046,18 +1727,38 @@ pub fn relay_chat_message(
     message_type: Option<String>,
     sender_name: String,
 ) -> Result<(), String> {
+    pin_pairing::check_outgoing(&state, &peer_id)?;
+    // The stamp send_message gave it
+    let lamport = state
+        .db
+        .get_message(&message_id)
+        .ok()
+        .flatten()
+        .map_or(0, |m| m.lamport);
     let signaling_msg = SignalingMessage::ChatMessage {
-        from: state.device_id.clone(),
+        from: state.device_id(),
         to: peer_id.clone(),
-        id: message_id,
+        id: message_id.clone(),
         content,
         message_type: message_type.unwrap_or_else(|| "text".into()),
         sender_name,
         timestamp: now(),
+        lamport,
+        auto_reply: false,
     };
 
-    // Try send; on Peer-not-found auto-register from discovery and retry
-    match state.signaling.send_message(&peer_id, &signaling_msg) {
+    delivery_status::track_send(&state, &message_id, || {
+        send_to_peer(&state, &peer_id, &signaling_msg)
+    })
+}
+
+/// Send a signaling message; on Peer-not-found auto-register from discovery and retry
+pub(crate) fn send_to_peer(
+    state: &AppState,
+    peer_id: &str,
+    msg: &SignalingMessage,
+) -> Result<(), String> {
+    match state.signaling.send_message(peer_id, msg) {
         Ok(()) => Ok(()),
         Err(ref e) if e.contains("not found") || e.contains("Not found") => {
             // Look up peer in discovery manager
@@ -1065,8 +1766,8 @@ pub fn relay_chat_message(
             if let Some(p) = peers.iter().find(|p| p.device_id == peer_id) {
                 state
                     .signaling
-                    .register_peer(&peer_id, &p.ip_address, p.port)?;
-                state.signaling.send_message(&peer_id, &signaling_msg)
+                    .register_peer(peer_id, &p.ip_address, p.port)?;
+                state.signaling.send_message(peer_id, msg)
             } else {
                 Err(format!(
                     "Peer {} not found in signaling or discovery",
@@ -1078,16 +1779,109 @@ pub fn relay_chat_message(
     }
 }
 
+/// Store an outgoing direct message and relay it to the peer. The message stays undelivered
+/// in the DB and is resent by the offline queue if the relay fails. `auto_reply` marks replies
+/// sent by an auto-reply rule, which the peer's auto-responder won't answer.
+pub(crate) fn send_direct_message(
+    state: &AppState,
+    peer_id: &str,
+    content: String,
+    message_type: &str,
+    auto_reply: bool,
+) -> Result<Message, String> {
+    pin_pairing::check_outgoing(state, peer_id)?;
+    let sender_name = state
+        .db
+        .get_user(&state.device_id())
+        .map_err(|e| e.to_string())?
+        .map(|u| u.username)
+        .unwrap_or_default();
+
+    let message = Message {
+        id: generate_id(),
+        sender_id: state.device_id(),
+        receiver_id: peer_id.to_string(),
+        content,
+        message_type: message_type.to_string(),
+        file_path: None,
+        is_read: false,
+        is_delivered: false,
+        created_at: now(),
+        lamport: state.db.next_lamport().map_err(|e| e.to_string())?,
+        status: MessageStatus::Queued,
+    };
+    state
+        .db
+        .create_message(&message)
+        .map_err(|e| e.to_string())?;
+
+    let signaling_msg = SignalingMessage::ChatMessage {
+        from: state.device_id(),
+        to: peer_id.to_string(),
+        id: message.id.clone(),
+        content: message.content.clone(),
+        message_type: message.message_type.clone(),
+        sender_name,
+        timestamp: message.created_at.clone(),
+        lamport: message.lamport,
+        auto_reply,
+    };
+    if let Err(e) = delivery_status::track_send(state, &message.id, || {
+        send_to_peer(state, peer_id, &signaling_msg)
+    }) {
+        warn!("Message to {} not sent: {}", peer_id, e);
+    }
+    Ok(message)
+}
+
+/// Send a reply from the quick reply popup: store it, relay it, and close the popup.
+/// Emits "chat-message-sent" so the main window can append the message to the open chat.
+#[tauri::command]
+pub fn send_quick_reply<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+    content: String,
+) -> Result<Message, String> {
+    if content.trim().is_empty() {
+        return Err("Reply is empty".to_string());
+    }
+    let message = send_direct_message(&state, &peer_id, content, "text", false)?;
+
+    let _ = app.emit("chat-message-sent", &message);
+    window_manager::route_to_chat_window(&app, &state.chat_windows, &peer_id, &message);
+    window_manager::close_quick_reply(&app);
+    Ok(message)
+}
+
+/// Process an uploaded avatar (base64 or data URL): crop to a square, shrink to 256px and
+/// store it as a PNG on the file server. Returns the local URL now saved as our avatar.
 #[tauri::command]
 pub fn save_avatar(state: State<AppState>, image_data: String) -> Result<String, String> {
     let mut user = state
         .db
-        .get_user(&state.device_id)
+        .get_user(&state.device_id())
         .map_err(|e| e.to_string())?
         .ok_or("User not found")?;
-    user.avatar_path = Some(image_data.clone());
+    let png = avatar::process(&avatar::decode_image_data(&image_data)?)?;
+    let file_id = avatar::file_id(&user.id);
+    state
+        .file_server
+        .store_bytes(&file_id, &png, "avatar.png", "image/png")?;
+    // Peers are pointed at it with a ProfileUpdate, so it's served on the LAN as is
+    state.file_server.publish(&file_id);
+    let url = state.file_server.local_url(&file_id);
+    user.avatar_path = Some(url.clone());
     state.db.create_user(&user).map_err(|e| e.to_string())?;
-    Ok(image_data)
+    // New version: announced in discovery so peers know their cached copy is stale
+    let hash = avatar::content_hash(&png);
+    state
+        .db
+        .set_user_avatar_hash(&user.id, &hash)
+        .map_err(|e| e.to_string())?;
+    state.discovery.set_avatar_hash(Some(hash));
+    info!("Saved avatar ({} bytes)", png.len());
+    Ok(url)
 }
 
 /// Download an avatar from remote URL and cache locally in Documents/Pingo/avatars/
@@ -1097,51 +1891,40 @@ pub fn save_avatar(state: State<AppState>, image_data: String) -> Result<String,
 /// - Fixes "avatar disappears" bug by storing avatars locally
 /// - Prevents presence updates from overwriting profile data
 /// - Enables offline rendering from local filesystem
+/// - With `avatar_hash`, skips the download when the cached copy is that version
 #[tauri::command]
 pub fn download_and_cache_avatar(
     state: State<AppState>,
     device_id: String,
     remote_url: String,
     _hint_name: Option<String>,
+    avatar_hash: Option<String>,
 ) -> Result<String, String> {
     if device_id.is_empty() || remote_url.is_empty() {
         return Err("device_id and remote_url required".to_string());
     }
 
-    // Create avatars directory: Documents/Pingo/avatars/
-    // Uses standard Windows/Mac/Linux locations
-    let avatars_path = if cfg!(target_os = "windows") {
-        let docs = std::env::var("USERPROFILE")
-            .map(|p| std::path::PathBuf::from(p).join("Documents"))
-            .unwrap_or_else(|_| std::path::PathBuf::from("."));
-        docs.join("Pingo").join("avatars")
-    } else if cfg!(target_os = "macos") {
-        let home = std::env::var("HOME").unwrap_or_default();
-        std::path::PathBuf::from(home).join("Documents/Pingo/avatars")
-    } else {
-        let home = std::env::var("HOME").unwrap_or_default();
-        std::path::PathBuf::from(home).join(".local/share/Pingo/avatars")
-    };
-
+    let avatars_path = avatar::cache_dir();
     std::fs::create_dir_all(&avatars_path)
         .map_err(|e| format!("Failed to create avatars dir: {}", e))?;
 
-    // Generate stable filename: user_<device_id>.png
-    // This ensures same device_id always overwrites same file (safe update when avatar changes)
-    let filename = format!("user_{}.png", device_id);
-    let file_path = avatars_path.join(&filename);
-
-    // Check if this is a local file server URL (e.g., from previous app run with different port)
-    // If so, and the file exists, just register it instead of re-downloading
-    if remote_url.starts_with("http://127.0.0.1:") || remote_url.starts_with("http://localhost:") {
-        if file_path.exists() {
-            // File exists locally, just register it with the file server
-            let file_id = format!("avatar_{}", device_id);
-            state
-                .file_server
-                .register_file(&file_id, &file_path, &filename);
-            let port = state.file_server.get_port();
-            let file_url = format!("http://127.0.0.1:{}/file/{}", port, file_id);
+    // Same version as the cached copy: no need to fetch it again from whatever port
+    // the peer's file server is on today
+    let stored_hash = state
+        .db
+        .get_user_avatar_hash(&device_id)
+        .map_err(|e| e.to_string())?;
+    if avatar_hash.is_some() && avatar_hash == stored_hash {
+        if let Some(file_url) = avatar::cached_url(&state.file_server, &device_id) {
+            let _ = state.db.set_user_avatar(&device_id, &file_url);
+            return Ok(file_url);
+        }
+    }
+
+    // Check if this is a local file server URL (e.g., from previous app run with different port)
+    // If so, and the file exists, just register it instead of re-downloading
+    if remote_url.starts_with("http://127.0.0.1:") || remote_url.starts_with("http://localhost:") {
+        if let Some(file_url) = avatar::cached_url(&state.file_server, &device_id) {
             return Ok(file_url);
         }
     }
@@ -1151,27 +1934,28 @@ pub fn download_and_cache_avatar(
     if bytes.is_empty() {
         return Err("Downloaded empty avatar".to_string());
     }
+    let hash = avatar::content_hash(&bytes);
 
     // Write to local file (overwrites if exists — required for avatar updates)
+    let file_path = avatar::cache_path(&device_id);
     std::fs::write(&file_path, bytes).map_err(|e| format!("Failed to write avatar: {}", e))?;
 
     // Register avatar with local file server and return an HTTP URL the UI can load (127.0.0.1)
-    let file_id = format!("avatar_{}", device_id);
-    state
-        .file_server
-        .register_file(&file_id, &file_path, &filename);
-    let port = state.file_server.get_port();
-    let file_url = format!("http://127.0.0.1:{}/file/{}", port, file_id);
+    let file_url =
+        avatar::cached_url(&state.file_server, &device_id).ok_or("Cached avatar disappeared")?;
 
     // Update database to store local file server URL instead of a file:// URL
     match state.db.set_user_avatar(&device_id, &file_url) {
-        Ok(_) => println!(
-            "[Pingo] Cached avatar for {} at {} (served as {})",
+        Ok(_) => info!(
+            "Cached avatar for {} at {} (served as {})",
             device_id,
             file_path.display(),
             file_url
         ),
-        Err(e) => println!("[Pingo] Warning: failed to update avatar in DB: {}", e),
+        Err(e) => warn!("failed to update avatar in DB: {}", e),
+    }
+    if let Err(e) = state.db.set_user_avatar_hash(&device_id, &hash) {
+        warn!("failed to store avatar hash: {}", e);
     }
 
     Ok(file_url)
@@ -1207,41 +1991,63 @@ pub fn register_local_avatar(
     state
         .file_server
         .register_file(&file_id, &path_buf, &filename);
-    let port = state.file_server.get_port();
-    let local_url = format!("http://127.0.0.1:{}/file/{}", port, file_id);
+    if device_id == state.device_id() {
+        state.file_server.publish(&file_id);
+    }
+    let local_url = state.file_server.local_url(&file_id);
 
     // Persist the new URL in DB
     match state.db.set_user_avatar(&device_id, &local_url) {
-        Ok(_) => println!(
-            "[Pingo] Registered local avatar for {} as {}",
-            device_id, local_url
-        ),
-        Err(e) => println!("[Pingo] Warning: failed to update avatar in DB: {}", e),
+        Ok(_) => info!("Registered local avatar for {} as {}", device_id, local_url),
+        Err(e) => warn!("failed to update avatar in DB: {}", e),
     }
 
     Ok(local_url)
 }
 
+/// A page of the media shared with a peer, newest first. Pass the created_at of the last
+/// item as `filter.before` to load the next page.
 #[tauri::command]
 pub fn get_shared_media(
     state: State<AppState>,
     peer_id: String,
     media_type: Option<String>,
+    filter: Option<SharedMediaFilter>,
 ) -> Result<Vec<Message>, String> {
+    let mut filter = filter.unwrap_or_default();
+    filter.media_type = filter.media_type.or(media_type);
     state
         .db
-        .get_shared_media(&state.device_id, &peer_id, media_type.as_deref())
+        .get_shared_media(&state.device_id(), &peer_id, &filter)
         .map_err(|e| e.to_string())
 }
 
+/// How many media items of each type match the filter, for the gallery tabs
 #[tauri::command]
-pub fn get_users_with_messages(state: State<AppState>) -> Result<Vec<User>, String> {
+pub fn get_shared_media_summary(
+    state: State<AppState>,
+    peer_id: String,
+    filter: Option<SharedMediaFilter>,
+) -> Result<Vec<SharedMediaCount>, String> {
     state
         .db
-        .get_users_with_messages(&state.device_id)
+        .get_shared_media_counts(&state.device_id(), &peer_id, &filter.unwrap_or_default())
         .map_err(|e| e.to_string())
 }
 
+/// Contacts with a conversation; only those tagged `tag` when one is given
+#[tauri::command]
+pub fn get_users_with_messages(
+    state: State<AppState>,
+    tag: Option<String>,
+) -> Result<Vec<User>, String> {
+    let users = state
+        .db
+        .get_users_with_messages(&state.device_id())
+        .map_err(|e| e.to_string())?;
+    contact_tags::filter_users(&state.db, users, tag.as_deref())
+}
+
 // ============ NOTES COMMANDS ============
 
 #[derive(Deserialize)]
@@ -1253,38 +2059,325 @@ pub struct SaveNoteInput {
     pub pinned: Option<bool>,
     pub category: Option<String>,
     pub created_at: Option<String>,
+    /// Required to edit a locked note
+    pub passphrase: Option<String>,
+}
+
+/// What the UI sees of a locked note until it's unlocked
+fn note_placeholder(note: Note) -> Note {
+    if note.locked {
+        Note {
+            content: String::new(),
+            ..note
+        }
+    } else {
+        note
+    }
+}
+
+fn locked_content(note: &Note, passphrase: &str) -> Result<String, String> {
+    let envelope: PassphraseEnvelope =
+        serde_json::from_str(&note.content).map_err(|e| e.to_string())?;
+    decrypt_with_passphrase(&envelope, passphrase)
+}
+
+fn lock_content(content: &str, passphrase: &str) -> Result<String, String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase can't be empty".to_string());
+    }
+    serde_json::to_string(&encrypt_with_passphrase(content, passphrase)?).map_err(|e| e.to_string())
+}
+
+fn get_note_or_err(state: &AppState, id: &str) -> Result<Note, String> {
+    state
+        .db
+        .get_note(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Note not found".to_string())
+}
+
+/// Store a note and send it to linked devices and collaborators
+fn store_note(state: &AppState, note: Note, base: Option<String>) -> Result<Note, String> {
+    state.db.save_note(&note).map_err(|e| e.to_string())?;
+    // Re-read for the sharing state save_note leaves untouched
+    let note = state
+        .db
+        .get_note(&note.id)
+        .map_err(|e| e.to_string())?
+        .unwrap_or(note);
+    device_sync::push_notes(state, vec![note.clone()], Vec::new());
+    note_sharing::propagate(state, &note, base);
+    Ok(note_placeholder(note))
 }
 
 #[tauri::command]
 pub fn save_note(state: State<AppState>, input: SaveNoteInput) -> Result<Note, String> {
     let ts = now();
+    let existing = state.db.get_note(&input.id).map_err(|e| e.to_string())?;
+    let locked = existing.as_ref().is_some_and(|n| n.locked);
+    let mut content = input.content.unwrap_or_default();
+    if let Some(existing) = existing.as_ref().filter(|n| n.locked) {
+        let passphrase = input
+            .passphrase
+            .as_deref()
+            .ok_or("This note is locked; enter its passphrase to edit it")?;
+        locked_content(existing, passphrase)?;
+        content = lock_content(&content, passphrase)?;
+    }
     let note = Note {
         id: input.id,
         title: input.title,
-        content: input.content.unwrap_or_default(),
+        content,
         color: input.color.unwrap_or_else(|| "#fef3c7".into()),
         pinned: input.pinned.unwrap_or(false),
         category: input.category,
         created_at: input.created_at.unwrap_or_else(|| ts.clone()),
         updated_at: ts,
+        shared_by: None,
+        collaborators: Vec::new(),
+        locked,
     };
-    state.db.save_note(&note).map_err(|e| e.to_string())?;
-    Ok(note)
+    store_note(&state, note, existing.map(|n| n.updated_at))
 }
 
+/// Locked notes come back with empty content; use unlock_note to read them
 #[tauri::command]
 pub fn get_all_notes(state: State<AppState>) -> Result<Vec<Note>, String> {
-    state.db.get_all_notes().map_err(|e| e.to_string())
+    Ok(state
+        .db
+        .get_all_notes()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(note_placeholder)
+        .collect())
+}
+
+/// Encrypt a note's content with a passphrase (Argon2id key, AES-256-GCM)
+#[tauri::command]
+pub fn lock_note(
+    state: State<AppState>,
+    note_id: String,
+    passphrase: String,
+) -> Result<Note, String> {
+    let note = get_note_or_err(&state, &note_id)?;
+    if note.locked {
+        return Err("Note is already locked".to_string());
+    }
+    if note.shared_by.is_some() || !note.collaborators.is_empty() {
+        return Err("Shared notes can't be locked".to_string());
+    }
+    let base = Some(note.updated_at.clone());
+    let note = Note {
+        content: lock_content(&note.content, &passphrase)?,
+        locked: true,
+        updated_at: now(),
+        ..note
+    };
+    store_note(&state, note, base)
+}
+
+/// Decrypt a locked note for viewing; it stays locked in storage
+#[tauri::command]
+pub fn unlock_note(
+    state: State<AppState>,
+    note_id: String,
+    passphrase: String,
+) -> Result<Note, String> {
+    let note = get_note_or_err(&state, &note_id)?;
+    if !note.locked {
+        return Ok(note);
+    }
+    Ok(Note {
+        content: locked_content(&note, &passphrase)?,
+        ..note
+    })
+}
+
+/// Decrypt a locked note and store it as a normal note again
+#[tauri::command]
+pub fn remove_note_lock(
+    state: State<AppState>,
+    note_id: String,
+    passphrase: String,
+) -> Result<Note, String> {
+    let note = get_note_or_err(&state, &note_id)?;
+    if !note.locked {
+        return Ok(note);
+    }
+    let base = Some(note.updated_at.clone());
+    let note = Note {
+        content: locked_content(&note, &passphrase)?,
+        locked: false,
+        updated_at: now(),
+        ..note
+    };
+    store_note(&state, note, base)
 }
 
 #[tauri::command]
 pub fn delete_note(state: State<AppState>, id: String) -> Result<(), String> {
-    state.db.delete_note(&id).map_err(|e| e.to_string())
+    remove_note_attachments(&state.db, &id);
+    let tombstone = state.db.delete_note(&id).map_err(|e| e.to_string())?;
+    device_sync::push_notes(&state, Vec::new(), vec![tombstone]);
+    Ok(())
 }
 
 #[tauri::command]
 pub fn toggle_note_pin(state: State<AppState>, id: String) -> Result<(), String> {
-    state.db.toggle_note_pin(&id).map_err(|e| e.to_string())
+    let base = state
+        .db
+        .get_note(&id)
+        .map_err(|e| e.to_string())?
+        .map(|n| n.updated_at);
+    state.db.toggle_note_pin(&id).map_err(|e| e.to_string())?;
+    if let Some(note) = state.db.get_note(&id).map_err(|e| e.to_string())? {
+        device_sync::push_notes(&state, vec![note.clone()], Vec::new());
+        note_sharing::propagate(&state, &note, base);
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct NoteAttachmentInfo {
+    #[serde(flatten)]
+    pub attachment: NoteAttachment,
+    /// Local file server URL the UI can load
+    pub url: String,
+}
+
+fn note_attachment_file_id(attachment_id: &str) -> String {
+    format!("note_att_{}", attachment_id)
+}
+
+/// Peers are told to fetch our avatar from the file server, so it goes out without a token.
+/// A saved URL from before the localhost listener points at the LAN port; move it over.
+fn publish_own_avatar(state: &AppState) {
+    let file_id = avatar::file_id(&state.device_id());
+    state.file_server.publish(&file_id);
+    let Ok(Some(me)) = state.db.get_user(&state.device_id()) else {
+        return;
+    };
+    let local_url = state.file_server.local_url(&file_id);
+    let stale = me.avatar_path.as_deref().is_some_and(|path| {
+        path.starts_with("http://127.0.0.1:") && path.ends_with(&file_id) && path != local_url
+    });
+    if stale {
+        let _ = state.db.set_user_avatar(&me.id, &local_url);
+    }
+}
+
+fn note_attachments_dir() -> std::path::PathBuf {
+    profiles::app_dir(&profiles::active_profile()).join("note_attachments")
+}
+
+fn note_attachment_info(state: &AppState, attachment: NoteAttachment) -> NoteAttachmentInfo {
+    let url = state
+        .file_server
+        .local_url(&note_attachment_file_id(&attachment.id));
+    NoteAttachmentInfo { attachment, url }
+}
+
+/// Serve every stored note attachment (registrations don't survive a restart or profile switch)
+fn register_note_attachments(state: &AppState) {
+    for a in state.db.get_note_attachments(None).unwrap_or_default() {
+        state.file_server.register_file(
+            &note_attachment_file_id(&a.id),
+            std::path::Path::new(&a.file_path),
+            &a.file_name,
+        );
+    }
+}
+
+/// Delete a note's attachment files and rows (the note itself is removed by the caller)
+pub(crate) fn remove_note_attachments(db: &Database, note_id: &str) {
+    for a in db.get_note_attachments(Some(note_id)).unwrap_or_default() {
+        if let Err(e) = std::fs::remove_file(&a.file_path) {
+            warn!("Removing note attachment {}: {}", a.file_path, e);
+        }
+        let _ = db.delete_note_attachment(&a.id);
+    }
+}
+
+/// Copy a file into app storage, attach it to a note and serve it locally
+#[tauri::command]
+pub fn add_note_attachment(
+    state: State<AppState>,
+    note_id: String,
+    file_path: String,
+) -> Result<NoteAttachmentInfo, String> {
+    get_note_or_err(&state, &note_id)?;
+    let source = std::path::PathBuf::from(&file_path);
+    let meta =
+        std::fs::metadata(&source).map_err(|e| format!("Can't read {}: {}", file_path, e))?;
+    if !meta.is_file() {
+        return Err(format!("Not a file: {}", file_path));
+    }
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "attachment".to_string());
+
+    let id = generate_id();
+    let dir = note_attachments_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // Keep the extension so the file server can infer the MIME type
+    let stored_name = match source.extension() {
+        Some(ext) => format!("{}.{}", id, ext.to_string_lossy()),
+        None => id.clone(),
+    };
+    let dest = dir.join(stored_name);
+    std::fs::copy(&source, &dest).map_err(|e| format!("Copy attachment: {}", e))?;
+
+    let attachment = NoteAttachment {
+        id,
+        note_id,
+        mime_type: file_server::guess_mime(&file_name),
+        file_name,
+        file_path: dest.to_string_lossy().to_string(),
+        size: meta.len() as i64,
+        created_at: now(),
+    };
+    if let Err(e) = state.db.add_note_attachment(&attachment) {
+        let _ = std::fs::remove_file(&dest);
+        return Err(e.to_string());
+    }
+    state.file_server.register_file(
+        &note_attachment_file_id(&attachment.id),
+        &dest,
+        &attachment.file_name,
+    );
+    Ok(note_attachment_info(&state, attachment))
+}
+
+#[tauri::command]
+pub fn get_note_attachments(
+    state: State<AppState>,
+    note_id: String,
+) -> Result<Vec<NoteAttachmentInfo>, String> {
+    Ok(state
+        .db
+        .get_note_attachments(Some(&note_id))
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|a| note_attachment_info(&state, a))
+        .collect())
+}
+
+#[tauri::command]
+pub fn remove_note_attachment(state: State<AppState>, attachment_id: String) -> Result<(), String> {
+    let attachment = state
+        .db
+        .get_note_attachments(None)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|a| a.id == attachment_id)
+        .ok_or("Attachment not found")?;
+    state
+        .db
+        .delete_note_attachment(&attachment.id)
+        .map_err(|e| e.to_string())?;
+    let _ = std::fs::remove_file(&attachment.file_path);
+    Ok(())
 }
 
 // ============ GROUP COMMANDS ============
@@ -1294,6 +2387,9 @@ pub struct CreateGroupInput {
     pub name: String,
     pub member_ids: Vec<String>,
     pub member_names: Vec<String>,
+    /// "group" (default) or "channel" (only admins post, see channels)
+    #[serde(default)]
+    pub kind: Option<String>,
 }
 
 #[tauri::command]
@@ -1301,23 +2397,24 @@ pub fn create_group(state: State<AppState>, input: CreateGroupInput) -> Result<G
     let group = Group {
         id: generate_id(),
         name: input.name,
-        created_by: state.device_id.clone(),
+        created_by: state.device_id(),
         avatar_color: Some("#4f46e5".into()),
         created_at: now(),
+        kind: channels::validate_kind(input.kind.as_deref())?,
     };
     state.db.create_group(&group).map_err(|e| e.to_string())?;
 
     // Add creator as admin
     let local_user = state
         .db
-        .get_user(&state.device_id)
+        .get_user(&state.device_id())
         .map_err(|e| e.to_string())?
         .unwrap();
     state
         .db
         .add_group_member(&GroupMember {
             group_id: group.id.clone(),
-            user_id: state.device_id.clone(),
+            user_id: state.device_id(),
             username: local_user.username.clone(),
             role: "admin".into(),
             joined_at: now(),
@@ -1339,22 +2436,24 @@ pub fn create_group(state: State<AppState>, input: CreateGroupInput) -> Result<G
     }
 
     // Build full member list including creator for the notification
-    let mut all_member_ids = vec![state.device_id.clone()];
+    let mut all_member_ids = vec![state.device_id()];
     all_member_ids.extend(input.member_ids.iter().cloned());
     let mut all_member_names = vec![local_user.username.clone()];
     all_member_names.extend(input.member_names.iter().cloned());
 
     // Notify members (send signaling message) so other peers create the group locally
     for uid in input.member_ids.iter() {
-        if uid != &state.device_id {
+        if uid != &state.device_id() {
             let signaling_msg = SignalingMessage::GroupCreated {
-                from: state.device_id.clone(),
+                from: state.device_id(),
                 to: uid.clone(),
                 id: group.id.clone(),
                 name: group.name.clone(),
                 member_ids: all_member_ids.clone(),
                 member_names: all_member_names.clone(),
                 created_at: group.created_at.clone(),
+                kind: Some(group.kind.clone()),
+                admin_ids: Vec::new(),
             };
             // Try sending; auto-register from discovery on failure
             match state.signaling.send_message(&uid, &signaling_msg) {
@@ -1382,7 +2481,7 @@ pub fn create_group(state: State<AppState>, input: CreateGroupInput) -> Result<G
 pub fn get_groups(state: State<AppState>) -> Result<Vec<Group>, String> {
     state
         .db
-        .get_groups(&state.device_id)
+        .get_groups(&state.device_id())
         .map_err(|e| e.to_string())
 }
 
@@ -1409,31 +2508,48 @@ pub fn send_group_message(
     state: State<AppState>,
     input: SendGroupMsgInput,
 ) -> Result<GroupMessage, String> {
+    post_group_message(&state, &input.group_id, input.content, input.message_type)
+}
+
+/// Store an outgoing group message and relay it to every other member
+pub(crate) fn post_group_message(
+    state: &AppState,
+    group_id: &str,
+    content: String,
+    message_type: Option<String>,
+) -> Result<GroupMessage, String> {
+    if !channels::can_post(&state.db, group_id, &state.device_id()) {
+        return Err("Only channel admins can post here".to_string());
+    }
     let local_user = state
         .db
-        .get_user(&state.device_id)
+        .get_user(&state.device_id())
         .map_err(|e| e.to_string())?
         .unwrap();
     let msg = GroupMessage {
         id: generate_id(),
-        group_id: input.group_id.clone(),
-        sender_id: state.device_id.clone(),
+        group_id: group_id.to_string(),
+        sender_id: state.device_id(),
         sender_name: local_user.username,
-        content: input.content,
-        message_type: input.message_type.unwrap_or_else(|| "text".into()),
+        content,
+        message_type: message_type.unwrap_or_else(|| "text".into()),
         created_at: now(),
+        lamport: state.db.next_lamport().map_err(|e| e.to_string())?,
     };
     state
         .db
         .send_group_message(&msg)
         .map_err(|e| e.to_string())?;
+    if msg.message_type == "text" {
+        link_snapshots::queue(state, &msg.id, &msg.content, false);
+    }
 
     // Relay to group members via signaling (with auto-discovery fallback)
-    if let Ok(members) = state.db.get_group_members(&input.group_id) {
+    if let Ok(members) = state.db.get_group_members(group_id) {
         for m in members {
-            if m.user_id != state.device_id {
+            if m.user_id != state.device_id() {
                 let signaling_msg = SignalingMessage::GroupChatMessage {
-                    from: state.device_id.clone(),
+                    from: state.device_id(),
                     to: m.user_id.clone(),
                     group_id: msg.group_id.clone(),
                     id: msg.id.clone(),
@@ -1441,6 +2557,7 @@ pub fn send_group_message(
                     message_type: msg.message_type.clone(),
                     sender_name: msg.sender_name.clone(),
                     timestamp: msg.created_at.clone(),
+                    lamport: msg.lamport,
                 };
                 match state.signaling.send_message(&m.user_id, &signaling_msg) {
                     Ok(()) => {}
@@ -1496,8 +2613,47 @@ pub fn store_shared_file(
     state
         .file_server
         .store_data_url(&file_id, &data_url, &file_name)?;
+    // Before peers can fetch it or its checksum is taken
+    if let Some(path) = state.file_server.file_path(&file_id) {
+        if let Err(e) = image_metadata::strip_in_place(&state.db, &path) {
+            for path in state.file_server.forget_file(&file_id) {
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(e);
+        }
+    }
+    if file_id == avatar::file_id(&state.device_id()) {
+        state.file_server.publish(&file_id);
+    } else {
+        // The original stays available; get_image_original says where
+        image_compression::compress_stored(&state, &file_id);
+        if let Some(path) = state.file_server.file_path(&file_id) {
+            previews::queue(&state, &file_id, &path, &file_name);
+        }
+    }
     let port = state.file_server.get_port();
-    Ok(format!("http://{{IP}}:{}/file/{}", port, file_id))
+    Ok(format!(
+        "http://{{IP}}:{}/file/{}?token={}",
+        port,
+        file_id,
+        state.file_server.token(&file_id)
+    ))
+}
+
+/// Token peers need to fetch a file we share; sent in the file message as "token"
+#[tauri::command]
+pub fn get_shared_file_token(state: State<AppState>, file_id: String) -> String {
+    state.file_server.token(&file_id)
+}
+
+/// SHA-256 of a file we serve, for the file message that announces it
+#[tauri::command]
+pub fn get_shared_file_checksum(state: State<AppState>, file_id: String) -> Option<String> {
+    let checksum = state.file_server.checksum(&file_id)?;
+    if let Some(path) = state.file_server.file_path(&file_id) {
+        file_index::record(&state.db, &checksum, &path);
+    }
+    Some(checksum)
 }
 
 #[tauri::command]
@@ -1505,6 +2661,12 @@ pub fn get_file_server_port(state: State<AppState>) -> u16 {
     state.file_server.get_port()
 }
 
+/// Port of the localhost-only listener, for the webview's own file URLs
+#[tauri::command]
+pub fn get_local_file_server_port(state: State<AppState>) -> u16 {
+    state.file_server.get_local_port()
+}
+
 /// Read a file directly from disk and return as base64 data URL
 /// This bypasses the HTTP file server entirely for faster, direct file access
 #[tauri::command]
@@ -1548,8 +2710,27 @@ pub fn read_file_as_data_url(file_id: String) -> Result<String, String> {
     Err(format!("File not found: {}", file_id))
 }
 
+/// Delete a message on this device; it can be restored for a while (see deleted_messages).
+/// `secure` shreds its files and erases it for good instead (see shred).
 #[tauri::command]
-pub fn delete_message(state: State<AppState>, message_id: String) -> Result<(), String> {
+pub fn delete_message(
+    state: State<AppState>,
+    message_id: String,
+    secure: Option<bool>,
+) -> Result<(), String> {
+    if secure.unwrap_or(false) {
+        if let Some(message) = state
+            .db
+            .get_message(&message_id)
+            .map_err(|e| e.to_string())?
+        {
+            shred::shred_message_files(&state, &message);
+        }
+        return state
+            .db
+            .erase_message(&message_id)
+            .map_err(|e| e.to_string());
+    }
     state
         .db
         .delete_message(&message_id)
@@ -1560,10 +2741,20 @@ pub fn delete_message(state: State<AppState>, message_id: String) -> Result<(),
 pub fn delete_all_messages_with_peer(
     state: State<AppState>,
     peer_id: String,
+    secure: Option<bool>,
 ) -> Result<(), String> {
+    if secure.unwrap_or(false) {
+        for message in state
+            .db
+            .get_file_messages_with_peer(&state.device_id(), &peer_id)
+            .map_err(|e| e.to_string())?
+        {
+            shred::shred_message_files(&state, &message);
+        }
+    }
     state
         .db
-        .delete_all_messages_with_peer(&state.device_id, &peer_id)
+        .delete_all_messages_with_peer(&state.device_id(), &peer_id)
         .map_err(|e| e.to_string())
 }
 
@@ -1574,16 +2765,19 @@ pub fn delete_user<R: Runtime>(
     user_id: String,
 ) -> Result<(), String> {
     // Prevent accidental deletion of local user
-    if user_id == state.device_id {
+    if user_id == state.device_id() {
         return Err("Cannot delete the local user".into());
     }
     // Remove messages related to this peer for the current local user
     state
         .db
-        .delete_all_messages_with_peer(&state.device_id, &user_id)
+        .delete_all_messages_with_peer(&state.device_id(), &user_id)
         .map_err(|e| e.to_string())?;
     // Delete user from users table
     state.db.delete_user(&user_id).map_err(|e| e.to_string())?;
+    let _ = state.db.delete_chat_settings(&user_id);
+    let _ = state.db.remove_chat_from_folders(&user_id);
+    let _ = state.db.clear_user_tags(&user_id);
 
     // Notify UI that a user was deleted so views can refresh
     let _ = app.emit("user-deleted", serde_json::json!({ "user_id": user_id }));
@@ -1612,20 +2806,22 @@ pub fn add_group_member(
         .map_err(|e| e.to_string())?;
 
     // Notify the newly-added member about the group so their client will create the group locally
-    if let Ok(groups) = state.db.get_groups(&state.device_id) {
+    if let Ok(groups) = state.db.get_groups(&state.device_id()) {
         if let Some(g) = groups.into_iter().find(|gg| gg.id == group_id) {
             let member_rows = state.db.get_group_members(&g.id).unwrap_or_default();
             let member_ids: Vec<String> = member_rows.iter().map(|m| m.user_id.clone()).collect();
             let member_names: Vec<String> =
                 member_rows.iter().map(|m| m.username.clone()).collect();
             let signaling_msg = SignalingMessage::GroupCreated {
-                from: state.device_id.clone(),
+                from: state.device_id(),
                 to: user_id.clone(),
                 id: g.id.clone(),
                 name: g.name.clone(),
                 member_ids: member_ids.clone(),
                 member_names: member_names.clone(),
                 created_at: g.created_at.clone(),
+                kind: Some(g.kind.clone()),
+                admin_ids: channels::admin_ids(&state.db, &g.id, &state.device_id()),
             };
             match state.signaling.send_message(&user_id, &signaling_msg) {
                 Ok(()) => {}
@@ -1647,9 +2843,9 @@ pub fn add_group_member(
 
             // Notify existing members about the new addition
             for mid in &member_ids {
-                if mid != &state.device_id && mid != &user_id {
+                if mid != &state.device_id() && mid != &user_id {
                     let notify_msg = SignalingMessage::GroupMemberAdded {
-                        from: state.device_id.clone(),
+                        from: state.device_id(),
                         to: mid.clone(),
                         group_id: group_id.clone(),
                         user_id: user_id.clone(),
@@ -1679,9 +2875,9 @@ pub fn remove_group_member(
 
     // Notify all remaining members about the removal
     for m in &members_before {
-        if m.user_id != state.device_id && m.user_id != user_id {
+        if m.user_id != state.device_id() && m.user_id != user_id {
             let notify_msg = SignalingMessage::GroupMemberRemoved {
-                from: state.device_id.clone(),
+                from: state.device_id(),
                 to: m.user_id.clone(),
                 group_id: group_id.clone(),
                 user_id: user_id.clone(),
@@ -1697,7 +2893,7 @@ pub fn remove_group_member(
 pub fn leave_group(state: State<AppState>, group_id: String) -> Result<(), String> {
     state
         .db
-        .remove_group_member(&group_id, &state.device_id)
+        .remove_group_member(&group_id, &state.device_id())
         .map_err(|e| e.to_string())
 }
 
@@ -1709,9 +2905,8 @@ pub fn get_all_users_for_group(state: State<AppState>) -> Result<Vec<User>, Stri
 // ============ FILE DOWNLOAD & MANAGEMENT COMMANDS ============
 
 /// Utility function to download bytes from HTTP URL
-fn http_get_bytes(url: &str) -> Result<Vec<u8>, String> {
-    let client = reqwest::blocking::Client::new();
-    let response = client
+pub(crate) fn http_get_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let response = http_client::client()
         .get(url)
         .send()
         .map_err(|e| format!("HTTP request failed: {}", e))?;
@@ -1724,18 +2919,60 @@ fn http_get_bytes(url: &str) -> Result<Vec<u8>, String> {
         .map_err(|e| format!("Read response: {}", e))
 }
 
-fn sanitize_folder_name(name: &str) -> String {
-    name.chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' || c == '.' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>()
-        .trim()
-        .to_string()
+/// Fetch `url` into `part_path`, continuing with a Range request from whatever an earlier
+/// attempt left there. A failed fetch keeps the partial file for the next attempt.
+/// `on_progress(bytes on disk, total)` runs as the body streams in; total comes from
+/// Content-Length and is None when the server doesn't send one.
+pub(crate) fn download_resumable(
+    url: &str,
+    part_path: &Path,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<(), String> {
+    use std::io::{Read, Write};
+
+    let have = std::fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+    let mut request = http_client::client().get(url);
+    if have > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", have));
+    }
+    let mut response = request
+        .send()
+        .map_err(|e| format!("HTTP request failed: {}", e))?;
+    let append = match response.status() {
+        reqwest::StatusCode::PARTIAL_CONTENT => true,
+        // Everything was already there
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE if have > 0 => return Ok(()),
+        // No range support (or no partial file): start over
+        status if status.is_success() => false,
+        status => return Err(format!("HTTP {}: {}", status, url)),
+    };
+    if append {
+        info!("Resuming {} from byte {}", url, have);
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(part_path)
+        .map_err(|e| format!("Open partial file: {}", e))?;
+    let mut done = if append { have } else { 0 };
+    let total = response.content_length().map(|len| done + len);
+    on_progress(done, total);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = response
+            .read(&mut buf)
+            .map_err(|e| format!("Read response: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n])
+            .map_err(|e| format!("Write partial file: {}", e))?;
+        done += n as u64;
+        on_progress(done, total);
+    }
+    Ok(())
 }
 
 fn ext_from_filename(name: &str) -> &str {
@@ -1743,9 +2980,12 @@ fn ext_from_filename(name: &str) -> &str {
 }
 
 /// Auto-download a file from sender's HTTP file server and save locally
+/// Queues the download in the download pool and returns its id right away; the file is
+/// ready once "download-finished" arrives for that id.
 /// Emits "file-download-progress" events: { file_id, file_name, stage, progress }
 /// stages: "downloading" (0..99), "saving" (99), "complete" (100)
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn auto_download_file<R: Runtime>(
     app: AppHandle<R>,
     state: State<AppState>,
@@ -1754,9 +2994,107 @@ pub fn auto_download_file<R: Runtime>(
     file_name: String,
     file_type: String,
     message_id: Option<String>,
+    checksum: Option<String>,
+) -> Result<String, String> {
+    // Already on its way (a re-render asked again)
+    if let Some(id) = download_pool::in_flight(&url) {
+        return Ok(id);
+    }
+    let source_peer = message_id
+        .as_deref()
+        .and_then(|mid| state.db.get_message_sender(mid).ok().flatten());
+    let sender_id = source_peer.clone();
+    let id = downloads::begin(
+        &state.db,
+        None,
+        downloads::Source {
+            kind: "auto",
+            source_peer,
+            source_name: Some(&sender_name),
+            message_id: message_id.as_deref(),
+            url: Some(&url),
+            file_name: &file_name,
+            file_type: &file_type,
+            checksum: checksum.as_deref(),
+            ..Default::default()
+        },
+    );
+
+    // Decide before fetching anything whether this one may download on its own
+    let size = download_policy::remote_size(&url);
+    let policy = download_policy::load(&state.db);
+    let action = download_policy::evaluate(
+        &policy,
+        &file_type,
+        &file_name,
+        size,
+        download_policy::is_wifi_route(&url),
+    );
+    // A file whose transfer request was already accepted isn't asked about again
+    let file_id = file_server::file_id_from_url(&url);
+    let action = if file_requests::is_accepted(file_id) {
+        PolicyAction::Auto
+    } else {
+        trust::download_action(&state.db, sender_id.as_deref(), action)
+    };
+    match action {
+        PolicyAction::Auto => {}
+        PolicyAction::Ask => {
+            let _ = state
+                .db
+                .update_download(&id, "awaiting_approval", None, None, None);
+            if let Ok(Some(record)) = state.db.get_download(&id) {
+                let _ = app.emit("download-approval-needed", &record);
+            }
+            return Err("Download is waiting for approval".to_string());
+        }
+        PolicyAction::Never => {
+            let reason = "Blocked by the auto-download policy";
+            let _ = state
+                .db
+                .update_download(&id, "blocked", None, None, Some(reason));
+            return Err(reason.to_string());
+        }
+    }
+
+    // Paused rather than failed: retrying once space is freed picks it up again
+    if let Err(e) = disk_space::ensure_download_space(&app, &state, size.unwrap_or(0), &file_name) {
+        let _ = state
+            .db
+            .update_download(&id, "paused", None, None, Some(&e));
+        return Err(e);
+    }
+
+    download_pool::enqueue(
+        &app,
+        download_pool::Job {
+            id: id.clone(),
+            url,
+            sender_name,
+            file_name,
+            file_type,
+            message_id,
+            checksum,
+        },
+    );
+    Ok(id)
+}
+
+/// Fetch a chat attachment into shared_files and Downloads/<sender>/<type>/, verifying it
+/// against the sender's SHA-256 when the file message carried one
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn fetch_attachment<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    url: &str,
+    sender_name: &str,
+    file_name: &str,
+    file_type: &str,
+    message_id: Option<&str>,
+    checksum: Option<&str>,
 ) -> Result<String, String> {
-    // Extract fileId from URL (last path segment)
-    let file_id = url.rsplit('/').next().unwrap_or("unknown").to_string();
+    // Extract fileId from URL (last path segment, without the token)
+    let file_id = file_server::file_id_from_url(url).to_string();
 
     // Emit "downloading" progress
     let _ = app.emit(
@@ -1771,9 +3109,46 @@ pub fn auto_download_file<R: Runtime>(
 
     // Check if already in shared_files (file server can already serve it)
     let shared_dir = state.file_server.get_storage_dir();
-    let ext = ext_from_filename(&file_name);
+    let ext = ext_from_filename(file_name);
     let shared_path = shared_dir.join(format!("{}.{}", file_id, ext));
 
+    // Checked before a download is moved into place (and for a copy we already had)
+    let verify = |bytes: &[u8], path: &Path| -> Result<(), String> {
+        let Some(expected) = checksum else {
+            return Ok(());
+        };
+        let actual = crate::crypto::generate_checksum(bytes);
+        if actual.eq_ignore_ascii_case(expected) {
+            return Ok(());
+        }
+        // Don't keep (or serve) a corrupt copy; a retry fetches it again
+        let _ = std::fs::remove_file(path);
+        warn!(
+            "Checksum mismatch for {} from {}: expected {}, got {}",
+            file_name, url, expected, actual
+        );
+        let _ = app.emit(
+            "file-download-progress",
+            serde_json::json!({
+                "fileId": file_id,
+                "fileName": file_name,
+                "stage": "error",
+                "progress": 0
+            }),
+        );
+        let _ = app.emit(
+            "download-integrity-failed",
+            serde_json::json!({
+                "fileId": file_id,
+                "fileName": file_name,
+                "messageId": message_id,
+                "expected": expected,
+                "actual": actual
+            }),
+        );
+        Err("Checksum mismatch".to_string())
+    };
+
     let bytes = if shared_path.exists() {
         // Already downloaded — skip network fetch
         let _ = app.emit(
@@ -1785,11 +3160,66 @@ pub fn auto_download_file<R: Runtime>(
                 "progress": 100
             }),
         );
-        std::fs::read(&shared_path).map_err(|e| e.to_string())?
+        let cached = std::fs::read(&shared_path).map_err(|e| e.to_string())?;
+        verify(&cached, &shared_path)?;
+        cached
+    } else if let Some(local) = checksum.and_then(|c| file_index::lookup(&state.db, c)) {
+        // Same content is already on disk under another name — no need for the network
+        info!(
+            "{} is already at {}, not downloading",
+            file_name,
+            local.display()
+        );
+        std::fs::create_dir_all(&shared_dir).ok();
+        file_index::link_or_copy(&local, &shared_path)?;
+        let _ = app.emit(
+            "file-download-progress",
+            serde_json::json!({
+                "fileId": file_id,
+                "fileName": file_name,
+                "stage": "cached",
+                "progress": 100
+            }),
+        );
+        let copied = std::fs::read(&shared_path).map_err(|e| e.to_string())?;
+        if let Err(e) = verify(&copied, &shared_path) {
+            // The indexed file changed since; a retry downloads it
+            let _ = state.db.forget_file_hash(checksum.unwrap_or_default());
+            return Err(e);
+        }
+        state
+            .file_server
+            .register_file(&file_id, &shared_path, file_name);
+        copied
     } else {
-        // Download from sender's file server
-        let downloaded = http_get_bytes(&url)?;
+        // Download from sender's file server into <id>.<ext>.part, resuming an earlier attempt
+        std::fs::create_dir_all(&shared_dir).ok();
+        let part_path = shared_dir.join(format!("{}.{}.part", file_id, ext));
+        // The download is the first 80% of the bar; saving and organizing take the rest
+        let mut last_percent = 0;
+        download_resumable(url, &part_path, |done, total| {
+            let Some(total) = total.filter(|t| *t > 0) else {
+                return;
+            };
+            let percent = (done.min(total) * 80 / total) as u32;
+            if percent == last_percent {
+                return;
+            }
+            last_percent = percent;
+            let _ = app.emit(
+                "file-download-progress",
+                serde_json::json!({
+                    "fileId": file_id,
+                    "fileName": file_name,
+                    "stage": "downloading",
+                    "progress": percent
+                }),
+            );
+        })?;
+        let downloaded =
+            std::fs::read(&part_path).map_err(|e| format!("Read partial file: {}", e))?;
         if downloaded.is_empty() {
+            let _ = std::fs::remove_file(&part_path);
             let _ = app.emit(
                 "file-download-progress",
                 serde_json::json!({
@@ -1810,38 +3240,50 @@ pub fn auto_download_file<R: Runtime>(
                 "progress": 80
             }),
         );
-        std::fs::create_dir_all(&shared_dir).ok();
-        std::fs::write(&shared_path, &downloaded)
-            .map_err(|e| format!("Write shared file: {}", e))?;
+        verify(&downloaded, &part_path)?;
+        std::fs::rename(&part_path, &shared_path)
+            .map_err(|e| format!("Move download into place: {}", e))?;
         // Register in file server for local serving
         state
             .file_server
-            .register_file(&file_id, &shared_path, &file_name);
+            .register_file(&file_id, &shared_path, file_name);
         downloaded
     };
 
-    // Also save to organized downloads: Pingo/Downloads/<sender_name>/<type>/<file_name>
-    let type_folder = match file_type.as_str() {
-        "image" => "images",
-        "video" => "videos",
-        _ => "files",
-    };
+    // Configured scanner gets the file before it lands in the organized folder
+    if let Err(e) = scan::check(app, &state.db, &shared_path, file_name, message_id) {
+        let _ = app.emit(
+            "file-download-progress",
+            serde_json::json!({
+                "fileId": file_id,
+                "fileName": file_name,
+                "stage": "error",
+                "progress": 0
+            }),
+        );
+        return Err(e);
+    }
+
+    // Also save to organized downloads: Pingo/Downloads/<device_id>/<type>/<file_name>
     let downloads_base = state.file_transfer.get_downloads_dir();
-    let user_folder = downloads_base
-        .join(sanitize_folder_name(&sender_name))
-        .join(type_folder);
+    let user_folder =
+        download_folders::folder_for(&state.db, &downloads_base, message_id, sender_name)
+            .join(download_folders::type_folder(file_type));
     std::fs::create_dir_all(&user_folder).map_err(|e| e.to_string())?;
 
-    let organized_path = user_folder.join(&file_name);
+    let organized_path = user_folder.join(file_name);
     if !organized_path.exists() {
         std::fs::write(&organized_path, &bytes).map_err(|e| format!("Write organized: {}", e))?;
     }
+    if let Some(checksum) = checksum {
+        file_index::record(&state.db, checksum, &shared_path);
+    }
 
     // Update message file_path in DB
     if let Some(mid) = message_id {
         let _ = state
             .db
-            .update_message_file_path(&mid, &organized_path.to_string_lossy());
+            .update_message_file_path(mid, &organized_path.to_string_lossy());
     }
 
     // Emit "complete" — includes the local path so the front-end can immediately display
@@ -1896,13 +3338,76 @@ pub fn open_file_location(path: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Extensions the user allowed to open without confirming (JSON list, e.g. ["sh"])
+const OPEN_ALLOWED_EXTS_KEY: &str = "open_file_allowed_exts";
+const OPEN_NEEDS_CONFIRMATION: &str = "Opening this file type needs confirmation";
+
+/// Open a file with its default application. Executable types are only opened when
+/// `confirmed` is set or their extension is in the user's allowlist.
+#[tauri::command]
+pub fn open_file<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    path: String,
+    confirmed: Option<bool>,
+) -> Result<(), String> {
+    let p = std::fs::canonicalize(&path).map_err(|_| format!("File not found: {}", path))?;
+    if !p.is_file() {
+        return Err(format!("Not a file: {}", path));
+    }
+    let name = p
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if download_policy::is_executable(&name) && !confirmed.unwrap_or(false) {
+        let ext = download_policy::extension(&name);
+        let allowed: Vec<String> = state
+            .db
+            .get_setting(OPEN_ALLOWED_EXTS_KEY)
+            .ok()
+            .flatten()
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default();
+        if !allowed.iter().any(|a| a.eq_ignore_ascii_case(&ext)) {
+            return Err(OPEN_NEEDS_CONFIRMATION.to_string());
+        }
+    }
+    // canonicalize() gives \\?\ paths on Windows, which some default apps can't open
+    let target = p.to_string_lossy();
+    let target = target.strip_prefix(r"\\?\").unwrap_or(&target);
+    app.opener()
+        .open_path(target, None::<&str>)
+        .map_err(|e| format!("Could not open {}: {}", name, e))
+}
+
 /// Save a file from URL with a native save dialog (Windows PowerShell)
 #[tauri::command]
-pub fn save_file_with_dialog(url: String, default_name: String) -> Result<Option<String>, String> {
+pub fn save_file_with_dialog(
+    state: State<AppState>,
+    url: String,
+    default_name: String,
+) -> Result<Option<String>, String> {
     let save_path = show_save_dialog(&default_name);
     if let Some(ref path) = save_path {
-        let bytes = http_get_bytes(&url)?;
-        std::fs::write(path, &bytes).map_err(|e| format!("Write failed: {}", e))?;
+        let id = downloads::begin(
+            &state.db,
+            None,
+            downloads::Source {
+                kind: "save_as",
+                url: Some(&url),
+                file_name: &default_name,
+                file_type: "file",
+                path: Some(path),
+                ..Default::default()
+            },
+        );
+        let result = http_get_bytes(&url).and_then(|bytes| {
+            std::fs::write(path, &bytes)
+                .map(|_| path.clone())
+                .map_err(|e| format!("Write failed: {}", e))
+        });
+        downloads::settle(&state.db, &id, &result);
+        result?;
     }
     Ok(save_path)
 }
@@ -1931,7 +3436,8 @@ fn show_save_dialog(_default_name: &str) -> Option<String> {
     None
 }
 
-/// Rename a user's download folder (when they change username)
+/// Record our new username for our download folder (when we change username). Folders
+/// are keyed by device id, so nothing moves; only the display name mapping changes.
 #[tauri::command]
 pub fn rename_user_download_folder(
     state: State<AppState>,
@@ -1939,15 +3445,8 @@ pub fn rename_user_download_folder(
     new_name: String,
 ) -> Result<(), String> {
     let base = state.file_transfer.get_downloads_dir();
-    let old_path = base.join(sanitize_folder_name(&old_name));
-    let new_path = base.join(sanitize_folder_name(&new_name));
-    if old_path.exists() && !new_path.exists() {
-        std::fs::rename(&old_path, &new_path).map_err(|e| e.to_string())?;
-        println!(
-            "[Pingo] Renamed download folder: {:?} -> {:?}",
-            old_path, new_path
-        );
-    }
+    download_folders::remember_name(&base, &state.device_id(), &new_name);
+    info!("Download folder name: {} -> {}", old_name, new_name);
     Ok(())
 }
 
@@ -1961,29 +3460,27 @@ pub fn get_pingo_downloads_base(state: State<AppState>) -> String {
         .to_string()
 }
 
-/// Check if a file has been auto-downloaded locally
-/// Checks both organized downloads AND shared_files dir (for sender's own files)
+/// Check if a file has been auto-downloaded locally: in the sender's device folder, or
+/// in a folder named after them for files we couldn't attribute to a device
 #[tauri::command]
 pub fn check_file_downloaded(
     state: State<AppState>,
     sender_name: String,
     file_name: String,
     file_type: String,
+    sender_id: Option<String>,
 ) -> Option<String> {
-    let type_folder = match file_type.as_str() {
-        "image" => "images",
-        "video" => "videos",
-        _ => "files",
-    };
     let base = state.file_transfer.get_downloads_dir();
-    let path = base
-        .join(sanitize_folder_name(&sender_name))
-        .join(type_folder)
-        .join(&file_name);
-    if path.exists() {
-        return Some(path.to_string_lossy().to_string());
-    }
-    None
+    let by_device = sender_id.map(|id| download_folders::sender_dir(&base, &id));
+    by_device
+        .into_iter()
+        .chain([download_folders::legacy_dir(&base, &sender_name)])
+        .map(|dir| {
+            dir.join(download_folders::type_folder(&file_type))
+                .join(&file_name)
+        })
+        .find(|path| path.exists())
+        .map(|path| path.to_string_lossy().to_string())
 }
 
 /// Find the local path of a shared file by its file_id (for sender's own uploaded files)
@@ -2005,11 +3502,10 @@ pub fn get_shared_file_path(state: State<AppState>, file_id: String) -> Option<S
 /// Get the local file server URL for a given file ID (uses 127.0.0.1)
 #[tauri::command]
 pub fn get_local_file_url(state: State<AppState>, file_id: String) -> Option<String> {
-    let port = state.file_server.get_port();
-    if port == 0 {
+    if state.file_server.get_local_port() == 0 {
         return None;
     }
-    Some(format!("http://127.0.0.1:{}/file/{}", port, file_id))
+    Some(state.file_server.local_url(&file_id))
 }
 
 // ============ STORAGE STATS COMMANDS ============
@@ -2023,6 +3519,8 @@ pub struct StorageStats {
     pub downloads_path: String,
     pub downloads_size: u64,
     pub total_size: u64,
+    /// Free space on the downloads volume
+    pub available_space: Option<u64>,
 }
 
 fn dir_size(path: &std::path::Path) -> u64 {
@@ -2065,6 +3563,7 @@ pub fn get_storage_stats(state: State<AppState>) -> StorageStats {
         shared_files_path: shared_files_path.to_string_lossy().to_string(),
         shared_files_size,
         downloads_path: downloads_path.to_string_lossy().to_string(),
+        available_space: disk_space::available(&downloads_path),
         downloads_size,
         total_size,
     }
@@ -0,0 +1,389 @@
+// src-tauri/src/config.rs
+// Typed settings. The settings table is plain key/value text; `Setting` lists the keys the
+// backend knows and `Config` holds them with their types and defaults. get_config returns the
+// whole thing, update_config takes a partial object, validates it, stores only what changed,
+// applies it to the running subsystems and emits "settings-changed". The per-feature commands
+// (set_privacy_mode, set_download_policy, ...) keep working on the same keys.
+
+use crate::automation_api;
+use crate::commands::AppState;
+use crate::db::Database;
+use crate::device_sync;
+use crate::discovery::ANNOUNCE_INTERVAL_SECS;
+use crate::disk_space::DEFAULT_RESERVE_MB;
+use crate::download_policy::DownloadPolicy;
+use crate::lan_policy;
+use crate::logging;
+use crate::remote_control;
+use crate::trust::TrustPolicy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+pub const DEFAULT_FILE_SERVER_PORT: u16 = 18080;
+pub const DEFAULT_SIGNALING_PORT: u16 = 45678;
+// Peers are dropped after 15s of silence, so hellos must come more often than that
+const MAX_DISCOVERY_INTERVAL_SECS: u64 = 5;
+
+/// Every setting `Config` covers; serialized as its field name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Setting {
+    LogLevel,
+    DiscoveryVisible,
+    DiscoveryIntervalSecs,
+    FileServerPort,
+    SignalingPort,
+    AutomationApiEnabled,
+    AutomationApiPort,
+    AllowWan,
+    PrivacyMode,
+    RequirePinPairing,
+    StorageReserveMb,
+    QuickReplyPopup,
+    AllowRemoteControl,
+    DownloadPolicy,
+    TrustPolicy,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 15] = [
+        Setting::LogLevel,
+        Setting::DiscoveryVisible,
+        Setting::DiscoveryIntervalSecs,
+        Setting::FileServerPort,
+        Setting::SignalingPort,
+        Setting::AutomationApiEnabled,
+        Setting::AutomationApiPort,
+        Setting::AllowWan,
+        Setting::PrivacyMode,
+        Setting::RequirePinPairing,
+        Setting::StorageReserveMb,
+        Setting::QuickReplyPopup,
+        Setting::AllowRemoteControl,
+        Setting::DownloadPolicy,
+        Setting::TrustPolicy,
+    ];
+
+    /// Field name in `Config`
+    pub fn name(self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default()
+    }
+
+    /// Key in the settings table
+    pub fn key(self) -> &'static str {
+        match self {
+            Setting::LogLevel => "log_level",
+            Setting::DiscoveryVisible => "discovery_visible",
+            Setting::DiscoveryIntervalSecs => "discovery_interval_secs",
+            Setting::FileServerPort => "file_server_port",
+            Setting::SignalingPort => "signaling_port",
+            Setting::AutomationApiEnabled => "automation_api_enabled",
+            Setting::AutomationApiPort => "automation_api_port",
+            Setting::AllowWan => "allow_wan",
+            Setting::PrivacyMode => "privacy_mode",
+            Setting::RequirePinPairing => "require_pin_pairing",
+            Setting::StorageReserveMb => "storage_reserve_mb",
+            Setting::QuickReplyPopup => "quick_reply_popup",
+            Setting::AllowRemoteControl => remote_control::SETTING_KEY,
+            Setting::DownloadPolicy => "auto_download_policy",
+            Setting::TrustPolicy => "trust_policy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub log_level: String,
+    pub discovery_visible: bool,
+    pub discovery_interval_secs: u64,
+    /// Ports apply the next time the server starts
+    pub file_server_port: u16,
+    pub signaling_port: u16,
+    pub automation_api_enabled: bool,
+    pub automation_api_port: u16,
+    pub allow_wan: bool,
+    pub privacy_mode: bool,
+    pub require_pin_pairing: bool,
+    pub storage_reserve_mb: u64,
+    pub quick_reply_popup: bool,
+    /// Let viewers of our screen share ask to control it
+    pub allow_remote_control: bool,
+    pub download_policy: DownloadPolicy,
+    pub trust_policy: TrustPolicy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            log_level: "info".to_string(),
+            discovery_visible: true,
+            discovery_interval_secs: ANNOUNCE_INTERVAL_SECS,
+            file_server_port: DEFAULT_FILE_SERVER_PORT,
+            signaling_port: DEFAULT_SIGNALING_PORT,
+            automation_api_enabled: false,
+            automation_api_port: automation_api::DEFAULT_PORT,
+            allow_wan: false,
+            privacy_mode: false,
+            require_pin_pairing: false,
+            storage_reserve_mb: DEFAULT_RESERVE_MB,
+            quick_reply_popup: false,
+            allow_remote_control: false,
+            download_policy: DownloadPolicy::default(),
+            trust_policy: TrustPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsChanged {
+    pub changed: Vec<Setting>,
+    pub config: Config,
+}
+
+/// Stored text -> JSON value shaped like the default: strings are stored bare, everything
+/// else (bools, numbers, policies) as JSON
+fn parse_stored(raw: &str, default: &Value) -> Option<Value> {
+    match default {
+        Value::String(_) => Some(Value::String(raw.to_string())),
+        _ => serde_json::from_str(raw).ok(),
+    }
+}
+
+fn to_stored(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn as_object(config: &Config) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(config) {
+        Ok(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// Settings as stored; unset or unreadable ones fall back to their defaults
+pub fn load(db: &Database) -> Config {
+    let defaults = as_object(&Config::default());
+    let mut values = defaults.clone();
+    for setting in Setting::ALL {
+        let name = setting.name();
+        let Some(default) = defaults.get(&name) else {
+            continue;
+        };
+        let stored = db
+            .get_setting(setting.key())
+            .ok()
+            .flatten()
+            .and_then(|raw| parse_stored(&raw, default));
+        if let Some(value) = stored {
+            // A value that doesn't fit the type (an old format, a hand edit) keeps the default
+            let mut candidate = values.clone();
+            candidate.insert(name.clone(), value.clone());
+            if serde_json::from_value::<Config>(Value::Object(candidate)).is_ok() {
+                values.insert(name, value);
+            }
+        }
+    }
+    serde_json::from_value(Value::Object(values)).unwrap_or_default()
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), String> {
+        if !logging::LEVELS.contains(&self.log_level.as_str()) {
+            return Err(format!("Unknown log level: {}", self.log_level));
+        }
+        if !(1..=MAX_DISCOVERY_INTERVAL_SECS).contains(&self.discovery_interval_secs) {
+            return Err(format!(
+                "Discovery interval must be 1 to {} seconds",
+                MAX_DISCOVERY_INTERVAL_SECS
+            ));
+        }
+        let ports = [
+            ("File server", self.file_server_port),
+            ("Signaling", self.signaling_port),
+            ("Automation API", self.automation_api_port),
+        ];
+        for (name, port) in ports {
+            if port < 1024 {
+                return Err(format!("{} port must be 1024 or higher", name));
+            }
+        }
+        // The file server also takes the next port up for its localhost listener
+        let Some(local_port) = self.file_server_port.checked_add(1) else {
+            return Err("File server port must leave room for the next port up".to_string());
+        };
+        if self.automation_api_port == self.file_server_port
+            || self.automation_api_port == local_port
+        {
+            return Err("Automation API port clashes with the file server".to_string());
+        }
+        Ok(())
+    }
+
+    /// Settings whose values differ from `other`
+    fn diff(&self, other: &Config) -> Vec<Setting> {
+        let (a, b) = (as_object(self), as_object(other));
+        Setting::ALL
+            .into_iter()
+            .filter(|s| a.get(&s.name()) != b.get(&s.name()))
+            .collect()
+    }
+}
+
+/// Bring running subsystems in line with changed settings. Settings read where they're used
+/// (privacy mode, policies, ...) need nothing here; ports wait for the next start.
+fn apply<R: Runtime>(app: &AppHandle<R>, state: &AppState, config: &Config, changed: &[Setting]) {
+    for setting in changed {
+        match setting {
+            // RUST_LOG overrides the setting, as at startup
+            Setting::LogLevel if std::env::var("RUST_LOG").is_err() => {
+                if let Err(e) = logging::apply_level(&config.log_level) {
+                    warn!("Applying log level failed: {}", e);
+                }
+            }
+            Setting::DiscoveryVisible => {
+                state.discovery.set_visible(config.discovery_visible);
+                let _ = app.emit(
+                    "visibility-changed",
+                    serde_json::json!({ "visible": config.discovery_visible }),
+                );
+            }
+            Setting::DiscoveryIntervalSecs => state
+                .discovery
+                .set_announce_interval(Duration::from_secs(config.discovery_interval_secs)),
+            Setting::AllowWan => lan_policy::configure(&state.db),
+            Setting::AutomationApiEnabled | Setting::AutomationApiPort => {
+                automation_api::reconfigure(app, &state.db)
+            }
+            Setting::AllowRemoteControl if !config.allow_remote_control => {
+                remote_control::end_sharing_sessions(app)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Apply the stored settings that aren't read on use (at startup)
+pub fn apply_saved(state: &AppState) {
+    let config = load(&state.db);
+    state
+        .discovery
+        .set_announce_interval(Duration::from_secs(config.discovery_interval_secs));
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_config(state: State<AppState>) -> Config {
+    load(&state.db)
+}
+
+/// Change some settings: `partial` holds only the fields to change. Returns the new config.
+#[tauri::command]
+pub fn update_config<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    partial: Value,
+) -> Result<Config, String> {
+    let Value::Object(partial) = partial else {
+        return Err("Settings update must be an object".to_string());
+    };
+    let current = load(&state.db);
+    let mut values = as_object(&current);
+    for (name, value) in partial {
+        if !values.contains_key(&name) {
+            return Err(format!("Unknown setting: {}", name));
+        }
+        values.insert(name, value);
+    }
+    let updated: Config =
+        serde_json::from_value(Value::Object(values.clone())).map_err(|e| e.to_string())?;
+    updated.validate()?;
+
+    let changed = updated.diff(&current);
+    if changed.is_empty() {
+        return Ok(updated);
+    }
+    for setting in &changed {
+        if let Some(value) = values.get(&setting.name()) {
+            state
+                .db
+                .set_setting(setting.key(), &to_stored(value))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    info!("Settings changed: {:?}", changed);
+    apply(&app, &state, &updated, &changed);
+    let keys: Vec<&str> = changed.iter().map(|s| s.key()).collect();
+    device_sync::push_settings(&state, &keys);
+    let _ = app.emit(
+        "settings-changed",
+        SettingsChanged {
+            changed,
+            config: updated.clone(),
+        },
+    );
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_values_round_trip() {
+        let config = Config {
+            log_level: "debug".to_string(),
+            discovery_interval_secs: 2,
+            allow_wan: true,
+            ..Config::default()
+        };
+        let defaults = as_object(&Config::default());
+        for (name, value) in as_object(&config) {
+            let back = parse_stored(&to_stored(&value), &defaults[&name]).unwrap();
+            assert_eq!(back, value, "{}", name);
+        }
+        assert_eq!(
+            config.diff(&Config::default()),
+            vec![
+                Setting::LogLevel,
+                Setting::DiscoveryIntervalSecs,
+                Setting::AllowWan
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_values() {
+        assert!(Config::default().validate().is_ok());
+        let bad_level = Config {
+            log_level: "loud".to_string(),
+            ..Config::default()
+        };
+        assert!(bad_level.validate().is_err());
+        let slow = Config {
+            discovery_interval_secs: 30,
+            ..Config::default()
+        };
+        assert!(slow.validate().is_err());
+        let clash = Config {
+            automation_api_port: DEFAULT_FILE_SERVER_PORT + 1,
+            ..Config::default()
+        };
+        assert!(clash.validate().is_err());
+        let top = Config {
+            file_server_port: u16::MAX,
+            ..Config::default()
+        };
+        assert!(top.validate().is_err());
+    }
+}
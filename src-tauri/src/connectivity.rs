@@ -0,0 +1,248 @@
+// src-tauri/src/connectivity.rs
+// Per-peer connectivity test: checks each path a message or file takes so the UI can
+// explain why delivery fails instead of just showing "not delivered"
+
+use crate::commands::AppState;
+use crate::http_client;
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+const TCP_TIMEOUT: Duration = Duration::from_secs(2);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(3);
+// Port peers try first; used when the peer didn't tell us its own
+const DEFAULT_FILE_PORT: u16 = 18080;
+const FILE_SERVER_BANNER: &str = "Pingo File Server";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectivityCheck {
+    /// "local", "discovery", "signaling_route", "udp_ping", "tcp" or "file_server"
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+    pub latency_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectivityReport {
+    pub peer_id: String,
+    pub address: Option<String>,
+    pub checks: Vec<ConnectivityCheck>,
+    /// Most likely cause of failure, or None when every check passed
+    pub diagnosis: Option<String>,
+}
+
+fn check(
+    name: &str,
+    ok: bool,
+    detail: impl Into<String>,
+    latency: Option<Duration>,
+) -> ConnectivityCheck {
+    ConnectivityCheck {
+        name: name.to_string(),
+        ok,
+        detail: detail.into(),
+        latency_ms: latency.map(|d| d.as_millis() as u64),
+    }
+}
+
+/// The first failing check explains the rest, since later paths depend on earlier ones
+fn diagnose(checks: &[ConnectivityCheck]) -> Option<String> {
+    let failed = checks.iter().find(|c| !c.ok)?;
+    let hint = match failed.name.as_str() {
+        "local" => "Pingo's own network services aren't running; restart the app",
+        "discovery" => {
+            "The peer isn't announcing itself on this network. It may be offline, on another subnet, or blocked by a firewall (UDP 15353)"
+        }
+        "signaling_route" => {
+            "Messages are being routed to an outdated address for this peer; reconnecting should fix it"
+        }
+        "udp_ping" => {
+            "The peer is visible but doesn't answer on its signaling port. A firewall is likely dropping UDP, or the peer runs an older Pingo"
+        }
+        "tcp" => "Messages can get through but TCP is blocked, so files and avatars won't download",
+        _ => "The peer's file server isn't answering; files and avatars won't download",
+    };
+    Some(hint.to_string())
+}
+
+fn check_file_server(ip: IpAddr, port: u16) -> ConnectivityCheck {
+    let started = Instant::now();
+    let result = http_client::client()
+        .get(format!("http://{}:{}/", ip, port))
+        .timeout(HTTP_TIMEOUT)
+        .send()
+        .and_then(|response| response.text());
+    match result {
+        Ok(body) if body.trim() == FILE_SERVER_BANNER => check(
+            "file_server",
+            true,
+            "File server answered",
+            Some(started.elapsed()),
+        ),
+        Ok(_) => check(
+            "file_server",
+            false,
+            format!("Port {} answered but it isn't a Pingo file server", port),
+            None,
+        ),
+        Err(e) => check(
+            "file_server",
+            false,
+            format!("HTTP request failed: {}", e),
+            None,
+        ),
+    }
+}
+
+/// Actively test every path to a peer: discovery, signaling route, UDP ping, TCP and HTTP
+#[tauri::command(async)]
+pub fn test_peer_connectivity(
+    state: State<AppState>,
+    peer_id: String,
+) -> Result<ConnectivityReport, String> {
+    let mut checks = Vec::new();
+
+    let local_ok = state.signaling.is_running() && state.file_server.get_port() != 0;
+    checks.push(check(
+        "local",
+        local_ok,
+        format!(
+            "Signaling port {:?}, file server port {}, discovery {}",
+            state.signaling.local_port(),
+            state.file_server.get_port(),
+            if state.discovery.is_running() {
+                "running"
+            } else {
+                "stopped"
+            }
+        ),
+        None,
+    ));
+
+    let discovered = state.discovery.get_peer(&peer_id);
+    let address: Option<SocketAddr> = discovered
+        .as_ref()
+        .and_then(|p| format!("{}:{}", p.ip_address, p.port).parse().ok())
+        .or_else(|| state.signaling.get_peer(&peer_id).map(|c| c.address));
+    checks.push(match &discovered {
+        Some(p) if p.is_online => check(
+            "discovery",
+            true,
+            format!("Seen at {}:{}", p.ip_address, p.port),
+            None,
+        ),
+        Some(p) => check(
+            "discovery",
+            false,
+            format!(
+                "Last seen at {}:{} but announcements stopped",
+                p.ip_address, p.port
+            ),
+            None,
+        ),
+        None => check("discovery", false, "Not seen by LAN discovery", None),
+    });
+
+    let Some(addr) = address else {
+        return Ok(ConnectivityReport {
+            peer_id,
+            address: None,
+            diagnosis: diagnose(&checks),
+            checks,
+        });
+    };
+
+    checks.push(match state.signaling.get_peer(&peer_id) {
+        Some(c) if c.address == addr => check(
+            "signaling_route",
+            true,
+            format!("Messages go to {}", c.address),
+            None,
+        ),
+        Some(c) => check(
+            "signaling_route",
+            false,
+            format!(
+                "Messages go to {} but the peer announces {}",
+                c.address, addr
+            ),
+            None,
+        ),
+        // send_to_peer registers the route from discovery on first send
+        None => check(
+            "signaling_route",
+            true,
+            "Not registered yet; will be added on first message",
+            None,
+        ),
+    });
+
+    let mut file_port = None;
+    checks.push(match state.signaling.ping(addr, PING_TIMEOUT) {
+        Ok(reply) => {
+            file_port = reply.file_port;
+            check(
+                "udp_ping",
+                true,
+                format!("Reply from {}", addr),
+                Some(reply.rtt),
+            )
+        }
+        Err(e) => check(
+            "udp_ping",
+            false,
+            format!("{} from {} within {}s", e, addr, PING_TIMEOUT.as_secs()),
+            None,
+        ),
+    });
+
+    let file_addr = SocketAddr::new(addr.ip(), file_port.unwrap_or(DEFAULT_FILE_PORT));
+    let started = Instant::now();
+    checks.push(match TcpStream::connect_timeout(&file_addr, TCP_TIMEOUT) {
+        Ok(_) => check(
+            "tcp",
+            true,
+            format!("Connected to {}", file_addr),
+            Some(started.elapsed()),
+        ),
+        Err(e) => check(
+            "tcp",
+            false,
+            format!("Connecting to {} failed: {}", file_addr, e),
+            None,
+        ),
+    });
+    // The blocking client can't run on the async command's runtime thread
+    let file_server =
+        std::thread::spawn(move || check_file_server(file_addr.ip(), file_addr.port()))
+            .join()
+            .map_err(|_| "File server check failed".to_string())?;
+    checks.push(file_server);
+
+    Ok(ConnectivityReport {
+        peer_id,
+        address: Some(addr.to_string()),
+        diagnosis: diagnose(&checks),
+        checks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnoses_first_failing_check() {
+        let checks = vec![
+            check("local", true, "", None),
+            check("discovery", true, "", None),
+            check("udp_ping", false, "", None),
+            check("tcp", false, "", None),
+        ];
+        assert!(diagnose(&checks).unwrap().contains("signaling port"));
+        assert!(diagnose(&checks[..2]).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+// src-tauri/src/contact_tags.rs
+// Contact tags: free-form labels on users ("ops", "hr") to filter the contact lists by and to
+// address bulk actions to (see bulk_messages::broadcast_to_tag). Tags are stored lowercase, so
+// "Ops" and "ops" are the same tag.
+
+use crate::commands::AppState;
+use crate::db::{Database, TagCount, User};
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+const MAX_TAG_CHARS: usize = 24;
+const MAX_TAGS_PER_USER: usize = 20;
+const MAX_BATCH: usize = 500;
+
+/// Lowercase, trimmed tag; letters, digits, '-' and '_' only
+pub fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    if tag.is_empty() {
+        return Err("Tag can't be empty".to_string());
+    }
+    if tag.chars().count() > MAX_TAG_CHARS {
+        return Err(format!("Tags are at most {} characters", MAX_TAG_CHARS));
+    }
+    if !tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("Tags can only use letters, digits, '-' and '_'".to_string());
+    }
+    Ok(tag)
+}
+
+/// Ids of the users carrying `tag`
+pub fn tagged_user_ids(db: &Database, tag: &str) -> Result<HashSet<String>, String> {
+    let tag = normalize_tag(tag)?;
+    Ok(db
+        .get_tagged_user_ids(&tag)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect())
+}
+
+/// Keep the users carrying `tag`; everyone when there's no tag
+pub fn filter_users(
+    db: &Database,
+    users: Vec<User>,
+    tag: Option<&str>,
+) -> Result<Vec<User>, String> {
+    let Some(tag) = tag.filter(|t| !t.trim().is_empty()) else {
+        return Ok(users);
+    };
+    let ids = tagged_user_ids(db, tag)?;
+    Ok(users.into_iter().filter(|u| ids.contains(&u.id)).collect())
+}
+
+fn tags_by_user(db: &Database) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut by_user: HashMap<String, Vec<String>> = HashMap::new();
+    for (user_id, tag) in db.get_user_tags().map_err(|e| e.to_string())? {
+        by_user.entry(user_id).or_default().push(tag);
+    }
+    Ok(by_user)
+}
+
+fn add_tag(db: &Database, user_id: &str, tag: &str) -> Result<bool, String> {
+    let current = tags_by_user(db)?.remove(user_id).unwrap_or_default();
+    if current.iter().any(|t| t == tag) {
+        return Ok(false);
+    }
+    if current.len() >= MAX_TAGS_PER_USER {
+        return Err(format!(
+            "A contact can have at most {} tags",
+            MAX_TAGS_PER_USER
+        ));
+    }
+    db.add_user_tag(user_id, tag).map_err(|e| e.to_string())
+}
+
+fn emit_changed<R: Runtime>(app: &AppHandle<R>, db: &Database) {
+    if let Ok(tags) = tags_by_user(db) {
+        let _ = app.emit("contact-tags-changed", tags);
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Tag one contact; returns false when it already had the tag
+#[tauri::command]
+pub fn add_user_tag<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    user_id: String,
+    tag: String,
+) -> Result<bool, String> {
+    let tag = normalize_tag(&tag)?;
+    let added = add_tag(&state.db, &user_id, &tag)?;
+    if added {
+        emit_changed(&app, &state.db);
+    }
+    Ok(added)
+}
+
+#[tauri::command]
+pub fn remove_user_tag<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    user_id: String,
+    tag: String,
+) -> Result<bool, String> {
+    let tag = normalize_tag(&tag)?;
+    let removed = state
+        .db
+        .remove_user_tag(&user_id, &tag)
+        .map_err(|e| e.to_string())?;
+    if removed {
+        emit_changed(&app, &state.db);
+    }
+    Ok(removed)
+}
+
+/// Tag many contacts at once (multi-select in the contact list); returns how many were newly
+/// tagged
+#[tauri::command]
+pub fn tag_users<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    user_ids: Vec<String>,
+    tag: String,
+) -> Result<usize, String> {
+    if user_ids.len() > MAX_BATCH {
+        return Err(format!("At most {} contacts at a time", MAX_BATCH));
+    }
+    let tag = normalize_tag(&tag)?;
+    let mut added = 0;
+    for user_id in &user_ids {
+        if add_tag(&state.db, user_id, &tag)? {
+            added += 1;
+        }
+    }
+    if added > 0 {
+        emit_changed(&app, &state.db);
+    }
+    Ok(added)
+}
+
+/// Tags per contact id
+#[tauri::command]
+pub fn get_user_tags(state: State<AppState>) -> Result<HashMap<String, Vec<String>>, String> {
+    tags_by_user(&state.db)
+}
+
+/// Every tag in use with how many contacts carry it
+#[tauri::command]
+pub fn get_all_tags(state: State<AppState>) -> Result<Vec<TagCount>, String> {
+    state.db.get_tag_counts().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag("  #Ops ").unwrap(), "ops");
+        assert_eq!(normalize_tag("on-call_2").unwrap(), "on-call_2");
+        assert!(normalize_tag("").is_err());
+        assert!(normalize_tag("two words").is_err());
+        assert!(normalize_tag(&"x".repeat(MAX_TAG_CHARS + 1)).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+// src-tauri/src/conversation_clear.rs
+// Clearing a conversation for both sides: history is deleted here, the peer is sent a
+// ConversationCleared message, and a tombstone keeps messages up to that point from being
+// stored again (pending-message flushes, linked-device sync, history imports). What we do
+// when a peer clears a conversation with us is up to the "conversation_clear_policy" setting.
+// A peer's clear time never lies in the future: a tombstone there would also swallow the
+// messages still to come, so a slightly early clock is clamped to now and anything further
+// ahead is refused.
+
+use crate::clock_skew;
+use crate::commands::{self, AppState};
+use crate::db::{now, Database};
+use crate::signaling::SignalingMessage;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const POLICY_KEY: &str = "conversation_clear_policy";
+/// How far ahead of our clock a peer's clear time may be and still pass as now
+const MAX_AHEAD_SECS: i64 = 5 * 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClearPolicy {
+    /// Clear our copy too
+    Honor,
+    /// Let the user decide ("conversation-clear-requested")
+    #[default]
+    Ask,
+    /// Keep our copy
+    Ignore,
+}
+
+pub fn load_policy(db: &Database) -> ClearPolicy {
+    db.get_setting(POLICY_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v)).ok())
+        .unwrap_or_default()
+}
+
+/// `cleared_at` as a clear time we can keep: at most `now`, refused when it's further ahead
+/// than MAX_AHEAD_SECS or isn't a timestamp
+fn bounded_cleared_at(cleared_at: &str, now: DateTime<Utc>) -> Result<String, String> {
+    let at = DateTime::parse_from_rfc3339(cleared_at)
+        .map_err(|_| format!("Invalid clear time {}", cleared_at))?
+        .with_timezone(&Utc);
+    if at > now + Duration::seconds(MAX_AHEAD_SECS) {
+        return Err(format!("Clear time {} is in the future", cleared_at));
+    }
+    Ok(at.min(now).to_rfc3339())
+}
+
+/// A peer's clear time, corrected for its clock offset and bounded
+fn peer_cleared_at(db: &Database, peer_id: &str, cleared_at: &str) -> Result<String, String> {
+    bounded_cleared_at(&clock_skew::normalize(db, peer_id, cleared_at), Utc::now())
+}
+
+fn clear<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    local_id: &str,
+    peer_id: &str,
+    cleared_at: &str,
+    cleared_by: &str,
+) -> Result<usize, String> {
+    let n = db
+        .clear_conversation(local_id, peer_id, cleared_at, cleared_by)
+        .map_err(|e| e.to_string())?;
+    info!(
+        "Cleared {} messages with {} (by {})",
+        n, peer_id, cleared_by
+    );
+    let payload = serde_json::json!({
+        "peer_id": peer_id,
+        "cleared_at": cleared_at,
+        "cleared_by": cleared_by,
+        "count": n,
+    });
+    let _ = app.emit("conversation-cleared", payload);
+    Ok(n)
+}
+
+/// A peer cleared our conversation on their side
+pub fn handle_message<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    let SignalingMessage::ConversationCleared {
+        from, cleared_at, ..
+    } = msg
+    else {
+        return;
+    };
+    let state = app.state::<AppState>();
+    let cleared_at = match peer_cleared_at(&state.db, from, cleared_at) {
+        Ok(at) => at,
+        Err(e) => {
+            warn!("Ignoring conversation clear from {}: {}", from, e);
+            return;
+        }
+    };
+    // Already cleared up to there (a repeat, or we cleared it ourselves)
+    let tombstone = state.db.get_conversation_tombstone(from).ok().flatten();
+    if tombstone.is_some_and(|t| cleared_at.as_str() <= t.as_str()) {
+        return;
+    }
+    match load_policy(&state.db) {
+        ClearPolicy::Honor => {
+            if let Err(e) = clear(app, &state.db, &state.device_id(), from, &cleared_at, from) {
+                warn!("Clearing conversation with {} failed: {}", from, e);
+            }
+        }
+        ClearPolicy::Ask => {
+            let _ = app.emit(
+                "conversation-clear-requested",
+                serde_json::json!({ "peer_id": from, "cleared_at": cleared_at }),
+            );
+        }
+        ClearPolicy::Ignore => info!("Ignoring conversation clear from {}", from),
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Delete the conversation here and ask the peer to do the same. `confirmed` must be set;
+/// returns whether the peer could be told right away.
+#[tauri::command]
+pub fn clear_chat_for_both<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+    confirmed: bool,
+) -> Result<bool, String> {
+    if !confirmed {
+        return Err("Clearing a chat for both sides needs confirmation".to_string());
+    }
+    let local_id = state.device_id();
+    let cleared_at = now();
+    clear(&app, &state.db, &local_id, &peer_id, &cleared_at, &local_id)?;
+    let msg = SignalingMessage::ConversationCleared {
+        from: local_id,
+        to: peer_id.clone(),
+        cleared_at,
+    };
+    match commands::send_to_peer(&state, &peer_id, &msg) {
+        Ok(()) => Ok(true),
+        Err(e) => {
+            warn!("Couldn't tell {} about the cleared chat: {}", peer_id, e);
+            Ok(false)
+        }
+    }
+}
+
+/// Answer a conversation-clear-requested prompt with yes
+#[tauri::command]
+pub fn accept_conversation_clear<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+    cleared_at: String,
+) -> Result<usize, String> {
+    // Already corrected for the peer's clock when the prompt went out
+    let cleared_at = bounded_cleared_at(&cleared_at, Utc::now())?;
+    clear(
+        &app,
+        &state.db,
+        &state.device_id(),
+        &peer_id,
+        &cleared_at,
+        &peer_id,
+    )
+}
+
+#[tauri::command]
+pub fn get_conversation_clear_policy(state: State<AppState>) -> ClearPolicy {
+    load_policy(&state.db)
+}
+
+#[tauri::command]
+pub fn set_conversation_clear_policy(
+    state: State<AppState>,
+    policy: ClearPolicy,
+) -> Result<(), String> {
+    let value = serde_json::to_value(policy).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(POLICY_KEY, value.as_str().unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Message;
+    use crate::delivery_status::MessageStatus;
+
+    fn msg(id: &str, created_at: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            sender_id: "peer".to_string(),
+            receiver_id: "me".to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: false,
+            created_at: created_at.to_string(),
+            lamport: 0,
+            status: MessageStatus::Queued,
+        }
+    }
+
+    #[test]
+    fn test_tombstone_blocks_resurrection() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("me", "Me", None).unwrap();
+        db.upsert_peer_as_user("peer", "Peer", None).unwrap();
+        db.create_message(&msg("old", "2026-01-01T00:00:00+00:00"))
+            .unwrap();
+
+        let n = db
+            .clear_conversation("me", "peer", "2026-02-01T00:00:00+00:00", "me")
+            .unwrap();
+        assert_eq!(n, 1);
+        assert!(db
+            .get_messages_between("me", "peer", 10)
+            .unwrap()
+            .is_empty());
+
+        // A re-sent copy from before the clear is dropped, newer messages still arrive
+        db.import_messages(&[msg("older", "2026-01-15T00:00:00+00:00")])
+            .unwrap();
+        assert!(db.get_message("older").unwrap().is_none());
+        db.create_message(&msg("new", "2026-03-01T00:00:00+00:00"))
+            .unwrap();
+        assert_eq!(db.get_messages_between("me", "peer", 10).unwrap().len(), 1);
+        assert_eq!(
+            db.get_conversation_tombstone("peer").unwrap().as_deref(),
+            Some("2026-02-01T00:00:00+00:00")
+        );
+    }
+
+    #[test]
+    fn test_clear_time_is_bounded() {
+        let now = DateTime::parse_from_rfc3339("2026-02-01T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            bounded_cleared_at("2026-02-01T11:00:00+01:00", now).unwrap(),
+            "2026-02-01T10:00:00+00:00"
+        );
+        // A clock a little ahead is clamped, one far ahead refused
+        assert_eq!(
+            bounded_cleared_at("2026-02-01T12:02:00+00:00", now).unwrap(),
+            now.to_rfc3339()
+        );
+        assert!(bounded_cleared_at("2099-01-01T00:00:00+00:00", now).is_err());
+        assert!(bounded_cleared_at("not a time", now).is_err());
+    }
+}
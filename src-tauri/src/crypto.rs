@@ -5,16 +5,37 @@ use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
 };
+use argon2::Argon2;
+use chacha20poly1305::{aead::Payload, XChaCha20Poly1305, XNonce};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hkdf::Hkdf;
 use rand::RngCore;
 use sha2::{Sha256, Digest};
 use x25519_dalek::{StaticSecret, PublicKey};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
 use std::sync::RwLock;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use tracing::warn;
+use zeroize::{Zeroize, Zeroizing};
 
-// Nonce size for AES-GCM
+// Nonce size for AES-GCM (passphrase envelopes)
 const NONCE_SIZE: usize = 12;
+// Nonce size for XChaCha20-Poly1305 (peer sessions); random nonces this long don't collide
+const SESSION_NONCE_SIZE: usize = 24;
+// HKDF salt for session keys; bump it if the key schedule changes
+const SESSION_KDF_SALT: &[u8] = b"pingo-session-v2";
+// Salt size for passphrase key derivation
+const SALT_SIZE: usize = 16;
+// Start of a file sealed with a passphrase (remote backups)
+const SEALED_FILE_MAGIC: &[u8] = b"PINGOSF1";
+// Plaintext bytes per chunk of a sealed file
+const SEALED_CHUNK_SIZE: usize = 1024 * 1024;
+// HKDF salt for the key that seals sessions for storage
+const SESSION_STORE_SALT: &[u8] = b"pingo-session-store";
 
 /// Encrypted message envelope
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,52 +43,189 @@ pub struct EncryptedEnvelope {
     pub nonce: String,          // Base64 encoded nonce
     pub ciphertext: String,     // Base64 encoded ciphertext
     pub sender_public_key: String, // Base64 encoded public key
+    // Authenticated as associated data (see envelope_aad): changing any of them fails decryption
+    #[serde(default)]
+    pub sender_id: String,
+    #[serde(default)]
+    pub recipient_id: String,
+    #[serde(default)]
+    pub message_id: String,
+    #[serde(default)]
+    pub timestamp: i64,         // Unix millis at encryption
 }
 
-/// Key pair for this device
-#[derive(Clone)]
+/// How far an envelope's encryption time may be from our clock in decrypt_fresh
+const MAX_ENVELOPE_SKEW_MS: i64 = 10 * 60 * 1000;
+
+/// Associated data binding an envelope to its sender, recipient, message and time
+fn envelope_aad(envelope: &EncryptedEnvelope) -> Vec<u8> {
+    format!("pingo-envelope-v1\0{}\0{}\0{}\0{}",
+        envelope.sender_id, envelope.recipient_id, envelope.message_id, envelope.timestamp).into_bytes()
+}
+
+/// Proof that a session key belongs to a device: its long-term Ed25519 identity key and that
+/// key's signature over the device id and the session key (see session_key_message)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdentityProof {
+    pub identity_key: String, // Base64
+    pub signature: String,    // Base64
+}
+
+fn session_key_message(device_id: &str, session_public_key: &str) -> Vec<u8> {
+    format!("pingo-session-key-v1\0{}\0{}", device_id, session_public_key).into_bytes()
+}
+
+/// Check that `proof`'s identity key signed `session_public_key` for `device_id`
+pub fn verify_identity_proof(device_id: &str, session_public_key: &str, proof: &IdentityProof) -> Result<(), String> {
+    let key_bytes: [u8; 32] = BASE64.decode(&proof.identity_key)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "Invalid identity key length")?;
+    let identity = VerifyingKey::from_bytes(&key_bytes).map_err(|e| e.to_string())?;
+    let signature_bytes: [u8; 64] = BASE64.decode(&proof.signature)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "Invalid signature length")?;
+    identity.verify(&session_key_message(device_id, session_public_key), &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| "Session key isn't signed by this identity".to_string())
+}
+
+/// Session key pair for this device (the X25519 key peers run ECDH against). It's signed by
+/// the identity key and can be rotated without changing who we are. StaticSecret wipes itself.
 pub struct DeviceKeyPair {
     pub public_key: PublicKey,
-    secret_key: [u8; 32],
+    secret_key: StaticSecret,
 }
 
-/// Session key for a peer (derived from ECDH)
+/// Session keys for a peer (derived from ECDH): one per direction
 #[allow(dead_code)]
 struct SessionKey {
-    shared_secret: [u8; 32],
+    send_key: [u8; 32],
+    recv_key: [u8; 32],
     #[allow(dead_code)]
     peer_public_key: PublicKey,
 }
 
+impl Drop for SessionKey {
+    // Removed, replaced and cleared sessions don't leave their keys behind in memory
+    fn drop(&mut self) {
+        self.send_key.zeroize();
+        self.recv_key.zeroize();
+    }
+}
+
+/// HKDF-SHA256 over the DH output, with the sender's and receiver's public keys in the info:
+/// the two directions of a session never share a key
+fn directional_key(hkdf: &Hkdf<Sha256>, sender: &PublicKey, receiver: &PublicKey) -> Result<[u8; 32], String> {
+    let info = [&b"pingo message key"[..], sender.as_bytes(), receiver.as_bytes()].concat();
+    let mut key = [0u8; 32];
+    hkdf.expand(&info, &mut key).map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// A session's keys sealed for storage under a key derived from the device secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSession {
+    pub peer_id: String,
+    pub peer_public_key: String, // Base64
+    pub nonce: String,           // Base64
+    pub sealed: String,          // Base64 send_key || recv_key
+}
+
+/// Called with every new or re-keyed session so it can be persisted (see session_store)
+pub type SessionListener = Box<dyn Fn(StoredSession) + Send + Sync>;
+
+/// Associated data for a stored session: the keys only unseal for the peer and key they were made with
+fn stored_session_aad(peer_id: &str, peer_public_key: &str) -> Vec<u8> {
+    format!("pingo-stored-session-v1\0{}\0{}", peer_id, peer_public_key).into_bytes()
+}
+
 /// Crypto manager for handling all encryption operations
 pub struct CryptoManager {
+    /// Long-term identity; only signs session keys, never used for encryption
+    identity_key: RwLock<Option<SigningKey>>,
     device_keypair: RwLock<Option<DeviceKeyPair>>,
     session_keys: RwLock<HashMap<String, SessionKey>>,
+    /// Our device id, the sender of what we encrypt and the recipient of what we decrypt
+    device_id: RwLock<String>,
+    on_session: RwLock<Option<SessionListener>>,
 }
 
 impl CryptoManager {
     /// Create a new crypto manager
     pub fn new() -> Self {
         CryptoManager {
+            identity_key: RwLock::new(None),
             device_keypair: RwLock::new(None),
             session_keys: RwLock::new(HashMap::new()),
+            device_id: RwLock::new(String::new()),
+            on_session: RwLock::new(None),
         }
     }
 
+    pub fn set_session_listener(&self, listener: SessionListener) {
+        *self.on_session.write().unwrap() = Some(listener);
+    }
+
+    pub fn set_device_id(&self, device_id: &str) {
+        *self.device_id.write().unwrap() = device_id.to_string();
+    }
+
+    /// Generate a new identity key; returns its public half as base64
+    pub fn generate_identity(&self) -> String {
+        let signing_key = SigningKey::generate(&mut rand::thread_rng());
+        let public_b64 = BASE64.encode(signing_key.verifying_key().as_bytes());
+        *self.identity_key.write().unwrap() = Some(signing_key);
+        public_b64
+    }
+
+    /// Load the identity key from a stored secret; returns its public half as base64
+    pub fn load_identity(&self, secret_b64: &str) -> Result<String, String> {
+        let decoded = Zeroizing::new(BASE64.decode(secret_b64.trim()).map_err(|e| e.to_string())?);
+        let secret_bytes: Zeroizing<[u8; 32]> = Zeroizing::new(decoded.as_slice()
+            .try_into()
+            .map_err(|_| "Invalid identity key length")?);
+        let signing_key = SigningKey::from_bytes(&secret_bytes);
+        let public_b64 = BASE64.encode(signing_key.verifying_key().as_bytes());
+        *self.identity_key.write().unwrap() = Some(signing_key);
+        Ok(public_b64)
+    }
+
+    /// The identity secret as base64, for writing to the identity key file
+    pub fn export_identity_secret(&self) -> Option<Zeroizing<String>> {
+        let identity = self.identity_key.read().unwrap();
+        identity.as_ref().map(|k| Zeroizing::new(BASE64.encode(Zeroizing::new(k.to_bytes()))))
+    }
+
+    /// Public identity key as base64
+    pub fn get_identity_key(&self) -> Option<String> {
+        let identity = self.identity_key.read().unwrap();
+        identity.as_ref().map(|k| BASE64.encode(k.verifying_key().as_bytes()))
+    }
+
+    /// Sign the current session key with the identity key, for announcing it
+    pub fn identity_proof(&self) -> Option<IdentityProof> {
+        let session_public_key = self.get_public_key()?;
+        let identity = self.identity_key.read().unwrap();
+        let signing_key = identity.as_ref()?;
+        let message = session_key_message(&self.device_id.read().unwrap(), &session_public_key);
+        Some(IdentityProof {
+            identity_key: BASE64.encode(signing_key.verifying_key().as_bytes()),
+            signature: BASE64.encode(signing_key.sign(&message).to_bytes()),
+        })
+    }
+
     /// Generate a new device key pair
     pub fn generate_keypair(&self) -> String {
         let mut rng = rand::thread_rng();
         let secret = StaticSecret::random_from_rng(&mut rng);
         let public = PublicKey::from(&secret);
 
-        // Store secret key bytes
-        let secret_bytes = secret.to_bytes();
-
         // For simplicity, we'll use a static secret approach
         // In production, you'd want proper key storage
         let keypair = DeviceKeyPair {
             public_key: public,
-            secret_key: secret_bytes,
+            secret_key: secret,
         };
 
         let public_key_b64 = BASE64.encode(public.as_bytes());
@@ -80,31 +238,98 @@ impl CryptoManager {
         public_key_b64
     }
 
-    /// Load an existing key pair from storage
-    #[allow(dead_code)]
-    pub fn load_keypair(&self, secret_b64: &str, public_b64: &str) -> Result<(), String> {
-        let secret_bytes: [u8; 32] = BASE64.decode(secret_b64)
-            .map_err(|e| e.to_string())?
-            .try_into()
-            .map_err(|_| "Invalid secret key length")?;
-
-        let public_bytes: [u8; 32] = BASE64.decode(public_b64)
-            .map_err(|e| e.to_string())?
+    /// Load the key pair from a stored secret key; returns the public key as base64
+    pub fn load_keypair(&self, secret_b64: &str) -> Result<String, String> {
+        // The decoded copies are wiped as soon as the key is built
+        let decoded = Zeroizing::new(BASE64.decode(secret_b64.trim()).map_err(|e| e.to_string())?);
+        let secret_bytes: Zeroizing<[u8; 32]> = Zeroizing::new(decoded.as_slice()
             .try_into()
-            .map_err(|_| "Invalid public key length")?;
+            .map_err(|_| "Invalid secret key length")?);
 
-        let public_key = PublicKey::from(public_bytes);
-
-        let keypair = DeviceKeyPair {
-            public_key,
-            secret_key: secret_bytes,
-        };
+        let secret_key = StaticSecret::from(*secret_bytes);
+        let public_key = PublicKey::from(&secret_key);
+        let public_key_b64 = BASE64.encode(public_key.as_bytes());
 
         {
             let mut kp = self.device_keypair.write().unwrap();
-            *kp = Some(keypair);
+            *kp = Some(DeviceKeyPair { public_key, secret_key });
         }
 
+        Ok(public_key_b64)
+    }
+
+    /// The secret key as base64, for writing to the key file
+    pub fn export_secret_key(&self) -> Option<Zeroizing<String>> {
+        let kp = self.device_keypair.read().unwrap();
+        kp.as_ref().map(|k| Zeroizing::new(BASE64.encode(Zeroizing::new(k.secret_key.to_bytes()))))
+    }
+
+    /// Key for sealing stored sessions, derived from the device secret
+    fn session_store_key(keypair: &DeviceKeyPair) -> Result<Zeroizing<[u8; 32]>, String> {
+        let secret = Zeroizing::new(keypair.secret_key.to_bytes());
+        let hkdf = Hkdf::<Sha256>::new(Some(SESSION_STORE_SALT), secret.as_slice());
+        let mut key = Zeroizing::new([0u8; 32]);
+        hkdf.expand(b"pingo session store key", key.as_mut()).map_err(|e| e.to_string())?;
+        Ok(key)
+    }
+
+    fn seal_session(keypair: &DeviceKeyPair, peer_id: &str, session: &SessionKey) -> Result<StoredSession, String> {
+        let key = Self::session_store_key(keypair)?;
+        let cipher = XChaCha20Poly1305::new_from_slice(key.as_slice()).map_err(|e| e.to_string())?;
+        let mut nonce_bytes = [0u8; SESSION_NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let peer_public_key = BASE64.encode(session.peer_public_key.as_bytes());
+
+        let mut keys = Zeroizing::new([0u8; 64]);
+        keys[..32].copy_from_slice(&session.send_key);
+        keys[32..].copy_from_slice(&session.recv_key);
+        let aad = stored_session_aad(peer_id, &peer_public_key);
+        let sealed = cipher.encrypt(XNonce::from_slice(&nonce_bytes), Payload { msg: keys.as_slice(), aad: &aad })
+            .map_err(|e| format!("Sealing session failed: {}", e))?;
+
+        Ok(StoredSession {
+            peer_id: peer_id.to_string(),
+            peer_public_key,
+            nonce: BASE64.encode(nonce_bytes),
+            sealed: BASE64.encode(sealed),
+        })
+    }
+
+    /// Bring back a session sealed by an earlier run with the same device key
+    pub fn restore_session(&self, stored: &StoredSession) -> Result<(), String> {
+        let peer_public_bytes: [u8; 32] = BASE64.decode(&stored.peer_public_key)
+            .map_err(|e| e.to_string())?
+            .try_into()
+            .map_err(|_| "Invalid peer public key length")?;
+        let nonce_bytes = BASE64.decode(&stored.nonce).map_err(|e| e.to_string())?;
+        if nonce_bytes.len() != SESSION_NONCE_SIZE {
+            return Err("Invalid nonce length".to_string());
+        }
+        let sealed = BASE64.decode(&stored.sealed).map_err(|e| e.to_string())?;
+
+        let keys = {
+            let kp = self.device_keypair.read().unwrap();
+            let keypair = kp.as_ref().ok_or("No keypair generated")?;
+            let key = Self::session_store_key(keypair)?;
+            let cipher = XChaCha20Poly1305::new_from_slice(key.as_slice()).map_err(|e| e.to_string())?;
+            let aad = stored_session_aad(&stored.peer_id, &stored.peer_public_key);
+            Zeroizing::new(cipher.decrypt(XNonce::from_slice(&nonce_bytes), Payload { msg: &sealed, aad: &aad })
+                .map_err(|_| "Stored session doesn't unseal with this device key")?)
+        };
+        if keys.len() != 64 {
+            return Err("Invalid stored session length".to_string());
+        }
+
+        let mut session = SessionKey {
+            send_key: [0u8; 32],
+            recv_key: [0u8; 32],
+            peer_public_key: PublicKey::from(peer_public_bytes),
+        };
+        session.send_key.copy_from_slice(&keys[..32]);
+        session.recv_key.copy_from_slice(&keys[32..]);
+
+        let mut sessions = self.session_keys.write().unwrap();
+        sessions.insert(stored.peer_id.clone(), session);
         Ok(())
     }
 
@@ -127,46 +352,55 @@ impl CryptoManager {
         let kp = self.device_keypair.read().unwrap();
         let keypair = kp.as_ref().ok_or("No keypair generated")?;
 
-        // Perform ECDH key exchange
-        let secret = StaticSecret::from(keypair.secret_key);
-        let shared_secret_dh = secret.diffie_hellman(&peer_public);
-        
-        // Derive session key using SHA256
-        let mut hasher = Sha256::new();
-        hasher.update(shared_secret_dh.as_bytes());
-        let shared_secret: [u8; 32] = hasher.finalize().into();
+        // Perform ECDH key exchange (the DH output wipes itself when dropped)
+        let shared_secret_dh = keypair.secret_key.diffie_hellman(&peer_public);
 
+        // Derive the directional session keys with HKDF
+        let hkdf = Hkdf::<Sha256>::new(Some(SESSION_KDF_SALT), shared_secret_dh.as_bytes());
         let session = SessionKey {
-            shared_secret,
+            send_key: directional_key(&hkdf, &keypair.public_key, &peer_public)?,
+            recv_key: directional_key(&hkdf, &peer_public, &keypair.public_key)?,
             peer_public_key: peer_public,
         };
 
-        {
+        // Only a new session or a changed peer key needs persisting; the keys are otherwise the same
+        let to_store = {
             let mut sessions = self.session_keys.write().unwrap();
+            let changed = sessions.get(peer_id).is_none_or(|s| s.peer_public_key != peer_public);
+            let stored = if changed { Some(Self::seal_session(keypair, peer_id, &session)) } else { None };
             sessions.insert(peer_id.to_string(), session);
+            stored
+        };
+        drop(kp);
+
+        if let Some(stored) = to_store {
+            match stored {
+                Ok(stored) => {
+                    if let Some(listener) = self.on_session.read().unwrap().as_ref() {
+                        listener(stored);
+                    }
+                }
+                Err(e) => warn!("Couldn't seal session with {}: {}", peer_id, e),
+            }
         }
 
         Ok(())
     }
 
-    /// Encrypt a message for a peer
-    pub fn encrypt(&self, peer_id: &str, plaintext: &[u8]) -> Result<EncryptedEnvelope, String> {
+    /// Encrypt a message for a peer; `message_id` is bound into the envelope
+    pub fn encrypt(&self, peer_id: &str, message_id: &str, plaintext: &[u8]) -> Result<EncryptedEnvelope, String> {
         let sessions = self.session_keys.read().unwrap();
         let session = sessions.get(peer_id)
             .ok_or("No session established with peer")?;
 
         // Create cipher
-        let cipher = Aes256Gcm::new_from_slice(&session.shared_secret)
+        let cipher = XChaCha20Poly1305::new_from_slice(&session.send_key)
             .map_err(|e| e.to_string())?;
 
         // Generate random nonce
-        let mut nonce_bytes = [0u8; NONCE_SIZE];
+        let mut nonce_bytes = [0u8; SESSION_NONCE_SIZE];
         rand::thread_rng().fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        // Encrypt
-        let ciphertext = cipher.encrypt(nonce, plaintext)
-            .map_err(|e| e.to_string())?;
+        let nonce = XNonce::from_slice(&nonce_bytes);
 
         // Get our public key
         let kp = self.device_keypair.read().unwrap();
@@ -174,11 +408,22 @@ impl CryptoManager {
             .map(|k| BASE64.encode(k.public_key.as_bytes()))
             .unwrap_or_default();
 
-        Ok(EncryptedEnvelope {
+        let mut envelope = EncryptedEnvelope {
             nonce: BASE64.encode(nonce_bytes),
-            ciphertext: BASE64.encode(ciphertext),
+            ciphertext: String::new(),
             sender_public_key: public_key,
-        })
+            sender_id: self.device_id.read().unwrap().clone(),
+            recipient_id: peer_id.to_string(),
+            message_id: message_id.to_string(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        };
+
+        // Encrypt
+        let aad = envelope_aad(&envelope);
+        let ciphertext = cipher.encrypt(nonce, Payload { msg: plaintext, aad: &aad })
+            .map_err(|e| e.to_string())?;
+        envelope.ciphertext = BASE64.encode(ciphertext);
+        Ok(envelope)
     }
 
     /// Decrypt a message from a peer
@@ -187,8 +432,18 @@ impl CryptoManager {
         let session = sessions.get(peer_id)
             .ok_or("No session established with peer")?;
 
+        // The claimed sender has to be the session's peer, using the key we hold for it
+        if envelope.sender_id != peer_id
+            || envelope.sender_public_key != BASE64.encode(session.peer_public_key.as_bytes())
+        {
+            return Err("Envelope sender doesn't match the session".to_string());
+        }
+        if envelope.recipient_id != *self.device_id.read().unwrap() {
+            return Err("Envelope is addressed to another device".to_string());
+        }
+
         // Decode envelope
-        let nonce_bytes: [u8; NONCE_SIZE] = BASE64.decode(&envelope.nonce)
+        let nonce_bytes: [u8; SESSION_NONCE_SIZE] = BASE64.decode(&envelope.nonce)
             .map_err(|e| e.to_string())?
             .try_into()
             .map_err(|_| "Invalid nonce length")?;
@@ -197,29 +452,45 @@ impl CryptoManager {
             .map_err(|e| e.to_string())?;
 
         // Create cipher
-        let cipher = Aes256Gcm::new_from_slice(&session.shared_secret)
+        let cipher = XChaCha20Poly1305::new_from_slice(&session.recv_key)
             .map_err(|e| e.to_string())?;
 
-        let nonce = Nonce::from_slice(&nonce_bytes);
+        let nonce = XNonce::from_slice(&nonce_bytes);
 
         // Decrypt
-        let plaintext = cipher.decrypt(nonce, ciphertext.as_ref())
-            .map_err(|_| "Decryption failed - invalid ciphertext or key")?;
+        let aad = envelope_aad(envelope);
+        let plaintext = cipher.decrypt(nonce, Payload { msg: &ciphertext, aad: &aad })
+            .map_err(|_| "Decryption failed - invalid ciphertext, key or envelope header")?;
 
         Ok(plaintext)
     }
 
-    /// Encrypt a string message
-    pub fn encrypt_message(&self, peer_id: &str, message: &str) -> Result<EncryptedEnvelope, String> {
-        self.encrypt(peer_id, message.as_bytes())
+    /// Encrypt a string message; without a message id a random one is bound in
+    pub fn encrypt_message(&self, peer_id: &str, message_id: Option<&str>, message: &str) -> Result<EncryptedEnvelope, String> {
+        let message_id = message_id.map(String::from).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        self.encrypt(peer_id, &message_id, message.as_bytes())
     }
 
-    /// Decrypt a string message
-    pub fn decrypt_message(&self, peer_id: &str, envelope: &EncryptedEnvelope) -> Result<String, String> {
+    /// Decrypt a string message; with `message_id`, the envelope has to be bound to that message,
+    /// so one captured for another message can't stand in for it
+    pub fn decrypt_message(&self, peer_id: &str, envelope: &EncryptedEnvelope, message_id: Option<&str>) -> Result<String, String> {
+        if message_id.is_some_and(|id| id != envelope.message_id) {
+            return Err("Envelope belongs to another message".to_string());
+        }
         let plaintext = self.decrypt(peer_id, envelope)?;
         String::from_utf8(plaintext).map_err(|e| e.to_string())
     }
 
+    /// Decrypt a string message that was sent just now (live exchanges such as device sync):
+    /// one encrypted more than MAX_ENVELOPE_SKEW_MS from our clock is refused as a replay
+    pub fn decrypt_fresh(&self, peer_id: &str, envelope: &EncryptedEnvelope) -> Result<String, String> {
+        let skew = chrono::Utc::now().timestamp_millis().saturating_sub(envelope.timestamp);
+        if skew.abs() > MAX_ENVELOPE_SKEW_MS {
+            return Err("Envelope is too old or from the future".to_string());
+        }
+        self.decrypt_message(peer_id, envelope, None)
+    }
+
     #[allow(dead_code)]
     /// Check if we have a session with a peer
     pub fn has_session(&self, peer_id: &str) -> bool {
@@ -227,6 +498,13 @@ impl CryptoManager {
         sessions.contains_key(peer_id)
     }
 
+    /// Whether the session with `peer_id` was made with this key of theirs
+    pub fn has_session_with(&self, peer_id: &str, peer_public_key_b64: &str) -> bool {
+        let sessions = self.session_keys.read().unwrap();
+        sessions.get(peer_id)
+            .is_some_and(|s| BASE64.encode(s.peer_public_key.as_bytes()) == peer_public_key_b64)
+    }
+
     #[allow(dead_code)]
     /// Remove a session
     pub fn remove_session(&self, peer_id: &str) {
@@ -235,7 +513,7 @@ impl CryptoManager {
     }
 #[allow(dead_code)]
     
-    /// Clear all sessions
+    /// Clear all sessions; their keys are wiped as they're dropped
     pub fn clear_sessions(&self) {
         let mut sessions = self.session_keys.write().unwrap();
         sessions.clear();
@@ -257,6 +535,21 @@ pub fn generate_checksum(data: &[u8]) -> String {
     hex::encode(result)
 }
 
+/// SHA-256 of a file's content, read through a buffer rather than all at once
+pub fn file_checksum(path: &Path) -> std::io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
 /// Verify file integrity
 #[allow(dead_code)]
 pub fn verify_checksum(data: &[u8], expected: &str) -> bool {
@@ -270,6 +563,159 @@ pub fn generate_device_id() -> String {
     hex::encode(bytes)
 }
 
+/// Data sealed with a passphrase rather than a peer session (e.g. locked notes)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassphraseEnvelope {
+    pub salt: String,           // Base64 encoded Argon2 salt
+    pub nonce: String,          // Base64 encoded nonce
+    pub ciphertext: String,     // Base64 encoded ciphertext
+}
+
+/// Derive an AES-256 key from a passphrase with Argon2id (default parameters)
+fn passphrase_key(passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, String> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default().hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// Encrypt with a key derived from `passphrase`; a fresh salt and nonce every time
+pub fn encrypt_with_passphrase(plaintext: &str, passphrase: &str) -> Result<PassphraseEnvelope, String> {
+    let mut salt = [0u8; SALT_SIZE];
+    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+
+    let cipher = Aes256Gcm::new_from_slice(passphrase_key(passphrase, &salt)?.as_ref())
+        .map_err(|e| e.to_string())?;
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    Ok(PassphraseEnvelope {
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce_bytes),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+/// Decrypt a PassphraseEnvelope; fails on a wrong passphrase
+pub fn decrypt_with_passphrase(envelope: &PassphraseEnvelope, passphrase: &str) -> Result<String, String> {
+    let salt = BASE64.decode(&envelope.salt).map_err(|e| e.to_string())?;
+    let nonce_bytes: [u8; NONCE_SIZE] = BASE64.decode(&envelope.nonce)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "Invalid nonce length")?;
+    let ciphertext = BASE64.decode(&envelope.ciphertext)
+        .map_err(|e| e.to_string())?;
+
+    let cipher = Aes256Gcm::new_from_slice(passphrase_key(passphrase, &salt)?.as_ref())
+        .map_err(|e| e.to_string())?;
+    let plaintext = cipher.decrypt(Nonce::from_slice(&nonce_bytes), ciphertext.as_ref())
+        .map_err(|_| "Wrong passphrase")?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+/// Up to `limit` bytes from `input`; fewer only at the end of it
+fn read_up_to(input: &mut impl Read, limit: usize) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::with_capacity(limit);
+    input.take(limit as u64).read_to_end(&mut buf).map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+/// Associated data of sealed chunk `index`; binding the last-chunk flag catches truncation
+fn sealed_chunk_aad(index: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0u8; 9];
+    aad[..8].copy_from_slice(&index.to_be_bytes());
+    aad[8] = last as u8;
+    aad
+}
+
+/// Encrypt the file at `src` into `dest` with a key derived from `passphrase`, a chunk at a
+/// time: magic and salt, then per chunk nonce | last flag | length | XChaCha20-Poly1305 ciphertext
+pub fn seal_file(src: &Path, dest: &Path, passphrase: &str) -> Result<(), String> {
+    let mut input = BufReader::new(File::open(src).map_err(|e| e.to_string())?);
+    let mut output = BufWriter::new(File::create(dest).map_err(|e| e.to_string())?);
+    let mut salt = [0u8; SALT_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let cipher = XChaCha20Poly1305::new_from_slice(passphrase_key(passphrase, &salt)?.as_ref())
+        .map_err(|e| e.to_string())?;
+    output.write_all(SEALED_FILE_MAGIC).and_then(|_| output.write_all(&salt))
+        .map_err(|e| e.to_string())?;
+
+    let mut chunk = read_up_to(&mut input, SEALED_CHUNK_SIZE)?;
+    let mut index = 0u64;
+    loop {
+        // A full chunk may still be the last one; only reading on tells
+        let next = if chunk.len() == SEALED_CHUNK_SIZE { read_up_to(&mut input, SEALED_CHUNK_SIZE)? } else { Vec::new() };
+        let last = next.is_empty();
+        let mut nonce = [0u8; SESSION_NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let aad = sealed_chunk_aad(index, last);
+        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), Payload { msg: &chunk, aad: &aad })
+            .map_err(|e| e.to_string())?;
+        output.write_all(&nonce)
+            .and_then(|_| output.write_all(&[last as u8]))
+            .and_then(|_| output.write_all(&(ciphertext.len() as u32).to_be_bytes()))
+            .and_then(|_| output.write_all(&ciphertext))
+            .map_err(|e| e.to_string())?;
+        if last {
+            break;
+        }
+        chunk = next;
+        index += 1;
+    }
+    output.flush().map_err(|e| e.to_string())
+}
+
+/// Decrypt a file written by seal_file into `dest`. Fails on a wrong passphrase and on any
+/// changed, reordered, missing or extra chunk; `dest` may then hold part of the plaintext.
+pub fn open_sealed_file(src: &Path, dest: &Path, passphrase: &str) -> Result<(), String> {
+    let mut input = BufReader::new(File::open(src).map_err(|e| e.to_string())?);
+    let mut output = BufWriter::new(File::create(dest).map_err(|e| e.to_string())?);
+    let header = read_up_to(&mut input, SEALED_FILE_MAGIC.len() + SALT_SIZE)?;
+    if header.len() < SEALED_FILE_MAGIC.len() + SALT_SIZE || !header.starts_with(SEALED_FILE_MAGIC) {
+        return Err("The file isn't encrypted with a backup passphrase".to_string());
+    }
+    let salt = &header[SEALED_FILE_MAGIC.len()..];
+    let cipher = XChaCha20Poly1305::new_from_slice(passphrase_key(passphrase, salt)?.as_ref())
+        .map_err(|e| e.to_string())?;
+
+    let truncated = || "The encrypted file is truncated".to_string();
+    let mut index = 0u64;
+    loop {
+        let frame = read_up_to(&mut input, SESSION_NONCE_SIZE + 5)?;
+        if frame.len() < SESSION_NONCE_SIZE + 5 {
+            return Err(truncated());
+        }
+        let (nonce, rest) = frame.split_at(SESSION_NONCE_SIZE);
+        let last = match rest[0] {
+            0 => false,
+            1 => true,
+            _ => return Err("The encrypted file is damaged".to_string()),
+        };
+        let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        if len > SEALED_CHUNK_SIZE + 16 {
+            return Err("The encrypted file is damaged".to_string());
+        }
+        let ciphertext = read_up_to(&mut input, len)?;
+        if ciphertext.len() < len {
+            return Err(truncated());
+        }
+        let aad = sealed_chunk_aad(index, last);
+        let plaintext = cipher.decrypt(XNonce::from_slice(nonce), Payload { msg: &ciphertext, aad: &aad })
+            .map_err(|_| "Wrong passphrase, or the file was changed")?;
+        output.write_all(&plaintext).map_err(|e| e.to_string())?;
+        if last {
+            break;
+        }
+        index += 1;
+    }
+    if !read_up_to(&mut input, 1)?.is_empty() {
+        return Err("The encrypted file has data after its end".to_string());
+    }
+    output.flush().map_err(|e| e.to_string())
+}
+
 // Add hex encoding helper
 mod hex {
     pub fn encode(bytes: impl AsRef<[u8]>) -> String {
@@ -291,6 +737,8 @@ mod tests {
 
         let id_a = "device_a";
         let id_b = "device_b";
+        crypto_a.set_device_id(id_a);
+        crypto_b.set_device_id(id_b);
 
         // Establish session
         crypto_a.establish_session(id_b, &pub_b).unwrap();
@@ -298,12 +746,133 @@ mod tests {
 
         // Encrypt message from A to B
         let message = "Hello, secure world!";
-        let envelope = crypto_a.encrypt_message(id_b, message).unwrap();
+        let envelope = crypto_a.encrypt_message(id_b, Some("m1"), message).unwrap();
 
         // Decrypt at B
-        let decrypted = crypto_b.decrypt_message(id_a, &envelope).unwrap();
+        let decrypted = crypto_b.decrypt_message(id_a, &envelope, Some("m1")).unwrap();
 
         assert_eq!(message, decrypted);
+        // Bound to its message, and to the time it was encrypted
+        assert!(crypto_b.decrypt_message(id_a, &envelope, Some("m2")).is_err());
+        assert!(crypto_b.decrypt_fresh(id_a, &envelope).is_ok());
+        let mut stale = envelope.clone();
+        stale.timestamp -= MAX_ENVELOPE_SKEW_MS + 1;
+        assert!(crypto_b.decrypt_fresh(id_a, &stale).is_err());
+
+        // The header is authenticated
+        let mut forged = envelope.clone();
+        forged.message_id = "m2".to_string();
+        assert!(crypto_b.decrypt_message(id_a, &forged, None).is_err());
+        let mut forged = envelope;
+        forged.sender_id = "device_c".to_string();
+        assert!(crypto_b.decrypt_message(id_a, &forged, None).is_err());
+    }
+
+    #[test]
+    fn test_session_keys_are_directional() {
+        let crypto_a = CryptoManager::new();
+        let crypto_b = CryptoManager::new();
+        let pub_a = crypto_a.generate_keypair();
+        let pub_b = crypto_b.generate_keypair();
+        crypto_a.set_device_id("device_a");
+        crypto_b.set_device_id("device_b");
+        crypto_a.establish_session("device_b", &pub_b).unwrap();
+        crypto_b.establish_session("device_a", &pub_a).unwrap();
+
+        let envelope = crypto_a.encrypt_message("device_b", None, "ping").unwrap();
+        assert_eq!(BASE64.decode(&envelope.nonce).unwrap().len(), SESSION_NONCE_SIZE);
+        // A's own receive key can't open what A sent
+        assert!(crypto_a.decrypt_message("device_b", &envelope, None).is_err());
+        let reply = crypto_b.encrypt_message("device_a", None, "pong").unwrap();
+        assert_eq!(crypto_a.decrypt_message("device_b", &reply, None).unwrap(), "pong");
+    }
+
+    #[test]
+    fn test_stored_session_survives_restart() {
+        let crypto_a = CryptoManager::new();
+        let crypto_b = CryptoManager::new();
+        let pub_a = crypto_a.generate_keypair();
+        let pub_b = crypto_b.generate_keypair();
+        crypto_a.set_device_id("device_a");
+        crypto_b.set_device_id("device_b");
+        let stored = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = stored.clone();
+        crypto_a.set_session_listener(Box::new(move |s| sink.lock().unwrap().push(s)));
+        crypto_a.establish_session("device_b", &pub_b).unwrap();
+        crypto_a.establish_session("device_b", &pub_b).unwrap();
+        crypto_b.establish_session("device_a", &pub_a).unwrap();
+        // Re-establishing with the same key doesn't store it again
+        assert_eq!(stored.lock().unwrap().len(), 1);
+        let session = stored.lock().unwrap()[0].clone();
+
+        // Same device key after the restart: the session comes back without a new handshake
+        let restarted = CryptoManager::new();
+        restarted.load_keypair(&crypto_a.export_secret_key().unwrap()).unwrap();
+        restarted.set_device_id("device_a");
+        restarted.restore_session(&session).unwrap();
+        let envelope = restarted.encrypt_message("device_b", None, "still here").unwrap();
+        assert_eq!(crypto_b.decrypt_message("device_a", &envelope, None).unwrap(), "still here");
+
+        // Another device key can't unseal it, nor can it be moved to another peer
+        let other = CryptoManager::new();
+        other.generate_keypair();
+        assert!(other.restore_session(&session).is_err());
+        let moved = StoredSession { peer_id: "device_c".to_string(), ..session };
+        assert!(restarted.restore_session(&moved).is_err());
+    }
+
+    #[test]
+    fn test_identity_signs_session_key() {
+        let crypto = CryptoManager::new();
+        crypto.set_device_id("device_a");
+        crypto.generate_identity();
+        let session_key = crypto.generate_keypair();
+        let proof = crypto.identity_proof().unwrap();
+        assert!(verify_identity_proof("device_a", &session_key, &proof).is_ok());
+        // Bound to the device id and to this session key
+        assert!(verify_identity_proof("device_b", &session_key, &proof).is_err());
+        let rotated = crypto.generate_keypair();
+        assert!(verify_identity_proof("device_a", &rotated, &proof).is_err());
+        // Rotation keeps the identity
+        assert_eq!(crypto.identity_proof().unwrap().identity_key, proof.identity_key);
+    }
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        let envelope = encrypt_with_passphrase("wifi: hunter2", "correct horse").unwrap();
+        assert_eq!(decrypt_with_passphrase(&envelope, "correct horse").unwrap(), "wifi: hunter2");
+        assert!(decrypt_with_passphrase(&envelope, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_sealed_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("pingo-sealed-{}", generate_device_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (plain, sealed, opened) = (dir.join("plain"), dir.join("sealed"), dir.join("opened"));
+        // Exactly two chunks, so the last one is full
+        let data: Vec<u8> = (0..2 * SEALED_CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&plain, &data).unwrap();
+
+        seal_file(&plain, &sealed, "correct horse").unwrap();
+        assert!(!std::fs::read(&sealed).unwrap().windows(64).any(|w| data.starts_with(w)));
+        open_sealed_file(&sealed, &opened, "correct horse").unwrap();
+        assert_eq!(std::fs::read(&opened).unwrap(), data);
+        assert!(open_sealed_file(&sealed, &opened, "wrong").is_err());
+
+        // Dropping the last chunk doesn't pass as a shorter file
+        let bytes = std::fs::read(&sealed).unwrap();
+        let first_frame = SEALED_FILE_MAGIC.len() + SALT_SIZE + SESSION_NONCE_SIZE + 5 + SEALED_CHUNK_SIZE + 16;
+        std::fs::write(&sealed, &bytes[..first_frame]).unwrap();
+        assert!(open_sealed_file(&sealed, &opened, "correct horse").is_err());
+
+        // Neither does an unencrypted file
+        assert!(open_sealed_file(&plain, &opened, "correct horse").is_err());
+
+        std::fs::write(&plain, b"").unwrap();
+        seal_file(&plain, &sealed, "correct horse").unwrap();
+        open_sealed_file(&sealed, &opened, "correct horse").unwrap();
+        assert!(std::fs::read(&opened).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
 
@@ -316,8 +885,8 @@ THREAT MODEL & ENCRYPTION FLOW:
 │                                                                      │
 │  THREATS MITIGATED:                                                  │
 │  ├── Man-in-the-middle attacks (ECDH key exchange)                 │
-│  ├── Message interception (AES-256-GCM encryption)                 │
-│  ├── Message tampering (GCM authentication tag)                    │
+│  ├── Message interception (XChaCha20-Poly1305 encryption)          │
+│  ├── Message tampering (Poly1305 authentication tag)               │
 │  ├── Replay attacks (unique nonce per message)                     │
 │  └── Key compromise (per-session derived keys)                     │
 │                                                                      │
@@ -325,9 +894,10 @@ THREAT MODEL & ENCRYPTION FLOW:
 │                                                                      │
 │  1. KEY GENERATION (on first run):                                   │
 │     ┌─────────────────────────────────────────────┐                 │
-│     │  Generate X25519 key pair                    │                 │
-│     │  Store private key securely                 │                 │
-│     │  Advertise public key via mDNS              │                 │
+│     │  Generate Ed25519 identity key (long-term)  │                 │
+│     │  Generate X25519 session key (weekly)       │                 │
+│     │  Identity signs session key + device id     │                 │
+│     │  Advertise both + signature via discovery   │                 │
 │     └─────────────────────────────────────────────┘                 │
 │                                                                      │
 │  2. SESSION ESTABLISHMENT:                                           │
@@ -343,14 +913,14 @@ THREAT MODEL & ENCRYPTION FLOW:
 │          │ ECDH(PrivateKey_A, PublicKey_B)                          │
 │          │ = SharedSecret                │                          │
 │          │                               │                          │
-│          │ DeriveKey(SharedSecret)       │                          │
-│          │ = SessionKey (AES-256)        │                          │
+│          │ HKDF-SHA256(SharedSecret)     │                          │
+│          │ = SendKey, ReceiveKey         │                          │
 │          │                               │                          │
 │                                                                      │
 │  3. MESSAGE ENCRYPTION:                                              │
 │     ┌─────────────────────────────────────────────┐                 │
-│     │  Generate random 12-byte nonce               │                 │
-│     │  Encrypt: AES-256-GCM(SessionKey, nonce)    │                 │
+│     │  Generate random 24-byte nonce               │                 │
+│     │  Encrypt: XChaCha20-Poly1305(SendKey, nonce)│                 │
 │     │  Create envelope: {nonce, ciphertext, pubkey}│                 │
 │     │  Send via WebRTC DataChannel                │                 │
 │     └─────────────────────────────────────────────┘                 │
@@ -358,8 +928,8 @@ THREAT MODEL & ENCRYPTION FLOW:
 │  4. MESSAGE DECRYPTION:                                              │
 │     ┌─────────────────────────────────────────────┐                 │
 │     │  Extract nonce from envelope                │                 │
-│     │  Decrypt: AES-256-GCM(SessionKey, nonce)    │                 │
-│     │  Verify auth tag (implicit in GCM)          │                 │
+│     │  Decrypt: XChaCha20-Poly1305(ReceiveKey, n) │                 │
+│     │  Verify auth tag (implicit in Poly1305)     │                 │
 │     │  Return plaintext                           │                 │
 │     └─────────────────────────────────────────────┘                 │
 │                                                                      │
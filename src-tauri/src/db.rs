@@ -5,9 +5,12 @@ use rusqlite::{Connection, Result as SqliteResult, params};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Mutex;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use crate::crypto::StoredSession;
+use crate::delivery_status::MessageStatus;
+use crate::query_cache::{PageKey, QueryCache};
 
-pub struct Database { conn: Mutex<Connection> }
+pub struct Database { conn: Mutex<Connection>, cache: QueryCache, on_change: Mutex<Option<ChangeListener>> }
 
 // ============ DATA MODELS ============
 
@@ -17,6 +20,54 @@ pub struct User {
     pub public_key: Option<String>, pub avatar_path: Option<String>,
     pub bio: Option<String>, pub designation: Option<String>,
     pub last_seen: Option<String>, pub is_online: bool, pub created_at: String,
+    #[serde(flatten)]
+    pub profile: ProfileFields,
+}
+
+/// Directory info for org deployments; every field is optional
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ProfileFields {
+    #[serde(default)] pub email: Option<String>,
+    #[serde(default)] pub phone: Option<String>,
+    #[serde(default)] pub department: Option<String>,
+    #[serde(default)] pub pronouns: Option<String>,
+    /// IANA name, e.g. "Europe/Berlin"
+    #[serde(default)] pub timezone: Option<String>,
+}
+
+/// Custom status ("🌴 Out until Monday"); gone once `expires_at` (RFC 3339) has passed
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct UserStatus {
+    #[serde(default)] pub emoji: Option<String>,
+    #[serde(default)] pub text: Option<String>,
+    #[serde(default)] pub expires_at: Option<String>,
+}
+
+impl UserStatus {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|t| t <= Utc::now())
+    }
+}
+
+impl ProfileFields {
+    const MAX_LEN: usize = 128;
+
+    /// Trimmed, empty fields dropped, overlong ones cut
+    pub fn normalized(self) -> Self {
+        let clean = |v: Option<String>| v.map(|s| s.trim().chars().take(Self::MAX_LEN).collect::<String>())
+            .filter(|s| !s.is_empty());
+        ProfileFields { email: clean(self.email), phone: clean(self.phone), department: clean(self.department),
+                        pronouns: clean(self.pronouns), timezone: clean(self.timezone) }
+    }
+
+    /// Fields set here, the rest from `other`
+    pub fn or(self, other: ProfileFields) -> Self {
+        ProfileFields { email: self.email.or(other.email), phone: self.phone.or(other.phone),
+                        department: self.department.or(other.department), pronouns: self.pronouns.or(other.pronouns),
+                        timezone: self.timezone.or(other.timezone) }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -25,6 +76,10 @@ pub struct Message {
     pub content: String, pub message_type: String,
     pub file_path: Option<String>, pub is_read: bool, pub is_delivered: bool,
     pub created_at: String,
+    /// Lamport clock the sender stamped it with (see next_lamport); 0 for messages from before
+    #[serde(default)] pub lamport: i64,
+    /// Delivery state; is_read/is_delivered are kept in step with it for older peers
+    #[serde(default)] pub status: MessageStatus,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -39,19 +94,33 @@ pub struct FileRecord {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings { pub key: String, pub value: String }
 
+/// A setting replicated between linked devices; the newest `updated_at` wins
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SyncedSetting { pub key: String, pub value: String, pub updated_at: String }
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Note {
     pub id: String, pub title: String, pub content: String, pub color: String,
     pub pinned: bool, pub category: Option<String>,
     pub created_at: String, pub updated_at: String,
+    /// Owner's device id when this is a copy of a note shared with us
+    #[serde(default)] pub shared_by: Option<String>,
+    /// Peers edits are exchanged with (see note_sharing)
+    #[serde(default)] pub collaborators: Vec<String>,
+    /// `content` holds a passphrase-encrypted PassphraseEnvelope as JSON
+    #[serde(default)] pub locked: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Group {
     pub id: String, pub name: String, pub created_by: String,
     pub avatar_color: Option<String>, pub created_at: String,
+    /// "group", or "channel" where only admins post (see channels)
+    #[serde(default = "default_group_kind")] pub kind: String,
 }
 
+fn default_group_kind() -> String { "group".to_string() }
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GroupMember {
     pub group_id: String, pub user_id: String, pub username: String,
@@ -63,6 +132,21 @@ pub struct GroupMessage {
     pub id: String, pub group_id: String, pub sender_id: String,
     pub sender_name: String, pub content: String, pub message_type: String,
     pub created_at: String,
+    #[serde(default)] pub lamport: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkedDevice {
+    pub device_id: String, pub name: String, pub linked_at: String, pub last_sync: Option<String>,
+}
+
+/// Per-conversation overrides (chat_id = peer device_id or group_id); None keeps the default
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ChatSettings {
+    pub chat_id: String, pub nickname: Option<String>, pub color: Option<String>,
+    pub wallpaper: Option<String>,
+    /// "default", "all" (even during do-not-disturb) or "muted"
+    pub notifications: String, pub updated_at: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -70,28 +154,255 @@ pub struct LastMessageInfo {
     pub peer_id: String, pub content: String, pub created_at: String, pub is_from_me: bool,
 }
 
+/// Outgoing webhook; None filters match everything
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Webhook {
+    pub id: String, pub url: String, pub peer_id: Option<String>, pub group_id: Option<String>,
+    pub message_type: Option<String>, pub enabled: bool, pub created_at: String,
+    pub last_status: Option<String>, pub last_delivery_at: Option<String>,
+}
+
+/// Auto-responder rule (see auto_reply). Empty `pattern`, `peer_ids` or `days` match everything;
+/// times are local "HH:MM", `days` are ISO weekdays (1 = Monday)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AutoReplyRule {
+    pub id: String, pub name: String, pub pattern: String, pub match_mode: String,
+    pub peer_ids: Vec<String>, pub days: Vec<u32>, pub start_time: Option<String>, pub end_time: Option<String>,
+    pub active_from: Option<String>, pub active_until: Option<String>, pub response: String,
+    pub cooldown_minutes: i64, pub enabled: bool, pub created_at: String,
+}
+
+/// Marks a deleted note so the delete replicates to linked devices instead of the note reappearing
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteTombstone { pub id: String, pub deleted_at: String }
+
+/// A file attached to a note; `file_path` is our own copy in the profile's app dir
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteAttachment {
+    pub id: String, pub note_id: String, pub file_name: String, pub file_path: String,
+    pub mime_type: String, pub size: i64, pub created_at: String,
+}
+
+/// One reminder per note. `remind_at` is UTC RFC 3339; `repeat` is none/daily/weekly/monthly;
+/// `status` is pending or done.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteReminder {
+    pub note_id: String, pub remind_at: String, pub repeat: String, pub status: String,
+    pub last_fired_at: Option<String>,
+}
+
+/// A meeting we hosted, were invited to or joined; built from meeting signaling
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MeetingRecord {
+    pub id: String, pub host_id: String, pub host_name: Option<String>,
+    pub started_at: String, pub ended_at: Option<String>,
+    /// Saved whiteboard PNG, if one was kept
+    #[serde(default)] pub whiteboard_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MeetingParticipant {
+    pub meeting_id: String, pub user_id: String, pub username: Option<String>,
+    pub joined_at: String, pub left_at: Option<String>,
+}
+
+/// A meeting planned for later. `participants` excludes the host; `reminded` is set once the
+/// five-minute reminder has fired.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduledMeeting {
+    pub id: String, pub title: String, pub host_id: String, pub host_name: String,
+    pub start_at: String, pub duration_minutes: i64, pub participants: Vec<String>,
+    pub created_at: String, #[serde(default)] pub reminded: bool,
+}
+
+/// Shared-media gallery query. `before`, `before_lamport` and `before_id` are the created_at,
+/// lamport and id of the last item already loaded (items sharing a timestamp aren't skipped);
+/// `since`/`until` bound created_at (RFC 3339); `sender_id` limits to one side.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SharedMediaFilter {
+    #[serde(default)] pub media_type: Option<String>, #[serde(default)] pub sender_id: Option<String>,
+    #[serde(default)] pub since: Option<String>, #[serde(default)] pub until: Option<String>,
+    #[serde(default)] pub before: Option<String>, #[serde(default)] pub before_lamport: Option<i64>,
+    #[serde(default)] pub before_id: Option<String>, #[serde(default)] pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SharedMediaCount { pub message_type: String, pub count: i64 }
+
+/// One file we downloaded or received. `kind` is auto (chat attachment), save_as or transfer;
+/// `status` is downloading, complete, failed or cancelled. `url` is kept so it can be retried.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadRecord {
+    pub id: String, pub kind: String, pub source_peer: Option<String>, pub source_name: Option<String>,
+    pub message_id: Option<String>, pub url: Option<String>, pub file_name: String, pub file_type: String,
+    pub path: Option<String>, pub size: i64, pub status: String, pub error: Option<String>,
+    pub created_at: String, pub updated_at: String,
+    /// SHA-256 the sender announced; the download is verified against it
+    #[serde(default)] pub checksum: Option<String>,
+    /// Speech-to-text of a voice message (see transcription)
+    #[serde(default)] pub transcript: Option<String>,
+    /// Text read off an image (see ocr)
+    #[serde(default)] pub ocr_text: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DownloadFilter {
+    #[serde(default)] pub status: Option<String>, #[serde(default)] pub kind: Option<String>,
+    #[serde(default)] pub source_peer: Option<String>, #[serde(default)] pub limit: Option<i64>,
+}
+
+/// Word or phrase that raises a keyword alert wherever it shows up (see keyword_alerts)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlertKeyword { pub id: String, pub keyword: String, pub created_at: String }
+
+/// An image we sent compressed: `file_id` serves the compressed copy, `original_file_id` the
+/// untouched original for "download original" (see image_compression)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageVariant {
+    pub file_id: String, pub original_file_id: String, pub original_path: String, pub original_size: i64,
+    pub size: i64, pub mode: String, pub created_at: String,
+}
+
+/// One run of the backup scheduler (see backups); `trigger` is "scheduled" or "manual", `status`
+/// "ok" or "failed"
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupRecord {
+    pub id: String, pub started_at: String, pub finished_at: String, pub trigger: String, pub status: String,
+    pub path: Option<String>, pub size: i64, pub error: Option<String>,
+    #[serde(default)] pub remote: Option<String>, #[serde(default)] pub remote_error: Option<String>,
+}
+
+/// A page a message linked to, saved when the message came or went so the context survives the
+/// page changing (see link_snapshots). `path` is the saved HTML; `status` is "saved" or "failed".
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkSnapshot {
+    pub id: String, pub message_id: String, pub url: String, pub title: Option<String>,
+    pub excerpt: Option<String>, pub path: Option<String>, pub status: String, pub error: Option<String>,
+    pub created_at: String,
+}
+
+/// How far a peer's clock is from ours, last measured over Ping/Pong (see clock_skew).
+/// `offset_ms` is their clock minus ours.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PeerClock {
+    pub peer_id: String, pub offset_ms: i64, pub rtt_ms: i64, pub measured_at: String,
+}
+
+/// An attachment whose extracted text matched a search: a voice transcript or OCR text.
+/// Files we sent have no download, only a message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttachmentTextHit {
+    pub download_id: Option<String>, pub message_id: Option<String>, pub kind: String, pub file_name: String,
+    pub path: Option<String>, pub snippet: String,
+}
+
+/// A message translated into one language (see translation)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageTranslation {
+    pub message_id: String, pub lang: String, pub text: String,
+    /// Language the backend detected, when it reports one
+    pub source_lang: Option<String>,
+    pub created_at: String,
+}
+
+/// A contact tag and how many contacts carry it (see contact_tags)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TagCount { pub tag: String, pub count: i32 }
+
+/// User-defined folder for organizing the sidebar (see chat_folders)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatFolder { pub id: String, pub name: String, pub color: Option<String>, pub created_at: String }
+
+/// A chat filed in a folder; `name` is the contact or group name, empty if it's gone
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FolderChat {
+    pub chat_id: String, pub name: String, pub is_group: bool, pub added_at: String,
+    #[serde(default)]
+    pub unread: i32,
+}
+
+/// Something an unknown peer sent while privacy mode was on (see message_requests)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageRequest {
+    pub id: String, pub peer_id: String, pub sender_name: String,
+    /// "message", "file" or "group_invite"
+    pub kind: String, pub preview: String, pub created_at: String,
+}
+
+/// Which direct messages a write touched, for the event bus
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataChange {
+    /// Messages between these two users
+    Conversation(String, String),
+    /// Anything, or more than one conversation
+    Messages,
+}
+
+pub type ChangeListener = Box<dyn Fn(DataChange) + Send>;
+
+/// One message reduced to what chat statistics need
+#[derive(Debug, Clone)]
+pub struct ActivityRow { pub sender_id: String, pub message_type: String, pub created_at: String }
+
 // ============ DATABASE IMPLEMENTATION ============
 
 impl Database {
     pub fn get_db_path() -> PathBuf {
-        let instance = std::env::var("PINGO_INSTANCE").unwrap_or_default();
-        let app_name = if instance.is_empty() { "Pingo".to_string() } else { format!("Pingo_{}", instance) };
-        let app_dir = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from(".")).join(app_name);
+        let app_dir = crate::profiles::app_dir(&crate::profiles::active_profile());
         std::fs::create_dir_all(&app_dir).ok();
         app_dir.join("pingo.db")
     }
 
     pub fn new() -> SqliteResult<Self> {
         let conn = Connection::open(Self::get_db_path())?;
-        let db = Database { conn: Mutex::new(conn) };
+        let db = Database { conn: Mutex::new(conn), cache: QueryCache::default(), on_change: Mutex::new(None) };
         db.run_migrations()?;
         Ok(db)
     }
 
+    /// Swap the connection for the active profile's database (profile switch)
+    pub fn reopen(&self) -> SqliteResult<()> {
+        let conn = Connection::open(Self::get_db_path())?;
+        *self.conn.lock().unwrap() = conn;
+        self.messages_changed();
+        self.run_migrations()
+    }
+
+    /// Let go of the database file (the connection is swapped for an empty in-memory one) so it
+    /// can be moved; reopen() picks the file up again
+    pub fn release(&self) -> SqliteResult<()> {
+        *self.conn.lock().unwrap() = Connection::open_in_memory()?;
+        self.messages_changed();
+        Ok(())
+    }
+
+    /// Called after every write to direct messages (see event_bus)
+    pub fn set_change_listener(&self, listener: ChangeListener) {
+        *self.on_change.lock().unwrap() = Some(listener);
+    }
+
+    fn notify(&self, change: DataChange) {
+        if let Some(listener) = self.on_change.lock().unwrap().as_ref() { listener(change); }
+    }
+
+    fn conversation_changed(&self, user1: &str, user2: &str) {
+        self.cache.invalidate_conversation(user1, user2);
+        self.notify(DataChange::Conversation(user1.to_string(), user2.to_string()));
+    }
+
+    fn messages_changed(&self) {
+        self.cache.invalidate_all();
+        self.notify(DataChange::Messages);
+    }
+
     #[allow(dead_code)]
     pub fn new_in_memory() -> SqliteResult<Self> {
-        let conn = Connection::open_in_memory()?;
-        let db = Database { conn: Mutex::new(conn) };
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Wrap an open connection, bringing its schema up to date
+    pub(crate) fn from_connection(conn: Connection) -> SqliteResult<Self> {
+        let db = Database { conn: Mutex::new(conn), cache: QueryCache::default(), on_change: Mutex::new(None) };
         db.run_migrations()?;
         Ok(db)
     }
@@ -116,6 +427,17 @@ impl Database {
             )", [])?;
         let _ = conn.execute("ALTER TABLE users ADD COLUMN bio TEXT DEFAULT ''", []);
         let _ = conn.execute("ALTER TABLE users ADD COLUMN designation TEXT DEFAULT ''", []);
+        // Content hash of the avatar we have for the user; peers announce theirs so we only
+        // re-download on change
+        let _ = conn.execute("ALTER TABLE users ADD COLUMN avatar_hash TEXT", []);
+        // Extended profile (ProfileFields)
+        for col in &["email", "phone", "department", "pronouns", "timezone"] {
+            let _ = conn.execute(&format!("ALTER TABLE users ADD COLUMN {} TEXT", col), []);
+        }
+        // Custom status (UserStatus)
+        for col in &["status_emoji", "status_text", "status_expires_at"] {
+            let _ = conn.execute(&format!("ALTER TABLE users ADD COLUMN {} TEXT", col), []);
+        }
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS messages (
@@ -125,6 +447,22 @@ impl Database {
                 FOREIGN KEY (sender_id) REFERENCES users(id),
                 FOREIGN KEY (receiver_id) REFERENCES users(id)
             )", [])?;
+        // When the message was marked read; lets read state replicate to linked devices
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN read_at TEXT", []);
+        // Soft delete: hidden from every query until restored or purged
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN deleted_at TEXT", []);
+        // Sender's Lamport clock; breaks timestamp ties the same way on every device
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN lamport INTEGER NOT NULL DEFAULT 0", []);
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_messages_lamport ON messages(lamport)", [])?;
+        // Delivery state (see delivery_status); existing rows start from what their flags say
+        if conn.execute("ALTER TABLE messages ADD COLUMN status TEXT NOT NULL DEFAULT 'queued'", []).is_ok() {
+            conn.execute(
+                "UPDATE messages SET status=CASE WHEN is_read=1 THEN 'read' WHEN is_delivered=1 THEN 'delivered' ELSE 'sent' END",
+                [])?;
+        }
+        // Delivery queue bookkeeping (see delivery_retry): attempts so far and when the next is due
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN send_attempts INTEGER NOT NULL DEFAULT 0", []);
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN retry_at TEXT", []);
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS files (
@@ -135,12 +473,17 @@ impl Database {
             )", [])?;
 
         conn.execute("CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)", [])?;
+        // When the value last changed, for last-writer-wins settings sync; NULL for older rows
+        let _ = conn.execute("ALTER TABLE settings ADD COLUMN updated_at TEXT", []);
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS peers (
                 device_id TEXT PRIMARY KEY, username TEXT NOT NULL, ip_address TEXT NOT NULL,
                 port INTEGER NOT NULL, public_key TEXT, last_seen TEXT NOT NULL, is_trusted INTEGER DEFAULT 0
             )", [])?;
+        // Checksum of the key trust was given to (see trust); rows trusted before it was kept
+        // count as untrusted until the peer is verified again
+        let _ = conn.execute("ALTER TABLE peers ADD COLUMN trusted_key TEXT", []);
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS notes (
@@ -148,6 +491,9 @@ impl Database {
                 color TEXT DEFAULT '#fef3c7', pinned INTEGER DEFAULT 0, category TEXT DEFAULT '',
                 created_at TEXT NOT NULL, updated_at TEXT NOT NULL
             )", [])?;
+        let _ = conn.execute("ALTER TABLE notes ADD COLUMN shared_by TEXT", []);
+        let _ = conn.execute("ALTER TABLE notes ADD COLUMN collaborators TEXT NOT NULL DEFAULT ''", []);
+        let _ = conn.execute("ALTER TABLE notes ADD COLUMN locked INTEGER NOT NULL DEFAULT 0", []);
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS groups (
@@ -162,6 +508,7 @@ impl Database {
                 PRIMARY KEY (group_id, user_id),
                 FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
             )", [])?;
+        let _ = conn.execute("ALTER TABLE groups ADD COLUMN kind TEXT NOT NULL DEFAULT 'group'", []);
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS group_messages (
@@ -170,6 +517,203 @@ impl Database {
                 message_type TEXT DEFAULT 'text', created_at TEXT NOT NULL,
                 FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
             )", [])?;
+        let _ = conn.execute("ALTER TABLE group_messages ADD COLUMN lamport INTEGER NOT NULL DEFAULT 0", []);
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_group_messages_lamport ON group_messages(lamport)", [])?;
+
+        // Per-chat notification sound overrides (chat_id = peer device_id or group_id)
+        conn.execute("CREATE TABLE IF NOT EXISTS chat_sounds (chat_id TEXT PRIMARY KEY, sound_id TEXT NOT NULL)", [])?;
+
+        // Per-chat appearance and behaviour overrides (see chat_settings)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS chat_settings (
+                chat_id TEXT PRIMARY KEY, nickname TEXT, color TEXT, wallpaper TEXT,
+                notifications TEXT NOT NULL DEFAULT 'default', updated_at TEXT NOT NULL
+            )", [])?;
+
+        // Other devices sharing this identity (see device_sync)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS linked_devices (
+                device_id TEXT PRIMARY KEY, name TEXT NOT NULL DEFAULT '',
+                linked_at TEXT NOT NULL, last_sync TEXT
+            )", [])?;
+
+        // Outgoing webhooks (see webhooks)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS webhooks (
+                id TEXT PRIMARY KEY, url TEXT NOT NULL, peer_id TEXT, group_id TEXT, message_type TEXT,
+                enabled INTEGER NOT NULL DEFAULT 1, created_at TEXT NOT NULL,
+                last_status TEXT, last_delivery_at TEXT
+            )", [])?;
+
+        // Auto-responder rules; peer_ids and days are comma-separated
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS auto_reply_rules (
+                id TEXT PRIMARY KEY, name TEXT NOT NULL DEFAULT '', pattern TEXT NOT NULL DEFAULT '',
+                match_mode TEXT NOT NULL DEFAULT 'contains', peer_ids TEXT NOT NULL DEFAULT '',
+                days TEXT NOT NULL DEFAULT '', start_time TEXT, end_time TEXT, active_from TEXT, active_until TEXT,
+                response TEXT NOT NULL, cooldown_minutes INTEGER NOT NULL DEFAULT 60,
+                enabled INTEGER NOT NULL DEFAULT 1, created_at TEXT NOT NULL
+            )", [])?;
+
+        // Deleted note ids, kept so deletes win over older copies on linked devices
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS note_tombstones (
+                id TEXT PRIMARY KEY, deleted_at TEXT NOT NULL
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS note_attachments (
+                id TEXT PRIMARY KEY, note_id TEXT NOT NULL, file_name TEXT NOT NULL, file_path TEXT NOT NULL,
+                mime_type TEXT NOT NULL, size INTEGER NOT NULL DEFAULT 0, created_at TEXT NOT NULL
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS note_reminders (
+                note_id TEXT PRIMARY KEY, remind_at TEXT NOT NULL, repeat TEXT NOT NULL DEFAULT 'none',
+                status TEXT NOT NULL DEFAULT 'pending', last_fired_at TEXT
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS meetings (
+                id TEXT PRIMARY KEY, host_id TEXT NOT NULL, host_name TEXT,
+                started_at TEXT NOT NULL, ended_at TEXT
+            )", [])?;
+        let _ = conn.execute("ALTER TABLE meetings ADD COLUMN whiteboard_path TEXT", []);
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS meeting_participants (
+                meeting_id TEXT NOT NULL, user_id TEXT NOT NULL, username TEXT,
+                joined_at TEXT NOT NULL, left_at TEXT,
+                PRIMARY KEY (meeting_id, user_id)
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS scheduled_meetings (
+                id TEXT PRIMARY KEY, title TEXT NOT NULL, host_id TEXT NOT NULL, host_name TEXT NOT NULL DEFAULT '',
+                start_at TEXT NOT NULL, duration_minutes INTEGER NOT NULL DEFAULT 30,
+                participants TEXT NOT NULL DEFAULT '', created_at TEXT NOT NULL, reminded INTEGER NOT NULL DEFAULT 0
+            )", [])?;
+
+        // Invitations not yet acknowledged by the peer; resent while they're online
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS scheduled_meeting_invites (
+                meeting_id TEXT NOT NULL, peer_id TEXT NOT NULL, delivered_at TEXT,
+                PRIMARY KEY (meeting_id, peer_id)
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS downloads (
+                id TEXT PRIMARY KEY, kind TEXT NOT NULL, source_peer TEXT, source_name TEXT, message_id TEXT,
+                url TEXT, file_name TEXT NOT NULL, file_type TEXT NOT NULL DEFAULT 'file', path TEXT,
+                size INTEGER NOT NULL DEFAULT 0, status TEXT NOT NULL, error TEXT,
+                created_at TEXT NOT NULL, updated_at TEXT NOT NULL
+            )", [])?;
+        let _ = conn.execute("ALTER TABLE downloads ADD COLUMN checksum TEXT", []);
+        let _ = conn.execute("ALTER TABLE downloads ADD COLUMN transcript TEXT", []);
+        let _ = conn.execute("ALTER TABLE downloads ADD COLUMN ocr_text TEXT", []);
+        // OCR text of images we sent ourselves, which have no download row
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN ocr_text TEXT", []);
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS image_variants (
+                file_id TEXT PRIMARY KEY, original_file_id TEXT NOT NULL, original_path TEXT NOT NULL,
+                original_size INTEGER NOT NULL, size INTEGER NOT NULL, mode TEXT NOT NULL, created_at TEXT NOT NULL
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS backup_history (
+                id TEXT PRIMARY KEY, started_at TEXT NOT NULL, finished_at TEXT NOT NULL, trigger TEXT NOT NULL,
+                status TEXT NOT NULL, path TEXT, size INTEGER NOT NULL DEFAULT 0, error TEXT
+            )", [])?;
+        // Where the copy on the remote target went, or why it didn't (see backup_targets)
+        let _ = conn.execute("ALTER TABLE backup_history ADD COLUMN remote TEXT", []);
+        let _ = conn.execute("ALTER TABLE backup_history ADD COLUMN remote_error TEXT", []);
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS link_snapshots (
+                id TEXT PRIMARY KEY, message_id TEXT NOT NULL, url TEXT NOT NULL, title TEXT, excerpt TEXT,
+                path TEXT, status TEXT NOT NULL, error TEXT, created_at TEXT NOT NULL
+            )", [])?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_link_snapshots_message ON link_snapshots(message_id)", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS peer_clock (
+                peer_id TEXT PRIMARY KEY, offset_ms INTEGER NOT NULL, rtt_ms INTEGER NOT NULL, measured_at TEXT NOT NULL
+            )", [])?;
+
+        // Full-text index over text pulled out of attachments; `kind` says where it came from
+        conn.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS attachment_text USING fts5(
+                download_id UNINDEXED, message_id UNINDEXED, kind UNINDEXED, text
+            )", [])?;
+
+        // Conversations cleared for both sides: messages up to cleared_at are never stored again
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS conversation_tombstones (
+                peer_id TEXT PRIMARY KEY, cleared_at TEXT NOT NULL, cleared_by TEXT NOT NULL
+            )", [])?;
+
+        // Translations cache, keyed by message (direct or group) and target language
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS message_translations (
+                message_id TEXT NOT NULL, lang TEXT NOT NULL, text TEXT NOT NULL, source_lang TEXT,
+                created_at TEXT NOT NULL, PRIMARY KEY (message_id, lang)
+            )", [])?;
+
+        // Free-form labels on contacts ("ops", "hr"), stored lowercase
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS user_tags (
+                user_id TEXT NOT NULL, tag TEXT NOT NULL, created_at TEXT NOT NULL,
+                PRIMARY KEY (user_id, tag)
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS chat_folders (
+                id TEXT PRIMARY KEY, name TEXT NOT NULL UNIQUE COLLATE NOCASE, color TEXT,
+                created_at TEXT NOT NULL
+            )", [])?;
+        // A chat can be filed in several folders, like labels (chat_id = peer device_id or group_id)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS chat_folder_members (
+                folder_id TEXT NOT NULL, chat_id TEXT NOT NULL, added_at TEXT NOT NULL,
+                PRIMARY KEY (folder_id, chat_id),
+                FOREIGN KEY (folder_id) REFERENCES chat_folders(id) ON DELETE CASCADE
+            )", [])?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS alert_keywords (
+                id TEXT PRIMARY KEY, keyword TEXT NOT NULL UNIQUE COLLATE NOCASE, created_at TEXT NOT NULL
+            )", [])?;
+
+        // Privacy mode: what unknown peers sent, held until accepted; `payload` is the signaling message
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS message_requests (
+                id TEXT PRIMARY KEY, peer_id TEXT NOT NULL, sender_name TEXT NOT NULL DEFAULT '',
+                kind TEXT NOT NULL, preview TEXT NOT NULL DEFAULT '', payload TEXT NOT NULL, created_at TEXT NOT NULL
+            )", [])?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS approved_contacts (device_id TEXT PRIMARY KEY, approved_at TEXT NOT NULL)", [])?;
+        // The identity key each device first announced; later hellos must be signed with it
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS identity_pins (device_id TEXT PRIMARY KEY, identity_key TEXT NOT NULL, pinned_at TEXT NOT NULL)", [])?;
+        // Devices paired by PIN and the checksum of the key they proved (see pin_pairing)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS paired_peers (
+                device_id TEXT PRIMARY KEY, key_fingerprint TEXT NOT NULL, paired_at TEXT NOT NULL
+            )", [])?;
+
+        // SHA-256 -> a local copy of that content, so a file we already have isn't downloaded again
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS file_hashes (
+                checksum TEXT PRIMARY KEY, path TEXT NOT NULL, size INTEGER NOT NULL, indexed_at TEXT NOT NULL
+            )", [])?;
+
+        // Established E2E sessions, sealed under a key derived from the device key (see session_store)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS crypto_sessions (
+                peer_id TEXT PRIMARY KEY, peer_public_key TEXT NOT NULL, nonce TEXT NOT NULL,
+                sealed TEXT NOT NULL, updated_at TEXT NOT NULL
+            )", [])?;
 
         for idx in &[
             "CREATE INDEX IF NOT EXISTS idx_msg_sender   ON messages(sender_id)",
@@ -178,8 +722,13 @@ impl Database {
             "CREATE INDEX IF NOT EXISTS idx_msg_conv      ON messages(sender_id, receiver_id, created_at)",
             "CREATE INDEX IF NOT EXISTS idx_msg_unread    ON messages(receiver_id, is_read, sender_id)",
             "CREATE INDEX IF NOT EXISTS idx_notes_pin     ON notes(pinned, updated_at)",
+            "CREATE INDEX IF NOT EXISTS idx_noteatt_note  ON note_attachments(note_id)",
             "CREATE INDEX IF NOT EXISTS idx_grpmsg_grp    ON group_messages(group_id, created_at)",
             "CREATE INDEX IF NOT EXISTS idx_grpmem_grp    ON group_members(group_id)",
+            "CREATE INDEX IF NOT EXISTS idx_meetings_start ON meetings(started_at)",
+            "CREATE INDEX IF NOT EXISTS idx_schedmtg_start ON scheduled_meetings(start_at)",
+            "CREATE INDEX IF NOT EXISTS idx_downloads_created ON downloads(created_at)",
+            "CREATE INDEX IF NOT EXISTS idx_filehash_path ON file_hashes(path)",
         ] { conn.execute(idx, [])?; }
 
         Ok(())
@@ -190,10 +739,19 @@ impl Database {
     pub fn create_user(&self, user: &User) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT OR REPLACE INTO users (id,username,device_id,public_key,avatar_path,bio,designation,last_seen,is_online,created_at)
-             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)",
+            "INSERT INTO users (id,username,device_id,public_key,avatar_path,bio,designation,last_seen,is_online,created_at,
+                                email,phone,department,pronouns,timezone)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15)
+             ON CONFLICT(id) DO UPDATE SET username=excluded.username, device_id=excluded.device_id,
+                public_key=excluded.public_key, avatar_path=excluded.avatar_path, bio=excluded.bio,
+                designation=excluded.designation, last_seen=excluded.last_seen,
+                is_online=excluded.is_online, created_at=excluded.created_at,
+                email=excluded.email, phone=excluded.phone, department=excluded.department,
+                pronouns=excluded.pronouns, timezone=excluded.timezone",
             params![user.id, user.username, user.device_id, user.public_key, user.avatar_path,
-                    user.bio, user.designation, user.last_seen, user.is_online as i32, user.created_at],
+                    user.bio, user.designation, user.last_seen, user.is_online as i32, user.created_at,
+                    user.profile.email, user.profile.phone, user.profile.department,
+                    user.profile.pronouns, user.profile.timezone],
         )?;
         Ok(())
     }
@@ -204,11 +762,14 @@ impl Database {
             public_key: row.get(3)?, avatar_path: row.get(4)?,
             bio: row.get(5)?, designation: row.get(6)?,
             last_seen: row.get(7)?, is_online: row.get::<_, i32>(8)? != 0, created_at: row.get(9)?,
+            profile: ProfileFields { email: row.get(10)?, phone: row.get(11)?, department: row.get(12)?,
+                                     pronouns: row.get(13)?, timezone: row.get(14)? },
         })
     }
 
     const USER_COLS: &'static str =
-        "id,username,device_id,public_key,avatar_path,COALESCE(bio,'') as bio,COALESCE(designation,'') as designation,last_seen,is_online,created_at";
+        "id,username,device_id,public_key,avatar_path,COALESCE(bio,'') as bio,COALESCE(designation,'') as designation,last_seen,is_online,created_at,\
+         email,phone,department,pronouns,timezone";
 
     pub fn get_user(&self, id: &str) -> SqliteResult<Option<User>> {
         let conn = self.conn.lock().unwrap();
@@ -235,149 +796,441 @@ impl Database {
 
     #[allow(dead_code)]
     pub fn delete_user(&self, id: &str) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute("DELETE FROM users WHERE id=?1", params![id])?; Ok(())
+        self.conn.lock().unwrap().execute("DELETE FROM users WHERE id=?1", params![id])?;
+        self.messages_changed();
+        Ok(())
     }
 
     // ============ MESSAGE CRUD ============
 
+    /// Condition for inserting message ?1..?9: not covered by a cleared conversation
+    const NOT_CLEARED: &'static str =
+        "NOT EXISTS (SELECT 1 FROM conversation_tombstones t WHERE t.peer_id IN (?2, ?3) AND ?9 <= t.cleared_at)";
+
+    /// How far a peer's stamp may run ahead of our clock; anything further (a peer pushing the
+    /// clock toward overflow) is restamped locally
+    const MAX_LAMPORT_LEAD: i64 = 1_000_000;
+
+    /// The highest Lamport stamp seen, ours or a peer's
+    fn lamport_clock(conn: &Connection) -> SqliteResult<i64> {
+        conn.query_row("SELECT COALESCE(MAX(l),0) FROM (SELECT MAX(lamport) AS l FROM messages UNION ALL SELECT MAX(lamport) FROM group_messages)",
+                       [], |r| r.get(0))
+    }
+
+    /// One past the clock, or the clock itself once it's at the top
+    fn tick_lamport(clock: i64) -> i64 {
+        clock.checked_add(1).unwrap_or(clock)
+    }
+
+    /// The stamp to store a message with: its own when it has one within MAX_LAMPORT_LEAD of our
+    /// clock, the next local one otherwise
+    fn bounded_lamport(conn: &Connection, lamport: i64) -> SqliteResult<i64> {
+        let clock = Self::lamport_clock(conn)?;
+        Ok(if lamport > 0 && lamport <= clock.saturating_add(Self::MAX_LAMPORT_LEAD) { lamport } else { Self::tick_lamport(clock) })
+    }
+
+    /// The local Lamport clock: one past the highest stamp seen. Stamped on outgoing messages so
+    /// every device orders messages with equal timestamps the same way.
+    pub fn next_lamport(&self) -> SqliteResult<i64> {
+        Ok(Self::tick_lamport(Self::lamport_clock(&self.conn.lock().unwrap())?))
+    }
+
+    /// A message that's already stored (it came live and again from the offline queue, or from a
+    /// linked device) is merged rather than ignored: read and delivered only ever go from 0 to 1,
+    /// and a file path fills in one that was missing. Content, timestamps and deletion stay as stored.
+    /// Only a copy between the same sender and receiver merges; a reused id from anyone else
+    /// changes nothing.
+    const MERGE_EXISTING: &'static str =
+        "ON CONFLICT(id) DO UPDATE SET
+           is_read=MAX(is_read, excluded.is_read),
+           read_at=CASE WHEN is_read=0 AND excluded.is_read=1 THEN excluded.read_at ELSE read_at END,
+           is_delivered=MAX(is_delivered, excluded.is_delivered),
+           status=CASE WHEN MAX(is_read, excluded.is_read)=1 THEN 'read'
+                       WHEN MAX(is_delivered, excluded.is_delivered)=1 THEN 'delivered' ELSE status END,
+           file_path=COALESCE(file_path, excluded.file_path)
+         WHERE messages.sender_id=excluded.sender_id AND messages.receiver_id=excluded.receiver_id";
+
+    /// Status to store a message with; the flags win so peers that only send those are read right
+    fn stored_status(m: &Message) -> &'static str {
+        if m.is_read || m.is_delivered { MessageStatus::from_flags(m.is_read, m.is_delivered) } else { m.status }.as_str()
+    }
+
+    /// Messages without a Lamport stamp (local ones nobody stamped, older senders) or with one too
+    /// far ahead get the next one (see bounded_lamport)
     pub fn create_message(&self, message: &Message) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
+        let lamport = Self::bounded_lamport(&conn, message.lamport)?;
         conn.execute(
-            "INSERT OR IGNORE INTO messages (id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at)
-             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)",
+            &format!("INSERT INTO messages (id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,read_at,lamport,status)
+             SELECT ?1,?2,?3,?4,?5,?6,?7,?8,?9,CASE WHEN ?7=1 THEN ?10 END,?11,?12 WHERE {} {}",
+                     Self::NOT_CLEARED, Self::MERGE_EXISTING),
             params![message.id, message.sender_id, message.receiver_id, message.content,
                     message.message_type, message.file_path, message.is_read as i32,
-                    message.is_delivered as i32, message.created_at],
+                    message.is_delivered as i32, message.created_at, now(), lamport,
+                    Self::stored_status(message)],
         )?;
+        self.conversation_changed(&message.sender_id, &message.receiver_id);
         Ok(())
     }
 
+    /// Bulk insert in one transaction (history import); returns how many were new
+    pub fn import_messages(&self, messages: &[Message]) -> SqliteResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut inserted = 0;
+        {
+            let mut stmt = tx.prepare(&format!(
+                "INSERT OR IGNORE INTO messages (id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status)
+                 SELECT ?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11 WHERE {}", Self::NOT_CLEARED))?;
+            for m in messages {
+                // Imported history keeps missing stamps missing but can't push the clock either
+                let lamport = if m.lamport > 0 { Self::bounded_lamport(&tx, m.lamport)? } else { 0 };
+                inserted += stmt.execute(params![m.id, m.sender_id, m.receiver_id, m.content, m.message_type,
+                                                 m.file_path, m.is_read as i32, m.is_delivered as i32, m.created_at,
+                                                 lamport, Self::stored_status(m)])?;
+            }
+        }
+        tx.commit()?;
+        self.messages_changed();
+        Ok(inserted)
+    }
+
     fn row_to_message(row: &rusqlite::Row<'_>) -> rusqlite::Result<Message> {
         Ok(Message {
             id: row.get(0)?, sender_id: row.get(1)?, receiver_id: row.get(2)?,
             content: row.get(3)?, message_type: row.get(4)?, file_path: row.get(5)?,
             is_read: row.get::<_,i32>(6)?!=0, is_delivered: row.get::<_,i32>(7)?!=0,
-            created_at: row.get(8)?,
+            created_at: row.get(8)?, lamport: row.get(9)?,
+            status: MessageStatus::parse(&row.get::<_,String>(10)?),
         })
     }
 
     pub fn get_messages_between(&self, user1: &str, user2: &str, limit: i32) -> SqliteResult<Vec<Message>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at
-             FROM messages
-             WHERE (sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)
-             ORDER BY created_at DESC LIMIT ?3")?;
-        let result = stmt.query_map(params![user1,user2,limit], |r| Self::row_to_message(r))?.collect();
-        result
+        self.get_messages_paginated(user1, user2, None, limit)
     }
 
+    /// Newest-first page of a conversation; served from the query cache until the next write
     pub fn get_messages_paginated(&self, user1: &str, user2: &str, before: Option<&str>, limit: i32) -> SqliteResult<Vec<Message>> {
+        let key = PageKey::new(user1, user2, before, limit);
+        if let Some(page) = self.cache.page(&key) { return Ok(page); }
         let conn = self.conn.lock().unwrap();
-        if let Some(cursor) = before {
+        let page: Vec<Message> = if let Some(cursor) = before {
             let mut stmt = conn.prepare(
-                "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at
+                "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
                  FROM messages
                  WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)) AND created_at < ?3
-                 ORDER BY created_at DESC LIMIT ?4")?;
-            let result = stmt.query_map(params![user1,user2,cursor,limit], |r| Self::row_to_message(r))?.collect();
+                   AND deleted_at IS NULL
+                 ORDER BY created_at DESC, lamport DESC, id DESC LIMIT ?4")?;
+            let result = stmt.query_map(params![user1,user2,cursor,limit], |r| Self::row_to_message(r))?.collect::<SqliteResult<_>>()?;
             result
         } else {
             let mut stmt = conn.prepare(
-                "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at
+                "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
                  FROM messages
-                 WHERE (sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)
-                 ORDER BY created_at DESC LIMIT ?3")?;
-            let result = stmt.query_map(params![user1,user2,limit], |r| Self::row_to_message(r))?.collect();
+                 WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)) AND deleted_at IS NULL
+                 ORDER BY created_at DESC, lamport DESC, id DESC LIMIT ?3")?;
+            let result = stmt.query_map(params![user1,user2,limit], |r| Self::row_to_message(r))?.collect::<SqliteResult<_>>()?;
             result
-        }
+        };
+        // Filled under the connection lock so a concurrent write can't be overtaken
+        self.cache.put_page(key, page.clone());
+        Ok(page)
     }
 
     pub fn get_new_messages_since(&self, user1: &str, user2: &str, since: &str) -> SqliteResult<Vec<Message>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at
+            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
              FROM messages
              WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)) AND created_at > ?3
-             ORDER BY created_at ASC")?;
+               AND deleted_at IS NULL
+             ORDER BY created_at ASC, lamport ASC, id ASC")?;
         let result = stmt.query_map(params![user1,user2,since], |r| Self::row_to_message(r))?.collect();
         result
     }
 
     pub fn mark_message_read(&self, id: &str) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute("UPDATE messages SET is_read=1 WHERE id=?1", params![id])?; Ok(())
+        self.conn.lock().unwrap().execute("UPDATE messages SET is_read=1,read_at=?2,status='read' WHERE id=?1 AND is_read=0", params![id, now()])?;
+        self.messages_changed();
+        Ok(())
     }
 
     pub fn mark_messages_read_from_peer(&self, local_id: &str, peer_id: &str) -> SqliteResult<()> {
         self.conn.lock().unwrap().execute(
-            "UPDATE messages SET is_read=1 WHERE receiver_id=?1 AND sender_id=?2 AND is_read=0",
-            params![local_id, peer_id])?;
+            "UPDATE messages SET is_read=1,read_at=?3,status='read' WHERE receiver_id=?1 AND sender_id=?2 AND is_read=0",
+            params![local_id, peer_id, now()])?;
+        self.conversation_changed(local_id, peer_id);
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn mark_message_delivered(&self, id: &str) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute("UPDATE messages SET is_delivered=1 WHERE id=?1", params![id])?; Ok(())
+    /// Move a message along the delivery state machine; false when it's unknown or the move isn't
+    /// allowed from where it is. The read/delivered flags follow.
+    pub fn set_message_status(&self, id: &str, status: MessageStatus) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let current: Option<String> = match conn.query_row("SELECT status FROM messages WHERE id=?1", params![id], |r| r.get(0)) {
+            Ok(s) => Some(s),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e),
+        };
+        if !current.is_some_and(|c| MessageStatus::parse(&c).can_become(status)) { return Ok(false); }
+        conn.execute(
+            "UPDATE messages SET status=?2, is_delivered=MAX(is_delivered, ?3),
+                    is_read=MAX(is_read, ?4), read_at=CASE WHEN ?4=1 AND is_read=0 THEN ?5 ELSE read_at END
+             WHERE id=?1",
+            params![id, status.as_str(), status.is_delivered() as i32, (status == MessageStatus::Read) as i32, now()])?;
+        drop(conn);
+        self.messages_changed();
+        Ok(true)
     }
 
+    /// Soft delete; the message can be restored until it's purged
     pub fn delete_message(&self, id: &str) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute("DELETE FROM messages WHERE id=?1", params![id])?; Ok(())
-    }
-
-    pub fn delete_all_messages_with_peer(&self, local_id: &str, peer_id: &str) -> SqliteResult<()> {
         self.conn.lock().unwrap().execute(
-            "DELETE FROM messages WHERE (sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)",
-            params![local_id, peer_id])?;
+            "UPDATE messages SET deleted_at=?2 WHERE id=?1 AND deleted_at IS NULL", params![id, now()])?;
+        self.messages_changed();
         Ok(())
     }
 
-    pub fn update_message_file_path(&self, message_id: &str, file_path: &str) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute(
-            "UPDATE messages SET file_path=?1 WHERE id=?2",
-            params![file_path, message_id])?;
-        Ok(())
+    /// Soft delete several messages in one transaction; returns how many were deleted
+    pub fn delete_messages(&self, ids: &[String]) -> SqliteResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut n = 0;
+        {
+            let mut stmt = tx.prepare("UPDATE messages SET deleted_at=?2 WHERE id=?1 AND deleted_at IS NULL")?;
+            let at = now();
+            for id in ids { n += stmt.execute(params![id, at])?; }
+        }
+        tx.commit()?;
+        self.messages_changed();
+        Ok(n)
     }
 
-    pub fn get_undelivered_messages_for_peer(&self, sender_id: &str, receiver_id: &str) -> SqliteResult<Vec<Message>> {
+    /// Mark several messages read in one transaction; returns how many changed
+    pub fn mark_messages_read(&self, ids: &[String]) -> SqliteResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut n = 0;
+        {
+            let mut stmt = tx.prepare("UPDATE messages SET is_read=1,read_at=?2,status='read' WHERE id=?1 AND is_read=0")?;
+            let at = now();
+            for id in ids { n += stmt.execute(params![id, at])?; }
+        }
+        tx.commit()?;
+        self.messages_changed();
+        Ok(n)
+    }
+
+    /// Undo a soft delete; false when there's nothing to restore
+    pub fn restore_message(&self, id: &str) -> SqliteResult<bool> {
+        let n = self.conn.lock().unwrap().execute(
+            "UPDATE messages SET deleted_at=NULL WHERE id=?1 AND deleted_at IS NOT NULL", params![id])?;
+        self.messages_changed();
+        Ok(n > 0)
+    }
+
+    /// Remove messages soft-deleted before `before` (and their translations) for good; returns how many
+    pub fn purge_deleted_messages(&self, before: &str) -> SqliteResult<usize> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at
-             FROM messages WHERE sender_id=?1 AND receiver_id=?2 AND is_delivered=0
-             ORDER BY created_at ASC LIMIT 100")?;
-        let result = stmt.query_map(params![sender_id, receiver_id], |r| Self::row_to_message(r))?.collect();
-        result
+        conn.execute(
+            "DELETE FROM message_translations WHERE message_id IN
+             (SELECT id FROM messages WHERE deleted_at IS NOT NULL AND deleted_at < ?1)", params![before])?;
+        let n = conn.execute(
+            "DELETE FROM messages WHERE deleted_at IS NOT NULL AND deleted_at < ?1", params![before])?;
+        drop(conn);
+        self.messages_changed();
+        Ok(n)
     }
 
-    pub fn get_unread_count(&self, user_id: &str) -> SqliteResult<i32> {
+    /// A single message, deleted or not
+    pub fn get_message(&self, id: &str) -> SqliteResult<Option<Message>> {
         let conn = self.conn.lock().unwrap();
-        conn.query_row("SELECT COUNT(*) FROM messages WHERE receiver_id=?1 AND is_read=0", params![user_id], |r| r.get(0))
+        match conn.query_row(
+            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
+             FROM messages WHERE id=?1", params![id], Self::row_to_message) {
+            Ok(m) => Ok(Some(m)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
     }
 
-    pub fn get_unread_count_from_peer(&self, local_id: &str, peer_id: &str) -> SqliteResult<i32> {
+    /// Delete a message for good, skipping the restorable soft delete (secure delete)
+    pub fn erase_message(&self, id: &str) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
-        conn.query_row("SELECT COUNT(*) FROM messages WHERE receiver_id=?1 AND sender_id=?2 AND is_read=0",
-            params![local_id, peer_id], |r| r.get(0))
+        conn.execute("DELETE FROM messages WHERE id=?1", params![id])?;
+        conn.execute("DELETE FROM message_translations WHERE message_id=?1", params![id])?;
+        conn.execute("DELETE FROM attachment_text WHERE message_id=?1", params![id])?;
+        drop(conn);
+        self.messages_changed();
+        Ok(())
     }
 
-    pub fn get_last_messages(&self, local_id: &str) -> SqliteResult<Vec<LastMessageInfo>> {
+    /// Messages with a peer that carry a file, deleted or not
+    pub fn get_file_messages_with_peer(&self, local_id: &str, peer_id: &str) -> SqliteResult<Vec<Message>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT peer_id, content, created_at, is_from_me FROM (
-                SELECT
-                    CASE WHEN sender_id=?1 THEN receiver_id ELSE sender_id END as peer_id,
-                    content, created_at,
-                    CASE WHEN sender_id=?1 THEN 1 ELSE 0 END as is_from_me,
-                    ROW_NUMBER() OVER (
-                        PARTITION BY CASE WHEN sender_id=?1 THEN receiver_id ELSE sender_id END
-                        ORDER BY created_at DESC
-                    ) as rn
-                FROM messages WHERE sender_id=?1 OR receiver_id=?1
+            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status FROM messages
+             WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1))
+             AND (file_path IS NOT NULL OR content LIKE '{%fileId%')")?;
+        let result = stmt.query_map(params![local_id, peer_id], Self::row_to_message)?.collect::<SqliteResult<_>>()?;
+        Ok(result)
+    }
+
+    pub fn delete_all_messages_with_peer(&self, local_id: &str, peer_id: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM message_translations WHERE message_id IN (SELECT id FROM messages
+             WHERE (sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1))",
+            params![local_id, peer_id])?;
+        conn.execute(
+            "DELETE FROM messages WHERE (sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)",
+            params![local_id, peer_id])?;
+        drop(conn);
+        self.conversation_changed(local_id, peer_id);
+        Ok(())
+    }
+
+    pub fn get_message_sender(&self, message_id: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT sender_id FROM messages WHERE id=?1 UNION ALL SELECT sender_id FROM group_messages WHERE id=?1 LIMIT 1",
+            params![message_id], |r| r.get(0)) {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn update_message_file_path(&self, message_id: &str, file_path: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE messages SET file_path=?1 WHERE id=?2",
+            params![file_path, message_id])?;
+        self.messages_changed();
+        Ok(())
+    }
+
+    /// Point stored paths under `old_prefix` at `new_prefix` (download folders that moved)
+    pub fn rebase_file_paths(&self, old_prefix: &str, new_prefix: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        for (table, col) in [("messages", "file_path"), ("files", "file_path"), ("downloads", "path")] {
+            conn.execute(
+                &format!("UPDATE {t} SET {c} = ?2 || substr({c}, length(?1) + 1) WHERE substr({c}, 1, length(?1)) = ?1", t = table, c = col),
+                params![old_prefix, new_prefix])?;
+        }
+        self.messages_changed();
+        Ok(())
+    }
+
+    /// Messages still waiting for an ack; with `max_attempts`, those that used them all are left out
+    pub fn get_undelivered_messages_for_peer(&self, sender_id: &str, receiver_id: &str, max_attempts: Option<u32>) -> SqliteResult<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
+             FROM messages WHERE sender_id=?1 AND receiver_id=?2 AND is_delivered=0 AND deleted_at IS NULL
+               AND (?3 IS NULL OR send_attempts < ?3)
+             ORDER BY created_at ASC, lamport ASC, id ASC LIMIT 100")?;
+        let result = stmt.query_map(params![sender_id, receiver_id, max_attempts], |r| Self::row_to_message(r))?.collect();
+        result
+    }
+
+    /// Count one more send attempt; returns the count so far
+    pub fn record_send_attempt(&self, id: &str) -> SqliteResult<u32> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE messages SET send_attempts=send_attempts+1 WHERE id=?1", params![id])?;
+        conn.query_row("SELECT send_attempts FROM messages WHERE id=?1", params![id], |r| r.get(0))
+    }
+
+    pub fn set_retry_at(&self, id: &str, at: Option<&str>) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("UPDATE messages SET retry_at=?2 WHERE id=?1", params![id, at])?;
+        Ok(())
+    }
+
+    /// Start a message's attempts over (a manual retry)
+    pub fn reset_send_attempts(&self, id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE messages SET send_attempts=0, retry_at=NULL WHERE id=?1", params![id])?;
+        Ok(())
+    }
+
+    /// Undelivered messages to any of `receiver_ids` whose retry is due and that have attempts left
+    pub fn get_due_retries(&self, sender_id: &str, receiver_ids: &[String], at: &str, max_attempts: Option<u32>) -> SqliteResult<Vec<Message>> {
+        if receiver_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let receivers = (4..4 + receiver_ids.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(",");
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
+             FROM messages WHERE sender_id=?1 AND receiver_id IN ({}) AND is_delivered=0 AND deleted_at IS NULL
+               AND retry_at IS NOT NULL AND retry_at <= ?2 AND (?3 IS NULL OR send_attempts < ?3)
+             ORDER BY created_at ASC, lamport ASC, id ASC LIMIT 100", receivers))?;
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&sender_id, &at, &max_attempts];
+        values.extend(receiver_ids.iter().map(|id| id as &dyn rusqlite::ToSql));
+        let result = stmt.query_map(values.as_slice(), Self::row_to_message)?.collect();
+        result
+    }
+
+    /// Ids of undelivered messages that used up `max_attempts` and whose last wait for an ack is
+    /// over; each is only returned once
+    pub fn take_exhausted_messages(&self, sender_id: &str, at: &str, max_attempts: u32) -> SqliteResult<Vec<String>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let ids: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM messages WHERE sender_id=?1 AND is_delivered=0 AND deleted_at IS NULL
+                   AND retry_at IS NOT NULL AND retry_at <= ?2 AND send_attempts >= ?3")?;
+            let result = stmt.query_map(params![sender_id, at, max_attempts], |r| r.get(0))?.collect::<SqliteResult<_>>()?;
+            result
+        };
+        {
+            let mut stmt = tx.prepare("UPDATE messages SET retry_at=NULL WHERE id=?1")?;
+            for id in &ids { stmt.execute(params![id])?; }
+        }
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    // Unread counts all come from the cached per-peer breakdown
+
+    pub fn get_unread_count(&self, user_id: &str) -> SqliteResult<i32> {
+        Ok(self.get_unread_counts_by_peer(user_id)?.iter().map(|(_, n)| n).sum())
+    }
+
+    pub fn get_unread_count_from_peer(&self, local_id: &str, peer_id: &str) -> SqliteResult<i32> {
+        Ok(self.get_unread_counts_by_peer(local_id)?.into_iter().find(|(p, _)| p == peer_id).map_or(0, |(_, n)| n))
+    }
+
+    pub fn get_unread_counts_by_peer(&self, local_id: &str) -> SqliteResult<Vec<(String, i32)>> {
+        if let Some(counts) = self.cache.unread_by_peer(local_id) { return Ok(counts); }
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT sender_id, COUNT(*) FROM messages WHERE receiver_id=?1 AND is_read=0 AND deleted_at IS NULL GROUP BY sender_id")?;
+        let counts: Vec<(String, i32)> = stmt.query_map(params![local_id], |r| Ok((r.get(0)?, r.get(1)?)))?.collect::<SqliteResult<_>>()?;
+        self.cache.put_unread_by_peer(local_id, counts.clone());
+        Ok(counts)
+    }
+
+    pub fn get_last_messages(&self, local_id: &str) -> SqliteResult<Vec<LastMessageInfo>> {
+        if let Some(rows) = self.cache.last_messages(local_id) { return Ok(rows); }
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT peer_id, content, created_at, is_from_me FROM (
+                SELECT
+                    CASE WHEN sender_id=?1 THEN receiver_id ELSE sender_id END as peer_id,
+                    content, created_at,
+                    CASE WHEN sender_id=?1 THEN 1 ELSE 0 END as is_from_me,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY CASE WHEN sender_id=?1 THEN receiver_id ELSE sender_id END
+                        ORDER BY created_at DESC, lamport DESC, id DESC
+                    ) as rn
+                FROM messages WHERE (sender_id=?1 OR receiver_id=?1) AND deleted_at IS NULL
             ) WHERE rn=1")?;
-        let result = stmt.query_map(params![local_id], |r| Ok(LastMessageInfo {
+        let rows: Vec<LastMessageInfo> = stmt.query_map(params![local_id], |r| Ok(LastMessageInfo {
             peer_id: r.get(0)?, content: r.get(1)?, created_at: r.get(2)?,
             is_from_me: r.get::<_,i32>(3)?!=0,
-        }))?.collect();
-        result
+        }))?.collect::<SqliteResult<_>>()?;
+        self.cache.put_last_messages(local_id, rows.clone());
+        Ok(rows)
     }
 
     // ============ FILE CRUD ============
@@ -417,7 +1270,10 @@ impl Database {
     // ============ SETTINGS CRUD ============
 
     pub fn set_setting(&self, key: &str, value: &str) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute("INSERT OR REPLACE INTO settings (key,value) VALUES (?1,?2)", params![key,value])?; Ok(())
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO settings (key,value,updated_at) VALUES (?1,?2,?3)
+             ON CONFLICT(key) DO UPDATE SET value=excluded.value, updated_at=excluded.updated_at",
+            params![key,value,now()])?; Ok(())
     }
 
     pub fn get_setting(&self, key: &str) -> SqliteResult<Option<String>> {
@@ -436,28 +1292,207 @@ impl Database {
         result
     }
 
+    /// The given settings that are set and know when they changed
+    pub fn get_synced_settings(&self, keys: &[&str]) -> SqliteResult<Vec<SyncedSetting>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT key,value,updated_at FROM settings WHERE key=?1 AND updated_at IS NOT NULL")?;
+        let mut result = Vec::new();
+        for key in keys {
+            let mut rows = stmt.query_map(params![key], |r| Ok(SyncedSetting{key:r.get(0)?,value:r.get(1)?,updated_at:r.get(2)?}))?;
+            if let Some(row) = rows.next() { result.push(row?); }
+        }
+        Ok(result)
+    }
+
+    /// Store a setting from a linked device unless ours changed later. Returns whether it was applied.
+    pub fn apply_synced_setting(&self, s: &SyncedSetting) -> SqliteResult<bool> {
+        let changed = self.conn.lock().unwrap().execute(
+            "INSERT INTO settings (key,value,updated_at) VALUES (?1,?2,?3)
+             ON CONFLICT(key) DO UPDATE SET value=excluded.value, updated_at=excluded.updated_at
+             WHERE (settings.updated_at IS NULL OR excluded.updated_at > settings.updated_at)
+                AND excluded.value != settings.value",
+            params![s.key,s.value,s.updated_at])?;
+        Ok(changed > 0)
+    }
+
+    // ============ NOTIFICATION SOUNDS ============
+
+    pub fn set_chat_sound(&self, chat_id: &str, sound_id: Option<&str>) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        match sound_id {
+            Some(sid) => conn.execute("INSERT OR REPLACE INTO chat_sounds (chat_id,sound_id) VALUES (?1,?2)", params![chat_id,sid])?,
+            None => conn.execute("DELETE FROM chat_sounds WHERE chat_id=?1", params![chat_id])?,
+        };
+        Ok(())
+    }
+
+    pub fn get_chat_sound(&self, chat_id: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT sound_id FROM chat_sounds WHERE chat_id=?1", params![chat_id], |r| r.get(0)) {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn clear_chat_sounds_using(&self, sound_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM chat_sounds WHERE sound_id=?1", params![sound_id])?; Ok(())
+    }
+
+    // ============ CHAT SETTINGS ============
+
+    fn row_to_chat_settings(r: &rusqlite::Row<'_>) -> rusqlite::Result<ChatSettings> {
+        Ok(ChatSettings { chat_id: r.get(0)?, nickname: r.get(1)?, color: r.get(2)?, wallpaper: r.get(3)?,
+            notifications: r.get(4)?, updated_at: r.get(5)? })
+    }
+
+    pub fn get_chat_settings(&self, chat_id: &str) -> SqliteResult<Option<ChatSettings>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT chat_id,nickname,color,wallpaper,notifications,updated_at FROM chat_settings WHERE chat_id=?1",
+            params![chat_id], Self::row_to_chat_settings) {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_all_chat_settings(&self) -> SqliteResult<Vec<ChatSettings>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT chat_id,nickname,color,wallpaper,notifications,updated_at FROM chat_settings")?;
+        let result = stmt.query_map([], Self::row_to_chat_settings)?.collect::<SqliteResult<_>>()?;
+        Ok(result)
+    }
+
+    pub fn save_chat_settings(&self, settings: &ChatSettings) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO chat_settings (chat_id,nickname,color,wallpaper,notifications,updated_at) VALUES (?1,?2,?3,?4,?5,?6)",
+            params![settings.chat_id, settings.nickname, settings.color, settings.wallpaper, settings.notifications, settings.updated_at])?;
+        Ok(())
+    }
+
+    pub fn delete_chat_settings(&self, chat_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM chat_settings WHERE chat_id=?1", params![chat_id])?;
+        Ok(())
+    }
+
     // ============ NOTES CRUD ============
 
+    const NOTE_COLS: &'static str = "id,title,content,color,pinned,category,created_at,updated_at,shared_by,collaborators,locked";
+
+    fn row_to_note(r: &rusqlite::Row<'_>) -> rusqlite::Result<Note> {
+        Ok(Note {
+            id:r.get(0)?,title:r.get(1)?,content:r.get(2)?,color:r.get(3)?,
+            pinned:r.get::<_,i32>(4)?!=0,category:r.get(5)?,created_at:r.get(6)?,updated_at:r.get(7)?,
+            shared_by:r.get(8)?,
+            collaborators:r.get::<_,String>(9)?.split(',').filter(|p| !p.is_empty()).map(String::from).collect(),
+            locked:r.get(10)?,
+        })
+    }
+
+    /// Save a local edit; sharing state is left as it is
     pub fn save_note(&self, note: &Note) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT OR REPLACE INTO notes (id,title,content,color,pinned,category,created_at,updated_at) VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
-            params![note.id,note.title,note.content,note.color,note.pinned as i32,note.category,note.created_at,note.updated_at])?;
+            "INSERT INTO notes (id,title,content,color,pinned,category,created_at,updated_at,locked) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)
+             ON CONFLICT(id) DO UPDATE SET title=excluded.title, content=excluded.content, color=excluded.color,
+                pinned=excluded.pinned, category=excluded.category, updated_at=excluded.updated_at, locked=excluded.locked",
+            params![note.id,note.title,note.content,note.color,note.pinned as i32,note.category,note.created_at,note.updated_at,note.locked])?;
+        Ok(())
+    }
+
+    /// Write every column, including sharing state (for notes received from collaborators)
+    pub fn upsert_note(&self, n: &Note) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            &format!("INSERT OR REPLACE INTO notes ({}) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)", Self::NOTE_COLS),
+            params![n.id,n.title,n.content,n.color,n.pinned as i32,n.category,n.created_at,n.updated_at,
+                    n.shared_by,n.collaborators.join(","),n.locked])?;
+        Ok(())
+    }
+
+    pub fn set_note_collaborators(&self, id: &str, collaborators: &[String]) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE notes SET collaborators=?2 WHERE id=?1", params![id, collaborators.join(",")])?;
         Ok(())
     }
 
     pub fn get_all_notes(&self) -> SqliteResult<Vec<Note>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id,title,content,color,pinned,category,created_at,updated_at FROM notes ORDER BY pinned DESC, updated_at DESC")?;
-        let result = stmt.query_map([], |r| Ok(Note {
-            id:r.get(0)?,title:r.get(1)?,content:r.get(2)?,color:r.get(3)?,
-            pinned:r.get::<_,i32>(4)?!=0,category:r.get(5)?,created_at:r.get(6)?,updated_at:r.get(7)?,
-        }))?.collect();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM notes ORDER BY pinned DESC, updated_at DESC", Self::NOTE_COLS))?;
+        let result = stmt.query_map([], Self::row_to_note)?.collect();
+        result
+    }
+
+    /// Delete a note and return the tombstone to replicate
+    pub fn delete_note(&self, id: &str) -> SqliteResult<NoteTombstone> {
+        let conn = self.conn.lock().unwrap();
+        let tombstone = NoteTombstone { id: id.to_string(), deleted_at: now() };
+        conn.execute("DELETE FROM notes WHERE id=?1", params![id])?;
+        conn.execute("DELETE FROM note_attachments WHERE note_id=?1", params![id])?;
+        conn.execute("DELETE FROM note_reminders WHERE note_id=?1", params![id])?;
+        conn.execute("INSERT OR REPLACE INTO note_tombstones (id,deleted_at) VALUES (?1,?2)", params![tombstone.id, tombstone.deleted_at])?;
+        Ok(tombstone)
+    }
+
+    pub fn get_note(&self, id: &str) -> SqliteResult<Option<Note>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(&format!("SELECT {} FROM notes WHERE id=?1", Self::NOTE_COLS), params![id], Self::row_to_note) {
+            Ok(n) => Ok(Some(n)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Notes edited after `since` (all notes when None), for replication to linked devices
+    pub fn get_notes_changed_since(&self, since: Option<&str>) -> SqliteResult<Vec<Note>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM notes WHERE ?1 IS NULL OR updated_at > ?1 ORDER BY updated_at ASC", Self::NOTE_COLS))?;
+        let result = stmt.query_map(params![since], Self::row_to_note)?.collect();
         result
     }
 
-    pub fn delete_note(&self, id: &str) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute("DELETE FROM notes WHERE id=?1", params![id])?; Ok(())
+    pub fn get_note_tombstones_since(&self, since: Option<&str>) -> SqliteResult<Vec<NoteTombstone>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,deleted_at FROM note_tombstones WHERE ?1 IS NULL OR deleted_at > ?1 ORDER BY deleted_at ASC")?;
+        let result = stmt.query_map(params![since], |r| Ok(NoteTombstone { id: r.get(0)?, deleted_at: r.get(1)? }))?.collect();
+        result
+    }
+
+    pub fn is_note_deleted(&self, id: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let n: i64 = conn.query_row("SELECT COUNT(*) FROM note_tombstones WHERE id=?1", params![id], |r| r.get(0))?;
+        Ok(n > 0)
+    }
+
+    /// Last-writer-wins merge of a note from a linked device. Returns whether anything changed.
+    pub fn apply_synced_note(&self, n: &Note) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT deleted_at FROM note_tombstones WHERE id=?1", params![n.id], |r| r.get::<_, String>(0)) {
+            Ok(deleted_at) if deleted_at >= n.updated_at => return Ok(false),
+            Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e),
+        }
+        let changed = conn.execute(
+            &format!("INSERT INTO notes ({}) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)
+             ON CONFLICT(id) DO UPDATE SET title=excluded.title, content=excluded.content, color=excluded.color,
+                pinned=excluded.pinned, category=excluded.category, updated_at=excluded.updated_at,
+                shared_by=excluded.shared_by, collaborators=excluded.collaborators, locked=excluded.locked
+             WHERE excluded.updated_at > notes.updated_at", Self::NOTE_COLS),
+            params![n.id,n.title,n.content,n.color,n.pinned as i32,n.category,n.created_at,n.updated_at,
+                    n.shared_by,n.collaborators.join(","),n.locked])?;
+        Ok(changed > 0)
+    }
+
+    /// Apply a delete from a linked device unless the note was edited after it. Returns whether a note was removed.
+    pub fn apply_note_tombstone(&self, t: &NoteTombstone) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO note_tombstones (id,deleted_at) VALUES (?1,?2)
+             ON CONFLICT(id) DO UPDATE SET deleted_at=MAX(deleted_at,excluded.deleted_at)",
+            params![t.id, t.deleted_at])?;
+        let removed = conn.execute("DELETE FROM notes WHERE id=?1 AND updated_at<=?2", params![t.id, t.deleted_at])?;
+        Ok(removed > 0)
     }
 
     pub fn toggle_note_pin(&self, id: &str) -> SqliteResult<()> {
@@ -467,12 +1502,59 @@ impl Database {
         Ok(())
     }
 
+    pub fn add_note_attachment(&self, a: &NoteAttachment) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO note_attachments (id,note_id,file_name,file_path,mime_type,size,created_at) VALUES (?1,?2,?3,?4,?5,?6,?7)",
+            params![a.id, a.note_id, a.file_name, a.file_path, a.mime_type, a.size, a.created_at])?;
+        Ok(())
+    }
+
+    /// Attachments of one note, or of every note when `note_id` is None
+    pub fn get_note_attachments(&self, note_id: Option<&str>) -> SqliteResult<Vec<NoteAttachment>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,note_id,file_name,file_path,mime_type,size,created_at FROM note_attachments
+             WHERE ?1 IS NULL OR note_id=?1 ORDER BY created_at")?;
+        let result = stmt.query_map(params![note_id], |r| Ok(NoteAttachment {
+            id: r.get(0)?, note_id: r.get(1)?, file_name: r.get(2)?, file_path: r.get(3)?,
+            mime_type: r.get(4)?, size: r.get(5)?, created_at: r.get(6)?,
+        }))?.collect();
+        result
+    }
+
+    pub fn delete_note_attachment(&self, id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM note_attachments WHERE id=?1", params![id])?; Ok(())
+    }
+
+    pub fn save_note_reminder(&self, r: &NoteReminder) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO note_reminders (note_id,remind_at,repeat,status,last_fired_at) VALUES (?1,?2,?3,?4,?5)",
+            params![r.note_id, r.remind_at, r.repeat, r.status, r.last_fired_at])?;
+        Ok(())
+    }
+
+    /// All reminders, or only pending ones due at or before `due_by`
+    pub fn get_note_reminders(&self, due_by: Option<&str>) -> SqliteResult<Vec<NoteReminder>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT note_id,remind_at,repeat,status,last_fired_at FROM note_reminders
+             WHERE ?1 IS NULL OR (status='pending' AND remind_at<=?1) ORDER BY remind_at")?;
+        let result = stmt.query_map(params![due_by], |r| Ok(NoteReminder {
+            note_id: r.get(0)?, remind_at: r.get(1)?, repeat: r.get(2)?, status: r.get(3)?, last_fired_at: r.get(4)?,
+        }))?.collect();
+        result
+    }
+
+    pub fn delete_note_reminder(&self, note_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM note_reminders WHERE note_id=?1", params![note_id])?; Ok(())
+    }
+
     // ============ GROUP CRUD ============
 
     pub fn create_group(&self, group: &Group) -> SqliteResult<()> {
         self.conn.lock().unwrap().execute(
-            "INSERT INTO groups (id,name,created_by,avatar_color,created_at) VALUES (?1,?2,?3,?4,?5)",
-            params![group.id,group.name,group.created_by,group.avatar_color,group.created_at])?;
+            "INSERT INTO groups (id,name,created_by,avatar_color,created_at,kind) VALUES (?1,?2,?3,?4,?5,?6)",
+            params![group.id,group.name,group.created_by,group.avatar_color,group.created_at,group.kind])?;
         Ok(())
     }
 
@@ -492,14 +1574,39 @@ impl Database {
     pub fn get_groups(&self, user_id: &str) -> SqliteResult<Vec<Group>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT g.id,g.name,g.created_by,g.avatar_color,g.created_at FROM groups g
+            "SELECT g.id,g.name,g.created_by,g.avatar_color,g.created_at,g.kind FROM groups g
              INNER JOIN group_members gm ON g.id=gm.group_id WHERE gm.user_id=?1 ORDER BY g.created_at DESC")?;
         let result = stmt.query_map(params![user_id], |r| Ok(Group {
-            id:r.get(0)?,name:r.get(1)?,created_by:r.get(2)?,avatar_color:r.get(3)?,created_at:r.get(4)?,
+            id:r.get(0)?,name:r.get(1)?,created_by:r.get(2)?,avatar_color:r.get(3)?,created_at:r.get(4)?,kind:r.get(5)?,
         }))?.collect();
         result
     }
 
+    pub fn get_group(&self, group_id: &str) -> SqliteResult<Option<Group>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT id,name,created_by,avatar_color,created_at,kind FROM groups WHERE id=?1", params![group_id],
+            |r| Ok(Group { id:r.get(0)?,name:r.get(1)?,created_by:r.get(2)?,avatar_color:r.get(3)?,created_at:r.get(4)?,kind:r.get(5)? })) {
+            Ok(g) => Ok(Some(g)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The member's role ("admin" or "member"), None when not a member
+    pub fn get_group_member_role(&self, group_id: &str, user_id: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT role FROM group_members WHERE group_id=?1 AND user_id=?2", params![group_id, user_id], |r| r.get(0)) {
+            Ok(role) => Ok(Some(role)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_group_member_role(&self, group_id: &str, user_id: &str, role: &str) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute("UPDATE group_members SET role=?3 WHERE group_id=?1 AND user_id=?2",
+            params![group_id, user_id, role])? > 0)
+    }
+
     pub fn get_group_members(&self, group_id: &str) -> SqliteResult<Vec<GroupMember>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare("SELECT group_id,user_id,username,role,joined_at FROM group_members WHERE group_id=?1")?;
@@ -510,28 +1617,35 @@ impl Database {
     }
 
     pub fn send_group_message(&self, msg: &GroupMessage) -> SqliteResult<()> {
-        self.conn.lock().unwrap().execute(
-            "INSERT INTO group_messages (id,group_id,sender_id,sender_name,content,message_type,created_at) VALUES (?1,?2,?3,?4,?5,?6,?7)",
-            params![msg.id,msg.group_id,msg.sender_id,msg.sender_name,msg.content,msg.message_type,msg.created_at])?;
+        let conn = self.conn.lock().unwrap();
+        let lamport = Self::bounded_lamport(&conn, msg.lamport)?;
+        conn.execute(
+            "INSERT INTO group_messages (id,group_id,sender_id,sender_name,content,message_type,created_at,lamport)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
+            params![msg.id,msg.group_id,msg.sender_id,msg.sender_name,msg.content,msg.message_type,msg.created_at,lamport])?;
         Ok(())
     }
 
     pub fn get_group_messages(&self, group_id: &str, limit: i32) -> SqliteResult<Vec<GroupMessage>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id,group_id,sender_id,sender_name,content,message_type,created_at FROM group_messages WHERE group_id=?1 ORDER BY created_at DESC LIMIT ?2")?;
+            "SELECT id,group_id,sender_id,sender_name,content,message_type,created_at,lamport FROM group_messages WHERE group_id=?1 ORDER BY created_at DESC, lamport DESC, id DESC LIMIT ?2")?;
         let result = stmt.query_map(params![group_id,limit], |r| Ok(GroupMessage {
             id:r.get(0)?,group_id:r.get(1)?,sender_id:r.get(2)?,sender_name:r.get(3)?,
-            content:r.get(4)?,message_type:r.get(5)?,created_at:r.get(6)?,
+            content:r.get(4)?,message_type:r.get(5)?,created_at:r.get(6)?,lamport:r.get(7)?,
         }))?.collect();
         result
     }
 
     pub fn delete_group(&self, group_id: &str) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM message_translations WHERE message_id IN (SELECT id FROM group_messages WHERE group_id=?1)",
+            params![group_id])?;
         conn.execute("DELETE FROM group_messages WHERE group_id=?1", params![group_id])?;
         conn.execute("DELETE FROM group_members WHERE group_id=?1", params![group_id])?;
         conn.execute("DELETE FROM groups WHERE id=?1", params![group_id])?;
+        conn.execute("DELETE FROM chat_folder_members WHERE chat_id=?1", params![group_id])?;
         Ok(())
     }
 
@@ -550,6 +1664,43 @@ impl Database {
         Ok(())
     }
 
+    /// Directory fields a peer announced in its ProfileUpdate; replaces what we had
+    pub fn set_user_profile_fields(&self, device_id: &str, p: &ProfileFields) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE users SET email=?2, phone=?3, department=?4, pronouns=?5, timezone=?6 WHERE id=?1",
+            params![device_id, p.email, p.phone, p.department, p.pronouns, p.timezone])?;
+        Ok(())
+    }
+
+    /// None clears the status
+    pub fn set_user_status(&self, device_id: &str, status: Option<&UserStatus>) -> SqliteResult<()> {
+        let s = status.cloned().unwrap_or_default();
+        self.conn.lock().unwrap().execute(
+            "UPDATE users SET status_emoji=?2, status_text=?3, status_expires_at=?4 WHERE id=?1",
+            params![device_id, s.emoji, s.text, s.expires_at])?;
+        Ok(())
+    }
+
+    /// Clear the status if it expired by `now` (UTC RFC 3339); true when it did
+    pub fn clear_expired_status(&self, device_id: &str, now: &str) -> SqliteResult<bool> {
+        let n = self.conn.lock().unwrap().execute(
+            "UPDATE users SET status_emoji=NULL, status_text=NULL, status_expires_at=NULL
+             WHERE id=?1 AND status_expires_at IS NOT NULL AND status_expires_at<=?2",
+            params![device_id, now])?;
+        Ok(n > 0)
+    }
+
+    /// Current status, None when unset or expired
+    pub fn get_user_status(&self, device_id: &str) -> SqliteResult<Option<UserStatus>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT status_emoji,status_text,status_expires_at FROM users WHERE id=?1", params![device_id],
+            |r| Ok(UserStatus { emoji: r.get(0)?, text: r.get(1)?, expires_at: r.get(2)? })) {
+            Ok(s) if (s.emoji.is_some() || s.text.is_some()) && !s.is_expired() => Ok(Some(s)),
+            Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn set_user_avatar(&self, device_id: &str, avatar_url: &str) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
         // Ensure user exists; insert a minimal record if missing
@@ -560,20 +1711,51 @@ impl Database {
         Ok(())
     }
 
-    pub fn get_shared_media(&self, user1: &str, user2: &str, media_type: Option<&str>) -> SqliteResult<Vec<Message>> {
+    pub fn set_user_avatar_hash(&self, device_id: &str, hash: &str) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
-        let query = if let Some(mt) = media_type {
-            format!(
-                "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at
-                 FROM messages WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1))
-                 AND message_type='{}' ORDER BY created_at DESC", mt)
-        } else {
-            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at
-             FROM messages WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1))
-             AND message_type IN ('image','file') ORDER BY created_at DESC".to_string()
-        };
-        let mut stmt = conn.prepare(&query)?;
-        let result = stmt.query_map(params![user1,user2], |r| Self::row_to_message(r))?.collect();
+        conn.execute("UPDATE users SET avatar_hash=?1 WHERE id=?2", params![hash, device_id])?;
+        Ok(())
+    }
+
+    pub fn get_user_avatar_hash(&self, device_id: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT avatar_hash FROM users WHERE id=?1", params![device_id], |r| r.get(0)) {
+            Ok(v) => Ok(v),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    const SHARED_MEDIA_WHERE: &'static str =
+        "((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1))
+         AND (?3 IS NULL AND message_type IN ('image','file') OR message_type=?3)
+         AND (?4 IS NULL OR sender_id=?4) AND (?5 IS NULL OR created_at>=?5) AND (?6 IS NULL OR created_at<=?6)
+         AND deleted_at IS NULL";
+
+    /// One page of media messages in a conversation, newest first
+    pub fn get_shared_media(&self, user1: &str, user2: &str, filter: &SharedMediaFilter) -> SqliteResult<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
+             FROM messages WHERE {}
+               AND (?7 IS NULL OR (created_at, lamport, id) < (?7, COALESCE(?8, -9223372036854775808), COALESCE(?9, '')))
+             ORDER BY created_at DESC, lamport DESC, id DESC LIMIT ?10",
+            Self::SHARED_MEDIA_WHERE))?;
+        let limit = filter.limit.unwrap_or(100).clamp(1, 500);
+        let result = stmt.query_map(params![user1, user2, filter.media_type, filter.sender_id, filter.since,
+                                            filter.until, filter.before, filter.before_lamport, filter.before_id, limit],
+                                    |r| Self::row_to_message(r))?.collect();
+        result
+    }
+
+    /// Media counts per type for the same filters (the cursor and limit are ignored)
+    pub fn get_shared_media_counts(&self, user1: &str, user2: &str, filter: &SharedMediaFilter) -> SqliteResult<Vec<SharedMediaCount>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT message_type, COUNT(*) FROM messages WHERE {} GROUP BY message_type ORDER BY message_type",
+            Self::SHARED_MEDIA_WHERE))?;
+        let result = stmt.query_map(params![user1, user2, filter.media_type, filter.sender_id, filter.since, filter.until],
+            |r| Ok(SharedMediaCount { message_type: r.get(0)?, count: r.get(1)? }))?.collect();
         result
     }
 
@@ -581,7 +1763,8 @@ impl Database {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT DISTINCT u.id,u.username,u.device_id,u.public_key,u.avatar_path,
-                    COALESCE(u.bio,''),COALESCE(u.designation,''),u.last_seen,u.is_online,u.created_at
+                    COALESCE(u.bio,''),COALESCE(u.designation,''),u.last_seen,u.is_online,u.created_at,
+                    u.email,u.phone,u.department,u.pronouns,u.timezone
              FROM users u INNER JOIN messages m ON (m.sender_id=u.id OR m.receiver_id=u.id)
              WHERE u.id!=?1 AND (m.sender_id=?1 OR m.receiver_id=?1) ORDER BY u.username"
         )?;
@@ -589,22 +1772,1106 @@ impl Database {
         result
     }
 
-    #[allow(dead_code)]
     pub fn cache_peer(&self, device_id: &str, username: &str, ip: &str, port: i32, public_key: Option<&str>) -> SqliteResult<()> {
+        // Upsert rather than replace so the trust flag survives
         self.conn.lock().unwrap().execute(
-            "INSERT OR REPLACE INTO peers (device_id,username,ip_address,port,public_key,last_seen) VALUES (?1,?2,?3,?4,?5,?6)",
+            "INSERT INTO peers (device_id,username,ip_address,port,public_key,last_seen) VALUES (?1,?2,?3,?4,?5,?6)
+             ON CONFLICT(device_id) DO UPDATE SET username=excluded.username, ip_address=excluded.ip_address,
+                port=excluded.port, public_key=excluded.public_key, last_seen=excluded.last_seen",
             params![device_id,username,ip,port,public_key,Utc::now().to_rfc3339()])?;
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn get_cached_peers(&self) -> SqliteResult<Vec<(String,String,String,i32)>> {
+    /// Trust `device_id` as long as it holds the key with checksum `trusted_key`; None drops trust.
+    /// Works for peers never cached too (trusted straight from discovery); the row borrows
+    /// the name and key from users until cache_peer fills in the address
+    pub fn set_peer_trusted(&self, device_id: &str, trusted_key: Option<&str>) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO peers (device_id,username,ip_address,port,public_key,last_seen,is_trusted,trusted_key)
+             SELECT ?1, COALESCE((SELECT username FROM users WHERE id=?1),''), '', 0,
+                    (SELECT public_key FROM users WHERE id=?1), ?3, ?2, ?4 WHERE true
+             ON CONFLICT(device_id) DO UPDATE SET is_trusted=excluded.is_trusted, trusted_key=excluded.trusted_key",
+            params![device_id, trusted_key.is_some() as i32, Utc::now().to_rfc3339(), trusted_key])?;
+        Ok(())
+    }
+
+    pub fn is_peer_trusted(&self, device_id: &str) -> SqliteResult<bool> {
+        Ok(self.get_trusted_key(device_id)?.is_some())
+    }
+
+    /// Checksum of the key `device_id` is trusted with, if it is
+    pub fn get_trusted_key(&self, device_id: &str) -> SqliteResult<Option<String>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT device_id,username,ip_address,port FROM peers ORDER BY last_seen DESC")?;
-        let result = stmt.query_map([], |r| Ok((r.get(0)?,r.get(1)?,r.get(2)?,r.get(3)?)))?.collect();
-        result
+        match conn.query_row("SELECT trusted_key FROM peers WHERE device_id=?1 AND is_trusted=1 AND trusted_key IS NOT NULL",
+                             params![device_id], |r| r.get(0)) {
+            Ok(key) => Ok(Some(key)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
     }
-}
+
+    /// Pin the identity key `device_id` announced; the first one pinned stays
+    pub fn pin_identity(&self, device_id: &str, identity_key: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO identity_pins (device_id, identity_key, pinned_at) VALUES (?1,?2,?3)",
+            params![device_id, identity_key, now()])?;
+        Ok(())
+    }
+
+    pub fn unpin_identity(&self, device_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM identity_pins WHERE device_id=?1", params![device_id])?;
+        Ok(())
+    }
+
+    /// (device id, identity key) of every pinned device
+    pub fn get_identity_pins(&self) -> SqliteResult<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT device_id, identity_key FROM identity_pins")?;
+        let result = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?.collect();
+        result
+    }
+
+    pub fn get_trusted_peer_ids(&self) -> SqliteResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT device_id FROM peers WHERE is_trusted=1 AND trusted_key IS NOT NULL ORDER BY username")?;
+        let result = stmt.query_map([], |r| r.get(0))?.collect();
+        result
+    }
+
+    #[allow(dead_code)]
+    pub fn get_cached_peers(&self) -> SqliteResult<Vec<(String,String,String,i32)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT device_id,username,ip_address,port FROM peers ORDER BY last_seen DESC")?;
+        let result = stmt.query_map([], |r| Ok((r.get(0)?,r.get(1)?,r.get(2)?,r.get(3)?)))?.collect();
+        result
+    }
+
+    // ============ LINKED DEVICES / SYNC ============
+
+    pub fn add_linked_device(&self, device_id: &str, name: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO linked_devices (device_id,name,linked_at) VALUES (?1,?2,?3)
+             ON CONFLICT(device_id) DO UPDATE SET name=excluded.name",
+            params![device_id, name, now()])?;
+        Ok(())
+    }
+
+    pub fn get_linked_devices(&self) -> SqliteResult<Vec<LinkedDevice>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT device_id,name,linked_at,last_sync FROM linked_devices ORDER BY linked_at")?;
+        let result = stmt.query_map([], |r| Ok(LinkedDevice {
+            device_id: r.get(0)?, name: r.get(1)?, linked_at: r.get(2)?, last_sync: r.get(3)?,
+        }))?.collect();
+        result
+    }
+
+    pub fn get_linked_device(&self, device_id: &str) -> SqliteResult<Option<LinkedDevice>> {
+        Ok(self.get_linked_devices()?.into_iter().find(|d| d.device_id == device_id))
+    }
+
+    pub fn remove_linked_device(&self, device_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM linked_devices WHERE device_id=?1", params![device_id])?; Ok(())
+    }
+
+    /// Record how far we have pulled from a linked device (its clock, not ours)
+    pub fn set_linked_device_synced(&self, device_id: &str, until: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE linked_devices SET last_sync=?2 WHERE device_id=?1", params![device_id, until])?;
+        Ok(())
+    }
+
+    /// Direct messages created or marked read after `since` (everything when None)
+    pub fn get_messages_changed_since(&self, since: Option<&str>) -> SqliteResult<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,lamport,status
+             FROM messages WHERE (?1 IS NULL OR created_at > ?1 OR read_at > ?1) AND deleted_at IS NULL
+             ORDER BY created_at ASC, lamport ASC, id ASC")?;
+        let result = stmt.query_map(params![since], Self::row_to_message)?.collect();
+        result
+    }
+
+    /// Store a message replicated from a linked device; read/delivered flags only move forward
+    pub fn apply_synced_message(&self, m: &Message) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let lamport = Self::bounded_lamport(&conn, m.lamport)?;
+        conn.execute(
+            &format!("INSERT INTO messages (id,sender_id,receiver_id,content,message_type,file_path,is_read,is_delivered,created_at,read_at,lamport,status)
+             SELECT ?1,?2,?3,?4,?5,?6,?7,?8,?9,CASE WHEN ?7=1 THEN ?10 END,?11,?12 WHERE {} {}",
+                     Self::NOT_CLEARED, Self::MERGE_EXISTING),
+            params![m.id, m.sender_id, m.receiver_id, m.content, m.message_type, m.file_path,
+                    m.is_read as i32, m.is_delivered as i32, m.created_at, now(), lamport, Self::stored_status(m)])?;
+        self.conversation_changed(&m.sender_id, &m.receiver_id);
+        Ok(())
+    }
+
+    /// Merge a contact replicated from a linked device without clobbering local presence
+    pub fn merge_synced_user(&self, u: &User) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO users (id,username,device_id,public_key,avatar_path,bio,designation,last_seen,is_online,created_at,
+                                email,phone,department,pronouns,timezone)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,0,?9,?10,?11,?12,?13,?14)
+             ON CONFLICT(id) DO UPDATE SET username=excluded.username,
+                public_key=COALESCE(users.public_key,excluded.public_key),
+                avatar_path=COALESCE(users.avatar_path,excluded.avatar_path),
+                bio=COALESCE(NULLIF(excluded.bio,''),users.bio),
+                designation=COALESCE(NULLIF(excluded.designation,''),users.designation),
+                email=COALESCE(excluded.email,users.email), phone=COALESCE(excluded.phone,users.phone),
+                department=COALESCE(excluded.department,users.department),
+                pronouns=COALESCE(excluded.pronouns,users.pronouns),
+                timezone=COALESCE(excluded.timezone,users.timezone)",
+            params![u.id, u.username, u.device_id, u.public_key, u.avatar_path,
+                    u.bio, u.designation, u.last_seen, u.created_at,
+                    u.profile.email, u.profile.phone, u.profile.department, u.profile.pronouns, u.profile.timezone])?;
+        Ok(())
+    }
+
+    // ============ WEBHOOKS ============
+
+    pub fn save_webhook(&self, w: &Webhook) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO webhooks (id,url,peer_id,group_id,message_type,enabled,created_at) VALUES (?1,?2,?3,?4,?5,?6,?7)
+             ON CONFLICT(id) DO UPDATE SET url=excluded.url, peer_id=excluded.peer_id, group_id=excluded.group_id,
+                message_type=excluded.message_type, enabled=excluded.enabled",
+            params![w.id, w.url, w.peer_id, w.group_id, w.message_type, w.enabled, w.created_at])?;
+        Ok(())
+    }
+
+    pub fn get_webhooks(&self) -> SqliteResult<Vec<Webhook>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,url,peer_id,group_id,message_type,enabled,created_at,last_status,last_delivery_at
+             FROM webhooks ORDER BY created_at")?;
+        let result = stmt.query_map([], |r| Ok(Webhook {
+            id: r.get(0)?, url: r.get(1)?, peer_id: r.get(2)?, group_id: r.get(3)?, message_type: r.get(4)?,
+            enabled: r.get(5)?, created_at: r.get(6)?, last_status: r.get(7)?, last_delivery_at: r.get(8)?,
+        }))?.collect();
+        result
+    }
+
+    pub fn delete_webhook(&self, id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM webhooks WHERE id=?1", params![id])?; Ok(())
+    }
+
+    pub fn set_webhook_result(&self, id: &str, status: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE webhooks SET last_status=?2, last_delivery_at=?3 WHERE id=?1", params![id, status, now()])?;
+        Ok(())
+    }
+
+    // ============ AUTO-REPLY RULES ============
+
+    pub fn save_auto_reply_rule(&self, r: &AutoReplyRule) -> SqliteResult<()> {
+        let days = r.days.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",");
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO auto_reply_rules
+             (id,name,pattern,match_mode,peer_ids,days,start_time,end_time,active_from,active_until,response,cooldown_minutes,enabled,created_at)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14)",
+            params![r.id, r.name, r.pattern, r.match_mode, r.peer_ids.join(","), days, r.start_time, r.end_time,
+                    r.active_from, r.active_until, r.response, r.cooldown_minutes, r.enabled, r.created_at])?;
+        Ok(())
+    }
+
+    pub fn get_auto_reply_rules(&self) -> SqliteResult<Vec<AutoReplyRule>> {
+        fn split(s: String) -> Vec<String> { s.split(',').filter(|p| !p.is_empty()).map(String::from).collect() }
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,name,pattern,match_mode,peer_ids,days,start_time,end_time,active_from,active_until,
+                    response,cooldown_minutes,enabled,created_at
+             FROM auto_reply_rules ORDER BY created_at")?;
+        let result = stmt.query_map([], |r| Ok(AutoReplyRule {
+            id: r.get(0)?, name: r.get(1)?, pattern: r.get(2)?, match_mode: r.get(3)?,
+            peer_ids: split(r.get(4)?),
+            days: split(r.get(5)?).iter().filter_map(|d| d.parse().ok()).collect(),
+            start_time: r.get(6)?, end_time: r.get(7)?, active_from: r.get(8)?, active_until: r.get(9)?,
+            response: r.get(10)?, cooldown_minutes: r.get(11)?, enabled: r.get(12)?, created_at: r.get(13)?,
+        }))?.collect();
+        result
+    }
+
+    pub fn delete_auto_reply_rule(&self, id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM auto_reply_rules WHERE id=?1", params![id])?; Ok(())
+    }
+
+    pub fn set_auto_reply_rule_enabled(&self, id: &str, enabled: bool) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE auto_reply_rules SET enabled=?2 WHERE id=?1", params![id, enabled])?;
+        Ok(())
+    }
+
+    // ============ MEETING HISTORY ============
+
+    /// Record a meeting the first time we hear of it; later calls only fill in a missing host name
+    pub fn upsert_meeting(&self, m: &MeetingRecord) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO meetings (id,host_id,host_name,started_at,ended_at) VALUES (?1,?2,?3,?4,?5)
+             ON CONFLICT(id) DO UPDATE SET host_name=COALESCE(meetings.host_name, excluded.host_name)",
+            params![m.id, m.host_id, m.host_name, m.started_at, m.ended_at])?;
+        Ok(())
+    }
+
+    /// Mark a participant as present; a rejoin keeps the first join time
+    pub fn meeting_participant_joined(&self, p: &MeetingParticipant) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO meeting_participants (meeting_id,user_id,username,joined_at,left_at) VALUES (?1,?2,?3,?4,NULL)
+             ON CONFLICT(meeting_id,user_id) DO UPDATE SET left_at=NULL,
+                username=COALESCE(excluded.username, meeting_participants.username)",
+            params![p.meeting_id, p.user_id, p.username, p.joined_at])?;
+        Ok(())
+    }
+
+    pub fn meeting_participant_left(&self, meeting_id: &str, user_id: &str, at: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE meeting_participants SET left_at=?3 WHERE meeting_id=?1 AND user_id=?2 AND left_at IS NULL",
+            params![meeting_id, user_id, at])?;
+        Ok(())
+    }
+
+    /// End a meeting; everyone still in it leaves at the same time
+    pub fn end_meeting(&self, meeting_id: &str, at: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE meetings SET ended_at=?2 WHERE id=?1 AND ended_at IS NULL", params![meeting_id, at])?;
+        conn.execute("UPDATE meeting_participants SET left_at=?2 WHERE meeting_id=?1 AND left_at IS NULL", params![meeting_id, at])?;
+        Ok(())
+    }
+
+    pub fn get_meetings(&self, limit: i64) -> SqliteResult<Vec<MeetingRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,host_id,host_name,started_at,ended_at,whiteboard_path FROM meetings ORDER BY started_at DESC LIMIT ?1")?;
+        let result = stmt.query_map(params![limit], |r| Ok(MeetingRecord {
+            id: r.get(0)?, host_id: r.get(1)?, host_name: r.get(2)?, started_at: r.get(3)?, ended_at: r.get(4)?,
+            whiteboard_path: r.get(5)?,
+        }))?.collect();
+        result
+    }
+
+    pub fn set_meeting_whiteboard(&self, meeting_id: &str, path: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("UPDATE meetings SET whiteboard_path=?2 WHERE id=?1", params![meeting_id, path])?;
+        Ok(())
+    }
+
+    pub fn get_meeting_participants(&self, meeting_id: &str) -> SqliteResult<Vec<MeetingParticipant>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT meeting_id,user_id,username,joined_at,left_at FROM meeting_participants
+             WHERE meeting_id=?1 ORDER BY joined_at")?;
+        let result = stmt.query_map(params![meeting_id], |r| Ok(MeetingParticipant {
+            meeting_id: r.get(0)?, user_id: r.get(1)?, username: r.get(2)?, joined_at: r.get(3)?, left_at: r.get(4)?,
+        }))?.collect();
+        result
+    }
+
+    // ============ SCHEDULED MEETINGS ============
+
+    pub fn save_scheduled_meeting(&self, m: &ScheduledMeeting) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO scheduled_meetings (id,title,host_id,host_name,start_at,duration_minutes,participants,created_at,reminded)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)",
+            params![m.id, m.title, m.host_id, m.host_name, m.start_at, m.duration_minutes,
+                    m.participants.join(","), m.created_at, m.reminded as i32])?;
+        Ok(())
+    }
+
+    /// Meetings ordered by start time; with `ends_after`, only those that haven't finished by then
+    pub fn get_scheduled_meetings(&self, ends_after: Option<&str>) -> SqliteResult<Vec<ScheduledMeeting>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,title,host_id,host_name,start_at,duration_minutes,participants,created_at,reminded
+             FROM scheduled_meetings ORDER BY start_at")?;
+        let rows: Vec<ScheduledMeeting> = stmt.query_map([], |r| {
+            let participants: String = r.get(6)?;
+            Ok(ScheduledMeeting {
+                id: r.get(0)?, title: r.get(1)?, host_id: r.get(2)?, host_name: r.get(3)?, start_at: r.get(4)?,
+                duration_minutes: r.get(5)?,
+                participants: participants.split(',').filter(|p| !p.is_empty()).map(String::from).collect(),
+                created_at: r.get(7)?, reminded: r.get::<_, i32>(8)? != 0,
+            })
+        })?.collect::<SqliteResult<_>>()?;
+        let Some(after) = ends_after.and_then(|a| chrono::DateTime::parse_from_rfc3339(a).ok()) else { return Ok(rows) };
+        Ok(rows.into_iter().filter(|m| {
+            chrono::DateTime::parse_from_rfc3339(&m.start_at)
+                .map(|start| start + chrono::Duration::minutes(m.duration_minutes) > after)
+                .unwrap_or(true)
+        }).collect())
+    }
+
+    pub fn set_scheduled_meeting_reminded(&self, id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("UPDATE scheduled_meetings SET reminded=1 WHERE id=?1", params![id])?; Ok(())
+    }
+
+    pub fn queue_meeting_invite(&self, meeting_id: &str, peer_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO scheduled_meeting_invites (meeting_id,peer_id,delivered_at) VALUES (?1,?2,NULL)",
+            params![meeting_id, peer_id])?;
+        Ok(())
+    }
+
+    /// (meeting_id, peer_id) pairs still waiting for the peer to acknowledge
+    pub fn get_pending_meeting_invites(&self) -> SqliteResult<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT meeting_id,peer_id FROM scheduled_meeting_invites WHERE delivered_at IS NULL")?;
+        let result = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?.collect();
+        result
+    }
+
+    pub fn mark_meeting_invite_delivered(&self, meeting_id: &str, peer_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE scheduled_meeting_invites SET delivered_at=?3 WHERE meeting_id=?1 AND peer_id=?2",
+            params![meeting_id, peer_id, now()])?;
+        Ok(())
+    }
+
+    // ============ DOWNLOADS ============
+
+    const DOWNLOAD_COLS: &'static str =
+        "id,kind,source_peer,source_name,message_id,url,file_name,file_type,path,size,status,error,created_at,updated_at,checksum,transcript,ocr_text";
+
+    fn row_to_download(r: &rusqlite::Row<'_>) -> rusqlite::Result<DownloadRecord> {
+        Ok(DownloadRecord {
+            id: r.get(0)?, kind: r.get(1)?, source_peer: r.get(2)?, source_name: r.get(3)?, message_id: r.get(4)?,
+            url: r.get(5)?, file_name: r.get(6)?, file_type: r.get(7)?, path: r.get(8)?, size: r.get(9)?,
+            status: r.get(10)?, error: r.get(11)?, created_at: r.get(12)?, updated_at: r.get(13)?, checksum: r.get(14)?,
+            transcript: r.get(15)?, ocr_text: r.get(16)?,
+        })
+    }
+
+    pub fn save_download(&self, d: &DownloadRecord) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            &format!("INSERT OR REPLACE INTO downloads ({}) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17)", Self::DOWNLOAD_COLS),
+            params![d.id, d.kind, d.source_peer, d.source_name, d.message_id, d.url, d.file_name, d.file_type,
+                    d.path, d.size, d.status, d.error, d.created_at, d.updated_at, d.checksum, d.transcript, d.ocr_text])?;
+        Ok(())
+    }
+
+    /// Move a download to `status`; path and size are only overwritten when given
+    pub fn update_download(&self, id: &str, status: &str, path: Option<&str>, size: Option<i64>, error: Option<&str>) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE downloads SET status=?2, path=COALESCE(?3,path), size=COALESCE(?4,size), error=?5, updated_at=?6 WHERE id=?1",
+            params![id, status, path, size, error, now()])?;
+        Ok(())
+    }
+
+    /// Where downloads for a message were saved
+    pub fn get_download_paths_for_message(&self, message_id: &str) -> SqliteResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT path FROM downloads WHERE message_id=?1 AND path IS NOT NULL")?;
+        let result = stmt.query_map(params![message_id], |r| r.get(0))?.collect::<SqliteResult<_>>()?;
+        Ok(result)
+    }
+
+    pub fn get_downloads_for_message(&self, message_id: &str) -> SqliteResult<Vec<DownloadRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM downloads WHERE message_id=?1 ORDER BY created_at DESC", Self::DOWNLOAD_COLS))?;
+        let result = stmt.query_map(params![message_id], Self::row_to_download)?.collect();
+        result
+    }
+
+    pub fn get_download(&self, id: &str) -> SqliteResult<Option<DownloadRecord>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(&format!("SELECT {} FROM downloads WHERE id=?1", Self::DOWNLOAD_COLS), params![id], Self::row_to_download) {
+            Ok(d) => Ok(Some(d)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Newest first
+    pub fn get_downloads(&self, filter: &DownloadFilter) -> SqliteResult<Vec<DownloadRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM downloads WHERE (?1 IS NULL OR status=?1) AND (?2 IS NULL OR kind=?2)
+             AND (?3 IS NULL OR source_peer=?3) ORDER BY created_at DESC LIMIT ?4", Self::DOWNLOAD_COLS))?;
+        let limit = filter.limit.unwrap_or(200).clamp(1, 1000);
+        let result = stmt.query_map(params![filter.status, filter.kind, filter.source_peer, limit], Self::row_to_download)?.collect();
+        result
+    }
+
+    // ============ IMAGE VARIANTS ============
+
+    pub fn save_image_variant(&self, v: &ImageVariant) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO image_variants (file_id,original_file_id,original_path,original_size,size,mode,created_at)
+             VALUES (?1,?2,?3,?4,?5,?6,?7)",
+            params![v.file_id, v.original_file_id, v.original_path, v.original_size, v.size, v.mode, v.created_at])?;
+        Ok(())
+    }
+
+    fn row_to_image_variant(r: &rusqlite::Row<'_>) -> rusqlite::Result<ImageVariant> {
+        Ok(ImageVariant {
+            file_id: r.get(0)?, original_file_id: r.get(1)?, original_path: r.get(2)?, original_size: r.get(3)?,
+            size: r.get(4)?, mode: r.get(5)?, created_at: r.get(6)?,
+        })
+    }
+
+    pub fn get_image_variant(&self, file_id: &str) -> SqliteResult<Option<ImageVariant>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT file_id,original_file_id,original_path,original_size,size,mode,created_at FROM image_variants WHERE file_id=?1",
+            params![file_id], Self::row_to_image_variant) {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_image_variants(&self) -> SqliteResult<Vec<ImageVariant>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT file_id,original_file_id,original_path,original_size,size,mode,created_at FROM image_variants")?;
+        let result = stmt.query_map([], Self::row_to_image_variant)?.collect();
+        result
+    }
+
+    // ============ BACKUPS ============
+
+    /// Copy the live database to `dest` with SQLite's online backup API. The pages are read through
+    /// a connection of its own inside one read transaction: with WAL that's a consistent snapshot
+    /// while writes carry on, and the shared connection is only held to look up the file.
+    pub fn backup_to(&self, dest: &std::path::Path) -> SqliteResult<()> {
+        let path = self.conn.lock().unwrap().path().filter(|p| !p.is_empty()).map(str::to_string);
+        let mut target = Connection::open(dest)?;
+        let Some(path) = path else {
+            // An in-memory database can't be opened twice
+            let conn = self.conn.lock().unwrap();
+            let backup = rusqlite::backup::Backup::new(&conn, &mut target)?;
+            return backup.run_to_completion(1024, std::time::Duration::ZERO, None);
+        };
+        let source = Connection::open_with_flags(&path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        source.execute_batch("BEGIN")?;
+        // The snapshot is taken at the first read, not at BEGIN
+        source.query_row("SELECT COUNT(*) FROM sqlite_master", [], |r| r.get::<_, i64>(0))?;
+        let backup = rusqlite::backup::Backup::new(&source, &mut target)?;
+        backup.run_to_completion(1024, std::time::Duration::from_millis(5), None)
+    }
+
+    pub fn add_backup_record(&self, b: &BackupRecord) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO backup_history (id,started_at,finished_at,trigger,status,path,size,error,remote,remote_error)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)",
+            params![b.id, b.started_at, b.finished_at, b.trigger, b.status, b.path, b.size, b.error,
+                    b.remote, b.remote_error])?;
+        Ok(())
+    }
+
+    /// Newest first
+    pub fn get_backup_history(&self, limit: i64) -> SqliteResult<Vec<BackupRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,started_at,finished_at,trigger,status,path,size,error,remote,remote_error FROM backup_history
+             ORDER BY started_at DESC LIMIT ?1")?;
+        let result = stmt.query_map(params![limit], |r| Ok(BackupRecord {
+            id: r.get(0)?, started_at: r.get(1)?, finished_at: r.get(2)?, trigger: r.get(3)?, status: r.get(4)?,
+            path: r.get(5)?, size: r.get(6)?, error: r.get(7)?, remote: r.get(8)?, remote_error: r.get(9)?,
+        }))?.collect();
+        result
+    }
+
+    // ============ LINK SNAPSHOTS ============
+
+    pub fn save_link_snapshot(&self, l: &LinkSnapshot) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO link_snapshots (id,message_id,url,title,excerpt,path,status,error,created_at)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)",
+            params![l.id, l.message_id, l.url, l.title, l.excerpt, l.path, l.status, l.error, l.created_at])?;
+        Ok(())
+    }
+
+    fn row_to_link_snapshot(r: &rusqlite::Row<'_>) -> rusqlite::Result<LinkSnapshot> {
+        Ok(LinkSnapshot {
+            id: r.get(0)?, message_id: r.get(1)?, url: r.get(2)?, title: r.get(3)?, excerpt: r.get(4)?,
+            path: r.get(5)?, status: r.get(6)?, error: r.get(7)?, created_at: r.get(8)?,
+        })
+    }
+
+    pub fn get_link_snapshots(&self, message_id: &str) -> SqliteResult<Vec<LinkSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,message_id,url,title,excerpt,path,status,error,created_at FROM link_snapshots
+             WHERE message_id=?1 ORDER BY created_at")?;
+        let result = stmt.query_map(params![message_id], Self::row_to_link_snapshot)?.collect();
+        result
+    }
+
+    pub fn get_link_snapshot(&self, id: &str) -> SqliteResult<Option<LinkSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT id,message_id,url,title,excerpt,path,status,error,created_at FROM link_snapshots WHERE id=?1",
+            params![id], Self::row_to_link_snapshot) {
+            Ok(l) => Ok(Some(l)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn delete_link_snapshot(&self, id: &str) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute("DELETE FROM link_snapshots WHERE id=?1", params![id])? > 0)
+    }
+
+    // ============ PEER CLOCKS ============
+
+    pub fn set_peer_clock(&self, c: &PeerClock) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO peer_clock (peer_id,offset_ms,rtt_ms,measured_at) VALUES (?1,?2,?3,?4)",
+            params![c.peer_id, c.offset_ms, c.rtt_ms, c.measured_at])?;
+        Ok(())
+    }
+
+    pub fn get_peer_clock(&self, peer_id: &str) -> SqliteResult<Option<PeerClock>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT peer_id,offset_ms,rtt_ms,measured_at FROM peer_clock WHERE peer_id=?1", params![peer_id],
+            |r| Ok(PeerClock { peer_id: r.get(0)?, offset_ms: r.get(1)?, rtt_ms: r.get(2)?, measured_at: r.get(3)? })) {
+            Ok(c) => Ok(Some(c)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_peer_clocks(&self) -> SqliteResult<Vec<PeerClock>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT peer_id,offset_ms,rtt_ms,measured_at FROM peer_clock ORDER BY peer_id")?;
+        let result = stmt.query_map([], |r| Ok(PeerClock {
+            peer_id: r.get(0)?, offset_ms: r.get(1)?, rtt_ms: r.get(2)?, measured_at: r.get(3)?,
+        }))?.collect();
+        result
+    }
+
+    // ============ ATTACHMENT TEXT ============
+
+    /// Store a voice message's transcript on its download row and index it for search
+    pub fn set_download_transcript(&self, id: &str, transcript: &str) -> SqliteResult<bool> {
+        let changed = self.conn.lock().unwrap().execute(
+            "UPDATE downloads SET transcript=?2, updated_at=?3 WHERE id=?1", params![id, transcript, now()])?;
+        if changed > 0 {
+            self.index_download_text(id, "transcript", transcript)?;
+        }
+        Ok(changed > 0)
+    }
+
+    /// Store text read off a received image on its download row and index it for search
+    pub fn set_download_ocr_text(&self, id: &str, text: &str) -> SqliteResult<bool> {
+        let changed = self.conn.lock().unwrap().execute(
+            "UPDATE downloads SET ocr_text=?2, updated_at=?3 WHERE id=?1", params![id, text, now()])?;
+        if changed > 0 {
+            self.index_download_text(id, "ocr", text)?;
+        }
+        Ok(changed > 0)
+    }
+
+    /// Same for an image we sent: stored on the message, indexed without a download
+    pub fn set_message_ocr_text(&self, message_id: &str, text: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let changed = conn.execute("UPDATE messages SET ocr_text=?2 WHERE id=?1", params![message_id, text])?;
+        if changed > 0 {
+            conn.execute("DELETE FROM attachment_text WHERE download_id IS NULL AND message_id=?1 AND kind='ocr'",
+                params![message_id])?;
+            conn.execute("INSERT INTO attachment_text (download_id,message_id,kind,text) VALUES (NULL,?1,'ocr',?2)",
+                params![message_id, text])?;
+        }
+        Ok(changed > 0)
+    }
+
+    /// (Re)index text extracted from a download; one entry per download and kind
+    fn index_download_text(&self, download_id: &str, kind: &str, text: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM attachment_text WHERE download_id=?1 AND kind=?2", params![download_id, kind])?;
+        conn.execute(
+            "INSERT INTO attachment_text (download_id,message_id,kind,text)
+             SELECT id,message_id,?2,?3 FROM downloads WHERE id=?1",
+            params![download_id, kind, text])?;
+        Ok(())
+    }
+
+    /// Full-text search over attachment text; `query` is an FTS5 match expression
+    pub fn search_attachment_text(&self, query: &str, limit: i64) -> SqliteResult<Vec<AttachmentTextHit>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT t.download_id, t.message_id, t.kind, d.file_name, COALESCE(d.path, m.file_path),
+                    snippet(attachment_text, 3, '[', ']', '…', 12)
+             FROM attachment_text t
+             LEFT JOIN downloads d ON d.id = t.download_id
+             LEFT JOIN messages m ON t.download_id IS NULL AND m.id = t.message_id
+             WHERE attachment_text MATCH ?1 AND (d.id IS NOT NULL OR m.id IS NOT NULL)
+             ORDER BY rank LIMIT ?2")?;
+        let result = stmt.query_map(params![query, limit], |r| {
+            let path: Option<String> = r.get(4)?;
+            // Sent files only know their path; name them after it
+            let file_name = r.get::<_, Option<String>>(3)?.unwrap_or_else(|| {
+                path.as_deref().and_then(|p| std::path::Path::new(p).file_name())
+                    .map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+            });
+            Ok(AttachmentTextHit {
+                download_id: r.get(0)?, message_id: r.get(1)?, kind: r.get(2)?, file_name, path,
+                snippet: r.get(5)?,
+            })
+        })?.collect();
+        result
+    }
+
+    // ============ FILE HASH INDEX ============
+
+    pub fn index_file_hash(&self, checksum: &str, path: &str, size: i64) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO file_hashes (checksum, path, size, indexed_at) VALUES (?1,?2,?3,?4)",
+            params![checksum.to_lowercase(), path, size, now()])?;
+        Ok(())
+    }
+
+    /// (path, size) of the copy indexed for this checksum
+    pub fn find_file_by_hash(&self, checksum: &str) -> SqliteResult<Option<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT path, size FROM file_hashes WHERE checksum=?1",
+            params![checksum.to_lowercase()], |r| Ok((r.get(0)?, r.get(1)?))) {
+            Ok(found) => Ok(Some(found)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn is_path_hashed(&self, path: &str) -> SqliteResult<bool> {
+        self.conn.lock().unwrap().query_row(
+            "SELECT EXISTS(SELECT 1 FROM file_hashes WHERE path=?1)", params![path], |r| r.get(0))
+    }
+
+    pub fn forget_file_path(&self, path: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM file_hashes WHERE path=?1", params![path])?;
+        Ok(())
+    }
+
+    pub fn forget_file_hash(&self, checksum: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM file_hashes WHERE checksum=?1", params![checksum.to_lowercase()])?;
+        Ok(())
+    }
+
+    // ============ STORED SESSIONS ============
+
+    pub fn save_crypto_session(&self, session: &StoredSession) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO crypto_sessions (peer_id, peer_public_key, nonce, sealed, updated_at) VALUES (?1,?2,?3,?4,?5)",
+            params![session.peer_id, session.peer_public_key, session.nonce, session.sealed, now()])?;
+        Ok(())
+    }
+
+    pub fn get_crypto_sessions(&self) -> SqliteResult<Vec<StoredSession>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT peer_id, peer_public_key, nonce, sealed FROM crypto_sessions")?;
+        let result = stmt.query_map([], |r| Ok(StoredSession {
+            peer_id: r.get(0)?, peer_public_key: r.get(1)?, nonce: r.get(2)?, sealed: r.get(3)?,
+        }))?.collect::<SqliteResult<_>>()?;
+        Ok(result)
+    }
+
+    pub fn delete_crypto_session(&self, peer_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM crypto_sessions WHERE peer_id=?1", params![peer_id])?;
+        Ok(())
+    }
+
+    pub fn clear_crypto_sessions(&self) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM crypto_sessions", [])?;
+        Ok(())
+    }
+
+    // ============ CONVERSATION TOMBSTONES ============
+
+    /// Clear a conversation up to `cleared_at` (soft delete) and keep it from coming back
+    pub fn clear_conversation(&self, local_id: &str, peer_id: &str, cleared_at: &str, cleared_by: &str) -> SqliteResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO conversation_tombstones (peer_id, cleared_at, cleared_by) VALUES (?1,?2,?3)
+             ON CONFLICT(peer_id) DO UPDATE SET cleared_at=MAX(cleared_at, excluded.cleared_at), cleared_by=excluded.cleared_by",
+            params![peer_id, cleared_at, cleared_by])?;
+        let n = tx.execute(
+            "UPDATE messages SET deleted_at=?4 WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1))
+             AND created_at <= ?3 AND deleted_at IS NULL",
+            params![local_id, peer_id, cleared_at, now()])?;
+        tx.commit()?;
+        self.conversation_changed(local_id, peer_id);
+        Ok(n)
+    }
+
+    pub fn get_conversation_tombstone(&self, peer_id: &str) -> SqliteResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT cleared_at FROM conversation_tombstones WHERE peer_id=?1", params![peer_id], |r| r.get(0)) {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // ============ ALERT KEYWORDS ============
+
+    /// Returns the stored keyword; adding one that exists (any case) returns the existing one
+    pub fn add_alert_keyword(&self, keyword: &str) -> SqliteResult<AlertKeyword> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("INSERT OR IGNORE INTO alert_keywords (id, keyword, created_at) VALUES (?1,?2,?3)",
+            params![generate_id(), keyword, now()])?;
+        conn.query_row("SELECT id, keyword, created_at FROM alert_keywords WHERE keyword=?1", params![keyword],
+            |r| Ok(AlertKeyword { id: r.get(0)?, keyword: r.get(1)?, created_at: r.get(2)? }))
+    }
+
+    pub fn remove_alert_keyword(&self, id: &str) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute("DELETE FROM alert_keywords WHERE id=?1", params![id])? > 0)
+    }
+
+    pub fn get_alert_keywords(&self) -> SqliteResult<Vec<AlertKeyword>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, keyword, created_at FROM alert_keywords ORDER BY keyword")?;
+        let result = stmt.query_map([], |r| Ok(AlertKeyword { id: r.get(0)?, keyword: r.get(1)?, created_at: r.get(2)? }))?
+            .collect();
+        result
+    }
+
+    // ============ TRANSLATIONS ============
+
+    /// Content and type of a direct or group message; None once it is deleted
+    pub fn get_message_text(&self, id: &str) -> SqliteResult<Option<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT content, COALESCE(message_type,'text') FROM messages WHERE id=?1 AND deleted_at IS NULL
+             UNION ALL SELECT content, COALESCE(message_type,'text') FROM group_messages WHERE id=?1 LIMIT 1",
+            params![id], |r| Ok((r.get(0)?, r.get(1)?))) {
+            Ok(row) => Ok(Some(row)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_translation(&self, message_id: &str, lang: &str) -> SqliteResult<Option<MessageTranslation>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT message_id,lang,text,source_lang,created_at FROM message_translations WHERE message_id=?1 AND lang=?2",
+            params![message_id, lang],
+            |r| Ok(MessageTranslation { message_id: r.get(0)?, lang: r.get(1)?, text: r.get(2)?, source_lang: r.get(3)?, created_at: r.get(4)? })) {
+            Ok(t) => Ok(Some(t)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save_translation(&self, t: &MessageTranslation) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO message_translations (message_id,lang,text,source_lang,created_at) VALUES (?1,?2,?3,?4,?5)",
+            params![t.message_id, t.lang, t.text, t.source_lang, t.created_at])?;
+        Ok(())
+    }
+
+    /// Forget every cached translation (the backend changed)
+    pub fn clear_translations(&self) -> SqliteResult<usize> {
+        self.conn.lock().unwrap().execute("DELETE FROM message_translations", [])
+    }
+
+    // ============ CONTACT TAGS ============
+
+    pub fn add_user_tag(&self, user_id: &str, tag: &str) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute("INSERT OR IGNORE INTO user_tags (user_id,tag,created_at) VALUES (?1,?2,?3)",
+            params![user_id, tag, now()])? > 0)
+    }
+
+    pub fn remove_user_tag(&self, user_id: &str, tag: &str) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute("DELETE FROM user_tags WHERE user_id=?1 AND tag=?2", params![user_id, tag])? > 0)
+    }
+
+    /// Drop every tag of a contact (it was deleted)
+    pub fn clear_user_tags(&self, user_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM user_tags WHERE user_id=?1", params![user_id])?;
+        Ok(())
+    }
+
+    /// Every (user_id, tag) pair, tags in alphabetical order
+    pub fn get_user_tags(&self) -> SqliteResult<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT user_id, tag FROM user_tags ORDER BY tag")?;
+        let result = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?.collect();
+        result
+    }
+
+    pub fn get_tagged_user_ids(&self, tag: &str) -> SqliteResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT user_id FROM user_tags WHERE tag=?1")?;
+        let result = stmt.query_map(params![tag], |r| r.get(0))?.collect();
+        result
+    }
+
+    pub fn get_tag_counts(&self) -> SqliteResult<Vec<TagCount>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT tag, COUNT(*) FROM user_tags GROUP BY tag ORDER BY tag")?;
+        let result = stmt.query_map([], |r| Ok(TagCount { tag: r.get(0)?, count: r.get(1)? }))?.collect();
+        result
+    }
+
+    // ============ CHAT FOLDERS ============
+
+    pub fn create_chat_folder(&self, name: &str, color: Option<&str>) -> SqliteResult<ChatFolder> {
+        let folder = ChatFolder { id: generate_id(), name: name.to_string(), color: color.map(String::from), created_at: now() };
+        self.conn.lock().unwrap().execute("INSERT INTO chat_folders (id,name,color,created_at) VALUES (?1,?2,?3,?4)",
+            params![folder.id, folder.name, folder.color, folder.created_at])?;
+        Ok(folder)
+    }
+
+    pub fn update_chat_folder(&self, id: &str, name: &str, color: Option<&str>) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute("UPDATE chat_folders SET name=?2, color=?3 WHERE id=?1", params![id, name, color])? > 0)
+    }
+
+    pub fn delete_chat_folder(&self, id: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM chat_folder_members WHERE folder_id=?1", params![id])?;
+        Ok(conn.execute("DELETE FROM chat_folders WHERE id=?1", params![id])? > 0)
+    }
+
+    pub fn get_chat_folder(&self, id: &str) -> SqliteResult<Option<ChatFolder>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT id,name,color,created_at FROM chat_folders WHERE id=?1", params![id],
+            |r| Ok(ChatFolder { id: r.get(0)?, name: r.get(1)?, color: r.get(2)?, created_at: r.get(3)? })) {
+            Ok(f) => Ok(Some(f)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_chat_folders(&self) -> SqliteResult<Vec<ChatFolder>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id,name,color,created_at FROM chat_folders ORDER BY created_at, name")?;
+        let result = stmt.query_map([], |r| Ok(ChatFolder { id: r.get(0)?, name: r.get(1)?, color: r.get(2)?, created_at: r.get(3)? }))?
+            .collect();
+        result
+    }
+
+    pub fn add_chat_to_folder(&self, folder_id: &str, chat_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("INSERT OR IGNORE INTO chat_folder_members (folder_id,chat_id,added_at) VALUES (?1,?2,?3)",
+            params![folder_id, chat_id, now()])?;
+        Ok(())
+    }
+
+    pub fn remove_chat_from_folder(&self, folder_id: &str, chat_id: &str) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute("DELETE FROM chat_folder_members WHERE folder_id=?1 AND chat_id=?2",
+            params![folder_id, chat_id])? > 0)
+    }
+
+    /// Take a chat out of every folder (the contact or group was deleted)
+    pub fn remove_chat_from_folders(&self, chat_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute("DELETE FROM chat_folder_members WHERE chat_id=?1", params![chat_id])?;
+        Ok(())
+    }
+
+    pub fn get_folder_chats(&self, folder_id: &str) -> SqliteResult<Vec<FolderChat>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT m.chat_id, COALESCE(u.username, g.name, ''), g.id IS NOT NULL, m.added_at
+             FROM chat_folder_members m
+             LEFT JOIN users u ON u.id=m.chat_id LEFT JOIN groups g ON g.id=m.chat_id
+             WHERE m.folder_id=?1 ORDER BY m.added_at")?;
+        let result = stmt.query_map(params![folder_id], |r| Ok(FolderChat {
+            chat_id: r.get(0)?, name: r.get(1)?, is_group: r.get(2)?, added_at: r.get(3)?, unread: 0,
+        }))?.collect();
+        result
+    }
+
+    /// Every (folder_id, chat_id) pair
+    pub fn get_folder_assignments(&self) -> SqliteResult<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT folder_id, chat_id FROM chat_folder_members")?;
+        let result = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?.collect();
+        result
+    }
+
+    // ============ MESSAGE REQUESTS ============
+
+    /// Held again when the sender retries: the id (message, transfer or group id) is kept once
+    pub fn add_message_request(&self, request: &MessageRequest, payload: &str) -> SqliteResult<bool> {
+        Ok(self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO message_requests (id,peer_id,sender_name,kind,preview,payload,created_at)
+             VALUES (?1,?2,?3,?4,?5,?6,?7)",
+            params![request.id, request.peer_id, request.sender_name, request.kind, request.preview, payload, request.created_at])? > 0)
+    }
+
+    pub fn get_message_requests(&self) -> SqliteResult<Vec<MessageRequest>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id,peer_id,sender_name,kind,preview,created_at FROM message_requests ORDER BY created_at")?;
+        let result = stmt.query_map([], |r| Ok(MessageRequest {
+            id: r.get(0)?, peer_id: r.get(1)?, sender_name: r.get(2)?, kind: r.get(3)?, preview: r.get(4)?, created_at: r.get(5)?,
+        }))?.collect();
+        result
+    }
+
+    /// Remove a peer's held requests, returning their payloads oldest first
+    pub fn take_message_requests(&self, peer_id: &str) -> SqliteResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let payloads = conn.prepare("SELECT payload FROM message_requests WHERE peer_id=?1 ORDER BY created_at")?
+            .query_map(params![peer_id], |r| r.get(0))?.collect::<SqliteResult<Vec<String>>>()?;
+        conn.execute("DELETE FROM message_requests WHERE peer_id=?1", params![peer_id])?;
+        Ok(payloads)
+    }
+
+    pub fn approve_contact(&self, device_id: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO approved_contacts (device_id, approved_at) VALUES (?1,?2)", params![device_id, now()])?;
+        Ok(())
+    }
+
+    /// Approved, trusted, or already in a conversation with us (either direction); meeting chat
+    /// older versions stored as direct messages doesn't count
+    pub fn is_known_contact(&self, local_id: &str, peer_id: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM approved_contacts WHERE device_id=?2)
+                 OR EXISTS(SELECT 1 FROM peers WHERE device_id=?2 AND is_trusted=1 AND trusted_key IS NOT NULL)
+                 OR EXISTS(SELECT 1 FROM messages WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1))
+                     AND content NOT LIKE '[MEETING_CHAT]%')",
+            params![local_id, peer_id], |r| r.get(0))
+    }
+
+    pub fn record_pairing(&self, device_id: &str, key_fingerprint: &str) -> SqliteResult<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO paired_peers (device_id, key_fingerprint, paired_at) VALUES (?1,?2,?3)",
+            params![device_id, key_fingerprint, now()])?;
+        Ok(())
+    }
+
+    /// Trusted, or paired by PIN with the key it has now (`key_fingerprint`; see pin_pairing)
+    pub fn is_paired_contact(&self, peer_id: &str, key_fingerprint: Option<&str>) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM paired_peers WHERE device_id=?1 AND key_fingerprint=?2)
+                 OR EXISTS(SELECT 1 FROM peers WHERE device_id=?1 AND is_trusted=1 AND trusted_key IS NOT NULL)",
+            params![peer_id, key_fingerprint], |r| r.get(0))
+    }
+
+    // ============ CHAT STATS ============
+
+    /// Every message with a peer, oldest first (see chat_stats)
+    pub fn get_conversation_activity(&self, local_id: &str, peer_id: &str) -> SqliteResult<Vec<ActivityRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT sender_id, COALESCE(message_type,'text'), created_at FROM messages
+             WHERE ((sender_id=?1 AND receiver_id=?2) OR (sender_id=?2 AND receiver_id=?1)) AND deleted_at IS NULL
+             ORDER BY created_at")?;
+        let result = stmt.query_map(params![local_id, peer_id],
+            |r| Ok(ActivityRow { sender_id: r.get(0)?, message_type: r.get(1)?, created_at: r.get(2)? }))?.collect();
+        result
+    }
+
+    /// Every message in a group, oldest first
+    pub fn get_group_activity(&self, group_id: &str) -> SqliteResult<Vec<ActivityRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT sender_id, COALESCE(message_type,'text'), created_at FROM group_messages
+             WHERE group_id=?1 ORDER BY created_at")?;
+        let result = stmt.query_map(params![group_id],
+            |r| Ok(ActivityRow { sender_id: r.get(0)?, message_type: r.get(1)?, created_at: r.get(2)? }))?.collect();
+        result
+    }
+
+    // ============ DIAGNOSTICS ============
+
+    /// PRAGMA integrity_check; empty when the database is fine
+    pub fn integrity_check(&self) -> SqliteResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows: Vec<String> = stmt.query_map([], |r| r.get(0))?.collect::<SqliteResult<_>>()?;
+        Ok(rows.into_iter().filter(|r| r != "ok").collect())
+    }
+
+    /// Query cache (hits, misses) since startup, for diagnostics reports
+    pub fn cache_stats(&self) -> (u64, u64) { self.cache.stats() }
+
+    /// Row count per table, for diagnostics reports
+    pub fn get_table_counts(&self) -> SqliteResult<Vec<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+        let tables: Vec<String> = stmt.query_map([], |r| r.get(0))?.collect::<SqliteResult<_>>()?;
+        tables.into_iter().map(|t| {
+            let n: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", t), [], |r| r.get(0))?;
+            Ok((t, n))
+        }).collect()
+    }
+}
 
 pub fn generate_id() -> String { uuid::Uuid::new_v4().to_string() }
 pub fn now() -> String { Utc::now().to_rfc3339() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, sender: &str, receiver: &str, created_at: &str, lamport: i64) -> Message {
+        Message {
+            id: id.to_string(), sender_id: sender.to_string(), receiver_id: receiver.to_string(),
+            content: "hi".to_string(), message_type: "text".to_string(), file_path: None,
+            is_read: true, is_delivered: true, created_at: created_at.to_string(), lamport,
+            status: MessageStatus::Read,
+        }
+    }
+
+    #[test]
+    fn test_lamport_orders_ties_and_restamps_runaway_clocks() {
+        let db = Database::new_in_memory().unwrap();
+        let at = now();
+        // Unstamped messages get the next local stamp
+        db.create_message(&message("m1", "a", "b", &at, 0)).unwrap();
+        assert_eq!(db.get_message("m1").unwrap().unwrap().lamport, 1);
+
+        // Equal timestamps are ordered by stamp, and the local clock moves past the peer's
+        db.create_message(&message("m0", "b", "a", &at, 7)).unwrap();
+        let ordered: Vec<String> = db.get_messages_between("a", "b", 10).unwrap()
+            .into_iter().map(|m| m.id).collect();
+        assert_eq!(ordered, ["m0", "m1"]);
+        assert_eq!(db.next_lamport().unwrap(), 8);
+
+        // A stamp far beyond our clock can't push it toward overflow: it's restamped locally
+        db.create_message(&message("m2", "b", "a", &at, i64::MAX)).unwrap();
+        assert_eq!(db.get_message("m2").unwrap().unwrap().lamport, 8);
+        assert_eq!(db.next_lamport().unwrap(), 9);
+    }
+
+    #[test]
+    fn test_lamport_tick_stops_at_the_top() {
+        assert_eq!(Database::tick_lamport(41), 42);
+        assert_eq!(Database::tick_lamport(i64::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn test_message_arriving_twice_merges_flags() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("bob", "Bob", None).unwrap();
+        db.upsert_peer_as_user("me", "Me", None).unwrap();
+        db.create_message(&message("m1", "bob", "me", &now(), 0)).unwrap();
+
+        // The offline queue resends it later: unread, now with a file
+        let mut resent = message("m1", "bob", "me", &now(), 0);
+        resent.content = "changed".to_string();
+        resent.is_read = false;
+        resent.is_delivered = false;
+        resent.file_path = Some("/tmp/photo.jpg".to_string());
+        db.create_message(&resent).unwrap();
+        let stored = db.get_message("m1").unwrap().unwrap();
+        assert!(stored.is_read && stored.is_delivered);
+        assert_eq!(stored.file_path.as_deref(), Some("/tmp/photo.jpg"));
+        assert_eq!(stored.content, "hi");
+
+        // A linked device's copy can only move the flags forward
+        let mut synced = message("m2", "me", "bob", &now(), 0);
+        synced.is_delivered = false;
+        db.create_message(&synced).unwrap();
+        synced.is_delivered = true;
+        db.apply_synced_message(&synced).unwrap();
+        assert!(db.get_message("m2").unwrap().unwrap().is_delivered);
+
+        // Someone else reusing the id can't mark our message read or attach a file to it
+        let mut outgoing = message("m3", "me", "bob", &now(), 0);
+        outgoing.is_read = false;
+        outgoing.is_delivered = false;
+        outgoing.status = MessageStatus::Sent;
+        db.create_message(&outgoing).unwrap();
+        let mut spoofed = message("m3", "mallory", "me", &now(), 0);
+        spoofed.file_path = Some("/tmp/evil".to_string());
+        db.create_message(&spoofed).unwrap();
+        let stored = db.get_message("m3").unwrap().unwrap();
+        assert!(!stored.is_read && !stored.is_delivered);
+        assert_eq!((stored.sender_id.as_str(), stored.file_path), ("me", None));
+    }
+}
@@ -0,0 +1,315 @@
+// src-tauri/src/db_recovery.rs
+// Database integrity check and recovery. When the database can't be opened or migrated at
+// startup because the file is damaged (SQLite reports it corrupt or not a database, or its
+// integrity check fails), whatever is still readable is dumped into a fresh file, the damaged
+// one is moved to <app dir>/corrupt/ (never over an earlier one) and the app carries on with the
+// recovered copy instead of failing to start. Any other failure (locked, no permission) is
+// reported as is and the file is left alone. The same recovery can be run on demand after
+// check_database reports problems.
+
+use crate::commands::AppState;
+use crate::db::{now, Database};
+use chrono::Utc;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, ErrorCode, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+const LAST_RECOVERY_KEY: &str = "last_db_recovery";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RecoveryReport {
+    pub recovered_at: String,
+    /// What triggered it (the open error, or a manual repair)
+    pub reason: String,
+    /// Where the damaged file went
+    pub quarantined_path: Option<String>,
+    pub tables: usize,
+    pub rows_recovered: usize,
+    /// Tables or rows that couldn't be read
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    pub problems: Vec<String>,
+    pub path: String,
+    pub last_recovery: Option<RecoveryReport>,
+}
+
+/// Copy one table row by row; a read error ends the table but keeps what was copied
+fn copy_rows(src: &Connection, dest: &Connection, table: &str, report: &mut RecoveryReport) {
+    let mut copy = || -> rusqlite::Result<usize> {
+        let mut select = src.prepare(&format!("SELECT * FROM \"{}\"", table))?;
+        let cols = select.column_count();
+        let placeholders = vec!["?"; cols].join(",");
+        let tx = dest.unchecked_transaction()?;
+        let mut copied = 0;
+        {
+            let mut insert = tx.prepare(&format!(
+                "INSERT OR IGNORE INTO \"{}\" VALUES ({})",
+                table, placeholders
+            ))?;
+            let mut rows = select.query([])?;
+            loop {
+                match rows.next() {
+                    Ok(Some(row)) => {
+                        let values = (0..cols)
+                            .map(|i| row.get::<_, Value>(i))
+                            .collect::<rusqlite::Result<Vec<_>>>()?;
+                        copied += insert.execute(params_from_iter(values))?;
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        report
+                            .errors
+                            .push(format!("{}: stopped after {} rows: {}", table, copied, e));
+                        break;
+                    }
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(copied)
+    };
+    match copy() {
+        Ok(n) => report.rows_recovered += n,
+        Err(e) => report.errors.push(format!("{}: {}", table, e)),
+    }
+}
+
+/// Recreate every table of `src` in `dest` and copy what can be read. Indexes are left to
+/// the migrations that run when the recovered database is opened.
+fn dump_and_reload(src: &Connection, dest: &Connection, report: &mut RecoveryReport) {
+    let tables = src
+        .prepare(
+            "SELECT name, sql FROM sqlite_master
+             WHERE type='table' AND name NOT LIKE 'sqlite_%' AND sql IS NOT NULL",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        });
+    let tables = match tables {
+        Ok(t) => t,
+        Err(e) => {
+            report.errors.push(format!("Schema unreadable: {}", e));
+            return;
+        }
+    };
+    for (name, sql) in tables {
+        if let Err(e) = dest.execute_batch(&sql) {
+            report.errors.push(format!("{}: {}", name, e));
+            continue;
+        }
+        report.tables += 1;
+        copy_rows(src, dest, &name, report);
+    }
+}
+
+/// Move the database file and its WAL/SHM/journal siblings to <dir>/corrupt/
+fn quarantine(path: &Path) -> Result<PathBuf, String> {
+    let dir = path
+        .parent()
+        .map(|p| p.join("corrupt"))
+        .ok_or("Database path has no parent")?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+    let name = (0..)
+        .map(|n| match n {
+            0 => format!("pingo-{}.db", stamp),
+            n => format!("pingo-{}-{}.db", stamp, n),
+        })
+        .find(|name| !dir.join(name).exists())
+        .ok_or("No free name in the corrupt folder")?;
+    let target = dir.join(&name);
+    fs::rename(path, &target).map_err(|e| e.to_string())?;
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let side = PathBuf::from(format!("{}{}", path.display(), suffix));
+        if side.exists() {
+            let _ = fs::rename(&side, dir.join(format!("{}{}", name, suffix)));
+        }
+    }
+    Ok(target)
+}
+
+/// Rebuild the database at `path` from whatever is readable, quarantining the original.
+/// A file with nothing readable still gets replaced, by an empty database.
+pub fn recover_file(path: &Path, reason: &str) -> Result<RecoveryReport, String> {
+    let fresh = PathBuf::from(format!("{}.recovering", path.display()));
+    let _ = fs::remove_file(&fresh);
+    let mut report = RecoveryReport {
+        recovered_at: now(),
+        reason: reason.to_string(),
+        ..Default::default()
+    };
+    {
+        let dest = Connection::open(&fresh).map_err(|e| e.to_string())?;
+        match Connection::open(path) {
+            Ok(src) => dump_and_reload(&src, &dest, &mut report),
+            Err(e) => report.errors.push(format!("Couldn't open: {}", e)),
+        }
+    }
+    if path.exists() {
+        report.quarantined_path = Some(quarantine(path)?.to_string_lossy().to_string());
+    }
+    fs::rename(&fresh, path).map_err(|e| e.to_string())?;
+    warn!(
+        "Database recovered: {} tables, {} rows, {} errors",
+        report.tables,
+        report.rows_recovered,
+        report.errors.len()
+    );
+    Ok(report)
+}
+
+fn remember(db: &Database, report: &RecoveryReport) {
+    if let Ok(json) = serde_json::to_string(report) {
+        let _ = db.set_setting(LAST_RECOVERY_KEY, &json);
+    }
+}
+
+/// Whether an error says the file itself is damaged
+fn is_corruption(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+    )
+}
+
+/// What PRAGMA integrity_check finds in the file, opened read-only. Empty when it's fine or
+/// the check couldn't run for reasons other than damage.
+fn file_problems(path: &Path) -> Vec<String> {
+    let check = || -> rusqlite::Result<Vec<String>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt
+            .query_map([], |r| r.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows.into_iter().filter(|r| r != "ok").collect())
+    };
+    match check() {
+        Ok(problems) => problems,
+        Err(e) if is_corruption(&e) => vec![e.to_string()],
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Open the active profile's database, recovering it if it's damaged
+pub fn open() -> Result<Database, String> {
+    let error = match Database::new() {
+        Ok(db) => return Ok(db),
+        Err(e) => e,
+    };
+    let path = Database::get_db_path();
+    let reason = if is_corruption(&error) {
+        error.to_string()
+    } else {
+        let problems = file_problems(&path);
+        if problems.is_empty() {
+            return Err(format!("Opening the database failed: {}", error));
+        }
+        format!("{} (integrity check: {})", error, problems.join("; "))
+    };
+    warn!(
+        "Opening the database failed ({}), attempting recovery",
+        reason
+    );
+    let report = recover_file(&path, &reason)?;
+    let db =
+        Database::new().map_err(|e| format!("Database still unusable after recovery: {}", e))?;
+    remember(&db, &report);
+    info!("Continuing with the recovered database");
+    Ok(db)
+}
+
+// ============ COMMANDS ============
+
+/// Run PRAGMA integrity_check on the live database
+#[tauri::command]
+pub fn check_database(state: State<AppState>) -> DatabaseCheck {
+    let problems = state
+        .db
+        .integrity_check()
+        .unwrap_or_else(|e| vec![e.to_string()]);
+    let last_recovery = state
+        .db
+        .get_setting(LAST_RECOVERY_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok());
+    DatabaseCheck {
+        ok: problems.is_empty(),
+        problems,
+        path: Database::get_db_path().to_string_lossy().to_string(),
+        last_recovery,
+    }
+}
+
+/// Dump and reload the live database into a fresh file (after check_database found problems)
+#[tauri::command]
+pub fn repair_database<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+) -> Result<RecoveryReport, String> {
+    state.db.release().map_err(|e| e.to_string())?;
+    let result = recover_file(&Database::get_db_path(), "Repair requested");
+    state.db.reopen().map_err(|e| e.to_string())?;
+    let report = result?;
+    remember(&state.db, &report);
+    let _ = app.emit("database-recovered", &report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recover_file_keeps_rows_and_quarantines_original() {
+        let dir = std::env::temp_dir().join(format!("pingo_recover_{}", crate::db::generate_id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pingo.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+                 INSERT INTO settings VALUES ('a','1'),('b','2');",
+            )
+            .unwrap();
+        }
+
+        let report = recover_file(&path, "test").unwrap();
+        assert_eq!((report.tables, report.rows_recovered), (1, 2));
+        assert!(report.errors.is_empty());
+        assert!(Path::new(report.quarantined_path.as_deref().unwrap()).exists());
+        let conn = Connection::open(&path).unwrap();
+        let n: i64 = conn
+            .query_row("SELECT COUNT(*) FROM settings", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(n, 2);
+
+        // Garbage instead of a database: replaced by an empty one
+        drop(conn);
+        fs::write(&path, b"definitely not sqlite").unwrap();
+        let report = recover_file(&path, "test").unwrap();
+        assert_eq!(report.rows_recovered, 0);
+        assert!(!report.errors.is_empty());
+        assert!(Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x)")
+            .is_ok());
+        // Both damaged originals are kept, even within the same second
+        assert_eq!(fs::read_dir(dir.join("corrupt")).unwrap().count(), 2);
+
+        // Only damage counts as a reason to recover
+        assert!(file_problems(&path).is_empty());
+        fs::write(&path, b"definitely not sqlite").unwrap();
+        assert!(!file_problems(&path).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,136 @@
+// src-tauri/src/deleted_messages.rs
+// Deleted messages: deleting is a soft delete that can be undone, and soft-deleted messages
+// are purged for good after PURGE_AFTER_DAYS. Deleting "for everyone" also sends the peer a
+// MessageDeleted tombstone so the message disappears on their side too.
+
+use crate::commands::{self, AppState};
+use crate::db::Database;
+use crate::signaling::SignalingMessage;
+use chrono::{Duration as ChronoDuration, Utc};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const PURGE_AFTER_DAYS: i64 = 30;
+const PURGE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Drop messages that were deleted more than PURGE_AFTER_DAYS ago
+pub fn purge(db: &Database) {
+    let cutoff = (Utc::now() - ChronoDuration::days(PURGE_AFTER_DAYS)).to_rfc3339();
+    match db.purge_deleted_messages(&cutoff) {
+        Ok(0) => {}
+        Ok(n) => info!("Purged {} deleted messages", n),
+        Err(e) => warn!("Purging deleted messages failed: {}", e),
+    }
+}
+
+/// Purge old deleted messages now and then for the life of the app
+pub fn start_purger<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || loop {
+        purge(&app.state::<AppState>().db);
+        thread::sleep(PURGE_INTERVAL);
+    });
+}
+
+/// Tombstone from a peer: only the message's author can delete it for everyone
+pub fn handle_tombstone<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    from: &str,
+    message_id: &str,
+) {
+    let sender = db.get_message_sender(message_id).ok().flatten();
+    if sender.as_deref() != Some(from) {
+        warn!(
+            "Ignoring delete of {} from {}: not the author",
+            message_id, from
+        );
+        return;
+    }
+    if let Err(e) = db.delete_message(message_id) {
+        warn!("Deleting message {} failed: {}", message_id, e);
+        return;
+    }
+    let _ = app.emit(
+        "message-deleted",
+        serde_json::json!({ "message_id": message_id, "from": from, "for_everyone": true }),
+    );
+}
+
+// ============ COMMANDS ============
+
+/// Undo a delete made in the last PURGE_AFTER_DAYS days
+#[tauri::command]
+pub fn restore_message(state: State<AppState>, message_id: String) -> Result<bool, String> {
+    state
+        .db
+        .restore_message(&message_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Delete one of our messages here and on the peer's side
+#[tauri::command]
+pub fn delete_for_everyone(state: State<AppState>, message_id: String) -> Result<(), String> {
+    let message = state
+        .db
+        .get_message(&message_id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Message not found")?;
+    let local_id = state.device_id();
+    if message.sender_id != local_id {
+        return Err("Only messages you sent can be deleted for everyone".to_string());
+    }
+    state
+        .db
+        .delete_message(&message_id)
+        .map_err(|e| e.to_string())?;
+    let tombstone = SignalingMessage::MessageDeleted {
+        from: local_id,
+        to: message.receiver_id.clone(),
+        message_id,
+    };
+    commands::send_to_peer(&state, &message.receiver_id, &tombstone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{now, Message};
+    use crate::delivery_status::MessageStatus;
+
+    #[test]
+    fn test_soft_delete_restore_and_purge() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("a", "A", None).unwrap();
+        db.upsert_peer_as_user("b", "B", None).unwrap();
+        db.create_message(&Message {
+            id: "m1".to_string(),
+            sender_id: "a".to_string(),
+            receiver_id: "b".to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: false,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Queued,
+        })
+        .unwrap();
+
+        db.delete_message("m1").unwrap();
+        assert!(db.get_messages_between("a", "b", 10).unwrap().is_empty());
+        assert!(db.restore_message("m1").unwrap());
+        assert_eq!(db.get_messages_between("a", "b", 10).unwrap().len(), 1);
+
+        // Recently deleted messages survive a purge
+        db.delete_message("m1").unwrap();
+        purge(&db);
+        assert!(db.get_message("m1").unwrap().is_some());
+        let future = (Utc::now() + ChronoDuration::days(1)).to_rfc3339();
+        assert_eq!(db.purge_deleted_messages(&future).unwrap(), 1);
+        assert!(db.get_message("m1").unwrap().is_none());
+    }
+}
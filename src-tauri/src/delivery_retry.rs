@@ -0,0 +1,399 @@
+// src-tauri/src/delivery_retry.rs
+// Retry policy of the delivery queue. Every attempt at sending a direct message is counted and
+// schedules the next one after the matching step of the backoff schedule (the last step repeats);
+// a message that still has no ack by then is sent again while its peer is online. Once it has
+// used max_attempts (0 = no limit) it is given up on: "keep_queued" puts it back in the queue,
+// where it goes out again with the next flush (reconnect, peer coming online), and "mark_failed"
+// marks it failed and keeps every flush from sending it until it's retried by hand.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::db::{now, Database, Message};
+use crate::delivery_status::{self, MessageStatus};
+use crate::signaling::SignalingMessage;
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime, State};
+use tracing::{debug, info, warn};
+
+const SETTING_KEY: &str = "delivery_retry";
+const TICK: Duration = Duration::from_secs(5);
+const MAX_BACKOFF_STEPS: usize = 20;
+const MAX_BACKOFF_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GiveUp {
+    /// Back in the queue for the next flush
+    #[default]
+    KeepQueued,
+    /// Failed until retried by hand
+    MarkFailed,
+}
+
+impl GiveUp {
+    /// Where a message that ran out of attempts ends up
+    fn status(self) -> MessageStatus {
+        match self {
+            GiveUp::KeepQueued => MessageStatus::Queued,
+            GiveUp::MarkFailed => MessageStatus::Failed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts before giving up; 0 keeps retrying
+    pub max_attempts: u32,
+    /// Seconds to wait for an ack after each attempt; the last step repeats
+    pub backoff_secs: Vec<u64>,
+    #[serde(default)]
+    pub give_up: GiveUp,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 8,
+            backoff_secs: vec![5, 15, 60, 300, 900],
+            give_up: GiveUp::KeepQueued,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn validate(&self) -> Result<(), String> {
+        if self.backoff_secs.is_empty() || self.backoff_secs.len() > MAX_BACKOFF_STEPS {
+            return Err(format!(
+                "The backoff schedule needs 1 to {} steps",
+                MAX_BACKOFF_STEPS
+            ));
+        }
+        if self
+            .backoff_secs
+            .iter()
+            .any(|&s| s == 0 || s > MAX_BACKOFF_SECS)
+        {
+            return Err(format!(
+                "Backoff steps must be between 1 and {} seconds",
+                MAX_BACKOFF_SECS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Wait after attempt number `attempt` (1-based)
+    fn backoff(&self, attempt: u32) -> Duration {
+        let step = (attempt.max(1) as usize - 1).min(self.backoff_secs.len() - 1);
+        Duration::from_secs(self.backoff_secs[step])
+    }
+
+    fn limit(&self) -> Option<u32> {
+        (self.max_attempts > 0).then_some(self.max_attempts)
+    }
+
+    /// Attempts after which flushes leave a message alone
+    pub fn exhausted_after(&self) -> Option<u32> {
+        self.limit().filter(|_| self.give_up == GiveUp::MarkFailed)
+    }
+}
+
+pub fn load(db: &Database) -> RetryPolicy {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str::<RetryPolicy>(&v).ok())
+        .filter(|p| p.validate().is_ok())
+        .unwrap_or_default()
+}
+
+/// Count an attempt at sending `message_id` and schedule the next one
+pub fn record_attempt(db: &Database, message_id: &str) {
+    let policy = load(db);
+    let attempts = match db.record_send_attempt(message_id) {
+        Ok(n) => n,
+        Err(e) => {
+            warn!("Failed to count send attempt of {}: {}", message_id, e);
+            return;
+        }
+    };
+    let wait = ChronoDuration::from_std(policy.backoff(attempts)).unwrap_or_default();
+    let at = (Utc::now() + wait).to_rfc3339();
+    if let Err(e) = db.set_retry_at(message_id, Some(&at)) {
+        warn!("Failed to schedule retry of {}: {}", message_id, e);
+    }
+}
+
+fn sender_name(state: &AppState) -> String {
+    state
+        .db
+        .get_user(&state.device_id())
+        .ok()
+        .flatten()
+        .map(|u| u.username)
+        .unwrap_or_default()
+}
+
+/// Send a stored message to `peer_id` again
+pub(crate) fn resend(
+    state: &AppState,
+    peer_id: &str,
+    m: &Message,
+    sender_name: &str,
+) -> Result<(), String> {
+    let msg = SignalingMessage::ChatMessage {
+        from: state.device_id(),
+        to: peer_id.to_string(),
+        id: m.id.clone(),
+        content: m.content.clone(),
+        message_type: m.message_type.clone(),
+        sender_name: sender_name.to_string(),
+        timestamp: m.created_at.clone(),
+        lamport: m.lamport,
+        auto_reply: false,
+    };
+    delivery_status::track_send(state, &m.id, || send_to_peer(state, peer_id, &msg))
+}
+
+/// Every undelivered message to an online peer, whatever its schedule (reconnects, peers coming
+/// back); returns how many went out
+pub(crate) fn flush(state: &AppState) -> usize {
+    let local_id = state.device_id();
+    let name = sender_name(state);
+    let exhausted_after = load(&state.db).exhausted_after();
+    let mut resent = 0;
+    for peer in state.discovery.get_online_peers() {
+        let pending = state
+            .db
+            .get_undelivered_messages_for_peer(&local_id, &peer.device_id, exhausted_after)
+            .unwrap_or_default();
+        for m in pending {
+            match resend(state, &peer.device_id, &m, &name) {
+                Ok(()) => resent += 1,
+                Err(e) => warn!("Resend to {} failed: {}", peer.device_id, e),
+            }
+        }
+    }
+    resent
+}
+
+/// Give up on messages that are out of attempts, then resend the ones that are due
+fn tick(state: &AppState) {
+    let policy = load(&state.db);
+    let local_id = state.device_id();
+    let at = now();
+    if let Some(max) = policy.limit() {
+        let exhausted = state
+            .db
+            .take_exhausted_messages(&local_id, &at, max)
+            .unwrap_or_default();
+        let next = policy.give_up.status();
+        for id in exhausted {
+            info!("Gave up on {} after {} attempts", id, max);
+            delivery_status::update(state, &id, next);
+        }
+    }
+    let online: Vec<String> = state
+        .discovery
+        .get_online_peers()
+        .into_iter()
+        .map(|p| p.device_id)
+        .collect();
+    let due = state
+        .db
+        .get_due_retries(&local_id, &online, &at, policy.limit())
+        .unwrap_or_default();
+    let name = sender_name(state);
+    for m in due {
+        if let Err(e) = resend(state, &m.receiver_id, &m, &name) {
+            debug!("Retry of {} to {} failed: {}", m.id, m.receiver_id, e);
+        }
+    }
+}
+
+/// Work the queue for the life of the app
+pub fn start_scheduler<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(TICK);
+        let state = app.state::<AppState>();
+        if state.signaling.is_running() {
+            tick(&state);
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_delivery_retry_policy(state: State<AppState>) -> RetryPolicy {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_delivery_retry_policy(
+    state: State<AppState>,
+    policy: RetryPolicy,
+) -> Result<(), String> {
+    policy.validate()?;
+    let json = serde_json::to_string(&policy).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())
+}
+
+/// Start a message's attempts over and send it now (a failed message's "retry")
+#[tauri::command]
+pub fn retry_message(state: State<AppState>, message_id: String) -> Result<(), String> {
+    let message = state
+        .db
+        .get_message(&message_id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Message not found")?;
+    if message.sender_id != state.device_id() {
+        return Err("Only our own messages can be retried".to_string());
+    }
+    if message.status.is_delivered() {
+        return Ok(());
+    }
+    state
+        .db
+        .reset_send_attempts(&message_id)
+        .map_err(|e| e.to_string())?;
+    resend(&state, &message.receiver_id, &message, &sender_name(&state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_and_limits() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff_secs: vec![2, 10],
+            give_up: GiveUp::MarkFailed,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(10));
+        assert_eq!(policy.backoff(7), Duration::from_secs(10));
+        assert_eq!(policy.exhausted_after(), Some(3));
+
+        let keep = RetryPolicy {
+            give_up: GiveUp::KeepQueued,
+            ..policy.clone()
+        };
+        assert_eq!(keep.exhausted_after(), None);
+        let unlimited = RetryPolicy {
+            max_attempts: 0,
+            ..policy
+        };
+        assert_eq!(unlimited.limit(), None);
+        assert_eq!(unlimited.exhausted_after(), None);
+
+        assert!(RetryPolicy::default().validate().is_ok());
+        let empty = RetryPolicy {
+            backoff_secs: vec![],
+            ..RetryPolicy::default()
+        };
+        assert!(empty.validate().is_err());
+        let zero = RetryPolicy {
+            backoff_secs: vec![0],
+            ..RetryPolicy::default()
+        };
+        assert!(zero.validate().is_err());
+    }
+
+    fn queue(db: &Database, id: &str, to: &str, lamport: i64) {
+        db.create_message(&Message {
+            id: id.to_string(),
+            sender_id: "me".to_string(),
+            receiver_id: to.to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: false,
+            created_at: now(),
+            lamport,
+            status: MessageStatus::Queued,
+        })
+        .unwrap();
+    }
+
+    fn ids(messages: Vec<Message>) -> Vec<String> {
+        messages.into_iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn test_due_and_exhausted_messages() {
+        let db = Database::new_in_memory().unwrap();
+        for (id, name) in [("me", "Me"), ("bob", "Bob"), ("carol", "Carol")] {
+            db.upsert_peer_as_user(id, name, None).unwrap();
+        }
+        let past = "2000-01-01T00:00:00+00:00";
+        let future = "2999-01-01T00:00:00+00:00";
+        // m1: tried once and due; m2: not due yet; m3: out of attempts
+        queue(&db, "m1", "bob", 1);
+        queue(&db, "m2", "carol", 2);
+        queue(&db, "m3", "bob", 3);
+        db.record_send_attempt("m1").unwrap();
+        db.set_retry_at("m1", Some(past)).unwrap();
+        db.record_send_attempt("m2").unwrap();
+        db.set_retry_at("m2", Some(future)).unwrap();
+        for _ in 0..3 {
+            db.record_send_attempt("m3").unwrap();
+        }
+        db.set_retry_at("m3", Some(past)).unwrap();
+
+        let at = now();
+        let online = vec!["bob".to_string(), "carol".to_string()];
+        let due = db.get_due_retries("me", &online, &at, Some(3)).unwrap();
+        assert_eq!(ids(due), vec!["m1"]);
+        let due = db.get_due_retries("me", &online, &at, None).unwrap();
+        assert_eq!(ids(due), vec!["m1", "m3"]);
+        let carol_only = vec!["carol".to_string()];
+        assert!(db
+            .get_due_retries("me", &carol_only, &at, Some(3))
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .get_due_retries("me", &[], &at, Some(3))
+            .unwrap()
+            .is_empty());
+
+        // Given up on once, then left to the give-up policy
+        assert_eq!(
+            db.take_exhausted_messages("me", &at, 3).unwrap(),
+            vec!["m3"]
+        );
+        assert!(db.take_exhausted_messages("me", &at, 3).unwrap().is_empty());
+        assert_eq!(
+            ids(db.get_due_retries("me", &online, &at, None).unwrap()),
+            vec!["m1"]
+        );
+
+        // keep_queued: back in the queue, so the next flush sends it again
+        let keep = RetryPolicy {
+            max_attempts: 3,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(keep.give_up.status(), MessageStatus::Queued);
+        let flushed = db
+            .get_undelivered_messages_for_peer("me", "bob", keep.exhausted_after())
+            .unwrap();
+        assert_eq!(ids(flushed), vec!["m1", "m3"]);
+        // mark_failed: failed, and every flush leaves it alone
+        let fail = RetryPolicy {
+            give_up: GiveUp::MarkFailed,
+            ..keep
+        };
+        assert_eq!(fail.give_up.status(), MessageStatus::Failed);
+        let flushed = db
+            .get_undelivered_messages_for_peer("me", "bob", fail.exhausted_after())
+            .unwrap();
+        assert_eq!(ids(flushed), vec!["m1"]);
+    }
+}
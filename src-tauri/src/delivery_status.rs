@@ -0,0 +1,238 @@
+// src-tauri/src/delivery_status.rs
+// Delivery state of a direct message. Our own messages go queued -> sending -> sent, or end up
+// failed, and go through retrying whenever the delivery queue sends them again (see delivery_retry,
+// which also puts them back to queued or failed when it gives up); an ack makes them
+// delivered and a read receipt read, whatever the sending side last thought. Messages we received
+// start out delivered. The state lives in messages.status (is_read/is_delivered follow it) and
+// every change is pushed as "message-status-changed" { message_id, status }.
+
+use crate::commands::AppState;
+use crate::delivery_retry;
+use crate::event_bus::BackendEvent;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStatus {
+    /// Stored, not handed to the network yet
+    #[default]
+    Queued,
+    Sending,
+    /// Handed to the network, no ack yet
+    Sent,
+    /// Sending it again after a failure or a missing ack
+    Retrying,
+    /// The last attempt didn't go out, or the retry policy gave up on it
+    Failed,
+    Delivered,
+    Read,
+}
+
+impl MessageStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MessageStatus::Queued => "queued",
+            MessageStatus::Sending => "sending",
+            MessageStatus::Sent => "sent",
+            MessageStatus::Retrying => "retrying",
+            MessageStatus::Failed => "failed",
+            MessageStatus::Delivered => "delivered",
+            MessageStatus::Read => "read",
+        }
+    }
+
+    /// Unknown values (a newer version wrote them) read as sent
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "queued" => MessageStatus::Queued,
+            "sending" => MessageStatus::Sending,
+            "retrying" => MessageStatus::Retrying,
+            "failed" => MessageStatus::Failed,
+            "delivered" => MessageStatus::Delivered,
+            "read" => MessageStatus::Read,
+            _ => MessageStatus::Sent,
+        }
+    }
+
+    /// What the old flags say, for rows and peers that only have those
+    pub fn from_flags(is_read: bool, is_delivered: bool) -> Self {
+        if is_read {
+            MessageStatus::Read
+        } else if is_delivered {
+            MessageStatus::Delivered
+        } else {
+            MessageStatus::Sent
+        }
+    }
+
+    pub fn is_delivered(self) -> bool {
+        matches!(self, MessageStatus::Delivered | MessageStatus::Read)
+    }
+
+    /// Whether a message in this state may move to `next`
+    pub fn can_become(self, next: MessageStatus) -> bool {
+        use MessageStatus::*;
+        match (self, next) {
+            (a, b) if a == b => false,
+            (Read, _) => false,
+            (Delivered, next) => next == Read,
+            // Acks and receipts can overtake whatever we're doing on our side
+            (_, Delivered | Read) => true,
+            (Queued, Sending | Failed) => true,
+            (Sending | Retrying, Sent | Failed) => true,
+            (Queued | Sent | Failed, Retrying) => true,
+            // The retry policy gave up: no ack after the last attempt, or back to the queue
+            (Sent, Failed) => true,
+            (Sent | Failed, Queued) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Move a message to `next` if the state machine allows it, and push the change
+pub fn update(state: &AppState, message_id: &str, next: MessageStatus) -> bool {
+    try_update(state, message_id, next).unwrap_or_else(|e| {
+        warn!("Failed to set status of {}: {}", message_id, e);
+        false
+    })
+}
+
+/// Like update, but a database error is the caller's to report
+pub fn try_update(state: &AppState, message_id: &str, next: MessageStatus) -> Result<bool, String> {
+    let changed = state
+        .db
+        .set_message_status(message_id, next)
+        .map_err(|e| e.to_string())?;
+    if changed {
+        state.events.publish(BackendEvent::MessageStatusChanged {
+            message_id: message_id.to_string(),
+            status: next,
+        });
+    }
+    Ok(changed)
+}
+
+/// Track one attempt at sending a stored message: sending (retrying if it was tried before),
+/// then sent or failed depending on how `send` went
+pub fn track_send(
+    state: &AppState,
+    message_id: &str,
+    send: impl FnOnce() -> Result<(), String>,
+) -> Result<(), String> {
+    let first = state
+        .db
+        .get_message(message_id)
+        .ok()
+        .flatten()
+        .is_none_or(|m| m.status == MessageStatus::Queued);
+    let attempt = if first {
+        MessageStatus::Sending
+    } else {
+        MessageStatus::Retrying
+    };
+    update(state, message_id, attempt);
+    let result = send();
+    delivery_retry::record_attempt(&state.db, message_id);
+    let outcome = if result.is_ok() {
+        MessageStatus::Sent
+    } else {
+        MessageStatus::Failed
+    };
+    update(state, message_id, outcome);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{now, Database, Message};
+    use rusqlite::Connection;
+    use MessageStatus::*;
+
+    #[test]
+    fn test_transitions() {
+        let path = [Queued, Sending, Sent, Delivered, Read];
+        for pair in path.windows(2) {
+            assert!(pair[0].can_become(pair[1]));
+        }
+        // Failure and the offline queue
+        assert!(Sending.can_become(Failed));
+        assert!(Failed.can_become(Retrying));
+        assert!(Sent.can_become(Retrying));
+        assert!(Retrying.can_become(Sent));
+        // An ack arriving while we still think it failed
+        assert!(Failed.can_become(Delivered));
+        // Given up on by the retry policy
+        assert!(Sent.can_become(Failed));
+        assert!(Sent.can_become(Queued));
+        assert!(Failed.can_become(Queued));
+        // Never backwards
+        assert!(!Delivered.can_become(Retrying));
+        assert!(!Delivered.can_become(Sent));
+        assert!(!Read.can_become(Delivered));
+        assert!(!Sent.can_become(Sent));
+        for s in [Queued, Sending, Sent, Retrying, Failed, Delivered, Read] {
+            assert_eq!(MessageStatus::parse(s.as_str()), s);
+        }
+    }
+
+    #[test]
+    fn test_set_message_status() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("me", "Me", None).unwrap();
+        db.upsert_peer_as_user("bob", "Bob", None).unwrap();
+        db.create_message(&Message {
+            id: "m1".to_string(),
+            sender_id: "me".to_string(),
+            receiver_id: "bob".to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: false,
+            created_at: now(),
+            lamport: 0,
+            status: Queued,
+        })
+        .unwrap();
+        let get = || db.get_message("m1").unwrap().unwrap();
+
+        assert!(db.set_message_status("m1", Sending).unwrap());
+        assert!(db.set_message_status("m1", Sent).unwrap());
+        // Refused transitions leave the row alone
+        assert!(!db.set_message_status("m1", Sending).unwrap());
+        assert_eq!(get().status, Sent);
+        assert!(!get().is_delivered);
+
+        assert!(db.set_message_status("m1", Delivered).unwrap());
+        assert!(get().is_delivered && !get().is_read);
+        assert!(db.set_message_status("m1", Read).unwrap());
+        assert!(get().is_read);
+        assert!(!db.set_message_status("m1", Delivered).unwrap());
+        assert_eq!(get().status, Read);
+        assert!(!db.set_message_status("nope", Delivered).unwrap());
+    }
+
+    #[test]
+    fn test_status_backfill() {
+        // A database from before messages.status: the flags say how far each message got
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (
+                id TEXT PRIMARY KEY, sender_id TEXT NOT NULL, receiver_id TEXT NOT NULL,
+                content TEXT NOT NULL, message_type TEXT DEFAULT 'text', file_path TEXT,
+                is_read INTEGER DEFAULT 0, is_delivered INTEGER DEFAULT 0, created_at TEXT NOT NULL
+            );
+            INSERT INTO messages (id, sender_id, receiver_id, content, is_read, is_delivered, created_at)
+            VALUES ('read', 'me', 'bob', 'a', 1, 1, '2024-01-01T00:00:00Z'),
+                   ('delivered', 'me', 'bob', 'b', 0, 1, '2024-01-01T00:00:01Z'),
+                   ('sent', 'me', 'bob', 'c', 0, 0, '2024-01-01T00:00:02Z');",
+        )
+        .unwrap();
+        let db = Database::from_connection(conn).unwrap();
+        for (id, status) in [("read", Read), ("delivered", Delivered), ("sent", Sent)] {
+            assert_eq!(db.get_message(id).unwrap().unwrap().status, status);
+        }
+    }
+}
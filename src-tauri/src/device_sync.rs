@@ -0,0 +1,1041 @@
+// src-tauri/src/device_sync.rs
+// Multi-device sync: link devices that share one identity and replicate messages,
+// contacts, read state, notes and a whitelist of settings between them over signaling
+
+use crate::commands::{self, send_to_peer, AppState};
+use crate::crypto::{generate_checksum, EncryptedEnvelope};
+use crate::db::{
+    generate_id, now, Database, LinkedDevice, Message, Note, NoteTombstone, ProfileFields,
+    SyncedSetting, User,
+};
+use crate::signaling::{SignalingMessage, SignalingServer};
+use base64::Engine;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const LINK_CODE_TTL: Duration = Duration::from_secs(5 * 60);
+// Plaintext budget per SyncBatch; base64 + envelope must stay under the 64KB UDP datagram
+const MAX_BATCH_BYTES: usize = 40 * 1024;
+// Gap between batches so a burst doesn't overflow the receiver's socket buffer
+const BATCH_INTERVAL: Duration = Duration::from_millis(5);
+// A sync whose batches stop arriving is forgotten after this long
+const SYNC_TTL: Duration = Duration::from_secs(10 * 60);
+// Syncs tracked at once per linked device; a newer one replaces the oldest
+const MAX_SYNCS_PER_DEVICE: usize = 4;
+/// Settings kept the same on every linked device (last writer wins). Ports, discovery,
+/// storage and security settings stay per device.
+pub const SYNCED_SETTINGS: [&str; 4] = [
+    "do_not_disturb",
+    "notification_sound",
+    "quick_reply_popup",
+    "auto_download_policy",
+];
+
+/// Pairing code shown on the device that already has the identity
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkCode {
+    pub code: String,
+    pub device_id: String,
+    pub expires_at: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkRequestPayload {
+    code: String,
+    device_name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkAcceptPayload {
+    identity_key: String,
+    device_name: String,
+    username: String,
+    bio: Option<String>,
+    designation: Option<String>,
+    #[serde(default)]
+    profile: ProfileFields,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SyncPayload {
+    /// Proves the sender holds our identity key
+    identity_proof: String,
+    /// Sender's clock when the sync started; becomes our next `since`
+    until: String,
+    users: Vec<User>,
+    messages: Vec<Message>,
+    // Absent from devices that predate notes sync
+    #[serde(default)]
+    notes: Vec<Note>,
+    #[serde(default)]
+    note_tombstones: Vec<NoteTombstone>,
+    // Absent from devices that predate settings sync
+    #[serde(default)]
+    settings: Vec<SyncedSetting>,
+}
+
+impl SyncPayload {
+    fn is_empty(&self) -> bool {
+        self.users.is_empty()
+            && self.messages.is_empty()
+            && self.notes.is_empty()
+            && self.note_tombstones.is_empty()
+            && self.settings.is_empty()
+    }
+}
+
+/// Note changes pushed to linked devices as they're made
+#[derive(Serialize, Deserialize)]
+struct NotesPayload {
+    identity_proof: String,
+    notes: Vec<Note>,
+    note_tombstones: Vec<NoteTombstone>,
+}
+
+/// Synced settings pushed to linked devices as they change
+#[derive(Serialize, Deserialize)]
+struct SettingsPayload {
+    identity_proof: String,
+    settings: Vec<SyncedSetting>,
+}
+
+struct SyncProgress {
+    from: String,
+    started: Instant,
+    received: HashSet<u32>,
+    messages: usize,
+    notes: usize,
+    settings: Vec<String>,
+}
+
+/// Items applied from one completed sync
+struct SyncCounts {
+    messages: usize,
+    notes: usize,
+    settings: Vec<String>,
+}
+
+// Code we generated and are waiting for a device to enter
+static LINK_CODE: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+// Device we sent a LinkRequest to; only its LinkAccept is honoured
+static PENDING_LINK: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+// sync_id -> batches received so far (see track_sync)
+static INCOMING_SYNCS: Mutex<Option<HashMap<String, SyncProgress>>> = Mutex::new(None);
+
+/// The secret shared by all devices of one identity, created on first use
+fn identity_key(db: &Database) -> Result<String, String> {
+    if let Some(key) = db.get_setting("identity_key").map_err(|e| e.to_string())? {
+        if !key.is_empty() {
+            return Ok(key);
+        }
+    }
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let key = base64::engine::general_purpose::STANDARD.encode(bytes);
+    db.set_setting("identity_key", &key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+fn identity_proof(db: &Database) -> Result<String, String> {
+    Ok(generate_checksum(identity_key(db)?.as_bytes()))
+}
+
+fn device_name() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "Pingo device".to_string())
+}
+
+fn is_linked(db: &Database, device_id: &str) -> bool {
+    db.get_linked_device(device_id).ok().flatten().is_some()
+}
+
+/// Encrypt for a peer using the public key it currently announces via discovery
+pub(crate) fn encrypt_for(
+    state: &AppState,
+    peer_id: &str,
+    plaintext: &str,
+) -> Result<EncryptedEnvelope, String> {
+    let peer = state
+        .discovery
+        .get_peer(peer_id)
+        .ok_or_else(|| format!("Device {} is not online", peer_id))?;
+    state.crypto.establish_session(peer_id, &peer.public_key)?;
+    state.crypto.encrypt_message(peer_id, None, plaintext)
+}
+
+/// Decrypt what a peer just sent, with the key it announces via discovery (never the key the
+/// envelope claims)
+pub(crate) fn decrypt_from(
+    state: &AppState,
+    peer_id: &str,
+    envelope: &EncryptedEnvelope,
+) -> Result<String, String> {
+    let public_key = state
+        .discovery
+        .get_peer(peer_id)
+        .map(|peer| peer.public_key)
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| format!("Device {} is not online", peer_id))?;
+    state.crypto.establish_session(peer_id, &public_key)?;
+    state.crypto.decrypt_fresh(peer_id, envelope)
+}
+
+/// Consume the pending link code, whatever `code` is: true if it matched and hadn't expired.
+/// A wrong guess burns the code too, so it can't be brute-forced within its lifetime; the user
+/// shows a new one.
+fn take_link_code(code: &str) -> bool {
+    match LINK_CODE.lock().unwrap().take() {
+        Some((expected, created)) => created.elapsed() < LINK_CODE_TTL && expected == code.trim(),
+        None => false,
+    }
+}
+
+/// Ask a linked device for everything that changed since our last sync with it
+pub fn request_sync(db: &Database, signaling: &SignalingServer, local_id: &str, device_id: &str) {
+    let since = db
+        .get_linked_device(device_id)
+        .ok()
+        .flatten()
+        .and_then(|d| d.last_sync);
+    let msg = SignalingMessage::SyncRequest {
+        from: local_id.to_string(),
+        to: device_id.to_string(),
+        since,
+    };
+    if let Err(e) = signaling.send_message(device_id, &msg) {
+        warn!("Sync request to {} failed: {}", device_id, e);
+    }
+}
+
+/// Rewrite a message from a linked device's point of view to ours. Messages exchanged
+/// between the two devices themselves are not part of the shared history.
+fn remap_message(mut m: Message, remote_id: &str, local_id: &str) -> Option<Message> {
+    let own = |id: &str| id == remote_id || id == local_id;
+    if own(&m.sender_id) && own(&m.receiver_id) {
+        return None;
+    }
+    if m.sender_id == remote_id {
+        m.sender_id = local_id.to_string();
+    }
+    if m.receiver_id == remote_id {
+        m.receiver_id = local_id.to_string();
+    }
+    Some(m)
+}
+
+fn json_len<T: Serialize>(item: &T) -> usize {
+    serde_json::to_vec(item).map(|v| v.len()).unwrap_or(0)
+}
+
+/// Split settings, users, messages and notes into payloads that each fit in one datagram.
+/// Users go first so contacts exist before the messages that reference them.
+fn build_batches(
+    settings: Vec<SyncedSetting>,
+    users: Vec<User>,
+    messages: Vec<Message>,
+    notes: Vec<Note>,
+    note_tombstones: Vec<NoteTombstone>,
+    proof: &str,
+    until: &str,
+) -> Vec<SyncPayload> {
+    let new_batch = || SyncPayload {
+        identity_proof: proof.to_string(),
+        until: until.to_string(),
+        users: Vec::new(),
+        messages: Vec::new(),
+        notes: Vec::new(),
+        note_tombstones: Vec::new(),
+        settings: Vec::new(),
+    };
+    let mut batches = vec![new_batch()];
+    let mut size = 0;
+
+    // Start a new batch when `len` more bytes won't fit, then return the batch to add to
+    let mut reserve = |batches: &mut Vec<SyncPayload>, len: usize| {
+        if size + len > MAX_BATCH_BYTES && !batches.last().unwrap().is_empty() {
+            batches.push(new_batch());
+            size = 0;
+        }
+        size += len;
+        batches.len() - 1
+    };
+
+    for setting in settings {
+        let i = reserve(&mut batches, json_len(&setting));
+        batches[i].settings.push(setting);
+    }
+    for user in users {
+        let i = reserve(&mut batches, json_len(&user));
+        batches[i].users.push(user);
+    }
+    for message in messages {
+        let i = reserve(&mut batches, json_len(&message));
+        batches[i].messages.push(message);
+    }
+    for note in notes {
+        let i = reserve(&mut batches, json_len(&note));
+        batches[i].notes.push(note);
+    }
+    for tombstone in note_tombstones {
+        let i = reserve(&mut batches, json_len(&tombstone));
+        batches[i].note_tombstones.push(tombstone);
+    }
+    batches
+}
+
+/// Answer a SyncRequest: send contacts and synced settings plus messages and notes changed
+/// since `since`
+fn send_sync(state: &AppState, device_id: &str, since: Option<&str>) -> Result<(), String> {
+    let local_id = state.device_id();
+    let linked: HashSet<String> = state
+        .db
+        .get_linked_devices()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|d| d.device_id)
+        .collect();
+    let users: Vec<User> = state
+        .db
+        .get_all_users()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|u| u.id != local_id && !linked.contains(&u.id))
+        .collect();
+    let messages = state
+        .db
+        .get_messages_changed_since(since)
+        .map_err(|e| e.to_string())?;
+    let notes = state
+        .db
+        .get_notes_changed_since(since)
+        .map_err(|e| e.to_string())?;
+    let note_tombstones = state
+        .db
+        .get_note_tombstones_since(since)
+        .map_err(|e| e.to_string())?;
+
+    // Always sent whole: a handful of rows, and the receiver keeps whichever is newer
+    let settings = state
+        .db
+        .get_synced_settings(&SYNCED_SETTINGS)
+        .map_err(|e| e.to_string())?;
+
+    let until = now();
+    let batches = build_batches(
+        settings,
+        users,
+        messages,
+        notes,
+        note_tombstones,
+        &identity_proof(&state.db)?,
+        &until,
+    );
+    let sync_id = generate_id();
+    let total = batches.len() as u32;
+    for (index, batch) in batches.into_iter().enumerate() {
+        let json = serde_json::to_string(&batch).map_err(|e| e.to_string())?;
+        let msg = SignalingMessage::SyncBatch {
+            from: local_id.clone(),
+            to: device_id.to_string(),
+            sync_id: sync_id.clone(),
+            index: index as u32,
+            total,
+            payload: encrypt_for(state, device_id, &json)?,
+        };
+        send_to_peer(state, device_id, &msg)?;
+        std::thread::sleep(BATCH_INTERVAL);
+    }
+    info!(
+        "Sent {} sync batch(es) to linked device {}",
+        total,
+        &device_id[..8.min(device_id.len())]
+    );
+    Ok(())
+}
+
+/// Apply one SyncBatch. Once every batch of the sync has arrived, advances last_sync and
+/// returns how many messages and notes were applied.
+fn apply_batch(
+    state: &AppState,
+    from: &str,
+    sync_id: &str,
+    index: u32,
+    total: u32,
+    payload: &EncryptedEnvelope,
+) -> Result<Option<SyncCounts>, String> {
+    if index >= total {
+        return Err(format!("Sync batch {} of {}", index, total));
+    }
+    let json = decrypt_from(state, from, payload)?;
+    let batch: SyncPayload = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if batch.identity_proof != identity_proof(&state.db)? {
+        return Err("Sync batch from a device with a different identity".to_string());
+    }
+
+    let local_id = state.device_id();
+    for user in &batch.users {
+        if user.id == local_id || is_linked(&state.db, &user.id) {
+            continue;
+        }
+        state
+            .db
+            .merge_synced_user(user)
+            .map_err(|e| e.to_string())?;
+    }
+    let mut applied = 0;
+    for message in batch.messages {
+        if let Some(m) = remap_message(message, from, &local_id) {
+            // A contact missing from an earlier, dropped batch fails the foreign key;
+            // last_sync isn't advanced, so the next sync retries it
+            if state.db.apply_synced_message(&m).is_ok() {
+                applied += 1;
+            }
+        }
+    }
+    let notes = apply_notes(&state.db, &batch.notes, &batch.note_tombstones);
+    let settings = apply_settings(&state.db, &batch.settings);
+
+    let mut syncs = INCOMING_SYNCS.lock().unwrap();
+    let syncs = syncs.get_or_insert_with(HashMap::new);
+    let progress = track_sync(syncs, from, sync_id);
+    progress.received.insert(index);
+    progress.messages += applied;
+    progress.notes += notes;
+    progress.settings.extend(settings);
+    if progress.received.len() as u32 >= total {
+        let counts = SyncCounts {
+            messages: progress.messages,
+            notes: progress.notes,
+            settings: std::mem::take(&mut progress.settings),
+        };
+        syncs.remove(sync_id);
+        state
+            .db
+            .set_linked_device_synced(from, &batch.until)
+            .map_err(|e| e.to_string())?;
+        return Ok(Some(counts));
+    }
+    Ok(None)
+}
+
+/// Progress of a sync, started on its first batch. Stale syncs are dropped first, and a device
+/// starting one too many loses its oldest, so abandoned syncs can't pile up.
+fn track_sync<'a>(
+    syncs: &'a mut HashMap<String, SyncProgress>,
+    from: &str,
+    sync_id: &str,
+) -> &'a mut SyncProgress {
+    syncs.retain(|_, p| p.started.elapsed() < SYNC_TTL);
+    if !syncs.contains_key(sync_id) {
+        let mut theirs: Vec<(String, Instant)> = syncs
+            .iter()
+            .filter(|(_, p)| p.from == from)
+            .map(|(id, p)| (id.clone(), p.started))
+            .collect();
+        if theirs.len() >= MAX_SYNCS_PER_DEVICE {
+            theirs.sort_by_key(|(_, started)| *started);
+            let excess = theirs.len() + 1 - MAX_SYNCS_PER_DEVICE;
+            for (id, _) in theirs.into_iter().take(excess) {
+                warn!("Dropping unfinished sync {} from {}", id, from);
+                syncs.remove(&id);
+            }
+        }
+    }
+    syncs
+        .entry(sync_id.to_string())
+        .or_insert_with(|| SyncProgress {
+            from: from.to_string(),
+            started: Instant::now(),
+            received: HashSet::new(),
+            messages: 0,
+            notes: 0,
+            settings: Vec::new(),
+        })
+}
+
+/// Merge note edits and deletes from a linked device (last writer wins); returns how many changed
+fn apply_notes(db: &Database, notes: &[Note], tombstones: &[NoteTombstone]) -> usize {
+    let mut changed = 0;
+    for note in notes {
+        match db.apply_synced_note(note) {
+            Ok(true) => changed += 1,
+            Ok(false) => {}
+            Err(e) => warn!("Applying synced note {} failed: {}", note.id, e),
+        }
+    }
+    for tombstone in tombstones {
+        match db.apply_note_tombstone(tombstone) {
+            Ok(true) => {
+                commands::remove_note_attachments(db, &tombstone.id);
+                changed += 1
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Applying note delete {} failed: {}", tombstone.id, e),
+        }
+    }
+    changed
+}
+
+/// Merge settings from a linked device (last writer wins); returns the keys that changed
+fn apply_settings(db: &Database, settings: &[SyncedSetting]) -> Vec<String> {
+    let mut changed = Vec::new();
+    for setting in settings {
+        // Only the whitelist, whatever the other device sends
+        if !SYNCED_SETTINGS.contains(&setting.key.as_str()) {
+            continue;
+        }
+        match db.apply_synced_setting(setting) {
+            Ok(true) => changed.push(setting.key.clone()),
+            Ok(false) => {}
+            Err(e) => warn!("Applying synced setting {} failed: {}", setting.key, e),
+        }
+    }
+    changed
+}
+
+/// Encrypt `json` for every linked device that is online and send it as the message `build`
+/// makes from (from, to, payload). Offline devices pick changes up on their next sync.
+fn push_to_linked(
+    state: &AppState,
+    what: &str,
+    build_json: impl FnOnce(String) -> Result<String, String>,
+    build: impl Fn(String, String, EncryptedEnvelope) -> SignalingMessage,
+) {
+    let devices = match state.db.get_linked_devices() {
+        Ok(devices) if !devices.is_empty() => devices,
+        _ => return,
+    };
+    let json = match identity_proof(&state.db).and_then(build_json) {
+        Ok(json) => json,
+        Err(e) => {
+            warn!("{} sync skipped: {}", what, e);
+            return;
+        }
+    };
+    let local_id = state.device_id();
+    for device in devices {
+        if state.discovery.get_peer(&device.device_id).is_none() {
+            continue;
+        }
+        let result = encrypt_for(state, &device.device_id, &json).and_then(|payload| {
+            let msg = build(local_id.clone(), device.device_id.clone(), payload);
+            send_to_peer(state, &device.device_id, &msg)
+        });
+        if let Err(e) = result {
+            warn!("{} sync to {} failed: {}", what, device.device_id, e);
+        }
+    }
+}
+
+/// Send local note changes to every linked device that is online
+pub fn push_notes(state: &AppState, notes: Vec<Note>, note_tombstones: Vec<NoteTombstone>) {
+    push_to_linked(
+        state,
+        "Notes",
+        |identity_proof| {
+            serde_json::to_string(&NotesPayload {
+                identity_proof,
+                notes,
+                note_tombstones,
+            })
+            .map_err(|e| e.to_string())
+        },
+        |from, to, payload| SignalingMessage::NoteSync { from, to, payload },
+    );
+}
+
+/// Send the current value of `keys` to linked devices, skipping keys that aren't synced.
+/// Call after a setting is changed locally.
+pub fn push_settings(state: &AppState, keys: &[&str]) {
+    let keys: Vec<&str> = keys
+        .iter()
+        .copied()
+        .filter(|key| SYNCED_SETTINGS.contains(key))
+        .collect();
+    if keys.is_empty() {
+        return;
+    }
+    let settings = match state.db.get_synced_settings(&keys) {
+        Ok(settings) if !settings.is_empty() => settings,
+        _ => return,
+    };
+    push_to_linked(
+        state,
+        "Settings",
+        |identity_proof| {
+            serde_json::to_string(&SettingsPayload {
+                identity_proof,
+                settings,
+            })
+            .map_err(|e| e.to_string())
+        },
+        |from, to, payload| SignalingMessage::SettingsSync { from, to, payload },
+    );
+}
+
+fn handle_note_sync(
+    state: &AppState,
+    from: &str,
+    payload: &EncryptedEnvelope,
+) -> Result<usize, String> {
+    let json = decrypt_from(state, from, payload)?;
+    let update: NotesPayload = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if update.identity_proof != identity_proof(&state.db)? {
+        return Err("Notes from a device with a different identity".to_string());
+    }
+    Ok(apply_notes(
+        &state.db,
+        &update.notes,
+        &update.note_tombstones,
+    ))
+}
+
+fn handle_settings_sync(
+    state: &AppState,
+    from: &str,
+    payload: &EncryptedEnvelope,
+) -> Result<Vec<String>, String> {
+    let json = decrypt_from(state, from, payload)?;
+    let update: SettingsPayload = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if update.identity_proof != identity_proof(&state.db)? {
+        return Err("Settings from a device with a different identity".to_string());
+    }
+    Ok(apply_settings(&state.db, &update.settings))
+}
+
+fn emit_settings_synced<R: Runtime>(app: &AppHandle<R>, from: &str, keys: &[String]) {
+    if keys.is_empty() {
+        return;
+    }
+    info!("Applied {} setting(s) from linked device", keys.len());
+    let _ = app.emit(
+        "settings-synced",
+        serde_json::json!({ "device_id": from, "keys": keys }),
+    );
+}
+
+/// Handle link/sync signaling messages (called from the signaling forwarder)
+pub fn handle_message<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    let state = app.state::<AppState>();
+    let result = match msg {
+        SignalingMessage::LinkRequest { from, payload, .. } => {
+            handle_link_request(app, &state, from, payload)
+        }
+        SignalingMessage::LinkAccept { from, payload, .. } => {
+            handle_link_accept(app, &state, from, payload)
+        }
+        SignalingMessage::SyncRequest { from, since, .. } => {
+            if !is_linked(&state.db, from) {
+                return;
+            }
+            let app = app.clone();
+            let from = from.clone();
+            let since = since.clone();
+            // Batches are paced, so don't hold up the signaling forwarder
+            std::thread::spawn(move || {
+                let state = app.state::<AppState>();
+                if let Err(e) = send_sync(&state, &from, since.as_deref()) {
+                    warn!("Sync to {} failed: {}", from, e);
+                }
+            });
+            Ok(())
+        }
+        SignalingMessage::SyncBatch {
+            from,
+            sync_id,
+            index,
+            total,
+            payload,
+            ..
+        } => {
+            if !is_linked(&state.db, from) {
+                return;
+            }
+            match apply_batch(&state, from, sync_id, *index, *total, payload) {
+                Ok(Some(counts)) => {
+                    emit_settings_synced(app, from, &counts.settings);
+                    if counts.notes > 0 {
+                        let _ = app.emit(
+                            "notes-synced",
+                            serde_json::json!({ "device_id": from, "changed": counts.notes }),
+                        );
+                    }
+                    let _ = app.emit(
+                        "sync-complete",
+                        serde_json::json!({
+                            "device_id": from,
+                            "messages": counts.messages,
+                            "notes": counts.notes,
+                        }),
+                    );
+                    Ok(())
+                }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            }
+        }
+        SignalingMessage::NoteSync { from, payload, .. } => {
+            if !is_linked(&state.db, from) {
+                return;
+            }
+            handle_note_sync(&state, from, payload).map(|changed| {
+                if changed > 0 {
+                    let _ = app.emit(
+                        "notes-synced",
+                        serde_json::json!({ "device_id": from, "changed": changed }),
+                    );
+                }
+            })
+        }
+        SignalingMessage::SettingsSync { from, payload, .. } => {
+            if !is_linked(&state.db, from) {
+                return;
+            }
+            handle_settings_sync(&state, from, payload)
+                .map(|changed| emit_settings_synced(app, from, &changed))
+        }
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        warn!("Device sync: {}", e);
+    }
+}
+
+fn handle_link_request<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    from: &str,
+    payload: &EncryptedEnvelope,
+) -> Result<(), String> {
+    let json = decrypt_from(state, from, payload)?;
+    let request: LinkRequestPayload = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if !take_link_code(&request.code) {
+        let _ = app.emit(
+            "device-link-failed",
+            serde_json::json!({ "device_id": from }),
+        );
+        return Err(format!(
+            "Rejected link request from {}: invalid or expired code",
+            from
+        ));
+    }
+
+    let me = state
+        .db
+        .get_user(&state.device_id())
+        .map_err(|e| e.to_string())?
+        .ok_or("Local user not initialized")?;
+    let accept = LinkAcceptPayload {
+        identity_key: identity_key(&state.db)?,
+        device_name: device_name(),
+        username: me.username,
+        bio: me.bio,
+        designation: me.designation,
+        profile: me.profile,
+    };
+    let json = serde_json::to_string(&accept).map_err(|e| e.to_string())?;
+    let msg = SignalingMessage::LinkAccept {
+        from: state.device_id(),
+        to: from.to_string(),
+        payload: encrypt_for(state, from, &json)?,
+    };
+    send_to_peer(state, from, &msg)?;
+
+    state
+        .db
+        .add_linked_device(from, &request.device_name)
+        .map_err(|e| e.to_string())?;
+    finish_link(app, state, from)
+}
+
+fn handle_link_accept<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    from: &str,
+    payload: &EncryptedEnvelope,
+) -> Result<(), String> {
+    {
+        let mut pending = PENDING_LINK.lock().unwrap();
+        match pending.as_ref() {
+            Some((peer, started)) if peer == from && started.elapsed() < LINK_CODE_TTL => {
+                *pending = None
+            }
+            _ => return Err(format!("Unexpected link accept from {}", from)),
+        }
+    }
+    let json = decrypt_from(state, from, payload)?;
+    let accept: LinkAcceptPayload = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+    // Adopt the identity: shared key plus the profile everyone knows us by
+    state
+        .db
+        .set_setting("identity_key", &accept.identity_key)
+        .map_err(|e| e.to_string())?;
+    if let Some(mut me) = state
+        .db
+        .get_user(&state.device_id())
+        .map_err(|e| e.to_string())?
+    {
+        me.username = accept.username;
+        me.bio = accept.bio;
+        me.designation = accept.designation;
+        me.profile = accept.profile;
+        state.db.create_user(&me).map_err(|e| e.to_string())?;
+    }
+    state
+        .db
+        .add_linked_device(from, &accept.device_name)
+        .map_err(|e| e.to_string())?;
+    finish_link(app, state, from)
+}
+
+/// Both sides pull from each other right after linking
+fn finish_link<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    device_id: &str,
+) -> Result<(), String> {
+    if let Some(device) = state
+        .db
+        .get_linked_device(device_id)
+        .map_err(|e| e.to_string())?
+    {
+        let _ = app.emit("device-linked", &device);
+    }
+    request_sync(&state.db, &state.signaling, &state.device_id(), device_id);
+    Ok(())
+}
+
+// ============ COMMANDS ============
+
+/// Generate a 6-digit code to enter on the device being linked (valid for 5 minutes)
+#[tauri::command]
+pub fn create_link_code(state: State<AppState>) -> Result<LinkCode, String> {
+    identity_key(&state.db)?;
+    let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+    *LINK_CODE.lock().unwrap() = Some((code.clone(), Instant::now()));
+    let expires_at = chrono::Utc::now()
+        + chrono::Duration::from_std(LINK_CODE_TTL).map_err(|e| e.to_string())?;
+    Ok(LinkCode {
+        code,
+        device_id: state.device_id(),
+        expires_at: expires_at.to_rfc3339(),
+    })
+}
+
+/// Link this device to `peer_id` (a device of the identity to join) using its code.
+/// Completion is reported through the `device-linked` / `device-link-failed` events.
+#[tauri::command]
+pub fn link_device(state: State<AppState>, peer_id: String, code: String) -> Result<(), String> {
+    if peer_id == state.device_id() {
+        return Err("Cannot link a device to itself".to_string());
+    }
+    let request = LinkRequestPayload {
+        code: code.trim().to_string(),
+        device_name: device_name(),
+    };
+    let json = serde_json::to_string(&request).map_err(|e| e.to_string())?;
+    let msg = SignalingMessage::LinkRequest {
+        from: state.device_id(),
+        to: peer_id.clone(),
+        payload: encrypt_for(&state, &peer_id, &json)?,
+    };
+    *PENDING_LINK.lock().unwrap() = Some((peer_id.clone(), Instant::now()));
+    send_to_peer(&state, &peer_id, &msg)
+}
+
+#[tauri::command]
+pub fn get_linked_devices(state: State<AppState>) -> Result<Vec<LinkedDevice>, String> {
+    state.db.get_linked_devices().map_err(|e| e.to_string())
+}
+
+/// Stop syncing with a device. The shared identity key is kept; link again to resume.
+#[tauri::command]
+pub fn unlink_device(state: State<AppState>, device_id: String) -> Result<(), String> {
+    state
+        .db
+        .remove_linked_device(&device_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Pull from every linked device that is currently online; returns how many were asked
+#[tauri::command]
+pub fn sync_linked_devices(state: State<AppState>) -> Result<usize, String> {
+    let devices = state.db.get_linked_devices().map_err(|e| e.to_string())?;
+    let mut requested = 0;
+    for device in devices {
+        if state.discovery.get_peer(&device.device_id).is_some() {
+            request_sync(
+                &state.db,
+                &state.signaling,
+                &state.device_id(),
+                &device.device_id,
+            );
+            requested += 1;
+        }
+    }
+    Ok(requested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery_status::MessageStatus;
+
+    fn message(id: &str, from: &str, to: &str, content: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            sender_id: from.to_string(),
+            receiver_id: to.to_string(),
+            content: content.to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: true,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Delivered,
+        }
+    }
+
+    #[test]
+    fn test_wrong_link_code_burns_it() {
+        *LINK_CODE.lock().unwrap() = Some(("123456".to_string(), Instant::now()));
+        assert!(!take_link_code("000000"));
+        assert!(!take_link_code("123456"));
+
+        *LINK_CODE.lock().unwrap() = Some(("123456".to_string(), Instant::now()));
+        assert!(take_link_code(" 123456 "));
+        assert!(!take_link_code("123456"));
+    }
+
+    #[test]
+    fn test_remap_message_to_local_device() {
+        let out = remap_message(message("1", "laptop", "bob", "hi"), "laptop", "phone").unwrap();
+        assert_eq!(out.sender_id, "phone");
+        assert_eq!(out.receiver_id, "bob");
+        let inc = remap_message(message("2", "bob", "laptop", "yo"), "laptop", "phone").unwrap();
+        assert_eq!(inc.receiver_id, "phone");
+        assert!(remap_message(message("3", "laptop", "phone", "x"), "laptop", "phone").is_none());
+    }
+
+    #[test]
+    fn test_build_batches_fit_datagram_budget() {
+        let messages: Vec<Message> = (0..200)
+            .map(|i| message(&i.to_string(), "a", "b", &"x".repeat(1000)))
+            .collect();
+        let batches = build_batches(
+            Vec::new(),
+            Vec::new(),
+            messages,
+            Vec::new(),
+            Vec::new(),
+            "proof",
+            "now",
+        );
+        assert!(batches.len() > 1);
+        assert_eq!(batches.iter().map(|b| b.messages.len()).sum::<usize>(), 200);
+        for b in &batches {
+            assert!(serde_json::to_vec(b).unwrap().len() <= MAX_BATCH_BYTES + 1024);
+        }
+        let empty = build_batches(
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            "p",
+            "n",
+        );
+        assert_eq!(empty.len(), 1);
+    }
+
+    #[test]
+    fn test_synced_contact_keeps_profile_fields() {
+        let db = Database::new_in_memory().unwrap();
+        let user: User = serde_json::from_str(
+            r#"{"id":"bob","username":"Bob","device_id":"bob","public_key":null,
+                "avatar_path":null,"bio":"","designation":"","last_seen":null,
+                "is_online":false,"created_at":"now","email":"bob@example.org",
+                "timezone":"Europe/Berlin"}"#,
+        )
+        .unwrap();
+        db.merge_synced_user(&user).unwrap();
+        let stored = db.get_user("bob").unwrap().unwrap();
+        assert_eq!(stored.profile.email.as_deref(), Some("bob@example.org"));
+        assert_eq!(stored.profile.timezone.as_deref(), Some("Europe/Berlin"));
+        assert_eq!(stored.profile.phone, None);
+
+        // A contact from an older device without the fields doesn't wipe them
+        let mut older = user.clone();
+        older.profile = ProfileFields::default();
+        db.merge_synced_user(&older).unwrap();
+        let stored = db.get_user("bob").unwrap().unwrap();
+        assert_eq!(stored.profile.email.as_deref(), Some("bob@example.org"));
+    }
+
+    #[test]
+    fn test_sync_payload_from_older_device_has_no_notes() {
+        let json = r#"{"identity_proof":"p","until":"n","users":[],"messages":[]}"#;
+        let payload: SyncPayload = serde_json::from_str(json).unwrap();
+        assert!(payload.notes.is_empty() && payload.note_tombstones.is_empty());
+        assert!(payload.settings.is_empty());
+        assert!(payload.is_empty());
+    }
+
+    #[test]
+    fn test_synced_settings_last_writer_wins() {
+        let db = Database::new_in_memory().unwrap();
+        db.set_setting("do_not_disturb", "true").unwrap();
+        assert_eq!(db.get_synced_settings(&SYNCED_SETTINGS).unwrap().len(), 1);
+        let setting = |key: &str, value: &str, updated_at: &str| SyncedSetting {
+            key: key.to_string(),
+            value: value.to_string(),
+            updated_at: updated_at.to_string(),
+        };
+
+        // Older than the local change: ignored
+        let older = setting("do_not_disturb", "false", "2000-01-01T00:00:00+00:00");
+        assert!(apply_settings(&db, &[older]).is_empty());
+        // Newer, and not on the whitelist: only the whitelisted one lands
+        let newer = setting("do_not_disturb", "false", "2999-01-01T00:00:00+00:00");
+        let port = setting("signaling_port", "1", "2999-01-01T00:00:00+00:00");
+        assert_eq!(apply_settings(&db, &[newer, port]), vec!["do_not_disturb"]);
+        assert_eq!(
+            db.get_setting("do_not_disturb").unwrap().as_deref(),
+            Some("false")
+        );
+        assert!(db.get_setting("signaling_port").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_incoming_syncs_expire_and_are_capped_per_device() {
+        let mut syncs = HashMap::new();
+        for i in 0..MAX_SYNCS_PER_DEVICE {
+            track_sync(&mut syncs, "laptop", &format!("s{}", i));
+        }
+        track_sync(&mut syncs, "phone", "p0");
+        // A fifth sync from the laptop replaces its oldest; the phone's is untouched
+        track_sync(&mut syncs, "laptop", "s9").received.insert(0);
+        assert_eq!(syncs.len(), MAX_SYNCS_PER_DEVICE + 1);
+        assert!(!syncs.contains_key("s0"));
+        assert!(syncs.contains_key("p0"));
+        // Batches of a tracked sync keep its progress
+        track_sync(&mut syncs, "laptop", "s9").received.insert(1);
+        assert_eq!(syncs["s9"].received.len(), 2);
+
+        // Right after boot the monotonic clock can't go back that far
+        if let Some(stale) = Instant::now().checked_sub(SYNC_TTL) {
+            syncs.get_mut("p0").unwrap().started = stale;
+            track_sync(&mut syncs, "laptop", "s9");
+            assert!(!syncs.contains_key("p0"));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// src-tauri/src/diagnostics.rs
+// Diagnostics report for bug reports: versions, network, service status, peers, DB stats
+// and recent logs, with names, device ids and host parts of addresses redacted
+
+use crate::automation_api;
+use crate::commands::AppState;
+use crate::db::{now, Database};
+use crate::discovery::{self, DISCOVERY_PORT};
+use crate::logging;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::net::Ipv4Addr;
+use tauri::{AppHandle, Runtime, State};
+use zip::write::SimpleFileOptions;
+
+const LOG_LINES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    /// Zip with report.json and recent.log, ready to attach to an issue
+    pub path: String,
+    pub report: serde_json::Value,
+}
+
+/// Replaces identifying strings with stable placeholders
+struct Redactor {
+    replacements: Vec<(String, String)>,
+}
+
+impl Redactor {
+    fn new() -> Self {
+        Redactor {
+            replacements: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, secret: &str, placeholder: String) -> String {
+        // Very short values (e.g. a one-letter username) would mangle unrelated text
+        if secret.len() >= 3 && !self.replacements.iter().any(|(s, _)| s == secret) {
+            self.replacements
+                .push((secret.to_string(), placeholder.clone()));
+        }
+        placeholder
+    }
+
+    /// Device ids are also logged cut to 8 characters
+    fn add_device_id(&mut self, id: &str, placeholder: String) -> String {
+        if let Some(short) = id.get(..8).filter(|_| id.len() > 8) {
+            self.add(short, placeholder.clone());
+        }
+        self.add(id, placeholder)
+    }
+
+    /// Keep the subnet, which matters for LAN issues, and drop the host part
+    fn add_ip(&mut self, ip: &str) -> String {
+        match ip.parse::<Ipv4Addr>() {
+            Ok(addr) => {
+                let [a, b, c, _] = addr.octets();
+                let masked = format!("{}.{}.{}.x", a, b, c);
+                self.add(ip, masked)
+            }
+            Err(_) => self.add(ip, "<ip>".to_string()),
+        }
+    }
+
+    fn redact(&self, text: &str) -> String {
+        let mut sorted: Vec<&(String, String)> = self.replacements.iter().collect();
+        // Longest first so a device id isn't partially replaced by a shorter match
+        sorted.sort_by_key(|(s, _)| std::cmp::Reverse(s.len()));
+        sorted
+            .into_iter()
+            .fold(text.to_string(), |acc, (s, p)| acc.replace(s.as_str(), p))
+    }
+}
+
+fn database_stats(db: &Database) -> serde_json::Value {
+    let size = std::fs::metadata(Database::get_db_path())
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let tables: serde_json::Map<String, serde_json::Value> = db
+        .get_table_counts()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(t, n)| (t, n.into()))
+        .collect();
+    let (hits, misses) = db.cache_stats();
+    serde_json::json!({
+        "size_bytes": size,
+        "tables": tables,
+        "query_cache": { "hits": hits, "misses": misses },
+    })
+}
+
+fn build_report<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    redactor: &mut Redactor,
+) -> serde_json::Value {
+    redactor.add_device_id(&state.device_id(), "<self>".to_string());
+    if let Ok(Some(user)) = state.db.get_user(&state.device_id()) {
+        redactor.add(&user.username, "<self-name>".to_string());
+    }
+
+    let interfaces: Vec<String> = discovery::local_ip_addresses()
+        .unwrap_or_default()
+        .iter()
+        .map(|ip| redactor.add_ip(&ip.to_string()))
+        .collect();
+    let broadcast: Vec<String> = discovery::get_local_broadcast_addresses()
+        .iter()
+        .map(|ip| ip.to_string())
+        .collect();
+
+    let peers: Vec<serde_json::Value> = state
+        .discovery
+        .get_peers()
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let label = redactor.add_device_id(&p.device_id, format!("<peer-{}>", i + 1));
+            redactor.add(&p.username, format!("<peer-{}-name>", i + 1));
+            let signaling = state.signaling.get_peer(&p.device_id);
+            serde_json::json!({
+                "peer": label,
+                "online": p.is_online,
+                "ip": redactor.add_ip(&p.ip_address),
+                "port": p.port,
+                "has_public_key": !p.public_key.is_empty(),
+                "signaling_registered": signaling.is_some(),
+                "signaling_address_matches": signaling
+                    .map(|c| c.address.ip().to_string() == p.ip_address),
+            })
+        })
+        .collect();
+
+    let file_port = state.file_server.get_port();
+    let info = app.package_info();
+    serde_json::json!({
+        "generated_at": now(),
+        "app": {
+            "name": info.name,
+            "version": info.version.to_string(),
+            "debug_build": cfg!(debug_assertions),
+        },
+        "os": {
+            "os": std::env::consts::OS,
+            "family": std::env::consts::FAMILY,
+            "arch": std::env::consts::ARCH,
+        },
+        "network": {
+            "interfaces": interfaces,
+            "broadcast_addresses": broadcast,
+        },
+        "services": {
+            "discovery": { "running": state.discovery.is_running(), "port": DISCOVERY_PORT },
+            "signaling": {
+                "running": state.signaling.is_running(),
+                "port": state.signaling.local_port(),
+                "connected_peers": state.signaling.get_connected_peers().len(),
+            },
+            "file_server": { "running": file_port != 0, "port": file_port },
+            "automation_api": { "port": automation_api::running_port() },
+        },
+        "peers": peers,
+        "database": database_stats(&state.db),
+    })
+}
+
+fn write_zip(path: &std::path::Path, report: &str, logs: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+    for (name, content) in [("report.json", report), ("recent.log", logs)] {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(content.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Build the report and save it as a zip in the downloads folder
+#[tauri::command]
+pub fn generate_diagnostics_report<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+) -> Result<DiagnosticsReport, String> {
+    let mut redactor = Redactor::new();
+    let report = build_report(&app, &state, &mut redactor);
+    let logs: Vec<String> = logging::get_recent_logs(Some(LOG_LINES))
+        .iter()
+        .map(|line| redactor.redact(line))
+        .collect();
+    let report_text = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+
+    let dir = state.file_transfer.get_downloads_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!(
+        "pingo-diagnostics-{}.zip",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    write_zip(&path, &report_text, &logs.join("\n"))?;
+    tracing::info!("Diagnostics report written to {:?}", path);
+
+    Ok(DiagnosticsReport {
+        path: path.to_string_lossy().to_string(),
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_ids_names_and_host_octets() {
+        let mut r = Redactor::new();
+        r.add_device_id("device-1234", "<peer-1>".into());
+        r.add("device-1234-extra", "<peer-2>".into());
+        r.add("al", "<short>".into());
+        assert_eq!(r.add_ip("192.168.1.42"), "192.168.1.x");
+        assert_eq!(
+            r.redact("from device-1234-extra, device-1234 (device-1) at 192.168.1.42 (al)"),
+            "from <peer-2>, <peer-1> (<peer-1>) at 192.168.1.x (al)"
+        );
+    }
+}
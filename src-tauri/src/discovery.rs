@@ -7,12 +7,15 @@ use std::thread;
 use std::time::{Duration, Instant};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use network_interface::NetworkInterfaceConfig;
+use tracing::{info, warn};
+use crate::crypto::{verify_identity_proof, IdentityProof};
+use crate::db::UserStatus;
 
-const DISCOVERY_PORT: u16 = 15353;
+pub(crate) const DISCOVERY_PORT: u16 = 15353;
 const PEER_TIMEOUT_SECS: u64 = 15;
-const ANNOUNCE_INTERVAL_SECS: u64 = 3;
+pub(crate) const ANNOUNCE_INTERVAL_SECS: u64 = 3;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PeerInfo {
     pub device_id: String,
     pub username: String,
@@ -20,6 +23,18 @@ pub struct PeerInfo {
     pub port: u16,
     pub public_key: String,
     pub is_online: bool,
+    /// Content hash of the peer's avatar; changes only when the picture does
+    #[serde(default)]
+    pub avatar_hash: Option<String>,
+    /// The peer's custom status, announced with every hello
+    #[serde(default)]
+    pub status: Option<UserStatus>,
+    /// Accepts compressed signaling messages (older versions don't send this)
+    #[serde(default)]
+    pub compression: bool,
+    /// Identity key signature over `public_key`; older versions announce the key unsigned
+    #[serde(default)]
+    pub identity: Option<IdentityProof>,
 }
 
 #[derive(Clone, Debug)]
@@ -30,7 +45,13 @@ struct Peer {
     port: u16,
     public_key: String,
     is_online: bool,
+    avatar_hash: Option<String>,
+    status: Option<UserStatus>,
+    compression: bool,
+    identity: Option<IdentityProof>,
     last_seen: Instant,
+    /// Added by QR pairing rather than broadcast; exempt from the silence timeout
+    manual: bool,
 }
 
 impl From<&Peer> for PeerInfo {
@@ -42,6 +63,11 @@ impl From<&Peer> for PeerInfo {
             port: peer.port,
             public_key: peer.public_key.clone(),
             is_online: peer.is_online,
+            avatar_hash: peer.avatar_hash.clone(),
+            // Hellos stop when a peer goes away, so its last status can outlive its expiry
+            status: peer.status.clone().filter(|s| !s.is_expired()),
+            compression: peer.compression,
+            identity: peer.identity.clone(),
         }
     }
 }
@@ -65,9 +91,40 @@ struct DiscoveryPacket {
     peer: PeerInfo,
 }
 
+/// A signed key has to check out, and a device that has shown an identity key once (`pins`) has
+/// to sign with that key from then on; unsigned hellos only pass from devices that never signed
+/// (older versions). The first identity key a device shows is pinned.
+fn check_identity(pins: &RwLock<HashMap<String, String>>, peer: &PeerInfo) -> Result<(), String> {
+    if let Some(proof) = &peer.identity {
+        verify_identity_proof(&peer.device_id, &peer.public_key, proof)?;
+    }
+    let pinned = pins.read().unwrap().get(&peer.device_id).cloned();
+    match (pinned, &peer.identity) {
+        (Some(key), Some(proof)) if proof.identity_key != key => Err("identity key changed".to_string()),
+        (Some(_), None) => Err("unsigned key from a device with a known identity".to_string()),
+        (None, Some(proof)) => {
+            pins.write().unwrap().entry(peer.device_id.clone()).or_insert_with(|| proof.identity_key.clone());
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
 pub struct DiscoveryManager {
     peers: Arc<RwLock<HashMap<String, Peer>>>,
     running: Arc<Mutex<bool>>,
+    /// Our avatar version, announced with every hello
+    avatar_hash: Arc<RwLock<Option<String>>>,
+    /// Our custom status, announced with every hello
+    status: Arc<RwLock<Option<UserStatus>>>,
+    /// False in invisible mode: we keep listening but stop sending hellos
+    visible: Arc<RwLock<bool>>,
+    /// Our session key and its signature; replaced when the key is rotated
+    session_key: Arc<RwLock<(String, Option<IdentityProof>)>>,
+    /// device id -> the identity key it first showed (kept in the database across restarts)
+    identity_pins: Arc<RwLock<HashMap<String, String>>>,
+    /// Time between hellos (the discovery_interval_secs setting)
+    interval: Arc<RwLock<Duration>>,
     event_sender: Sender<DiscoveryEvent>,
     event_receiver: Receiver<DiscoveryEvent>,
 }
@@ -78,22 +135,35 @@ impl DiscoveryManager {
         Self {
             peers: Arc::new(RwLock::new(HashMap::new())),
             running: Arc::new(Mutex::new(false)),
+            avatar_hash: Arc::new(RwLock::new(None)),
+            status: Arc::new(RwLock::new(None)),
+            visible: Arc::new(RwLock::new(true)),
+            session_key: Arc::new(RwLock::new((String::new(), None))),
+            identity_pins: Arc::new(RwLock::new(HashMap::new())),
+            interval: Arc::new(RwLock::new(Duration::from_secs(ANNOUNCE_INTERVAL_SECS))),
             event_sender: sender,
             event_receiver: receiver,
         }
     }
 
-    pub fn start(&self, device_id: String, username: String, port: u16, public_key: String) -> Result<bool, String> {
+    pub fn start(&self, device_id: String, username: String, port: u16, public_key: String, identity: Option<IdentityProof>) -> Result<bool, String> {
         let mut running = self.running.lock().unwrap();
         if *running {
             return Ok(false);
         }
+        self.set_session_key(public_key, identity);
 
         *running = true;
         let running_clone = self.running.clone();
         let peers = self.peers.clone();
         let event_sender = self.event_sender.clone();
         let local_device_id = device_id.clone();
+        let avatar_hash = self.avatar_hash.clone();
+        let status = self.status.clone();
+        let visible = self.visible.clone();
+        let session_key = self.session_key.clone();
+        let identity_pins = self.identity_pins.clone();
+        let interval = self.interval.clone();
 
         // Create UDP socket
         let socket = create_multicast_socket(DISCOVERY_PORT).map_err(|e| e.to_string())?;
@@ -106,11 +176,15 @@ impl DiscoveryManager {
             username: username.clone(),
             ip_address: "0.0.0.0".to_string(),
             port,
-            public_key: public_key.clone(),
+            public_key: String::new(),
             is_online: true,
+            avatar_hash: None,
+            status: None,
+            compression: crate::signaling::SUPPORTS_COMPRESSION,
+            identity: None,
         };
 
-        println!("Starting UDP discovery on port {}", DISCOVERY_PORT);
+        info!("Starting UDP discovery on port {}", DISCOVERY_PORT);
 
         // Spawn listener thread
         let peers_listen = peers.clone();
@@ -129,6 +203,10 @@ impl DiscoveryManager {
                             if packet.peer.device_id == local_device_id {
                                 continue;
                             }
+                            if let Err(e) = check_identity(&identity_pins, &packet.peer) {
+                                warn!("Ignoring hello from {}: {}", packet.peer.device_id, e);
+                                continue;
+                            }
 
                             match packet.msg_type {
                                 MessageType::Hello => {
@@ -146,7 +224,12 @@ impl DiscoveryManager {
                                             port: packet.peer.port,
                                             public_key: packet.peer.public_key.clone(),
                                             is_online: true,
+                                            avatar_hash: packet.peer.avatar_hash.clone(),
+                                            status: packet.peer.status.clone(),
+                                            compression: packet.peer.compression,
+                                            identity: packet.peer.identity.clone(),
                                             last_seen: now,
+                                            manual: false,
                                         }
                                     });
 
@@ -155,6 +238,10 @@ impl DiscoveryManager {
                                     peer.ip_address = ip; // Use source IP
                                     peer.port = packet.peer.port;
                                     peer.public_key = packet.peer.public_key;
+                                    peer.avatar_hash = packet.peer.avatar_hash;
+                                    peer.status = packet.peer.status;
+                                    peer.compression = packet.peer.compression;
+                                    peer.identity = packet.peer.identity;
                                     peer.is_online = true;
                                     peer.last_seen = now;
 
@@ -194,22 +281,38 @@ impl DiscoveryManager {
                 .map(|ip| SocketAddr::new(IpAddr::V4(ip), DISCOVERY_PORT))
                 .collect();
 
-            println!("[Pingo Discovery] Announcer started. Broadcast targets: {:?} + {:?}", broadcast_addr, extra_broadcasts);
-            
+            info!("Announcer started. Broadcast targets: {:?} + {:?}", broadcast_addr, extra_broadcasts);
+            let send_all = |data: &[u8]| {
+                // Send to global broadcast
+                let _ = socket_send.send_to(data, broadcast_addr);
+                // Send to all subnet-specific broadcast addresses
+                for addr in &extra_broadcasts {
+                    let _ = socket_send.send_to(data, addr);
+                }
+            };
+            // Whether peers currently see us; going invisible says Bye once so they don't wait for the timeout
+            let mut announced = false;
+
             while *running_clone.lock().unwrap() {
-                let packet = DiscoveryPacket {
-                    msg_type: MessageType::Hello,
-                    peer: local_peer_info.clone(),
-                };
-                
-                if let Ok(data) = serde_json::to_vec(&packet) {
-                    // Send to global broadcast
-                    let _ = socket_send.send_to(&data, broadcast_addr);
-                    // Send to all subnet-specific broadcast addresses
-                    for addr in &extra_broadcasts {
-                        let _ = socket_send.send_to(&data, addr);
+                let is_visible = *visible.read().unwrap();
+                let msg_type = if is_visible { MessageType::Hello } else { MessageType::Bye };
+                if is_visible || announced {
+                    let (public_key, identity) = session_key.read().unwrap().clone();
+                    let packet = DiscoveryPacket {
+                        msg_type,
+                        peer: PeerInfo {
+                            public_key,
+                            avatar_hash: avatar_hash.read().unwrap().clone(),
+                            status: status.read().unwrap().clone(),
+                            identity,
+                            ..local_peer_info.clone()
+                        },
+                    };
+                    if let Ok(data) = serde_json::to_vec(&packet) {
+                        send_all(&data);
                     }
                 }
+                announced = is_visible;
 
                 // Check for stale peers
                 {
@@ -218,7 +321,7 @@ impl DiscoveryManager {
                     let timeout = Duration::from_secs(PEER_TIMEOUT_SECS);
                     
                     for (id, peer) in peers_lock.iter_mut() {
-                        if peer.is_online && now.duration_since(peer.last_seen) > timeout {
+                        if peer.is_online && !peer.manual && now.duration_since(peer.last_seen) > timeout {
                             peer.is_online = false;
                             let _ = event_sender.send(DiscoveryEvent::PeerLost {
                                 device_id: id.clone(),
@@ -227,22 +330,70 @@ impl DiscoveryManager {
                     }
                 }
 
-                thread::sleep(Duration::from_secs(ANNOUNCE_INTERVAL_SECS));
+                let wait = *interval.read().unwrap();
+                thread::sleep(wait);
             }
 
-            // Send Bye
-            let packet = DiscoveryPacket {
-                msg_type: MessageType::Bye,
-                peer: local_peer_info,
-            };
-            if let Ok(data) = serde_json::to_vec(&packet) {
-                let _ = socket_send.send_to(&data, broadcast_addr);
+            // Send Bye (already sent if we went invisible)
+            if announced {
+                let packet = DiscoveryPacket {
+                    msg_type: MessageType::Bye,
+                    peer: local_peer_info,
+                };
+                if let Ok(data) = serde_json::to_vec(&packet) {
+                    let _ = socket_send.send_to(&data, broadcast_addr);
+                }
             }
         });
 
         Ok(true)
     }
 
+    /// How often to say hello; takes effect after the current wait
+    pub fn set_announce_interval(&self, interval: Duration) {
+        *self.interval.write().unwrap() = interval;
+    }
+
+    /// Identity keys seen before (from the database), which hellos from those devices must carry
+    pub fn set_identity_pins(&self, pins: HashMap<String, String>) {
+        *self.identity_pins.write().unwrap() = pins;
+    }
+
+    /// Forget a device's identity key (it was reinstalled); the next one it shows is pinned
+    pub fn forget_identity_pin(&self, device_id: &str) {
+        self.identity_pins.write().unwrap().remove(device_id);
+    }
+
+    /// Check a peer announced some other way (QR pairing) like a hello
+    pub fn check_identity(&self, peer: &PeerInfo) -> Result<(), String> {
+        check_identity(&self.identity_pins, peer)
+    }
+
+    /// Announce a new session key with its identity signature (after rotation)
+    pub fn set_session_key(&self, public_key: String, identity: Option<IdentityProof>) {
+        *self.session_key.write().unwrap() = (public_key, identity);
+    }
+
+    /// Announce a new avatar version (None when we have no avatar)
+    pub fn set_avatar_hash(&self, hash: Option<String>) {
+        *self.avatar_hash.write().unwrap() = hash;
+    }
+
+    /// Announce a new custom status (None to clear it)
+    pub fn set_status(&self, status: Option<UserStatus>) {
+        *self.status.write().unwrap() = status;
+    }
+
+    /// Invisible mode when false: peers stop seeing us within one announce interval, while we
+    /// still discover them and receive messages from contacts that know our address
+    pub fn set_visible(&self, visible: bool) {
+        *self.visible.write().unwrap() = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        *self.visible.read().unwrap()
+    }
+
     pub fn stop(&self) {
         let mut running = self.running.lock().unwrap();
         *running = false;
@@ -259,6 +410,37 @@ impl DiscoveryManager {
             .collect()
     }
     
+    /// Add or refresh a peer learned out of band (QR pairing) and announce it like a
+    /// broadcast discovery would, so the usual peer-discovered handling applies. Only a peer
+    /// broadcast hasn't found is marked manual (kept online without hellos); one it has found
+    /// still times out when its hellos stop.
+    pub fn add_manual_peer(&self, info: &PeerInfo) {
+        let mut peers_lock = self.peers.write().unwrap();
+        let is_new = !peers_lock.contains_key(&info.device_id);
+        let peer = peers_lock.entry(info.device_id.clone()).or_insert_with(|| Peer {
+            device_id: info.device_id.clone(), username: String::new(), ip_address: String::new(),
+            port: 0, public_key: String::new(), is_online: true, avatar_hash: None, status: None,
+            compression: false, identity: None, last_seen: Instant::now(), manual: true,
+        });
+        peer.username = info.username.clone();
+        peer.ip_address = info.ip_address.clone();
+        peer.port = info.port;
+        peer.public_key = info.public_key.clone();
+        peer.avatar_hash = info.avatar_hash.clone();
+        peer.status = info.status.clone();
+        peer.compression = info.compression;
+        peer.identity = info.identity.clone();
+        peer.is_online = true;
+        peer.last_seen = Instant::now();
+
+        let event = if is_new {
+            DiscoveryEvent::PeerDiscovered { peer: (&*peer).into() }
+        } else {
+            DiscoveryEvent::PeerUpdated { peer: (&*peer).into() }
+        };
+        let _ = self.event_sender.send(event);
+    }
+
     #[allow(dead_code)]
     pub fn get_peer(&self, device_id: &str) -> Option<PeerInfo> {
         self.peers.read().unwrap().get(device_id).map(|p| p.into())
@@ -281,6 +463,66 @@ impl Default for DiscoveryManager {
     }
 }
 
+/// How long peer updates are collected before they go to the UI as one `peers-updated`
+pub const PEER_BATCH_WINDOW: Duration = Duration::from_millis(500);
+
+/// Sits between discovery events and the UI: hellos that repeat what the UI already knows are
+/// dropped, real changes are collected and handed out together once the batch window closes.
+#[derive(Default)]
+pub struct PeerEventCoalescer {
+    announced: HashMap<String, PeerInfo>,
+    pending: HashMap<String, PeerInfo>,
+    flush_at: Option<Instant>,
+}
+
+impl PeerEventCoalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A newly discovered peer is announced right away; remember it as such
+    pub fn discovered(&mut self, peer: &PeerInfo) {
+        self.pending.remove(&peer.device_id);
+        self.announced.insert(peer.device_id.clone(), peer.clone());
+    }
+
+    /// Queue an update; false when nothing changed since the last announcement
+    pub fn updated(&mut self, peer: &PeerInfo, now: Instant) -> bool {
+        if self.announced.get(&peer.device_id) == Some(peer) {
+            self.pending.remove(&peer.device_id);
+            return false;
+        }
+        self.announced.insert(peer.device_id.clone(), peer.clone());
+        self.pending.insert(peer.device_id.clone(), peer.clone());
+        self.flush_at.get_or_insert(now + PEER_BATCH_WINDOW);
+        true
+    }
+
+    /// Forget a peer that went away; false when the UI never heard of it (duplicate bye)
+    pub fn lost(&mut self, device_id: &str) -> bool {
+        self.pending.remove(device_id);
+        self.announced.remove(device_id).is_some()
+    }
+
+    /// How long to wait for the next event before the batch is due
+    pub fn wait(&self, now: Instant, idle: Duration) -> Duration {
+        self.flush_at.map_or(idle, |at| at.saturating_duration_since(now))
+    }
+
+    /// The collected updates once the window has closed
+    pub fn take_due(&mut self, now: Instant) -> Vec<PeerInfo> {
+        match self.flush_at {
+            Some(at) if at <= now => {
+                self.flush_at = None;
+                let mut batch: Vec<PeerInfo> = self.pending.drain().map(|(_, p)| p).collect();
+                batch.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+                batch
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
 fn create_multicast_socket(port: u16) -> std::io::Result<UdpSocket> {
     let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
     
@@ -299,7 +541,7 @@ fn create_multicast_socket(port: u16) -> std::io::Result<UdpSocket> {
 
 /// Get broadcast addresses for all local network interfaces
 /// Uses network_interface crate for accurate enumeration of ALL NICs
-fn get_local_broadcast_addresses() -> Vec<Ipv4Addr> {
+pub(crate) fn get_local_broadcast_addresses() -> Vec<Ipv4Addr> {
     let mut addresses = Vec::new();
 
     // Use the network_interface crate for proper enumeration
@@ -357,7 +599,7 @@ fn get_local_broadcast_addresses() -> Vec<Ipv4Addr> {
 }
 
 /// Get all local IPv4 addresses (non-loopback)
-fn local_ip_addresses() -> Result<Vec<Ipv4Addr>, String> {
+pub(crate) fn local_ip_addresses() -> Result<Vec<Ipv4Addr>, String> {
     let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
     
     // Try to find local IPs by connecting to a well-known address
@@ -396,8 +638,8 @@ mod tests {
         let pk1 = "pubkey1".to_string();
         let pk2 = "pubkey2".to_string();
         
-        dm1.start(id1.clone(), "User1".to_string(), 1234, pk1.clone()).unwrap();
-        dm2.start(id2.clone(), "User2".to_string(), 5678, pk2.clone()).unwrap();
+        dm1.start(id1.clone(), "User1".to_string(), 1234, pk1.clone(), None).unwrap();
+        dm2.start(id2.clone(), "User2".to_string(), 5678, pk2.clone(), None).unwrap();
         
         // Wait for discovery
         thread::sleep(Duration::from_secs(4));
@@ -418,4 +660,69 @@ mod tests {
         assert!(found_dm2, "DM1 should have found DM2");
         assert!(found_dm1, "DM2 should have found DM1");
     }
+
+    #[test]
+    fn test_coalescer_drops_repeats_and_batches_changes() {
+        let peer = |id: &str, port: u16| PeerInfo {
+            device_id: id.to_string(),
+            username: id.to_string(),
+            ip_address: "10.0.0.2".to_string(),
+            port,
+            public_key: "pk".to_string(),
+            is_online: true,
+            avatar_hash: None,
+            status: None,
+            compression: false,
+            identity: None,
+        };
+        let start = Instant::now();
+        let mut c = PeerEventCoalescer::new();
+        c.discovered(&peer("a", 1));
+        assert!(!c.updated(&peer("a", 1), start));
+        assert!(c.updated(&peer("a", 2), start));
+        assert!(c.updated(&peer("b", 1), start));
+        assert!(c.take_due(start).is_empty());
+
+        let batch = c.take_due(start + PEER_BATCH_WINDOW);
+        assert_eq!(batch.iter().map(|p| p.device_id.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert!(c.lost("a"));
+        assert!(!c.lost("a"));
+    }
+
+    #[test]
+    fn test_identity_pins_survive_and_reject_unsigned_hellos() {
+        let signed = |id: &str| {
+            let crypto = crate::crypto::CryptoManager::new();
+            crypto.set_device_id(id);
+            crypto.generate_identity();
+            let public_key = crypto.generate_keypair();
+            PeerInfo {
+                device_id: id.to_string(),
+                username: id.to_string(),
+                ip_address: "10.0.0.2".to_string(),
+                port: 1,
+                public_key,
+                is_online: true,
+                avatar_hash: None,
+                status: None,
+                compression: false,
+                identity: crypto.identity_proof(),
+            }
+        };
+        let pins = RwLock::new(HashMap::new());
+        let ana = signed("ana");
+        assert!(check_identity(&pins, &ana).is_ok());
+        let pinned = pins.read().unwrap().get("ana").cloned();
+        assert_eq!(pinned, ana.identity.as_ref().map(|i| i.identity_key.clone()));
+
+        // Pins loaded from the database after a restart still hold
+        let pins = RwLock::new(pins.into_inner().unwrap());
+        let unsigned = PeerInfo { identity: None, ..ana.clone() };
+        assert!(check_identity(&pins, &unsigned).is_err());
+        let impostor = PeerInfo { device_id: "ana".to_string(), ..signed("ana") };
+        assert!(check_identity(&pins, &impostor).is_err());
+        assert!(check_identity(&pins, &ana).is_ok());
+        // Devices that never signed (older versions) still get through
+        assert!(check_identity(&pins, &PeerInfo { device_id: "old".to_string(), ..unsigned }).is_ok());
+    }
 }
@@ -0,0 +1,108 @@
+// src-tauri/src/disk_space.rs
+// Free-space checks before incoming files are written. A transfer or download that wouldn't
+// leave the configured reserve free is refused up front (with a "storage-low" event) instead
+// of failing halfway through a multi-GB write.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::warn;
+
+const RESERVE_KEY: &str = "storage_reserve_mb";
+pub(crate) const DEFAULT_RESERVE_MB: u64 = 500;
+const MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageLow {
+    pub path: String,
+    pub file_name: String,
+    pub needed: u64,
+    pub available: u64,
+    pub reserve: u64,
+}
+
+/// Space to always leave free, in bytes
+pub fn reserve_bytes(db: &Database) -> u64 {
+    db.get_setting(RESERVE_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RESERVE_MB)
+        * MB
+}
+
+/// Free space on the volume holding `path`; the path itself doesn't have to exist yet
+pub fn available(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    fs4::available_space(existing).ok()
+}
+
+/// Make sure `needed` bytes fit in `dir` with the reserve to spare. Emits "storage-low" and
+/// returns an error when they don't; when free space can't be read the write goes ahead.
+pub fn ensure_space<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    dir: &Path,
+    needed: u64,
+    file_name: &str,
+) -> Result<(), String> {
+    let Some(free) = available(dir) else {
+        return Ok(());
+    };
+    let reserve = reserve_bytes(db);
+    if free >= needed.saturating_add(reserve) {
+        return Ok(());
+    }
+    warn!(
+        "Not enough space for {} in {:?}: {} needed, {} free, {} reserved",
+        file_name, dir, needed, free, reserve
+    );
+    let _ = app.emit(
+        "storage-low",
+        StorageLow {
+            path: dir.to_string_lossy().to_string(),
+            file_name: file_name.to_string(),
+            needed,
+            available: free,
+            reserve,
+        },
+    );
+    Err(format!(
+        "Not enough disk space for {} ({} MB needed, {} MB free, {} MB kept in reserve)",
+        file_name,
+        needed.div_ceil(MB),
+        free / MB,
+        reserve / MB
+    ))
+}
+
+/// Room for an attachment download, which is written to shared_files and the organized
+/// downloads folder; `size` is 0 when the sender didn't report one
+pub fn ensure_download_space<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    size: u64,
+    file_name: &str,
+) -> Result<(), String> {
+    let downloads = state.file_transfer.get_downloads_dir();
+    ensure_space(app, &state.db, &downloads, size, file_name)?;
+    let shared = state.file_server.get_storage_dir();
+    ensure_space(app, &state.db, &shared, size, file_name)
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_storage_reserve(state: State<AppState>) -> u64 {
+    reserve_bytes(&state.db) / MB
+}
+
+#[tauri::command]
+pub fn set_storage_reserve(state: State<AppState>, megabytes: u64) -> Result<(), String> {
+    state
+        .db
+        .set_setting(RESERVE_KEY, &megabytes.to_string())
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,211 @@
+// src-tauri/src/download_folders.rs
+// Layout of the organized downloads folder: Downloads/<device_id>/<images|videos|files>/.
+// Device ids don't change when a peer renames themselves, so folders no longer go stale.
+// senders.json maps each device id to its latest display name, and on Unix a symlink named
+// after the sender points at their folder so it's still easy to find by hand.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use tauri::State;
+use tracing::{info, warn};
+
+const MAPPING_FILE: &str = "senders.json";
+const LAYOUT_KEY: &str = "downloads_layout";
+const LAYOUT: &str = "device_id";
+
+pub fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+pub fn type_folder(file_type: &str) -> &'static str {
+    match file_type {
+        "image" => "images",
+        "video" => "videos",
+        _ => "files",
+    }
+}
+
+pub fn sender_dir(base: &Path, device_id: &str) -> PathBuf {
+    base.join(sanitize_name(device_id))
+}
+
+/// Folder for a sender we only know by name (messages we can't attribute to a device)
+pub fn legacy_dir(base: &Path, sender_name: &str) -> PathBuf {
+    base.join(sanitize_name(sender_name))
+}
+
+fn load_names(base: &Path) -> BTreeMap<String, String> {
+    fs::read_to_string(base.join(MAPPING_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Record a sender's current display name next to their folder
+pub fn remember_name(base: &Path, device_id: &str, name: &str) {
+    if name.trim().is_empty() || name == "Unknown" {
+        return;
+    }
+    let mut map = load_names(base);
+    let old = map.insert(device_id.to_string(), name.to_string());
+    if old.as_deref() == Some(name) {
+        return;
+    }
+    let json = serde_json::to_string_pretty(&map).unwrap_or_default();
+    if let Err(e) = fs::create_dir_all(base).and_then(|_| fs::write(base.join(MAPPING_FILE), json))
+    {
+        warn!("Saving download folder names failed: {}", e);
+        return;
+    }
+    relink(base, device_id, old.as_deref(), name);
+}
+
+/// Point a symlink named after the sender at their folder, dropping the one for the old name
+#[cfg(unix)]
+fn relink(base: &Path, device_id: &str, old: Option<&str>, new: &str) {
+    let target = PathBuf::from(sanitize_name(device_id));
+    if let Some(old) = old {
+        let link = legacy_dir(base, old);
+        if fs::read_link(&link).ok().as_ref() == Some(&target) {
+            let _ = fs::remove_file(&link);
+        }
+    }
+    let link = legacy_dir(base, new);
+    if link != sender_dir(base, device_id) && fs::symlink_metadata(&link).is_err() {
+        let _ = std::os::unix::fs::symlink(&target, &link);
+    }
+}
+
+/// Symlinks need elevated rights on Windows; senders.json carries the names there
+#[cfg(not(unix))]
+fn relink(_base: &Path, _device_id: &str, _old: Option<&str>, _new: &str) {}
+
+/// Where a received file goes: the sender's device folder when the message can be traced
+/// back to a device, otherwise a folder named after the sender
+pub fn folder_for(
+    db: &Database,
+    base: &Path,
+    message_id: Option<&str>,
+    sender_name: &str,
+) -> PathBuf {
+    match message_id.and_then(|mid| db.get_message_sender(mid).ok().flatten()) {
+        Some(device_id) => {
+            remember_name(base, &device_id, sender_name);
+            sender_dir(base, &device_id)
+        }
+        None => legacy_dir(base, sender_name),
+    }
+}
+
+/// Move everything in `from` into `to`, merging folders that exist on both sides. Entries
+/// that would overwrite something are left where they are.
+fn merge_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !to.exists() {
+        return fs::rename(from, to);
+    }
+    for entry in fs::read_dir(from)?.flatten() {
+        let src = entry.path();
+        let dest = to.join(entry.file_name());
+        if !dest.exists() {
+            fs::rename(&src, &dest)?;
+        } else if src.is_dir() && dest.is_dir() {
+            merge_dir(&src, &dest)?;
+        }
+    }
+    // Only succeeds once it's empty
+    let _ = fs::remove_dir(from);
+    Ok(())
+}
+
+/// One-time move from the old Downloads/<username>/ layout to Downloads/<device_id>/.
+/// Paths stored for messages and downloads are rewritten to match.
+pub fn migrate(db: &Database, base: &Path) {
+    if db.get_setting(LAYOUT_KEY).ok().flatten().as_deref() == Some(LAYOUT) {
+        return;
+    }
+    let users = match db.get_all_users() {
+        Ok(users) => users,
+        Err(e) => {
+            warn!("Download folder migration skipped: {}", e);
+            return;
+        }
+    };
+    let mut moved = 0;
+    for user in users {
+        let old = legacy_dir(base, &user.username);
+        let new = sender_dir(base, &user.id);
+        let is_real_dir = fs::symlink_metadata(&old)
+            .map(|m| m.is_dir())
+            .unwrap_or(false);
+        if user.username.trim().is_empty() || old == new || !is_real_dir {
+            continue;
+        }
+        if let Err(e) = merge_dir(&old, &new) {
+            warn!("Moving download folder {:?} failed: {}", old, e);
+            continue;
+        }
+        let old_prefix = format!("{}{}", old.to_string_lossy(), MAIN_SEPARATOR);
+        let new_prefix = format!("{}{}", new.to_string_lossy(), MAIN_SEPARATOR);
+        if let Err(e) = db.rebase_file_paths(&old_prefix, &new_prefix) {
+            warn!("Updating stored paths for {:?} failed: {}", old, e);
+        }
+        remember_name(base, &user.id, &user.username);
+        moved += 1;
+    }
+    let _ = db.set_setting(LAYOUT_KEY, LAYOUT);
+    info!("Download folders keyed by device id ({} moved)", moved);
+}
+
+// ============ COMMANDS ============
+
+/// Display name on record for each sender folder, keyed by device id
+#[tauri::command]
+pub fn get_download_folder_names(state: State<AppState>) -> BTreeMap<String, String> {
+    load_names(&state.file_transfer.get_downloads_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_moves_name_folders() {
+        let base = std::env::temp_dir().join(format!("pingo_dl_{}", crate::db::generate_id()));
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("dev-1", "Bob", None).unwrap();
+        fs::create_dir_all(base.join("Bob").join("images")).unwrap();
+        fs::write(base.join("Bob").join("images").join("a.png"), b"x").unwrap();
+
+        migrate(&db, &base);
+        assert!(sender_dir(&base, "dev-1")
+            .join("images")
+            .join("a.png")
+            .exists());
+        assert_eq!(
+            load_names(&base).get("dev-1").map(String::as_str),
+            Some("Bob")
+        );
+        // Only once
+        assert_eq!(db.get_setting(LAYOUT_KEY).unwrap().as_deref(), Some(LAYOUT));
+
+        remember_name(&base, "dev-1", "Robert");
+        assert_eq!(
+            load_names(&base).get("dev-1").map(String::as_str),
+            Some("Robert")
+        );
+        let _ = fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,223 @@
+// src-tauri/src/download_policy.rs
+// Auto-download policy: decides, before a chat attachment is fetched, whether it downloads
+// on its own, waits for the user to approve it, or is never downloaded automatically. Rules
+// are per kind of file, with a size limit and an optional "only on a wired connection".
+
+use crate::commands::AppState;
+use crate::db::Database;
+use crate::device_sync;
+use crate::http_client;
+use network_interface::NetworkInterfaceConfig;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, UdpSocket};
+use tauri::State;
+
+const SETTING_KEY: &str = "auto_download_policy";
+
+const EXECUTABLE_EXTS: &[&str] = &[
+    "exe", "msi", "bat", "cmd", "com", "scr", "ps1", "vbs", "js", "jar", "sh", "app", "dmg", "pkg",
+    "apk", "deb", "rpm", "appimage",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    Auto,
+    Ask,
+    Never,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PolicyRule {
+    pub action: PolicyAction,
+    /// Larger files need approval even when the action is auto
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+    /// Ask instead of downloading automatically while on Wi-Fi
+    #[serde(default)]
+    pub wired_only: bool,
+}
+
+impl PolicyRule {
+    fn new(action: PolicyAction, max_size_mb: Option<u64>) -> Self {
+        PolicyRule {
+            action,
+            max_size_mb,
+            wired_only: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DownloadPolicy {
+    pub images: PolicyRule,
+    pub videos: PolicyRule,
+    pub files: PolicyRule,
+    pub executables: PolicyRule,
+}
+
+impl Default for DownloadPolicy {
+    fn default() -> Self {
+        DownloadPolicy {
+            images: PolicyRule::new(PolicyAction::Auto, Some(10)),
+            videos: PolicyRule::new(PolicyAction::Ask, None),
+            files: PolicyRule::new(PolicyAction::Auto, Some(25)),
+            executables: PolicyRule::new(PolicyAction::Never, None),
+        }
+    }
+}
+
+/// Lowercased extension of `file_name`, empty when it has none
+pub fn extension(file_name: &str) -> String {
+    file_name
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Whether a file can run code when opened (installers, scripts, binaries)
+pub fn is_executable(file_name: &str) -> bool {
+    EXECUTABLE_EXTS.contains(&extension(file_name).as_str())
+}
+
+impl DownloadPolicy {
+    fn rule_for(&self, file_type: &str, file_name: &str) -> &PolicyRule {
+        if is_executable(file_name) {
+            &self.executables
+        } else if file_type == "image" {
+            &self.images
+        } else if file_type == "video" {
+            &self.videos
+        } else {
+            &self.files
+        }
+    }
+}
+
+pub fn load(db: &Database) -> DownloadPolicy {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+/// What to do with a download. `size` and `on_wifi` are None when they couldn't be found
+/// out; an unknown size doesn't block an automatic download.
+pub fn evaluate(
+    policy: &DownloadPolicy,
+    file_type: &str,
+    file_name: &str,
+    size: Option<u64>,
+    on_wifi: Option<bool>,
+) -> PolicyAction {
+    let rule = policy.rule_for(file_type, file_name);
+    if rule.action != PolicyAction::Auto {
+        return rule.action;
+    }
+    let too_big =
+        matches!((rule.max_size_mb, size), (Some(max), Some(size)) if size > max * 1024 * 1024);
+    if too_big || (rule.wired_only && on_wifi == Some(true)) {
+        PolicyAction::Ask
+    } else {
+        PolicyAction::Auto
+    }
+}
+
+/// Size the sender's file server reports for `url`
+pub fn remote_size(url: &str) -> Option<u64> {
+    let response = http_client::client().head(url).send().ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Whether traffic to `url`'s host leaves through a wireless interface
+pub fn is_wifi_route(url: &str) -> Option<bool> {
+    let host = url.split("://").nth(1)?.split(['/', ':']).next()?;
+    let peer: IpAddr = host.parse().ok()?;
+    // connect() on UDP only picks the route; nothing is sent
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect((peer, 9)).ok()?;
+    let local = socket.local_addr().ok()?.ip();
+    let interfaces = network_interface::NetworkInterface::show().ok()?;
+    let iface = interfaces
+        .iter()
+        .find(|i| i.addr.iter().any(|a| a.ip() == local))?;
+    Some(is_wireless(&iface.name))
+}
+
+fn is_wireless(name: &str) -> bool {
+    if cfg!(target_os = "linux") {
+        return std::path::Path::new("/sys/class/net")
+            .join(name)
+            .join("wireless")
+            .exists()
+            || name.starts_with("wl");
+    }
+    let lower = name.to_ascii_lowercase();
+    lower.contains("wi-fi") || lower.contains("wireless") || lower.contains("wlan")
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_download_policy(state: State<AppState>) -> DownloadPolicy {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_download_policy(state: State<AppState>, policy: DownloadPolicy) -> Result<(), String> {
+    let json = serde_json::to_string(&policy).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    device_sync::push_settings(&state, &[SETTING_KEY]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = DownloadPolicy::default();
+        let mb = 1024 * 1024;
+        assert_eq!(
+            evaluate(&policy, "image", "cat.png", Some(2 * mb), None),
+            PolicyAction::Auto
+        );
+        assert_eq!(
+            evaluate(&policy, "image", "huge.png", Some(40 * mb), None),
+            PolicyAction::Ask
+        );
+        assert_eq!(
+            evaluate(&policy, "video", "clip.mp4", Some(mb), None),
+            PolicyAction::Ask
+        );
+        assert_eq!(
+            evaluate(&policy, "file", "setup.EXE", Some(mb), None),
+            PolicyAction::Never
+        );
+
+        let mut wired = policy.clone();
+        wired.images.wired_only = true;
+        assert_eq!(
+            evaluate(&wired, "image", "cat.png", Some(mb), Some(true)),
+            PolicyAction::Ask
+        );
+        assert_eq!(
+            evaluate(&wired, "image", "cat.png", Some(mb), Some(false)),
+            PolicyAction::Auto
+        );
+    }
+}
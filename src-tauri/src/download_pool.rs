@@ -0,0 +1,186 @@
+// src-tauri/src/download_pool.rs
+// Background pool for auto-downloaded attachments. auto_download_file records the download
+// and queues it here instead of fetching inline, so an album of photos comes down a few at a
+// time rather than as a line of blocking calls. At most MAX_ACTIVE downloads run at once and
+// at most MAX_PER_HOST from one sender; failed fetches are retried with backoff. Every
+// download ends with a "download-finished" event.
+
+use crate::commands::{fetch_attachment, AppState};
+use crate::downloads;
+use crate::file_server;
+use crate::ocr;
+use crate::scan;
+use crate::transcription;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{info, warn};
+
+const MAX_ACTIVE: usize = 4;
+const MAX_PER_HOST: usize = 2;
+const MAX_ATTEMPTS: u32 = 3;
+/// Doubled after every failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// Row in the downloads table
+    pub id: String,
+    pub url: String,
+    pub sender_name: String,
+    pub file_name: String,
+    pub file_type: String,
+    pub message_id: Option<String>,
+    pub checksum: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Finished<'a> {
+    id: &'a str,
+    file_id: &'a str,
+    file_name: &'a str,
+    message_id: Option<&'a str>,
+    path: Option<&'a str>,
+    error: Option<&'a str>,
+}
+
+struct Queued {
+    job: Job,
+    attempt: u32,
+}
+
+#[derive(Default)]
+struct Pool {
+    queue: VecDeque<Queued>,
+    // host -> downloads running against it
+    active: HashMap<String, usize>,
+    // url -> download id, for everything queued, running or waiting to retry
+    in_flight: HashMap<String, String>,
+}
+
+static POOL: Mutex<Option<Pool>> = Mutex::new(None);
+
+fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(String::from))
+        .unwrap_or_default()
+}
+
+/// Quarantined files aren't fetched again; everything else may be a flaky connection
+fn retryable(error: &str) -> bool {
+    !error.starts_with(scan::QUARANTINED)
+}
+
+/// The download already queued or running for `url`, if any
+pub fn in_flight(url: &str) -> Option<String> {
+    POOL.lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|p| p.in_flight.get(url).cloned())
+}
+
+pub fn enqueue<R: Runtime>(app: &AppHandle<R>, job: Job) {
+    {
+        let mut guard = POOL.lock().unwrap();
+        let pool = guard.get_or_insert_with(Pool::default);
+        pool.in_flight.insert(job.url.clone(), job.id.clone());
+        pool.queue.push_back(Queued { job, attempt: 1 });
+    }
+    pump(app);
+}
+
+/// Start queued downloads while there's room, passing over hosts that are at their cap
+fn pump<R: Runtime>(app: &AppHandle<R>) {
+    let mut guard = POOL.lock().unwrap();
+    let pool = guard.get_or_insert_with(Pool::default);
+    while pool.active.values().sum::<usize>() < MAX_ACTIVE {
+        let Some(index) = pool.queue.iter().position(|q| {
+            pool.active.get(&host_of(&q.job.url)).copied().unwrap_or(0) < MAX_PER_HOST
+        }) else {
+            break;
+        };
+        let Some(queued) = pool.queue.remove(index) else {
+            break;
+        };
+        *pool.active.entry(host_of(&queued.job.url)).or_insert(0) += 1;
+        let app = app.clone();
+        std::thread::spawn(move || run(&app, queued));
+    }
+}
+
+fn run<R: Runtime>(app: &AppHandle<R>, queued: Queued) {
+    let Queued { job, attempt } = queued;
+    let state = app.state::<AppState>();
+    let result = fetch_attachment(
+        app,
+        &state,
+        &job.url,
+        &job.sender_name,
+        &job.file_name,
+        &job.file_type,
+        job.message_id.as_deref(),
+        job.checksum.as_deref(),
+    );
+    {
+        let host = host_of(&job.url);
+        let mut guard = POOL.lock().unwrap();
+        let pool = guard.get_or_insert_with(Pool::default);
+        if let Some(running) = pool.active.get_mut(&host) {
+            *running -= 1;
+            if *running == 0 {
+                pool.active.remove(&host);
+            }
+        }
+    }
+
+    match result {
+        Err(e) if attempt < MAX_ATTEMPTS && retryable(&e) => {
+            let delay = RETRY_DELAY * 2u32.pow(attempt - 1);
+            warn!(
+                "Download {} failed ({}), retrying in {:?}",
+                job.id, e, delay
+            );
+            // Waiting out the backoff doesn't hold a slot
+            let retry_app = app.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                POOL.lock()
+                    .unwrap()
+                    .get_or_insert_with(Pool::default)
+                    .queue
+                    .push_back(Queued {
+                        job,
+                        attempt: attempt + 1,
+                    });
+                pump(&retry_app);
+            });
+        }
+        result => {
+            downloads::settle(&state.db, &job.id, &result);
+            if result.is_ok() {
+                transcription::queue(app, &state.db, &job.id);
+                ocr::queue_download(app, &state.db, &job.id);
+            }
+            if let Some(pool) = POOL.lock().unwrap().as_mut() {
+                pool.in_flight.remove(&job.url);
+            }
+            info!("Download {} finished: {}", job.id, result.is_ok());
+            let _ = app.emit(
+                "download-finished",
+                Finished {
+                    id: &job.id,
+                    file_id: file_server::file_id_from_url(&job.url),
+                    file_name: &job.file_name,
+                    message_id: job.message_id.as_deref(),
+                    path: result.as_deref().ok(),
+                    error: result.as_ref().err().map(String::as_str),
+                },
+            );
+        }
+    }
+    pump(app);
+}
@@ -0,0 +1,177 @@
+// src-tauri/src/downloads.rs
+// Downloads manager: every way a file reaches this machine (auto-downloaded chat
+// attachments, "save as" downloads and chunked transfers) is recorded in the downloads
+// table, so the downloads view can list, filter and retry them. Downloads the auto-download
+// policy held back wait in the table (awaiting_approval or blocked) until approved.
+
+use crate::commands::{self, AppState};
+use crate::db::{generate_id, now, Database, DownloadFilter, DownloadRecord};
+use crate::disk_space;
+use crate::download_policy;
+use crate::scan;
+use std::path::Path;
+use tauri::{AppHandle, Runtime, State};
+use tracing::{info, warn};
+
+/// Where a download came from, as known when it starts
+#[derive(Default)]
+pub struct Source<'a> {
+    pub kind: &'a str,
+    pub source_peer: Option<String>,
+    pub source_name: Option<&'a str>,
+    pub message_id: Option<&'a str>,
+    pub url: Option<&'a str>,
+    pub file_name: &'a str,
+    pub file_type: &'a str,
+    pub path: Option<&'a str>,
+    pub size: i64,
+    pub checksum: Option<&'a str>,
+}
+
+/// Record a download as started; `id` lets callers reuse an existing id (transfer ids)
+pub fn begin(db: &Database, id: Option<&str>, src: Source) -> String {
+    let at = now();
+    let record = DownloadRecord {
+        id: id.map(String::from).unwrap_or_else(generate_id),
+        kind: src.kind.to_string(),
+        source_peer: src.source_peer,
+        source_name: src.source_name.map(String::from),
+        message_id: src.message_id.map(String::from),
+        url: src.url.map(String::from),
+        file_name: src.file_name.to_string(),
+        file_type: src.file_type.to_string(),
+        path: src.path.map(String::from),
+        size: src.size,
+        status: "downloading".to_string(),
+        error: None,
+        created_at: at.clone(),
+        updated_at: at,
+        checksum: src.checksum.map(String::from),
+        transcript: None,
+        ocr_text: None,
+    };
+    if let Err(e) = db.save_download(&record) {
+        warn!("Recording download failed: {}", e);
+    }
+    record.id
+}
+
+/// Mark a download complete (with the final path), failed or quarantined
+pub fn settle(db: &Database, id: &str, result: &Result<String, String>) {
+    let updated = match result {
+        Ok(path) => {
+            let size = std::fs::metadata(path).map(|m| m.len() as i64).ok();
+            db.update_download(id, "complete", Some(path), size, None)
+        }
+        Err(e) if e.starts_with(scan::QUARANTINED) => {
+            db.update_download(id, "quarantined", None, None, Some(e))
+        }
+        Err(e) => db.update_download(id, "failed", None, None, Some(e)),
+    };
+    if let Err(e) = updated {
+        warn!("Updating download {} failed: {}", id, e);
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Answer a download-approval-needed prompt: download it now, or drop it
+#[tauri::command]
+pub fn approve_download<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    id: String,
+    approve: bool,
+) -> Result<DownloadRecord, String> {
+    if approve {
+        return retry_download(app, state, id);
+    }
+    state
+        .db
+        .update_download(&id, "cancelled", None, None, None)
+        .map_err(|e| e.to_string())?;
+    state
+        .db
+        .get_download(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Unknown download".to_string())
+}
+
+#[tauri::command]
+pub fn get_downloads(
+    state: State<AppState>,
+    filter: Option<DownloadFilter>,
+) -> Result<Vec<DownloadRecord>, String> {
+    state
+        .db
+        .get_downloads(&filter.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Try a failed, cancelled, paused or held-back download again from its original URL
+#[tauri::command]
+pub fn retry_download<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    id: String,
+) -> Result<DownloadRecord, String> {
+    let record = state
+        .db
+        .get_download(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Unknown download")?;
+    if record.status == "downloading" || record.status == "complete" {
+        return Err(format!("Download is already {}", record.status));
+    }
+    let url = record
+        .url
+        .clone()
+        .ok_or("This download has to be sent again by the sender")?;
+    let size = download_policy::remote_size(&url).unwrap_or(0);
+    let space = match record.path.as_deref() {
+        Some(path) if record.kind == "save_as" => {
+            let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+            disk_space::ensure_space(&app, &state.db, dir, size, &record.file_name)
+        }
+        _ => disk_space::ensure_download_space(&app, &state, size, &record.file_name),
+    };
+    if let Err(e) = space {
+        let _ = state
+            .db
+            .update_download(&id, "paused", None, None, Some(&e));
+        return Err(e);
+    }
+    state
+        .db
+        .update_download(&id, "downloading", None, None, None)
+        .map_err(|e| e.to_string())?;
+
+    let result = match record.kind.as_str() {
+        "save_as" => {
+            let path = record.path.clone().ok_or("No destination to save to")?;
+            commands::http_get_bytes(&url).and_then(|bytes| {
+                std::fs::write(&path, bytes)
+                    .map(|_| path)
+                    .map_err(|e| format!("Write failed: {}", e))
+            })
+        }
+        _ => commands::fetch_attachment(
+            &app,
+            &state,
+            &url,
+            record.source_name.as_deref().unwrap_or("Unknown"),
+            &record.file_name,
+            &record.file_type,
+            record.message_id.as_deref(),
+            record.checksum.as_deref(),
+        ),
+    };
+    settle(&state.db, &id, &result);
+    info!("Retried download {}: {}", id, result.is_ok());
+    result?;
+    state
+        .db
+        .get_download(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Unknown download".to_string())
+}
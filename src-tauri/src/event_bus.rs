@@ -0,0 +1,237 @@
+// src-tauri/src/event_bus.rs
+// One push stream from the backend to the webview. Message writes in the DB, transfer progress
+// and delivery changes are published here and emitted as typed "backend-event"s, so the UI
+// no longer re-queries last messages, unread counts or progress after everything it does.
+// Summaries are recomputed once per burst of writes rather than once per write.
+
+use crate::chat_folders;
+use crate::commands::AppState;
+use crate::db::{DataChange, LastMessageInfo};
+use crate::delivery_status::MessageStatus;
+use crate::file_transfer::{ProgressListener, TransferProgress, TransferStatus};
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+const EVENT_NAME: &str = "backend-event";
+/// Transfer progress also goes out on its own, for views that only follow transfers
+const PROGRESS_EVENT_NAME: &str = "transfer-progress";
+/// Same for delivery state changes
+const STATUS_EVENT_NAME: &str = "message-status-changed";
+/// Writes closer together than this share one summary refresh
+const SUMMARY_DEBOUNCE: Duration = Duration::from_millis(150);
+/// Progress for a transfer is emitted at most this often (completion always goes out)
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+const IDLE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BackendEvent {
+    /// Direct messages with this peer were stored, read, deleted…; None when several chats
+    MessagesChanged {
+        peer_id: Option<String>,
+    },
+    UnreadCounts {
+        total: i32,
+        by_peer: HashMap<String, i32>,
+        /// Folder id -> unread total of its chats
+        by_folder: HashMap<String, i32>,
+    },
+    /// Newest message per conversation, for the sidebar
+    LastMessages {
+        items: Vec<LastMessageInfo>,
+    },
+    TransferProgress {
+        progress: TransferProgress,
+    },
+    /// A direct message moved along the delivery state machine (see delivery_status)
+    MessageStatusChanged {
+        message_id: String,
+        status: MessageStatus,
+    },
+}
+
+/// Payload of STATUS_EVENT_NAME
+#[derive(Serialize)]
+struct StatusChange<'a> {
+    message_id: &'a str,
+    status: MessageStatus,
+}
+
+enum Input {
+    Data(DataChange),
+    Event(BackendEvent),
+}
+
+pub struct EventBus {
+    sender: Sender<Input>,
+    receiver: Receiver<Input>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded();
+        EventBus { sender, receiver }
+    }
+
+    pub fn publish(&self, event: BackendEvent) {
+        let _ = self.sender.send(Input::Event(event));
+    }
+
+    /// Hook for FileTransferManager::set_progress_listener
+    pub fn progress_listener(&self) -> ProgressListener {
+        let sender = self.sender.clone();
+        Box::new(move |progress| {
+            let _ = sender.send(Input::Event(BackendEvent::TransferProgress { progress }));
+        })
+    }
+
+    /// Hook for Database::set_change_listener
+    pub fn data_listener(&self) -> Box<dyn Fn(DataChange) + Send> {
+        let sender = self.sender.clone();
+        Box::new(move |change| {
+            let _ = sender.send(Input::Data(change));
+        })
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn emit<R: Runtime>(app: &AppHandle<R>, event: &BackendEvent) {
+    let _ = app.emit(EVENT_NAME, event);
+}
+
+fn emit_summaries<R: Runtime>(app: &AppHandle<R>, state: &AppState) {
+    let local_id = state.device_id();
+    if let Ok(counts) = state.db.get_unread_counts_by_peer(&local_id) {
+        let by_peer: HashMap<String, i32> = counts.into_iter().collect();
+        emit(
+            app,
+            &BackendEvent::UnreadCounts {
+                total: by_peer.values().sum(),
+                by_folder: chat_folders::unread_by_folder(&state.db, &by_peer),
+                by_peer,
+            },
+        );
+    }
+    if let Ok(items) = state.db.get_last_messages(&local_id) {
+        emit(app, &BackendEvent::LastMessages { items });
+    }
+}
+
+/// The peer in a conversation pair, from our side
+fn peer_of(change: DataChange, local_id: &str) -> Option<String> {
+    match change {
+        DataChange::Conversation(a, b) => Some(if a == local_id { b } else { a }),
+        DataChange::Messages => None,
+    }
+}
+
+/// Forward published events to the webview for the life of the app
+pub fn start<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let receiver = state.events.receiver.clone();
+        let mut summaries_at: Option<Instant> = None;
+        let mut progress_sent: HashMap<String, Instant> = HashMap::new();
+        loop {
+            let wait = summaries_at.map_or(IDLE, |at| at.saturating_duration_since(Instant::now()));
+            match receiver.recv_timeout(wait) {
+                Ok(Input::Data(change)) => {
+                    let peer_id = peer_of(change, &state.device_id());
+                    emit(&app, &BackendEvent::MessagesChanged { peer_id });
+                    summaries_at.get_or_insert(Instant::now() + SUMMARY_DEBOUNCE);
+                }
+                Ok(Input::Event(BackendEvent::TransferProgress { progress })) => {
+                    let done = progress.status != TransferStatus::Active
+                        || progress.chunks_completed >= progress.total_chunks;
+                    let recent = progress_sent
+                        .get(&progress.transfer_id)
+                        .is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL);
+                    if done {
+                        progress_sent.remove(&progress.transfer_id);
+                    } else if recent {
+                        continue;
+                    } else {
+                        progress_sent.insert(progress.transfer_id.clone(), Instant::now());
+                    }
+                    let _ = app.emit(PROGRESS_EVENT_NAME, &progress);
+                    emit(&app, &BackendEvent::TransferProgress { progress });
+                }
+                Ok(Input::Event(BackendEvent::MessageStatusChanged { message_id, status })) => {
+                    let change = StatusChange {
+                        message_id: &message_id,
+                        status,
+                    };
+                    let _ = app.emit(STATUS_EVENT_NAME, &change);
+                    emit(
+                        &app,
+                        &BackendEvent::MessageStatusChanged { message_id, status },
+                    );
+                }
+                Ok(Input::Event(event)) => emit(&app, &event),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if summaries_at.is_some_and(|at| at <= Instant::now()) {
+                summaries_at = None;
+                emit_summaries(&app, &state);
+            }
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+/// Push the current summaries right away (the UI calls this once after subscribing)
+#[tauri::command]
+pub fn refresh_backend_summaries<R: Runtime>(app: AppHandle<R>, state: State<AppState>) {
+    emit_summaries(&app, &state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{now, Database, Message};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_db_writes_reach_the_bus() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("me", "Me", None).unwrap();
+        db.upsert_peer_as_user("peer", "Peer", None).unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        db.set_change_listener(Box::new(move |c| sink.lock().unwrap().push(c)));
+
+        db.create_message(&Message {
+            id: "m1".to_string(),
+            sender_id: "peer".to_string(),
+            receiver_id: "me".to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: true,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Delivered,
+        })
+        .unwrap();
+        db.mark_messages_read_from_peer("me", "peer").unwrap();
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        assert!(seen
+            .into_iter()
+            .all(|c| peer_of(c, "me").as_deref() == Some("peer")));
+    }
+}
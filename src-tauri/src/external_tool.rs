@@ -0,0 +1,124 @@
+// src-tauri/src/external_tool.rs
+// Running the external programs work is handed to (OCR, speech-to-text, a translator command,
+// document converters): each one is spawned with a deadline, its stdout is read on the side so
+// the pipe can't fill up and stall it, and a failed exit becomes an error that names the tool.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Stands for the input file's path in configured arguments
+pub const FILE_PLACEHOLDER: &str = "{file}";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `args` with `{file}` filled in; the path is appended when no argument uses it
+pub fn args_with_file(args: &[String], file: &Path) -> Vec<String> {
+    let file = file.to_string_lossy();
+    let mut filled: Vec<String> = args
+        .iter()
+        .map(|a| a.replace(FILE_PLACEHOLDER, &file))
+        .collect();
+    if !args.iter().any(|a| a.contains(FILE_PLACEHOLDER)) {
+        filled.push(file.into_owned());
+    }
+    filled
+}
+
+pub struct Tool<'a> {
+    /// What errors call it: "OCR timed out"
+    pub name: &'a str,
+    pub program: &'a str,
+    pub args: &'a [String],
+    pub timeout_secs: u64,
+}
+
+impl Tool<'_> {
+    fn spawn(&self, stdin: Stdio, stdout: Stdio) -> Result<Child, String> {
+        Command::new(self.program)
+            .args(self.args)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("{} could not be started: {}", self.name, e))
+    }
+
+    /// Wait for the exit, killing it once the timeout is up
+    fn wait(&self, child: &mut Child) -> Result<(), String> {
+        let deadline = Instant::now() + Duration::from_secs(self.timeout_secs.max(1));
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => std::thread::sleep(POLL_INTERVAL),
+                Ok(None) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("{} timed out", self.name));
+                }
+                Err(e) => return Err(format!("{} failed: {}", self.name, e)),
+            }
+        };
+        match status.code() {
+            Some(0) => Ok(()),
+            Some(code) => Err(format!("{} exited with code {}", self.name, code)),
+            None => Err(format!("{} was terminated", self.name)),
+        }
+    }
+
+    /// Run to completion for its side effects (converters write files, not stdout)
+    pub fn run(&self) -> Result<(), String> {
+        let mut child = self.spawn(Stdio::null(), Stdio::null())?;
+        self.wait(&mut child)
+    }
+
+    /// Run to completion with `input` on stdin, if any, and return what it printed
+    pub fn output(&self, input: Option<&str>) -> Result<String, String> {
+        let stdin = if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        let mut child = self.spawn(stdin, Stdio::piped())?;
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+            let input = input.to_string();
+            // Written on the side: a tool that reads slowly mustn't block the timeout
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            });
+        }
+        let mut stdout = child.stdout.take();
+        let reader = std::thread::spawn(move || {
+            let mut out = String::new();
+            if let Some(s) = stdout.as_mut() {
+                let _ = s.read_to_string(&mut out);
+            }
+            out
+        });
+        self.wait(&mut child)?;
+        Ok(reader.join().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_with_file() {
+        let file = Path::new("/tmp/shot.png");
+        let args = vec!["{file}".to_string(), "stdout".to_string()];
+        assert_eq!(args_with_file(&args, file), vec!["/tmp/shot.png", "stdout"]);
+        let args = vec!["-f".to_string(), "--in={file}".to_string()];
+        assert_eq!(
+            args_with_file(&args, file),
+            vec!["-f", "--in=/tmp/shot.png"]
+        );
+        let args = vec!["-l".to_string(), "eng".to_string()];
+        assert_eq!(
+            args_with_file(&args, file),
+            vec!["-l", "eng", "/tmp/shot.png"]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+// src-tauri/src/file_index.rs
+// Content-hash index over shared_files and the downloads folder. File messages carry the
+// sender's SHA-256, so before downloading an attachment fetch_attachment looks the hash up
+// here and links or copies the local file instead of going to the network. Files are indexed
+// as they're downloaded or shared, and a scan at startup picks up everything else.
+
+use crate::commands::AppState;
+use crate::crypto::file_checksum;
+use crate::db::Database;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{info, warn};
+
+/// The startup scan leaves bigger files alone; they're indexed when shared or downloaded
+const MAX_SCAN_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Remember that `path` holds content with this SHA-256
+pub fn record(db: &Database, checksum: &str, path: &Path) {
+    let Ok(meta) = std::fs::metadata(path) else {
+        return;
+    };
+    if let Err(e) = db.index_file_hash(checksum, &path.to_string_lossy(), meta.len() as i64) {
+        warn!("Indexing {} failed: {}", path.display(), e);
+    }
+}
+
+/// A local file with this SHA-256, if we still have one. Entries whose file was removed or
+/// changed size are dropped; callers verify the content itself.
+pub fn lookup(db: &Database, checksum: &str) -> Option<PathBuf> {
+    let (path, size) = db.find_file_by_hash(checksum).ok().flatten()?;
+    let path = PathBuf::from(path);
+    match std::fs::metadata(&path) {
+        Ok(meta) if meta.is_file() && meta.len() as i64 == size => Some(path),
+        _ => {
+            let _ = db.forget_file_hash(checksum);
+            None
+        }
+    }
+}
+
+/// Put `source`'s content at `dest`: a hard link where the filesystem allows, a copy otherwise
+pub fn link_or_copy(source: &Path, dest: &Path) -> Result<(), String> {
+    if std::fs::hard_link(source, dest).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(source, dest)
+        .map(|_| ())
+        .map_err(|e| format!("Copy {}: {}", source.display(), e))
+}
+
+/// Hash every file under `dir` that isn't indexed yet and isn't too big to scan
+fn index_dir(db: &Database, dir: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut indexed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            indexed += index_dir(db, &path);
+            continue;
+        }
+        // Unfinished downloads
+        if path.extension().is_some_and(|ext| ext == "part") {
+            continue;
+        }
+        if entry.metadata().map_or(true, |m| m.len() > MAX_SCAN_BYTES) {
+            continue;
+        }
+        if db.is_path_hashed(&path.to_string_lossy()).unwrap_or(true) {
+            continue;
+        }
+        if let Ok(checksum) = file_checksum(&path) {
+            record(db, &checksum, &path);
+            indexed += 1;
+        }
+    }
+    indexed
+}
+
+/// Index shared_files and the downloads folder in the background
+pub fn start_scan<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let dirs = [
+            state.file_server.get_storage_dir(),
+            state.file_transfer.get_downloads_dir(),
+        ];
+        let indexed: usize = dirs.iter().map(|dir| index_dir(&state.db, dir)).sum();
+        if indexed > 0 {
+            info!("Indexed {} files by content hash", indexed);
+        }
+    });
+}
@@ -0,0 +1,354 @@
+// src-tauri/src/file_requests.rs
+// Accept/decline for incoming FileTransferRequests. A request becomes a pending entry and a
+// "file-transfer-requested" event; nothing is prepared or downloaded for it until the user
+// accepts (or the download policy / a trusted sender accepts it for them). The sender gets a
+// FileTransferResponse either way, including when the request times out unanswered. Only so
+// many requests wait at once, per peer and in all; past that new ones are declined.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::db::now;
+use crate::download_policy::{self, PolicyAction};
+use crate::file_send::{self, FileOffer};
+use crate::signaling::SignalingMessage;
+use crate::trust;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+/// Unanswered requests are declined after this long
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const MAX_PENDING_PER_PEER: usize = 20;
+const MAX_PENDING: usize = 200;
+/// How often unanswered requests are checked for expiry
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileRequest {
+    pub transfer_id: String,
+    pub peer_id: String,
+    pub sender_name: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub file_type: String,
+    pub received_at: String,
+    /// Sent by backend senders (file_send); None when the sender drives the transfer itself
+    #[serde(skip)]
+    pub offer: Option<FileOffer>,
+}
+
+enum Decision {
+    /// Waiting for the user until the deadline
+    Pending(Box<FileRequest>, Instant),
+    Accepted,
+    Declined,
+}
+
+// transfer id -> what became of the request
+static REQUESTS: Mutex<Option<HashMap<String, Decision>>> = Mutex::new(None);
+// Answers to files we offered: transfer id -> (receiver, accepted)
+static RESPONSES: Mutex<Option<HashMap<String, (String, bool)>>> = Mutex::new(None);
+
+/// Whether the user (or policy) accepted this transfer; receiving it is refused otherwise
+pub fn is_accepted(transfer_id: &str) -> bool {
+    matches!(
+        REQUESTS
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|r| r.get(transfer_id)),
+        Some(Decision::Accepted)
+    )
+}
+
+fn pending() -> Vec<FileRequest> {
+    REQUESTS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|r| {
+            r.values()
+                .filter_map(|d| match d {
+                    Decision::Pending(request, _) => Some(request.as_ref().clone()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Settle a pending request and tell the sender. Errors when it isn't pending any more.
+fn decide<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    transfer_id: &str,
+    accept: bool,
+) -> Result<FileRequest, String> {
+    let request = {
+        let mut guard = REQUESTS.lock().unwrap();
+        let requests = guard.get_or_insert_with(HashMap::new);
+        match requests.get(transfer_id) {
+            Some(Decision::Pending(request, _)) => {
+                let request = request.as_ref().clone();
+                let decision = if accept {
+                    Decision::Accepted
+                } else {
+                    Decision::Declined
+                };
+                requests.insert(transfer_id.to_string(), decision);
+                request
+            }
+            Some(_) => return Err("This file transfer was already answered".to_string()),
+            None => return Err(format!("No file transfer request {}", transfer_id)),
+        }
+    };
+    // A backend sender starts pushing chunks as soon as it hears yes, so be ready first
+    let mut accept = accept;
+    if let (true, Some(offer)) = (accept, request.offer.as_ref()) {
+        if let Err(e) = file_send::begin_receive(
+            app,
+            state,
+            &request.peer_id,
+            transfer_id,
+            &request.file_name,
+            request.file_size,
+            offer,
+        ) {
+            warn!("Can't receive {}: {}", request.file_name, e);
+            REQUESTS
+                .lock()
+                .unwrap()
+                .get_or_insert_with(HashMap::new)
+                .insert(transfer_id.to_string(), Decision::Declined);
+            accept = false;
+        }
+    }
+    let response = SignalingMessage::FileTransferResponse {
+        from: state.device_id(),
+        to: request.peer_id.clone(),
+        transfer_id: transfer_id.to_string(),
+        accepted: accept,
+    };
+    if let Err(e) = send_to_peer(state, &request.peer_id, &response) {
+        warn!("Couldn't answer file transfer {}: {}", transfer_id, e);
+    }
+    let _ = app.emit("file-transfer-requests-changed", transfer_id);
+    Ok(request)
+}
+
+/// Whether `peer_id` (or everyone together) already has as many requests waiting as we hold
+fn over_limit(requests: &HashMap<String, Decision>, peer_id: &str) -> bool {
+    let waiting: Vec<&FileRequest> = requests
+        .values()
+        .filter_map(|d| match d {
+            Decision::Pending(request, _) => Some(request.as_ref()),
+            _ => None,
+        })
+        .collect();
+    waiting.len() > MAX_PENDING
+        || waiting.iter().filter(|r| r.peer_id == peer_id).count() > MAX_PENDING_PER_PEER
+}
+
+/// Pending requests whose deadline has passed
+fn expired() -> Vec<String> {
+    let now = Instant::now();
+    REQUESTS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|r| {
+            r.iter()
+                .filter_map(|(id, d)| match d {
+                    Decision::Pending(_, deadline) if *deadline <= now => Some(id.clone()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Decline requests still unanswered after REQUEST_TIMEOUT, for the life of the app
+pub fn start_sweeper<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SWEEP_INTERVAL);
+        let state = app.state::<AppState>();
+        for transfer_id in expired() {
+            if let Ok(request) = decide(&app, &state, &transfer_id, false) {
+                info!("File transfer request {} timed out", transfer_id);
+                let _ = app.emit("file-transfer-request-expired", &request);
+            }
+        }
+    });
+}
+
+/// Incoming FileTransferRequest: hold it for the user unless policy decides it outright
+pub fn handle_request<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    let SignalingMessage::FileTransferRequest {
+        from,
+        file_name,
+        file_size,
+        file_type,
+        transfer_id,
+        offer,
+        ..
+    } = msg
+    else {
+        return;
+    };
+    let state = app.state::<AppState>();
+    let request = FileRequest {
+        transfer_id: transfer_id.clone(),
+        peer_id: from.clone(),
+        sender_name: state
+            .db
+            .get_user(from)
+            .ok()
+            .flatten()
+            .map(|u| u.username)
+            .unwrap_or_default(),
+        file_name: file_name.clone(),
+        file_size: *file_size,
+        file_type: file_type.clone(),
+        received_at: now(),
+        offer: offer.clone(),
+    };
+    let crowded = {
+        let mut guard = REQUESTS.lock().unwrap();
+        let requests = guard.get_or_insert_with(HashMap::new);
+        // A resend of a request we've seen; answered ones keep their answer
+        if requests.contains_key(transfer_id) {
+            return;
+        }
+        requests.insert(
+            transfer_id.clone(),
+            Decision::Pending(Box::new(request.clone()), Instant::now() + REQUEST_TIMEOUT),
+        );
+        over_limit(requests, from)
+    };
+
+    let policy = download_policy::load(&state.db);
+    let action = download_policy::evaluate(&policy, file_type, file_name, Some(*file_size), None);
+    match trust::download_action(&state.db, Some(from), action) {
+        PolicyAction::Auto => {
+            let _ = decide(app, &state, transfer_id, true);
+        }
+        PolicyAction::Never => {
+            info!("Declined {} from {} by policy", file_name, from);
+            let _ = decide(app, &state, transfer_id, false);
+        }
+        PolicyAction::Ask if crowded => {
+            warn!(
+                "Declined {} from {}: too many requests waiting",
+                file_name, from
+            );
+            let _ = decide(app, &state, transfer_id, false);
+        }
+        PolicyAction::Ask => {
+            let _ = app.emit("file-transfer-requested", &request);
+        }
+    }
+}
+
+/// The receiver's answer to a file we offered
+pub fn handle_response<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    if let SignalingMessage::FileTransferResponse {
+        from,
+        transfer_id,
+        accepted,
+        ..
+    } = msg
+    {
+        RESPONSES
+            .lock()
+            .unwrap()
+            .get_or_insert_with(HashMap::new)
+            .insert(transfer_id.clone(), (from.clone(), *accepted));
+        let _ = app.emit(
+            "file-transfer-response",
+            serde_json::json!({
+                "peer_id": from,
+                "transfer_id": transfer_id,
+                "accepted": accepted,
+            }),
+        );
+    }
+}
+
+/// Wait for `peer_id` to answer a file we offered; None on timeout
+pub fn wait_for_response(transfer_id: &str, peer_id: &str, timeout: Duration) -> Option<bool> {
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        {
+            let mut guard = RESPONSES.lock().unwrap();
+            let responses = guard.get_or_insert_with(HashMap::new);
+            if responses
+                .get(transfer_id)
+                .is_some_and(|(from, _)| from == peer_id)
+            {
+                return responses.remove(transfer_id).map(|(_, accepted)| accepted);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    None
+}
+
+// ============ COMMANDS ============
+
+/// Requests waiting for an answer
+#[tauri::command]
+pub fn get_file_transfer_requests() -> Vec<FileRequest> {
+    pending()
+}
+
+#[tauri::command]
+pub fn respond_to_file_transfer<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    transfer_id: String,
+    accept: bool,
+) -> Result<FileRequest, String> {
+    decide(&app, &state, &transfer_id, accept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiting(peer_id: &str) -> Decision {
+        Decision::Pending(
+            Box::new(FileRequest {
+                transfer_id: String::new(),
+                peer_id: peer_id.to_string(),
+                sender_name: String::new(),
+                file_name: "a.txt".to_string(),
+                file_size: 1,
+                file_type: "file".to_string(),
+                received_at: now(),
+                offer: None,
+            }),
+            Instant::now() + REQUEST_TIMEOUT,
+        )
+    }
+
+    #[test]
+    fn test_pending_limits() {
+        let mut requests = HashMap::new();
+        for i in 0..=MAX_PENDING_PER_PEER {
+            requests.insert(format!("a{}", i), waiting("alice"));
+        }
+        assert!(over_limit(&requests, "alice"));
+        assert!(!over_limit(&requests, "bob"));
+        // Answered requests don't count
+        requests.insert("a0".to_string(), Decision::Accepted);
+        assert!(!over_limit(&requests, "alice"));
+
+        for i in 0..MAX_PENDING {
+            requests.insert(format!("p{}", i), waiting(&format!("peer{}", i)));
+        }
+        assert!(over_limit(&requests, "bob"));
+    }
+}
@@ -0,0 +1,603 @@
+// src-tauri/src/file_send.rs
+// Sending a file in one call. send_file stores the message, offers the file with a
+// FileTransferRequest carrying its metadata and, once the receiver accepts, delivers it:
+// small files as chunks over signaling (missing chunks are resent until the receiver has
+// verified the checksum), larger ones from our file server over HTTP. The receiving half of
+// the chunked transport lives here too. Progress goes out as "file-send-status" events, plus
+// "transfer-progress" for the chunks themselves.
+
+use crate::commands::{self, send_to_peer, AppState};
+use crate::db::{generate_id, now, Message};
+use crate::delivery_status::{self, MessageStatus};
+use crate::file_requests;
+use crate::file_transfer::{FileChunk, FileMetadata, MAX_RETRIES};
+use crate::image_compression;
+use crate::image_metadata;
+use crate::ocr;
+use crate::pin_pairing;
+use crate::previews;
+use crate::signaling::SignalingMessage;
+use crate::video_prepare;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+/// Chunk size over signaling: base64 plus the message must fit one UDP datagram
+const SIGNALING_CHUNK_SIZE: u32 = 32 * 1024;
+/// Files up to this size go as chunks; larger ones are fetched over HTTP
+const CHUNKED_MAX_SIZE: u64 = 8 * 1024 * 1024;
+/// Gap between chunks so a burst doesn't overflow the receiver's socket buffer
+const CHUNK_INTERVAL: Duration = Duration::from_millis(2);
+/// How long the receiver has to answer a FileTransferDone before we ask again
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Missing chunk indices per status message, to stay within one datagram
+const MAX_MISSING_REPORTED: usize = 2000;
+
+pub const TRANSPORT_CHUNKS: &str = "chunks";
+pub const TRANSPORT_HTTP: &str = "http";
+
+/// What a backend sender adds to a FileTransferRequest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileOffer {
+    /// Id of the chat message the file belongs to, the same on both sides
+    pub message_id: String,
+    pub message_type: String,
+    pub checksum: String,
+    pub total_chunks: u32,
+    pub chunk_size: u32,
+    /// TRANSPORT_CHUNKS or TRANSPORT_HTTP
+    pub transport: String,
+    /// Set when an image was sent compressed: where to fetch the original (see
+    /// image_compression::original_info)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSendStatus {
+    pub transfer_id: String,
+    pub peer_id: String,
+    pub message_id: String,
+    pub transport: String,
+    /// "requested", "accepted", "declined", "sending", "retrying", "sent", "complete", "failed"
+    pub stage: String,
+    pub error: Option<String>,
+}
+
+/// A chunked transfer we accepted, until it's verified
+struct Receiving {
+    peer_id: String,
+    file_name: String,
+    offer: FileOffer,
+    /// Set once complete_transfer ran, so a repeated FileTransferDone gets the same answer
+    verified: Option<bool>,
+}
+
+/// A FileTransferStatus, kept until the sending thread picks it up
+struct ReceiverStatus {
+    from: String,
+    missing: Vec<u32>,
+    verified: Option<bool>,
+}
+
+// transfer id -> what the receiver last reported
+static STATUSES: Mutex<Option<HashMap<String, ReceiverStatus>>> = Mutex::new(None);
+static RECEIVING: Mutex<Option<HashMap<String, Receiving>>> = Mutex::new(None);
+
+/// The chat message content for a file, as the frontend writes it
+fn file_info(
+    transfer_id: &str,
+    file_name: &str,
+    port: u16,
+    token: &str,
+    offer: &FileOffer,
+) -> String {
+    let mut info = serde_json::json!({
+        "fileId": transfer_id,
+        "fileName": file_name,
+        "port": port,
+        "token": token,
+        "type": offer.message_type,
+        "checksum": offer.checksum,
+    });
+    if let Some(original) = &offer.original {
+        info["original"] = original.clone();
+    }
+    info.to_string()
+}
+
+fn message_type_for(file_name: &str) -> &'static str {
+    let mime = crate::file_server::guess_mime(file_name);
+    if mime.starts_with("image/") {
+        "image"
+    } else if mime.starts_with("video/") {
+        "video"
+    } else {
+        "file"
+    }
+}
+
+/// Where a send stage leaves the file message; declined leaves it as it was
+fn message_status(stage: &str) -> Option<MessageStatus> {
+    match stage {
+        "requested" | "sending" => Some(MessageStatus::Sending),
+        "retrying" => Some(MessageStatus::Retrying),
+        "sent" => Some(MessageStatus::Sent),
+        "failed" => Some(MessageStatus::Failed),
+        _ => None,
+    }
+}
+
+struct Sender<'a, R: Runtime> {
+    app: &'a AppHandle<R>,
+    state: &'a AppState,
+    peer_id: String,
+    transfer_id: String,
+    message: Message,
+    file_name: String,
+    offer: FileOffer,
+}
+
+impl<R: Runtime> Sender<'_, R> {
+    fn emit(&self, stage: &str, error: Option<String>) {
+        let status = FileSendStatus {
+            transfer_id: self.transfer_id.clone(),
+            peer_id: self.peer_id.clone(),
+            message_id: self.message.id.clone(),
+            transport: self.offer.transport.clone(),
+            stage: stage.to_string(),
+            error,
+        };
+        let _ = self.app.emit("file-send-status", &status);
+        if let Some(next) = message_status(stage) {
+            delivery_status::update(self.state, &self.message.id, next);
+        }
+    }
+
+    fn delivered(&self) {
+        delivery_status::update(self.state, &self.message.id, MessageStatus::Delivered);
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let request = SignalingMessage::FileTransferRequest {
+            from: self.state.device_id(),
+            to: self.peer_id.clone(),
+            file_name: self.file_name.clone(),
+            file_size: self.file_size(),
+            file_type: self.offer.message_type.clone(),
+            transfer_id: self.transfer_id.clone(),
+            offer: Some(self.offer.clone()),
+        };
+        send_to_peer(self.state, &self.peer_id, &request)?;
+        self.emit("requested", None);
+
+        match file_requests::wait_for_response(
+            &self.transfer_id,
+            &self.peer_id,
+            file_requests::REQUEST_TIMEOUT + STATUS_TIMEOUT,
+        ) {
+            Some(true) => self.emit("accepted", None),
+            Some(false) => {
+                self.emit("declined", None);
+                return Ok(());
+            }
+            None => return Err("The receiver didn't answer".to_string()),
+        }
+
+        if self.offer.transport == TRANSPORT_HTTP {
+            self.send_http()
+        } else {
+            self.send_chunks()
+        }
+    }
+
+    fn file_size(&self) -> u64 {
+        self.state
+            .file_transfer
+            .get_transfer(&self.transfer_id)
+            .map(|t| t.file_size)
+            .unwrap_or(0)
+    }
+
+    /// Serve the file and relay the message; the receiver downloads it and acknowledges the
+    /// message like any other
+    fn send_http(&self) -> Result<(), String> {
+        let path = self
+            .message
+            .file_path
+            .as_deref()
+            .ok_or("The file has no local path")?;
+        self.state
+            .file_server
+            .register_file(&self.transfer_id, Path::new(path), &self.file_name);
+        let sender_name = self
+            .state
+            .db
+            .get_user(&self.state.device_id())
+            .ok()
+            .flatten()
+            .map(|u| u.username)
+            .unwrap_or_default();
+        let relay = SignalingMessage::ChatMessage {
+            from: self.state.device_id(),
+            to: self.peer_id.clone(),
+            id: self.message.id.clone(),
+            content: self.message.content.clone(),
+            message_type: self.message.message_type.clone(),
+            sender_name,
+            timestamp: self.message.created_at.clone(),
+            lamport: self.message.lamport,
+            auto_reply: false,
+        };
+        send_to_peer(self.state, &self.peer_id, &relay)?;
+        self.emit("sent", None);
+        Ok(())
+    }
+
+    fn send_chunk_list(&self, indices: &[u32]) -> Result<(), String> {
+        for &index in indices {
+            let chunk = self
+                .state
+                .file_transfer
+                .get_chunk(&self.transfer_id, index)?;
+            let msg = SignalingMessage::FileChunkData {
+                from: self.state.device_id(),
+                to: self.peer_id.clone(),
+                chunk,
+            };
+            send_to_peer(self.state, &self.peer_id, &msg)?;
+            std::thread::sleep(CHUNK_INTERVAL);
+        }
+        Ok(())
+    }
+
+    /// Ask which chunks are missing; None when the receiver never answers
+    fn request_status(&self) -> Option<(Vec<u32>, Option<bool>)> {
+        let done = SignalingMessage::FileTransferDone {
+            from: self.state.device_id(),
+            to: self.peer_id.clone(),
+            transfer_id: self.transfer_id.clone(),
+        };
+        for _ in 0..MAX_RETRIES {
+            if send_to_peer(self.state, &self.peer_id, &done).is_err() {
+                return None;
+            }
+            let asked = Instant::now();
+            while asked.elapsed() < STATUS_TIMEOUT {
+                if let Some(status) = take_status(&self.transfer_id, &self.peer_id) {
+                    return Some(status);
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+        None
+    }
+
+    fn send_chunks(&self) -> Result<(), String> {
+        self.emit("sending", None);
+        let mut pending: Vec<u32> = (0..self.offer.total_chunks).collect();
+        for attempt in 0..=MAX_RETRIES {
+            if attempt > 0 {
+                info!("Resending {} chunks of {}", pending.len(), self.transfer_id);
+                self.emit("retrying", None);
+            }
+            self.send_chunk_list(&pending)?;
+            match self.request_status() {
+                Some((_, Some(true))) => {
+                    self.delivered();
+                    self.emit("complete", None);
+                    return Ok(());
+                }
+                Some((_, Some(false))) => {
+                    return Err("The receiver's copy didn't match the checksum".to_string())
+                }
+                Some((missing, None)) => pending = missing,
+                None => return Err("The receiver stopped answering".to_string()),
+            }
+        }
+        Err(format!(
+            "{} chunks still missing after {} retries",
+            pending.len(),
+            MAX_RETRIES
+        ))
+    }
+}
+
+fn take_status(transfer_id: &str, peer_id: &str) -> Option<(Vec<u32>, Option<bool>)> {
+    let mut guard = STATUSES.lock().unwrap();
+    let statuses = guard.get_or_insert_with(HashMap::new);
+    match statuses.get(transfer_id) {
+        Some(status) if status.from == peer_id => statuses
+            .remove(transfer_id)
+            .map(|status| (status.missing, status.verified)),
+        _ => None,
+    }
+}
+
+// ============ RECEIVING ============
+
+/// Set up an accepted chunked transfer (file_requests calls this before answering)
+pub(crate) fn begin_receive<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    peer_id: &str,
+    transfer_id: &str,
+    file_name: &str,
+    file_size: u64,
+    offer: &FileOffer,
+) -> Result<(), String> {
+    if offer.transport != TRANSPORT_CHUNKS {
+        return Ok(());
+    }
+    let metadata = FileMetadata {
+        transfer_id: transfer_id.to_string(),
+        file_name: file_name.to_string(),
+        file_size,
+        file_type: offer.message_type.clone(),
+        total_chunks: offer.total_chunks,
+        checksum: offer.checksum.clone(),
+        chunk_size: offer.chunk_size,
+    };
+    commands::begin_receive(app, state, &metadata)?;
+    RECEIVING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(
+            transfer_id.to_string(),
+            Receiving {
+                peer_id: peer_id.to_string(),
+                file_name: file_name.to_string(),
+                offer: offer.clone(),
+                verified: None,
+            },
+        );
+    Ok(())
+}
+
+fn is_receiving_from(transfer_id: &str, peer_id: &str) -> bool {
+    RECEIVING
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|r| r.get(transfer_id))
+        .is_some_and(|r| r.peer_id == peer_id && r.verified.is_none())
+}
+
+/// Verify the finished file and store its message. Returns whether it checked out.
+fn finish_receive<R: Runtime>(app: &AppHandle<R>, state: &AppState, transfer_id: &str) -> bool {
+    let path = state
+        .file_transfer
+        .get_transfer(transfer_id)
+        .map(|t| t.file_path);
+    let verified = commands::finish_receive(app, state, transfer_id).unwrap_or_else(|e| {
+        warn!("Verifying {} failed: {}", transfer_id, e);
+        false
+    });
+    let Some((peer_id, file_name, offer)) = RECEIVING
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|r| r.get_mut(transfer_id))
+        .map(|r| {
+            r.verified = Some(verified);
+            (r.peer_id.clone(), r.file_name.clone(), r.offer.clone())
+        })
+    else {
+        return verified;
+    };
+    if verified {
+        let message = Message {
+            id: offer.message_id.clone(),
+            sender_id: peer_id,
+            receiver_id: state.device_id(),
+            content: file_info(transfer_id, &file_name, 0, "", &offer),
+            message_type: offer.message_type.clone(),
+            file_path: path.map(|p: PathBuf| p.to_string_lossy().to_string()),
+            is_read: false,
+            is_delivered: true,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Delivered,
+        };
+        match state.db.create_message(&message) {
+            Ok(_) => {
+                let _ = app.emit("chat-message-received", &message);
+            }
+            Err(e) => warn!("Failed to store file message {}: {}", message.id, e),
+        }
+    }
+    verified
+}
+
+fn handle_chunk(state: &AppState, from: &str, chunk: &FileChunk) {
+    if !is_receiving_from(&chunk.transfer_id, from) {
+        return;
+    }
+    if let Err(e) = state.file_transfer.receive_chunk(chunk) {
+        warn!(
+            "Chunk {} of {} failed: {}",
+            chunk.chunk_index, chunk.transfer_id, e
+        );
+    }
+}
+
+fn handle_done<R: Runtime>(app: &AppHandle<R>, state: &AppState, from: &str, transfer_id: &str) {
+    let known = RECEIVING
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|r| r.get(transfer_id))
+        .filter(|r| r.peer_id == from)
+        .map(|r| r.verified);
+    let Some(verified) = known else {
+        return;
+    };
+    let (missing, verified) = match verified {
+        Some(v) => (Vec::new(), Some(v)),
+        None => {
+            let mut missing = state.file_transfer.get_missing_chunks(transfer_id);
+            if missing.is_empty() {
+                (missing, Some(finish_receive(app, state, transfer_id)))
+            } else {
+                missing.truncate(MAX_MISSING_REPORTED);
+                (missing, None)
+            }
+        }
+    };
+    let status = SignalingMessage::FileTransferStatus {
+        from: state.device_id(),
+        to: from.to_string(),
+        transfer_id: transfer_id.to_string(),
+        missing,
+        verified,
+    };
+    if let Err(e) = send_to_peer(state, from, &status) {
+        warn!("Couldn't report status of {}: {}", transfer_id, e);
+    }
+}
+
+/// Signaling handler for FileChunkData, FileTransferDone and FileTransferStatus
+pub fn handle_message<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    let state = app.state::<AppState>();
+    match msg {
+        SignalingMessage::FileChunkData { from, chunk, .. } => handle_chunk(&state, from, chunk),
+        SignalingMessage::FileTransferDone {
+            from, transfer_id, ..
+        } => handle_done(app, &state, from, transfer_id),
+        SignalingMessage::FileTransferStatus {
+            from,
+            transfer_id,
+            missing,
+            verified,
+            ..
+        } => {
+            STATUSES
+                .lock()
+                .unwrap()
+                .get_or_insert_with(HashMap::new)
+                .insert(
+                    transfer_id.clone(),
+                    ReceiverStatus {
+                        from: from.clone(),
+                        missing: missing.clone(),
+                        verified: *verified,
+                    },
+                );
+        }
+        _ => {}
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Send a file from disk to a peer. Returns the stored (not yet delivered) message right
+/// away; the transfer continues in the background and reports "file-send-status" events.
+#[tauri::command]
+pub fn send_file<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+    path: String,
+) -> Result<Message, String> {
+    pin_pairing::check_outgoing(&state, &peer_id)?;
+    let transfer_id = generate_id();
+    let file_name = Path::new(&path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or("Not a file")?;
+    // A prepared video leaves its job folder for good
+    let path = video_prepare::take_prepared(&state, Path::new(&path), &transfer_id)?;
+    // Photos go out without their EXIF/GPS metadata (a cleaned copy; the original stays as is)
+    let path = image_metadata::stripped_copy(
+        &state.db,
+        &path,
+        &state.file_server.get_storage_dir(),
+        &transfer_id,
+    )?;
+    // and scaled down when image compression is on; the uncompressed one stays downloadable
+    let (path, original) = match image_compression::compress_for_send(&state, &transfer_id, &path) {
+        Some((compressed, variant)) => (
+            compressed,
+            Some(image_compression::original_info(&state, &variant)),
+        ),
+        None => (path, None),
+    };
+    let size = std::fs::metadata(&path)
+        .map_err(|e| format!("Can't read {}: {}", path.display(), e))?
+        .len();
+    let chunked = size <= CHUNKED_MAX_SIZE;
+    let mut metadata = if chunked {
+        state
+            .file_transfer
+            .prepare_send_chunked(&path, &transfer_id, SIGNALING_CHUNK_SIZE)?
+    } else {
+        state.file_transfer.prepare_send(&path, &transfer_id)?
+    };
+    // The file on disk may be a cleaned or compressed copy; the peer sees the real name
+    metadata.file_name = file_name;
+    let offer = FileOffer {
+        message_id: generate_id(),
+        message_type: message_type_for(&metadata.file_name).to_string(),
+        checksum: metadata.checksum.clone(),
+        total_chunks: metadata.total_chunks,
+        chunk_size: metadata.chunk_size,
+        transport: if chunked {
+            TRANSPORT_CHUNKS
+        } else {
+            TRANSPORT_HTTP
+        }
+        .to_string(),
+        original,
+    };
+
+    let message = Message {
+        id: offer.message_id.clone(),
+        sender_id: state.device_id(),
+        receiver_id: peer_id.clone(),
+        content: file_info(
+            &transfer_id,
+            &metadata.file_name,
+            state.file_server.get_port(),
+            &state.file_server.token(&transfer_id),
+            &offer,
+        ),
+        message_type: offer.message_type.clone(),
+        file_path: Some(path.to_string_lossy().to_string()),
+        is_read: false,
+        is_delivered: false,
+        created_at: now(),
+        lamport: state.db.next_lamport().map_err(|e| e.to_string())?,
+        status: MessageStatus::Queued,
+    };
+    state
+        .db
+        .create_message(&message)
+        .map_err(|e| e.to_string())?;
+    ocr::queue_sent(&app, &state.db, &message.id, &path.to_string_lossy());
+    previews::queue(&state, &transfer_id, &path, &metadata.file_name);
+
+    let handle = app.clone();
+    let result = message.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        let sender = Sender {
+            app: &handle,
+            state: &state,
+            peer_id,
+            transfer_id,
+            message,
+            file_name: metadata.file_name,
+            offer,
+        };
+        if let Err(e) = sender.run() {
+            warn!("Sending {} failed: {}", sender.file_name, e);
+            sender.emit("failed", Some(e));
+        }
+    });
+    Ok(result)
+}
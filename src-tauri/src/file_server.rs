@@ -1,17 +1,88 @@
 // src-tauri/src/file_server.rs
 // Tiny HTTP file server for serving images/files to LAN peers
-
-use std::collections::HashMap;
+//
+// Two listeners: one on 127.0.0.1 for the app's own URLs (avatars, previews, note attachments)
+// that serves anything registered, and one on the LAN that only serves files we shared, each
+// behind a per-file token handed out with the file message. Our own avatar is the exception:
+// it's published without a token, since every peer is told where to fetch it. Neither
+// listener answers browser requests from pages other than the app's own. Preview images of
+// shared files (see previews) are served from /thumb/<id> with the file's own token.
+
+use crate::crypto::generate_checksum;
+use crate::lan_policy;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::{Read, Seek, SeekFrom, Take};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use std::thread;
+use tracing::{info, warn};
 
 /// A simple HTTP file server that serves stored files to LAN peers
 pub struct FileServer {
     files: Arc<RwLock<HashMap<String, StoredFile>>>,
+    /// LAN listener; the port peers are told about
     port: Arc<RwLock<u16>>,
+    /// Localhost listener for the app's own URLs
+    local_port: Arc<RwLock<u16>>,
     storage_dir: PathBuf,
+    lan: LanAccess,
+}
+
+/// Who the LAN listener serves: published files, and shared files asked for with their token
+#[derive(Clone)]
+struct LanAccess {
+    /// Tokens are a keyed hash of the file id, so they survive restarts without being stored
+    secret: Arc<String>,
+    public: Arc<RwLock<HashSet<String>>>,
+}
+
+impl LanAccess {
+    fn token(&self, file_id: &str) -> String {
+        // Key on both sides so an id can't be extended onto a known token
+        generate_checksum(format!("{}:{}:{}", self.secret, file_id, self.secret).as_bytes())[..32]
+            .to_string()
+    }
+
+    fn allows(&self, file_id: &str, query: &str) -> bool {
+        if self.public.read().unwrap().contains(file_id) {
+            return true;
+        }
+        let expected = self.token(file_id);
+        query
+            .split('&')
+            .filter_map(|pair| pair.strip_prefix("token="))
+            .any(|token| token == expected)
+    }
+}
+
+/// The token key kept next to the shared files, created on first run
+fn load_secret(storage_dir: &std::path::Path) -> String {
+    let path = storage_dir.join(".lan_token_key");
+    if let Ok(secret) = fs::read_to_string(&path) {
+        if secret.trim().len() >= 32 {
+            return secret.trim().to_string();
+        }
+    }
+    let secret = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    if let Err(e) = fs::write(&path, &secret) {
+        // Tokens change with every run then; files shared before a restart need resending
+        warn!("Couldn't save the file server token key: {}", e);
+    }
+    secret
+}
+
+/// The file id a /file/ URL points at, without any query
+pub fn file_id_from_url(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
 }
 
 #[allow(dead_code)]
@@ -30,11 +101,17 @@ impl FileServer {
             .join("Pingo")
             .join("shared_files");
         fs::create_dir_all(&storage_dir).ok();
+        let lan = LanAccess {
+            secret: Arc::new(load_secret(&storage_dir)),
+            public: Arc::new(RwLock::new(HashSet::new())),
+        };
 
         FileServer {
             files: Arc::new(RwLock::new(HashMap::new())),
             port: Arc::new(RwLock::new(0)),
+            local_port: Arc::new(RwLock::new(0)),
             storage_dir,
+            lan,
         }
     }
 
@@ -82,7 +159,6 @@ impl FileServer {
     }
 
     /// Store raw bytes
-    #[allow(dead_code)]
     pub fn store_bytes(
         &self,
         file_id: &str,
@@ -117,7 +193,12 @@ impl FileServer {
         }
         let files = self.files.read().unwrap();
         if files.contains_key(file_id) {
-            Some(format!("http://0.0.0.0:{}/file/{}", port, file_id))
+            Some(format!(
+                "http://0.0.0.0:{}/file/{}?token={}",
+                port,
+                file_id,
+                self.token(file_id)
+            ))
         } else {
             None
         }
@@ -127,11 +208,48 @@ impl FileServer {
         *self.port.read().unwrap()
     }
 
+    pub fn get_local_port(&self) -> u16 {
+        *self.local_port.read().unwrap()
+    }
+
+    /// URL for this machine only (webview previews, avatars we display)
+    pub fn local_url(&self, file_id: &str) -> String {
+        format!(
+            "http://127.0.0.1:{}/file/{}",
+            self.get_local_port(),
+            file_id
+        )
+    }
+
+    /// Token a LAN peer needs to fetch this file; goes out with the file message
+    pub fn token(&self, file_id: &str) -> String {
+        self.lan.token(file_id)
+    }
+
+    /// Serve this file on the LAN without a token (our avatar)
+    pub fn publish(&self, file_id: &str) {
+        self.lan.public.write().unwrap().insert(file_id.to_string());
+    }
+
     /// Get the storage directory path
     pub fn get_storage_dir(&self) -> PathBuf {
         self.storage_dir.clone()
     }
 
+    /// Where the preview image of a file is cached, whether or not it exists yet
+    pub fn thumbnail_path(&self, file_id: &str) -> PathBuf {
+        thumbnail_path(&self.storage_dir, file_id)
+    }
+
+    /// URL of a file's preview image for this machine only
+    pub fn local_thumbnail_url(&self, file_id: &str) -> String {
+        format!(
+            "http://127.0.0.1:{}/thumb/{}",
+            self.get_local_port(),
+            file_id
+        )
+    }
+
     /// Register an externally-downloaded file so the HTTP server can serve it
     pub fn register_file(&self, file_id: &str, path: &std::path::Path, file_name: &str) {
         let mime = guess_mime(file_name);
@@ -147,20 +265,70 @@ impl FileServer {
             .insert(file_id.to_string(), stored);
     }
 
-    /// Start the HTTP server
+    /// Where a registered file lives on disk
+    pub fn file_path(&self, file_id: &str) -> Option<PathBuf> {
+        self.files
+            .read()
+            .unwrap()
+            .get(file_id)
+            .map(|f| f.path.clone())
+    }
+
+    /// SHA-256 of a served file, sent along with file messages so receivers can verify it
+    pub fn checksum(&self, file_id: &str) -> Option<String> {
+        let path = self.files.read().unwrap().get(file_id)?.path.clone();
+        crate::crypto::file_checksum(&path).ok()
+    }
+
+    /// Stop serving a file; returns every copy of it on disk (registered path, stored copies
+    /// and unfinished downloads) for the caller to delete
+    pub fn forget_file(&self, file_id: &str) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .files
+            .write()
+            .unwrap()
+            .remove(file_id)
+            .map(|f| f.path)
+            .into_iter()
+            .collect();
+        if file_id.is_empty() || file_id.starts_with('.') {
+            return paths;
+        }
+        let thumbnail = self.thumbnail_path(file_id);
+        if thumbnail.exists() {
+            paths.push(thumbnail);
+        }
+        if let Ok(entries) = fs::read_dir(&self.storage_dir) {
+            for entry in entries.flatten() {
+                let fname = entry.file_name().to_string_lossy().to_string();
+                if fname == file_id
+                    || fname
+                        .strip_prefix(file_id)
+                        .is_some_and(|rest| rest.starts_with('.'))
+                {
+                    paths.push(entry.path());
+                }
+            }
+        }
+        paths
+    }
+
+    /// Forget every registered file (profile switch); files on disk are left alone
+    pub fn clear_registered_files(&self) {
+        self.files.write().unwrap().clear();
+    }
+
+    /// Start the LAN and localhost listeners; returns the LAN port
     pub fn start(&self, preferred_port: u16) -> Result<u16, String> {
         // Try preferred port first
         let server = match tiny_http::Server::http(format!("0.0.0.0:{}", preferred_port)) {
             Ok(s) => {
-                println!(
-                    "[Pingo] File server bound to preferred port {}",
-                    preferred_port
-                );
+                info!("File server bound to preferred port {}", preferred_port);
                 s
             }
             Err(e) => {
-                println!(
-                    "[Pingo] Failed to bind to port {}: {}. Trying random port...",
+                warn!(
+                    "Failed to bind to port {}: {}. Trying random port...",
                     preferred_port, e
                 );
                 // Try any available port (0 means OS assigns)
@@ -179,91 +347,255 @@ impl FileServer {
             return Err("Server bound to port 0 - this should not happen".to_string());
         }
 
+        // Next port up when it's free, so local URLs usually stay the same between runs
+        let local_server =
+            tiny_http::Server::http(format!("127.0.0.1:{}", actual_port.wrapping_add(1)))
+                .or_else(|_| tiny_http::Server::http("127.0.0.1:0"))
+                .map_err(|e| format!("Failed to start local file server: {}", e))?;
+        let local_port = local_server
+            .server_addr()
+            .to_ip()
+            .map(|a| a.port())
+            .unwrap_or(0);
+
         *self.port.write().unwrap() = actual_port;
-        println!(
-            "[Pingo] File server listening on port {} and ready",
-            actual_port
+        *self.local_port.write().unwrap() = local_port;
+        info!(
+            "File server listening on port {} (LAN) and {} (local) and ready",
+            actual_port, local_port
         );
 
         let files = Arc::clone(&self.files);
         let storage_dir = self.storage_dir.clone();
+        thread::spawn(move || serve(local_server, files, storage_dir, None));
+
+        let files = Arc::clone(&self.files);
+        let storage_dir = self.storage_dir.clone();
+        let lan = self.lan.clone();
+        thread::spawn(move || serve(server, files, storage_dir, Some(lan)));
+
+        Ok(actual_port)
+    }
+}
+
+/// Answer requests on one listener; `lan` is None for the localhost one
+fn serve(
+    server: tiny_http::Server,
+    files: Arc<RwLock<HashMap<String, StoredFile>>>,
+    storage_dir: PathBuf,
+    lan: Option<LanAccess>,
+) {
+    info!(
+        "File server request handler thread started (lan: {})",
+        lan.is_some()
+    );
+    for request in server.incoming_requests() {
+        if lan.is_some() {
+            let outside_lan = request
+                .remote_addr()
+                .is_some_and(|addr| !lan_policy::accepts(addr.ip()));
+            if outside_lan {
+                let _ = request.respond(tiny_http::Response::empty(403));
+                continue;
+            }
+        }
+        let url = request.url().to_string();
+
+        // Browsers name the page making the request; only the app's own pages get an answer.
+        // Peers' downloads and <img> loads send no Origin and rely on the token instead.
+        let origin = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Origin"))
+            .map(|h| h.value.as_str().to_string());
+        if origin.as_deref().is_some_and(|o| !is_app_origin(o)) {
+            let _ = request.respond(tiny_http::Response::empty(403));
+            continue;
+        }
+
+        // Helper to create CORS header each time (tiny_http headers are consumed)
+        let cors = || match origin.as_deref() {
+            Some(origin) => tiny_http::Header::from_bytes(
+                &b"Access-Control-Allow-Origin"[..],
+                origin.as_bytes(),
+            )
+            .unwrap(),
+            None => tiny_http::Header::from_bytes(&b"Vary"[..], &b"Origin"[..]).unwrap(),
+        };
+
+        if request.method() == &tiny_http::Method::Options {
+            let response = tiny_http::Response::empty(200)
+                .with_header(cors())
+                .with_header(
+                    tiny_http::Header::from_bytes(
+                        &b"Access-Control-Allow-Methods"[..],
+                        &b"GET, OPTIONS"[..],
+                    )
+                    .unwrap(),
+                );
+            let _ = request.respond(response);
+            continue;
+        }
 
-        thread::spawn(move || {
-            println!("[Pingo] File server request handler thread started");
-            for request in server.incoming_requests() {
-                let url = request.url().to_string();
-
-                // Helper to create CORS header each time (tiny_http headers are consumed)
-                let cors = || {
-                    tiny_http::Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..])
-                        .unwrap()
-                };
-
-                if request.method() == &tiny_http::Method::Options {
-                    let response = tiny_http::Response::empty(200)
-                        .with_header(cors())
-                        .with_header(
-                            tiny_http::Header::from_bytes(
-                                &b"Access-Control-Allow-Methods"[..],
-                                &b"GET, OPTIONS"[..],
-                            )
-                            .unwrap(),
-                        );
-                    let _ = request.respond(response);
+        if let Some(rest) = url.strip_prefix("/file/") {
+            let (file_id, query) = rest.split_once('?').unwrap_or((rest, ""));
+            let file_id = file_id.trim_matches('/');
+            let not_found = || {
+                tiny_http::Response::from_string("Not found")
+                    .with_status_code(404)
+                    .with_header(cors())
+            };
+            // Unshared files and wrong tokens look the same as missing ones
+            if file_id.is_empty()
+                || file_id.starts_with('.')
+                || lan.as_ref().is_some_and(|lan| !lan.allows(file_id, query))
+            {
+                let _ = request.respond(not_found());
+                continue;
+            }
+            let range = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("Range"))
+                .map(|h| h.value.as_str().to_string());
+
+            // First check in-memory registry
+            let stored = files.read().unwrap().get(file_id).cloned();
+
+            if let Some(stored) = stored {
+                if let Ok(resp) = fs::File::open(&stored.path)
+                    .and_then(|file| file_response(file, &stored.mime_type, range.as_deref()))
+                {
+                    let _ = request.respond(resp.with_header(cors()));
                     continue;
                 }
+            }
 
-                if let Some(file_id) = url.strip_prefix("/file/") {
-                    let file_id = file_id.trim_matches('/');
-
-                    // First check in-memory registry
-                    let stored = files.read().unwrap().get(file_id).cloned();
-
-                    if let Some(stored) = stored {
-                        if stored.path.exists() {
-                            if let Ok(data) = fs::read(&stored.path) {
-                                let ct = tiny_http::Header::from_bytes(
-                                    &b"Content-Type"[..],
-                                    stored.mime_type.as_bytes(),
-                                )
-                                .unwrap();
-                                let resp = tiny_http::Response::from_data(data)
-                                    .with_header(ct)
-                                    .with_header(cors());
-                                let _ = request.respond(resp);
-                                continue;
-                            }
-                        }
-                    }
-
-                    // Try finding file on disk by ID prefix
-                    let disk_data = find_file_on_disk(&storage_dir, file_id);
-                    if let Some((data, mime)) = disk_data {
-                        let ct =
-                            tiny_http::Header::from_bytes(&b"Content-Type"[..], mime.as_bytes())
-                                .unwrap();
-                        let resp = tiny_http::Response::from_data(data)
-                            .with_header(ct)
-                            .with_header(cors());
-                        let _ = request.respond(resp);
-                        continue;
-                    }
-
-                    // 404
-                    let resp = tiny_http::Response::from_string("Not found")
-                        .with_status_code(404)
-                        .with_header(cors());
-                    let _ = request.respond(resp);
-                } else {
-                    let resp =
-                        tiny_http::Response::from_string("Pingo File Server").with_header(cors());
-                    let _ = request.respond(resp);
-                }
+            // Try finding file on disk by ID
+            let on_disk = find_file_on_disk(&storage_dir, file_id);
+            if let Some(resp) =
+                on_disk.and_then(|(file, mime)| file_response(file, &mime, range.as_deref()).ok())
+            {
+                let _ = request.respond(resp.with_header(cors()));
+                continue;
             }
-        });
 
-        Ok(actual_port)
+            let _ = request.respond(not_found());
+        } else if let Some(rest) = url.strip_prefix("/thumb/") {
+            // Same id and token as the file itself
+            let (file_id, query) = rest.split_once('?').unwrap_or((rest, ""));
+            let file_id = file_id.trim_matches('/');
+            let allowed =
+                is_plain_id(file_id) && lan.as_ref().is_none_or(|lan| lan.allows(file_id, query));
+            let thumb = fs::File::open(thumbnail_path(&storage_dir, file_id))
+                .ok()
+                .filter(|_| allowed);
+            let _ = match thumb.and_then(|file| file_response(file, "image/png", None).ok()) {
+                Some(resp) => request.respond(resp.with_header(cors())),
+                None => request.respond(
+                    tiny_http::Response::from_string("Not found")
+                        .with_status_code(404)
+                        .with_header(cors()),
+                ),
+            };
+        } else {
+            let resp = tiny_http::Response::from_string("Pingo File Server").with_header(cors());
+            let _ = request.respond(resp);
+        }
+    }
+}
+
+/// A file id that can't climb out of the storage folder
+pub fn is_plain_id(file_id: &str) -> bool {
+    !file_id.is_empty() && !file_id.starts_with('.') && !file_id.contains(['/', '\\', ':'])
+}
+
+fn thumbnail_path(storage_dir: &std::path::Path, file_id: &str) -> PathBuf {
+    storage_dir
+        .join("thumbnails")
+        .join(format!("{}.png", file_id))
+}
+
+/// The webview's origin (it differs by platform), or the dev server in debug builds
+fn is_app_origin(origin: &str) -> bool {
+    if matches!(
+        origin,
+        "tauri://localhost" | "http://tauri.localhost" | "https://tauri.localhost"
+    ) {
+        return true;
+    }
+    cfg!(debug_assertions)
+        && ["http://localhost:", "http://127.0.0.1:"]
+            .iter()
+            .any(|prefix| {
+                origin
+                    .strip_prefix(prefix)
+                    .is_some_and(|port| port.parse::<u16>().is_ok())
+            })
+}
+
+/// Byte range asked for by a "Range: bytes=START-[END]" header, as (start, inclusive end)
+/// clamped to `len`. Err when it starts past the end; Ok(None) for no or unsupported ranges.
+fn parse_range(header: Option<&str>, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return Ok(None);
+    };
+    // Multiple ranges and suffix ranges ("-500") get the whole file
+    let Some((start, end)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return Ok(None);
+    };
+    let Ok(start) = start.trim().parse::<u64>() else {
+        return Ok(None);
+    };
+    if start >= len {
+        return Err(());
+    }
+    let end = end
+        .trim()
+        .parse::<u64>()
+        .map_or(len - 1, |e| e.min(len - 1));
+    if end < start {
+        return Ok(None);
+    }
+    Ok(Some((start, end)))
+}
+
+/// The whole file, or the requested part of it (206) so interrupted downloads can resume.
+/// Streamed from disk starting at the range, so large files are never read into memory.
+fn file_response(
+    mut file: fs::File,
+    mime: &str,
+    range: Option<&str>,
+) -> std::io::Result<tiny_http::Response<Take<fs::File>>> {
+    let header = |name: &str, value: &str| {
+        tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+    };
+    let len = file.metadata()?.len();
+    let (status, start, count, content_range) = match parse_range(range, len) {
+        Ok(Some((start, end))) => (
+            206,
+            start,
+            end - start + 1,
+            Some(format!("bytes {}-{}/{}", start, end, len)),
+        ),
+        Ok(None) => (200, 0, len, None),
+        Err(()) => (416, 0, 0, Some(format!("bytes */{}", len))),
+    };
+    file.seek(SeekFrom::Start(start))?;
+    let body = file.take(count);
+    let mut resp = tiny_http::Response::new(
+        tiny_http::StatusCode(status),
+        Vec::new(),
+        body,
+        usize::try_from(count).ok(),
+        None,
+    );
+    if let Some(value) = content_range {
+        resp = resp.with_header(header("Content-Range", &value));
     }
+    Ok(resp
+        .with_header(header("Content-Type", mime))
+        .with_header(header("Accept-Ranges", "bytes")))
 }
 
 fn mime_to_ext(mime: &str) -> &str {
@@ -281,7 +613,7 @@ fn mime_to_ext(mime: &str) -> &str {
     }
 }
 
-fn guess_mime(filename: &str) -> String {
+pub(crate) fn guess_mime(filename: &str) -> String {
     let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
     match ext.as_str() {
         "png" => "image/png",
@@ -298,14 +630,19 @@ fn guess_mime(filename: &str) -> String {
     .to_string()
 }
 
-fn find_file_on_disk(storage_dir: &std::path::Path, file_id: &str) -> Option<(Vec<u8>, String)> {
+fn find_file_on_disk(storage_dir: &std::path::Path, file_id: &str) -> Option<(fs::File, String)> {
     if let Ok(entries) = fs::read_dir(storage_dir) {
         for entry in entries.flatten() {
             let fname = entry.file_name().to_string_lossy().to_string();
-            if fname.starts_with(file_id) {
-                if let Ok(data) = fs::read(entry.path()) {
+            // <id> or <id>.<ext>; unfinished downloads (.part) aren't served
+            let matches = fname == file_id
+                || fname
+                    .strip_prefix(file_id)
+                    .is_some_and(|rest| rest.starts_with('.'));
+            if matches && !fname.ends_with(".part") {
+                if let Ok(file) = fs::File::open(entry.path()) {
                     let mime = guess_mime(&fname);
-                    return Some((data, mime));
+                    return Some((file, mime));
                 }
             }
         }
@@ -6,14 +6,15 @@ use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{Read, Write, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use sha2::{Sha256, Digest};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
 // Chunk size: 64KB for good balance between overhead and reliability
 const CHUNK_SIZE: usize = 64 * 1024;
-#[allow(dead_code)]
-const MAX_RETRIES: u32 = 3;
+pub const MAX_RETRIES: u32 = 3;
+// Progress is reported every this many chunks, or when the whole percentage changes
+const PROGRESS_EVERY_CHUNKS: u32 = 16;
 
 /// File transfer metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +25,13 @@ pub struct FileMetadata {
     pub file_type: String,
     pub total_chunks: u32,
     pub checksum: String,
+    /// Bytes per chunk; smaller than the default when chunks travel over signaling
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: u32,
+}
+
+fn default_chunk_size() -> u32 {
+    CHUNK_SIZE as u32
 }
 
 /// Individual chunk data
@@ -63,6 +71,19 @@ pub struct TransferState {
     pub is_complete: bool,
     pub file_path: PathBuf,
     pub checksum: String,
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: u32,
+}
+
+/// Where a transfer stands; anything but Active is final
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    #[default]
+    Active,
+    Complete,
+    Failed,
+    Cancelled,
 }
 
 /// File transfer progress event
@@ -74,44 +95,88 @@ pub struct TransferProgress {
     pub bytes_transferred: u64,
     pub total_bytes: u64,
     pub percentage: f32,
+    /// Chunks handed out for sending rather than received
+    #[serde(default)]
+    pub is_sender: bool,
+    #[serde(default)]
+    pub status: TransferStatus,
 }
 
+/// Receives progress as chunks move and when a transfer completes, fails or is cancelled
+pub type ProgressListener = Box<dyn Fn(TransferProgress) + Send + Sync>;
+
 /// File transfer manager
 pub struct FileTransferManager {
     transfers: Arc<RwLock<HashMap<String, TransferState>>>,
-    downloads_dir: PathBuf,
+    downloads_dir: RwLock<PathBuf>,
+    on_progress: RwLock<Option<ProgressListener>>,
+    // transfer id -> chunks_completed when progress was last reported
+    reported: Mutex<HashMap<String, u32>>,
 }
 
 impl FileTransferManager {
     /// Create a new file transfer manager
     pub fn new() -> Self {
-        let instance = std::env::var("PINGO_INSTANCE").unwrap_or_default();
-        let folder_name = if instance.is_empty() {
-            "Pingo".to_string()
-        } else {
-            format!("Pingo_{}", instance)
-        };
-
-        let downloads_dir = dirs::download_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(folder_name);
+        let downloads_dir = crate::profiles::downloads_dir(&crate::profiles::active_profile());
 
         // Create downloads directory if it doesn't exist
         fs::create_dir_all(&downloads_dir).ok();
 
         FileTransferManager {
             transfers: Arc::new(RwLock::new(HashMap::new())),
-            downloads_dir,
+            downloads_dir: RwLock::new(downloads_dir),
+            on_progress: RwLock::new(None),
+            reported: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_progress_listener(&self, listener: ProgressListener) {
+        *self.on_progress.write().unwrap() = Some(listener);
+    }
+
+    /// Tell the listener, throttled while Active: every PROGRESS_EVERY_CHUNKS chunks, each whole
+    /// percent, and the last chunk. Final states always go out.
+    fn report(&self, mut progress: TransferProgress, status: TransferStatus) {
+        progress.status = status;
+        {
+            let mut reported = self.reported.lock().unwrap();
+            if status == TransferStatus::Active {
+                let last = reported.get(&progress.transfer_id).copied().unwrap_or(0);
+                let percent = |chunks: u32| chunks as u64 * 100 / progress.total_chunks.max(1) as u64;
+                let due = progress.chunks_completed >= last + PROGRESS_EVERY_CHUNKS
+                    || percent(progress.chunks_completed) != percent(last)
+                    || progress.chunks_completed == progress.total_chunks;
+                if !due || progress.chunks_completed == last {
+                    return;
+                }
+                reported.insert(progress.transfer_id.clone(), progress.chunks_completed);
+            } else {
+                reported.remove(&progress.transfer_id);
+            }
+        }
+        if let Some(listener) = self.on_progress.read().unwrap().as_ref() {
+            listener(progress);
         }
     }
 
     /// Get the downloads directory
     pub fn get_downloads_dir(&self) -> PathBuf {
-        self.downloads_dir.clone()
+        self.downloads_dir.read().unwrap().clone()
+    }
+
+    /// Point downloads at another folder (profile switch)
+    pub fn set_downloads_dir(&self, dir: PathBuf) {
+        fs::create_dir_all(&dir).ok();
+        *self.downloads_dir.write().unwrap() = dir;
     }
 
     /// Prepare a file for sending
     pub fn prepare_send(&self, file_path: &Path, transfer_id: &str) -> Result<FileMetadata, String> {
+        self.prepare_send_chunked(file_path, transfer_id, CHUNK_SIZE as u32)
+    }
+
+    /// Prepare a file for sending in chunks of `chunk_size` bytes
+    pub fn prepare_send_chunked(&self, file_path: &Path, transfer_id: &str, chunk_size: u32) -> Result<FileMetadata, String> {
         let file = File::open(file_path).map_err(|e| e.to_string())?;
         let metadata = file.metadata().map_err(|e| e.to_string())?;
         let file_size = metadata.len();
@@ -132,7 +197,7 @@ impl FileTransferManager {
         let checksum = self.calculate_file_checksum(file_path)?;
 
         // Calculate total chunks
-        let total_chunks = ((file_size as f64) / (CHUNK_SIZE as f64)).ceil() as u32;
+        let total_chunks = ((file_size as f64) / (chunk_size as f64)).ceil() as u32;
 
         // Create transfer state
         let state = TransferState {
@@ -145,6 +210,7 @@ impl FileTransferManager {
             is_complete: false,
             file_path: file_path.to_path_buf(),
             checksum: checksum.clone(),
+            chunk_size,
         };
 
         {
@@ -159,13 +225,15 @@ impl FileTransferManager {
             file_type,
             total_chunks,
             checksum,
+            chunk_size,
         })
     }
 
     /// Prepare to receive a file
     pub fn prepare_receive(&self, metadata: &FileMetadata) -> Result<PathBuf, String> {
         // Create unique file path
-        let mut file_path = self.downloads_dir.join(&metadata.file_name);
+        let downloads_dir = self.get_downloads_dir();
+        let mut file_path = downloads_dir.join(&metadata.file_name);
         let mut counter = 1;
 
         while file_path.exists() {
@@ -179,9 +247,9 @@ impl FileTransferManager {
                 .unwrap_or("");
 
             if ext.is_empty() {
-                file_path = self.downloads_dir.join(format!("{} ({})", stem, counter));
+                file_path = downloads_dir.join(format!("{} ({})", stem, counter));
             } else {
-                file_path = self.downloads_dir.join(format!("{} ({}).{}", stem, counter, ext));
+                file_path = downloads_dir.join(format!("{} ({}).{}", stem, counter, ext));
             }
             counter += 1;
         }
@@ -201,6 +269,7 @@ impl FileTransferManager {
             is_complete: false,
             file_path: file_path.clone(),
             checksum: metadata.checksum.clone(),
+            chunk_size: metadata.chunk_size,
         };
 
         {
@@ -213,24 +282,40 @@ impl FileTransferManager {
 
     /// Get a chunk to send
     pub fn get_chunk(&self, transfer_id: &str, chunk_index: u32) -> Result<FileChunk, String> {
-        let transfers = self.transfers.read().unwrap();
-        let state = transfers.get(transfer_id)
-            .ok_or("Transfer not found")?;
+        let (file_path, chunk_size) = {
+            let transfers = self.transfers.read().unwrap();
+            let state = transfers.get(transfer_id)
+                .ok_or("Transfer not found")?;
+            (state.file_path.clone(), state.chunk_size)
+        };
 
-        let mut file = File::open(&state.file_path).map_err(|e| e.to_string())?;
+        let mut file = File::open(&file_path).map_err(|e| e.to_string())?;
 
         // Seek to chunk position
-        let offset = (chunk_index as u64) * (CHUNK_SIZE as u64);
+        let offset = (chunk_index as u64) * (chunk_size as u64);
         file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
 
         // Read chunk
-        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut buffer = vec![0u8; chunk_size as usize];
         let bytes_read = file.read(&mut buffer).map_err(|e| e.to_string())?;
         buffer.truncate(bytes_read);
 
         // Calculate chunk checksum
         let checksum = self.calculate_checksum(&buffer);
 
+        // On the sending side received_chunks tracks chunks handed out
+        {
+            let mut transfers = self.transfers.write().unwrap();
+            if let Some(state) = transfers.get_mut(transfer_id) {
+                if let Some(sent) = state.received_chunks.get_mut(chunk_index as usize) {
+                    *sent = true;
+                }
+            }
+        }
+        if let Some(progress) = self.get_progress(transfer_id) {
+            self.report(progress, TransferStatus::Active);
+        }
+
         Ok(FileChunk {
             transfer_id: transfer_id.to_string(),
             chunk_index,
@@ -254,11 +339,11 @@ impl FileTransferManager {
         }
 
         // Get transfer state
-        let file_path = {
+        let (file_path, chunk_size) = {
             let transfers = self.transfers.read().unwrap();
             let state = transfers.get(&chunk.transfer_id)
                 .ok_or("Transfer not found")?;
-            state.file_path.clone()
+            (state.file_path.clone(), state.chunk_size)
         };
 
         // Write chunk to file
@@ -267,7 +352,7 @@ impl FileTransferManager {
             .open(&file_path)
             .map_err(|e| e.to_string())?;
 
-        let offset = (chunk.chunk_index as u64) * (CHUNK_SIZE as u64);
+        let offset = (chunk.chunk_index as u64) * (chunk_size as u64);
         file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
         file.write_all(&data).map_err(|e| e.to_string())?;
 
@@ -280,6 +365,9 @@ impl FileTransferManager {
                 }
             }
         }
+        if let Some(progress) = self.get_progress(&chunk.transfer_id) {
+            self.report(progress, TransferStatus::Active);
+        }
 
         Ok(ChunkAck {
             transfer_id: chunk.transfer_id.clone(),
@@ -294,7 +382,7 @@ impl FileTransferManager {
         let state = transfers.get(transfer_id)?;
 
         let chunks_completed = state.received_chunks.iter().filter(|&&c| c).count() as u32;
-        let bytes_transferred = (chunks_completed as u64) * (CHUNK_SIZE as u64);
+        let bytes_transferred = (chunks_completed as u64) * (state.chunk_size as u64);
         let percentage = (chunks_completed as f32) / (state.total_chunks as f32) * 100.0;
 
         Some(TransferProgress {
@@ -304,6 +392,8 @@ impl FileTransferManager {
             bytes_transferred: bytes_transferred.min(state.file_size),
             total_bytes: state.file_size,
             percentage,
+            is_sender: state.is_sender,
+            status: if state.is_complete { TransferStatus::Complete } else { TransferStatus::Active },
         })
     }
 
@@ -349,6 +439,10 @@ impl FileTransferManager {
                 state.is_complete = success;
             }
         }
+        if let Some(progress) = self.get_progress(transfer_id) {
+            let status = if success { TransferStatus::Complete } else { TransferStatus::Failed };
+            self.report(progress, status);
+        }
 
         Ok(TransferComplete {
             transfer_id: transfer_id.to_string(),
@@ -359,13 +453,17 @@ impl FileTransferManager {
 
     /// Cancel a transfer
     pub fn cancel_transfer(&self, transfer_id: &str) -> Result<(), String> {
-        let mut transfers = self.transfers.write().unwrap();
-        if let Some(state) = transfers.remove(transfer_id) {
+        let progress = self.get_progress(transfer_id);
+        let removed = self.transfers.write().unwrap().remove(transfer_id);
+        if let Some(state) = removed {
             // Delete incomplete file if receiving
             if !state.is_sender && !state.is_complete {
                 fs::remove_file(&state.file_path).ok();
             }
         }
+        if let Some(progress) = progress {
+            self.report(progress, TransferStatus::Cancelled);
+        }
         Ok(())
     }
 
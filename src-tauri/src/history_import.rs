@@ -0,0 +1,681 @@
+// src-tauri/src/history_import.rs
+// Import chat history from WhatsApp (.txt / .zip) and Telegram (result.json) exports
+
+use crate::commands::AppState;
+use crate::crypto::generate_checksum;
+use crate::db::{generate_id, Message};
+use crate::delivery_status::MessageStatus;
+use crate::file_server::guess_mime;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+
+// Attachments larger than this are imported as a text placeholder only
+const MAX_ATTACHMENT_BYTES: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource {
+    WhatsApp,
+    Telegram,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ParsedMessage {
+    timestamp: DateTime<Utc>,
+    sender: String,
+    text: String,
+    /// File name / relative path of an attached file inside the export
+    attachment: Option<String>,
+}
+
+/// Where attachment names in a parsed export are looked up
+enum Attachments {
+    Zip(PathBuf),
+    Dir(PathBuf),
+}
+
+struct PendingImport {
+    messages: Vec<ParsedMessage>,
+    attachments: Attachments,
+}
+
+// Parsed exports waiting for the user to confirm who is who
+static PENDING: Mutex<Option<HashMap<String, PendingImport>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportParticipant {
+    pub name: String,
+    pub message_count: usize,
+    /// Contact whose name matches, if any
+    pub suggested_peer_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportPreview {
+    pub import_id: String,
+    pub source: ImportSource,
+    pub chat_name: Option<String>,
+    pub participants: Vec<ImportParticipant>,
+    /// Participant that is most likely the local user
+    pub suggested_self: Option<String>,
+    pub message_count: usize,
+    pub first_at: Option<String>,
+    pub last_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    pub imported: usize,
+    /// Already present from an earlier import of the same export
+    pub duplicates: usize,
+    pub attachments: usize,
+}
+
+fn local_to_utc(naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+// ============ WHATSAPP ============
+
+struct WaEntry {
+    /// Date fields in file order; whether they are day/month or month/day is decided later
+    date: (u32, u32, i32),
+    time: (u32, u32, u32),
+    /// (sender, text); None for system lines ("Messages are end-to-end encrypted", ...)
+    message: Option<(String, String)>,
+}
+
+fn parse_wa_date(s: &str) -> Option<(u32, u32, i32)> {
+    let parts: Vec<&str> = s.split(['/', '.', '-']).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let year: i32 = parts[2].parse().ok()?;
+    Some((
+        parts[0].parse().ok()?,
+        parts[1].parse().ok()?,
+        if year < 100 { year + 2000 } else { year },
+    ))
+}
+
+/// "21:15", "21:15:42", "9:15 PM", "9:15\u{202f}p.m."
+fn parse_wa_time(s: &str) -> Option<(u32, u32, u32)> {
+    let lower = s.to_lowercase();
+    let (clock, pm) = if let Some(c) = lower.strip_suffix("pm").or(lower.strip_suffix("p.m.")) {
+        (c.trim(), Some(true))
+    } else if let Some(c) = lower.strip_suffix("am").or(lower.strip_suffix("a.m.")) {
+        (c.trim(), Some(false))
+    } else {
+        (lower.trim(), None)
+    };
+    let parts: Vec<u32> = clock
+        .split([':', '.'])
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let (mut h, m, sec) = (parts[0], parts[1], parts.get(2).copied().unwrap_or(0));
+    match pm {
+        Some(true) if h < 12 => h += 12,
+        Some(false) if h == 12 => h = 0,
+        _ => {}
+    }
+    if h > 23 || m > 59 || sec > 59 {
+        return None;
+    }
+    Some((h, m, sec))
+}
+
+/// Android: "31/12/2020, 21:15 - Name: text"; iOS: "[31/12/2020, 21:15:42] Name: text"
+fn parse_wa_header(line: &str) -> Option<WaEntry> {
+    let line = line
+        .trim_start_matches(['\u{200e}', '\u{feff}'])
+        .trim_start();
+    let (head, rest) = if let Some(stripped) = line.strip_prefix('[') {
+        let end = stripped.find(']')?;
+        (&stripped[..end], stripped[end + 1..].trim_start())
+    } else {
+        let sep = line.find(" - ")?;
+        (&line[..sep], &line[sep + 3..])
+    };
+    let (date, time) = head.split_once(',')?;
+    Some(WaEntry {
+        date: parse_wa_date(date.trim())?,
+        time: parse_wa_time(time.trim())?,
+        message: rest
+            .split_once(": ")
+            .map(|(sender, text)| (sender.trim().to_string(), text.to_string())),
+    })
+}
+
+/// "<attached: 00000012-PHOTO.jpg>" (iOS) or "IMG-20201231-WA0001.jpg (file attached)" (Android)
+fn wa_attachment(text: &str) -> Option<String> {
+    let first = text.lines().next()?.trim_start_matches('\u{200e}').trim();
+    if let Some(name) = first
+        .strip_prefix("<attached: ")
+        .and_then(|r| r.strip_suffix('>'))
+    {
+        return Some(name.trim().to_string());
+    }
+    first
+        .strip_suffix(" (file attached)")
+        .map(|name| name.trim().to_string())
+}
+
+fn parse_whatsapp(text: &str) -> Vec<ParsedMessage> {
+    let mut entries: Vec<WaEntry> = Vec::new();
+    for line in text.lines() {
+        if let Some(entry) = parse_wa_header(line) {
+            entries.push(entry);
+        } else if let Some(WaEntry {
+            message: Some((_, body)),
+            ..
+        }) = entries.last_mut()
+        {
+            // Multi-line message
+            body.push('\n');
+            body.push_str(line);
+        }
+    }
+
+    // Exports use the phone's locale; a first field over 12 means day-first, a second
+    // field over 12 means month-first. Ambiguous files default to day-first.
+    let day_first = entries.iter().any(|e| e.date.0 > 12) || !entries.iter().any(|e| e.date.1 > 12);
+
+    entries
+        .into_iter()
+        .filter_map(|e| {
+            let (sender, text) = e.message?;
+            let (a, b, year) = e.date;
+            let (day, month) = if day_first { (a, b) } else { (b, a) };
+            let naive = NaiveDate::from_ymd_opt(year, month, day)?
+                .and_hms_opt(e.time.0, e.time.1, e.time.2)?;
+            Some(ParsedMessage {
+                timestamp: local_to_utc(naive)?,
+                attachment: wa_attachment(&text),
+                sender,
+                text,
+            })
+        })
+        .collect()
+}
+
+/// "WhatsApp Chat with Bob" (Android) / "WhatsApp Chat - Bob" (iOS) -> "Bob"
+fn wa_chat_name(stem: &str) -> Option<String> {
+    stem.strip_prefix("WhatsApp Chat with ")
+        .or_else(|| stem.strip_prefix("WhatsApp Chat - "))
+        .map(|s| s.trim().to_string())
+}
+
+// ============ TELEGRAM ============
+
+/// Telegram stores formatted text as a mix of strings and {type, text} entities
+fn telegram_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .map(|p| match p {
+                serde_json::Value::String(s) => s.clone(),
+                other => other
+                    .get("text")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+            .collect(),
+        _ => String::new(),
+    }
+}
+
+fn parse_telegram(json: &str) -> Result<(Option<String>, Vec<ParsedMessage>), String> {
+    let root: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Not a Telegram export: {}", e))?;
+    if root.get("chats").is_some() {
+        return Err("This is a full account export; export a single chat instead".to_string());
+    }
+    let list = root
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .ok_or("Not a Telegram chat export")?;
+    let chat_name = root.get("name").and_then(|n| n.as_str()).map(String::from);
+
+    let mut messages = Vec::new();
+    for m in list {
+        if m.get("type").and_then(|t| t.as_str()) != Some("message") {
+            continue; // service messages: calls, pins, joins...
+        }
+        let timestamp = m
+            .get("date_unixtime")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse::<i64>().ok())
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .or_else(|| {
+                m.get("date")
+                    .and_then(|d| d.as_str())
+                    .and_then(|d| NaiveDateTime::parse_from_str(d, "%Y-%m-%dT%H:%M:%S").ok())
+                    .and_then(local_to_utc)
+            });
+        let Some(timestamp) = timestamp else { continue };
+        let sender = m
+            .get("from")
+            .and_then(|f| f.as_str())
+            .unwrap_or("Deleted Account")
+            .to_string();
+        let text = m.get("text").map(telegram_text).unwrap_or_default();
+        let attachment = ["photo", "file"]
+            .iter()
+            .filter_map(|k| m.get(*k)?.as_str())
+            .find(|p| !p.starts_with('('))
+            .map(String::from);
+        if text.is_empty() && attachment.is_none() {
+            continue;
+        }
+        messages.push(ParsedMessage {
+            timestamp,
+            sender,
+            text,
+            attachment,
+        });
+    }
+    Ok((chat_name, messages))
+}
+
+// ============ LOADING ============
+
+/// The chat transcript inside a WhatsApp zip ("_chat.txt" on iOS, "WhatsApp Chat with X.txt" on Android)
+fn read_zip_transcript(path: &Path) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(File::open(path).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Not a valid zip file: {}", e))?;
+    let name = archive
+        .file_names()
+        .filter(|n| n.to_lowercase().ends_with(".txt"))
+        .min_by_key(|n| !(n.ends_with("_chat.txt") || n.starts_with("WhatsApp Chat")))
+        .map(String::from)
+        .ok_or("No chat transcript (.txt) in the zip")?;
+    let mut text = String::new();
+    archive
+        .by_name(&name)
+        .map_err(|e| e.to_string())?
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read transcript: {}", e))?;
+    Ok(text)
+}
+
+type LoadedExport = (
+    ImportSource,
+    Option<String>,
+    Vec<ParsedMessage>,
+    Attachments,
+);
+
+fn load_export(path: &Path) -> Result<LoadedExport, String> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    match ext.as_str() {
+        "zip" => {
+            let text = read_zip_transcript(path)?;
+            Ok((
+                ImportSource::WhatsApp,
+                wa_chat_name(&stem),
+                parse_whatsapp(&text),
+                Attachments::Zip(path.to_path_buf()),
+            ))
+        }
+        "txt" => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read export: {}", e))?;
+            Ok((
+                ImportSource::WhatsApp,
+                wa_chat_name(&stem),
+                parse_whatsapp(&text),
+                Attachments::Dir(dir),
+            ))
+        }
+        "json" => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read export: {}", e))?;
+            let (chat_name, messages) = parse_telegram(&text)?;
+            Ok((
+                ImportSource::Telegram,
+                chat_name,
+                messages,
+                Attachments::Dir(dir),
+            ))
+        }
+        _ => {
+            Err("Unsupported export (use a WhatsApp .txt/.zip or Telegram result.json)".to_string())
+        }
+    }
+}
+
+fn read_attachment(
+    attachments: &Attachments,
+    zip: &mut Option<zip::ZipArchive<File>>,
+    name: &str,
+) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match attachments {
+        Attachments::Zip(_) => {
+            let file = zip.as_mut()?.by_name(name).ok()?;
+            if file.size() > MAX_ATTACHMENT_BYTES {
+                return None;
+            }
+            file.take(MAX_ATTACHMENT_BYTES)
+                .read_to_end(&mut bytes)
+                .ok()?;
+        }
+        Attachments::Dir(dir) => {
+            let rel = Path::new(name);
+            if rel.components().any(|c| !matches!(c, Component::Normal(_))) {
+                return None;
+            }
+            let path = dir.join(rel);
+            if std::fs::metadata(&path).ok()?.len() > MAX_ATTACHMENT_BYTES {
+                return None;
+            }
+            bytes = std::fs::read(path).ok()?;
+        }
+    }
+    Some(bytes)
+}
+
+/// Stable id so importing the same export twice doesn't duplicate messages
+fn import_message_id(peer_id: &str, index: usize, m: &ParsedMessage) -> String {
+    let key = format!(
+        "{}|{}|{}|{}|{}",
+        peer_id,
+        index,
+        m.timestamp.timestamp(),
+        m.sender,
+        m.text
+    );
+    format!("import-{}", &generate_checksum(key.as_bytes())[..32])
+}
+
+// ============ COMMANDS ============
+
+/// Parse an export and describe it so the user can confirm which participant is them and
+/// which contact the chat belongs to. Nothing is written until commit_import.
+#[tauri::command]
+pub fn preview_import(state: State<AppState>, file_path: String) -> Result<ImportPreview, String> {
+    let (source, chat_name, messages, attachments) = load_export(Path::new(&file_path))?;
+    if messages.is_empty() {
+        return Err("No messages found in this export".to_string());
+    }
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for m in &messages {
+        *counts.entry(m.sender.clone()).or_default() += 1;
+    }
+    let local_id = state.device_id();
+    let users = state.db.get_all_users().map_err(|e| e.to_string())?;
+    let local_name = users
+        .iter()
+        .find(|u| u.id == local_id)
+        .map(|u| u.username.clone());
+
+    let mut participants: Vec<ImportParticipant> = counts
+        .into_iter()
+        .map(|(name, message_count)| ImportParticipant {
+            suggested_peer_id: users
+                .iter()
+                .find(|u| u.id != local_id && u.username.trim().eq_ignore_ascii_case(name.trim()))
+                .map(|u| u.id.clone()),
+            name,
+            message_count,
+        })
+        .collect();
+    participants.sort_by_key(|p| std::cmp::Reverse(p.message_count));
+
+    let suggested_self = participants
+        .iter()
+        .find(|p| {
+            local_name
+                .as_deref()
+                .is_some_and(|n| n.eq_ignore_ascii_case(&p.name))
+        })
+        .or_else(|| match (&chat_name, participants.as_slice()) {
+            // One-to-one export named after the other person: we're the other participant
+            (Some(chat), [a, b]) if a.name == *chat => Some(b),
+            (Some(chat), [a, b]) if b.name == *chat => Some(a),
+            _ => None,
+        })
+        .map(|p| p.name.clone());
+
+    let preview = ImportPreview {
+        import_id: generate_id(),
+        source,
+        chat_name,
+        participants,
+        suggested_self,
+        message_count: messages.len(),
+        first_at: messages
+            .iter()
+            .map(|m| m.timestamp)
+            .min()
+            .map(|t| t.to_rfc3339()),
+        last_at: messages
+            .iter()
+            .map(|m| m.timestamp)
+            .max()
+            .map(|t| t.to_rfc3339()),
+    };
+    PENDING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(
+            preview.import_id.clone(),
+            PendingImport {
+                messages,
+                attachments,
+            },
+        );
+    Ok(preview)
+}
+
+/// Write a previewed export into the direct chat with `peer_id`. Messages from `self_name`
+/// become outgoing; everyone else's become incoming from the peer.
+#[tauri::command]
+pub fn commit_import(
+    state: State<AppState>,
+    import_id: String,
+    self_name: String,
+    peer_id: String,
+) -> Result<ImportResult, String> {
+    let pending = PENDING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .remove(&import_id)
+        .ok_or("Import not found or already completed")?;
+
+    let local_id = state.device_id();
+    if peer_id == local_id
+        || state
+            .db
+            .get_user(&peer_id)
+            .map_err(|e| e.to_string())?
+            .is_none()
+    {
+        return Err("Choose a contact to import this chat into".to_string());
+    }
+    let others: std::collections::HashSet<&str> = pending
+        .messages
+        .iter()
+        .map(|m| m.sender.as_str())
+        .filter(|s| *s != self_name)
+        .collect();
+    if others.len() > 1 {
+        return Err("Group chat exports can't be imported into a direct chat".to_string());
+    }
+
+    let mut zip = match &pending.attachments {
+        Attachments::Zip(path) => File::open(path)
+            .ok()
+            .and_then(|f| zip::ZipArchive::new(f).ok()),
+        Attachments::Dir(_) => None,
+    };
+    let port = state.file_server.get_port();
+    let mut attachments = 0;
+    let mut messages = Vec::with_capacity(pending.messages.len());
+
+    for (index, m) in pending.messages.iter().enumerate() {
+        let id = import_message_id(&peer_id, index, m);
+        let (sender_id, receiver_id) = if m.sender == self_name {
+            (local_id.clone(), peer_id.clone())
+        } else {
+            (peer_id.clone(), local_id.clone())
+        };
+
+        let mut content = m.text.clone();
+        let mut message_type = "text".to_string();
+        if let Some(name) = &m.attachment {
+            let file_name = Path::new(name)
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_else(|| name.clone());
+            if let Some(bytes) = read_attachment(&pending.attachments, &mut zip, name) {
+                let mime = guess_mime(&file_name);
+                let file_id = format!("imp_{}", &id["import-".len()..]);
+                state
+                    .file_server
+                    .store_bytes(&file_id, &bytes, &file_name, &mime)?;
+                message_type = if mime.starts_with("image/") {
+                    "image"
+                } else if mime.starts_with("video/") {
+                    "video"
+                } else {
+                    "file"
+                }
+                .to_string();
+                content = serde_json::json!({
+                    "fileId": file_id, "fileName": file_name, "port": port, "type": message_type,
+                })
+                .to_string();
+                attachments += 1;
+            } else if m.text.trim().is_empty() {
+                content = format!("[{}]", file_name);
+            }
+        }
+
+        messages.push(Message {
+            id,
+            sender_id,
+            receiver_id,
+            content,
+            message_type,
+            file_path: None,
+            is_read: true,
+            is_delivered: true,
+            created_at: m.timestamp.to_rfc3339(),
+            lamport: 0,
+            status: MessageStatus::Read,
+        });
+    }
+
+    let imported = state
+        .db
+        .import_messages(&messages)
+        .map_err(|e| format!("Failed to import messages: {}", e))?;
+    Ok(ImportResult {
+        imported,
+        duplicates: messages.len() - imported,
+        attachments,
+    })
+}
+
+/// Discard a previewed import
+#[tauri::command]
+pub fn cancel_import(import_id: String) {
+    if let Some(pending) = PENDING.lock().unwrap().as_mut() {
+        pending.remove(&import_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_whatsapp_android_and_ios() {
+        let android = "12/31/20, 9:15 PM - Messages are end-to-end encrypted.\n\
+                       12/31/20, 9:15 PM - Bob: Happy new year\n\
+                       see you tomorrow\n\
+                       1/1/21, 10:02 AM - Alice: IMG-20210101-WA0001.jpg (file attached)\n";
+        let msgs = parse_whatsapp(android);
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].sender, "Bob");
+        assert_eq!(msgs[0].text, "Happy new year\nsee you tomorrow");
+        assert_eq!(
+            msgs[1].attachment.as_deref(),
+            Some("IMG-20210101-WA0001.jpg")
+        );
+        // Second field > 12 -> month-first
+        let local = msgs[0].timestamp.with_timezone(&Local);
+        assert_eq!(
+            local.format("%Y-%m-%d %H:%M").to_string(),
+            "2020-12-31 21:15"
+        );
+
+        let ios = "[31.12.2020, 21:15:42] Bob: Hi\n\
+                   [01.01.2021, 08:00:00] Alice: \u{200e}<attached: 00000012-PHOTO.jpg>\n";
+        let msgs = parse_whatsapp(ios);
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[1].attachment.as_deref(), Some("00000012-PHOTO.jpg"));
+        let local = msgs[0].timestamp.with_timezone(&Local);
+        assert_eq!(
+            local.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2020-12-31 21:15:42"
+        );
+    }
+
+    #[test]
+    fn test_parse_telegram() {
+        let json = r#"{"name":"Bob","type":"personal_chat","messages":[
+            {"id":1,"type":"service","date":"2021-01-01T10:00:00","action":"phone_call"},
+            {"id":2,"type":"message","date":"2021-01-01T10:00:00","date_unixtime":"1609495200",
+             "from":"Bob","text":["Hello ",{"type":"bold","text":"there"}]},
+            {"id":3,"type":"message","date":"2021-01-01T10:01:00","date_unixtime":"1609495260",
+             "from":"Alice","text":"","photo":"photos/photo_1.jpg"}
+        ]}"#;
+        let (name, msgs) = parse_telegram(json).unwrap();
+        assert_eq!(name.as_deref(), Some("Bob"));
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].text, "Hello there");
+        assert_eq!(msgs[0].timestamp.timestamp(), 1609495200);
+        assert_eq!(msgs[1].attachment.as_deref(), Some("photos/photo_1.jpg"));
+        assert!(parse_telegram(r#"{"chats":{"list":[]}}"#).is_err());
+    }
+
+    #[test]
+    fn test_wa_time_and_chat_name() {
+        assert_eq!(parse_wa_time("12:05 AM"), Some((0, 5, 0)));
+        assert_eq!(parse_wa_time("9:15\u{202f}p.m."), Some((21, 15, 0)));
+        assert_eq!(parse_wa_time("25:00"), None);
+        assert_eq!(
+            wa_chat_name("WhatsApp Chat with Bob").as_deref(),
+            Some("Bob")
+        );
+        assert_eq!(wa_chat_name("WhatsApp Chat - Bob").as_deref(), Some("Bob"));
+        assert_eq!(wa_chat_name("notes"), None);
+    }
+}
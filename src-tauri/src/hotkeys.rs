@@ -0,0 +1,232 @@
+// src-tauri/src/hotkeys.rs
+// Global keyboard shortcuts (show/hide window, quick reply)
+
+use crate::commands::AppState;
+use crate::db::Database;
+use crate::window_manager;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+use tracing::warn;
+
+/// Actions that can be bound to a global shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    ToggleWindow,
+    QuickReply,
+}
+
+impl HotkeyAction {
+    const ALL: [HotkeyAction; 2] = [HotkeyAction::ToggleWindow, HotkeyAction::QuickReply];
+
+    fn name(self) -> &'static str {
+        match self {
+            HotkeyAction::ToggleWindow => "toggle_window",
+            HotkeyAction::QuickReply => "quick_reply",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    fn setting_key(self) -> String {
+        format!("hotkey_{}", self.name())
+    }
+
+    fn default_accelerator(self) -> &'static str {
+        match self {
+            HotkeyAction::ToggleWindow => "CommandOrControl+Shift+P",
+            HotkeyAction::QuickReply => "CommandOrControl+Shift+N",
+        }
+    }
+}
+
+/// A binding as reported to the settings UI
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyBinding {
+    pub action: String,
+    /// Empty when the shortcut is disabled
+    pub accelerator: String,
+    pub registered: bool,
+    pub error: Option<String>,
+}
+
+// Shortcut id -> action for the currently registered shortcuts
+static REGISTERED: Mutex<Option<HashMap<u32, HotkeyAction>>> = Mutex::new(None);
+
+/// Plugin handler: dispatch a pressed shortcut to its action
+pub fn handle_shortcut<R: Runtime>(app: &AppHandle<R>, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state != ShortcutState::Pressed {
+        return;
+    }
+    let action = REGISTERED
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|m| m.get(&shortcut.id()).copied());
+    match action {
+        Some(HotkeyAction::ToggleWindow) => toggle_main_window(app),
+        Some(HotkeyAction::QuickReply) => {
+            let opened = window_manager::last_incoming_peer()
+                .map(|peer_id| window_manager::open_quick_reply(app, &peer_id).is_ok())
+                .unwrap_or(false);
+            if !opened {
+                // Nobody to reply to yet — bring up the app on the new-message composer
+                show_main_window(app);
+                let _ = app.emit("compose-new-message", ());
+            }
+        }
+        None => {}
+    }
+}
+
+fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn toggle_main_window<R: Runtime>(app: &AppHandle<R>) {
+    if window_manager::is_main_window_focused(app) {
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.hide();
+        }
+    } else {
+        show_main_window(app);
+    }
+}
+
+fn accelerator_for(db: &Database, action: HotkeyAction) -> String {
+    db.get_setting(&action.setting_key())
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| action.default_accelerator().to_string())
+}
+
+/// (Re-)register every configured shortcut. Failures are reported per binding instead of
+/// aborting, so one shortcut taken by another application doesn't disable the rest.
+pub fn register_from_settings<R: Runtime>(app: &AppHandle<R>, db: &Database) -> Vec<HotkeyBinding> {
+    let manager = app.global_shortcut();
+    let _ = manager.unregister_all();
+
+    let mut registered = HashMap::new();
+    let mut bindings = Vec::new();
+    for action in HotkeyAction::ALL {
+        let accelerator = accelerator_for(db, action);
+        let mut binding = HotkeyBinding {
+            action: action.name().to_string(),
+            accelerator: accelerator.clone(),
+            registered: false,
+            error: None,
+        };
+        if accelerator.is_empty() {
+            bindings.push(binding);
+            continue;
+        }
+        match Shortcut::from_str(&accelerator) {
+            Ok(shortcut) => {
+                if let Some(other) = registered.get(&shortcut.id()) {
+                    binding.error = Some(format!("Already used by {}", HotkeyAction::name(*other)));
+                } else {
+                    match manager.register(shortcut) {
+                        Ok(()) => {
+                            registered.insert(shortcut.id(), action);
+                            binding.registered = true;
+                        }
+                        // Usually means another application owns this combination
+                        Err(e) => binding.error = Some(format!("Could not register: {}", e)),
+                    }
+                }
+            }
+            Err(e) => binding.error = Some(format!("Invalid shortcut: {}", e)),
+        }
+        if let Some(err) = &binding.error {
+            warn!("Hotkey {} ({}): {}", action.name(), accelerator, err);
+        }
+        bindings.push(binding);
+    }
+
+    *REGISTERED.lock().unwrap() = Some(registered);
+    bindings
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_hotkeys<R: Runtime>(app: AppHandle<R>, state: State<AppState>) -> Vec<HotkeyBinding> {
+    let manager = app.global_shortcut();
+    HotkeyAction::ALL
+        .into_iter()
+        .map(|action| {
+            let accelerator = accelerator_for(&state.db, action);
+            let registered = !accelerator.is_empty()
+                && Shortcut::from_str(&accelerator)
+                    .map(|s| manager.is_registered(s))
+                    .unwrap_or(false);
+            HotkeyBinding {
+                action: action.name().to_string(),
+                accelerator,
+                registered,
+                error: None,
+            }
+        })
+        .collect()
+}
+
+/// Change the shortcut for an action (empty accelerator disables it).
+/// Rejects invalid accelerators and combinations already bound to another Pingo action,
+/// then re-registers everything and returns the resulting bindings.
+#[tauri::command]
+pub fn set_hotkey<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    action: String,
+    accelerator: String,
+) -> Result<Vec<HotkeyBinding>, String> {
+    let action =
+        HotkeyAction::from_name(&action).ok_or_else(|| format!("Unknown action: {}", action))?;
+    let accelerator = accelerator.trim().to_string();
+
+    if !accelerator.is_empty() {
+        let shortcut = Shortcut::from_str(&accelerator)
+            .map_err(|e| format!("Invalid shortcut '{}': {}", accelerator, e))?;
+        for other in HotkeyAction::ALL.into_iter().filter(|a| *a != action) {
+            let other_acc = accelerator_for(&state.db, other);
+            if let Ok(other_shortcut) = Shortcut::from_str(&other_acc) {
+                if other_shortcut.id() == shortcut.id() {
+                    return Err(format!(
+                        "'{}' is already used by {}",
+                        accelerator,
+                        other.name()
+                    ));
+                }
+            }
+        }
+    }
+
+    state
+        .db
+        .set_setting(&action.setting_key(), &accelerator)
+        .map_err(|e| e.to_string())?;
+    Ok(register_from_settings(&app, &state.db))
+}
+
+/// Restore the default shortcuts for all actions
+#[tauri::command]
+pub fn reset_hotkeys<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+) -> Result<Vec<HotkeyBinding>, String> {
+    for action in HotkeyAction::ALL {
+        state
+            .db
+            .set_setting(&action.setting_key(), action.default_accelerator())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(register_from_settings(&app, &state.db))
+}
@@ -0,0 +1,132 @@
+// src-tauri/src/http_client.rs
+// Shared client for outbound HTTP with the user's proxy and extra CA certificates applied.
+// LAN peers are always reached directly.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use reqwest::blocking::{Client, ClientBuilder};
+use reqwest::{Certificate, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use tauri::State;
+use tracing::{info, warn};
+
+// Peer file servers and avatars live on the LAN; sending them through a corporate proxy breaks them
+const LAN_NO_PROXY: &str =
+    "localhost,127.0.0.1,::1,10.0.0.0/8,172.16.0.0/12,192.168.0.0/16,169.254.0.0/16";
+
+static CLIENT: RwLock<Option<Client>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpSettings {
+    /// http://, https://, socks5:// or socks5h:// URL; credentials may be embedded
+    pub proxy_url: Option<String>,
+    /// Extra comma-separated hosts or CIDRs that bypass the proxy
+    pub no_proxy: Option<String>,
+    /// PEM file with one or more CA certificates to trust (e.g. for TLS interception)
+    pub ca_cert_path: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn load(db: &Database) -> HttpSettings {
+    let get = |key: &str| non_empty(db.get_setting(key).ok().flatten());
+    HttpSettings {
+        proxy_url: get("http_proxy"),
+        no_proxy: get("http_no_proxy"),
+        ca_cert_path: get("http_ca_cert_path"),
+    }
+}
+
+fn builder_for(settings: &HttpSettings) -> Result<ClientBuilder, String> {
+    let mut builder = Client::builder();
+    let no_proxy = match &settings.no_proxy {
+        Some(extra) => format!("{},{}", LAN_NO_PROXY, extra),
+        None => LAN_NO_PROXY.to_string(),
+    };
+    if let Some(url) = &settings.proxy_url {
+        let proxy = Proxy::all(url)
+            .map_err(|e| format!("Invalid proxy URL: {}", e))?
+            .no_proxy(NoProxy::from_string(&no_proxy));
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &settings.ca_cert_path {
+        let pem = std::fs::read(path).map_err(|e| format!("Read CA certificate: {}", e))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA certificate: {}", e))?;
+        if certs.is_empty() {
+            return Err("No certificates found in CA file".to_string());
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder)
+}
+
+fn build(settings: &HttpSettings) -> Result<Client, String> {
+    builder_for(settings)?.build().map_err(|e| e.to_string())
+}
+
+/// A builder with the user's proxy and certificates applied, for requests that need options of
+/// their own (pinned addresses, no redirects)
+pub fn builder(db: &Database) -> Result<ClientBuilder, String> {
+    builder_for(&load(db))
+}
+
+/// Rebuild the shared client from settings; falls back to a direct client if they're broken
+pub fn configure(db: &Database) {
+    let settings = load(db);
+    let client = build(&settings).unwrap_or_else(|e| {
+        warn!("HTTP settings not applied: {}", e);
+        Client::new()
+    });
+    if settings.proxy_url.is_some() {
+        info!("Outbound HTTP uses a proxy");
+    }
+    *CLIENT.write().unwrap() = Some(client);
+}
+
+/// The shared client (cheap to clone). Blocking: don't call from async code.
+pub fn client() -> Client {
+    if let Some(client) = CLIENT.read().unwrap().as_ref() {
+        return client.clone();
+    }
+    let client = Client::new();
+    *CLIENT.write().unwrap() = Some(client.clone());
+    client
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_http_settings(state: State<AppState>) -> HttpSettings {
+    load(&state.db)
+}
+
+/// Validate and save proxy/CA settings, then apply them to all outbound HTTP
+#[tauri::command]
+pub fn set_http_settings(state: State<AppState>, settings: HttpSettings) -> Result<(), String> {
+    let settings = HttpSettings {
+        proxy_url: non_empty(settings.proxy_url),
+        no_proxy: non_empty(settings.no_proxy),
+        ca_cert_path: non_empty(settings.ca_cert_path),
+    };
+    build(&settings)?;
+    for (key, value) in [
+        ("http_proxy", &settings.proxy_url),
+        ("http_no_proxy", &settings.no_proxy),
+        ("http_ca_cert_path", &settings.ca_cert_path),
+    ] {
+        state
+            .db
+            .set_setting(key, value.as_deref().unwrap_or(""))
+            .map_err(|e| e.to_string())?;
+    }
+    configure(&state.db);
+    Ok(())
+}
@@ -0,0 +1,257 @@
+// src-tauri/src/ice_servers.rs
+// STUN/TURN servers for meetings. Host candidates are enough on a flat LAN, but some office
+// networks isolate clients or NAT between subnets; a STUN or TURN server gets through those.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+use tracing::warn;
+use webrtc::api::APIBuilder;
+use webrtc::ice_transport::ice_candidate::RTCIceCandidate;
+use webrtc::ice_transport::ice_candidate_type::RTCIceCandidateType;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::policy::ice_transport_policy::RTCIceTransportPolicy;
+
+const SETTING_KEY: &str = "meeting_ice_servers";
+const GATHER_TIMEOUT: Duration = Duration::from_secs(8);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct IceServer {
+    /// stun:, stuns:, turn: or turns: URLs, e.g. "turn:turn.example.com:3478?transport=tcp"
+    pub urls: Vec<String>,
+    /// Required for TURN
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub credential: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IceServerTest {
+    pub url: String,
+    pub ok: bool,
+    pub detail: String,
+    pub latency_ms: Option<u64>,
+}
+
+fn is_turn(url: &str) -> bool {
+    url.starts_with("turn:") || url.starts_with("turns:")
+}
+
+fn to_rtc(server: &IceServer) -> RTCIceServer {
+    RTCIceServer {
+        urls: server.urls.clone(),
+        username: server.username.clone().unwrap_or_default(),
+        credential: server.credential.clone().unwrap_or_default(),
+    }
+}
+
+/// Trim and check servers; empty entries are dropped
+fn normalize(servers: Vec<IceServer>) -> Result<Vec<IceServer>, String> {
+    let mut out = Vec::new();
+    for server in servers {
+        let urls: Vec<String> = server
+            .urls
+            .iter()
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .collect();
+        if urls.is_empty() {
+            continue;
+        }
+        for url in &urls {
+            if !["stun:", "stuns:", "turn:", "turns:"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme))
+            {
+                return Err(format!("Not a STUN/TURN URL: {}", url));
+            }
+        }
+        let username = server.username.filter(|u| !u.is_empty());
+        let credential = server.credential.filter(|c| !c.is_empty());
+        if urls.iter().any(|u| is_turn(u)) && (username.is_none() || credential.is_none()) {
+            return Err(format!(
+                "TURN server {} needs a username and credential",
+                urls[0]
+            ));
+        }
+        out.push(IceServer {
+            urls,
+            username,
+            credential,
+        });
+    }
+    Ok(out)
+}
+
+pub fn load(db: &Database) -> Vec<IceServer> {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Peer connection configuration for meetings
+pub fn rtc_configuration(db: &Database) -> RTCConfiguration {
+    RTCConfiguration {
+        ice_servers: load(db).iter().map(to_rtc).collect(),
+        ..Default::default()
+    }
+}
+
+/// Gather candidates through a single URL and report whether it produced the candidate type
+/// it should: server-reflexive for STUN, relay for TURN
+async fn test_url(server: &IceServer, url: &str) -> IceServerTest {
+    let turn = is_turn(url);
+    let started = Instant::now();
+    let result: Result<bool, String> = async {
+        let config = RTCConfiguration {
+            ice_servers: vec![to_rtc(&IceServer {
+                urls: vec![url.to_string()],
+                ..server.clone()
+            })],
+            ice_transport_policy: if turn {
+                RTCIceTransportPolicy::Relay
+            } else {
+                RTCIceTransportPolicy::All
+            },
+            ..Default::default()
+        };
+        let pc = APIBuilder::new()
+            .build()
+            .new_peer_connection(config)
+            .await
+            .map_err(|e| e.to_string())?;
+        let wanted = if turn {
+            RTCIceCandidateType::Relay
+        } else {
+            RTCIceCandidateType::Srflx
+        };
+        let found = Arc::new(Mutex::new(false));
+        let found_in_handler = Arc::clone(&found);
+        pc.on_ice_candidate(Box::new(move |candidate: Option<RTCIceCandidate>| {
+            if candidate.is_some_and(|c| c.typ == wanted) {
+                *found_in_handler.lock().unwrap() = true;
+            }
+            Box::pin(async {})
+        }));
+        // Gathering only starts once there's something to negotiate
+        pc.create_data_channel("ice-test", None)
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut gathered = pc.gathering_complete_promise().await;
+        let offer = pc.create_offer(None).await.map_err(|e| e.to_string())?;
+        pc.set_local_description(offer)
+            .await
+            .map_err(|e| e.to_string())?;
+        let _ = tokio::time::timeout(GATHER_TIMEOUT, gathered.recv()).await;
+        let _ = pc.close().await;
+        let ok = *found.lock().unwrap();
+        Ok(ok)
+    }
+    .await;
+
+    let latency_ms = Some(started.elapsed().as_millis() as u64);
+    match result {
+        Ok(true) => IceServerTest {
+            url: url.to_string(),
+            ok: true,
+            detail: if turn {
+                "Relay allocated".to_string()
+            } else {
+                "Public address discovered".to_string()
+            },
+            latency_ms,
+        },
+        Ok(false) => IceServerTest {
+            url: url.to_string(),
+            ok: false,
+            detail: if turn {
+                "No relay candidate (unreachable or bad credentials)".to_string()
+            } else {
+                "No response from server".to_string()
+            },
+            latency_ms: None,
+        },
+        Err(e) => IceServerTest {
+            url: url.to_string(),
+            ok: false,
+            detail: e,
+            latency_ms: None,
+        },
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_ice_servers(state: State<AppState>) -> Vec<IceServer> {
+    load(&state.db)
+}
+
+/// Validate and save STUN/TURN servers; used by meetings started afterwards
+#[tauri::command]
+pub fn set_ice_servers(state: State<AppState>, servers: Vec<IceServer>) -> Result<(), String> {
+    let servers = normalize(servers)?;
+    let json = serde_json::to_string(&servers).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())
+}
+
+/// Check each URL of the given servers (or the saved ones) by gathering candidates through it
+#[tauri::command]
+pub async fn test_ice_servers(
+    state: State<'_, AppState>,
+    servers: Option<Vec<IceServer>>,
+) -> Result<Vec<IceServerTest>, String> {
+    let servers = match servers {
+        Some(servers) => normalize(servers)?,
+        None => load(&state.db),
+    };
+    let mut results = Vec::new();
+    for server in &servers {
+        for url in &server.urls {
+            let result = test_url(server, url).await;
+            if !result.ok {
+                warn!("ICE server {} failed: {}", url, result.detail);
+            }
+            results.push(result);
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_validates_urls_and_turn_credentials() {
+        let stun = IceServer {
+            urls: vec![" stun:stun.example.com:3478 ".to_string(), "".to_string()],
+            ..Default::default()
+        };
+        let normalized = normalize(vec![stun, IceServer::default()]).unwrap();
+        assert_eq!(normalized.len(), 1);
+        assert_eq!(normalized[0].urls, vec!["stun:stun.example.com:3478"]);
+
+        let turn = IceServer {
+            urls: vec!["turn:turn.example.com".to_string()],
+            username: Some("user".to_string()),
+            credential: Some(String::new()),
+        };
+        assert!(normalize(vec![turn]).is_err());
+        let http = IceServer {
+            urls: vec!["https://example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(normalize(vec![http]).is_err());
+    }
+}
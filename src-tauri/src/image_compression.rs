@@ -0,0 +1,347 @@
+// src-tauri/src/image_compression.rs
+// Outbound image compression: with "balanced" or "data_saver" selected, large JPEG and PNG
+// images are scaled down and re-encoded before they're shared. The original isn't lost: it's
+// served under its own file id (recorded in image_variants) and the file message names it, so
+// the receiver can still "download original". "original" sends images untouched.
+
+use crate::commands::AppState;
+use crate::db::{now, Database, ImageVariant};
+use crate::image_metadata;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "image_compression";
+/// Larger inputs aren't decoded at all
+const MAX_INPUT_BYTES: usize = 60 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    #[default]
+    Original,
+    Balanced,
+    DataSaver,
+}
+
+struct Preset {
+    /// Longest side after scaling
+    max_side: u32,
+    jpeg_quality: u8,
+    /// JPEGs smaller than this are sent as they are
+    min_bytes: usize,
+}
+
+impl Mode {
+    fn preset(self) -> Option<Preset> {
+        match self {
+            Mode::Original => None,
+            Mode::Balanced => Some(Preset {
+                max_side: 2560,
+                jpeg_quality: 85,
+                min_bytes: 1536 * 1024,
+            }),
+            Mode::DataSaver => Some(Preset {
+                max_side: 1600,
+                jpeg_quality: 70,
+                min_bytes: 300 * 1024,
+            }),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::Original => "original",
+            Mode::Balanced => "balanced",
+            Mode::DataSaver => "data_saver",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CompressionConfig {
+    pub mode: Mode,
+}
+
+pub fn load(db: &Database) -> CompressionConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+/// The file id the original of `file_id` is served under
+pub fn original_file_id(file_id: &str) -> String {
+    format!("{}_original", file_id)
+}
+
+/// Undo the camera's EXIF rotation, which re-encoding would otherwise drop
+fn orient(img: DynamicImage, orientation: Option<u16>) -> DynamicImage {
+    match orientation {
+        Some(2) => img.fliph(),
+        Some(3) => img.rotate180(),
+        Some(4) => img.flipv(),
+        Some(5) => img.rotate90().fliph(),
+        Some(6) => img.rotate90(),
+        Some(7) => img.rotate270().fliph(),
+        Some(8) => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// Scale down and re-encode in the same format. None when the image is small already, isn't a
+/// JPEG or PNG, or wouldn't get meaningfully smaller.
+pub fn compress(mode: Mode, data: &[u8]) -> Option<Vec<u8>> {
+    let preset = mode.preset()?;
+    if data.len() > MAX_INPUT_BYTES {
+        return None;
+    }
+    let format = image::guess_format(data).ok()?;
+    let img = match format {
+        ImageFormat::Jpeg | ImageFormat::Png => image::load_from_memory(data).ok()?,
+        _ => return None,
+    };
+    let oversized = img.width().max(img.height()) > preset.max_side;
+    // Screenshots as PNG only get smaller by scaling; JPEG also by a lower quality
+    if !oversized && (format == ImageFormat::Png || data.len() < preset.min_bytes) {
+        return None;
+    }
+    let orientation = image_metadata::jpeg_orientation(data);
+    let mut img = orient(img, orientation);
+    if oversized {
+        img = img.resize(preset.max_side, preset.max_side, FilterType::Lanczos3);
+    }
+
+    let mut out = Vec::new();
+    let encoded = match format {
+        ImageFormat::Jpeg => JpegEncoder::new_with_quality(&mut out, preset.jpeg_quality)
+            .encode_image(&img.to_rgb8()),
+        _ => img.write_to(&mut Cursor::new(&mut out), ImageOutputFormat::Png),
+    };
+    if let Err(e) = encoded {
+        warn!("Re-encoding an image failed: {}", e);
+        return None;
+    }
+    // Not worth a second copy unless it saves at least a tenth
+    (out.len() * 10 <= data.len() * 9).then_some(out)
+}
+
+/// The file's bytes when it's a JPEG or PNG small enough to decode; anything else (videos,
+/// archives, huge scans) is turned away by its size and first bytes without being read
+fn read_candidate(path: &Path) -> Option<Vec<u8>> {
+    if std::fs::metadata(path).ok()?.len() > MAX_INPUT_BYTES as u64 {
+        return None;
+    }
+    let mut file = File::open(path).ok()?;
+    let mut data = Vec::new();
+    (&mut file).take(16).read_to_end(&mut data).ok()?;
+    match image::guess_format(&data).ok()? {
+        ImageFormat::Jpeg | ImageFormat::Png => {}
+        _ => return None,
+    }
+    file.read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+fn record(
+    db: &Database,
+    file_id: &str,
+    original: &Path,
+    original_size: usize,
+    size: usize,
+    mode: Mode,
+) -> Option<ImageVariant> {
+    let variant = ImageVariant {
+        file_id: file_id.to_string(),
+        original_file_id: original_file_id(file_id),
+        original_path: original.to_string_lossy().into_owned(),
+        original_size: original_size as i64,
+        size: size as i64,
+        mode: mode.as_str().to_string(),
+        created_at: now(),
+    };
+    if let Err(e) = db.save_image_variant(&variant) {
+        warn!("Recording the original of {} failed: {}", file_id, e);
+        return None;
+    }
+    info!(
+        "Compressed {} from {} to {} bytes ({})",
+        file_id, original_size, size, variant.mode
+    );
+    Some(variant)
+}
+
+/// Compressed copy of a file we're about to send under `file_id`, written next to the other
+/// served files; the original stays where it is and is served as the original. None means send
+/// `path` as it is.
+pub fn compress_for_send(
+    state: &AppState,
+    file_id: &str,
+    path: &Path,
+) -> Option<(PathBuf, ImageVariant)> {
+    let mode = load(&state.db).mode;
+    mode.preset()?;
+    let data = read_candidate(path)?;
+    let compressed = compress(mode, &data)?;
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "img".to_string());
+    let dest = state
+        .file_server
+        .get_storage_dir()
+        .join(format!("{}.compressed.{}", file_id, ext));
+    if let Err(e) = std::fs::write(&dest, &compressed) {
+        warn!("Writing compressed image failed: {}", e);
+        return None;
+    }
+    let variant = record(&state.db, file_id, path, data.len(), compressed.len(), mode)?;
+    serve_original(state, &variant);
+    Some((dest, variant))
+}
+
+/// Compress a file already stored and served under `file_id` (store_shared_file). The
+/// original moves aside to `<id>.original.<ext>` and is served as the original.
+pub fn compress_stored(state: &AppState, file_id: &str) -> Option<ImageVariant> {
+    let mode = load(&state.db).mode;
+    mode.preset()?;
+    let path = state.file_server.file_path(file_id)?;
+    let data = read_candidate(&path)?;
+    let compressed = compress(mode, &data)?;
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let original = path.with_file_name(format!("{}.original.{}", file_id, ext));
+    if let Err(e) =
+        std::fs::rename(&path, &original).and_then(|_| std::fs::write(&path, &compressed))
+    {
+        warn!("Compressing {} failed: {}", file_id, e);
+        // Put the original back if it had been moved already
+        if original.exists() {
+            let _ = std::fs::rename(&original, &path);
+        }
+        return None;
+    }
+    let variant = record(
+        &state.db,
+        file_id,
+        &original,
+        data.len(),
+        compressed.len(),
+        mode,
+    )?;
+    serve_original(state, &variant);
+    Some(variant)
+}
+
+fn serve_original(state: &AppState, variant: &ImageVariant) {
+    let name = Path::new(&variant.original_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| variant.original_file_id.clone());
+    state.file_server.register_file(
+        &variant.original_file_id,
+        Path::new(&variant.original_path),
+        &name,
+    );
+}
+
+/// Serve the originals of images sent compressed again (registrations don't survive a restart)
+pub fn register_originals(state: &AppState) {
+    for variant in state.db.get_image_variants().unwrap_or_default() {
+        if Path::new(&variant.original_path).exists() {
+            serve_original(state, &variant);
+        }
+    }
+}
+
+/// What a file message says about the original: its file id, the token to fetch it and its size
+pub fn original_info(state: &AppState, variant: &ImageVariant) -> serde_json::Value {
+    serde_json::json!({
+        "fileId": variant.original_file_id,
+        "token": state.file_server.token(&variant.original_file_id),
+        "size": variant.original_size,
+    })
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_image_compression_config(state: State<AppState>) -> CompressionConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_image_compression_config(
+    state: State<AppState>,
+    config: CompressionConfig,
+) -> Result<(), String> {
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("Image compression: {}", config.mode.as_str());
+    Ok(())
+}
+
+/// The original of a file we shared compressed, for the "original" field of a file message
+/// the webview builds itself; None when the file was sent as it is
+#[tauri::command]
+pub fn get_image_original(state: State<AppState>, file_id: String) -> Option<serde_json::Value> {
+    let variant = state.db.get_image_variant(&file_id).ok().flatten()?;
+    Some(original_info(&state, &variant))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgb, RgbImage};
+
+    fn photo(width: u32, height: u32) -> Vec<u8> {
+        // Noise-like content so the JPEG is realistically large
+        let img = RgbImage::from_fn(width, height, |x, y| {
+            let v = (x.wrapping_mul(2654435761) ^ y.wrapping_mul(40503)) as u8;
+            Rgb([v, v.wrapping_add(80), v.wrapping_mul(3)])
+        });
+        let mut out = Vec::new();
+        JpegEncoder::new_with_quality(&mut out, 98)
+            .encode_image(&img)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_large_photo_is_scaled_down() {
+        let data = photo(2000, 1000);
+        assert!(compress(Mode::Original, &data).is_none());
+
+        let smaller = compress(Mode::DataSaver, &data).unwrap();
+        assert!(smaller.len() < data.len());
+        let img = image::load_from_memory(&smaller).unwrap();
+        assert_eq!(img.dimensions(), (1600, 800));
+        assert_eq!(image::guess_format(&smaller).unwrap(), ImageFormat::Jpeg);
+
+        // Small and already within bounds: left alone
+        assert!(compress(Mode::Balanced, &photo(64, 64)).is_none());
+        assert!(compress(Mode::DataSaver, b"not an image").is_none());
+
+        // Files are only read when their first bytes say JPEG or PNG
+        let dir = std::env::temp_dir().join(format!("pingo-compress-{}", crate::db::generate_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (jpeg, fake) = (dir.join("a.jpg"), dir.join("b.jpg"));
+        std::fs::write(&jpeg, &data).unwrap();
+        std::fs::write(&fake, b"\0\0\0\x18ftypmp42 a video").unwrap();
+        assert_eq!(read_candidate(&jpeg).unwrap(), data);
+        assert!(read_candidate(&fake).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,651 @@
+// src-tauri/src/image_metadata.rs
+// Metadata stripping for outgoing images: before a JPEG, PNG or WebP is served to peers, the
+// EXIF block (GPS position, camera serial numbers, timestamps), XMP, IPTC and text comments are
+// cut out. Pixels are copied byte for byte, nothing is re-encoded. By default the orientation
+// survives (as a one-tag EXIF block) so phone photos don't arrive sideways, and so does the
+// color profile. Files are told apart by their first bytes and streamed, so videos and other
+// files are never read. HEIC and AVIF can't be cleaned yet; with stripping on they, like
+// images that don't parse, aren't sent and the user is told why.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tracing::info;
+
+const SETTING_KEY: &str = "image_metadata";
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+const ORIENTATION_TAG: u16 = 0x0112;
+/// Bytes needed to tell the formats apart
+const SNIFF_LEN: u64 = 12;
+/// JPEG segments before the image data; real files have far less
+const MAX_JPEG_HEADER: usize = 16 * 1024 * 1024;
+/// The VP8X chunk is 10 bytes
+const MAX_VP8X_LEN: u64 = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetadataConfig {
+    /// Strip metadata from images we send
+    pub strip: bool,
+    /// Keep the EXIF orientation so rotated photos display the right way up
+    #[serde(default = "default_true")]
+    pub keep_orientation: bool,
+    /// Keep embedded ICC color profiles
+    #[serde(default = "default_true")]
+    pub keep_color_profile: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        MetadataConfig {
+            strip: true,
+            keep_orientation: true,
+            keep_color_profile: true,
+        }
+    }
+}
+
+/// Image formats that carry metadata
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Jpeg,
+    Png,
+    Webp,
+    /// HEIC/HEIF and AVIF
+    Heif,
+}
+
+fn sniff(head: &[u8]) -> Option<Format> {
+    if head.starts_with(&[0xFF, 0xD8]) {
+        Some(Format::Jpeg)
+    } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(Format::Png)
+    } else if head.len() >= 12 && head.starts_with(b"RIFF") && &head[8..12] == b"WEBP" {
+        Some(Format::Webp)
+    } else if head.len() >= 12
+        && &head[4..8] == b"ftyp"
+        && matches!(
+            &head[8..12],
+            b"heic" | b"heix" | b"hevc" | b"heim" | b"heis" | b"mif1" | b"msf1" | b"avif"
+        )
+    {
+        Some(Format::Heif)
+    } else {
+        None
+    }
+}
+
+pub fn load(db: &Database) -> MetadataConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn note(removed: &mut Vec<&'static str>, what: &'static str) {
+    if !removed.contains(&what) {
+        removed.push(what);
+    }
+}
+
+/// Orientation (1-8) from an APP1 Exif payload, if it has one
+fn exif_orientation(payload: &[u8]) -> Option<u16> {
+    let tiff = payload.strip_prefix(EXIF_HEADER)?;
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let b: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let b: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    };
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|o| (2..=8).contains(o))
+}
+
+/// EXIF orientation of a JPEG (2-8; None when it's upright or unknown)
+pub(crate) fn jpeg_orientation(data: &[u8]) -> Option<u16> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while data.get(pos) == Some(&0xFF) {
+        let marker = *data.get(pos + 1)?;
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        let payload = data.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xE1 && payload.starts_with(EXIF_HEADER) {
+            return exif_orientation(payload);
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+/// APP1 segment holding nothing but the orientation
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut payload = EXIF_HEADER.to_vec();
+    payload.extend_from_slice(b"MM\0\x2A\0\0\0\x08"); // big-endian TIFF, IFD0 right after
+    payload.extend_from_slice(&1u16.to_be_bytes()); // one entry
+    payload.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    payload.extend_from_slice(&3u16.to_be_bytes()); // SHORT
+    payload.extend_from_slice(&1u32.to_be_bytes());
+    payload.extend_from_slice(&orientation.to_be_bytes());
+    payload.extend_from_slice(&[0, 0]);
+    payload.extend_from_slice(&0u32.to_be_bytes()); // no next IFD
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    segment.extend_from_slice(&payload);
+    segment
+}
+
+fn truncated() -> String {
+    "The image is truncated".to_string()
+}
+
+/// Up to `limit` bytes from `input`; fewer only at its end
+fn read_up_to(input: &mut impl Read, limit: u64) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    input
+        .take(limit)
+        .read_to_end(&mut buf)
+        .map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+fn read_exact(input: &mut impl Read, len: u64) -> Result<Vec<u8>, String> {
+    let buf = read_up_to(input, len)?;
+    if (buf.len() as u64) < len {
+        return Err(truncated());
+    }
+    Ok(buf)
+}
+
+/// Copy up to `len` bytes; how many there were
+fn copy_up_to(input: &mut impl Read, out: &mut impl Write, len: u64) -> Result<u64, String> {
+    io::copy(&mut input.take(len), out).map_err(|e| e.to_string())
+}
+
+fn copy_exact(input: &mut impl Read, out: &mut impl Write, len: u64) -> Result<(), String> {
+    if copy_up_to(input, out, len)? < len {
+        return Err(truncated());
+    }
+    Ok(())
+}
+
+/// Segments up to the start of scan are read into memory (each is at most 64 KiB); the
+/// entropy-coded image after it is copied through. Nothing is written when there's nothing
+/// to remove.
+fn strip_jpeg(
+    config: &MetadataConfig,
+    input: &mut impl Read,
+    out: &mut impl Write,
+) -> Result<Vec<&'static str>, String> {
+    let mut header = read_exact(input, 2)?;
+    let mut removed = Vec::new();
+    let mut orientation = None;
+    let mut header_end = None;
+    loop {
+        if header.len() > MAX_JPEG_HEADER {
+            return Err("The image has too much metadata".to_string());
+        }
+        if read_exact(input, 1)? != [0xFF] {
+            return Err("Not a valid JPEG".to_string());
+        }
+        let mut marker = read_exact(input, 1)?[0];
+        while marker == 0xFF {
+            marker = read_exact(input, 1)?[0]; // fill bytes
+        }
+        if marker == 0xD9 || marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            header.extend_from_slice(&[0xFF, marker]);
+            continue;
+        }
+        let len_bytes = read_exact(input, 2)?;
+        let len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as u64;
+        if len < 2 {
+            return Err("Not a valid JPEG".to_string());
+        }
+        let payload = read_exact(input, len - 2)?;
+        if header_end.is_none() && marker != 0xE0 {
+            header_end = Some(header.len());
+        }
+        let keep = match marker {
+            // Start of scan: the entropy-coded image and everything after it is kept verbatim
+            0xDA => true,
+            0xE0 | 0xEE => true, // JFIF, Adobe (color transform)
+            0xE1 => {
+                if payload.starts_with(EXIF_HEADER) {
+                    orientation = orientation.or(exif_orientation(&payload));
+                    note(&mut removed, "EXIF");
+                } else {
+                    note(&mut removed, "XMP");
+                }
+                false
+            }
+            0xE2 if payload.starts_with(ICC_HEADER) && config.keep_color_profile => true,
+            0xE2 if payload.starts_with(ICC_HEADER) => {
+                note(&mut removed, "color profile");
+                false
+            }
+            0xED => {
+                note(&mut removed, "IPTC");
+                false
+            }
+            0xFE => {
+                note(&mut removed, "comment");
+                false
+            }
+            0xE2..=0xEF => {
+                note(&mut removed, "vendor data");
+                false
+            }
+            _ => true,
+        };
+        if keep {
+            header.extend_from_slice(&[0xFF, marker]);
+            header.extend_from_slice(&len_bytes);
+            header.extend_from_slice(&payload);
+        }
+        if marker == 0xDA {
+            break;
+        }
+    }
+    if removed.is_empty() {
+        return Ok(removed);
+    }
+    if let Some(orientation) = orientation.filter(|_| config.keep_orientation) {
+        // Right after SOI and JFIF, where EXIF belongs
+        let at = header_end.unwrap_or(2);
+        header.splice(at..at, orientation_segment(orientation));
+    }
+    out.write_all(&header).map_err(|e| e.to_string())?;
+    io::copy(input, out).map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+fn strip_png(
+    config: &MetadataConfig,
+    input: &mut impl Read,
+    out: &mut impl Write,
+) -> Result<Vec<&'static str>, String> {
+    let signature = read_exact(input, 8)?;
+    out.write_all(&signature).map_err(|e| e.to_string())?;
+    let mut removed = Vec::new();
+    loop {
+        let head = read_up_to(input, 8)?;
+        if head.is_empty() {
+            break;
+        }
+        if head.len() < 8 {
+            return Err(truncated());
+        }
+        let len = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as u64;
+        let keep = match &head[4..8] {
+            b"eXIf" => {
+                note(&mut removed, "EXIF");
+                false
+            }
+            b"tEXt" | b"zTXt" | b"iTXt" => {
+                note(&mut removed, "text");
+                false
+            }
+            b"tIME" => {
+                note(&mut removed, "timestamp");
+                false
+            }
+            b"iCCP" if config.keep_color_profile => true,
+            b"iCCP" => {
+                note(&mut removed, "color profile");
+                false
+            }
+            _ => true,
+        };
+        // Data and CRC
+        if keep {
+            out.write_all(&head).map_err(|e| e.to_string())?;
+            copy_exact(input, out, len + 4)?;
+        } else {
+            copy_exact(input, &mut io::sink(), len + 4)?;
+        }
+    }
+    Ok(removed)
+}
+
+/// The RIFF size in the header is filled in at the end, hence the Seek
+fn strip_webp<W: Write + Seek>(
+    config: &MetadataConfig,
+    input: &mut impl Read,
+    out: &mut W,
+) -> Result<Vec<&'static str>, String> {
+    read_exact(input, 12)?;
+    let start = out.stream_position().map_err(|e| e.to_string())?;
+    out.write_all(b"RIFF\0\0\0\0WEBP")
+        .map_err(|e| e.to_string())?;
+    let mut removed = Vec::new();
+    loop {
+        let head = read_up_to(input, 8)?;
+        if head.is_empty() {
+            break;
+        }
+        if head.len() < 8 {
+            return Err(truncated());
+        }
+        let len = u32::from_le_bytes([head[4], head[5], head[6], head[7]]) as u64;
+        let padded = len + (len & 1);
+        let drop = match &head[..4] {
+            b"EXIF" => Some("EXIF"),
+            b"XMP " => Some("XMP"),
+            b"ICCP" if !config.keep_color_profile => Some("color profile"),
+            _ => None,
+        };
+        if let Some(what) = drop {
+            note(&mut removed, what);
+            copy_up_to(input, &mut io::sink(), padded)?;
+            continue;
+        }
+        out.write_all(&head).map_err(|e| e.to_string())?;
+        if &head[..4] == b"VP8X" {
+            if len > MAX_VP8X_LEN {
+                return Err("Not a valid WebP".to_string());
+            }
+            let mut payload = read_up_to(input, padded)?;
+            if let Some(flags) = payload.first_mut() {
+                // Clear the EXIF and XMP flags (and ICC when it goes too)
+                *flags &= !(0x08 | 0x04);
+                if !config.keep_color_profile {
+                    *flags &= !0x20;
+                }
+            }
+            out.write_all(&payload).map_err(|e| e.to_string())?;
+        } else if copy_up_to(input, out, padded)? < len {
+            return Err(truncated());
+        }
+    }
+    let end = out.stream_position().map_err(|e| e.to_string())?;
+    let size = u32::try_from(end - start - 8).map_err(|_| "The image is too large".to_string())?;
+    out.seek(SeekFrom::Start(start + 4))
+        .and_then(|_| out.write_all(&size.to_le_bytes()))
+        .and_then(|_| out.seek(SeekFrom::Start(end)))
+        .map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+/// Strip an image of `format` from `input` into `out`; what was removed. The output is only
+/// complete when something was; an error means the image couldn't be cleaned.
+fn strip_stream<W: Write + Seek>(
+    config: &MetadataConfig,
+    format: Format,
+    input: &mut impl Read,
+    out: &mut W,
+) -> Result<Vec<&'static str>, String> {
+    match format {
+        Format::Jpeg => strip_jpeg(config, input, out),
+        Format::Png => strip_png(config, input, out),
+        Format::Webp => strip_webp(config, input, out),
+        Format::Heif => Err("HEIC and AVIF images aren't supported".to_string()),
+    }
+}
+
+/// Format of the image at `path` from its first bytes; None for everything else, which is
+/// never read any further
+fn sniff_file(path: &Path) -> Result<Option<Format>, String> {
+    let mut file = File::open(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    Ok(sniff(&read_up_to(&mut file, SNIFF_LEN)?))
+}
+
+/// Stream a cleaned copy of `path` into `dest`; what was removed
+fn strip_file(
+    config: &MetadataConfig,
+    format: Format,
+    path: &Path,
+    dest: &Path,
+) -> Result<Vec<&'static str>, String> {
+    let mut input = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut out = BufWriter::new(File::create(dest).map_err(|e| e.to_string())?);
+    let removed = strip_stream(config, format, &mut input, &mut out)?;
+    out.flush().map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+/// What the user sees when an image that may carry metadata can't be cleaned; it isn't sent
+fn could_not_strip(path: &Path, reason: &str) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!(
+        "Couldn't remove the metadata from {} ({}). Turn off metadata stripping to send it as it is.",
+        name, reason
+    )
+}
+
+/// Strip a file we're about to serve in place (it's already our own copy). An image that can't
+/// be cleaned is an error rather than going out with its metadata.
+pub fn strip_in_place(db: &Database, path: &Path) -> Result<(), String> {
+    let config = load(db);
+    if !config.strip {
+        return Ok(());
+    }
+    let Some(format) = sniff_file(path)? else {
+        return Ok(());
+    };
+    let temp = path.with_extension("stripping");
+    let removed = match strip_file(&config, format, path, &temp) {
+        Ok(removed) => removed,
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp);
+            return Err(could_not_strip(path, &e));
+        }
+    };
+    if removed.is_empty() {
+        let _ = std::fs::remove_file(&temp);
+        return Ok(());
+    }
+    std::fs::rename(&temp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        could_not_strip(path, &e.to_string())
+    })?;
+    info!("Removed {} from {}", removed.join(", "), path.display());
+    Ok(())
+}
+
+/// The file to send instead of `path`: a stripped copy in `dir` named after `id`, or `path`
+/// itself when there's nothing to strip. The user's original is never touched; one that can't
+/// be cleaned is an error.
+pub fn stripped_copy(db: &Database, path: &Path, dir: &Path, id: &str) -> Result<PathBuf, String> {
+    let config = load(db);
+    if !config.strip {
+        return Ok(path.to_path_buf());
+    }
+    let Some(format) = sniff_file(path)? else {
+        return Ok(path.to_path_buf());
+    };
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "img".to_string());
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let copy = dir.join(format!("{}.{}", id, ext));
+    match strip_file(&config, format, path, &copy) {
+        Ok(removed) if !removed.is_empty() => {
+            info!("Sending {} without {}", path.display(), removed.join(", "));
+            Ok(copy)
+        }
+        Ok(_) => {
+            let _ = std::fs::remove_file(&copy);
+            Ok(path.to_path_buf())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&copy);
+            Err(could_not_strip(path, &e))
+        }
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_image_metadata_config(state: State<AppState>) -> MetadataConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_image_metadata_config(
+    state: State<AppState>,
+    config: MetadataConfig,
+) -> Result<(), String> {
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("Strip metadata from sent images: {}", config.strip);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A cleaned image and what was taken out of it
+    #[derive(Debug)]
+    struct Stripped {
+        bytes: Vec<u8>,
+        removed: Vec<&'static str>,
+    }
+
+    /// `strip_stream` in memory; None when nothing was removed or the image didn't parse
+    fn strip(config: &MetadataConfig, data: &[u8]) -> Option<Stripped> {
+        let format = sniff(data)?;
+        let mut out = Cursor::new(Vec::new());
+        let removed = strip_stream(config, format, &mut &data[..], &mut out).ok()?;
+        (!removed.is_empty()).then(|| Stripped {
+            bytes: out.into_inner(),
+            removed,
+        })
+    }
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut s = vec![0xFF, marker];
+        s.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        s.extend_from_slice(payload);
+        s
+    }
+
+    #[test]
+    fn test_jpeg_loses_exif_but_keeps_orientation() {
+        // Little-endian EXIF with orientation 6 and a GPS IFD pointer
+        let mut exif = EXIF_HEADER.to_vec();
+        exif.extend_from_slice(b"II\x2A\0\x08\0\0\0");
+        exif.extend_from_slice(&2u16.to_le_bytes());
+        exif.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+        exif.extend_from_slice(&[0x25, 0x88, 4, 0, 1, 0, 0, 0, 0x26, 0, 0, 0]);
+        exif.extend_from_slice(b"\0\0\0\0GPS 52.52N 13.40E");
+
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend(segment(0xE0, b"JFIF\0\x01\x01"));
+        jpeg.extend(segment(0xE1, &exif));
+        jpeg.extend(segment(0xFE, b"shot on a Pixel, serial 1234"));
+        jpeg.extend(segment(0xDB, &[0; 65]));
+        jpeg.extend(segment(0xDA, &[1, 2, 3]));
+        jpeg.extend_from_slice(&[0xAB, 0xCD, 0xFF, 0xD9]);
+
+        let config = MetadataConfig::default();
+        let stripped = strip(&config, &jpeg).unwrap();
+        assert_eq!(stripped.removed, vec!["EXIF", "comment"]);
+        let out = &stripped.bytes;
+        assert!(!out.windows(3).any(|w| w == b"GPS"));
+        assert!(!out.windows(6).any(|w| w == b"serial"));
+        assert!(out.ends_with(&[0xAB, 0xCD, 0xFF, 0xD9]));
+        // JFIF first, then the orientation-only EXIF
+        let app1 = 2 + 4 + 7;
+        assert_eq!(&out[app1..app1 + 2], &[0xFF, 0xE1]);
+        assert_eq!(exif_orientation(&out[app1 + 4..]), Some(6));
+
+        let no_rotation = MetadataConfig {
+            keep_orientation: false,
+            ..config
+        };
+        let out = strip(&no_rotation, &jpeg).unwrap().bytes;
+        assert!(!out.windows(4).any(|w| w == &EXIF_HEADER[..4]));
+        assert!(strip(&config, &out).is_none());
+    }
+
+    #[test]
+    fn test_png_text_chunks_removed() {
+        fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+            let mut c = (data.len() as u32).to_be_bytes().to_vec();
+            c.extend_from_slice(kind);
+            c.extend_from_slice(data);
+            c.extend_from_slice(&[0; 4]);
+            c
+        }
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(chunk(b"IHDR", &[0; 13]));
+        png.extend(chunk(b"tEXt", b"Author\0jdoe"));
+        png.extend(chunk(b"IDAT", &[9; 10]));
+        png.extend(chunk(b"IEND", &[]));
+        let stripped = strip(&MetadataConfig::default(), &png).unwrap();
+        assert_eq!(stripped.removed, vec!["text"]);
+        assert_eq!(stripped.bytes.len(), png.len() - (12 + 11));
+        assert!(strip(&MetadataConfig::default(), b"GIF89a").is_none());
+        // A chunk cut short isn't passed on half cleaned
+        assert!(strip(&MetadataConfig::default(), &png[..png.len() - 20]).is_none());
+    }
+
+    #[test]
+    fn test_files_are_sniffed_and_unstrippable_images_refused() {
+        let dir = std::env::temp_dir().join(format!("pingo-meta-{}", crate::db::generate_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new_in_memory().unwrap();
+
+        // Not an image: handed back untouched, whatever its extension
+        let video = dir.join("clip.jpg");
+        std::fs::write(&video, b"\0\0\0\x18ftypmp42 and then a lot of video").unwrap();
+        assert_eq!(stripped_copy(&db, &video, &dir, "v1").unwrap(), video);
+
+        // HEIC may hold GPS positions we can't take out: not sent, and the user hears why
+        let heic = dir.join("photo.heic");
+        std::fs::write(&heic, b"\0\0\0\x18ftypheic\0\0\0\0mif1heic").unwrap();
+        let err = stripped_copy(&db, &heic, &dir, "h1").unwrap_err();
+        assert!(err.contains("photo.heic"));
+        assert!(strip_in_place(&db, &heic).is_err());
+        assert!(!dir.join("h1.heic").exists());
+
+        let bad = dir.join("broken.jpg");
+        std::fs::write(&bad, [0xFF, 0xD8, 0xFF, 0xE1, 0x00]).unwrap();
+        assert!(stripped_copy(&db, &bad, &dir, "b1").is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,132 @@
+// src-tauri/src/keyword_alerts.rs
+// Keyword alerts: words or phrases ("prod down", your own name) that should always get the
+// user's attention. Incoming messages are scanned before the usual notification checks, so a
+// match alerts even in a muted chat or group.
+
+use crate::commands::AppState;
+use crate::db::{AlertKeyword, Database};
+use crate::notifications::{self, NotificationTarget};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::info;
+
+const MAX_KEYWORD_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeywordAlert {
+    pub chat_id: String,
+    pub is_group: bool,
+    pub sender_name: String,
+    pub keywords: Vec<String>,
+    pub preview: String,
+}
+
+/// Keywords found in `content` as whole words, ignoring case
+pub fn find_matches(content: &str, keywords: &[String]) -> Vec<String> {
+    let haystack = content.to_lowercase();
+    keywords
+        .iter()
+        .filter(|k| {
+            let needle = k.to_lowercase();
+            !needle.is_empty()
+                && haystack.match_indices(&needle).any(|(at, _)| {
+                    let before = haystack[..at].chars().next_back();
+                    let after = haystack[at + needle.len()..].chars().next();
+                    !before.is_some_and(char::is_alphanumeric)
+                        && !after.is_some_and(char::is_alphanumeric)
+                })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Scan an incoming message; on a match emit "keyword-alert" and show a high-priority
+/// notification. Returns true when it alerted, so the regular notification is skipped.
+pub fn check<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    target: &NotificationTarget,
+    sender_name: &str,
+    message_type: &str,
+    content: &str,
+) -> bool {
+    if message_type != "text" {
+        return false;
+    }
+    let keywords: Vec<String> = db
+        .get_alert_keywords()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|k| k.keyword)
+        .collect();
+    if keywords.is_empty() {
+        return false;
+    }
+    let hits = find_matches(content, &keywords);
+    if hits.is_empty() {
+        return false;
+    }
+    info!("Keyword alert in {}: {:?}", target.chat_id(), hits);
+    let _ = app.emit(
+        "keyword-alert",
+        KeywordAlert {
+            chat_id: target.chat_id().to_string(),
+            is_group: matches!(target, NotificationTarget::Group { .. }),
+            sender_name: sender_name.to_string(),
+            keywords: hits.clone(),
+            preview: notifications::preview(message_type, content),
+        },
+    );
+    notifications::notify_keyword_alert(app, db, target, sender_name, &hits, content);
+    true
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_alert_keywords(state: State<AppState>) -> Result<Vec<AlertKeyword>, String> {
+    state.db.get_alert_keywords().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_alert_keyword(state: State<AppState>, keyword: String) -> Result<AlertKeyword, String> {
+    let keyword = keyword.trim();
+    if keyword.chars().count() < 2 {
+        return Err("Keywords need at least 2 characters".to_string());
+    }
+    if keyword.chars().count() > MAX_KEYWORD_CHARS {
+        return Err(format!(
+            "Keywords are at most {} characters",
+            MAX_KEYWORD_CHARS
+        ));
+    }
+    state
+        .db
+        .add_alert_keyword(keyword)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_alert_keyword(state: State<AppState>, id: String) -> Result<bool, String> {
+    state
+        .db
+        .remove_alert_keyword(&id)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches_whole_words_any_case() {
+        let keywords = vec!["prod down".to_string(), "Ana".to_string()];
+        assert_eq!(
+            find_matches("PROD DOWN again!", &keywords),
+            vec!["prod down"]
+        );
+        assert_eq!(find_matches("ping @ana, please", &keywords), vec!["Ana"]);
+        // Not inside other words
+        assert!(find_matches("banana production down", &keywords).is_empty());
+    }
+}
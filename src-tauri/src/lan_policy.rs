@@ -0,0 +1,108 @@
+// src-tauri/src/lan_policy.rs
+// LAN-only enforcement. Unless "allow WAN" is switched on, signaling packets and file server
+// requests from outside private address ranges are dropped and peers with public addresses
+// can't be registered. Defense in depth for laptops that roam onto networks where our ports
+// may be reachable from anywhere.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::State;
+use tracing::info;
+
+const SETTING_KEY: &str = "allow_wan";
+
+static ALLOW_WAN: AtomicBool = AtomicBool::new(false);
+
+fn is_lan_v4(ip: Ipv4Addr) -> bool {
+    ip.is_private() || ip.is_loopback() || ip.is_link_local()
+}
+
+/// Private, loopback or link-local (IPv6: unique local fc00::/7 and fe80::/10 too)
+pub fn is_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_lan_v4(v4),
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || v6.to_ipv4_mapped().is_some_and(is_lan_v4)
+        }
+    }
+}
+
+/// Whether traffic from `ip` may be handled
+pub fn accepts(ip: IpAddr) -> bool {
+    ALLOW_WAN.load(Ordering::Relaxed) || is_lan(ip)
+}
+
+/// For peer registration: an error naming the address when it's refused
+pub fn check_peer_address(ip: IpAddr) -> Result<(), String> {
+    if accepts(ip) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not a local network address (enable \"allow WAN\" to use it)",
+            ip
+        ))
+    }
+}
+
+/// Apply the stored setting (at startup)
+pub fn configure(db: &Database) {
+    let allow = db.get_setting(SETTING_KEY).ok().flatten().as_deref() == Some("true");
+    ALLOW_WAN.store(allow, Ordering::Relaxed);
+    if allow {
+        info!("Peers outside the local network are allowed");
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_allow_wan() -> bool {
+    ALLOW_WAN.load(Ordering::Relaxed)
+}
+
+#[tauri::command]
+pub fn set_allow_wan(state: State<AppState>, allow: bool) -> Result<(), String> {
+    state
+        .db
+        .set_setting(SETTING_KEY, if allow { "true" } else { "false" })
+        .map_err(|e| e.to_string())?;
+    ALLOW_WAN.store(allow, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lan_ranges() {
+        for lan in [
+            "10.1.2.3",
+            "172.20.0.5",
+            "192.168.1.10",
+            "127.0.0.1",
+            "169.254.3.4",
+            "fd12::1",
+            "fe80::1",
+            "::1",
+            "::ffff:192.168.0.2",
+        ] {
+            assert!(is_lan(lan.parse().unwrap()), "{}", lan);
+        }
+        for wan in [
+            "8.8.8.8",
+            "100.64.0.1",
+            "172.32.0.1",
+            "2001:db8::1",
+            "::ffff:1.1.1.1",
+        ] {
+            assert!(!is_lan(wan.parse().unwrap()), "{}", wan);
+        }
+    }
+}
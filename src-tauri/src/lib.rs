@@ -2,27 +2,106 @@
 // Pingo - P2P Desktop Messaging Application
 // Main library entry point
 
+mod archive_peek;
+mod auto_reply;
+mod automation_api;
+mod avatar;
+mod backup_targets;
+mod backups;
+mod bulk_messages;
+mod channels;
+mod chat_folders;
+mod chat_settings;
+mod chat_stats;
+mod clock_skew;
 mod commands;
+mod config;
+mod connectivity;
+mod contact_tags;
+mod conversation_clear;
 mod crypto;
 mod db;
+mod db_recovery;
+mod deleted_messages;
+mod delivery_retry;
+mod delivery_status;
+mod device_sync;
+mod diagnostics;
 mod discovery;
+mod disk_space;
+mod download_folders;
+mod download_policy;
+mod download_pool;
+mod downloads;
+mod event_bus;
+mod external_tool;
+mod file_index;
+mod file_requests;
+mod file_send;
 mod file_server;
 mod file_transfer;
+mod history_import;
+mod hotkeys;
+mod http_client;
+mod ice_servers;
+mod image_compression;
+mod image_metadata;
+mod keyword_alerts;
+mod lan_policy;
+mod link_snapshots;
+mod logging;
+mod meeting;
+mod meeting_history;
+mod meeting_recording;
+mod meeting_roster;
+mod meeting_schedule;
+mod message_requests;
+mod note_reminders;
+mod note_sharing;
+mod notifications;
+mod ocr;
+mod pairing;
+mod pin_pairing;
+mod power;
+mod previews;
+mod profiles;
+mod query_cache;
+mod remote_control;
+mod scan;
 mod screen_capture;
+mod session_store;
+mod shred;
 mod signaling;
+mod sounds;
+mod status;
+mod transcription;
+mod translation;
 mod tray;
+mod trust;
+mod video_prepare;
+mod webhooks;
+mod whiteboard;
+mod window_manager;
 
 use commands::AppState;
 use tauri::Manager;
 use tauri_plugin_autostart::MacosLauncher;
+use tracing::{info, warn};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
+
     tauri::Builder::default()
         // Core plugins
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(hotkeys::handle_shortcut)
+                .build(),
+        )
         .plugin(tauri_plugin_autostart::init(
             MacosLauncher::LaunchAgent,
             Some(vec!["--minimized"]),
@@ -38,7 +117,30 @@ pub fn run() {
             let handle = app.handle().clone();
             if let Err(e) = tray::init_tray(&handle) {
                 // Tray initialization failure should not abort app startup in dev/hot-reload
-                println!("[Pingo] Warning: failed to initialize tray: {}", e);
+                warn!("failed to initialize tray: {}", e);
+            }
+
+            // Register global shortcuts from settings
+            {
+                let state = app.state::<AppState>();
+                hotkeys::register_from_settings(&handle, &state.db);
+                logging::apply_saved_level(&state.db);
+                http_client::configure(&state.db);
+                lan_policy::configure(&state.db);
+                config::apply_saved(&state);
+                power::start_monitor(&handle);
+                note_reminders::start_scheduler(&handle);
+                meeting_schedule::start_scheduler(&handle);
+                backups::start_scheduler(&handle);
+                clock_skew::start_scheduler(&handle);
+                delivery_retry::start_scheduler(&handle);
+                deleted_messages::start_purger(&handle);
+                file_requests::start_sweeper(&handle);
+                file_index::start_scan(&handle);
+                video_prepare::clear_leftovers(&state);
+                status::start_expiry_timer(&handle);
+                event_bus::start(&handle);
+                automation_api::start_if_enabled(&handle, &state.db);
             }
 
             // Set up window close behavior (minimize to tray instead of closing)
@@ -54,12 +156,14 @@ pub fn run() {
                     }
                 });
             } else {
-                println!("[Pingo] Warning: main window not available during setup; skipping close-handler registration");
+                warn!(
+                    "main window not available during setup; skipping close-handler registration"
+                );
             }
 
             // Log initialization
-            println!("Pingo initialized successfully");
-            println!("Database path: {:?}", db::Database::get_db_path());
+            info!("Pingo initialized successfully");
+            info!("Database path: {:?}", db::Database::get_db_path());
 
             Ok(())
         })
@@ -89,12 +193,84 @@ pub fn run() {
             // Signaling commands
             commands::start_signaling,
             commands::register_peer,
+            commands::get_peer_capabilities,
+            trust::trust_peer,
+            trust::untrust_peer,
+            trust::get_trust_state,
+            trust::get_trusted_peers,
+            trust::get_trust_policy,
+            chat_settings::set_chat_setting,
+            chat_settings::get_chat_settings,
+            translation::translate_message,
+            translation::get_translation_config,
+            translation::set_translation_config,
+            transcription::transcribe_attachment,
+            transcription::search_attachment_text,
+            transcription::get_transcription_config,
+            transcription::set_transcription_config,
+            ocr::extract_image_text,
+            ocr::get_ocr_config,
+            ocr::set_ocr_config,
+            image_metadata::get_image_metadata_config,
+            image_metadata::set_image_metadata_config,
+            image_compression::get_image_compression_config,
+            image_compression::set_image_compression_config,
+            image_compression::get_image_original,
+            video_prepare::prepare_video_for_send,
+            video_prepare::cancel_video_prepare,
+            video_prepare::get_video_transcode_config,
+            video_prepare::set_video_transcode_config,
+            previews::get_document_preview,
+            previews::get_preview_config,
+            previews::set_preview_config,
+            archive_peek::peek_archive,
+            link_snapshots::get_link_snapshots,
+            link_snapshots::read_link_snapshot,
+            link_snapshots::snapshot_link,
+            link_snapshots::delete_link_snapshot,
+            link_snapshots::get_link_snapshot_config,
+            link_snapshots::set_link_snapshot_config,
+            backups::get_backup_config,
+            backups::set_backup_config,
+            backups::run_backup_now,
+            backups::get_backup_history,
+            backup_targets::get_backup_target,
+            backup_targets::set_backup_target,
+            backup_targets::list_remote_backups,
+            backup_targets::restore_remote_backup,
+            clock_skew::get_peer_clocks,
+            clock_skew::measure_peer_clock,
+            clock_skew::get_clock_skew_config,
+            clock_skew::set_clock_skew_config,
+            commands::get_message_status,
+            delivery_retry::get_delivery_retry_policy,
+            delivery_retry::set_delivery_retry_policy,
+            delivery_retry::retry_message,
+            chat_folders::get_chat_folders,
+            chat_folders::create_chat_folder,
+            chat_folders::update_chat_folder,
+            chat_folders::delete_chat_folder,
+            chat_folders::assign_chat_to_folder,
+            chat_folders::remove_chat_from_folder,
+            chat_folders::get_chats_by_folder,
+            chat_folders::get_chat_folder_assignments,
+            config::get_config,
+            config::update_config,
+            trust::set_trust_policy,
+            message_requests::get_privacy_mode,
+            message_requests::set_privacy_mode,
+            message_requests::get_message_requests,
+            message_requests::accept_message_request,
+            message_requests::decline_message_request,
             commands::send_signaling_message,
             // Encryption commands
             commands::establish_session,
             commands::encrypt_message,
             commands::decrypt_message,
             commands::get_public_key,
+            commands::get_identity_key,
+            commands::forget_identity_pin,
+            session_store::rotate_session_key,
             // File transfer commands
             commands::prepare_file_send,
             commands::prepare_file_receive,
@@ -114,6 +290,77 @@ pub fn run() {
             // Window commands
             commands::minimize_to_tray,
             commands::show_window,
+            // Quick reply popup
+            window_manager::open_quick_reply_window,
+            window_manager::close_quick_reply_window,
+            commands::send_quick_reply,
+            // Pop-out chat windows
+            window_manager::open_chat_window,
+            window_manager::close_chat_window,
+            window_manager::get_open_chat_windows,
+            // Notification commands
+            notifications::notification_reply,
+            notifications::notification_mark_read,
+            notifications::set_do_not_disturb,
+            notifications::get_do_not_disturb,
+            notifications::set_chat_muted,
+            notifications::get_chat_muted,
+            // Notification sound commands
+            sounds::list_sounds,
+            sounds::import_sound,
+            sounds::delete_sound,
+            sounds::preview_sound,
+            sounds::set_default_sound,
+            sounds::set_chat_sound,
+            sounds::get_chat_sound,
+            // Profile commands
+            profiles::list_profiles,
+            profiles::get_active_profile,
+            profiles::create_profile,
+            profiles::switch_profile,
+            // Linked device commands
+            device_sync::create_link_code,
+            device_sync::link_device,
+            device_sync::get_linked_devices,
+            device_sync::unlink_device,
+            device_sync::sync_linked_devices,
+            // QR pairing commands
+            pairing::generate_pairing_qr,
+            pairing::pair_from_qr,
+            pairing::get_identity_qr,
+            pairing::verify_identity_qr,
+            // History import commands
+            history_import::preview_import,
+            history_import::commit_import,
+            history_import::cancel_import,
+            // Logging and diagnostics commands
+            logging::get_log_level,
+            logging::set_log_level,
+            logging::get_recent_logs,
+            diagnostics::generate_diagnostics_report,
+            connectivity::test_peer_connectivity,
+            power::reconnect_network,
+            // Outbound HTTP proxy / CA settings
+            http_client::get_http_settings,
+            http_client::set_http_settings,
+            // Auto-responder commands
+            auto_reply::get_auto_reply_rules,
+            auto_reply::save_auto_reply_rule,
+            auto_reply::delete_auto_reply_rule,
+            auto_reply::set_auto_reply_rule_enabled,
+            // Automation API commands
+            automation_api::get_automation_api,
+            automation_api::set_automation_api_enabled,
+            automation_api::regenerate_automation_token,
+            // Webhook commands
+            webhooks::get_webhooks,
+            webhooks::save_webhook,
+            webhooks::delete_webhook,
+            webhooks::test_webhook,
+            // Global shortcut commands
+            hotkeys::get_hotkeys,
+            hotkeys::set_hotkey,
+            hotkeys::reset_hotkeys,
             // Utility commands
             commands::get_device_id,
             commands::generate_uuid,
@@ -126,6 +373,7 @@ pub fn run() {
             commands::relay_chat_message,
             commands::save_avatar,
             commands::get_shared_media,
+            commands::get_shared_media_summary,
             commands::get_users_with_messages,
             // Offline delivery commands
             commands::mark_message_delivered,
@@ -135,6 +383,46 @@ pub fn run() {
             commands::get_all_notes,
             commands::delete_note,
             commands::toggle_note_pin,
+            commands::lock_note,
+            commands::unlock_note,
+            commands::remove_note_lock,
+            commands::add_note_attachment,
+            commands::get_note_attachments,
+            commands::remove_note_attachment,
+            note_reminders::set_note_reminder,
+            note_reminders::get_note_reminders,
+            note_reminders::clear_note_reminder,
+            note_reminders::snooze_note_reminder,
+            note_reminders::complete_note_reminder,
+            ice_servers::get_ice_servers,
+            ice_servers::set_ice_servers,
+            ice_servers::test_ice_servers,
+            meeting::create_meeting,
+            meeting::join_meeting,
+            meeting::leave_meeting,
+            meeting::get_meeting,
+            meeting::send_track,
+            meeting::write_track_sample,
+            meeting::send_meeting_data,
+            meeting_history::get_meeting_history,
+            meeting_schedule::schedule_meeting,
+            meeting_schedule::get_scheduled_meetings,
+            meeting_roster::get_meeting_participants,
+            meeting_roster::decide_meeting_join,
+            whiteboard::send_whiteboard_op,
+            whiteboard::get_whiteboard,
+            whiteboard::save_whiteboard,
+            remote_control::request_remote_control,
+            remote_control::respond_remote_control,
+            remote_control::send_remote_input,
+            remote_control::stop_remote_control,
+            remote_control::get_remote_control_sessions,
+            remote_control::set_screen_sharing,
+            meeting_recording::start_meeting_recording,
+            meeting_recording::record_meeting_frame,
+            meeting_recording::stop_meeting_recording,
+            meeting_recording::get_meeting_recording,
+            note_sharing::share_note,
             // Group commands
             commands::create_group,
             commands::get_groups,
@@ -145,23 +433,76 @@ pub fn run() {
             // File server commands
             commands::store_shared_file,
             commands::get_file_server_port,
+            commands::get_local_file_server_port,
+            commands::get_shared_file_checksum,
+            commands::get_shared_file_token,
             commands::read_file_as_data_url,
             // Message deletion commands
             commands::delete_message,
+            deleted_messages::restore_message,
+            deleted_messages::delete_for_everyone,
+            bulk_messages::delete_messages,
+            bulk_messages::mark_messages_read,
+            bulk_messages::forward_messages,
+            bulk_messages::broadcast_to_tag,
+            contact_tags::add_user_tag,
+            contact_tags::remove_user_tag,
+            contact_tags::tag_users,
+            contact_tags::get_user_tags,
+            contact_tags::get_all_tags,
+            conversation_clear::clear_chat_for_both,
+            conversation_clear::accept_conversation_clear,
+            conversation_clear::get_conversation_clear_policy,
+            conversation_clear::set_conversation_clear_policy,
+            status::set_status,
+            status::clear_status,
+            status::get_status,
+            status::set_visibility,
+            status::get_visibility,
+            lan_policy::get_allow_wan,
+            lan_policy::set_allow_wan,
+            pin_pairing::get_require_pin_pairing,
+            pin_pairing::set_require_pin_pairing,
+            pin_pairing::is_pin_paired,
+            pin_pairing::start_pin_pairing,
+            pin_pairing::submit_pairing_pin,
+            pin_pairing::cancel_pin_pairing,
+            file_requests::get_file_transfer_requests,
+            file_requests::respond_to_file_transfer,
+            file_send::send_file,
+            keyword_alerts::get_alert_keywords,
+            keyword_alerts::add_alert_keyword,
+            keyword_alerts::remove_alert_keyword,
+            chat_stats::get_chat_stats,
+            db_recovery::check_database,
+            db_recovery::repair_database,
+            event_bus::refresh_backend_summaries,
             commands::delete_all_messages_with_peer,
             commands::delete_user,
             // Group management commands
             commands::add_group_member,
             commands::remove_group_member,
             commands::leave_group,
+            channels::set_group_role,
             commands::get_all_users_for_group,
             // File download & management commands
             commands::auto_download_file,
             commands::open_file_location,
+            commands::open_file,
             commands::save_file_with_dialog,
+            downloads::get_downloads,
+            downloads::retry_download,
+            downloads::approve_download,
+            download_policy::get_download_policy,
+            download_policy::set_download_policy,
+            scan::get_scan_config,
+            scan::set_scan_config,
+            disk_space::get_storage_reserve,
+            disk_space::set_storage_reserve,
             commands::rename_user_download_folder,
             commands::get_pingo_downloads_base,
             commands::check_file_downloaded,
+            download_folders::get_download_folder_names,
             commands::get_local_file_url,
             commands::get_shared_file_path,
             // Avatar caching command — download remote avatar and save locally
@@ -187,6 +528,7 @@ mod integration_tests {
     use crate::file_server::FileServer;
     use crate::file_transfer::FileTransferManager;
     use crate::signaling::SignalingServer;
+    use crate::window_manager::ChatWindows;
     use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
@@ -199,6 +541,7 @@ mod integration_tests {
         let db_a = Arc::new(Database::new_in_memory().unwrap());
         let disc_a = Arc::new(DiscoveryManager::new());
         let crypto_a = Arc::new(CryptoManager::new());
+        crypto_a.set_device_id("device_a");
         let sig_a = Arc::new(SignalingServer::new("device_a".to_string()));
         let ft_a = Arc::new(FileTransferManager::new());
         let fs_a = Arc::new(FileServer::new());
@@ -210,13 +553,17 @@ mod integration_tests {
             signaling: sig_a,
             file_transfer: ft_a,
             file_server: fs_a,
-            device_id: "device_a".to_string(),
+            chat_windows: Arc::new(ChatWindows::new()),
+            meeting_rosters: Default::default(),
+            events: Default::default(),
+            device_id: std::sync::RwLock::new("device_a".to_string()),
         };
 
         // Setup State B
         let db_b = Arc::new(Database::new_in_memory().unwrap());
         let disc_b = Arc::new(DiscoveryManager::new());
         let crypto_b = Arc::new(CryptoManager::new());
+        crypto_b.set_device_id("device_b");
         let sig_b = Arc::new(SignalingServer::new("device_b".to_string()));
         let ft_b = Arc::new(FileTransferManager::new());
         let fs_b = Arc::new(FileServer::new());
@@ -228,7 +575,10 @@ mod integration_tests {
             signaling: sig_b,
             file_transfer: ft_b,
             file_server: fs_b,
-            device_id: "device_b".to_string(),
+            chat_windows: Arc::new(ChatWindows::new()),
+            meeting_rosters: Default::default(),
+            events: Default::default(),
+            device_id: std::sync::RwLock::new("device_b".to_string()),
         };
 
         println!("1. Initializing Crypto Keys...");
@@ -239,19 +589,21 @@ mod integration_tests {
         state_a
             .discovery
             .start(
-                state_a.device_id.clone(),
+                state_a.device_id(),
                 "User A".to_string(),
                 1420,
                 pub_key_a.clone(),
+                None,
             )
             .unwrap();
         state_b
             .discovery
             .start(
-                state_b.device_id.clone(),
+                state_b.device_id(),
                 "User B".to_string(),
                 1421,
                 pub_key_b.clone(),
+                None,
             )
             .unwrap();
 
@@ -292,7 +644,7 @@ mod integration_tests {
         let msg_content = "Hello User B, this is a secret!";
         let envelope = state_a
             .crypto
-            .encrypt_message("device_b", msg_content)
+            .encrypt_message("device_b", None, msg_content)
             .expect("Encryption failed");
 
         println!("   Encrypted message: {:?}", envelope.ciphertext);
@@ -302,7 +654,7 @@ mod integration_tests {
         // B decrypts message
         let decrypted = state_b
             .crypto
-            .decrypt_message("device_a", &envelope)
+            .decrypt_message("device_a", &envelope, None)
             .expect("Decryption failed");
         println!("   Decrypted message: {}", decrypted);
 
@@ -325,6 +677,7 @@ mod integration_tests {
             last_seen: Some(crate::db::now()),
             is_online: true,
             created_at: crate::db::now(),
+            profile: Default::default(),
         };
         state_a.db.create_user(&user_a).unwrap();
 
@@ -339,6 +692,7 @@ mod integration_tests {
             last_seen: Some(crate::db::now()),
             is_online: true,
             created_at: crate::db::now(),
+            profile: Default::default(),
         };
         state_a.db.create_user(&user_b).unwrap();
 
@@ -353,6 +707,8 @@ mod integration_tests {
             is_read: true,
             is_delivered: true,
             created_at: crate::db::now(),
+            lamport: 0,
+            status: crate::delivery_status::MessageStatus::Read,
         };
         state_a.db.create_message(&msg_obj).unwrap();
 
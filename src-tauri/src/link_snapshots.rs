@@ -0,0 +1,633 @@
+// src-tauri/src/link_snapshots.rs
+// Link archiving (opt-in): when a text message carries web links, each page is fetched in the
+// background and saved as a small self-contained HTML file (title, address, date and the page's
+// main text) under the shared storage dir, with a link_snapshots row tied to the message. The
+// conversation keeps its context when the page later changes or disappears. Addresses on the
+// LAN (peers' file links, routers) are never fetched: names are resolved and checked before
+// connecting, the connection goes to the checked addresses, and every redirect is checked again.
+
+use crate::commands::AppState;
+use crate::db::{generate_id, now, Database, LinkSnapshot};
+use crate::http_client;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "link_snapshots";
+/// Pages are cut off here; the text that matters comes early
+const MAX_PAGE_BYTES: u64 = 5 * 1024 * 1024;
+const MAX_TEXT_CHARS: usize = 100_000;
+const EXCERPT_CHARS: usize = 280;
+const MAX_REDIRECTS: usize = 5;
+/// Their contents are never part of the readable text
+const SKIPPED_ELEMENTS: [&str; 10] = [
+    "script", "style", "noscript", "svg", "template", "iframe", "nav", "header", "footer", "form",
+];
+/// Elements that start a new line of text
+const BLOCK_ELEMENTS: [&str; 20] = [
+    "p",
+    "div",
+    "br",
+    "li",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "tr",
+    "section",
+    "article",
+    "blockquote",
+    "pre",
+    "dd",
+    "dt",
+    "table",
+    "figcaption",
+    "hr",
+];
+
+/// One page at a time; a pasted list of links shouldn't open a dozen connections
+static WORKER: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkSnapshotConfig {
+    pub enabled: bool,
+    /// Also archive links in messages we receive, not just the ones we send. Off by default:
+    /// anyone who can message us could otherwise make this device fetch pages of their choosing.
+    #[serde(default)]
+    pub include_received: bool,
+    /// Links archived per message
+    #[serde(default = "default_max_links")]
+    pub max_links: usize,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_max_links() -> usize {
+    3
+}
+
+fn default_timeout() -> u64 {
+    20
+}
+
+impl Default for LinkSnapshotConfig {
+    fn default() -> Self {
+        LinkSnapshotConfig {
+            enabled: false,
+            include_received: false,
+            max_links: default_max_links(),
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+pub fn load(db: &Database) -> LinkSnapshotConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+/// http(s) links in a message, without the punctuation around them, first occurrence only
+fn find_links(text: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for word in text.split(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '"') {
+        let Some(start) = word.find("http://").or_else(|| word.find("https://")) else {
+            continue;
+        };
+        let link =
+            word[start..].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}', '\'']);
+        if link.len() > "https://".len() && !links.iter().any(|l| l == link) {
+            links.push(link.to_string());
+        }
+    }
+    links
+}
+
+fn is_local_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Carrier-grade NAT (100.64.0.0/10)
+        || (a == 100 && b & 0xc0 == 64)
+}
+
+/// Whether an address is on the LAN or this machine (v4-mapped and NAT64 v6 addresses are
+/// judged by the v4 address inside)
+fn is_local_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_local_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_local_v4(v4);
+            }
+            let segments = ip.segments();
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., a, b, c, d] = ip.octets();
+                return is_local_v4(Ipv4Addr::new(a, b, c, d));
+            }
+            let first = segments[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+        }
+    }
+}
+
+/// The link's address as an IP when it's written as one (any form the URL parser accepts, e.g.
+/// 0x7f.1 or 2130706433)
+fn host_ip(url: &Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether a link points into the LAN or at this machine by its address alone, which is never
+/// fetched (names that resolve there are caught in `resolve_public`)
+fn is_local(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return true;
+    };
+    if let Some(ip) = host_ip(&url) {
+        return is_local_ip(ip);
+    }
+    let host = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    host.is_empty()
+        || host == "localhost"
+        || host.ends_with(".localhost")
+        || host.ends_with(".local")
+        || !host.contains('.')
+}
+
+/// Where to connect for `url`: every address its host resolves to, refused when any of them is
+/// local. Connecting to exactly these keeps a second lookup from pointing somewhere else.
+fn resolve_public(url: &Url) -> Result<Vec<SocketAddr>, String> {
+    if !matches!(url.scheme(), "http" | "https") || is_local(url.as_str()) {
+        return Err("Links into the local network aren't archived".to_string());
+    }
+    let host = url.host_str().ok_or("The link has no host")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = match host_ip(url) {
+        Some(ip) => vec![SocketAddr::new(ip, port)],
+        None => (host, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("Can't resolve {}: {}", host, e))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(format!("Can't resolve {}", host));
+    }
+    if addrs.iter().any(|a| is_local_ip(a.ip())) {
+        return Err(format!("{} points into the local network", host));
+    }
+    Ok(addrs)
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| (&rest[1..end], end));
+        let decoded = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => name.strip_prefix('#').and_then(|d| d.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (decoded, entity) {
+            (Some(c), Some((_, end))) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Byte range of the content inside the first `<tag ...>` and the last `</tag>`
+fn element_contents(lower: &str, tag: &str) -> Option<(usize, usize)> {
+    let open = lower.find(&format!("<{}", tag))?;
+    let after = lower.as_bytes().get(open + tag.len() + 1)?;
+    if !(after.is_ascii_whitespace() || *after == b'>') {
+        return None;
+    }
+    let start = open + lower[open..].find('>')? + 1;
+    let end = lower.rfind(&format!("</{}", tag)).filter(|&e| e >= start)?;
+    Some((start, end))
+}
+
+fn tag_name(tag: &str) -> &str {
+    let tag = tag.trim_start_matches('/');
+    let end = tag
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(tag.len());
+    &tag[..end]
+}
+
+/// The page's title and its readable text, one paragraph per line. Prefers the <article> or
+/// <main> part of the page and leaves out scripts, styles and navigation.
+fn extract(html: &str) -> (Option<String>, String) {
+    // ASCII lowercasing keeps byte offsets, so positions found in `lower` index `html` too
+    let lower = html.to_ascii_lowercase();
+    let title = element_contents(&lower, "title")
+        .map(|(start, end)| collapse(&decode_entities(&html[start..end])))
+        .filter(|t| !t.is_empty());
+    let (start, end) = ["article", "main", "body"]
+        .iter()
+        .find_map(|tag| element_contents(&lower, tag))
+        .unwrap_or((0, html.len()));
+
+    let mut text = String::new();
+    let mut pos = start;
+    while pos < end {
+        let Some(open) = lower[pos..end].find('<').map(|i| pos + i) else {
+            text.push_str(&html[pos..end]);
+            break;
+        };
+        text.push_str(&html[pos..open]);
+        if lower[open..].starts_with("<!--") {
+            pos = lower[open..].find("-->").map_or(end, |i| open + i + 3);
+            continue;
+        }
+        let Some(close) = lower[open..].find('>').map(|i| open + i) else {
+            break;
+        };
+        let name = tag_name(&lower[open + 1..close]);
+        pos = close + 1;
+        if SKIPPED_ELEMENTS.contains(&name) && !lower[open + 1..].starts_with('/') {
+            pos = lower[pos..]
+                .find(&format!("</{}", name))
+                .and_then(|i| lower[pos + i..].find('>').map(|j| pos + i + j + 1))
+                .unwrap_or(end);
+        } else if BLOCK_ELEMENTS.contains(&name) {
+            text.push('\n');
+        } else if name == "td" || name == "th" {
+            text.push(' ');
+        }
+    }
+
+    let text: Vec<String> = decode_entities(&text)
+        .lines()
+        .map(collapse)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let mut text = text.join("\n");
+    if let Some((cut, _)) = text.char_indices().nth(MAX_TEXT_CHARS) {
+        text.truncate(cut);
+    }
+    (title, text)
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The snapshot file: no scripts, no outside resources, opens anywhere
+fn render(url: &str, title: Option<&str>, text: &str, saved_at: &str) -> String {
+    let heading = escape(title.unwrap_or(url));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title></head><body>\n\
+         <p><a href=\"{}\">{}</a><br>Saved {}</p>\n<h1>{}</h1>\n",
+        heading,
+        escape(url),
+        escape(url),
+        escape(saved_at),
+        heading
+    );
+    for paragraph in text.lines() {
+        html.push_str(&format!("<p>{}</p>\n", escape(paragraph)));
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// GET `url` on the addresses `resolve_public` checked, following redirects by hand so each
+/// hop is checked too
+fn get_public(
+    db: &Database,
+    url: &str,
+    timeout_secs: u64,
+) -> Result<reqwest::blocking::Response, String> {
+    let mut url = Url::parse(url).map_err(|e| format!("Invalid link: {}", e))?;
+    for _ in 0..=MAX_REDIRECTS {
+        let addrs = resolve_public(&url)?;
+        let mut builder = http_client::builder(db)?
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(Duration::from_secs(timeout_secs.max(1)));
+        if host_ip(&url).is_none() {
+            builder = builder.resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs);
+        }
+        let response = builder
+            .build()
+            .map_err(|e| e.to_string())?
+            .get(url.clone())
+            .send()
+            .map_err(|e| format!("Fetching the page failed: {}", e))?;
+        if !response.status().is_redirection() {
+            return Ok(response);
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or("The site redirected nowhere")?;
+        url = url
+            .join(location)
+            .map_err(|e| format!("Bad redirect: {}", e))?;
+    }
+    Err("Too many redirects".to_string())
+}
+
+fn fetch(db: &Database, url: &str, timeout_secs: u64) -> Result<String, String> {
+    let response = get_public(db, url, timeout_secs)?;
+    if !response.status().is_success() {
+        return Err(format!("The site answered {}", response.status()));
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/html")
+        .to_ascii_lowercase();
+    if !content_type.contains("html") {
+        return Err(format!("Not a web page ({})", content_type));
+    }
+    let mut body = Vec::new();
+    response
+        .take(MAX_PAGE_BYTES)
+        .read_to_end(&mut body)
+        .map_err(|e| format!("Reading the page failed: {}", e))?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+fn snapshot_dir(storage_dir: PathBuf) -> PathBuf {
+    storage_dir.join("link_snapshots")
+}
+
+/// Fetch `url` and save its snapshot for `message_id`; the record is kept even when it fails
+fn archive(
+    db: &Database,
+    dir: &std::path::Path,
+    message_id: &str,
+    url: &str,
+    timeout_secs: u64,
+) -> LinkSnapshot {
+    let _running = WORKER.lock().unwrap_or_else(|e| e.into_inner());
+    let mut snapshot = LinkSnapshot {
+        id: generate_id(),
+        message_id: message_id.to_string(),
+        url: url.to_string(),
+        title: None,
+        excerpt: None,
+        path: None,
+        status: "saved".to_string(),
+        error: None,
+        created_at: now(),
+    };
+    let saved = fetch(db, url, timeout_secs).and_then(|page| {
+        let (title, text) = extract(&page);
+        let path = dir.join(format!("{}.html", snapshot.id));
+        std::fs::create_dir_all(dir)
+            .and_then(|_| {
+                std::fs::write(
+                    &path,
+                    render(url, title.as_deref(), &text, &snapshot.created_at),
+                )
+            })
+            .map_err(|e| format!("Saving the snapshot failed: {}", e))?;
+        snapshot.excerpt = Some(text.chars().take(EXCERPT_CHARS).collect());
+        snapshot.title = title;
+        snapshot.path = Some(path.to_string_lossy().into_owned());
+        Ok(())
+    });
+    if let Err(e) = saved {
+        warn!("No snapshot of {}: {}", url, e);
+        snapshot.status = "failed".to_string();
+        snapshot.error = Some(e);
+    } else {
+        info!("Saved a snapshot of {}", url);
+    }
+    if let Err(e) = db.save_link_snapshot(&snapshot) {
+        warn!("Recording the snapshot of {} failed: {}", url, e);
+    }
+    snapshot
+}
+
+/// Called for every text message stored, sent (`incoming` false) or received; archives its
+/// links in the background when the feature is on
+pub fn queue(state: &AppState, message_id: &str, content: &str, incoming: bool) {
+    let config = load(&state.db);
+    if !config.enabled || (incoming && !config.include_received) {
+        return;
+    }
+    let links: Vec<String> = find_links(content)
+        .into_iter()
+        .filter(|l| !is_local(l))
+        .take(config.max_links)
+        .collect();
+    if links.is_empty() {
+        return;
+    }
+    let db: Arc<Database> = state.db.clone();
+    let dir = snapshot_dir(state.file_server.get_storage_dir());
+    let message_id = message_id.to_string();
+    std::thread::spawn(move || {
+        for url in links {
+            archive(&db, &dir, &message_id, &url, config.timeout_secs);
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_link_snapshots(
+    state: State<AppState>,
+    message_id: String,
+) -> Result<Vec<LinkSnapshot>, String> {
+    state
+        .db
+        .get_link_snapshots(&message_id)
+        .map_err(|e| e.to_string())
+}
+
+/// The saved HTML of a snapshot, for showing it in a sandboxed frame
+#[tauri::command]
+pub fn read_link_snapshot(state: State<AppState>, id: String) -> Result<String, String> {
+    let snapshot = state
+        .db
+        .get_link_snapshot(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Snapshot not found")?;
+    let path = snapshot.path.ok_or("The page couldn't be saved")?;
+    std::fs::read_to_string(&path).map_err(|e| format!("Can't read the snapshot: {}", e))
+}
+
+/// Archive a link now (e.g. again, or with the feature off); returns the new snapshot
+#[tauri::command]
+pub async fn snapshot_link<R: Runtime>(
+    app: AppHandle<R>,
+    message_id: String,
+    url: String,
+) -> Result<LinkSnapshot, String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("Only web links can be archived".to_string());
+    }
+    if is_local(&url) {
+        return Err("Links into the local network aren't archived".to_string());
+    }
+    let state = app.state::<AppState>();
+    let db = state.db.clone();
+    let dir = snapshot_dir(state.file_server.get_storage_dir());
+    let timeout_secs = load(&db).timeout_secs;
+    tauri::async_runtime::spawn_blocking(move || {
+        archive(&db, &dir, &message_id, &url, timeout_secs)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_link_snapshot(state: State<AppState>, id: String) -> Result<(), String> {
+    if let Some(path) = state
+        .db
+        .get_link_snapshot(&id)
+        .map_err(|e| e.to_string())?
+        .and_then(|s| s.path)
+    {
+        let _ = std::fs::remove_file(path);
+    }
+    state
+        .db
+        .delete_link_snapshot(&id)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_link_snapshot_config(state: State<AppState>) -> LinkSnapshotConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_link_snapshot_config(
+    state: State<AppState>,
+    config: LinkSnapshotConfig,
+) -> Result<(), String> {
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("Link archiving enabled: {}", config.enabled);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_and_page_text() {
+        assert_eq!(
+            find_links("see (https://example.com/a?b=1), and http://192.168.1.20:8080/file/x."),
+            [
+                "https://example.com/a?b=1",
+                "http://192.168.1.20:8080/file/x"
+            ]
+        );
+        assert!(is_local("http://192.168.1.20:8080/file/x?token=t"));
+        assert!(is_local("http://localhost:3000/"));
+        assert!(is_local("http://[fe80::1]/"));
+        assert!(is_local("http://printer/"));
+        assert!(!is_local("https://example.com/a"));
+        assert!(!is_local("https://user@93.184.216.34:443/"));
+        // Other spellings of loopback and private addresses
+        assert!(is_local("http://0x7f.1/"));
+        assert!(is_local("http://127.1:8080/"));
+        assert!(is_local("http://2130706433/"));
+        assert!(is_local("http://[::ffff:127.0.0.1]/"));
+        assert!(is_local("http://[::ffff:192.168.1.1]/"));
+        assert!(is_local("http://[64:ff9b::10.0.0.1]/"));
+        assert!(is_local("http://100.64.0.1/"));
+        assert!(is_local("http://router.localhost./"));
+        assert!(is_local("file:///etc/passwd"));
+        // Names are resolved and their addresses checked before connecting
+        assert!(resolve_public(&Url::parse("http://localhost:3000/").unwrap()).is_err());
+        assert!(resolve_public(&Url::parse("ftp://example.com/").unwrap()).is_err());
+        assert_eq!(
+            resolve_public(&Url::parse("https://93.184.216.34/").unwrap()).unwrap(),
+            ["93.184.216.34:443".parse::<SocketAddr>().unwrap()]
+        );
+        assert!(!LinkSnapshotConfig::default().include_received);
+
+        let page = "<html><head><title>Release notes &amp; fixes</title>\
+            <script>var x = '<p>no</p>';</script></head><body><nav>Home | About</nav>\
+            <article><h1>Version 2</h1><p>Faster   sync,<br>fewer&nbsp;crashes &#8212; finally.</p>\
+            <!-- ad --><style>p{}</style><p>Thanks <b>everyone</b>!</p></article></body></html>";
+        let (title, text) = extract(page);
+        assert_eq!(title.as_deref(), Some("Release notes & fixes"));
+        assert_eq!(
+            text,
+            "Version 2\nFaster sync,\nfewer crashes \u{2014} finally.\nThanks everyone!"
+        );
+        let html = render(
+            "https://example.com/?a=<b>",
+            title.as_deref(),
+            &text,
+            "today",
+        );
+        assert!(html.contains("<p>Faster sync,</p>"));
+        assert!(html.contains("?a=&lt;b&gt;"));
+        assert!(!html.contains("<script"));
+    }
+}
@@ -0,0 +1,142 @@
+// src-tauri/src/logging.rs
+// Structured logging: stdout, a daily-rotated file in the app data dir, and an in-memory
+// tail for in-app diagnostics. The level can be changed at runtime.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use tauri::State;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+pub(crate) const LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+const DEFAULT_LEVEL: &str = "info";
+const MAX_LOG_FILES: usize = 7;
+const RECENT_CAPACITY: usize = 2000;
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+// Keeps the background file writer alive for the life of the process
+static FILE_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Feeds formatted lines into the in-memory tail; fmt writes one whole event per call
+struct RecentWriter;
+
+impl io::Write for RecentWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf).trim_end().to_string();
+        let mut recent = RECENT.lock().unwrap();
+        if recent.len() >= RECENT_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(line);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn log_dir() -> PathBuf {
+    Database::get_db_path()
+        .parent()
+        .map(|p| p.join("logs"))
+        .unwrap_or_else(|| PathBuf::from("logs"))
+}
+
+// Our own crate at `level`, dependencies only when they warn
+fn directive(level: &str) -> String {
+    format!("warn,pingo_lib={}", level)
+}
+
+/// Install the global subscriber; call once before anything logs. RUST_LOG overrides the default level.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(directive(DEFAULT_LEVEL)));
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let file_layer = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("pingo")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir())
+        .map(|appender| {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let _ = FILE_GUARD.set(guard);
+            fmt::layer().with_ansi(false).with_writer(writer)
+        })
+        .map_err(|e| eprintln!("[Pingo] Log file unavailable: {}", e))
+        .ok();
+
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(file_layer)
+        .with(fmt::layer().with_ansi(false).with_writer(|| RecentWriter))
+        .try_init();
+    if result.is_ok() {
+        let _ = FILTER.set(handle);
+    }
+}
+
+pub(crate) fn apply_level(level: &str) -> Result<(), String> {
+    let handle = FILTER.get().ok_or("Logging is not initialized")?;
+    handle
+        .reload(EnvFilter::new(directive(level)))
+        .map_err(|e| e.to_string())
+}
+
+/// Restore the level chosen in settings (the DB isn't open yet when `init` runs)
+pub fn apply_saved_level(db: &Database) {
+    if std::env::var("RUST_LOG").is_ok() {
+        return;
+    }
+    if let Ok(Some(level)) = db.get_setting("log_level") {
+        if LEVELS.contains(&level.as_str()) {
+            let _ = apply_level(&level);
+        }
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_log_level(state: State<AppState>) -> Result<String, String> {
+    Ok(state
+        .db
+        .get_setting("log_level")
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| DEFAULT_LEVEL.to_string()))
+}
+
+/// Change the level immediately and remember it for next launch
+#[tauri::command]
+pub fn set_log_level(state: State<AppState>, level: String) -> Result<(), String> {
+    let level = level.trim().to_lowercase();
+    if !LEVELS.contains(&level.as_str()) {
+        return Err(format!("Unknown log level: {}", level));
+    }
+    apply_level(&level)?;
+    state
+        .db
+        .set_setting("log_level", &level)
+        .map_err(|e| e.to_string())?;
+    tracing::info!("Log level set to {}", level);
+    Ok(())
+}
+
+/// Most recent log lines, oldest first
+#[tauri::command]
+pub fn get_recent_logs(limit: Option<usize>) -> Vec<String> {
+    let recent = RECENT.lock().unwrap();
+    let limit = limit.unwrap_or(500).min(recent.len());
+    recent.iter().skip(recent.len() - limit).cloned().collect()
+}
@@ -0,0 +1,742 @@
+// src-tauri/src/meeting.rs
+// Native meetings: the backend owns the WebRTC peer connections (webrtc-rs) instead of the
+// webview. Meetings are a full mesh: a joiner asks the host to join (the roster's waiting room,
+// see meeting_roster) and connects to it once admitted, the host's answer lists the other
+// participants, and the joiner connects to each of them. Local media is written
+// into tracks by the frontend; remote media, data channel messages and connection state
+// come back as events.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::db::generate_id;
+use crate::ice_servers;
+use crate::remote_control;
+use crate::signaling::SignalingMessage;
+use base64::Engine;
+use bytes::Bytes;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use webrtc::api::interceptor_registry::register_default_interceptors;
+use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_OPUS, MIME_TYPE_VP8};
+use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
+use webrtc::interceptor::registry::Registry;
+use webrtc::media::Sample;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
+use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
+use webrtc::track::track_local::TrackLocal;
+use webrtc::track::track_remote::TrackRemote;
+
+const DATA_CHANNEL_LABEL: &str = "pingo-meeting";
+const STREAM_ID: &str = "pingo";
+
+struct PeerLink {
+    pc: Arc<RTCPeerConnection>,
+    /// The offerer creates the channel; the answerer gets it through on_data_channel
+    channel: Option<Arc<RTCDataChannel>>,
+    /// Candidates that arrived before the remote description
+    pending_candidates: Vec<RTCIceCandidateInit>,
+}
+
+struct Meeting {
+    host_id: String,
+    peers: HashMap<String, PeerLink>,
+    tracks: HashMap<String, Arc<TrackLocalStaticSample>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeetingPeer {
+    pub peer_id: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeetingInfo {
+    pub meeting_id: String,
+    pub host_id: String,
+    pub is_host: bool,
+    pub peers: Vec<MeetingPeer>,
+    /// Ids of our own tracks
+    pub tracks: Vec<String>,
+}
+
+static MEETINGS: OnceLock<Mutex<HashMap<String, Meeting>>> = OnceLock::new();
+
+fn meetings() -> &'static Mutex<HashMap<String, Meeting>> {
+    MEETINGS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn err(e: impl std::fmt::Display) -> String {
+    e.to_string()
+}
+
+fn send<R: Runtime>(app: &AppHandle<R>, peer_id: &str, msg: &SignalingMessage) {
+    let state = app.state::<AppState>();
+    if let Err(e) = send_to_peer(&state, peer_id, msg) {
+        warn!("Meeting signal to {} failed: {}", peer_id, e);
+    }
+}
+
+async fn info<R: Runtime>(app: &AppHandle<R>, meeting_id: &str) -> Result<MeetingInfo, String> {
+    let all = meetings().lock().await;
+    let meeting = all.get(meeting_id).ok_or("Not in this meeting")?;
+    let mut peers = Vec::new();
+    for (peer_id, link) in &meeting.peers {
+        peers.push(MeetingPeer {
+            peer_id: peer_id.clone(),
+            state: link.pc.connection_state().to_string(),
+        });
+    }
+    Ok(MeetingInfo {
+        meeting_id: meeting_id.to_string(),
+        host_id: meeting.host_id.clone(),
+        is_host: meeting.host_id == app.state::<AppState>().device_id(),
+        peers,
+        tracks: meeting.tracks.keys().cloned().collect(),
+    })
+}
+
+fn attach_channel<R: Runtime>(
+    app: &AppHandle<R>,
+    meeting_id: &str,
+    peer_id: &str,
+    channel: &Arc<RTCDataChannel>,
+) {
+    let (app, meeting_id, peer_id) = (app.clone(), meeting_id.to_string(), peer_id.to_string());
+    channel.on_message(Box::new(move |msg: DataChannelMessage| {
+        let _ = app.emit(
+            "meeting-data",
+            serde_json::json!({
+                "meeting_id": meeting_id,
+                "peer_id": peer_id,
+                "data": String::from_utf8_lossy(&msg.data),
+            }),
+        );
+        Box::pin(async {})
+    }));
+}
+
+/// Relay a remote track's RTP payloads to the frontend until the track ends
+async fn forward_track<R: Runtime>(
+    app: AppHandle<R>,
+    meeting_id: String,
+    peer_id: String,
+    track: Arc<TrackRemote>,
+) {
+    let track_id = track.id();
+    let _ = app.emit(
+        "meeting-track",
+        serde_json::json!({
+            "meeting_id": meeting_id,
+            "peer_id": peer_id,
+            "track_id": track_id,
+            "kind": track.kind().to_string(),
+            "mime_type": track.codec().capability.mime_type,
+        }),
+    );
+    while let Ok((packet, _)) = track.read_rtp().await {
+        let _ = app.emit(
+            "meeting-media",
+            serde_json::json!({
+                "meeting_id": meeting_id,
+                "peer_id": peer_id,
+                "track_id": track_id,
+                "timestamp": packet.header.timestamp,
+                "payload": base64::engine::general_purpose::STANDARD.encode(&packet.payload),
+            }),
+        );
+    }
+}
+
+/// A peer connection wired to signaling and events, with our current tracks attached
+async fn new_peer_connection<R: Runtime>(
+    app: &AppHandle<R>,
+    meeting_id: &str,
+    peer_id: &str,
+    tracks: &[Arc<TrackLocalStaticSample>],
+) -> Result<Arc<RTCPeerConnection>, String> {
+    let mut media = MediaEngine::default();
+    media.register_default_codecs().map_err(err)?;
+    let registry = register_default_interceptors(Registry::new(), &mut media).map_err(err)?;
+    let api = APIBuilder::new()
+        .with_media_engine(media)
+        .with_interceptor_registry(registry)
+        .build();
+    let pc = Arc::new(
+        api.new_peer_connection(ice_servers::rtc_configuration(&app.state::<AppState>().db))
+            .await
+            .map_err(err)?,
+    );
+
+    let (a, m, p) = (app.clone(), meeting_id.to_string(), peer_id.to_string());
+    pc.on_ice_candidate(Box::new(move |candidate: Option<RTCIceCandidate>| {
+        let (a, m, p) = (a.clone(), m.clone(), p.clone());
+        Box::pin(async move {
+            let Some(init) = candidate.and_then(|c| c.to_json().ok()) else {
+                return;
+            };
+            let msg = SignalingMessage::NativeMeetingCandidate {
+                from: a.state::<AppState>().device_id(),
+                to: p.clone(),
+                meeting_id: m,
+                candidate: init.candidate,
+                sdp_mid: init.sdp_mid,
+                sdp_mline_index: init.sdp_mline_index,
+            };
+            send(&a, &p, &msg);
+        })
+    }));
+
+    let (a, m, p) = (app.clone(), meeting_id.to_string(), peer_id.to_string());
+    pc.on_peer_connection_state_change(Box::new(move |state: RTCPeerConnectionState| {
+        info!("Meeting {} peer {}: {}", m, p, state);
+        let _ = a.emit(
+            "meeting-peer-state",
+            serde_json::json!({ "meeting_id": m, "peer_id": p, "state": state.to_string() }),
+        );
+        Box::pin(async {})
+    }));
+
+    let (a, m, p) = (app.clone(), meeting_id.to_string(), peer_id.to_string());
+    pc.on_data_channel(Box::new(move |channel: Arc<RTCDataChannel>| {
+        let (a, m, p) = (a.clone(), m.clone(), p.clone());
+        Box::pin(async move {
+            if channel.label() != DATA_CHANNEL_LABEL {
+                return;
+            }
+            attach_channel(&a, &m, &p, &channel);
+            if let Some(link) = meetings()
+                .lock()
+                .await
+                .get_mut(&m)
+                .and_then(|meeting| meeting.peers.get_mut(&p))
+            {
+                link.channel = Some(channel);
+            }
+        })
+    }));
+
+    let (a, m, p) = (app.clone(), meeting_id.to_string(), peer_id.to_string());
+    pc.on_track(Box::new(move |track: Arc<TrackRemote>, _, _| {
+        tokio::spawn(forward_track(a.clone(), m.clone(), p.clone(), track));
+        Box::pin(async {})
+    }));
+
+    for track in tracks {
+        pc.add_track(Arc::clone(track) as Arc<dyn TrackLocal + Send + Sync>)
+            .await
+            .map_err(err)?;
+    }
+    Ok(pc)
+}
+
+async fn send_offer<R: Runtime>(
+    app: &AppHandle<R>,
+    meeting_id: &str,
+    peer_id: &str,
+    pc: &RTCPeerConnection,
+) -> Result<(), String> {
+    let offer = pc.create_offer(None).await.map_err(err)?;
+    pc.set_local_description(offer.clone()).await.map_err(err)?;
+    let msg = SignalingMessage::NativeMeetingOffer {
+        from: app.state::<AppState>().device_id(),
+        to: peer_id.to_string(),
+        meeting_id: meeting_id.to_string(),
+        sdp: offer.sdp,
+    };
+    send(app, peer_id, &msg);
+    Ok(())
+}
+
+/// Open a connection to a participant as the offering side
+async fn connect_to<R: Runtime>(
+    app: &AppHandle<R>,
+    meeting_id: &str,
+    peer_id: &str,
+) -> Result<(), String> {
+    let tracks: Vec<_> = {
+        let all = meetings().lock().await;
+        let meeting = all.get(meeting_id).ok_or("Not in this meeting")?;
+        if meeting.peers.contains_key(peer_id) {
+            return Ok(());
+        }
+        meeting.tracks.values().cloned().collect()
+    };
+    let pc = new_peer_connection(app, meeting_id, peer_id, &tracks).await?;
+    let channel = pc
+        .create_data_channel(DATA_CHANNEL_LABEL, None)
+        .await
+        .map_err(err)?;
+    attach_channel(app, meeting_id, peer_id, &channel);
+    {
+        let mut all = meetings().lock().await;
+        let Some(meeting) = all.get_mut(meeting_id) else {
+            let _ = pc.close().await;
+            return Err("Meeting was left".to_string());
+        };
+        meeting.peers.insert(
+            peer_id.to_string(),
+            PeerLink {
+                pc: Arc::clone(&pc),
+                channel: Some(channel),
+                pending_candidates: Vec::new(),
+            },
+        );
+    }
+    send_offer(app, meeting_id, peer_id, &pc).await
+}
+
+/// Apply candidates that arrived before the remote description
+async fn flush_candidates(meeting_id: &str, peer_id: &str, pc: &RTCPeerConnection) {
+    let pending = match meetings()
+        .lock()
+        .await
+        .get_mut(meeting_id)
+        .and_then(|m| m.peers.get_mut(peer_id))
+    {
+        Some(link) => std::mem::take(&mut link.pending_candidates),
+        None => return,
+    };
+    for candidate in pending {
+        if let Err(e) = pc.add_ice_candidate(candidate).await {
+            warn!("Adding ICE candidate from {} failed: {}", peer_id, e);
+        }
+    }
+}
+
+async fn handle_offer<R: Runtime>(
+    app: &AppHandle<R>,
+    from: &str,
+    meeting_id: &str,
+    sdp: String,
+) -> Result<(), String> {
+    let local_id = app.state::<AppState>().device_id();
+    let (existing, tracks, participants) = {
+        let all = meetings().lock().await;
+        let meeting = all
+            .get(meeting_id)
+            .ok_or("offer for a meeting we're not in")?;
+        let participants: Vec<String> = if meeting.host_id == local_id {
+            meeting
+                .peers
+                .keys()
+                .filter(|p| *p != from)
+                .cloned()
+                .collect()
+        } else {
+            Vec::new()
+        };
+        (
+            meeting.peers.get(from).map(|l| Arc::clone(&l.pc)),
+            meeting.tracks.values().cloned().collect::<Vec<_>>(),
+            participants,
+        )
+    };
+    let renegotiation = existing.is_some();
+    if !renegotiation && !participants.is_empty() {
+        announce_participants(app, &local_id, meeting_id, &participants);
+    }
+    let pc = match existing {
+        Some(pc) => pc,
+        None => {
+            let pc = new_peer_connection(app, meeting_id, from, &tracks).await?;
+            let mut all = meetings().lock().await;
+            let meeting = all.get_mut(meeting_id).ok_or("Meeting was left")?;
+            meeting.peers.insert(
+                from.to_string(),
+                PeerLink {
+                    pc: Arc::clone(&pc),
+                    channel: None,
+                    pending_candidates: Vec::new(),
+                },
+            );
+            pc
+        }
+    };
+
+    pc.set_remote_description(RTCSessionDescription::offer(sdp).map_err(err)?)
+        .await
+        .map_err(err)?;
+    flush_candidates(meeting_id, from, &pc).await;
+    let answer = pc.create_answer(None).await.map_err(err)?;
+    pc.set_local_description(answer.clone())
+        .await
+        .map_err(err)?;
+    let msg = SignalingMessage::NativeMeetingAnswer {
+        from: local_id,
+        to: from.to_string(),
+        meeting_id: meeting_id.to_string(),
+        sdp: answer.sdp,
+        participants: if renegotiation {
+            Vec::new()
+        } else {
+            participants
+        },
+    };
+    send(app, from, &msg);
+    Ok(())
+}
+
+/// Host: tell the participants already connected who's in the meeting now, so they take the
+/// newcomer's offers
+fn announce_participants<R: Runtime>(
+    app: &AppHandle<R>,
+    local_id: &str,
+    meeting_id: &str,
+    connected: &[String],
+) {
+    let state = app.state::<AppState>();
+    let Some(roster) = state.meeting_rosters.get(meeting_id) else {
+        return;
+    };
+    let participants: Vec<String> = roster
+        .participants
+        .iter()
+        .map(|p| p.peer_id.clone())
+        .collect();
+    for peer_id in connected {
+        let msg = SignalingMessage::MeetingParticipantList {
+            from: local_id.to_string(),
+            to: peer_id.clone(),
+            meeting_id: meeting_id.to_string(),
+            participants: participants.clone(),
+        };
+        send(app, peer_id, &msg);
+    }
+}
+
+async fn handle_answer<R: Runtime>(
+    app: &AppHandle<R>,
+    from: &str,
+    meeting_id: &str,
+    sdp: String,
+    participants: Vec<String>,
+) -> Result<(), String> {
+    let pc = meetings()
+        .lock()
+        .await
+        .get(meeting_id)
+        .and_then(|m| m.peers.get(from))
+        .map(|l| Arc::clone(&l.pc))
+        .ok_or("answer from a peer we didn't call")?;
+    pc.set_remote_description(RTCSessionDescription::answer(sdp).map_err(err)?)
+        .await
+        .map_err(err)?;
+    flush_candidates(meeting_id, from, &pc).await;
+
+    let local_id = app.state::<AppState>().device_id();
+    for peer_id in participants.into_iter().filter(|p| *p != local_id) {
+        if let Err(e) = connect_to(app, meeting_id, &peer_id).await {
+            warn!("Connecting to participant {} failed: {}", peer_id, e);
+        }
+    }
+    Ok(())
+}
+
+async fn handle_candidate(
+    from: &str,
+    meeting_id: &str,
+    candidate: RTCIceCandidateInit,
+) -> Result<(), String> {
+    let pc = {
+        let mut all = meetings().lock().await;
+        let link = all
+            .get_mut(meeting_id)
+            .and_then(|m| m.peers.get_mut(from))
+            .ok_or("candidate from an unknown peer")?;
+        if link.pc.remote_description().await.is_none() {
+            link.pending_candidates.push(candidate);
+            return Ok(());
+        }
+        Arc::clone(&link.pc)
+    };
+    pc.add_ice_candidate(candidate).await.map_err(err)
+}
+
+async fn handle_leave<R: Runtime>(app: &AppHandle<R>, from: &str, meeting_id: &str) {
+    let link = meetings()
+        .lock()
+        .await
+        .get_mut(meeting_id)
+        .and_then(|m| m.peers.remove(from));
+    app.state::<AppState>()
+        .meeting_rosters
+        .remove_participant(meeting_id, from);
+    if let Some(link) = link {
+        let _ = link.pc.close().await;
+        let _ = app.emit(
+            "meeting-peer-state",
+            serde_json::json!({ "meeting_id": meeting_id, "peer_id": from, "state": "left" }),
+        );
+    }
+}
+
+/// Handle native meeting signaling (called from the signaling forwarder)
+pub fn handle_signal<R: Runtime>(app: &AppHandle<R>, msg: SignalingMessage) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = match msg {
+            SignalingMessage::NativeMeetingOffer {
+                from,
+                meeting_id,
+                sdp,
+                ..
+            } => handle_offer(&app, &from, &meeting_id, sdp).await,
+            SignalingMessage::NativeMeetingAnswer {
+                from,
+                meeting_id,
+                sdp,
+                participants,
+                ..
+            } => handle_answer(&app, &from, &meeting_id, sdp, participants).await,
+            SignalingMessage::NativeMeetingCandidate {
+                from,
+                meeting_id,
+                candidate,
+                sdp_mid,
+                sdp_mline_index,
+                ..
+            } => {
+                let init = RTCIceCandidateInit {
+                    candidate,
+                    sdp_mid,
+                    sdp_mline_index,
+                    username_fragment: None,
+                };
+                handle_candidate(&from, &meeting_id, init).await
+            }
+            SignalingMessage::NativeMeetingLeave {
+                from, meeting_id, ..
+            } => {
+                handle_leave(&app, &from, &meeting_id).await;
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!("Meeting signaling: {}", e);
+        }
+    });
+}
+
+/// The host's participant list arrived: it admitted us, so connect to it if we haven't yet
+/// (called from the signaling forwarder)
+pub fn on_participant_list<R: Runtime>(app: &AppHandle<R>, from: &str, meeting_id: &str) {
+    let app = app.clone();
+    let (from, meeting_id) = (from.to_string(), meeting_id.to_string());
+    tauri::async_runtime::spawn(async move {
+        let joining = meetings()
+            .lock()
+            .await
+            .get(&meeting_id)
+            .is_some_and(|m| m.host_id == from && !m.peers.contains_key(&from));
+        if joining {
+            if let Err(e) = connect_to(&app, &meeting_id, &from).await {
+                warn!("Connecting to meeting host {} failed: {}", from, e);
+            }
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+/// Start a meeting hosted by this device; others join with its id
+#[tauri::command]
+pub async fn create_meeting<R: Runtime>(app: AppHandle<R>) -> Result<MeetingInfo, String> {
+    let meeting_id = generate_id();
+    let state = app.state::<AppState>();
+    let local_id = state.device_id();
+    state.meeting_rosters.host(&meeting_id, &local_id);
+    meetings().lock().await.insert(
+        meeting_id.clone(),
+        Meeting {
+            host_id: local_id,
+            peers: HashMap::new(),
+            tracks: HashMap::new(),
+        },
+    );
+    info!("Created native meeting {}", meeting_id);
+    info(&app, &meeting_id).await
+}
+
+/// Ask the host to join a meeting. Once it admits us (right away when invited, else from its
+/// waiting room) we connect to it, and to the other participants as it reports them.
+#[tauri::command]
+pub async fn join_meeting<R: Runtime>(
+    app: AppHandle<R>,
+    meeting_id: String,
+    host_id: String,
+) -> Result<MeetingInfo, String> {
+    meetings()
+        .lock()
+        .await
+        .entry(meeting_id.clone())
+        .or_insert_with(|| Meeting {
+            host_id: host_id.clone(),
+            peers: HashMap::new(),
+            tracks: HashMap::new(),
+        });
+    let state = app.state::<AppState>();
+    let local_id = state.device_id();
+    let username = state
+        .db
+        .get_user(&local_id)
+        .ok()
+        .flatten()
+        .map(|u| u.username)
+        .unwrap_or_default();
+    let request = SignalingMessage::MeetingJoinRequest {
+        from: local_id.clone(),
+        to: host_id.clone(),
+        meeting_id: meeting_id.clone(),
+        username,
+    };
+    state.meeting_rosters.apply(&local_id, &request, true);
+    send_to_peer(&state, &host_id, &request)?;
+    info(&app, &meeting_id).await
+}
+
+#[tauri::command]
+pub async fn leave_meeting<R: Runtime>(
+    app: AppHandle<R>,
+    meeting_id: String,
+) -> Result<(), String> {
+    let Some(meeting) = meetings().lock().await.remove(&meeting_id) else {
+        return Ok(());
+    };
+    app.state::<AppState>().meeting_rosters.forget(&meeting_id);
+    let local_id = app.state::<AppState>().device_id();
+    for (peer_id, link) in meeting.peers {
+        let msg = SignalingMessage::NativeMeetingLeave {
+            from: local_id.clone(),
+            to: peer_id.clone(),
+            meeting_id: meeting_id.clone(),
+        };
+        send(&app, &peer_id, &msg);
+        let _ = link.pc.close().await;
+    }
+    // Control handed out during the meeting doesn't outlive it
+    remote_control::end_all(&app);
+    info!("Left native meeting {}", meeting_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_meeting<R: Runtime>(
+    app: AppHandle<R>,
+    meeting_id: String,
+) -> Result<MeetingInfo, String> {
+    info(&app, &meeting_id).await
+}
+
+/// Add an outgoing "audio" (Opus) or "video" (VP8) track and renegotiate with every peer.
+/// Returns the track id to pass to write_track_sample.
+#[tauri::command]
+pub async fn send_track<R: Runtime>(
+    app: AppHandle<R>,
+    meeting_id: String,
+    kind: String,
+) -> Result<String, String> {
+    let codec = match kind.as_str() {
+        "audio" => RTCRtpCodecCapability {
+            mime_type: MIME_TYPE_OPUS.to_string(),
+            clock_rate: 48000,
+            channels: 2,
+            ..Default::default()
+        },
+        "video" => RTCRtpCodecCapability {
+            mime_type: MIME_TYPE_VP8.to_string(),
+            clock_rate: 90000,
+            ..Default::default()
+        },
+        other => return Err(format!("Unknown track kind: {}", other)),
+    };
+    let track_id = format!("{}-{}", kind, generate_id());
+    let track = Arc::new(TrackLocalStaticSample::new(
+        codec,
+        track_id.clone(),
+        STREAM_ID.to_string(),
+    ));
+
+    let peers: Vec<(String, Arc<RTCPeerConnection>)> = {
+        let mut all = meetings().lock().await;
+        let meeting = all.get_mut(&meeting_id).ok_or("Not in this meeting")?;
+        meeting.tracks.insert(track_id.clone(), Arc::clone(&track));
+        meeting
+            .peers
+            .iter()
+            .map(|(id, link)| (id.clone(), Arc::clone(&link.pc)))
+            .collect()
+    };
+    for (peer_id, pc) in peers {
+        let added = pc
+            .add_track(Arc::clone(&track) as Arc<dyn TrackLocal + Send + Sync>)
+            .await;
+        let result = match added {
+            Ok(_) => send_offer(&app, &meeting_id, &peer_id, &pc).await,
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            warn!("Adding track for {} failed: {}", peer_id, e);
+        }
+    }
+    Ok(track_id)
+}
+
+/// Write one encoded frame (base64) to a local track
+#[tauri::command]
+pub async fn write_track_sample(
+    meeting_id: String,
+    track_id: String,
+    data: String,
+    duration_ms: u64,
+) -> Result<(), String> {
+    let track = meetings()
+        .lock()
+        .await
+        .get(&meeting_id)
+        .and_then(|m| m.tracks.get(&track_id))
+        .cloned()
+        .ok_or("Unknown track")?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(err)?;
+    track
+        .write_sample(&Sample {
+            data: Bytes::from(bytes),
+            duration: Duration::from_millis(duration_ms),
+            ..Default::default()
+        })
+        .await
+        .map_err(err)
+}
+
+/// Send a text message over every open data channel; returns how many peers got it
+#[tauri::command]
+pub async fn send_meeting_data(meeting_id: String, data: String) -> Result<usize, String> {
+    let channels: Vec<Arc<RTCDataChannel>> = meetings()
+        .lock()
+        .await
+        .get(&meeting_id)
+        .ok_or("Not in this meeting")?
+        .peers
+        .values()
+        .filter_map(|l| l.channel.clone())
+        .collect();
+    let mut sent = 0;
+    for channel in channels {
+        if channel.send_text(data.clone()).await.is_ok() {
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
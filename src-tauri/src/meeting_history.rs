@@ -0,0 +1,184 @@
+// src-tauri/src/meeting_history.rs
+// Meeting history: meetings and who attended them, recorded from the meeting signaling we
+// send and receive (invites, accepts, rejoins, leaves and ends).
+
+use crate::commands::AppState;
+use crate::db::{now, Database, MeetingParticipant, MeetingRecord};
+use crate::signaling::SignalingMessage;
+use chrono::DateTime;
+use serde::Serialize;
+use tauri::State;
+use tracing::warn;
+
+const DEFAULT_LIMIT: i64 = 50;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeetingHistoryEntry {
+    #[serde(flatten)]
+    pub meeting: MeetingRecord,
+    /// From start to end, or to the last participant leaving; None while it's still going
+    pub duration_secs: Option<i64>,
+    pub participants: Vec<MeetingParticipant>,
+}
+
+fn joined(db: &Database, meeting_id: &str, user_id: &str, username: Option<String>) {
+    let p = MeetingParticipant {
+        meeting_id: meeting_id.to_string(),
+        user_id: user_id.to_string(),
+        username,
+        joined_at: now(),
+        left_at: None,
+    };
+    if let Err(e) = db.meeting_participant_joined(&p) {
+        warn!("Recording meeting participant failed: {}", e);
+    }
+}
+
+fn started(db: &Database, meeting_id: &str, host_id: &str, host_name: &str) {
+    let m = MeetingRecord {
+        id: meeting_id.to_string(),
+        host_id: host_id.to_string(),
+        host_name: Some(host_name.to_string()).filter(|n| !n.is_empty()),
+        started_at: now(),
+        ended_at: None,
+        whiteboard_path: None,
+    };
+    if let Err(e) = db.upsert_meeting(&m) {
+        warn!("Recording meeting failed: {}", e);
+    }
+}
+
+/// Update history from a meeting message. `outgoing` is true for messages we send, where
+/// we're the one inviting, joining or leaving.
+pub fn record(db: &Database, local_id: &str, msg: &SignalingMessage, outgoing: bool) {
+    let actor = |from: &str| {
+        if outgoing {
+            local_id.to_string()
+        } else {
+            from.to_string()
+        }
+    };
+    match msg {
+        SignalingMessage::MeetingInvite {
+            from,
+            meeting_id,
+            host_name,
+            ..
+        } => {
+            started(db, meeting_id, &actor(from), host_name);
+            if outgoing {
+                joined(db, meeting_id, local_id, Some(host_name.clone()));
+            }
+        }
+        SignalingMessage::MeetingInviteResponse {
+            from,
+            meeting_id,
+            accepted: true,
+            username,
+            ..
+        } => joined(db, meeting_id, &actor(from), username.clone()),
+        SignalingMessage::MeetingRejoinRequest {
+            from,
+            meeting_id,
+            username,
+            ..
+        } => joined(db, meeting_id, &actor(from), Some(username.clone())),
+        SignalingMessage::MeetingLeave {
+            from, meeting_id, ..
+        } => {
+            let _ = db.meeting_participant_left(meeting_id, &actor(from), &now());
+        }
+        SignalingMessage::MeetingEnded { meeting_id, .. } => {
+            let _ = db.end_meeting(meeting_id, &now());
+        }
+        _ => {}
+    }
+}
+
+fn duration_secs(meeting: &MeetingRecord, participants: &[MeetingParticipant]) -> Option<i64> {
+    let end = match &meeting.ended_at {
+        Some(end) => end.clone(),
+        None if !participants.is_empty() && participants.iter().all(|p| p.left_at.is_some()) => {
+            participants
+                .iter()
+                .filter_map(|p| p.left_at.clone())
+                .max()?
+        }
+        None => return None,
+    };
+    let start = DateTime::parse_from_rfc3339(&meeting.started_at).ok()?;
+    let end = DateTime::parse_from_rfc3339(&end).ok()?;
+    Some((end - start).num_seconds().max(0))
+}
+
+pub fn history(db: &Database, limit: i64) -> Result<Vec<MeetingHistoryEntry>, String> {
+    let meetings = db.get_meetings(limit).map_err(|e| e.to_string())?;
+    meetings
+        .into_iter()
+        .map(|meeting| {
+            let participants = db
+                .get_meeting_participants(&meeting.id)
+                .map_err(|e| e.to_string())?;
+            Ok(MeetingHistoryEntry {
+                duration_secs: duration_secs(&meeting, &participants),
+                meeting,
+                participants,
+            })
+        })
+        .collect()
+}
+
+// ============ COMMANDS ============
+
+/// Past and ongoing meetings, newest first, with attendance
+#[tauri::command]
+pub fn get_meeting_history(
+    state: State<AppState>,
+    limit: Option<i64>,
+) -> Result<Vec<MeetingHistoryEntry>, String> {
+    history(&state.db, limit.unwrap_or(DEFAULT_LIMIT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_from_signaling() {
+        let db = Database::new_in_memory().unwrap();
+        let invite = SignalingMessage::MeetingInvite {
+            from: "me".to_string(),
+            to: "bob".to_string(),
+            meeting_id: "m1".to_string(),
+            host_name: "Me".to_string(),
+        };
+        record(&db, "me", &invite, true);
+        // Inviting a second person doesn't restart the meeting
+        record(&db, "me", &invite, true);
+        let accept = SignalingMessage::MeetingInviteResponse {
+            from: "bob".to_string(),
+            to: "me".to_string(),
+            meeting_id: "m1".to_string(),
+            accepted: true,
+            username: Some("Bob".to_string()),
+        };
+        record(&db, "me", &accept, false);
+
+        let entries = history(&db, 10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].meeting.host_id, "me");
+        assert_eq!(entries[0].participants.len(), 2);
+        assert!(entries[0].duration_secs.is_none());
+
+        let ended = SignalingMessage::MeetingEnded {
+            from: "me".to_string(),
+            to: "bob".to_string(),
+            meeting_id: "m1".to_string(),
+        };
+        record(&db, "me", &ended, true);
+        let entries = history(&db, 10).unwrap();
+        assert!(entries[0].meeting.ended_at.is_some());
+        assert!(entries[0].participants.iter().all(|p| p.left_at.is_some()));
+        assert!(entries[0].duration_secs.is_some());
+    }
+}
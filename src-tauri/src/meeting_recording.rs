@@ -0,0 +1,239 @@
+// src-tauri/src/meeting_recording.rs
+// Local meeting recordings: screen frames and the meeting chat are written to a timestamped
+// folder under downloads. Participants are told when someone starts or stops recording.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::screen_capture;
+use crate::signaling::SignalingMessage;
+use base64::Engine;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+const FRAME_INTERVAL: Duration = Duration::from_secs(2);
+const RECORDINGS_DIR: &str = "Meeting recordings";
+
+struct Recording {
+    dir: PathBuf,
+    started_at: String,
+    participants: Vec<String>,
+    chat_log: File,
+    /// Chat ids already written; our own messages are sent once per participant
+    seen_chat: HashSet<String>,
+    chat_messages: usize,
+    frames: usize,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingInfo {
+    pub meeting_id: String,
+    pub path: String,
+    pub started_at: String,
+    pub frames: usize,
+    pub chat_messages: usize,
+}
+
+static RECORDINGS: OnceLock<Mutex<HashMap<String, Recording>>> = OnceLock::new();
+
+fn recordings() -> &'static Mutex<HashMap<String, Recording>> {
+    RECORDINGS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn info_for(meeting_id: &str, rec: &Recording) -> RecordingInfo {
+    RecordingInfo {
+        meeting_id: meeting_id.to_string(),
+        path: rec.dir.to_string_lossy().to_string(),
+        started_at: rec.started_at.clone(),
+        frames: rec.frames,
+        chat_messages: rec.chat_messages,
+    }
+}
+
+/// Store one PNG frame in a recording; returns false if the meeting isn't being recorded
+fn write_frame(meeting_id: &str, png: &[u8]) -> Result<bool, String> {
+    let mut all = recordings().lock().unwrap();
+    let Some(rec) = all.get_mut(meeting_id) else {
+        return Ok(false);
+    };
+    let path = rec
+        .dir
+        .join("screen")
+        .join(format!("{:06}.png", rec.frames + 1));
+    fs::write(&path, png).map_err(|e| e.to_string())?;
+    rec.frames += 1;
+    Ok(true)
+}
+
+/// Append a meeting chat message to the recording, if there is one
+pub fn record_chat(meeting_id: &str, from: &str, chat: &serde_json::Value) {
+    let mut all = recordings().lock().unwrap();
+    let Some(rec) = all.get_mut(meeting_id) else {
+        return;
+    };
+    if let Some(id) = chat.get("id").and_then(|v| v.as_str()) {
+        if !rec.seen_chat.insert(id.to_string()) {
+            return;
+        }
+    }
+    let line = serde_json::json!({ "at": crate::db::now(), "from": from, "chat": chat });
+    match writeln!(rec.chat_log, "{}", line) {
+        Ok(()) => rec.chat_messages += 1,
+        Err(e) => warn!("Writing meeting chat to recording failed: {}", e),
+    }
+}
+
+fn announce<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    meeting_id: &str,
+    participants: &[String],
+    recording: bool,
+) {
+    for peer_id in participants {
+        let msg = SignalingMessage::MeetingRecording {
+            from: state.device_id(),
+            to: peer_id.clone(),
+            meeting_id: meeting_id.to_string(),
+            recording,
+        };
+        if let Err(e) = send_to_peer(state, peer_id, &msg) {
+            warn!("Recording notice to {} failed: {}", peer_id, e);
+        }
+    }
+    let _ = app.emit(
+        "meeting-recording-state",
+        serde_json::json!({
+            "meeting_id": meeting_id,
+            "from": state.device_id(),
+            "recording": recording,
+        }),
+    );
+}
+
+// ============ COMMANDS ============
+
+/// Start recording a meeting. With `display_index`, that display is captured every two
+/// seconds (for when we're the one sharing); other frames come in via record_meeting_frame.
+#[tauri::command]
+pub fn start_meeting_recording<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    meeting_id: String,
+    participants: Vec<String>,
+    display_index: Option<usize>,
+) -> Result<RecordingInfo, String> {
+    if recordings().lock().unwrap().contains_key(&meeting_id) {
+        return Err("This meeting is already being recorded".to_string());
+    }
+    let dir = state
+        .file_transfer
+        .get_downloads_dir()
+        .join(RECORDINGS_DIR)
+        .join(format!(
+            "{} {}",
+            chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"),
+            &meeting_id[..8.min(meeting_id.len())]
+        ));
+    fs::create_dir_all(dir.join("screen")).map_err(|e| e.to_string())?;
+    let chat_log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("chat.jsonl"))
+        .map_err(|e| e.to_string())?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let rec = Recording {
+        dir,
+        started_at: crate::db::now(),
+        participants: participants.clone(),
+        chat_log,
+        seen_chat: HashSet::new(),
+        chat_messages: 0,
+        frames: 0,
+        stop: Arc::clone(&stop),
+    };
+    let info = info_for(&meeting_id, &rec);
+    recordings().lock().unwrap().insert(meeting_id.clone(), rec);
+
+    if let Some(display_index) = display_index {
+        let meeting_id = meeting_id.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                match screen_capture::capture_png(display_index) {
+                    Ok(png) => {
+                        if let Err(e) = write_frame(&meeting_id, &png) {
+                            warn!("Writing recording frame failed: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Recording capture failed: {}", e),
+                }
+                thread::sleep(FRAME_INTERVAL);
+            }
+        });
+    }
+
+    announce(&app, &state, &meeting_id, &participants, true);
+    info!("Recording meeting {} to {}", meeting_id, info.path);
+    Ok(info)
+}
+
+/// Add a frame (base64 PNG, optionally a data URL) such as a remote participant's shared screen
+#[tauri::command]
+pub fn record_meeting_frame(meeting_id: String, data: String) -> Result<(), String> {
+    let b64 = data.split_once("base64,").map(|(_, b)| b).unwrap_or(&data);
+    let png = base64::engine::general_purpose::STANDARD
+        .decode(b64)
+        .map_err(|e| e.to_string())?;
+    if !write_frame(&meeting_id, &png)? {
+        return Err("This meeting isn't being recorded".to_string());
+    }
+    Ok(())
+}
+
+/// Finish a recording and write its summary next to the media
+#[tauri::command]
+pub fn stop_meeting_recording<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    meeting_id: String,
+) -> Result<RecordingInfo, String> {
+    let rec = recordings()
+        .lock()
+        .unwrap()
+        .remove(&meeting_id)
+        .ok_or("This meeting isn't being recorded")?;
+    rec.stop.store(true, Ordering::Relaxed);
+    let info = info_for(&meeting_id, &rec);
+    let summary = serde_json::json!({
+        "meeting_id": meeting_id,
+        "started_at": rec.started_at,
+        "ended_at": crate::db::now(),
+        "participants": rec.participants,
+        "frames": rec.frames,
+        "chat_messages": rec.chat_messages,
+    });
+    let json = serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())?;
+    fs::write(rec.dir.join("recording.json"), json).map_err(|e| e.to_string())?;
+
+    announce(&app, &state, &meeting_id, &rec.participants, false);
+    info!("Stopped recording meeting {}", meeting_id);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn get_meeting_recording(meeting_id: String) -> Option<RecordingInfo> {
+    recordings()
+        .lock()
+        .unwrap()
+        .get(&meeting_id)
+        .map(|rec| info_for(&meeting_id, rec))
+}
@@ -0,0 +1,553 @@
+// src-tauri/src/meeting_roster.rs
+// Who is in each meeting, kept in the backend from the meeting signaling we send and
+// receive. The host answers rejoin requests from this roster, so a rejoiner gets the same
+// list no matter what state the host's UI is in. Peers the host didn't invite wait in a
+// waiting room until the host lets them in, and the host only sends offers to peers it has
+// admitted. Offers, answers and ICE candidates are only taken from peers in the meeting: the
+// admitted ones on the host, the host and known participants elsewhere. Native meetings
+// (meeting.rs) keep a roster too: joiners go through the same join request and waiting room
+// before the host takes their offer.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::db::now;
+use crate::signaling::SignalingMessage;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use tauri::State;
+use tracing::info;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RosterEntry {
+    pub peer_id: String,
+    pub username: Option<String>,
+    pub joined_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeetingRoster {
+    pub meeting_id: String,
+    pub host_id: String,
+    pub participants: Vec<RosterEntry>,
+    /// Join requests waiting for the host (only tracked on the host)
+    pub waiting: Vec<RosterEntry>,
+    /// Invited or approved peers; only these get offers from the host
+    #[serde(skip)]
+    admitted: HashSet<String>,
+}
+
+impl MeetingRoster {
+    fn new(meeting_id: &str, host_id: &str) -> Self {
+        MeetingRoster {
+            meeting_id: meeting_id.to_string(),
+            host_id: host_id.to_string(),
+            participants: Vec::new(),
+            waiting: Vec::new(),
+            admitted: HashSet::from([host_id.to_string()]),
+        }
+    }
+
+    fn add(&mut self, peer_id: &str, username: Option<String>) {
+        match self.participants.iter_mut().find(|p| p.peer_id == peer_id) {
+            Some(entry) => {
+                if username.is_some() {
+                    entry.username = username;
+                }
+            }
+            None => self.participants.push(RosterEntry {
+                peer_id: peer_id.to_string(),
+                username,
+                joined_at: now(),
+            }),
+        }
+    }
+
+    fn remove(&mut self, peer_id: &str) {
+        self.participants.retain(|p| p.peer_id != peer_id);
+    }
+
+    fn has_participant(&self, peer_id: &str) -> bool {
+        self.participants.iter().any(|p| p.peer_id == peer_id)
+    }
+
+    fn peer_ids(&self) -> Vec<String> {
+        self.participants
+            .iter()
+            .map(|p| p.peer_id.clone())
+            .collect()
+    }
+
+    fn wait(&mut self, peer_id: &str, username: &str) {
+        if !self.waiting.iter().any(|p| p.peer_id == peer_id) {
+            self.waiting.push(RosterEntry {
+                peer_id: peer_id.to_string(),
+                username: Some(username.to_string()),
+                joined_at: now(),
+            });
+        }
+    }
+
+    /// Let an admitted peer in: they're added and get the current participant list
+    fn admit(&mut self, local_id: &str, peer_id: &str, username: &str) -> SignalingMessage {
+        self.admitted.insert(peer_id.to_string());
+        self.add(peer_id, Some(username.to_string()));
+        SignalingMessage::MeetingParticipantList {
+            from: local_id.to_string(),
+            to: peer_id.to_string(),
+            meeting_id: self.meeting_id.clone(),
+            participants: self.peer_ids(),
+        }
+    }
+}
+
+/// Rosters of the meetings we know about, keyed by meeting_id
+pub struct MeetingRosters {
+    rosters: RwLock<HashMap<String, MeetingRoster>>,
+}
+
+impl MeetingRosters {
+    pub fn new() -> Self {
+        MeetingRosters {
+            rosters: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, meeting_id: &str) -> Option<MeetingRoster> {
+        self.rosters.read().unwrap().get(meeting_id).cloned()
+    }
+
+    /// Start the roster of a native meeting we host
+    pub fn host(&self, meeting_id: &str, local_id: &str) {
+        let mut roster = MeetingRoster::new(meeting_id, local_id);
+        roster.add(local_id, None);
+        self.rosters
+            .write()
+            .unwrap()
+            .insert(meeting_id.to_string(), roster);
+    }
+
+    /// Drop a meeting's roster (we left a native meeting)
+    pub fn forget(&self, meeting_id: &str) {
+        self.rosters.write().unwrap().remove(meeting_id);
+    }
+
+    /// A participant left a native meeting
+    pub fn remove_participant(&self, meeting_id: &str, peer_id: &str) {
+        if let Some(r) = self.rosters.write().unwrap().get_mut(meeting_id) {
+            r.remove(peer_id);
+        }
+    }
+
+    /// Whether `peer_id` is in the waiting room of a meeting we host
+    pub fn is_waiting(&self, meeting_id: &str, peer_id: &str) -> bool {
+        self.get(meeting_id)
+            .is_some_and(|r| r.waiting.iter().any(|p| p.peer_id == peer_id))
+    }
+
+    /// Refuse to send a meeting offer the host hasn't admitted the peer for
+    pub fn check_outgoing(&self, local_id: &str, msg: &SignalingMessage) -> Result<(), String> {
+        if let SignalingMessage::MeetingOffer { to, meeting_id, .. } = msg {
+            if let Some(r) = self.get(meeting_id) {
+                if r.host_id == local_id && !r.admitted.contains(to) {
+                    return Err("Peer hasn't been admitted to this meeting".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether incoming meeting signaling may go on to the meeting code. Offers, answers and
+    /// candidates need a roster we're in, for webview and native meetings alike.
+    pub fn admits_incoming(&self, local_id: &str, msg: &SignalingMessage) -> bool {
+        let (from, meeting_id) = match msg {
+            SignalingMessage::MeetingOffer {
+                from, meeting_id, ..
+            }
+            | SignalingMessage::MeetingAnswer {
+                from, meeting_id, ..
+            }
+            | SignalingMessage::MeetingIceCandidate {
+                from, meeting_id, ..
+            }
+            | SignalingMessage::NativeMeetingOffer {
+                from, meeting_id, ..
+            }
+            | SignalingMessage::NativeMeetingAnswer {
+                from, meeting_id, ..
+            }
+            | SignalingMessage::NativeMeetingCandidate {
+                from, meeting_id, ..
+            } => (from, meeting_id),
+            _ => return true,
+        };
+        let admitted = match self.get(meeting_id) {
+            Some(r) if r.host_id == local_id => r.admitted.contains(from),
+            Some(r) => r.host_id == *from || r.has_participant(from),
+            None => false,
+        };
+        if !admitted {
+            info!(
+                "Dropped meeting signaling from {}, who isn't in meeting {}",
+                from, meeting_id
+            );
+        }
+        admitted
+    }
+
+    /// Update rosters from a meeting message (`outgoing` for ones we send). Returns the
+    /// reply to send back when we're the host and someone asks to (re)join.
+    pub fn apply(
+        &self,
+        local_id: &str,
+        msg: &SignalingMessage,
+        outgoing: bool,
+    ) -> Option<SignalingMessage> {
+        let mut rosters = self.rosters.write().unwrap();
+        // Only the host of a meeting we know speaks for its roster
+        if let SignalingMessage::MeetingJoinDecision {
+            from, meeting_id, ..
+        }
+        | SignalingMessage::MeetingParticipantList {
+            from, meeting_id, ..
+        } = msg
+        {
+            if !outgoing && rosters.get(meeting_id).is_some_and(|r| r.host_id != *from) {
+                return None;
+            }
+        }
+        let mut roster = |meeting_id: &str, host_id: &str| {
+            rosters
+                .entry(meeting_id.to_string())
+                .or_insert_with(|| MeetingRoster::new(meeting_id, host_id))
+                .clone()
+        };
+        let updated = match msg {
+            SignalingMessage::MeetingInvite {
+                from,
+                to,
+                meeting_id,
+                host_name,
+            } => {
+                let mut r = roster(meeting_id, from);
+                r.add(from, Some(host_name.clone()));
+                if outgoing {
+                    r.admitted.insert(to.clone());
+                }
+                r
+            }
+            SignalingMessage::MeetingInviteResponse {
+                from,
+                to,
+                meeting_id,
+                accepted: true,
+                username,
+            } => {
+                let mut r = roster(meeting_id, if outgoing { to } else { from });
+                r.add(from, username.clone());
+                r
+            }
+            SignalingMessage::MeetingRejoinRequest {
+                from,
+                to,
+                meeting_id,
+                username,
+            }
+            | SignalingMessage::MeetingJoinRequest {
+                from,
+                to,
+                meeting_id,
+                username,
+            } => {
+                let mut r = roster(meeting_id, if outgoing { to } else { local_id });
+                let reply = if outgoing {
+                    None
+                } else if r.host_id != local_id {
+                    r.add(from, Some(username.clone()));
+                    None
+                } else if r.admitted.contains(from) {
+                    Some(r.admit(local_id, from, username))
+                } else {
+                    info!("{} is waiting to join meeting {}", from, meeting_id);
+                    r.wait(from, username);
+                    None
+                };
+                rosters.insert(meeting_id.clone(), r);
+                return reply;
+            }
+            SignalingMessage::MeetingJoinDecision {
+                from,
+                meeting_id,
+                approved: true,
+                ..
+            } if !outgoing => {
+                // The host answered, so it's in the meeting too
+                let mut r = roster(meeting_id, from);
+                r.add(from, None);
+                r.add(local_id, None);
+                r
+            }
+            SignalingMessage::MeetingParticipantList {
+                from,
+                meeting_id,
+                participants,
+                ..
+            } if !outgoing => {
+                let mut r = roster(meeting_id, from);
+                r.add(from, None);
+                for peer_id in participants {
+                    r.add(peer_id, None);
+                }
+                r.add(local_id, None);
+                r
+            }
+            SignalingMessage::MeetingLeave {
+                from, meeting_id, ..
+            } => {
+                if outgoing {
+                    // We left; the roster is no longer ours to keep
+                    rosters.remove(meeting_id);
+                    return None;
+                }
+                if let Some(r) = rosters.get_mut(meeting_id) {
+                    r.remove(from);
+                    r.waiting.retain(|p| &p.peer_id != from);
+                }
+                return None;
+            }
+            SignalingMessage::MeetingEnded { meeting_id, .. } => {
+                rosters.remove(meeting_id);
+                return None;
+            }
+            _ => return None,
+        };
+        rosters.insert(updated.meeting_id.clone(), updated);
+        None
+    }
+
+    /// Host's answer to a waiting peer; returns the messages to send them
+    fn decide(
+        &self,
+        local_id: &str,
+        meeting_id: &str,
+        peer_id: &str,
+        approve: bool,
+    ) -> Result<Vec<SignalingMessage>, String> {
+        let mut rosters = self.rosters.write().unwrap();
+        let r = rosters.get_mut(meeting_id).ok_or("Unknown meeting")?;
+        if r.host_id != local_id {
+            return Err("Only the host can admit participants".to_string());
+        }
+        let pos = r
+            .waiting
+            .iter()
+            .position(|p| p.peer_id == peer_id)
+            .ok_or("No pending request from this peer")?;
+        let entry = r.waiting.remove(pos);
+        let mut out = vec![SignalingMessage::MeetingJoinDecision {
+            from: local_id.to_string(),
+            to: peer_id.to_string(),
+            meeting_id: meeting_id.to_string(),
+            approved: approve,
+        }];
+        if approve {
+            let username = entry.username.unwrap_or_default();
+            out.push(r.admit(local_id, peer_id, &username));
+        }
+        Ok(out)
+    }
+}
+
+impl Default for MeetingRosters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Current participants of a meeting as the backend knows them
+#[tauri::command]
+pub fn get_meeting_participants(
+    state: State<AppState>,
+    meeting_id: String,
+) -> Result<MeetingRoster, String> {
+    state
+        .meeting_rosters
+        .get(&meeting_id)
+        .ok_or_else(|| "Unknown meeting".to_string())
+}
+
+/// Let a peer from the waiting room in, or turn them away
+#[tauri::command]
+pub fn decide_meeting_join(
+    state: State<AppState>,
+    meeting_id: String,
+    peer_id: String,
+    approve: bool,
+) -> Result<(), String> {
+    let messages =
+        state
+            .meeting_rosters
+            .decide(&state.device_id(), &meeting_id, &peer_id, approve)?;
+    for msg in &messages {
+        send_to_peer(&state, &peer_id, msg)?;
+    }
+    info!(
+        "{} {} for meeting {}",
+        if approve { "Admitted" } else { "Denied" },
+        peer_id,
+        meeting_id
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite(to: &str) -> SignalingMessage {
+        SignalingMessage::MeetingInvite {
+            from: "host".to_string(),
+            to: to.to_string(),
+            meeting_id: "m1".to_string(),
+            host_name: "Host".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_host_answers_rejoin_from_roster() {
+        let rosters = MeetingRosters::new();
+        rosters.apply("host", &invite("a"), true);
+        rosters.apply("host", &invite("b"), true);
+        for peer in ["a", "b"] {
+            rosters.apply(
+                "host",
+                &SignalingMessage::MeetingInviteResponse {
+                    from: peer.to_string(),
+                    to: "host".to_string(),
+                    meeting_id: "m1".to_string(),
+                    accepted: true,
+                    username: None,
+                },
+                false,
+            );
+        }
+        rosters.apply(
+            "host",
+            &SignalingMessage::MeetingLeave {
+                from: "a".to_string(),
+                to: "host".to_string(),
+                meeting_id: "m1".to_string(),
+            },
+            false,
+        );
+        let reply = rosters.apply(
+            "host",
+            &SignalingMessage::MeetingRejoinRequest {
+                from: "a".to_string(),
+                to: "host".to_string(),
+                meeting_id: "m1".to_string(),
+                username: "A".to_string(),
+            },
+            false,
+        );
+        match reply {
+            Some(SignalingMessage::MeetingParticipantList { participants, .. }) => {
+                assert_eq!(participants, vec!["host", "b", "a"]);
+            }
+            _ => panic!("expected a participant list"),
+        }
+
+        rosters.apply(
+            "host",
+            &SignalingMessage::MeetingEnded {
+                from: "host".to_string(),
+                to: "a".to_string(),
+                meeting_id: "m1".to_string(),
+            },
+            true,
+        );
+        assert!(rosters.get("m1").is_none());
+    }
+
+    #[test]
+    fn test_uninvited_peer_waits_for_approval() {
+        let rosters = MeetingRosters::new();
+        rosters.apply("host", &invite("a"), true);
+        let join = SignalingMessage::MeetingJoinRequest {
+            from: "x".to_string(),
+            to: "host".to_string(),
+            meeting_id: "m1".to_string(),
+            username: "X".to_string(),
+        };
+        assert!(rosters.apply("host", &join, false).is_none());
+        assert!(rosters.is_waiting("m1", "x"));
+
+        let offer = SignalingMessage::MeetingOffer {
+            from: "host".to_string(),
+            to: "x".to_string(),
+            meeting_id: "m1".to_string(),
+            sdp: String::new(),
+        };
+        assert!(rosters.check_outgoing("host", &offer).is_err());
+        assert!(rosters.decide("a", "m1", "x", true).is_err());
+        let answer = |from: &str, meeting_id: &str| SignalingMessage::MeetingAnswer {
+            from: from.to_string(),
+            to: "host".to_string(),
+            meeting_id: meeting_id.to_string(),
+            sdp: String::new(),
+        };
+        assert!(!rosters.admits_incoming("host", &answer("x", "m1")));
+        assert!(rosters.admits_incoming("host", &answer("a", "m1")));
+        assert!(!rosters.admits_incoming("host", &answer("a", "other")));
+
+        let sent = rosters.decide("host", "m1", "x", true).unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(!rosters.is_waiting("m1", "x"));
+        assert!(rosters.check_outgoing("host", &offer).is_ok());
+        assert!(rosters.admits_incoming("host", &answer("x", "m1")));
+
+        // On x, the host's offer is taken once its decision arrived; a stranger's isn't
+        let on_x = MeetingRosters::new();
+        for msg in &sent {
+            on_x.apply("x", msg, false);
+        }
+        assert!(on_x.admits_incoming("x", &offer));
+        let stranger = SignalingMessage::MeetingOffer {
+            from: "y".to_string(),
+            to: "x".to_string(),
+            meeting_id: "m1".to_string(),
+            sdp: String::new(),
+        };
+        assert!(!on_x.admits_incoming("x", &stranger));
+    }
+
+    #[test]
+    fn test_native_meeting_offers_need_admission() {
+        let rosters = MeetingRosters::new();
+        let offer = |from: &str| SignalingMessage::NativeMeetingOffer {
+            from: from.to_string(),
+            to: "host".to_string(),
+            meeting_id: "m1".to_string(),
+            sdp: String::new(),
+        };
+        // No roster, no meeting to take offers for
+        assert!(!rosters.admits_incoming("host", &offer("x")));
+
+        rosters.host("m1", "host");
+        assert!(!rosters.admits_incoming("host", &offer("x")));
+        let join = SignalingMessage::MeetingJoinRequest {
+            from: "x".to_string(),
+            to: "host".to_string(),
+            meeting_id: "m1".to_string(),
+            username: "X".to_string(),
+        };
+        assert!(rosters.apply("host", &join, false).is_none());
+        assert!(rosters.is_waiting("m1", "x"));
+        rosters.decide("host", "m1", "x", true).unwrap();
+        assert!(rosters.admits_incoming("host", &offer("x")));
+
+        rosters.forget("m1");
+        assert!(!rosters.admits_incoming("host", &offer("x")));
+    }
+}
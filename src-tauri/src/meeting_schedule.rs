@@ -0,0 +1,303 @@
+// src-tauri/src/meeting_schedule.rs
+// Scheduled meetings: invitations go out over signaling and are resent until each invitee
+// acknowledges them, so peers that were offline get them when they come back. Everyone
+// with the meeting gets a reminder five minutes before it starts.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::db::{generate_id, now, Database, ScheduledMeeting};
+use crate::notifications;
+use crate::signaling::SignalingMessage;
+use crate::tray;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const REMIND_BEFORE_MINUTES: i64 = 5;
+const DEFAULT_DURATION_MINUTES: i64 = 30;
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid time {}: {}", value, e))
+}
+
+fn find(db: &Database, meeting_id: &str) -> Option<ScheduledMeeting> {
+    db.get_scheduled_meetings(None)
+        .ok()?
+        .into_iter()
+        .find(|m| m.id == meeting_id)
+}
+
+fn send_invite(state: &AppState, meeting: &ScheduledMeeting, peer_id: &str) -> Result<(), String> {
+    let msg = SignalingMessage::ScheduledMeetingInvite {
+        from: state.device_id(),
+        to: peer_id.to_string(),
+        meeting_id: meeting.id.clone(),
+        title: meeting.title.clone(),
+        host_name: meeting.host_name.clone(),
+        start_at: meeting.start_at.clone(),
+        duration_minutes: meeting.duration_minutes,
+        participants: meeting.participants.clone(),
+    };
+    send_to_peer(state, peer_id, &msg)
+}
+
+/// Send every unacknowledged invitation whose invitee is online
+fn deliver_pending(state: &AppState) {
+    let pending = state.db.get_pending_meeting_invites().unwrap_or_default();
+    for (meeting_id, peer_id) in pending {
+        if state.discovery.get_peer(&peer_id).is_none() {
+            continue;
+        }
+        let Some(meeting) = find(&state.db, &meeting_id) else {
+            // Meeting is gone; nothing left to deliver
+            let _ = state
+                .db
+                .mark_meeting_invite_delivered(&meeting_id, &peer_id);
+            continue;
+        };
+        if let Err(e) = send_invite(state, &meeting, &peer_id) {
+            warn!("Meeting invite to {} failed: {}", peer_id, e);
+        }
+    }
+}
+
+/// Whether the reminder for `meeting` should fire at `at`
+fn reminder_due(meeting: &ScheduledMeeting, at: DateTime<Utc>) -> bool {
+    if meeting.reminded {
+        return false;
+    }
+    let Ok(start) = parse_time(&meeting.start_at) else {
+        return false;
+    };
+    let end = start + ChronoDuration::minutes(meeting.duration_minutes);
+    start - ChronoDuration::minutes(REMIND_BEFORE_MINUTES) <= at && at < end
+}
+
+fn fire_reminders<R: Runtime>(app: &AppHandle<R>, state: &AppState) {
+    if tray::is_muted() || notifications::is_do_not_disturb(&state.db) {
+        return;
+    }
+    let current = Utc::now();
+    let upcoming = state
+        .db
+        .get_scheduled_meetings(Some(&current.to_rfc3339()))
+        .unwrap_or_default();
+    for meeting in upcoming.into_iter().filter(|m| reminder_due(m, current)) {
+        let starts = parse_time(&meeting.start_at)
+            .map(|start| (start - current).num_minutes())
+            .unwrap_or(0);
+        let body = if starts > 0 {
+            format!("Starts in {} min · hosted by {}", starts, meeting.host_name)
+        } else {
+            format!("Starting now · hosted by {}", meeting.host_name)
+        };
+        let title = format!("Meeting: {}", meeting.title);
+        if let Err(e) = notifications::show_meeting_reminder(app, &meeting.id, &title, &body) {
+            warn!("Failed to show meeting reminder: {}", e);
+        }
+        let _ = state.db.set_scheduled_meeting_reminded(&meeting.id);
+        let _ = app.emit("meeting-reminder", &meeting);
+    }
+}
+
+/// Resend pending invitations and fire reminders for the life of the app
+pub fn start_scheduler<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || loop {
+        let state = app.state::<AppState>();
+        deliver_pending(&state);
+        fire_reminders(&app, &state);
+        thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+/// Handle ScheduledMeetingInvite/Ack from the signaling forwarder
+pub fn handle_message<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    let state = app.state::<AppState>();
+    match msg {
+        SignalingMessage::ScheduledMeetingInvite {
+            from,
+            meeting_id,
+            title,
+            host_name,
+            start_at,
+            duration_minutes,
+            participants,
+            ..
+        } => {
+            let existing = find(&state.db, meeting_id);
+            if existing.as_ref().is_some_and(|m| &m.host_id != from) {
+                warn!("Ignoring invite for meeting {} from a non-host", meeting_id);
+                return;
+            }
+            let meeting = ScheduledMeeting {
+                id: meeting_id.clone(),
+                title: title.clone(),
+                host_id: from.clone(),
+                host_name: host_name.clone(),
+                start_at: start_at.clone(),
+                duration_minutes: *duration_minutes,
+                participants: participants.clone(),
+                created_at: existing
+                    .as_ref()
+                    .map(|m| m.created_at.clone())
+                    .unwrap_or_else(now),
+                // A resent invite for the same time keeps the reminder state
+                reminded: existing.is_some_and(|m| m.reminded && &m.start_at == start_at),
+            };
+            if let Err(e) = state.db.save_scheduled_meeting(&meeting) {
+                warn!("Saving scheduled meeting failed: {}", e);
+                return;
+            }
+            let ack = SignalingMessage::ScheduledMeetingInviteAck {
+                from: state.device_id(),
+                to: from.clone(),
+                meeting_id: meeting_id.clone(),
+            };
+            let _ = send_to_peer(&state, from, &ack);
+            let _ = app.emit("meeting-scheduled", &meeting);
+        }
+        SignalingMessage::ScheduledMeetingInviteAck {
+            from, meeting_id, ..
+        } => {
+            let _ = state.db.mark_meeting_invite_delivered(meeting_id, from);
+        }
+        _ => {}
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Schedule a meeting and invite `participants`. `start_at` is RFC 3339.
+#[tauri::command]
+pub fn schedule_meeting(
+    state: State<AppState>,
+    title: String,
+    start_at: String,
+    duration_minutes: Option<i64>,
+    participants: Vec<String>,
+) -> Result<ScheduledMeeting, String> {
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err("Meeting title is required".to_string());
+    }
+    let start = parse_time(&start_at)?;
+    if start <= Utc::now() {
+        return Err("Meeting must start in the future".to_string());
+    }
+    let local_id = state.device_id();
+    let mut invitees: Vec<String> = Vec::new();
+    for p in participants {
+        if p != local_id && !invitees.contains(&p) {
+            invitees.push(p);
+        }
+    }
+    let host_name = state
+        .db
+        .get_user(&local_id)
+        .map_err(|e| e.to_string())?
+        .map(|u| u.username)
+        .unwrap_or_default();
+
+    let meeting = ScheduledMeeting {
+        id: generate_id(),
+        title,
+        host_id: local_id,
+        host_name,
+        start_at: start.to_rfc3339(),
+        duration_minutes: duration_minutes.unwrap_or(DEFAULT_DURATION_MINUTES).max(1),
+        participants: invitees,
+        created_at: now(),
+        reminded: false,
+    };
+    state
+        .db
+        .save_scheduled_meeting(&meeting)
+        .map_err(|e| e.to_string())?;
+    for peer_id in &meeting.participants {
+        state
+            .db
+            .queue_meeting_invite(&meeting.id, peer_id)
+            .map_err(|e| e.to_string())?;
+    }
+    deliver_pending(&state);
+    info!(
+        "Scheduled meeting {} at {} with {} invitee(s)",
+        meeting.id,
+        meeting.start_at,
+        meeting.participants.len()
+    );
+    Ok(meeting)
+}
+
+/// Scheduled meetings that haven't finished yet (or all of them), soonest first
+#[tauri::command]
+pub fn get_scheduled_meetings(
+    state: State<AppState>,
+    include_past: Option<bool>,
+) -> Result<Vec<ScheduledMeeting>, String> {
+    let ends_after = (!include_past.unwrap_or(false)).then(now);
+    state
+        .db
+        .get_scheduled_meetings(ends_after.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting(start_at: DateTime<Utc>) -> ScheduledMeeting {
+        ScheduledMeeting {
+            id: "m1".to_string(),
+            title: "Standup".to_string(),
+            host_id: "host".to_string(),
+            host_name: "Host".to_string(),
+            start_at: start_at.to_rfc3339(),
+            duration_minutes: 15,
+            participants: vec!["a".to_string(), "b".to_string()],
+            created_at: now(),
+            reminded: false,
+        }
+    }
+
+    #[test]
+    fn test_reminder_window() {
+        let at = Utc::now();
+        assert!(!reminder_due(
+            &meeting(at + ChronoDuration::minutes(10)),
+            at
+        ));
+        assert!(reminder_due(&meeting(at + ChronoDuration::minutes(4)), at));
+        assert!(reminder_due(&meeting(at - ChronoDuration::minutes(5)), at));
+        assert!(!reminder_due(
+            &meeting(at - ChronoDuration::minutes(20)),
+            at
+        ));
+        let reminded = ScheduledMeeting {
+            reminded: true,
+            ..meeting(at + ChronoDuration::minutes(4))
+        };
+        assert!(!reminder_due(&reminded, at));
+    }
+
+    #[test]
+    fn test_invites_stay_pending_until_acknowledged() {
+        let db = Database::new_in_memory().unwrap();
+        let m = meeting(Utc::now() + ChronoDuration::hours(1));
+        db.save_scheduled_meeting(&m).unwrap();
+        db.queue_meeting_invite(&m.id, "a").unwrap();
+        db.queue_meeting_invite(&m.id, "b").unwrap();
+        db.mark_meeting_invite_delivered(&m.id, "a").unwrap();
+        assert_eq!(
+            db.get_pending_meeting_invites().unwrap(),
+            vec![("m1".to_string(), "b".to_string())]
+        );
+        let stored = db.get_scheduled_meetings(Some(&now())).unwrap();
+        assert_eq!(stored[0].participants, vec!["a", "b"]);
+    }
+}
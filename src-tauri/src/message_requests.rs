@@ -0,0 +1,280 @@
+// src-tauri/src/message_requests.rs
+// Privacy mode. Discovery keeps tracking every peer, but direct messages, file offers and group
+// invitations from a device we've had no contact with are held as "message requests" instead
+// of landing in the chats. Accepting a peer approves it and replays what it sent through the
+// normal signaling handler; declining drops it.
+
+use crate::commands::AppState;
+use crate::db::{now, Database, MessageRequest};
+use crate::signaling::{SignalingMessage, SignalingServer};
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "privacy_mode";
+const PREVIEW_CHARS: usize = 100;
+
+pub fn is_enabled(db: &Database) -> bool {
+    db.get_setting(SETTING_KEY).ok().flatten().as_deref() == Some("true")
+}
+
+fn preview(text: &str) -> String {
+    text.chars().take(PREVIEW_CHARS).collect()
+}
+
+/// The request a message would become, for the kinds privacy mode holds
+fn as_request(db: &Database, msg: &SignalingMessage) -> Option<MessageRequest> {
+    let request =
+        |id: &str, from: &str, sender_name: String, kind: &str, preview: String| MessageRequest {
+            id: id.to_string(),
+            peer_id: from.to_string(),
+            sender_name,
+            kind: kind.to_string(),
+            preview,
+            created_at: now(),
+        };
+    match msg {
+        SignalingMessage::ChatMessage {
+            from,
+            id,
+            content,
+            message_type,
+            sender_name,
+            ..
+        } => {
+            let (kind, text) = if message_type == "text" {
+                ("message", preview(content))
+            } else {
+                ("file", format!("📎 {}", message_type))
+            };
+            Some(request(id, from, sender_name.clone(), kind, text))
+        }
+        SignalingMessage::FileTransferRequest {
+            from,
+            transfer_id,
+            file_name,
+            ..
+        } => {
+            let name = db
+                .get_user(from)
+                .ok()
+                .flatten()
+                .map(|u| u.username)
+                .unwrap_or_default();
+            Some(request(transfer_id, from, name, "file", preview(file_name)))
+        }
+        SignalingMessage::GroupCreated {
+            from,
+            id,
+            name,
+            member_ids,
+            member_names,
+            ..
+        } => {
+            let sender = member_ids
+                .iter()
+                .position(|m| m == from)
+                .and_then(|i| member_names.get(i).cloned())
+                .unwrap_or_default();
+            Some(request(id, from, sender, "group_invite", preview(name)))
+        }
+        _ => None,
+    }
+}
+
+/// Sender of a message of the kinds privacy mode holds (first-contact messages)
+pub(crate) fn sender_of_request(msg: &SignalingMessage) -> Option<&str> {
+    match msg {
+        SignalingMessage::ChatMessage { from, .. }
+        | SignalingMessage::FileTransferRequest { from, .. }
+        | SignalingMessage::GroupCreated { from, .. } => Some(from),
+        _ => None,
+    }
+}
+
+/// Hold `msg` when privacy mode is on and its sender is unknown. Returns whether it was held;
+/// if not, the caller handles the message as usual.
+pub fn hold<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    signaling: &SignalingServer,
+    local_id: &str,
+    msg: &SignalingMessage,
+) -> bool {
+    if !is_enabled(db) {
+        return false;
+    }
+    let Some(request) = as_request(db, msg) else {
+        return false;
+    };
+    if db
+        .is_known_contact(local_id, &request.peer_id)
+        .unwrap_or(true)
+    {
+        return false;
+    }
+    let Ok(payload) = serde_json::to_string(msg) else {
+        return false;
+    };
+    match db.add_message_request(&request, &payload) {
+        Ok(true) => {
+            info!(
+                "Held a {} from unknown peer {}",
+                request.kind, request.peer_id
+            );
+            let _ = app.emit("message-request", &request);
+        }
+        // A retry of something already held
+        Ok(false) => {}
+        Err(e) => {
+            warn!("Failed to hold message request: {}", e);
+            return false;
+        }
+    }
+    // It reached this device; acknowledging stops the sender's retries
+    if let SignalingMessage::ChatMessage { from, id, .. } = msg {
+        let ack = SignalingMessage::DeliveryAck {
+            from: local_id.to_string(),
+            to: from.clone(),
+            message_id: id.clone(),
+        };
+        let _ = signaling.send_message(from, &ack);
+    }
+    true
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_privacy_mode(state: State<AppState>) -> bool {
+    is_enabled(&state.db)
+}
+
+#[tauri::command]
+pub fn set_privacy_mode(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .db
+        .set_setting(SETTING_KEY, if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_message_requests(state: State<AppState>) -> Result<Vec<MessageRequest>, String> {
+    state.db.get_message_requests().map_err(|e| e.to_string())
+}
+
+/// Approve the peer and deliver everything it sent while held. Returns how many items.
+#[tauri::command]
+pub fn accept_message_request<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+) -> Result<usize, String> {
+    state
+        .db
+        .approve_contact(&peer_id)
+        .map_err(|e| e.to_string())?;
+    let payloads = state
+        .db
+        .take_message_requests(&peer_id)
+        .map_err(|e| e.to_string())?;
+    let count = payloads.len();
+    for payload in payloads {
+        match serde_json::from_str::<SignalingMessage>(&payload) {
+            Ok(msg) => state.signaling.redeliver(msg),
+            Err(e) => warn!("Dropping unreadable message request: {}", e),
+        }
+    }
+    let _ = app.emit("message-requests-changed", &peer_id);
+    Ok(count)
+}
+
+/// Drop everything the peer sent while held (it stays unknown, so new messages are held too)
+#[tauri::command]
+pub fn decline_message_request<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+) -> Result<usize, String> {
+    let count = state
+        .db
+        .take_message_requests(&peer_id)
+        .map_err(|e| e.to_string())?
+        .len();
+    let _ = app.emit("message-requests-changed", &peer_id);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Message;
+    use crate::delivery_status::MessageStatus;
+
+    fn chat(from: &str, id: &str) -> SignalingMessage {
+        SignalingMessage::ChatMessage {
+            from: from.to_string(),
+            to: "me".to_string(),
+            id: id.to_string(),
+            content: "hello there".to_string(),
+            message_type: "text".to_string(),
+            sender_name: "Stranger".to_string(),
+            timestamp: now(),
+            lamport: 0,
+            auto_reply: false,
+        }
+    }
+
+    #[test]
+    fn test_requests_are_kept_once_and_contacts_are_known() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("me", "Me", None).unwrap();
+        db.upsert_peer_as_user("ana", "Ana", None).unwrap();
+        assert!(!db.is_known_contact("me", "stranger").unwrap());
+
+        let request = as_request(&db, &chat("stranger", "m1")).unwrap();
+        assert_eq!(
+            (request.kind.as_str(), request.preview.as_str()),
+            ("message", "hello there")
+        );
+        assert!(db.add_message_request(&request, "{}").unwrap());
+        assert!(!db.add_message_request(&request, "{}").unwrap());
+        assert_eq!(db.get_message_requests().unwrap().len(), 1);
+        assert_eq!(db.take_message_requests("stranger").unwrap().len(), 1);
+        assert!(db.get_message_requests().unwrap().is_empty());
+
+        db.approve_contact("stranger").unwrap();
+        assert!(db.is_known_contact("me", "stranger").unwrap());
+        db.create_message(&Message {
+            id: "m2".to_string(),
+            sender_id: "me".to_string(),
+            receiver_id: "ana".to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: true,
+            is_delivered: true,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Read,
+        })
+        .unwrap();
+        assert!(db.is_known_contact("me", "ana").unwrap());
+
+        // Meeting chat stored by older versions isn't a conversation
+        db.create_message(&Message {
+            id: "m3".to_string(),
+            sender_id: "mallory".to_string(),
+            receiver_id: "me".to_string(),
+            content: "[MEETING_CHAT]hi all".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: true,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Delivered,
+        })
+        .unwrap();
+        assert!(!db.is_known_contact("me", "mallory").unwrap());
+    }
+}
@@ -0,0 +1,275 @@
+// src-tauri/src/note_reminders.rs
+// Note reminders: a background scheduler fires a native notification when a reminder is
+// due, then marks it done or moves a repeating one to its next occurrence
+
+use crate::commands::AppState;
+use crate::db::{now, Database, NoteReminder};
+use crate::notifications;
+use crate::tray;
+use chrono::{DateTime, Duration as ChronoDuration, Months, Utc};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_SNOOZE_MINUTES: i64 = 10;
+const MAX_SNOOZE_MINUTES: i64 = 30 * 24 * 60;
+const REPEATS: [&str; 4] = ["none", "daily", "weekly", "monthly"];
+const PREVIEW_LEN: usize = 120;
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid time {}: {}", value, e))
+}
+
+fn step(time: DateTime<Utc>, repeat: &str) -> Option<DateTime<Utc>> {
+    match repeat {
+        "daily" => Some(time + ChronoDuration::days(1)),
+        "weekly" => Some(time + ChronoDuration::weeks(1)),
+        "monthly" => time.checked_add_months(Months::new(1)),
+        _ => None,
+    }
+}
+
+/// First occurrence after `after`; occurrences missed while the app was closed are skipped
+fn next_occurrence(
+    remind_at: DateTime<Utc>,
+    repeat: &str,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let mut next = step(remind_at, repeat)?;
+    while next <= after {
+        next = step(next, repeat)?;
+    }
+    Some(next)
+}
+
+fn get_reminder(db: &Database, note_id: &str) -> Result<NoteReminder, String> {
+    db.get_note_reminders(None)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|r| r.note_id == note_id)
+        .ok_or_else(|| "No reminder for this note".to_string())
+}
+
+/// Push a pending reminder back by `minutes` (10 by default, at most 30 days)
+pub fn snooze(db: &Database, note_id: &str, minutes: Option<i64>) -> Result<NoteReminder, String> {
+    let minutes = minutes
+        .unwrap_or(DEFAULT_SNOOZE_MINUTES)
+        .clamp(1, MAX_SNOOZE_MINUTES);
+    let current = get_reminder(db, note_id)?;
+    if current.status == "done" {
+        return Err("This reminder is already done".to_string());
+    }
+    let remind_at = Utc::now()
+        .checked_add_signed(ChronoDuration::minutes(minutes))
+        .ok_or("Snooze time is out of range")?;
+    let reminder = NoteReminder {
+        remind_at: remind_at.to_rfc3339(),
+        status: "pending".to_string(),
+        ..current
+    };
+    db.save_note_reminder(&reminder)
+        .map_err(|e| e.to_string())?;
+    Ok(reminder)
+}
+
+/// Stop a reminder, including any further repeats
+pub fn mark_done(db: &Database, note_id: &str) -> Result<NoteReminder, String> {
+    let reminder = NoteReminder {
+        status: "done".to_string(),
+        ..get_reminder(db, note_id)?
+    };
+    db.save_note_reminder(&reminder)
+        .map_err(|e| e.to_string())?;
+    Ok(reminder)
+}
+
+fn notification_body(title: &str, content: &str, locked: bool) -> (String, String) {
+    let summary = format!(
+        "Reminder: {}",
+        if title.is_empty() { "Note" } else { title }
+    );
+    // Never show a locked note's ciphertext, or its content anywhere outside the app
+    let body = if locked {
+        "Locked note".to_string()
+    } else if content.chars().count() > PREVIEW_LEN {
+        format!("{}…", content.chars().take(PREVIEW_LEN).collect::<String>())
+    } else {
+        content.to_string()
+    };
+    (summary, body)
+}
+
+/// Fire every due reminder. While notifications are silenced, reminders stay pending and
+/// fire once they aren't.
+fn fire_due<R: Runtime>(app: &AppHandle<R>, state: &AppState) {
+    if tray::is_muted() || notifications::is_do_not_disturb(&state.db) {
+        return;
+    }
+    let current = Utc::now();
+    let due = state
+        .db
+        .get_note_reminders(Some(&current.to_rfc3339()))
+        .unwrap_or_default();
+    for reminder in due {
+        let Ok(Some(note)) = state.db.get_note(&reminder.note_id) else {
+            let _ = state.db.delete_note_reminder(&reminder.note_id);
+            continue;
+        };
+        let (title, body) = notification_body(&note.title, &note.content, note.locked);
+        if let Err(e) = notifications::show_reminder(app, &note.id, &title, &body) {
+            warn!("Failed to show reminder: {}", e);
+        }
+
+        let next = parse_time(&reminder.remind_at)
+            .ok()
+            .and_then(|at| next_occurrence(at, &reminder.repeat, current));
+        let updated = NoteReminder {
+            remind_at: next.map(|t| t.to_rfc3339()).unwrap_or(reminder.remind_at),
+            status: if next.is_some() { "pending" } else { "done" }.to_string(),
+            last_fired_at: Some(now()),
+            ..reminder
+        };
+        if let Err(e) = state.db.save_note_reminder(&updated) {
+            warn!("Failed to update reminder for note {}: {}", note.id, e);
+        }
+        let _ = app.emit("note-reminder", &updated);
+    }
+}
+
+/// Check for due reminders for the life of the app
+pub fn start_scheduler<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || loop {
+        fire_due(&app, &app.state::<AppState>());
+        thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+// ============ COMMANDS ============
+
+/// Set (or replace) a note's reminder. `remind_at` is RFC 3339; `repeat` defaults to none.
+#[tauri::command]
+pub fn set_note_reminder(
+    state: State<AppState>,
+    note_id: String,
+    remind_at: String,
+    repeat: Option<String>,
+) -> Result<NoteReminder, String> {
+    let repeat = repeat.unwrap_or_else(|| "none".to_string());
+    if !REPEATS.contains(&repeat.as_str()) {
+        return Err(format!("Unknown repeat: {}", repeat));
+    }
+    if state
+        .db
+        .get_note(&note_id)
+        .map_err(|e| e.to_string())?
+        .is_none()
+    {
+        return Err("Note not found".to_string());
+    }
+    let reminder = NoteReminder {
+        note_id,
+        remind_at: parse_time(&remind_at)?.to_rfc3339(),
+        repeat,
+        status: "pending".to_string(),
+        last_fired_at: None,
+    };
+    state
+        .db
+        .save_note_reminder(&reminder)
+        .map_err(|e| e.to_string())?;
+    info!(
+        "Reminder set for note {} at {}",
+        reminder.note_id, reminder.remind_at
+    );
+    Ok(reminder)
+}
+
+#[tauri::command]
+pub fn get_note_reminders(state: State<AppState>) -> Result<Vec<NoteReminder>, String> {
+    state.db.get_note_reminders(None).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn clear_note_reminder(state: State<AppState>, note_id: String) -> Result<(), String> {
+    state
+        .db
+        .delete_note_reminder(&note_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn snooze_note_reminder(
+    state: State<AppState>,
+    note_id: String,
+    minutes: Option<i64>,
+) -> Result<NoteReminder, String> {
+    snooze(&state.db, &note_id, minutes)
+}
+
+#[tauri::command]
+pub fn complete_note_reminder(
+    state: State<AppState>,
+    note_id: String,
+) -> Result<NoteReminder, String> {
+    mark_done(&state.db, &note_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_occurrence_skips_missed_repeats() {
+        let at = parse_time("2026-01-31T09:00:00+00:00").unwrap();
+        let after = parse_time("2026-02-03T12:00:00+00:00").unwrap();
+        assert_eq!(
+            next_occurrence(at, "daily", after).unwrap().to_rfc3339(),
+            "2026-02-04T09:00:00+00:00"
+        );
+        assert_eq!(
+            next_occurrence(at, "monthly", after).unwrap().to_rfc3339(),
+            "2026-02-28T09:00:00+00:00"
+        );
+        assert!(next_occurrence(at, "none", after).is_none());
+    }
+
+    #[test]
+    fn test_due_reminders_and_snooze() {
+        let db = Database::new_in_memory().unwrap();
+        db.save_note_reminder(&NoteReminder {
+            note_id: "n1".to_string(),
+            remind_at: "2026-01-01T09:00:00+00:00".to_string(),
+            repeat: "none".to_string(),
+            status: "pending".to_string(),
+            last_fired_at: None,
+        })
+        .unwrap();
+        assert_eq!(
+            db.get_note_reminders(Some("2026-01-01T10:00:00+00:00"))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(db
+            .get_note_reminders(Some("2026-01-01T08:00:00+00:00"))
+            .unwrap()
+            .is_empty());
+
+        let snoozed = snooze(&db, "n1", Some(5)).unwrap();
+        assert_eq!(snoozed.status, "pending");
+        assert!(snoozed.remind_at > now());
+        // Huge values are held to the maximum instead of overflowing
+        let far = snooze(&db, "n1", Some(i64::MAX)).unwrap();
+        let limit = Utc::now() + ChronoDuration::minutes(MAX_SNOOZE_MINUTES + 1);
+        assert!(parse_time(&far.remind_at).unwrap() < limit);
+
+        mark_done(&db, "n1").unwrap();
+        assert!(db.get_note_reminders(Some(&now())).unwrap().is_empty());
+        assert!(snooze(&db, "n1", Some(5)).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+// src-tauri/src/note_sharing.rs
+// Shared notes: send a note to a peer and exchange later edits with everyone it's shared
+// with. The owner relays edits between collaborators. When two people edit the same
+// version, the newer edit wins and the other is kept as a conflicted copy.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::crypto::EncryptedEnvelope;
+use crate::db::{generate_id, now, Note};
+use crate::device_sync::{decrypt_from, encrypt_for};
+use crate::signaling::SignalingMessage;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize)]
+struct SharedNotePayload {
+    note: Note,
+    /// `updated_at` of the version the edit started from; None for the initial share
+    base_updated_at: Option<String>,
+}
+
+/// What to do with an incoming version of a shared note
+struct Merge {
+    apply: bool,
+    /// The losing side of a concurrent edit, saved as a separate private note
+    conflicted: Option<Note>,
+}
+
+fn conflicted_copy(note: &Note) -> Note {
+    let ts = now();
+    Note {
+        id: generate_id(),
+        title: format!("{} (conflicted copy)", note.title),
+        shared_by: None,
+        collaborators: Vec::new(),
+        created_at: ts.clone(),
+        updated_at: ts,
+        ..note.clone()
+    }
+}
+
+fn merge(local: Option<&Note>, incoming: &Note, base: Option<&str>) -> Merge {
+    let Some(local) = local else {
+        return Merge {
+            apply: true,
+            conflicted: None,
+        };
+    };
+    let same = local.title == incoming.title && local.content == incoming.content;
+    if same || incoming.updated_at == local.updated_at {
+        // Already have it (e.g. an echo of our own edit)
+        return Merge {
+            apply: !same && incoming.updated_at > local.updated_at,
+            conflicted: None,
+        };
+    }
+    if base == Some(local.updated_at.as_str()) {
+        return Merge {
+            apply: true,
+            conflicted: None,
+        };
+    }
+    // Both sides changed the note since the version the edit was based on
+    if incoming.updated_at > local.updated_at {
+        Merge {
+            apply: true,
+            conflicted: Some(conflicted_copy(local)),
+        }
+    } else {
+        Merge {
+            apply: false,
+            conflicted: Some(conflicted_copy(incoming)),
+        }
+    }
+}
+
+fn send_note(
+    state: &AppState,
+    peer_id: &str,
+    note: &Note,
+    base_updated_at: Option<String>,
+) -> Result<(), String> {
+    let payload = SharedNotePayload {
+        note: note.clone(),
+        base_updated_at,
+    };
+    let json = serde_json::to_string(&payload).map_err(|e| e.to_string())?;
+    let msg = SignalingMessage::NoteShare {
+        from: state.device_id(),
+        to: peer_id.to_string(),
+        payload: encrypt_for(state, peer_id, &json)?,
+    };
+    send_to_peer(state, peer_id, &msg)
+}
+
+/// Send a local edit of a shared note to its collaborators that are online.
+/// `base_updated_at` is the version the edit was made on.
+pub fn propagate(state: &AppState, note: &Note, base_updated_at: Option<String>) {
+    propagate_except(state, note, base_updated_at, None);
+}
+
+fn propagate_except(
+    state: &AppState,
+    note: &Note,
+    base_updated_at: Option<String>,
+    except: Option<&str>,
+) {
+    for peer_id in &note.collaborators {
+        if Some(peer_id.as_str()) == except || state.discovery.get_peer(peer_id).is_none() {
+            continue;
+        }
+        if let Err(e) = send_note(state, peer_id, note, base_updated_at.clone()) {
+            warn!("Sending note {} to {} failed: {}", note.id, peer_id, e);
+        }
+    }
+}
+
+/// Handle a NoteShare from the signaling forwarder
+pub fn handle_message<R: Runtime>(app: &AppHandle<R>, from: &str, payload: &EncryptedEnvelope) {
+    let state = app.state::<AppState>();
+    if let Err(e) = apply_incoming(app, &state, from, payload) {
+        warn!("Shared note from {}: {}", from, e);
+    }
+}
+
+fn apply_incoming<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    from: &str,
+    payload: &EncryptedEnvelope,
+) -> Result<(), String> {
+    let json = decrypt_from(state, from, payload)?;
+    let SharedNotePayload {
+        note: incoming,
+        base_updated_at,
+    } = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+    let local = state.db.get_note(&incoming.id).map_err(|e| e.to_string())?;
+    match &local {
+        Some(l) if !l.collaborators.iter().any(|c| c == from) => {
+            return Err(format!("not a collaborator on note {}", incoming.id));
+        }
+        // We deleted our copy; don't let edits bring it back
+        None if state.db.is_note_deleted(&incoming.id).unwrap_or(false) => return Ok(()),
+        _ => {}
+    }
+
+    let outcome = merge(local.as_ref(), &incoming, base_updated_at.as_deref());
+    if let Some(copy) = &outcome.conflicted {
+        state.db.upsert_note(copy).map_err(|e| e.to_string())?;
+    }
+    if !outcome.apply {
+        return Ok(());
+    }
+
+    // Sharing state is ours, not the sender's
+    let note = Note {
+        shared_by: match &local {
+            Some(l) => l.shared_by.clone(),
+            None => Some(
+                incoming
+                    .shared_by
+                    .clone()
+                    .unwrap_or_else(|| from.to_string()),
+            ),
+        },
+        collaborators: match &local {
+            Some(l) => l.collaborators.clone(),
+            None => vec![from.to_string()],
+        },
+        ..incoming
+    };
+    state.db.upsert_note(&note).map_err(|e| e.to_string())?;
+
+    if local.is_none() {
+        info!("Note {} shared with us by {}", note.id, from);
+        let _ = app.emit(
+            "note-shared",
+            serde_json::json!({ "from": from, "note": note }),
+        );
+        return Ok(());
+    }
+    // As the owner, pass the edit on to everyone else it's shared with
+    if note.shared_by.is_none() {
+        let base = local.map(|l| l.updated_at);
+        propagate_except(state, &note, base, Some(from));
+    }
+    let _ = app.emit(
+        "shared-note-updated",
+        serde_json::json!({
+            "from": from,
+            "note_id": note.id,
+            "conflicted_copy_id": outcome.conflicted.map(|c| c.id),
+        }),
+    );
+    Ok(())
+}
+
+// ============ COMMANDS ============
+
+/// Share one of our notes with a peer; later edits on either side are exchanged
+#[tauri::command]
+pub fn share_note(
+    state: State<AppState>,
+    note_id: String,
+    peer_id: String,
+) -> Result<Note, String> {
+    let mut note = state
+        .db
+        .get_note(&note_id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Note not found")?;
+    if note.shared_by.is_some() {
+        return Err("Only the owner can share this note".to_string());
+    }
+    if note.locked {
+        return Err("Locked notes can't be shared".to_string());
+    }
+    if peer_id == state.device_id() {
+        return Err("Can't share a note with yourself".to_string());
+    }
+    send_note(&state, &peer_id, &note, None)?;
+    if !note.collaborators.contains(&peer_id) {
+        note.collaborators.push(peer_id);
+        state
+            .db
+            .set_note_collaborators(&note.id, &note.collaborators)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(content: &str, updated_at: &str) -> Note {
+        Note {
+            id: "n1".to_string(),
+            title: "Plan".to_string(),
+            content: content.to_string(),
+            color: "#fef3c7".to_string(),
+            pinned: false,
+            category: None,
+            created_at: "2026-01-01T00:00:00+00:00".to_string(),
+            updated_at: updated_at.to_string(),
+            shared_by: None,
+            collaborators: vec!["peer".to_string()],
+            locked: false,
+        }
+    }
+
+    #[test]
+    fn test_merge_fast_forward_and_conflict() {
+        let local = note("a", "2026-01-02T00:00:00+00:00");
+        let edit = note("b", "2026-01-03T00:00:00+00:00");
+        assert!(merge(None, &edit, None).apply);
+        let ff = merge(Some(&local), &edit, Some("2026-01-02T00:00:00+00:00"));
+        assert!(ff.apply && ff.conflicted.is_none());
+
+        // Based on an older version: newer incoming wins, local kept as a copy
+        let newer = merge(Some(&local), &edit, Some("2026-01-01T00:00:00+00:00"));
+        assert!(newer.apply);
+        assert_eq!(newer.conflicted.unwrap().content, "a");
+        let older = merge(Some(&edit), &local, Some("2026-01-01T00:00:00+00:00"));
+        assert!(!older.apply);
+        assert!(older.conflicted.unwrap().collaborators.is_empty());
+
+        let echo = merge(Some(&edit), &edit, None);
+        assert!(!echo.apply && echo.conflicted.is_none());
+    }
+}
@@ -0,0 +1,442 @@
+// src-tauri/src/notifications.rs
+// Native notifications for incoming messages, dispatched from the backend.
+// Reply / Mark read (and the reminders' Snooze / Done) buttons exist only where notify-rust
+// shows the notification (Linux/BSD and macOS). On Windows the notification plugin's toasts
+// have no action buttons: clicking one just brings the app forward, and replying or marking
+// read happens in the app or through the notification_reply / notification_mark_read commands.
+
+use crate::chat_settings;
+use crate::commands::AppState;
+use crate::db::Database;
+use crate::device_sync;
+use crate::keyword_alerts;
+use crate::sounds;
+use crate::tray;
+use crate::window_manager;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::warn;
+
+const APP_TITLE: &str = "Pingo Messenger";
+const PREVIEW_LEN: usize = 100;
+
+/// What a notification is about — decides the mute key and which actions it offers
+pub enum NotificationTarget<'a> {
+    Direct {
+        peer_id: &'a str,
+    },
+    Group {
+        group_id: &'a str,
+        group_name: &'a str,
+    },
+}
+
+impl NotificationTarget<'_> {
+    pub fn chat_id(&self) -> &str {
+        match self {
+            NotificationTarget::Direct { peer_id } => peer_id,
+            NotificationTarget::Group { group_id, .. } => group_id,
+        }
+    }
+}
+
+fn chat_mute_key(chat_id: &str) -> String {
+    format!("chat_muted:{}", chat_id)
+}
+
+fn setting_is_true(db: &Database, key: &str) -> bool {
+    db.get_setting(key)
+        .ok()
+        .flatten()
+        .map(|v| v == "true")
+        .unwrap_or(false)
+}
+
+pub fn is_do_not_disturb(db: &Database) -> bool {
+    setting_is_true(db, "do_not_disturb")
+}
+
+pub fn is_chat_muted(db: &Database, chat_id: &str) -> bool {
+    // chat_muted:<id> is how mutes were stored before chat_settings
+    setting_is_true(db, &chat_mute_key(chat_id))
+        || chat_settings::notification_override(db, chat_id) == chat_settings::NOTIFY_MUTED
+}
+
+pub fn preview(message_type: &str, content: &str) -> String {
+    if message_type != "text" {
+        return format!("Sent {}", message_type);
+    }
+    if content.chars().count() > PREVIEW_LEN {
+        let cut: String = content.chars().take(PREVIEW_LEN).collect();
+        format!("{}…", cut)
+    } else {
+        content.to_string()
+    }
+}
+
+/// Show a native notification for an incoming message unless the user is looking at the app
+/// or has silenced it (tray mute, do-not-disturb unless the chat overrides it, or a muted chat).
+pub fn notify_incoming<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    target: NotificationTarget,
+    sender_name: &str,
+    message_type: &str,
+    content: &str,
+) {
+    // Keyword alerts get through muted chats and replace the regular notification
+    if keyword_alerts::check(app, db, &target, sender_name, message_type, content) {
+        return;
+    }
+    let rings_through_dnd =
+        chat_settings::notification_override(db, target.chat_id()) == chat_settings::NOTIFY_ALL;
+    if tray::is_muted()
+        || (is_do_not_disturb(db) && !rings_through_dnd)
+        || is_chat_muted(db, target.chat_id())
+        || window_manager::is_main_window_focused(app)
+    {
+        return;
+    }
+
+    sounds::play(&sounds::sound_for_chat(db, target.chat_id()));
+
+    let text = preview(message_type, content);
+    let body = match &target {
+        NotificationTarget::Direct { .. } => format!("{}: {}", sender_name, text),
+        NotificationTarget::Group { group_name, .. } => {
+            format!("{} - {}: {}", group_name, sender_name, text)
+        }
+    };
+
+    if let Err(e) = show(app, &target, &body) {
+        warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// High-priority notification for a keyword alert: shown even when the chat is muted; only
+/// tray mute and do-not-disturb hold it back
+pub fn notify_keyword_alert<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    target: &NotificationTarget,
+    sender_name: &str,
+    keywords: &[String],
+    content: &str,
+) {
+    if tray::is_muted() || is_do_not_disturb(db) || window_manager::is_main_window_focused(app) {
+        return;
+    }
+    sounds::play(&sounds::sound_for_chat(db, target.chat_id()));
+    let from = match target {
+        NotificationTarget::Direct { .. } => sender_name.to_string(),
+        NotificationTarget::Group { group_name, .. } => format!("{} - {}", group_name, sender_name),
+    };
+    let body = format!(
+        "🔔 {}\n{}: {}",
+        keywords.join(", "),
+        from,
+        preview("text", content)
+    );
+    if let Err(e) = show(app, target, &body) {
+        warn!("Failed to show keyword alert: {}", e);
+    }
+}
+
+// Linux/BSD (XDG) and macOS support notification actions through notify-rust.
+// The handle blocks until the user acts, so each notification waits on its own thread.
+#[cfg(not(windows))]
+fn show<R: Runtime>(
+    app: &AppHandle<R>,
+    target: &NotificationTarget,
+    body: &str,
+) -> Result<(), String> {
+    let mut notification = notify_rust::Notification::new();
+    notification.summary(APP_TITLE).body(body).appname("Pingo");
+    if let NotificationTarget::Direct { .. } = target {
+        notification
+            .action("reply", "Reply")
+            .action("mark_read", "Mark read");
+    }
+    let handle = notification.show().map_err(|e| e.to_string())?;
+
+    let app = app.clone();
+    let (is_group, chat_id) = match target {
+        NotificationTarget::Direct { peer_id } => (false, peer_id.to_string()),
+        NotificationTarget::Group { group_id, .. } => (true, group_id.to_string()),
+    };
+    std::thread::spawn(move || {
+        handle.wait_for_action(|action| match action {
+            "reply" => {
+                let _ = reply(&app, &chat_id);
+            }
+            "mark_read" => {
+                let state = app.state::<AppState>();
+                let _ = mark_read(&app, &state, &chat_id);
+            }
+            "default" => open_chat(&app, &chat_id, is_group),
+            _ => {}
+        });
+    });
+    Ok(())
+}
+
+// Windows toasts from the notification plugin have no action buttons; clicking the toast
+// brings the app to the front and the frontend opens the latest chat.
+#[cfg(windows)]
+fn show<R: Runtime>(
+    app: &AppHandle<R>,
+    _target: &NotificationTarget,
+    body: &str,
+) -> Result<(), String> {
+    use tauri_plugin_notification::NotificationExt;
+    app.notification()
+        .builder()
+        .title(APP_TITLE)
+        .body(body)
+        .show()
+        .map_err(|e| e.to_string())
+}
+
+/// Native notification for a note reminder, with Snooze / Done actions where supported.
+/// The caller decides whether reminders are silenced.
+#[cfg(not(windows))]
+pub fn show_reminder<R: Runtime>(
+    app: &AppHandle<R>,
+    note_id: &str,
+    title: &str,
+    body: &str,
+) -> Result<(), String> {
+    let handle = notify_rust::Notification::new()
+        .summary(title)
+        .body(body)
+        .appname("Pingo")
+        .action("snooze", "Snooze 10 min")
+        .action("done", "Done")
+        .show()
+        .map_err(|e| e.to_string())?;
+
+    let app = app.clone();
+    let note_id = note_id.to_string();
+    std::thread::spawn(move || {
+        handle.wait_for_action(|action| {
+            let state = app.state::<AppState>();
+            match action {
+                "snooze" => {
+                    let _ = crate::note_reminders::snooze(&state.db, &note_id, None);
+                }
+                "done" => {
+                    let _ = crate::note_reminders::mark_done(&state.db, &note_id);
+                }
+                "default" => open_note(&app, &note_id),
+                _ => {}
+            }
+        });
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn show_reminder<R: Runtime>(
+    app: &AppHandle<R>,
+    _note_id: &str,
+    title: &str,
+    body: &str,
+) -> Result<(), String> {
+    use tauri_plugin_notification::NotificationExt;
+    app.notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+        .map_err(|e| e.to_string())
+}
+
+/// Native notification that a scheduled meeting is about to start; clicking it opens the meeting
+#[cfg(not(windows))]
+pub fn show_meeting_reminder<R: Runtime>(
+    app: &AppHandle<R>,
+    meeting_id: &str,
+    title: &str,
+    body: &str,
+) -> Result<(), String> {
+    let handle = notify_rust::Notification::new()
+        .summary(title)
+        .body(body)
+        .appname("Pingo")
+        .show()
+        .map_err(|e| e.to_string())?;
+
+    let app = app.clone();
+    let meeting_id = meeting_id.to_string();
+    std::thread::spawn(move || {
+        handle.wait_for_action(|action| {
+            if action == "default" {
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+                let _ = app.emit(
+                    "notification-open-meeting",
+                    serde_json::json!({ "meeting_id": meeting_id }),
+                );
+            }
+        });
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn show_meeting_reminder<R: Runtime>(
+    app: &AppHandle<R>,
+    _meeting_id: &str,
+    title: &str,
+    body: &str,
+) -> Result<(), String> {
+    use tauri_plugin_notification::NotificationExt;
+    app.notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+        .map_err(|e| e.to_string())
+}
+
+/// Plain native notification with nothing to click through to (background jobs like backups)
+pub fn show_plain<R: Runtime>(app: &AppHandle<R>, title: &str, body: &str) -> Result<(), String> {
+    use tauri_plugin_notification::NotificationExt;
+    app.notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn open_note<R: Runtime>(app: &AppHandle<R>, note_id: &str) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    let _ = app.emit(
+        "notification-open-note",
+        serde_json::json!({ "note_id": note_id }),
+    );
+}
+
+/// Bring the main window forward and ask the frontend to open the chat
+fn open_chat<R: Runtime>(app: &AppHandle<R>, chat_id: &str, is_group: bool) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    let _ = app.emit(
+        "notification-open-chat",
+        serde_json::json!({ "chat_id": chat_id, "is_group": is_group }),
+    );
+}
+
+fn reply<R: Runtime>(app: &AppHandle<R>, peer_id: &str) -> Result<(), String> {
+    window_manager::open_quick_reply(app, peer_id)
+}
+
+fn mark_read<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    peer_id: &str,
+) -> Result<(), String> {
+    state
+        .db
+        .mark_messages_read_from_peer(&state.device_id(), peer_id)
+        .map_err(|e| e.to_string())?;
+    let _ = app.emit(
+        "messages-marked-read",
+        serde_json::json!({ "peer_id": peer_id }),
+    );
+    Ok(())
+}
+
+// ============ COMMANDS ============
+
+/// "Reply" notification action: open the quick reply popup for the sender
+#[tauri::command]
+pub fn notification_reply<R: Runtime>(app: AppHandle<R>, peer_id: String) -> Result<(), String> {
+    reply(&app, &peer_id)
+}
+
+/// "Mark read" notification action: mark everything from the sender as read
+#[tauri::command]
+pub fn notification_mark_read<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+) -> Result<(), String> {
+    mark_read(&app, &state, &peer_id)
+}
+
+#[tauri::command]
+pub fn set_do_not_disturb(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .db
+        .set_setting("do_not_disturb", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())?;
+    device_sync::push_settings(&state, &["do_not_disturb"]);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_do_not_disturb(state: State<AppState>) -> bool {
+    is_do_not_disturb(&state.db)
+}
+
+/// Mute or unmute notifications for a single chat (peer device_id or group_id)
+#[tauri::command]
+pub fn set_chat_muted(state: State<AppState>, chat_id: String, muted: bool) -> Result<(), String> {
+    state
+        .db
+        .set_setting(&chat_mute_key(&chat_id), "false")
+        .map_err(|e| e.to_string())?;
+    let current = chat_settings::notification_override(&state.db, &chat_id);
+    if muted {
+        chat_settings::set(
+            &state.db,
+            &chat_id,
+            "notifications",
+            Some(chat_settings::NOTIFY_MUTED.to_string()),
+        )?;
+    } else if current == chat_settings::NOTIFY_MUTED {
+        chat_settings::set(&state.db, &chat_id, "notifications", None)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_chat_muted(state: State<AppState>, chat_id: String) -> bool {
+    is_chat_muted(&state.db, &chat_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_truncates_and_labels_media() {
+        assert_eq!(preview("text", "hello"), "hello");
+        let long = "x".repeat(150);
+        let p = preview("text", &long);
+        assert_eq!(p.chars().count(), PREVIEW_LEN + 1);
+        assert!(p.ends_with('…'));
+        assert_eq!(preview("image", "file:abc"), "Sent image");
+    }
+
+    #[test]
+    fn test_mute_settings() {
+        let db = Database::new_in_memory().unwrap();
+        assert!(!is_do_not_disturb(&db));
+        assert!(!is_chat_muted(&db, "peer-1"));
+        db.set_setting("do_not_disturb", "true").unwrap();
+        db.set_setting(&chat_mute_key("peer-1"), "true").unwrap();
+        assert!(is_do_not_disturb(&db));
+        assert!(is_chat_muted(&db, "peer-1"));
+        assert!(!is_chat_muted(&db, "peer-2"));
+    }
+}
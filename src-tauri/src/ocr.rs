@@ -0,0 +1,300 @@
+// src-tauri/src/ocr.rs
+// Image text extraction: when configured, images we receive or send are run through an OCR
+// command (tesseract, or anything that prints the text on stdout) in the background. The text
+// is stored with the attachment (the download row, or the message for images we sent) and
+// indexed next to voice transcripts, so search_attachment_text finds "that screenshot with the
+// error code". "ocr-ready" announces each result.
+
+use crate::commands::AppState;
+use crate::db::{Database, DownloadRecord};
+use crate::external_tool::{self, Tool};
+use crate::file_server;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "ocr";
+/// Screenshots are big, but not this big
+const MAX_IMAGE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// One image at a time, so a burst of screenshots doesn't start a dozen OCR processes
+static WORKER: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OcrConfig {
+    pub enabled: bool,
+    /// OCR executable, e.g. "tesseract"
+    pub command: String,
+    /// e.g. ["{file}", "stdout", "-l", "eng+deu"]
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Also read images we send, not just the ones we receive
+    #[serde(default = "default_true")]
+    pub include_sent: bool,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        OcrConfig {
+            enabled: false,
+            command: String::new(),
+            args: Vec::new(),
+            include_sent: true,
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+/// Where the text goes: a received image's download row, or a sent image's message
+enum Target {
+    Download(String),
+    Message(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OcrReady<'a> {
+    download_id: Option<&'a str>,
+    message_id: Option<&'a str>,
+    text: &'a str,
+}
+
+pub fn load(db: &Database) -> OcrConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn is_image(file_type: &str, file_name: &str) -> bool {
+    file_type == "image" || file_server::guess_mime(file_name).starts_with("image/")
+}
+
+/// OCR output is laid out like the image; searching only needs the words
+fn clean_output(output: &str) -> String {
+    output.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Run the OCR command on `file` and return the text it found (possibly none)
+pub fn run(config: &OcrConfig, file: &Path) -> Result<String, String> {
+    let args = external_tool::args_with_file(&config.args, file);
+    let output = Tool {
+        name: "OCR",
+        program: &config.command,
+        args: &args,
+        timeout_secs: config.timeout_secs,
+    }
+    .output(None)?;
+    Ok(clean_output(&output))
+}
+
+/// Read `path`, store the text on `target` and emit "ocr-ready"
+fn extract<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    config: &OcrConfig,
+    target: &Target,
+    path: &str,
+) -> Result<String, String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Can't read {}: {}", path, e))?
+        .len();
+    if size > MAX_IMAGE_BYTES {
+        return Err("Image is too large to read".to_string());
+    }
+
+    let _running = WORKER.lock().unwrap_or_else(|e| e.into_inner());
+    let text = run(config, Path::new(path))?;
+    let (download_id, message_id) = match target {
+        Target::Download(id) => {
+            db.set_download_ocr_text(id, &text)
+                .map_err(|e| e.to_string())?;
+            let message_id = db
+                .get_download(id)
+                .ok()
+                .flatten()
+                .and_then(|d| d.message_id);
+            (Some(id.as_str()), message_id)
+        }
+        Target::Message(id) => {
+            db.set_message_ocr_text(id, &text)
+                .map_err(|e| e.to_string())?;
+            (None, Some(id.clone()))
+        }
+    };
+    info!("Read {} chars of text from {}", text.len(), path);
+    let _ = app.emit(
+        "ocr-ready",
+        OcrReady {
+            download_id,
+            message_id: message_id.as_deref(),
+            text: &text,
+        },
+    );
+    Ok(text)
+}
+
+fn spawn<R: Runtime>(app: &AppHandle<R>, config: OcrConfig, target: Target, path: String) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        if let Err(e) = extract(&app, &state.db, &config, &target, &path) {
+            warn!("OCR of {} failed: {}", path, e);
+        }
+    });
+}
+
+fn enabled(db: &Database) -> Option<OcrConfig> {
+    let config = load(db);
+    (config.enabled && !config.command.trim().is_empty()).then_some(config)
+}
+
+/// Called once a download completes: images are read in the background when enabled
+pub fn queue_download<R: Runtime>(app: &AppHandle<R>, db: &Database, id: &str) {
+    let Some(config) = enabled(db) else {
+        return;
+    };
+    let Some(DownloadRecord {
+        file_type,
+        file_name,
+        path: Some(path),
+        ocr_text: None,
+        ..
+    }) = db.get_download(id).ok().flatten()
+    else {
+        return;
+    };
+    if is_image(&file_type, &file_name) {
+        spawn(app, config, Target::Download(id.to_string()), path);
+    }
+}
+
+/// Called when we send an image from disk
+pub fn queue_sent<R: Runtime>(app: &AppHandle<R>, db: &Database, message_id: &str, path: &str) {
+    let Some(config) = enabled(db).filter(|c| c.include_sent) else {
+        return;
+    };
+    if is_image("", path) {
+        spawn(
+            app,
+            config,
+            Target::Message(message_id.to_string()),
+            path.to_string(),
+        );
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Read the text off an image message now (or again), received or sent; returns the text
+#[tauri::command]
+pub async fn extract_image_text<R: Runtime>(
+    app: AppHandle<R>,
+    message_id: String,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        let db = &state.db;
+        let config = load(db);
+        if config.command.trim().is_empty() {
+            return Err("No OCR command is configured".to_string());
+        }
+        let message = db
+            .get_message(&message_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("Message not found")?;
+        if message.message_type != "image" {
+            return Err("Only images can be read".to_string());
+        }
+        // A received image has a completed download; one we sent has its path on the message
+        let download = db
+            .get_downloads_for_message(&message_id)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|d| d.status == "complete" && d.path.is_some());
+        let (target, path) = match (download, message.file_path) {
+            (Some(d), _) => (Target::Download(d.id), d.path.unwrap_or_default()),
+            (None, Some(path)) => (Target::Message(message_id), path),
+            (None, None) => return Err("The image hasn't been downloaded".to_string()),
+        };
+        extract(&app, db, &config, &target, &path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_ocr_config(state: State<AppState>) -> OcrConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_ocr_config(state: State<AppState>, config: OcrConfig) -> Result<(), String> {
+    if config.enabled && config.command.trim().is_empty() {
+        return Err("No OCR command given".to_string());
+    }
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("Image OCR enabled: {}", config.enabled);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{now, Message};
+    use crate::delivery_status::MessageStatus;
+
+    #[test]
+    fn test_sent_image_text_is_searchable() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("me", "Me", None).unwrap();
+        db.upsert_peer_as_user("bob", "Bob", None).unwrap();
+        db.create_message(&Message {
+            id: "m1".to_string(),
+            sender_id: "me".to_string(),
+            receiver_id: "bob".to_string(),
+            content: "{}".to_string(),
+            message_type: "image".to_string(),
+            file_path: Some("/tmp/shots/build-error.png".to_string()),
+            is_read: false,
+            is_delivered: false,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Queued,
+        })
+        .unwrap();
+        let text = clean_output("  Error E4012:\n\n  disk   quota exceeded \n");
+        assert_eq!(text, "Error E4012: disk quota exceeded");
+        assert!(db.set_message_ocr_text("m1", &text).unwrap());
+
+        let hits = db.search_attachment_text("\"E4012\"*", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].kind, "ocr");
+        assert_eq!(hits[0].download_id, None);
+        assert_eq!(hits[0].file_name, "build-error.png");
+
+        db.erase_message("m1").unwrap();
+        assert!(db
+            .search_attachment_text("\"E4012\"*", 10)
+            .unwrap()
+            .is_empty());
+        assert!(is_image("file", "shot.PNG"));
+        assert!(!is_image("file", "notes.txt"));
+    }
+}
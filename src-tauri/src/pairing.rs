@@ -0,0 +1,649 @@
+// src-tauri/src/pairing.rs
+// QR-code pairing: introduce and verify peers without relying on broadcast discovery.
+// Identity QRs carry only the device id and the identity key's hash, for checking in person
+// that the device on the network is the one in front of you. A pairing QR carries a one-time
+// code; the scanner's introduction must echo it (or come from a trusted peer) to be accepted.
+// Both carry the sender's identity proof, so trust is tied to the identity key like a hello's.
+
+use crate::commands::AppState;
+use crate::crypto::{generate_checksum, generate_device_id, verify_identity_proof, IdentityProof};
+use crate::discovery::{self, PeerInfo};
+use crate::screen_capture::png_bytes_to_data_url;
+use crate::signaling::SignalingMessage;
+use crate::trust::{self, TrustState};
+use base64::Engine;
+use qrcode::{Color, EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const QR_PREFIX: &str = "pingo:pair:";
+const PAIRING_VERSION: u32 = 1;
+const IDENTITY_PREFIX: &str = "pingo:identity:";
+// Rendering: pixels per QR module and the blank border (in modules) scanners need
+const MODULE_PX: u32 = 8;
+const QUIET_ZONE: u32 = 4;
+/// How long the code in a shown pairing QR accepts an introduction
+const PAIR_CODE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// One-time code of the pairing QR we last showed, and when
+static PAIR_CODE: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+
+/// Everything another device needs to reach and verify us
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PairingPayload {
+    v: u32,
+    device_id: String,
+    username: String,
+    public_key: String,
+    /// Every local IPv4 address; the scanner picks one on its own subnet
+    addresses: Vec<String>,
+    /// Signaling (UDP) port
+    port: u16,
+    /// Identity key signature over `public_key`, so trust can be tied to the identity key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identity: Option<IdentityProof>,
+    /// One-time code of this QR, for the scanner to echo in its introduction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pair_code: Option<String>,
+    /// In an introduction: the pair_code of the QR that was scanned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    answers: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingQr {
+    /// Text encoded in the QR, for copy/paste when no camera is available
+    pub payload: String,
+    /// PNG data URL
+    pub image: String,
+    pub fingerprint: String,
+}
+
+/// Who we are, shown as a QR for in-person verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IdentityPayload {
+    v: u32,
+    device_id: String,
+    /// Full SHA-256 of the identity key (hex)
+    fingerprint: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityCheck {
+    pub device_id: String,
+    pub username: String,
+    /// Short fingerprint from the scanned code
+    pub fingerprint: String,
+    /// Whether it matches the identity key the device announces
+    pub matches: bool,
+    /// Set when it matched and the peer is now trusted
+    pub trust: Option<TrustState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairResult {
+    pub peer: PeerInfo,
+    pub fingerprint: String,
+    /// True when the peer is now trusted: it came from a scanned QR that carries its identity
+    /// key. Introductions, and codes from versions without identity keys, are unverified.
+    pub verified: bool,
+}
+
+/// Short, human-comparable form of a public key ("AB12 CD34 ...")
+pub fn fingerprint(public_key: &str) -> String {
+    short_fingerprint(&generate_checksum(public_key.as_bytes()))
+}
+
+fn encode_payload(payload: &PairingPayload) -> Result<String, String> {
+    let json = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "{}{}",
+        QR_PREFIX,
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    ))
+}
+
+fn decode_payload(text: &str) -> Result<PairingPayload, String> {
+    let encoded = text
+        .trim()
+        .strip_prefix(QR_PREFIX)
+        .ok_or("Not a Pingo pairing code")?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_| "Pairing code is corrupted".to_string())?;
+    let payload: PairingPayload =
+        serde_json::from_slice(&json).map_err(|_| "Pairing code is corrupted".to_string())?;
+    if payload.v != PAIRING_VERSION {
+        return Err(format!(
+            "Pairing code version {} is not supported",
+            payload.v
+        ));
+    }
+    let key_len = base64::engine::general_purpose::STANDARD
+        .decode(&payload.public_key)
+        .map(|k| k.len())
+        .unwrap_or(0);
+    if payload.device_id.is_empty() || key_len != 32 || payload.port == 0 {
+        return Err("Pairing code is incomplete".to_string());
+    }
+    if let Some(proof) = &payload.identity {
+        verify_identity_proof(&payload.device_id, &payload.public_key, proof)
+            .map_err(|_| "Pairing code's identity signature doesn't check out".to_string())?;
+    }
+    Ok(payload)
+}
+
+fn encode_identity(payload: &IdentityPayload) -> Result<String, String> {
+    let json = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "{}{}",
+        IDENTITY_PREFIX,
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    ))
+}
+
+fn decode_identity(text: &str) -> Result<IdentityPayload, String> {
+    let encoded = text
+        .trim()
+        .strip_prefix(IDENTITY_PREFIX)
+        .ok_or("Not a Pingo identity code")?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_| "Identity code is corrupted".to_string())?;
+    let payload: IdentityPayload =
+        serde_json::from_slice(&json).map_err(|_| "Identity code is corrupted".to_string())?;
+    if payload.v != PAIRING_VERSION {
+        return Err(format!(
+            "Identity code version {} is not supported",
+            payload.v
+        ));
+    }
+    if payload.device_id.is_empty() || payload.fingerprint.len() != 64 {
+        return Err("Identity code is incomplete".to_string());
+    }
+    Ok(payload)
+}
+
+/// Short form of a full fingerprint, as `fingerprint` shows it
+fn short_fingerprint(full: &str) -> String {
+    full.to_uppercase().as_bytes()[..20]
+        .chunks(4)
+        .map(|c| String::from_utf8_lossy(c).to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Prefer an address on the same /24 as one of ours; otherwise take the first
+fn choose_address(addresses: &[String], local: &[std::net::Ipv4Addr]) -> Option<String> {
+    let parsed: Vec<std::net::Ipv4Addr> = addresses.iter().filter_map(|a| a.parse().ok()).collect();
+    parsed
+        .iter()
+        .find(|a| local.iter().any(|l| l.octets()[..3] == a.octets()[..3]))
+        .or_else(|| parsed.first())
+        .map(|a| a.to_string())
+}
+
+fn local_payload(state: &AppState) -> Result<PairingPayload, String> {
+    let device_id = state.device_id();
+    let public_key = state
+        .crypto
+        .get_public_key()
+        .ok_or("Public key not initialized")?;
+    let port = state
+        .signaling
+        .local_port()
+        .ok_or("Signaling is not running")?;
+    let username = state
+        .db
+        .get_user(&device_id)
+        .map_err(|e| e.to_string())?
+        .map(|u| u.username)
+        .unwrap_or_default();
+    let addresses: Vec<String> = discovery::local_ip_addresses()?
+        .into_iter()
+        .map(|ip| ip.to_string())
+        .collect();
+    if addresses.is_empty() {
+        return Err("No network address to share".to_string());
+    }
+    Ok(PairingPayload {
+        v: PAIRING_VERSION,
+        device_id,
+        username,
+        public_key,
+        addresses,
+        port,
+        identity: state.crypto.identity_proof(),
+        pair_code: None,
+        answers: None,
+    })
+}
+
+/// Consume the pending pair code if `code` matches it and it hasn't expired
+fn take_pair_code(code: Option<&str>) -> bool {
+    let mut pending = PAIR_CODE.lock().unwrap();
+    match (pending.as_ref(), code) {
+        (Some((expected, created)), Some(code))
+            if created.elapsed() < PAIR_CODE_TTL && expected == code =>
+        {
+            *pending = None;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn render_png(text: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::with_error_correction_level(text, EcLevel::M)
+        .map_err(|e| format!("Failed to build QR code: {}", e))?;
+    let modules = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE) * MODULE_PX;
+
+    let img = image::GrayImage::from_fn(size, size, |x, y| {
+        let (mx, my) = (x / MODULE_PX, y / MODULE_PX);
+        let dark = mx >= QUIET_ZONE
+            && my >= QUIET_ZONE
+            && mx - QUIET_ZONE < modules
+            && my - QUIET_ZONE < modules
+            && colors[((my - QUIET_ZONE) * modules + (mx - QUIET_ZONE)) as usize] == Color::Dark;
+        image::Luma([if dark { 0 } else { 255 }])
+    });
+
+    let mut png_bytes = Vec::new();
+    image::DynamicImage::ImageLuma8(img)
+        .write_to(
+            &mut std::io::Cursor::new(&mut png_bytes),
+            image::ImageOutputFormat::Png,
+        )
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(png_bytes)
+}
+
+/// Make a paired peer reachable: signaling route, session key, contact entry and
+/// a discovery record that doesn't time out. With `trust`, the peer is trusted by its identity
+/// key when it has one; returns whether it was.
+fn add_paired_peer(state: &AppState, peer: &PeerInfo, trust: bool) -> Result<bool, String> {
+    state.discovery.check_identity(peer)?;
+    if let Some(identity) = &peer.identity {
+        state
+            .db
+            .pin_identity(&peer.device_id, &identity.identity_key)
+            .map_err(|e| e.to_string())?;
+    }
+    state
+        .signaling
+        .register_peer(&peer.device_id, &peer.ip_address, peer.port)?;
+    state
+        .crypto
+        .establish_session(&peer.device_id, &peer.public_key)?;
+    state
+        .db
+        .upsert_peer_as_user(&peer.device_id, &peer.username, Some(&peer.public_key))
+        .map_err(|e| e.to_string())?;
+    state
+        .db
+        .cache_peer(
+            &peer.device_id,
+            &peer.username,
+            &peer.ip_address,
+            peer.port as i32,
+            Some(&peer.public_key),
+        )
+        .map_err(|e| e.to_string())?;
+    let trusted = match trust::trust_key(peer) {
+        Some(key) if trust => {
+            state
+                .db
+                .set_peer_trusted(&peer.device_id, Some(&generate_checksum(key.as_bytes())))
+                .map_err(|e| e.to_string())?;
+            true
+        }
+        None if trust => {
+            warn!(
+                "Not trusting {}: its pairing code carries no identity key",
+                peer.device_id
+            );
+            false
+        }
+        _ => false,
+    };
+    state.discovery.add_manual_peer(peer);
+    Ok(trusted)
+}
+
+/// Fingerprint to show for a paired peer: its identity key's, else its session key's
+fn peer_fingerprint(peer: &PeerInfo) -> String {
+    fingerprint(trust::trust_key(peer).unwrap_or(&peer.public_key))
+}
+
+/// Refuse a key that differs from what the device is announcing on the network
+fn check_known_key(state: &AppState, device_id: &str, public_key: &str) -> Result<(), String> {
+    match state.discovery.get_peer(device_id) {
+        Some(known) if known.is_online && known.public_key != public_key => Err(
+            "Key does not match the one this device is using now. Ask for a fresh QR code."
+                .to_string(),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// The peer a decoded pairing code describes, reached at `ip_address`
+fn payload_peer(p: PairingPayload, ip_address: String) -> PeerInfo {
+    PeerInfo {
+        device_id: p.device_id,
+        username: p.username,
+        ip_address,
+        port: p.port,
+        public_key: p.public_key,
+        is_online: true,
+        avatar_hash: None,
+        status: None,
+        compression: false,
+        identity: p.identity,
+    }
+}
+
+fn accept_introduction(state: &AppState, from: &str, payload: &str) -> Result<PairResult, String> {
+    let p = decode_payload(payload)?;
+    if p.device_id != from {
+        return Err(format!(
+            "Introduction from {} carries another device id",
+            from
+        ));
+    }
+    // Only from a peer we've verified, or one that just scanned the QR we're showing
+    if !trust::is_trusted(&state.db, from) && !take_pair_code(p.answers.as_deref()) {
+        return Err(format!(
+            "Introduction from {} doesn't answer a pairing code we showed",
+            from
+        ));
+    }
+    check_known_key(state, from, &p.public_key)?;
+    // Reply to the address the introduction actually came from
+    let ip = state
+        .signaling
+        .get_peer(from)
+        .map(|c| c.address.ip().to_string())
+        .or_else(|| {
+            choose_address(
+                &p.addresses,
+                &discovery::local_ip_addresses().unwrap_or_default(),
+            )
+        })
+        .ok_or("No address for introduced peer")?;
+    let peer = payload_peer(p, ip);
+    add_paired_peer(state, &peer, false)?;
+    Ok(PairResult {
+        fingerprint: peer_fingerprint(&peer),
+        peer,
+        verified: false,
+    })
+}
+
+/// Handle a PairIntroduction from a peer that scanned our QR (called from the signaling forwarder)
+pub fn handle_introduction<R: Runtime>(app: &AppHandle<R>, from: &str, payload: &str) {
+    let state = app.state::<AppState>();
+    match accept_introduction(&state, from, payload) {
+        Ok(paired) => {
+            let _ = app.emit("peer-paired", &paired);
+        }
+        Err(e) => info!("Ignoring pairing introduction: {}", e),
+    }
+}
+
+// ============ COMMANDS ============
+
+/// QR code (PNG data URL + raw text) with our device id, public key and address
+#[tauri::command]
+pub fn generate_pairing_qr(state: State<AppState>) -> Result<PairingQr, String> {
+    let mut payload = local_payload(&state)?;
+    let code = generate_device_id();
+    *PAIR_CODE.lock().unwrap() = Some((code.clone(), Instant::now()));
+    payload.pair_code = Some(code);
+    let text = encode_payload(&payload)?;
+    let png = render_png(&text)?;
+    Ok(PairingQr {
+        image: png_bytes_to_data_url(&png),
+        fingerprint: fingerprint(&payload.public_key),
+        payload: text,
+    })
+}
+
+/// QR code with our device id and identity key fingerprint, for another device to verify us
+#[tauri::command]
+pub fn get_identity_qr(state: State<AppState>) -> Result<PairingQr, String> {
+    let identity_key = state
+        .crypto
+        .get_identity_key()
+        .ok_or("Identity key not initialized")?;
+    let full = generate_checksum(identity_key.as_bytes());
+    let text = encode_identity(&IdentityPayload {
+        v: PAIRING_VERSION,
+        device_id: state.device_id(),
+        fingerprint: full.clone(),
+    })?;
+    let png = render_png(&text)?;
+    Ok(PairingQr {
+        image: png_bytes_to_data_url(&png),
+        fingerprint: short_fingerprint(&full),
+        payload: text,
+    })
+}
+
+/// Check a scanned identity code against the identity key the device announces on the
+/// network; a match marks the peer trusted
+#[tauri::command]
+pub fn verify_identity_qr<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    payload: String,
+) -> Result<IdentityCheck, String> {
+    let p = decode_identity(&payload)?;
+    if p.device_id == state.device_id() {
+        return Err("This is this device's own identity code".to_string());
+    }
+    let peer = state
+        .discovery
+        .get_peer(&p.device_id)
+        .ok_or("That device isn't on the network right now")?;
+    let announced = peer
+        .identity
+        .as_ref()
+        .ok_or("That device doesn't announce an identity key; it needs to be updated")?;
+    let matches =
+        generate_checksum(announced.identity_key.as_bytes()).eq_ignore_ascii_case(&p.fingerprint);
+    let trust = if matches {
+        info!("Verified identity of {} in person", p.device_id);
+        // Trust the key that was just compared, not whatever discovery holds by now
+        Some(trust::set_trusted(
+            &app,
+            &state,
+            &p.device_id,
+            Some(&announced.identity_key),
+        )?)
+    } else {
+        warn!(
+            "Identity code for {} doesn't match its announced key",
+            p.device_id
+        );
+        None
+    };
+    Ok(IdentityCheck {
+        device_id: p.device_id,
+        username: peer.username,
+        fingerprint: short_fingerprint(&p.fingerprint),
+        matches,
+        trust,
+    })
+}
+
+/// Add and trust the peer described by a scanned or pasted pairing code, then introduce
+/// ourselves so it learns about us without broadcast discovery either
+#[tauri::command]
+pub fn pair_from_qr(state: State<AppState>, payload: String) -> Result<PairResult, String> {
+    let p = decode_payload(&payload)?;
+    if p.device_id == state.device_id() {
+        return Err("This is this device's own pairing code".to_string());
+    }
+    check_known_key(&state, &p.device_id, &p.public_key)?;
+    let ip = choose_address(&p.addresses, &discovery::local_ip_addresses()?)
+        .ok_or("Pairing code has no usable address")?;
+    let pair_code = p.pair_code.clone();
+
+    let peer = payload_peer(p, ip);
+    let verified = add_paired_peer(&state, &peer, true)?;
+
+    let introduction = local_payload(&state).and_then(|mut ours| {
+        ours.answers = pair_code;
+        encode_payload(&ours)
+    });
+    match introduction {
+        Ok(ours) => {
+            let msg = SignalingMessage::PairIntroduction {
+                from: state.device_id(),
+                to: peer.device_id.clone(),
+                payload: ours,
+            };
+            if let Err(e) = state.signaling.send_message(&peer.device_id, &msg) {
+                warn!("Failed to introduce to {}: {}", peer.device_id, e);
+            }
+        }
+        Err(e) => warn!("Failed to introduce to {}: {}", peer.device_id, e),
+    }
+
+    Ok(PairResult {
+        fingerprint: peer_fingerprint(&peer),
+        peer,
+        verified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PairingPayload {
+        PairingPayload {
+            v: PAIRING_VERSION,
+            device_id: "abc123".to_string(),
+            username: "Laptop".to_string(),
+            public_key: base64::engine::general_purpose::STANDARD.encode([7u8; 32]),
+            addresses: vec!["10.0.0.5".to_string(), "192.168.1.20".to_string()],
+            port: 45678,
+            identity: None,
+            pair_code: Some("code".to_string()),
+            answers: None,
+        }
+    }
+
+    #[test]
+    fn test_payload_roundtrip_and_rejects() {
+        let text = encode_payload(&sample()).unwrap();
+        assert!(text.starts_with(QR_PREFIX));
+        let back = decode_payload(&format!("  {}\n", text)).unwrap();
+        assert_eq!(back.device_id, "abc123");
+        assert_eq!(back.port, 45678);
+
+        assert!(decode_payload("hello").is_err());
+        assert!(decode_payload("pingo:pair:!!!").is_err());
+        let mut bad = sample();
+        bad.public_key = "short".to_string();
+        assert!(decode_payload(&encode_payload(&bad).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_pair_code_is_one_time() {
+        *PAIR_CODE.lock().unwrap() = Some(("code".to_string(), Instant::now()));
+        assert!(!take_pair_code(None));
+        assert!(!take_pair_code(Some("other")));
+        assert!(take_pair_code(Some("code")));
+        assert!(!take_pair_code(Some("code")));
+
+        // Older introductions without the field still decode
+        let mut old = sample();
+        old.pair_code = None;
+        let back = decode_payload(&encode_payload(&old).unwrap()).unwrap();
+        assert_eq!(back.answers, None);
+    }
+
+    #[test]
+    fn test_paired_trust_survives_signed_hellos() {
+        let laptop = crate::crypto::CryptoManager::new();
+        laptop.set_device_id("abc123");
+        laptop.generate_identity();
+        let signed = PairingPayload {
+            public_key: laptop.generate_keypair(),
+            identity: laptop.identity_proof(),
+            ..sample()
+        };
+        let p = decode_payload(&encode_payload(&signed).unwrap()).unwrap();
+        let paired = payload_peer(p, "192.168.1.20".to_string());
+        assert_eq!(paired.identity, signed.identity);
+
+        // Trusted the way add_paired_peer does it, then seen in a signed hello after rotation
+        let db = crate::db::Database::new_in_memory().unwrap();
+        let key = trust::trust_key(&paired).unwrap();
+        db.set_peer_trusted("abc123", Some(&generate_checksum(key.as_bytes())))
+            .unwrap();
+        let crypto = crate::crypto::CryptoManager::new();
+        trust::on_peer_seen(&db, &crypto, &paired);
+        let hello = PeerInfo {
+            public_key: laptop.generate_keypair(),
+            identity: laptop.identity_proof(),
+            ..paired.clone()
+        };
+        trust::on_peer_seen(&db, &crypto, &hello);
+        assert!(trust::is_trusted(&db, "abc123"));
+
+        // A proof that doesn't sign the code's key is refused; no proof pairs untrusted
+        let forged = PairingPayload {
+            public_key: sample().public_key,
+            ..signed
+        };
+        assert!(decode_payload(&encode_payload(&forged).unwrap()).is_err());
+        let unsigned = decode_payload(&encode_payload(&sample()).unwrap()).unwrap();
+        assert!(trust::trust_key(&payload_peer(unsigned, String::new())).is_none());
+    }
+
+    #[test]
+    fn test_identity_code_roundtrip() {
+        let full = generate_checksum(b"identity");
+        let text = encode_identity(&IdentityPayload {
+            v: PAIRING_VERSION,
+            device_id: "abc123".to_string(),
+            fingerprint: full.clone(),
+        })
+        .unwrap();
+        let back = decode_identity(&text).unwrap();
+        assert_eq!(back.fingerprint, full);
+        assert_eq!(short_fingerprint(&full), fingerprint("identity"));
+        // A pairing code isn't an identity code
+        assert!(decode_identity(&encode_payload(&sample()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_choose_address_prefers_same_subnet() {
+        let addrs = sample().addresses;
+        let local = ["192.168.1.7".parse().unwrap()];
+        assert_eq!(
+            choose_address(&addrs, &local).as_deref(),
+            Some("192.168.1.20")
+        );
+        assert_eq!(choose_address(&addrs, &[]).as_deref(), Some("10.0.0.5"));
+        assert_eq!(choose_address(&[], &local), None);
+    }
+
+    #[test]
+    fn test_render_png_and_fingerprint() {
+        let png = render_png(&encode_payload(&sample()).unwrap()).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let fp = fingerprint("key");
+        assert_eq!(fp.len(), 24);
+        assert_eq!(fp, fingerprint("key"));
+        assert_ne!(fp, fingerprint("other"));
+    }
+}
@@ -0,0 +1,578 @@
+// src-tauri/src/pin_pairing.rs
+// PIN pairing for first contact. With "require PIN pairing" on, no direct messages, file offers
+// or group invitations go to or come from a device until both users have paired: one side
+// shows a 6-digit PIN, the other types it in. The PIN never goes over the network: the two sides
+// run SPAKE2 (over Ristretto255) with it, bound to the nonce and both identity keys, and confirm
+// the shared key. Nothing sent lets an eavesdropper test PINs offline, and an active attacker
+// gets one guess per exchange (MAX_ATTEMPTS per PIN). The pairing is kept with the checksum of
+// the key the peer proved; if it shows up with another key, it has to pair again.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::crypto::generate_checksum;
+use crate::db::Database;
+use crate::message_requests;
+use crate::signaling::SignalingMessage;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "require_pin_pairing";
+const PIN_TTL: Duration = Duration::from_secs(2 * 60);
+/// Wrong PINs accepted before the pairing is abandoned
+const MAX_ATTEMPTS: u32 = 3;
+
+/// A pairing we started: our SPAKE2 half for the PIN shown on our screen
+struct Outgoing {
+    nonce: String,
+    password: Scalar,
+    secret: Scalar,
+    message: String,
+    started: Instant,
+    attempts: u32,
+}
+
+/// A pairing a peer started with us; `key` is the shared key from the PIN our user typed,
+/// once they have
+struct Incoming {
+    nonce: String,
+    public_key: String,
+    message: String,
+    key: Option<[u8; 32]>,
+    started: Instant,
+}
+
+/// Which side of the exchange we're on: the initiator blinds with M, the responder with N
+#[derive(Clone, Copy)]
+enum Role {
+    Initiator,
+    Responder,
+}
+
+// peer id -> pairing in progress
+static OUTGOING: Mutex<Option<HashMap<String, Outgoing>>> = Mutex::new(None);
+static INCOMING: Mutex<Option<HashMap<String, Incoming>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PinPairingRequest {
+    pub peer_id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PinPairingResult {
+    pub peer_id: String,
+    pub paired: bool,
+    /// Set when a wrong PIN used up the last attempt or the pairing expired
+    pub error: Option<String>,
+}
+
+pub fn is_required(db: &Database) -> bool {
+    db.get_setting(SETTING_KEY).ok().flatten().as_deref() == Some("true")
+}
+
+fn fingerprint(public_key: &str) -> String {
+    generate_checksum(public_key.as_bytes())
+}
+
+/// Whether messages may flow with a peer whose current key has `key_fingerprint`: pairing isn't
+/// required, or we've trusted it or paired with it by PIN using that key
+fn is_paired_with(db: &Database, peer_id: &str, key_fingerprint: Option<&str>) -> bool {
+    !is_required(db)
+        || db
+            .is_paired_contact(peer_id, key_fingerprint)
+            .unwrap_or(false)
+}
+
+/// `is_paired_with` the key discovery currently sees for the peer
+pub fn is_paired(state: &AppState, peer_id: &str) -> bool {
+    let current = state
+        .discovery
+        .get_peer(peer_id)
+        .map(|p| fingerprint(&p.public_key));
+    is_paired_with(&state.db, peer_id, current.as_deref())
+}
+
+/// For outgoing messages
+pub fn check_outgoing(state: &AppState, peer_id: &str) -> Result<(), String> {
+    if is_paired(state, peer_id) {
+        Ok(())
+    } else {
+        Err(format!("Pair with {} using a PIN first", peer_id))
+    }
+}
+
+/// Incoming first-contact messages (what privacy mode would hold) from a peer we haven't paired
+/// with; the caller drops them
+pub fn blocks(state: &AppState, msg: &SignalingMessage) -> bool {
+    match message_requests::sender_of_request(msg) {
+        Some(from) if !is_paired(state, from) => {
+            info!("Dropped a message from unpaired peer {}", from);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// A point nobody knows the discrete log of
+fn fixed_point(label: &str) -> RistrettoPoint {
+    RistrettoPoint::from_uniform_bytes(&Sha512::digest(label.as_bytes()).into())
+}
+
+fn blinding_point(role: Role) -> RistrettoPoint {
+    match role {
+        Role::Initiator => fixed_point("pingo-pin-pairing|M"),
+        Role::Responder => fixed_point("pingo-pin-pairing|N"),
+    }
+}
+
+/// The SPAKE2 password: the PIN bound to this exchange and both identity keys
+fn password(pin: &str, nonce: &str, initiator_key: &str, responder_key: &str) -> Scalar {
+    let input = format!(
+        "pingo-pin-pairing|{}|{}|{}|{}",
+        pin, nonce, initiator_key, responder_key
+    );
+    Scalar::from_bytes_mod_order_wide(&Sha512::digest(input.as_bytes()).into())
+}
+
+/// Our secret and the blinded message to send for it
+fn spake_start(role: Role, password: &Scalar) -> (Scalar, String) {
+    let mut bytes = [0u8; 64];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let secret = Scalar::from_bytes_mod_order_wide(&bytes);
+    let element = RISTRETTO_BASEPOINT_POINT * secret + blinding_point(role) * password;
+    (secret, BASE64.encode(element.compress().as_bytes()))
+}
+
+/// The key both sides share if they used the same password; `initiator` and `responder` are
+/// the two SPAKE2 messages
+fn spake_finish(
+    role: Role,
+    secret: &Scalar,
+    password: &Scalar,
+    initiator: &str,
+    responder: &str,
+) -> Result<[u8; 32], String> {
+    let (theirs, their_role) = match role {
+        Role::Initiator => (responder, Role::Responder),
+        Role::Responder => (initiator, Role::Initiator),
+    };
+    let element = BASE64
+        .decode(theirs)
+        .ok()
+        .and_then(|b| CompressedRistretto::from_slice(&b).ok())
+        .and_then(|c| c.decompress())
+        .ok_or("Malformed pairing message")?;
+    let shared = (element - blinding_point(their_role) * password) * secret;
+    let mut hasher = Sha256::new();
+    hasher.update(b"pingo-pin-pairing");
+    hasher.update(initiator.as_bytes());
+    hasher.update(responder.as_bytes());
+    hasher.update(shared.compress().as_bytes());
+    hasher.update(password.as_bytes());
+    Ok(hasher.finalize().into())
+}
+
+/// What each side sends to show it derived the same key. `role` differs per direction so the
+/// initiator's confirmation can't be the responder's proof echoed back.
+fn proof(role: &str, key: &[u8; 32]) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(role.as_bytes());
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+fn local_key(state: &AppState) -> Result<String, String> {
+    state
+        .crypto
+        .get_public_key()
+        .ok_or_else(|| "No identity key yet".to_string())
+}
+
+/// The key discovery currently sees for the peer, which a pairing message must carry
+fn check_key(state: &AppState, peer_id: &str, public_key: &str) -> Result<(), String> {
+    match state.discovery.get_peer(peer_id) {
+        Some(known) if known.public_key == public_key => Ok(()),
+        Some(_) => Err(format!("Pairing from {} with an unexpected key", peer_id)),
+        None => Err(format!("Pairing from unknown peer {}", peer_id)),
+    }
+}
+
+fn paired<R: Runtime>(app: &AppHandle<R>, state: &AppState, peer_id: &str, public_key: &str) {
+    if let Err(e) = state.db.record_pairing(peer_id, &fingerprint(public_key)) {
+        warn!("Failed to record pairing with {}: {}", peer_id, e);
+    }
+    if let Err(e) = state.db.approve_contact(peer_id) {
+        warn!("Failed to approve {} after pairing: {}", peer_id, e);
+    }
+    if !state.crypto.has_session(peer_id) {
+        if let Err(e) = state.crypto.establish_session(peer_id, public_key) {
+            warn!("Session with {} failed after pairing: {}", peer_id, e);
+        }
+    }
+    info!("Paired with {} by PIN", peer_id);
+    emit_result(app, peer_id, true, None);
+}
+
+fn emit_result<R: Runtime>(app: &AppHandle<R>, peer_id: &str, paired: bool, error: Option<String>) {
+    let result = PinPairingResult {
+        peer_id: peer_id.to_string(),
+        paired,
+        error,
+    };
+    let _ = app.emit("pin-pairing-result", &result);
+}
+
+fn handle_request<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    from: &str,
+    nonce: &str,
+    public_key: &str,
+    username: &str,
+    spake: &str,
+) -> Result<(), String> {
+    check_key(state, from, public_key)?;
+    INCOMING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(
+            from.to_string(),
+            Incoming {
+                nonce: nonce.to_string(),
+                public_key: public_key.to_string(),
+                message: spake.to_string(),
+                key: None,
+                started: Instant::now(),
+            },
+        );
+    let request = PinPairingRequest {
+        peer_id: from.to_string(),
+        username: username.to_string(),
+    };
+    let _ = app.emit("pin-pairing-requested", &request);
+    Ok(())
+}
+
+/// We showed the PIN; check the peer's proof and confirm (or refuse) it
+fn handle_response<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    from: &str,
+    nonce: &str,
+    public_key: &str,
+    spake: &str,
+    response: &str,
+) -> Result<(), String> {
+    check_key(state, from, public_key)?;
+    let (accepted, confirm, error) = {
+        let mut guard = OUTGOING.lock().unwrap();
+        let outgoing = guard.get_or_insert_with(HashMap::new);
+        let pending = match outgoing.get_mut(from) {
+            Some(p) if p.nonce == nonce && p.started.elapsed() < PIN_TTL => p,
+            _ => return Err(format!("Unexpected PIN response from {}", from)),
+        };
+        let key = spake_finish(
+            Role::Initiator,
+            &pending.secret,
+            &pending.password,
+            &pending.message,
+            spake,
+        )?;
+        if proof("response", &key) == response {
+            let confirm = proof("confirm", &key);
+            outgoing.remove(from);
+            (true, Some(confirm), None)
+        } else {
+            pending.attempts += 1;
+            if pending.attempts >= MAX_ATTEMPTS {
+                outgoing.remove(from);
+                (false, None, Some("Too many wrong PINs".to_string()))
+            } else {
+                (false, None, None)
+            }
+        }
+    };
+    let result = SignalingMessage::PinPairResult {
+        from: state.device_id(),
+        to: from.to_string(),
+        nonce: nonce.to_string(),
+        accepted,
+        confirm,
+    };
+    send_to_peer(state, from, &result)?;
+    if accepted {
+        paired(app, state, from, public_key);
+    } else if error.is_some() {
+        emit_result(app, from, false, error);
+    }
+    Ok(())
+}
+
+/// We typed the PIN; a confirmation proves the initiator knew it too
+fn handle_result<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    from: &str,
+    nonce: &str,
+    accepted: bool,
+    confirm: Option<&str>,
+) -> Result<(), String> {
+    let mut guard = INCOMING.lock().unwrap();
+    let incoming = guard.get_or_insert_with(HashMap::new);
+    let pending = match incoming.get_mut(from) {
+        Some(p) if p.nonce == nonce => p,
+        _ => return Err(format!("Unexpected PIN result from {}", from)),
+    };
+    let Some(key) = pending.key.take() else {
+        return Err(format!("PIN result from {} before a PIN was entered", from));
+    };
+    if !accepted {
+        drop(guard);
+        emit_result(app, from, false, Some("Wrong PIN".to_string()));
+        return Ok(());
+    }
+    let expected = proof("confirm", &key);
+    if confirm != Some(expected.as_str()) {
+        incoming.remove(from);
+        drop(guard);
+        emit_result(
+            app,
+            from,
+            false,
+            Some("The peer could not confirm the PIN".to_string()),
+        );
+        return Err(format!("Bad PIN confirmation from {}", from));
+    }
+    let Some(done) = incoming.remove(from) else {
+        return Ok(());
+    };
+    drop(guard);
+    paired(app, state, from, &done.public_key);
+    Ok(())
+}
+
+/// Signaling handler for the PinPair* messages
+pub fn handle_message<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    let state = app.state::<AppState>();
+    let result = match msg {
+        SignalingMessage::PinPairRequest {
+            from,
+            nonce,
+            public_key,
+            username,
+            spake,
+            ..
+        } => handle_request(app, &state, from, nonce, public_key, username, spake),
+        SignalingMessage::PinPairResponse {
+            from,
+            nonce,
+            public_key,
+            spake,
+            proof,
+            ..
+        } => handle_response(app, &state, from, nonce, public_key, spake, proof),
+        SignalingMessage::PinPairResult {
+            from,
+            nonce,
+            accepted,
+            confirm,
+            ..
+        } => handle_result(app, &state, from, nonce, *accepted, confirm.as_deref()),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        info!("Ignoring PIN pairing message: {}", e);
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_require_pin_pairing(state: State<AppState>) -> bool {
+    is_required(&state.db)
+}
+
+#[tauri::command]
+pub fn set_require_pin_pairing(state: State<AppState>, required: bool) -> Result<(), String> {
+    state
+        .db
+        .set_setting(SETTING_KEY, if required { "true" } else { "false" })
+        .map_err(|e| e.to_string())
+}
+
+/// Whether messages can be exchanged with the peer without pairing first
+#[tauri::command]
+pub fn is_pin_paired(state: State<AppState>, peer_id: String) -> bool {
+    is_paired(&state, &peer_id)
+}
+
+/// Start pairing with a peer. Returns the PIN to show; the peer's user types it in.
+#[tauri::command]
+pub fn start_pin_pairing(state: State<AppState>, peer_id: String) -> Result<String, String> {
+    let Some(peer) = state.discovery.get_peer(&peer_id) else {
+        return Err(format!("Peer {} is not on the network", peer_id));
+    };
+    let pin = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+    let nonce = crate::db::generate_id();
+    let ours = local_key(&state)?;
+    let password = password(&pin, &nonce, &ours, &peer.public_key);
+    let (secret, message) = spake_start(Role::Initiator, &password);
+    let username = state
+        .db
+        .get_user(&state.device_id())
+        .ok()
+        .flatten()
+        .map(|u| u.username)
+        .unwrap_or_default();
+    let request = SignalingMessage::PinPairRequest {
+        from: state.device_id(),
+        to: peer_id.clone(),
+        nonce: nonce.clone(),
+        public_key: ours,
+        username,
+        spake: message.clone(),
+    };
+    OUTGOING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(
+            peer_id.clone(),
+            Outgoing {
+                nonce,
+                password,
+                secret,
+                message,
+                started: Instant::now(),
+                attempts: 0,
+            },
+        );
+    send_to_peer(&state, &peer_id, &request)?;
+    Ok(pin)
+}
+
+/// Answer a peer's pairing request with the PIN shown on its screen
+#[tauri::command]
+pub fn submit_pairing_pin(
+    state: State<AppState>,
+    peer_id: String,
+    pin: String,
+) -> Result<(), String> {
+    let pin: String = pin.chars().filter(|c| c.is_ascii_digit()).collect();
+    let ours = local_key(&state)?;
+    let response = {
+        let mut guard = INCOMING.lock().unwrap();
+        let incoming = guard.get_or_insert_with(HashMap::new);
+        let pending = match incoming.get_mut(&peer_id) {
+            Some(p) if p.started.elapsed() < PIN_TTL => p,
+            Some(_) => {
+                incoming.remove(&peer_id);
+                return Err("The pairing request expired".to_string());
+            }
+            None => return Err(format!("No pairing request from {}", peer_id)),
+        };
+        let password = password(&pin, &pending.nonce, &pending.public_key, &ours);
+        let (secret, message) = spake_start(Role::Responder, &password);
+        let key = spake_finish(
+            Role::Responder,
+            &secret,
+            &password,
+            &pending.message,
+            &message,
+        )?;
+        pending.key = Some(key);
+        SignalingMessage::PinPairResponse {
+            from: state.device_id(),
+            to: peer_id.clone(),
+            nonce: pending.nonce.clone(),
+            public_key: ours,
+            spake: message,
+            proof: proof("response", &key),
+        }
+    };
+    send_to_peer(&state, &peer_id, &response)
+}
+
+/// Abandon a pairing in either direction
+#[tauri::command]
+pub fn cancel_pin_pairing(peer_id: String) {
+    if let Some(outgoing) = OUTGOING.lock().unwrap().as_mut() {
+        outgoing.remove(&peer_id);
+    }
+    if let Some(incoming) = INCOMING.lock().unwrap().as_mut() {
+        incoming.remove(&peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{now, Message};
+    use crate::delivery_status::MessageStatus;
+
+    #[test]
+    fn test_spake_keys_agree_only_on_the_same_pin_and_keys() {
+        let initiator = password("123456", "n1", "keyA", "keyB");
+        let (x, message_a) = spake_start(Role::Initiator, &initiator);
+        let exchange = |pin: &str, responder_key: &str| {
+            let responder = password(pin, "n1", "keyA", responder_key);
+            let (y, message_b) = spake_start(Role::Responder, &responder);
+            let theirs =
+                spake_finish(Role::Responder, &y, &responder, &message_a, &message_b).unwrap();
+            let ours =
+                spake_finish(Role::Initiator, &x, &initiator, &message_a, &message_b).unwrap();
+            (ours, theirs)
+        };
+        let (ours, theirs) = exchange("123456", "keyB");
+        assert_eq!(ours, theirs);
+        assert_ne!(proof("response", &ours), proof("confirm", &ours));
+        let (ours, theirs) = exchange("123457", "keyB");
+        assert_ne!(ours, theirs);
+        let (ours, theirs) = exchange("123456", "keyC");
+        assert_ne!(ours, theirs);
+        assert!(spake_finish(Role::Initiator, &x, &initiator, &message_a, "junk").is_err());
+    }
+
+    #[test]
+    fn test_unpaired_peers_are_blocked_only_when_required() {
+        let db = Database::new_in_memory().unwrap();
+        let key = fingerprint("keyA");
+        assert!(is_paired_with(&db, "ana", Some(&key)));
+        db.set_setting(SETTING_KEY, "true").unwrap();
+        assert!(!is_paired_with(&db, "ana", Some(&key)));
+
+        // Conversation history is no proof of pairing
+        db.create_message(&Message {
+            id: "m1".to_string(),
+            sender_id: "ana".to_string(),
+            receiver_id: "me".to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: true,
+            is_delivered: true,
+            created_at: now(),
+            lamport: 0,
+            status: MessageStatus::Read,
+        })
+        .unwrap();
+        db.approve_contact("ana").unwrap();
+        assert!(!is_paired_with(&db, "ana", Some(&key)));
+
+        db.record_pairing("ana", &key).unwrap();
+        assert!(is_paired_with(&db, "ana", Some(&key)));
+        // Another key (or none seen) has to pair again
+        assert!(!is_paired_with(&db, "ana", Some(&fingerprint("keyB"))));
+        assert!(!is_paired_with(&db, "ana", None));
+    }
+}
@@ -0,0 +1,123 @@
+// src-tauri/src/power.rs
+// Sleep/resume handling: after a suspend (or a network change) sockets are stale and peers
+// may have moved, so rebind signaling and discovery, re-register peers and resend the
+// undelivered queue without waiting for the user to restart the app
+
+use crate::commands::{self, AppState};
+use crate::config;
+use crate::delivery_retry;
+use crate::discovery;
+use std::collections::BTreeSet;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{info, warn};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// A wall-clock gap this much longer than CHECK_INTERVAL means the machine was suspended
+const RESUME_THRESHOLD: Duration = Duration::from_secs(20);
+// Long enough for the old listener and forwarder threads (500ms poll) to exit
+const RESTART_GRACE: Duration = Duration::from_secs(1);
+// Give peers a couple of announce rounds to refresh their addresses before resending
+const ANNOUNCE_WAIT: Duration = Duration::from_secs(3);
+
+static RECOVERING: AtomicBool = AtomicBool::new(false);
+
+fn local_addresses() -> BTreeSet<Ipv4Addr> {
+    discovery::local_ip_addresses()
+        .unwrap_or_default()
+        .into_iter()
+        .collect()
+}
+
+/// Watch for suspend/resume and network changes for the life of the app
+pub fn start_monitor<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || {
+        let mut last_tick = SystemTime::now();
+        let mut last_addresses = local_addresses();
+        loop {
+            thread::sleep(CHECK_INTERVAL);
+            // Sleep runs on the monotonic clock, which stops during suspend; the wall clock doesn't
+            let elapsed = SystemTime::now()
+                .duration_since(last_tick)
+                .unwrap_or_default();
+            last_tick = SystemTime::now();
+
+            let addresses = local_addresses();
+            let network_changed = !addresses.is_empty() && addresses != last_addresses;
+            last_addresses = addresses;
+
+            if elapsed > CHECK_INTERVAL + RESUME_THRESHOLD {
+                recover(&app, &format!("Resumed after {}s", elapsed.as_secs()));
+            } else if network_changed {
+                recover(&app, "Network addresses changed");
+            }
+        }
+    });
+}
+
+/// Rebind sockets, re-announce, re-register peers and flush the offline queue.
+/// Services that weren't running (app not initialised yet) are left alone.
+fn recover<R: Runtime>(app: &AppHandle<R>, reason: &str) {
+    if RECOVERING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let state = app.state::<AppState>();
+    let signaling_port = state.signaling.local_port();
+    let discovery_was_running = state.discovery.is_running();
+    if signaling_port.is_none() && !discovery_was_running {
+        RECOVERING.store(false, Ordering::SeqCst);
+        return;
+    }
+    info!("{}: rebinding network services", reason);
+
+    state.discovery.stop();
+    state.signaling.stop();
+    thread::sleep(RESTART_GRACE);
+
+    let mut port = signaling_port;
+    if let Some(p) = signaling_port {
+        match commands::start_signaling(app.clone(), app.state(), Some(p)) {
+            Ok(actual) => port = Some(actual),
+            Err(e) => warn!("Failed to restart signaling: {}", e),
+        }
+    }
+    if discovery_was_running {
+        let username = state
+            .db
+            .get_user(&state.device_id())
+            .ok()
+            .flatten()
+            .map(|u| u.username)
+            .unwrap_or_default();
+        let port = port.unwrap_or_else(|| config::load(&state.db).signaling_port);
+        if let Err(e) = commands::start_discovery(app.clone(), app.state(), username, port) {
+            warn!("Failed to restart discovery: {}", e);
+        }
+    }
+
+    // Known peers first; fresh announcements update any that moved
+    for peer in state.discovery.get_peers() {
+        let _ = state
+            .signaling
+            .register_peer(&peer.device_id, &peer.ip_address, peer.port);
+    }
+    thread::sleep(ANNOUNCE_WAIT);
+    let resent = delivery_retry::flush(&state);
+    info!("Network services restored; resent {} message(s)", resent);
+
+    let _ = app.emit(
+        "system-resumed",
+        serde_json::json!({ "reason": reason, "resent": resent }),
+    );
+    RECOVERING.store(false, Ordering::SeqCst);
+}
+
+/// Manually run the same recovery as after a resume
+#[tauri::command]
+pub fn reconnect_network<R: Runtime>(app: AppHandle<R>) {
+    thread::spawn(move || recover(&app, "Manual reconnect"));
+}
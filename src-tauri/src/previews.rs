@@ -0,0 +1,306 @@
+// src-tauri/src/previews.rs
+// Document previews: the first page of a PDF, Word, Excel, PowerPoint or OpenDocument file is
+// rendered to a PNG and cached as the file's thumbnail, which the file server hands out at
+// /thumb/<file id>. PDFs go through poppler's pdftoppm; office documents are converted to PDF
+// with a headless LibreOffice first. Previews of files we share are rendered up front so peers
+// can show them before downloading; anything else is rendered on request.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use crate::external_tool::Tool;
+use crate::file_server::{self, FileServer};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "document_previews";
+const OFFICE_EXTENSIONS: [&str; 10] = [
+    "doc", "docx", "odt", "rtf", "xls", "xlsx", "ods", "ppt", "pptx", "odp",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreviewConfig {
+    pub enabled: bool,
+    /// poppler's pdftoppm
+    #[serde(default = "default_pdftoppm")]
+    pub pdftoppm: String,
+    /// LibreOffice, for office documents; empty to preview PDFs only
+    #[serde(default = "default_office")]
+    pub office: String,
+    /// Longest side of the preview in pixels
+    #[serde(default = "default_size")]
+    pub size: u32,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_pdftoppm() -> String {
+    "pdftoppm".to_string()
+}
+
+fn default_office() -> String {
+    "soffice".to_string()
+}
+
+fn default_size() -> u32 {
+    480
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        PreviewConfig {
+            enabled: true,
+            pdftoppm: default_pdftoppm(),
+            office: default_office(),
+            size: default_size(),
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DocKind {
+    Pdf,
+    Office,
+}
+
+pub fn load(db: &Database) -> PreviewConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn kind_of(file_name: &str) -> Option<DocKind> {
+    let ext = Path::new(file_name)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    if ext == "pdf" {
+        Some(DocKind::Pdf)
+    } else if OFFICE_EXTENSIONS.contains(&ext.as_str()) {
+        Some(DocKind::Office)
+    } else {
+        None
+    }
+}
+
+/// Run a converter to completion; its output goes to files, not stdout
+fn run_tool(program: &str, args: &[String], timeout_secs: u64) -> Result<(), String> {
+    Tool {
+        name: program,
+        program,
+        args,
+        timeout_secs,
+    }
+    .run()
+}
+
+/// file:// URL of a directory, for LibreOffice's -env:UserInstallation
+fn file_url(dir: &Path) -> String {
+    let path = dir.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+fn pdftoppm_args(config: &PreviewConfig, pdf: &Path, out_prefix: &Path) -> Vec<String> {
+    vec![
+        "-png".into(),
+        "-f".into(),
+        "1".into(),
+        "-l".into(),
+        "1".into(),
+        "-singlefile".into(),
+        "-scale-to".into(),
+        config.size.clamp(64, 2048).to_string(),
+        pdf.to_string_lossy().into_owned(),
+        out_prefix.to_string_lossy().into_owned(),
+    ]
+}
+
+fn render(
+    config: &PreviewConfig,
+    kind: DocKind,
+    source: &Path,
+    work: &Path,
+) -> Result<PathBuf, String> {
+    let pdf = match kind {
+        DocKind::Pdf => source.to_path_buf(),
+        DocKind::Office => {
+            if config.office.trim().is_empty() {
+                return Err("No office converter is configured".to_string());
+            }
+            // A profile of its own, so it doesn't collide with a LibreOffice the user has open
+            let args = vec![
+                format!("-env:UserInstallation={}", file_url(&work.join("profile"))),
+                "--headless".into(),
+                "--convert-to".into(),
+                "pdf".into(),
+                "--outdir".into(),
+                work.to_string_lossy().into_owned(),
+                source.to_string_lossy().into_owned(),
+            ];
+            run_tool(&config.office, &args, config.timeout_secs)?;
+            let stem = source
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            work.join(format!("{}.pdf", stem))
+        }
+    };
+    let prefix = work.join("page");
+    run_tool(
+        &config.pdftoppm,
+        &pdftoppm_args(config, &pdf, &prefix),
+        config.timeout_secs,
+    )?;
+    let png = work.join("page.png");
+    if !png.is_file() {
+        return Err("The renderer produced no image".to_string());
+    }
+    Ok(png)
+}
+
+/// Render the preview of `source` (named `file_name`) into the thumbnail cache under `file_id`,
+/// unless it's there already. Returns the cached PNG.
+fn generate(
+    config: &PreviewConfig,
+    file_server: &FileServer,
+    file_id: &str,
+    source: &Path,
+    file_name: &str,
+) -> Result<PathBuf, String> {
+    let kind = kind_of(file_name).ok_or("No preview for this kind of file")?;
+    let target = file_server.thumbnail_path(file_id);
+    if target.is_file() {
+        return Ok(target);
+    }
+    let thumbnails = target.parent().ok_or("Bad thumbnail path")?;
+    let work = thumbnails.join(format!(".work-{}", file_id));
+    std::fs::create_dir_all(&work).map_err(|e| e.to_string())?;
+    let rendered = render(config, kind, source, &work)
+        .and_then(|png| std::fs::rename(&png, &target).map_err(|e| e.to_string()));
+    let _ = std::fs::remove_dir_all(&work);
+    rendered?;
+    info!("Rendered a preview of {}", file_name);
+    Ok(target)
+}
+
+/// Render a preview of a document we're sharing in the background, so it's ready when peers
+/// ask for it
+pub fn queue(state: &AppState, file_id: &str, path: &Path, file_name: &str) {
+    let config = load(&state.db);
+    if !config.enabled || kind_of(file_name).is_none() || !file_server::is_plain_id(file_id) {
+        return;
+    }
+    let (file_server, file_id, path, file_name) = (
+        state.file_server.clone(),
+        file_id.to_string(),
+        path.to_path_buf(),
+        file_name.to_string(),
+    );
+    std::thread::spawn(move || {
+        if let Err(e) = generate(&config, &file_server, &file_id, &path, &file_name) {
+            warn!("No preview for {}: {}", file_name, e);
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+/// Local URL of a document's first-page preview, rendering it first if needed. `file_id` is
+/// the id in the file message (a file we shared, or one we downloaded). None when previews are
+/// off or the file isn't a document.
+#[tauri::command]
+pub async fn get_document_preview<R: Runtime>(
+    app: AppHandle<R>,
+    file_id: String,
+) -> Result<Option<String>, String> {
+    if !file_server::is_plain_id(&file_id) {
+        return Err("Invalid file id".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        if state.file_server.thumbnail_path(&file_id).is_file() {
+            return Ok(Some(state.file_server.local_thumbnail_url(&file_id)));
+        }
+        let config = load(&state.db);
+        if !config.enabled {
+            return Ok(None);
+        }
+        // Served by us, or received (chunked transfers keep the file id as the download id)
+        let download = state.db.get_download(&file_id).ok().flatten();
+        let (path, file_name) = match (state.file_server.file_path(&file_id), download) {
+            (_, Some(d)) if d.path.is_some() => {
+                (PathBuf::from(d.path.unwrap_or_default()), d.file_name)
+            }
+            (Some(path), _) => {
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                (path, name)
+            }
+            _ => return Err("File not found".to_string()),
+        };
+        if kind_of(&file_name).is_none() {
+            return Ok(None);
+        }
+        generate(&config, &state.file_server, &file_id, &path, &file_name)?;
+        Ok(Some(state.file_server.local_thumbnail_url(&file_id)))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_preview_config(state: State<AppState>) -> PreviewConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_preview_config(state: State<AppState>, config: PreviewConfig) -> Result<(), String> {
+    if config.enabled && config.pdftoppm.trim().is_empty() {
+        return Err("No PDF renderer given".to_string());
+    }
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("Document previews enabled: {}", config.enabled);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_kinds_and_args() {
+        assert_eq!(kind_of("Report.PDF"), Some(DocKind::Pdf));
+        assert_eq!(kind_of("minutes.docx"), Some(DocKind::Office));
+        assert_eq!(kind_of("photo.jpg"), None);
+        assert_eq!(kind_of("README"), None);
+        assert!(!file_server::is_plain_id("../etc"));
+        assert!(file_server::is_plain_id("f3a9c2"));
+
+        let args = pdftoppm_args(
+            &PreviewConfig::default(),
+            Path::new("a.pdf"),
+            Path::new("out/page"),
+        );
+        assert_eq!(args[..6], ["-png", "-f", "1", "-l", "1", "-singlefile"]);
+        assert_eq!(args.last().unwrap(), "out/page");
+        assert_eq!(file_url(Path::new("/tmp/p")), "file:///tmp/p");
+    }
+}
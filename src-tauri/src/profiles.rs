@@ -0,0 +1,263 @@
+// src-tauri/src/profiles.rs
+// Local profiles: separate databases, downloads and identities on one machine
+
+use crate::commands::AppState;
+use crate::hotkeys;
+use crate::remote_control;
+use crate::window_manager;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+/// The profile that owns the original, un-suffixed "Pingo" folders
+pub const DEFAULT_PROFILE: &str = "default";
+const REGISTRY_FILE: &str = "profiles.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub active: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Registry {
+    profiles: Vec<Profile>,
+    last_active: Option<String>,
+}
+
+// Resolved lazily on first use: PINGO_INSTANCE (if set) wins over the last active profile
+static ACTIVE_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Folder name used for a profile's data and downloads. Matches the old PINGO_INSTANCE
+/// layout ("Pingo" / "Pingo_<instance>") so existing instances show up as profiles.
+pub fn app_dir_name(profile_id: &str) -> String {
+    if profile_id.is_empty() || profile_id == DEFAULT_PROFILE {
+        "Pingo".to_string()
+    } else {
+        format!("Pingo_{}", profile_id)
+    }
+}
+
+pub fn app_dir(profile_id: &str) -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(app_dir_name(profile_id))
+}
+
+pub fn downloads_dir(profile_id: &str) -> PathBuf {
+    dirs::download_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(app_dir_name(profile_id))
+}
+
+/// The registry lives in the default profile's folder, shared by all profiles
+fn registry_path() -> PathBuf {
+    app_dir(DEFAULT_PROFILE).join(REGISTRY_FILE)
+}
+
+fn load_registry() -> Registry {
+    let mut registry: Registry = std::fs::read_to_string(registry_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    if !registry.profiles.iter().any(|p| p.id == DEFAULT_PROFILE) {
+        registry.profiles.insert(
+            0,
+            Profile {
+                id: DEFAULT_PROFILE.to_string(),
+                name: "Default".to_string(),
+                created_at: crate::db::now(),
+            },
+        );
+    }
+    registry
+}
+
+fn save_registry(registry: &Registry) -> Result<(), String> {
+    let path = registry_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(registry).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to save profiles: {}", e))
+}
+
+pub fn active_profile() -> String {
+    if let Some(id) = ACTIVE_PROFILE.read().unwrap().as_ref() {
+        return id.clone();
+    }
+    let mut active = ACTIVE_PROFILE.write().unwrap();
+    let id = active
+        .get_or_insert_with(|| {
+            let instance = std::env::var("PINGO_INSTANCE").unwrap_or_default();
+            if !instance.is_empty() {
+                let mut registry = load_registry();
+                if !registry.profiles.iter().any(|p| p.id == instance) {
+                    registry.profiles.push(Profile {
+                        id: instance.clone(),
+                        name: instance.clone(),
+                        created_at: crate::db::now(),
+                    });
+                    let _ = save_registry(&registry);
+                }
+                instance
+            } else {
+                load_registry()
+                    .last_active
+                    .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+            }
+        })
+        .clone();
+    id
+}
+
+fn set_active_profile(profile_id: &str) -> Result<(), String> {
+    let mut registry = load_registry();
+    if !registry.profiles.iter().any(|p| p.id == profile_id) {
+        return Err(format!("Unknown profile: {}", profile_id));
+    }
+    registry.last_active = Some(profile_id.to_string());
+    save_registry(&registry)?;
+    *ACTIVE_PROFILE.write().unwrap() = Some(profile_id.to_string());
+    Ok(())
+}
+
+/// Turn a display name into a folder-safe id
+fn slugify(name: &str) -> String {
+    let slug: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    slug.trim_matches('_').to_string()
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn list_profiles() -> Vec<ProfileInfo> {
+    let active = active_profile();
+    load_registry()
+        .profiles
+        .into_iter()
+        .map(|p| ProfileInfo {
+            active: p.id == active,
+            id: p.id,
+            name: p.name,
+            created_at: p.created_at,
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_active_profile() -> String {
+    active_profile()
+}
+
+/// Register a new, empty profile. Its database and identity are created on first switch.
+#[tauri::command]
+pub fn create_profile(name: String) -> Result<Profile, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    let base = slugify(&name);
+    let base = if base.is_empty() {
+        "profile".to_string()
+    } else {
+        base
+    };
+
+    let mut registry = load_registry();
+    let mut id = base.clone();
+    let mut n = 2;
+    while registry.profiles.iter().any(|p| p.id == id) {
+        id = format!("{}_{}", base, n);
+        n += 1;
+    }
+
+    let profile = Profile {
+        id,
+        name,
+        created_at: crate::db::now(),
+    };
+    registry.profiles.push(profile.clone());
+    save_registry(&registry)?;
+    Ok(profile)
+}
+
+/// Switch to another profile without restarting: stops networking, reopens the database,
+/// reloads the device identity and downloads folder, then emits `profile-switched` so the
+/// frontend can re-run init_app and restart discovery/signaling for the new identity.
+#[tauri::command]
+pub fn switch_profile<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    profile_id: String,
+) -> Result<ProfileInfo, String> {
+    if profile_id == active_profile() {
+        return Err("Profile is already active".to_string());
+    }
+    let previous = active_profile();
+    set_active_profile(&profile_id)?;
+
+    // Remote control and windows opened for the previous profile don't belong to the new one
+    remote_control::end_all(&app);
+    window_manager::clear_last_incoming_peer();
+    window_manager::close_quick_reply(&app);
+    for peer_id in state.chat_windows.open_peers() {
+        if let Some(w) = app.get_webview_window(&window_manager::chat_window_label(&peer_id)) {
+            let _ = w.close();
+        }
+    }
+
+    if let Err(e) = state.reload_profile() {
+        // Leave the app on a working profile rather than half-switched
+        let _ = set_active_profile(&previous);
+        let _ = state.reload_profile();
+        return Err(format!("Failed to switch profile: {}", e));
+    }
+    hotkeys::register_from_settings(&app, &state.db);
+
+    let info = list_profiles()
+        .into_iter()
+        .find(|p| p.id == profile_id)
+        .ok_or_else(|| format!("Unknown profile: {}", profile_id))?;
+    let _ = app.emit("profile-switched", &info);
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_names_match_instance_layout() {
+        assert_eq!(app_dir_name(DEFAULT_PROFILE), "Pingo");
+        assert_eq!(app_dir_name(""), "Pingo");
+        assert_eq!(app_dir_name("work"), "Pingo_work");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("  Work Laptop "), "work_laptop");
+        assert_eq!(slugify("Ünïcode!"), "n_code");
+        assert_eq!(slugify("///"), "");
+    }
+}
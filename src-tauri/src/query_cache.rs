@@ -0,0 +1,216 @@
+// src-tauri/src/query_cache.rs
+// Small in-memory cache for the conversation queries the UI polls constantly (recent pages,
+// last message per chat, unread counts). Owned by Database: reads fill it while holding the
+// connection lock and every write to `messages` invalidates it, per conversation where the
+// write knows which one and wholesale where it doesn't.
+
+use crate::db::{LastMessageInfo, Message};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const PAGE_CAPACITY: usize = 64;
+
+/// Fixed-size map that evicts the least recently used entry. Linear eviction is fine at the
+/// sizes used here.
+struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(value, used)| {
+            *used = tick;
+            value.clone()
+        })
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        self.tick += 1;
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            if let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(k, _)| k.clone())
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    fn retain(&mut self, keep: impl Fn(&K) -> bool) {
+        self.entries.retain(|k, _| keep(k));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// The two participants in a stable order, so (a, b) and (b, a) share entries
+fn conversation(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PageKey {
+    conversation: (String, String),
+    before: Option<String>,
+    limit: i32,
+}
+
+impl PageKey {
+    pub fn new(user1: &str, user2: &str, before: Option<&str>, limit: i32) -> Self {
+        PageKey {
+            conversation: conversation(user1, user2),
+            before: before.map(str::to_string),
+            limit,
+        }
+    }
+}
+
+struct Inner {
+    pages: Lru<PageKey, Vec<Message>>,
+    /// Keyed by local user id; there is only ever one
+    last_messages: Option<(String, Vec<LastMessageInfo>)>,
+    unread_by_peer: Option<(String, Vec<(String, i32)>)>,
+}
+
+pub struct QueryCache {
+    inner: Mutex<Inner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        QueryCache {
+            inner: Mutex::new(Inner {
+                pages: Lru::new(PAGE_CAPACITY),
+                last_messages: None,
+                unread_by_peer: None,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+}
+
+impl QueryCache {
+    fn count<T>(&self, found: Option<T>) -> Option<T> {
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub fn page(&self, key: &PageKey) -> Option<Vec<Message>> {
+        let found = self.inner.lock().unwrap().pages.get(key);
+        self.count(found)
+    }
+
+    pub fn put_page(&self, key: PageKey, messages: Vec<Message>) {
+        self.inner.lock().unwrap().pages.put(key, messages);
+    }
+
+    pub fn last_messages(&self, local_id: &str) -> Option<Vec<LastMessageInfo>> {
+        let found = match &self.inner.lock().unwrap().last_messages {
+            Some((id, rows)) if id == local_id => Some(rows.clone()),
+            _ => None,
+        };
+        self.count(found)
+    }
+
+    pub fn put_last_messages(&self, local_id: &str, rows: Vec<LastMessageInfo>) {
+        self.inner.lock().unwrap().last_messages = Some((local_id.to_string(), rows));
+    }
+
+    pub fn unread_by_peer(&self, local_id: &str) -> Option<Vec<(String, i32)>> {
+        let found = match &self.inner.lock().unwrap().unread_by_peer {
+            Some((id, rows)) if id == local_id => Some(rows.clone()),
+            _ => None,
+        };
+        self.count(found)
+    }
+
+    pub fn put_unread_by_peer(&self, local_id: &str, rows: Vec<(String, i32)>) {
+        self.inner.lock().unwrap().unread_by_peer = Some((local_id.to_string(), rows));
+    }
+
+    /// A write touched one conversation: drop its pages and the cross-chat summaries
+    pub fn invalidate_conversation(&self, user1: &str, user2: &str) {
+        let key = conversation(user1, user2);
+        let mut inner = self.inner.lock().unwrap();
+        inner.pages.retain(|k| k.conversation != key);
+        inner.last_messages = None;
+        inner.unread_by_peer = None;
+    }
+
+    /// A write whose conversation isn't known (by message id, bulk, reopen)
+    pub fn invalidate_all(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.pages.clear();
+        inner.last_messages = None;
+        inner.unread_by_peer = None;
+    }
+
+    /// (hits, misses) since startup, for diagnostics
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.put("a", 1);
+        lru.put("b", 2);
+        assert_eq!(lru.get(&"a"), Some(1));
+        lru.put("c", 3);
+        assert_eq!(lru.get(&"b"), None);
+        assert_eq!(lru.get(&"a"), Some(1));
+        assert_eq!(lru.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn test_invalidate_conversation_keeps_other_chats() {
+        let cache = QueryCache::default();
+        cache.put_page(PageKey::new("me", "ana", None, 50), vec![]);
+        cache.put_page(PageKey::new("me", "bob", None, 50), vec![]);
+        cache.put_unread_by_peer("me", vec![("ana".to_string(), 2)]);
+
+        cache.invalidate_conversation("ana", "me");
+        assert!(cache.page(&PageKey::new("me", "ana", None, 50)).is_none());
+        assert!(cache.page(&PageKey::new("bob", "me", None, 50)).is_some());
+        assert!(cache.unread_by_peer("me").is_none());
+    }
+}
@@ -0,0 +1,672 @@
+// src-tauri/src/remote_control.rs
+// Remote assistance during a screen share: the viewer asks to take control, the sharer
+// approves or denies in their UI, and only then does the sharer's backend inject the
+// viewer's mouse and keyboard input. Consent is per session and checked here, not in the
+// webview. Signaling is plain UDP, so the sender and session id of an input message prove
+// nothing: an approval hands the controller a random input key encrypted to its identity (the
+// crypto session), and each input carries an HMAC under that key and an increasing sequence
+// number. Input that doesn't verify, or replays an old number, is dropped.
+// Requests are only taken while remote control is turned on (allow_remote_control, off by
+// default) and we're sharing our screen; sessions end when the share stops, when we leave a
+// meeting and when the profile is switched.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::db::{generate_id, Database};
+use crate::signaling::SignalingMessage;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use enigo::{Axis, Button, Coordinate, Direction, Enigo, Key, Keyboard, Mouse, Settings};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const MAX_TEXT_LEN: usize = 1000;
+pub const SETTING_KEY: &str = "allow_remote_control";
+/// Requests waiting for the sharer's answer; more are dropped until some are answered
+const MAX_PENDING: usize = 4;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputAction {
+    Press,
+    Release,
+    Click,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RemoteInputEvent {
+    /// Pointer position as a fraction (0..1) of the shared screen
+    MouseMove {
+        x: f64,
+        y: f64,
+    },
+    /// `button` is left, middle or right
+    MouseButton {
+        button: String,
+        action: InputAction,
+    },
+    Scroll {
+        dx: i32,
+        dy: i32,
+    },
+    /// `key` is a browser KeyboardEvent.key value ("a", "Enter", "ArrowUp", "Control", ...)
+    Key {
+        key: String,
+        action: InputAction,
+    },
+    Text {
+        text: String,
+    },
+}
+
+/// A session as seen by one side: the other party and whether the sharer has approved it
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteSession {
+    pub session_id: String,
+    pub peer_id: String,
+    /// True when we're the one being controlled
+    pub sharing: bool,
+    pub approved: bool,
+    /// Input key of an approved session
+    #[serde(skip)]
+    key: Option<Vec<u8>>,
+    /// Last input sequence number sent (controller) or accepted (sharer)
+    #[serde(skip)]
+    seq: u64,
+}
+
+impl RemoteSession {
+    fn new(session_id: &str, peer_id: &str, sharing: bool) -> Self {
+        RemoteSession {
+            session_id: session_id.to_string(),
+            peer_id: peer_id.to_string(),
+            sharing,
+            approved: false,
+            key: None,
+            seq: 0,
+        }
+    }
+}
+
+static SESSIONS: OnceLock<Mutex<HashMap<String, RemoteSession>>> = OnceLock::new();
+static INJECTOR: OnceLock<Sender<RemoteInputEvent>> = OnceLock::new();
+/// Whether the UI is sharing our screen right now (set_screen_sharing)
+static SHARING: AtomicBool = AtomicBool::new(false);
+
+fn sessions() -> &'static Mutex<HashMap<String, RemoteSession>> {
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Whether the user turned remote control on
+pub fn is_enabled(db: &Database) -> bool {
+    db.get_setting(SETTING_KEY).ok().flatten().as_deref() == Some("true")
+}
+
+/// Whether a request from `from` may be queued for the sharer next to the `pending` ones
+fn accepts_request(
+    pending: &[&RemoteSession],
+    enabled: bool,
+    sharing: bool,
+    from: &str,
+) -> Result<(), &'static str> {
+    if !enabled {
+        return Err("remote control is turned off");
+    }
+    if !sharing {
+        return Err("we aren't sharing our screen");
+    }
+    if pending.len() >= MAX_PENDING || pending.iter().any(|s| s.peer_id == from) {
+        return Err("too many requests are waiting");
+    }
+    Ok(())
+}
+
+/// End the sessions `which` picks, telling the other side of each
+fn end_sessions<R: Runtime>(app: &AppHandle<R>, which: impl Fn(&RemoteSession) -> bool) {
+    let ended: Vec<RemoteSession> = {
+        let mut all = sessions().lock().unwrap();
+        let ids: Vec<String> = all
+            .values()
+            .filter(|s| which(s))
+            .map(|s| s.session_id.clone())
+            .collect();
+        ids.iter().filter_map(|id| all.remove(id)).collect()
+    };
+    let state = app.state::<AppState>();
+    for session in ended {
+        let msg = SignalingMessage::RemoteControlEnd {
+            from: state.device_id(),
+            to: session.peer_id.clone(),
+            session_id: session.session_id.clone(),
+        };
+        if let Err(e) = send_to_peer(&state, &session.peer_id, &msg) {
+            warn!(
+                "Failed to end remote control with {}: {}",
+                session.peer_id, e
+            );
+        }
+        info!("Remote control session {} ended", session.session_id);
+        let _ = app.emit("remote-control-ended", Some(&session));
+    }
+}
+
+/// End every session in which we're being controlled (the share stopped, or control was
+/// turned off)
+pub fn end_sharing_sessions<R: Runtime>(app: &AppHandle<R>) {
+    end_sessions(app, |s| s.sharing);
+}
+
+/// End every session on either side (leaving a meeting, switching profiles)
+pub fn end_all<R: Runtime>(app: &AppHandle<R>) {
+    end_sessions(app, |_| true);
+}
+
+fn direction(action: InputAction) -> Direction {
+    match action {
+        InputAction::Press => Direction::Press,
+        InputAction::Release => Direction::Release,
+        InputAction::Click => Direction::Click,
+    }
+}
+
+fn parse_button(button: &str) -> Result<Button, String> {
+    match button {
+        "left" => Ok(Button::Left),
+        "middle" => Ok(Button::Middle),
+        "right" => Ok(Button::Right),
+        _ => Err(format!("Unknown mouse button: {}", button)),
+    }
+}
+
+fn parse_key(key: &str) -> Result<Key, String> {
+    let named = match key {
+        "Enter" => Key::Return,
+        "Backspace" => Key::Backspace,
+        "Tab" => Key::Tab,
+        "Escape" => Key::Escape,
+        "Delete" => Key::Delete,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "ArrowUp" => Key::UpArrow,
+        "ArrowDown" => Key::DownArrow,
+        "ArrowLeft" => Key::LeftArrow,
+        "ArrowRight" => Key::RightArrow,
+        "Shift" => Key::Shift,
+        "Control" => Key::Control,
+        "Alt" => Key::Alt,
+        "Meta" => Key::Meta,
+        " " => Key::Space,
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        _ => {
+            let mut chars = key.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Key::Unicode(c)),
+                _ => Err(format!("Unknown key: {}", key)),
+            };
+        }
+    };
+    Ok(named)
+}
+
+fn validate(event: &RemoteInputEvent) -> Result<(), String> {
+    match event {
+        RemoteInputEvent::MouseMove { x, y }
+            if !(0.0..=1.0).contains(x) || !(0.0..=1.0).contains(y) =>
+        {
+            Err("Pointer position must be between 0 and 1".to_string())
+        }
+        RemoteInputEvent::MouseButton { button, .. } => parse_button(button).map(|_| ()),
+        RemoteInputEvent::Key { key, .. } => parse_key(key).map(|_| ()),
+        RemoteInputEvent::Text { text } if text.chars().count() > MAX_TEXT_LEN => Err(format!(
+            "Text input is limited to {} characters",
+            MAX_TEXT_LEN
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn inject(enigo: &mut Enigo, event: RemoteInputEvent) -> Result<(), String> {
+    match event {
+        RemoteInputEvent::MouseMove { x, y } => {
+            let (w, h) = enigo.main_display().map_err(|e| e.to_string())?;
+            let px = (x * f64::from(w - 1)).round() as i32;
+            let py = (y * f64::from(h - 1)).round() as i32;
+            enigo.move_mouse(px, py, Coordinate::Abs)
+        }
+        RemoteInputEvent::MouseButton { button, action } => {
+            enigo.button(parse_button(&button)?, direction(action))
+        }
+        RemoteInputEvent::Scroll { dx, dy } => {
+            if dx != 0 {
+                enigo
+                    .scroll(dx, Axis::Horizontal)
+                    .map_err(|e| e.to_string())?;
+            }
+            if dy != 0 {
+                enigo
+                    .scroll(dy, Axis::Vertical)
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+        RemoteInputEvent::Key { key, action } => enigo.key(parse_key(&key)?, direction(action)),
+        RemoteInputEvent::Text { text } => enigo.text(&text),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Input is injected on one thread that owns the Enigo connection
+fn injector() -> &'static Sender<RemoteInputEvent> {
+    INJECTOR.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<RemoteInputEvent>();
+        thread::spawn(move || {
+            let mut enigo = match Enigo::new(&Settings::default()) {
+                Ok(enigo) => enigo,
+                Err(e) => {
+                    warn!("Remote control input unavailable: {}", e);
+                    return;
+                }
+            };
+            for event in rx {
+                if let Err(e) = inject(&mut enigo, event) {
+                    warn!("Injecting remote input failed: {}", e);
+                }
+            }
+        });
+        tx
+    })
+}
+
+fn input_mac(key: &[u8], session_id: &str, seq: u64, event: &RemoteInputEvent) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(session_id.as_bytes());
+    mac.update(&seq.to_be_bytes());
+    mac.update(&serde_json::to_vec(event).unwrap_or_default());
+    mac
+}
+
+/// Whether input `seq` with `mac` from `from` may drive our input in `session_id`; accepting it
+/// uses up the sequence number
+fn is_authorized(
+    session_id: &str,
+    from: &str,
+    seq: u64,
+    mac: &str,
+    event: &RemoteInputEvent,
+) -> bool {
+    let mut all = sessions().lock().unwrap();
+    let Some(s) = all
+        .get_mut(session_id)
+        .filter(|s| s.sharing && s.approved && s.peer_id == from && seq > s.seq)
+    else {
+        return false;
+    };
+    let (Some(key), Ok(tag)) = (s.key.as_deref(), BASE64.decode(mac)) else {
+        return false;
+    };
+    if input_mac(key, session_id, seq, event)
+        .verify_slice(&tag)
+        .is_err()
+    {
+        return false;
+    }
+    s.seq = seq;
+    true
+}
+
+/// Handle RemoteControl*/RemoteInput messages from the signaling forwarder
+pub fn handle_message<R: Runtime>(app: &AppHandle<R>, msg: &SignalingMessage) {
+    match msg {
+        SignalingMessage::RemoteControlRequest {
+            from, session_id, ..
+        } => {
+            let enabled = is_enabled(&app.state::<AppState>().db);
+            let session = RemoteSession::new(session_id, from, true);
+            {
+                let mut all = sessions().lock().unwrap();
+                if all.contains_key(session_id) {
+                    return;
+                }
+                let pending: Vec<&RemoteSession> =
+                    all.values().filter(|s| s.sharing && !s.approved).collect();
+                let sharing = SHARING.load(Ordering::SeqCst);
+                if let Err(reason) = accepts_request(&pending, enabled, sharing, from) {
+                    info!("Ignoring remote control request from {}: {}", from, reason);
+                    return;
+                }
+                all.insert(session_id.clone(), session.clone());
+            }
+            info!("{} asked to control this device", from);
+            let _ = app.emit("remote-control-request", &session);
+        }
+        SignalingMessage::RemoteControlResponse {
+            from,
+            session_id,
+            allowed,
+            key,
+            ..
+        } => {
+            // The key only opens with our side of the crypto session with the sharer
+            let key = key.as_ref().and_then(|envelope| {
+                let state = app.state::<AppState>();
+                let key = state
+                    .crypto
+                    .decrypt_message(from, envelope, Some(session_id))
+                    .ok()?;
+                BASE64.decode(key).ok()
+            });
+            let allowed = &(*allowed && key.is_some());
+            let mut all = sessions().lock().unwrap();
+            let Some(s) = all
+                .get_mut(session_id)
+                .filter(|s| !s.sharing && &s.peer_id == from)
+            else {
+                warn!(
+                    "Ignoring remote control response for unknown session {}",
+                    session_id
+                );
+                return;
+            };
+            let session = if *allowed {
+                s.approved = true;
+                s.key = key;
+                s.clone()
+            } else {
+                all.remove(session_id).unwrap()
+            };
+            drop(all);
+            let _ = app.emit(
+                "remote-control-response",
+                serde_json::json!({ "session": session, "allowed": allowed }),
+            );
+        }
+        SignalingMessage::RemoteInput {
+            from,
+            session_id,
+            event,
+            seq,
+            mac,
+            ..
+        } => {
+            if !is_authorized(session_id, from, *seq, mac, event) {
+                warn!("Dropping remote input from {} without consent", from);
+                return;
+            }
+            if let Err(e) = validate(event) {
+                warn!("Remote input from {}: {}", from, e);
+                return;
+            }
+            let _ = injector().send(event.clone());
+        }
+        SignalingMessage::RemoteControlEnd {
+            from, session_id, ..
+        } => {
+            let mut all = sessions().lock().unwrap();
+            if all.get(session_id).is_some_and(|s| &s.peer_id == from) {
+                let session = all.remove(session_id);
+                drop(all);
+                info!("Remote control session {} ended by {}", session_id, from);
+                let _ = app.emit("remote-control-ended", &session);
+            }
+        }
+        _ => {}
+    }
+}
+
+// ============ COMMANDS ============
+
+/// Ask a peer who is sharing their screen to let us control it. Returns the session id.
+#[tauri::command]
+pub fn request_remote_control(state: State<AppState>, peer_id: String) -> Result<String, String> {
+    let session_id = generate_id();
+    let msg = SignalingMessage::RemoteControlRequest {
+        from: state.device_id(),
+        to: peer_id.clone(),
+        session_id: session_id.clone(),
+    };
+    send_to_peer(&state, &peer_id, &msg)?;
+    sessions().lock().unwrap().insert(
+        session_id.clone(),
+        RemoteSession::new(&session_id, &peer_id, false),
+    );
+    Ok(session_id)
+}
+
+/// Sharer's answer to a control request. Only one controller at a time is allowed, and only
+/// a peer we have an encrypted session with can be handed control.
+#[tauri::command]
+pub fn respond_remote_control(
+    state: State<AppState>,
+    session_id: String,
+    allow: bool,
+) -> Result<(), String> {
+    if allow && !(is_enabled(&state.db) && SHARING.load(Ordering::SeqCst)) {
+        return Err("Remote control needs to be turned on and the screen shared".to_string());
+    }
+    let (peer_id, key) = {
+        let mut all = sessions().lock().unwrap();
+        if allow && all.values().any(|s| s.sharing && s.approved) {
+            return Err("Another remote control session is active".to_string());
+        }
+        let s = all
+            .get_mut(&session_id)
+            .filter(|s| s.sharing && !s.approved)
+            .ok_or("No pending remote control request")?;
+        let peer_id = s.peer_id.clone();
+        let key = if allow {
+            let mut key = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            let envelope = state
+                .crypto
+                .encrypt_message(&peer_id, Some(&session_id), &BASE64.encode(&key))
+                .map_err(|_| "No encrypted session with this peer; control can't be handed over")?;
+            s.approved = true;
+            s.key = Some(key);
+            Some(envelope)
+        } else {
+            all.remove(&session_id);
+            None
+        };
+        (peer_id, key)
+    };
+    let msg = SignalingMessage::RemoteControlResponse {
+        from: state.device_id(),
+        to: peer_id.clone(),
+        session_id,
+        allowed: allow,
+        key,
+    };
+    send_to_peer(&state, &peer_id, &msg)?;
+    info!(
+        "Remote control by {} {}",
+        peer_id,
+        if allow { "allowed" } else { "denied" }
+    );
+    Ok(())
+}
+
+/// Send mouse/keyboard input in a session the sharer has approved
+#[tauri::command]
+pub fn send_remote_input(
+    state: State<AppState>,
+    session_id: String,
+    event: RemoteInputEvent,
+) -> Result<(), String> {
+    validate(&event)?;
+    let (peer_id, seq, mac) = {
+        let mut all = sessions().lock().unwrap();
+        let s = all
+            .get_mut(&session_id)
+            .filter(|s| !s.sharing && s.approved)
+            .ok_or("Remote control hasn't been allowed for this session")?;
+        let key = s.key.as_deref().ok_or("No input key for this session")?;
+        let seq = s.seq + 1;
+        let mac = input_mac(key, &session_id, seq, &event)
+            .finalize()
+            .into_bytes();
+        s.seq = seq;
+        (s.peer_id.clone(), seq, BASE64.encode(mac))
+    };
+    let msg = SignalingMessage::RemoteInput {
+        from: state.device_id(),
+        to: peer_id.clone(),
+        session_id,
+        event,
+        seq,
+        mac,
+    };
+    send_to_peer(&state, &peer_id, &msg)
+}
+
+/// End a session from either side; the sharer can revoke control at any time
+#[tauri::command]
+pub fn stop_remote_control(state: State<AppState>, session_id: String) -> Result<(), String> {
+    let session = sessions()
+        .lock()
+        .unwrap()
+        .remove(&session_id)
+        .ok_or("Unknown remote control session")?;
+    let msg = SignalingMessage::RemoteControlEnd {
+        from: state.device_id(),
+        to: session.peer_id.clone(),
+        session_id,
+    };
+    send_to_peer(&state, &session.peer_id, &msg)
+}
+
+#[tauri::command]
+pub fn get_remote_control_sessions() -> Vec<RemoteSession> {
+    sessions().lock().unwrap().values().cloned().collect()
+}
+
+/// The UI reports when it starts or stops sharing our screen; stopping ends every session in
+/// which we're being controlled
+#[tauri::command]
+pub fn set_screen_sharing<R: Runtime>(app: AppHandle<R>, sharing: bool) {
+    SHARING.store(sharing, Ordering::SeqCst);
+    if !sharing {
+        end_sharing_sessions(&app);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_needs_approved_session_from_controller() {
+        let event: RemoteInputEvent =
+            serde_json::from_str(r#"{"kind":"key","key":"Enter","action":"press"}"#).unwrap();
+        let key = vec![7u8; 32];
+        let sign = |seq: u64, key: &[u8]| {
+            BASE64.encode(
+                input_mac(key, "rc-test", seq, &event)
+                    .finalize()
+                    .into_bytes(),
+            )
+        };
+        sessions().lock().unwrap().insert(
+            "rc-test".to_string(),
+            RemoteSession::new("rc-test", "helper", true),
+        );
+        assert!(!is_authorized(
+            "rc-test",
+            "helper",
+            1,
+            &sign(1, &key),
+            &event
+        ));
+        {
+            let mut all = sessions().lock().unwrap();
+            let s = all.get_mut("rc-test").unwrap();
+            s.approved = true;
+            s.key = Some(key.clone());
+        }
+        // Knowing the session id and the controller's device id isn't enough
+        assert!(!is_authorized("rc-test", "helper", 1, "", &event));
+        assert!(!is_authorized(
+            "rc-test",
+            "helper",
+            1,
+            &sign(1, &[1u8; 32]),
+            &event
+        ));
+        assert!(!is_authorized(
+            "rc-test",
+            "someone-else",
+            1,
+            &sign(1, &key),
+            &event
+        ));
+        assert!(!is_authorized(
+            "rc-other",
+            "helper",
+            1,
+            &sign(1, &key),
+            &event
+        ));
+        assert!(is_authorized(
+            "rc-test",
+            "helper",
+            1,
+            &sign(1, &key),
+            &event
+        ));
+        // Replays are dropped
+        assert!(!is_authorized(
+            "rc-test",
+            "helper",
+            1,
+            &sign(1, &key),
+            &event
+        ));
+        assert!(is_authorized(
+            "rc-test",
+            "helper",
+            2,
+            &sign(2, &key),
+            &event
+        ));
+
+        assert!(validate(&event).is_ok());
+        assert!(validate(&RemoteInputEvent::MouseMove { x: 1.5, y: 0.2 }).is_err());
+        assert!(parse_key("NotAKey").is_err());
+        assert_eq!(parse_key("a").unwrap(), Key::Unicode('a'));
+    }
+
+    #[test]
+    fn test_requests_need_opt_in_share_and_room() {
+        let waiting: Vec<RemoteSession> = (0..MAX_PENDING)
+            .map(|i| RemoteSession::new(&format!("s{}", i), &format!("p{}", i), true))
+            .collect();
+        let few: Vec<&RemoteSession> = waiting.iter().take(1).collect();
+        assert!(accepts_request(&few, true, true, "helper").is_ok());
+        assert!(accepts_request(&few, false, true, "helper").is_err());
+        assert!(accepts_request(&few, true, false, "helper").is_err());
+        // One waiting request per peer, and only so many overall
+        assert!(accepts_request(&few, true, true, "p0").is_err());
+        let full: Vec<&RemoteSession> = waiting.iter().collect();
+        assert!(accepts_request(&full, true, true, "helper").is_err());
+    }
+}
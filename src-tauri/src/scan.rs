@@ -0,0 +1,234 @@
+// src-tauri/src/scan.rs
+// Pre-write scan hook: when configured, every incoming file is run through an external
+// scanner (MpCmdRun, clamscan, ...) before it reaches the organized downloads folder.
+// Anything the scanner doesn't pass is moved to the profile's quarantine folder.
+
+use crate::commands::AppState;
+use crate::db::Database;
+use crate::profiles;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "file_scan";
+/// Stands for the scanned file's path in `args`; appended when no argument uses it
+const FILE_PLACEHOLDER: &str = "{file}";
+/// Prefix of the error returned for quarantined files, so download records can tell them apart
+pub const QUARANTINED: &str = "Quarantined";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanConfig {
+    pub enabled: bool,
+    /// Scanner executable, e.g. "C:\\Program Files\\Windows Defender\\MpCmdRun.exe" or "clamscan"
+    pub command: String,
+    /// e.g. ["-Scan", "-ScanType", "3", "-File", "{file}", "-DisableRemediation"]
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    120
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig {
+            enabled: false,
+            command: String::new(),
+            args: Vec::new(),
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+pub fn load(db: &Database) -> ScanConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn build_args(config: &ScanConfig, file: &Path) -> Vec<String> {
+    let file = file.to_string_lossy();
+    let mut args: Vec<String> = config
+        .args
+        .iter()
+        .map(|a| a.replace(FILE_PLACEHOLDER, &file))
+        .collect();
+    if !config.args.iter().any(|a| a.contains(FILE_PLACEHOLDER)) {
+        args.push(file.into_owned());
+    }
+    args
+}
+
+/// Run the scanner on `file`. Exit code 0 means clean; anything else, including a scanner
+/// that can't be started or doesn't finish in time, is a failure with the reason.
+pub fn run(config: &ScanConfig, file: &Path) -> Result<(), String> {
+    let mut child = Command::new(&config.command)
+        .args(build_args(config, file))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Scanner could not be started: {}", e))?;
+
+    // Read output on the side so a chatty scanner can't fill the pipe and stall
+    let mut stdout = child.stdout.take();
+    let reader = std::thread::spawn(move || {
+        let mut out = String::new();
+        if let Some(s) = stdout.as_mut() {
+            let _ = s.read_to_string(&mut out);
+        }
+        out
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(config.timeout_secs.max(1));
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(100)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err("Scanner timed out".to_string());
+            }
+            Err(e) => return Err(format!("Scanner failed: {}", e)),
+        }
+    };
+    let output = reader.join().unwrap_or_default();
+    if status.success() {
+        return Ok(());
+    }
+    let detail = output
+        .lines()
+        .map(str::trim)
+        .rfind(|l| !l.is_empty())
+        .unwrap_or("");
+    Err(match status.code() {
+        Some(code) if detail.is_empty() => format!("Scanner exited with code {}", code),
+        Some(code) => format!("Scanner exited with code {}: {}", code, detail),
+        None => "Scanner was terminated".to_string(),
+    })
+}
+
+pub fn quarantine_dir() -> PathBuf {
+    profiles::app_dir(&profiles::active_profile()).join("quarantine")
+}
+
+fn quarantine(file: &Path) -> Result<PathBuf, String> {
+    let dir = quarantine_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+    let dest = dir.join(format!(
+        "{}_{}",
+        chrono::Utc::now().format("%Y%m%d%H%M%S"),
+        name
+    ));
+    if std::fs::rename(file, &dest).is_err() {
+        // Different volume: copy, then drop the original
+        std::fs::copy(file, &dest).map_err(|e| e.to_string())?;
+        std::fs::remove_file(file).map_err(|e| e.to_string())?;
+    }
+    Ok(dest)
+}
+
+/// Scan an incoming file if a scanner is configured. A file that fails the scan is moved to
+/// quarantine, "file-quarantined" is emitted, and the error starts with QUARANTINED.
+pub fn check<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Database,
+    file: &Path,
+    file_name: &str,
+    message_id: Option<&str>,
+) -> Result<(), String> {
+    let config = load(db);
+    if !config.enabled || config.command.trim().is_empty() {
+        return Ok(());
+    }
+    let reason = match run(&config, file) {
+        Ok(()) => return Ok(()),
+        Err(reason) => reason,
+    };
+    let moved = quarantine(file);
+    warn!(
+        "{} failed the scan ({}), quarantined: {:?}",
+        file_name, reason, moved
+    );
+    let _ = app.emit(
+        "file-quarantined",
+        serde_json::json!({
+            "fileName": file_name,
+            "messageId": message_id,
+            "reason": reason,
+            "path": moved.as_ref().ok(),
+        }),
+    );
+    match moved {
+        Ok(_) => Err(format!("{}: {}", QUARANTINED, reason)),
+        Err(e) => {
+            let _ = std::fs::remove_file(file);
+            Err(format!(
+                "{}: {} (moving it failed: {})",
+                QUARANTINED, reason, e
+            ))
+        }
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_scan_config(state: State<AppState>) -> ScanConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_scan_config(state: State<AppState>, config: ScanConfig) -> Result<(), String> {
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("File scan hook enabled: {}", config.enabled);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_args() {
+        let mut config = ScanConfig {
+            enabled: true,
+            command: "clamscan".to_string(),
+            args: vec!["--no-summary".to_string()],
+            ..Default::default()
+        };
+        let file = Path::new("/tmp/x.bin");
+        assert_eq!(
+            build_args(&config, file),
+            vec!["--no-summary", "/tmp/x.bin"]
+        );
+
+        config.args = vec![
+            "-File".to_string(),
+            "{file}".to_string(),
+            "-Quick".to_string(),
+        ];
+        assert_eq!(
+            build_args(&config, file),
+            vec!["-File", "/tmp/x.bin", "-Quick"]
+        );
+    }
+}
@@ -107,6 +107,21 @@ fn capture_frame_with_retry(capturer: &mut Capturer, max_attempts: u32) -> Resul
 
 /// Internal: Capture a display and return as data URL string
 fn capture_display(display: scrap::Display) -> Result<String, String> {
+    Ok(png_bytes_to_data_url(&capture_display_png(display)?))
+}
+
+/// Capture a display by index as PNG bytes (used for meeting recordings)
+pub fn capture_png(display_index: usize) -> Result<Vec<u8>, String> {
+    let displays = scrap::Display::all().map_err(|e| format!("Failed to get displays: {}", e))?;
+    let display = displays
+        .into_iter()
+        .nth(display_index)
+        .ok_or_else(|| format!("Display {} not found", display_index))?;
+    capture_display_png(display)
+}
+
+/// Internal: Capture a display and encode it as PNG
+fn capture_display_png(display: scrap::Display) -> Result<Vec<u8>, String> {
     let mut capturer =
         Capturer::new(display).map_err(|e| format!("Failed to create capturer: {}", e))?;
 
@@ -139,8 +154,7 @@ fn capture_display(display: scrap::Display) -> Result<String, String> {
         )
         .map_err(|e| format!("Failed to encode PNG: {}", e))?;
 
-    // Convert PNG bytes to data URL
-    Ok(png_bytes_to_data_url(&png_bytes))
+    Ok(png_bytes)
 }
 
 /// Convert PNG bytes to data URL for display in browser
@@ -0,0 +1,233 @@
+// src-tauri/src/session_store.rs
+// Keeps the device's keys and established E2E sessions across restarts. Two keys live in key
+// files in the profile's app dir rather than the database: the long-term Ed25519 identity key,
+// and the X25519 session key it signs, which peers run ECDH against. The session key is rotated
+// weekly (or on demand) without touching the identity. Sessions are stored in the database
+// sealed under a key derived from the session key, so a copy of the database alone reveals
+// nothing. A stored session is dropped when the peer's known key has changed or it no longer
+// unseals.
+
+use crate::commands::AppState;
+use crate::crypto::{CryptoManager, SessionListener};
+use crate::db::Database;
+use crate::trust;
+use chrono::{DateTime, Duration, Utc};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::State;
+use tracing::{info, warn};
+
+const KEY_FILE: &str = "device.key";
+const IDENTITY_FILE: &str = "identity.key";
+const ROTATED_AT_SETTING: &str = "session_key_rotated_at";
+/// Session keys older than this are replaced at startup
+const ROTATE_AFTER_DAYS: i64 = 7;
+
+fn key_path(name: &str) -> PathBuf {
+    crate::profiles::app_dir(&crate::profiles::active_profile()).join(name)
+}
+
+/// Replace a key file atomically: the secret goes to a temp file created owner-only (never
+/// readable by others, even briefly), is synced to disk and then renamed over the old file, so
+/// a crash leaves either the old key or the new one.
+fn write_key_file(path: &PathBuf, secret: &str) -> Result<(), String> {
+    use std::io::Write;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    let write = || -> std::io::Result<()> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(secret.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    };
+    if let Err(e) = write() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!("Writing {} failed: {}", path.display(), e));
+    }
+    // Make the rename itself durable
+    #[cfg(unix)]
+    {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::File::open(dir).and_then(|d| d.sync_all());
+        }
+    }
+    Ok(())
+}
+
+/// The key file's contents; None only when there is no key file yet
+fn read_key_file(path: &PathBuf) -> Result<Option<zeroize::Zeroizing<String>>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(secret) => Ok(Some(zeroize::Zeroizing::new(secret))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Reading {} failed: {}", path.display(), e)),
+    }
+}
+
+/// Load the identity key from its key file, creating it on first run. Returns the public key.
+/// A key file that can't be read or parsed is an error rather than a reason to start over
+/// with a new identity.
+pub fn load_identity(crypto: &CryptoManager) -> Result<String, String> {
+    let path = key_path(IDENTITY_FILE);
+    if let Some(secret) = read_key_file(&path)? {
+        return crypto
+            .load_identity(&secret)
+            .map_err(|e| format!("The identity key in {} is damaged: {}", path.display(), e));
+    }
+
+    let identity_key = crypto.generate_identity();
+    let secret = crypto
+        .export_identity_secret()
+        .ok_or("No identity generated")?;
+    write_key_file(&path, &secret)?;
+    info!("Generated a new identity key");
+    Ok(identity_key)
+}
+
+fn rotated_at(db: &Database) -> Option<DateTime<Utc>> {
+    db.get_setting(ROTATED_AT_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Load the session key pair from its key file, creating it on first run and rotating it once
+/// it's old. Returns the public key. Like the identity, a key file that can't be read or parsed
+/// is an error.
+pub fn load_keypair(db: &Database, crypto: &CryptoManager) -> Result<String, String> {
+    let path = key_path(KEY_FILE);
+    if let Some(secret) = read_key_file(&path)? {
+        let public_key = crypto
+            .load_keypair(&secret)
+            .map_err(|e| format!("The device key in {} is damaged: {}", path.display(), e))?;
+        match rotated_at(db) {
+            Some(at) if Utc::now() - at >= Duration::days(ROTATE_AFTER_DAYS) => {}
+            Some(_) => return Ok(public_key),
+            // Keys from before rotation was tracked start their week now
+            None => {
+                let _ = db.set_setting(ROTATED_AT_SETTING, &Utc::now().to_rfc3339());
+                return Ok(public_key);
+            }
+        }
+    }
+    rotate_keypair(db, crypto)
+}
+
+/// Replace the session key. The identity stays; sessions made with the old key are dropped.
+pub fn rotate_keypair(db: &Database, crypto: &CryptoManager) -> Result<String, String> {
+    let public_key = crypto.generate_keypair();
+    let secret = crypto.export_secret_key().ok_or("No keypair generated")?;
+    write_key_file(&key_path(KEY_FILE), &secret)?;
+    // Sessions sealed under the old key can't be opened any more
+    crypto.clear_sessions();
+    if let Err(e) = db.clear_crypto_sessions() {
+        warn!("Clearing stored sessions failed: {}", e);
+    }
+    let _ = db.set_setting(ROTATED_AT_SETTING, &Utc::now().to_rfc3339());
+    info!("Generated a new session key");
+    Ok(public_key)
+}
+
+/// Restore the stored sessions into `crypto`, dropping the ones that are no longer valid
+pub fn restore_sessions(db: &Database, crypto: &CryptoManager) {
+    let stored = match db.get_crypto_sessions() {
+        Ok(stored) => stored,
+        Err(e) => {
+            warn!("Loading stored sessions failed: {}", e);
+            return;
+        }
+    };
+    let mut restored = 0;
+    for session in stored {
+        // The peer has announced a different key since: the session was with the old one
+        let known_key = db
+            .get_user(&session.peer_id)
+            .ok()
+            .flatten()
+            .and_then(|u| u.public_key)
+            .filter(|k| !k.is_empty());
+        if known_key.is_some_and(|k| k != session.peer_public_key) {
+            info!(
+                "Dropping stored session with {}: peer key changed",
+                session.peer_id
+            );
+            let _ = db.delete_crypto_session(&session.peer_id);
+            continue;
+        }
+        match crypto.restore_session(&session) {
+            Ok(()) => restored += 1,
+            Err(e) => {
+                warn!("Dropping stored session with {}: {}", session.peer_id, e);
+                let _ = db.delete_crypto_session(&session.peer_id);
+            }
+        }
+    }
+    info!("Restored {} stored sessions", restored);
+}
+
+/// Persists new and re-keyed sessions as CryptoManager establishes them
+pub fn listener(db: Arc<Database>) -> SessionListener {
+    Box::new(move |session| {
+        if let Err(e) = db.save_crypto_session(&session) {
+            warn!("Storing session with {} failed: {}", session.peer_id, e);
+        }
+    })
+}
+
+// ============ COMMANDS ============
+
+/// Rotate the session key now and announce it; returns the new public key
+#[tauri::command]
+pub fn rotate_session_key(state: State<AppState>) -> Result<String, String> {
+    let public_key = rotate_keypair(&state.db, &state.crypto)?;
+    state
+        .db
+        .set_setting("public_key", &public_key)
+        .map_err(|e| e.to_string())?;
+    if let Ok(Some(mut user)) = state.db.get_user(&state.device_id()) {
+        user.public_key = Some(public_key.clone());
+        let _ = state.db.create_user(&user);
+    }
+    state
+        .discovery
+        .set_session_key(public_key.clone(), state.crypto.identity_proof());
+    // Trusted peers get a session with the new key straight away
+    for peer in state.discovery.get_online_peers() {
+        trust::on_peer_seen(&state.db, &state.crypto, &peer);
+    }
+    Ok(public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_file_is_replaced_owner_only() {
+        let dir = std::env::temp_dir().join(format!("pingo-keys-{}", crate::db::generate_id()));
+        let path = dir.join(KEY_FILE);
+        write_key_file(&path, "first").unwrap();
+        write_key_file(&path, "second").unwrap();
+        assert_eq!(read_key_file(&path).unwrap().unwrap().as_str(), "second");
+        // No temp file left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+// src-tauri/src/shred.rs
+// Secure delete: a message's files are overwritten before they're unlinked, and the file server
+// and hash index forget them. This is best effort. On SSDs (wear levelling remaps writes),
+// copy-on-write filesystems (APFS, btrfs, ZFS), snapshots and backups the old blocks can outlive
+// the overwrite; full-disk encryption is what protects those. Only files the app wrote (its file
+// storage and the downloads folder) are ever shredded: the original of a file we sent is the
+// user's own document and stays where it is.
+
+use crate::commands::AppState;
+use crate::db::Message;
+use crate::file_server;
+use rand::RngCore;
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+const CHUNK: usize = 64 * 1024;
+
+/// Overwrite `path` with random bytes, flush it to disk, then unlink it
+pub fn shred_file(path: &Path) -> Result<(), String> {
+    let meta = std::fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+    if !meta.is_file() {
+        // Links (download folders) point at a file that's shredded through its own path
+        return std::fs::remove_file(path).map_err(|e| e.to_string());
+    }
+    // Another hard link (file_index dedupe) still uses these blocks; just drop this name
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if meta.nlink() > 1 {
+            return std::fs::remove_file(path).map_err(|e| e.to_string());
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+    let mut buf = vec![0u8; CHUNK];
+    let mut left = meta.len();
+    while left > 0 {
+        let n = left.min(CHUNK as u64) as usize;
+        rand::thread_rng().fill_bytes(&mut buf[..n]);
+        file.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+        left -= n as u64;
+    }
+    file.sync_all().map_err(|e| e.to_string())?;
+    file.set_len(0).map_err(|e| e.to_string())?;
+    drop(file);
+
+    // Don't leave the original name behind in the directory either
+    let renamed = path.with_file_name(uuid::Uuid::new_v4().to_string());
+    let target = match std::fs::rename(path, &renamed) {
+        Ok(()) => renamed,
+        Err(_) => path.to_path_buf(),
+    };
+    std::fs::remove_file(target).map_err(|e| e.to_string())
+}
+
+/// Folders the app writes received files to
+fn app_file_roots(state: &AppState) -> Vec<PathBuf> {
+    [
+        state.file_server.get_storage_dir(),
+        state.file_transfer.get_downloads_dir(),
+    ]
+    .into_iter()
+    .filter_map(|dir| dir.canonicalize().ok())
+    .collect()
+}
+
+/// Whether `path` lies in one of `roots`. The folder is resolved, not the file itself, so a link
+/// in the downloads folder counts as inside whatever it points at.
+fn is_under(roots: &[PathBuf], path: &Path) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    let Ok(parent) = parent.canonicalize() else {
+        return false;
+    };
+    let resolved = parent.join(name);
+    roots.iter().any(|root| resolved.starts_with(root))
+}
+
+/// Every local copy of a message's file that the app made
+fn message_file_paths(state: &AppState, message: &Message) -> HashSet<PathBuf> {
+    let mut paths = HashSet::new();
+    // For a file we sent this is the original on the user's disk
+    let received = message.sender_id != state.device_id();
+    if let Some(path) = message
+        .file_path
+        .as_deref()
+        .filter(|p| received && !p.is_empty())
+    {
+        paths.insert(PathBuf::from(path));
+    }
+    // File messages carry { fileId, url, ... }; the id names the stored copy
+    let file_id = serde_json::from_str::<serde_json::Value>(&message.content)
+        .ok()
+        .and_then(|info| {
+            info.get("fileId")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .or_else(|| {
+                    info.get("url")
+                        .and_then(|v| v.as_str())
+                        .map(|url| file_server::file_id_from_url(url).to_string())
+                })
+        });
+    if let Some(file_id) = file_id {
+        paths.extend(state.file_server.forget_file(&file_id));
+    }
+    for path in state
+        .db
+        .get_download_paths_for_message(&message.id)
+        .unwrap_or_default()
+    {
+        paths.insert(PathBuf::from(path));
+    }
+    let roots = app_file_roots(state);
+    paths.retain(|path| is_under(&roots, path));
+    paths
+}
+
+/// Shred every local copy of a message's file; returns how many were removed
+pub fn shred_message_files(state: &AppState, message: &Message) -> usize {
+    let mut shredded = 0;
+    for path in message_file_paths(state, message) {
+        if !path.exists() {
+            continue;
+        }
+        match shred_file(&path) {
+            Ok(()) => shredded += 1,
+            Err(e) => warn!("Secure delete of {} failed: {}", path.display(), e),
+        }
+        let _ = state.db.forget_file_path(&path.to_string_lossy());
+    }
+    if shredded > 0 {
+        info!(
+            "Securely deleted {} files of message {}",
+            shredded, message.id
+        );
+    }
+    shredded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shred_file_removes_it() {
+        let dir = std::env::temp_dir().join(format!("pingo-shred-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("secret.txt");
+        std::fs::write(&path, vec![b'x'; CHUNK + 10]).unwrap();
+        shred_file(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_only_app_folders_are_shredded() {
+        let base = std::env::temp_dir().join(format!("pingo-roots-{}", uuid::Uuid::new_v4()));
+        let storage = base.join("files");
+        let documents = base.join("Documents");
+        std::fs::create_dir_all(&storage).unwrap();
+        std::fs::create_dir_all(&documents).unwrap();
+        let roots = vec![storage.canonicalize().unwrap()];
+        assert!(is_under(&roots, &storage.join("abc.jpg")));
+        assert!(!is_under(&roots, &documents.join("report.pdf")));
+        assert!(!is_under(
+            &roots,
+            &storage.join("..").join("Documents").join("report.pdf")
+        ));
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
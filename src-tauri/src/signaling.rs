@@ -2,15 +2,63 @@
 // WebRTC Signaling Bridge for Pingo
 // Handles SDP/ICE exchange for peer-to-peer connections
 
-use crossbeam_channel::{unbounded, Receiver, Sender};
+use crate::crypto::EncryptedEnvelope;
+use crate::db::{ProfileFields, UserStatus};
+use crate::file_send::FileOffer;
+use crate::file_transfer::FileChunk;
+use crate::lan_policy;
+use crate::remote_control::RemoteInputEvent;
+use crate::whiteboard::WhiteboardOperation;
+use crossbeam_channel::{bounded, Receiver, SendTimeoutError, Sender, TrySendError};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{Read, Write};
 use std::net::{SocketAddr, UdpSocket};
-use std::sync::{Arc, RwLock};
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use tracing::warn;
 
 const BUFFER_SIZE: usize = 65535;
+/// Messages waiting for the app. When it's full the receive task stops reading the socket
+/// (the kernel buffer absorbs or drops the excess) instead of queueing without limit.
+const EVENT_QUEUE: usize = 1024;
+/// How long a full queue may hold up the receive task before the message is dropped
+const QUEUE_FULL_WAIT: Duration = Duration::from_secs(1);
+/// Pause after a socket error so a persistent one can't spin the task
+const ERROR_BACKOFF: Duration = Duration::from_millis(100);
+/// Advertised in discovery hellos; peers that don't send it only ever get plain JSON
+pub const SUPPORTS_COMPRESSION: bool = true;
+/// Prefix of a deflated payload. JSON never starts with a NUL byte, so older peers drop these
+/// as unparseable instead of misreading them.
+const COMPRESSED_MAGIC: &[u8] = b"\0PZ1";
+/// Version of the signaling protocol spoken here, sent as "v" next to every message's "type".
+/// Older peers ignore the field; a message without it is version 1.
+pub const PROTOCOL_VERSION: u32 = 2;
+const VERSION_FIELD: &str = "v";
+
+/// Optional features, named as they appear in a Capabilities message. Names we don't know
+/// are kept as received, so newer peers can advertise features this build lacks.
+pub mod features {
+    /// Accepts deflated payloads (see encode)
+    pub const COMPRESSION: &str = "compression";
+    /// Handles EncryptedEnvelope payloads (device linking and sync)
+    pub const ENCRYPTION: &str = "encryption";
+}
+
+const LOCAL_FEATURES: &[&str] = &[features::COMPRESSION, features::ENCRYPTION];
+
+/// Smaller messages go out as plain JSON
+const COMPRESS_THRESHOLD: usize = 1024;
+/// Refuse to inflate past this (a datagram can't carry anything legitimately bigger)
+const MAX_INFLATED: u64 = 1024 * 1024;
+/// Ids remembered by a DedupWindow at most, however many arrive within its window
+const DEDUP_CAPACITY: usize = 4096;
 
 /// Signaling message types for WebRTC connection setup
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,6 +133,9 @@ pub enum SignalingMessage {
         file_size: u64,
         file_type: String,
         transfer_id: String,
+        /// Metadata and transport when the sender's backend drives the transfer (send_file)
+        #[serde(default)]
+        offer: Option<FileOffer>,
     },
     /// File transfer response
     FileTransferResponse {
@@ -95,8 +146,34 @@ pub enum SignalingMessage {
     },
     /// Ping for keepalive
     Ping { from: String, timestamp: u64 },
-    /// Pong response
-    Pong { from: String, timestamp: u64 },
+    /// Pong response; echoes the ping's timestamp and advertises the file server port
+    Pong {
+        from: String,
+        timestamp: u64,
+        #[serde(default)]
+        file_port: Option<u16>,
+        /// The responder's wall clock (ms since the epoch) when it answered, for clock-skew
+        /// measurement; absent from older versions
+        #[serde(default)]
+        clock: Option<u64>,
+    },
+    /// Protocol version handshake, sent on first contact; `reply` marks the answer so it
+    /// isn't answered again
+    Capabilities {
+        from: String,
+        to: String,
+        version: u32,
+        features: Vec<String>,
+        #[serde(default)]
+        reply: bool,
+    },
+    /// Answer to a message this build couldn't decode (a type or shape from a newer version)
+    Unsupported {
+        from: String,
+        to: String,
+        message_type: String,
+        version: u32,
+    },
     /// Chat message relay (LAN direct delivery via UDP signaling)
     ChatMessage {
         from: String,
@@ -106,6 +183,12 @@ pub enum SignalingMessage {
         message_type: String,
         sender_name: String,
         timestamp: String,
+        /// Sender's Lamport clock, the tiebreaker for equal timestamps (0 from older versions)
+        #[serde(default)]
+        lamport: i64,
+        /// Sent by an auto-reply rule; auto-responders don't answer it
+        #[serde(default)]
+        auto_reply: bool,
     },
     /// Delivery acknowledgement from receiver to sender
     DeliveryAck {
@@ -113,6 +196,18 @@ pub enum SignalingMessage {
         to: String,
         message_id: String,
     },
+    /// The author deleted a message for everyone
+    MessageDeleted {
+        from: String,
+        to: String,
+        message_id: String,
+    },
+    /// The sender cleared our conversation up to `cleared_at` and asks us to do the same
+    ConversationCleared {
+        from: String,
+        to: String,
+        cleared_at: String,
+    },
     /// Profile update broadcast
     ProfileUpdate {
         from: String,
@@ -123,6 +218,15 @@ pub enum SignalingMessage {
         avatar_file_port: Option<u16>,
         bio: Option<String>,
         designation: Option<String>,
+        /// Content hash of the avatar; receivers skip the download if they have this version
+        #[serde(default)]
+        avatar_hash: Option<String>,
+        /// email, phone, department, pronouns, timezone (sent flat, all optional)
+        #[serde(flatten)]
+        profile: ProfileFields,
+        /// Custom status; None when the sender has none
+        #[serde(default)]
+        status: Option<UserStatus>,
     },
     /// Group created / shared with peer
     GroupCreated {
@@ -133,6 +237,12 @@ pub enum SignalingMessage {
         member_ids: Vec<String>,
         member_names: Vec<String>,
         created_at: String,
+        /// "channel" for announcement channels; absent from older peers (a plain group)
+        #[serde(default)]
+        kind: Option<String>,
+        /// Members besides the sender who are admins
+        #[serde(default)]
+        admin_ids: Vec<String>,
     },
     /// Group chat message relay (separate from DM)
     GroupChatMessage {
@@ -144,6 +254,8 @@ pub enum SignalingMessage {
         message_type: String,
         sender_name: String,
         timestamp: String,
+        #[serde(default)]
+        lamport: i64,
     },
     /// Meeting chat message (ephemeral, NOT stored in DB)
     MeetingChatMessage {
@@ -170,6 +282,14 @@ pub enum SignalingMessage {
         group_id: String,
         user_id: String,
     },
+    /// A member was made admin or member by an admin
+    GroupRoleChanged {
+        from: String,
+        to: String,
+        group_id: String,
+        user_id: String,
+        role: String,
+    },
 
     // ─── Meeting signaling (WebRTC-based meetings) ────────────
     /// Invite to a meeting
@@ -258,6 +378,224 @@ pub enum SignalingMessage {
         meeting_id: String,
         participants: Vec<String>,
     },
+    /// Ask the host to join a meeting we weren't invited to (lands in the waiting room)
+    MeetingJoinRequest {
+        from: String,
+        to: String,
+        meeting_id: String,
+        username: String,
+    },
+    /// Host's answer to a join request
+    MeetingJoinDecision {
+        from: String,
+        to: String,
+        meeting_id: String,
+        approved: bool,
+    },
+    /// Whiteboard drawing operation, sent by its author to every participant
+    WhiteboardOp {
+        from: String,
+        to: String,
+        meeting_id: String,
+        op: WhiteboardOperation,
+    },
+    /// Ask a screen sharer to let us control their mouse and keyboard
+    RemoteControlRequest {
+        from: String,
+        to: String,
+        session_id: String,
+    },
+    /// Sharer's answer to a remote control request; an approval carries the session's input
+    /// key, encrypted to the controller
+    RemoteControlResponse {
+        from: String,
+        to: String,
+        session_id: String,
+        allowed: bool,
+        #[serde(default)]
+        key: Option<EncryptedEnvelope>,
+    },
+    /// Mouse/keyboard input for an approved remote control session. `mac` is an HMAC-SHA256
+    /// under the session's input key over the session id, `seq` and the event
+    RemoteInput {
+        from: String,
+        to: String,
+        session_id: String,
+        event: RemoteInputEvent,
+        #[serde(default)]
+        seq: u64,
+        #[serde(default)]
+        mac: String,
+    },
+    /// Either side ended a remote control session
+    RemoteControlEnd {
+        from: String,
+        to: String,
+        session_id: String,
+    },
+    /// A participant started or stopped recording the meeting
+    MeetingRecording {
+        from: String,
+        to: String,
+        meeting_id: String,
+        recording: bool,
+    },
+    /// Invitation to a meeting scheduled for later (resent until acknowledged)
+    ScheduledMeetingInvite {
+        from: String,
+        to: String,
+        meeting_id: String,
+        title: String,
+        host_name: String,
+        start_at: String,
+        duration_minutes: i64,
+        participants: Vec<String>,
+    },
+    ScheduledMeetingInviteAck {
+        from: String,
+        to: String,
+        meeting_id: String,
+    },
+    // ─── Native meetings (backend WebRTC, see meeting) ────────
+    /// SDP offer for a backend-owned peer connection (also used to renegotiate)
+    NativeMeetingOffer {
+        from: String,
+        to: String,
+        meeting_id: String,
+        sdp: String,
+    },
+    /// SDP answer; the host lists the other participants so a joiner can connect to them
+    NativeMeetingAnswer {
+        from: String,
+        to: String,
+        meeting_id: String,
+        sdp: String,
+        #[serde(default)]
+        participants: Vec<String>,
+    },
+    NativeMeetingCandidate {
+        from: String,
+        to: String,
+        meeting_id: String,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_mline_index: Option<u16>,
+    },
+    NativeMeetingLeave {
+        from: String,
+        to: String,
+        meeting_id: String,
+    },
+    // ─── Multi-device sync (devices sharing one identity) ─────
+    /// Ask to be linked; the payload carries the pairing code
+    LinkRequest {
+        from: String,
+        to: String,
+        payload: EncryptedEnvelope,
+    },
+    /// Link accepted; the payload carries the identity key and profile
+    LinkAccept {
+        from: String,
+        to: String,
+        payload: EncryptedEnvelope,
+    },
+    /// Ask a linked device for everything changed since `since`
+    SyncRequest {
+        from: String,
+        to: String,
+        since: Option<String>,
+    },
+    /// One chunk of a sync response (`index` of `total`)
+    SyncBatch {
+        from: String,
+        to: String,
+        sync_id: String,
+        index: u32,
+        total: u32,
+        payload: EncryptedEnvelope,
+    },
+    /// Note edits and deletes pushed to a linked device as they happen
+    NoteSync {
+        from: String,
+        to: String,
+        payload: EncryptedEnvelope,
+    },
+    /// Changes to synced settings pushed to a linked device
+    SettingsSync {
+        from: String,
+        to: String,
+        payload: EncryptedEnvelope,
+    },
+
+    // ─── QR pairing ───────────────────────────────────────────
+    /// Introduce ourselves to a peer whose pairing QR we scanned
+    PairIntroduction {
+        from: String,
+        to: String,
+        payload: String,
+    },
+
+    // ─── Shared notes ─────────────────────────────────────────
+    /// A shared note, or an edit to one, sent to its collaborators
+    NoteShare {
+        from: String,
+        to: String,
+        payload: EncryptedEnvelope,
+    },
+
+    // ─── Backend file sends ───────────────────────────────────
+    /// A chunk of an accepted transfer sent over signaling
+    FileChunkData {
+        from: String,
+        to: String,
+        chunk: FileChunk,
+    },
+    /// The sender has sent every chunk; asks which are still missing
+    FileTransferDone {
+        from: String,
+        to: String,
+        transfer_id: String,
+    },
+    /// The receiver's answer: chunks still missing, or whether the finished file verified
+    FileTransferStatus {
+        from: String,
+        to: String,
+        transfer_id: String,
+        missing: Vec<u32>,
+        verified: Option<bool>,
+    },
+
+    // ─── PIN pairing ──────────────────────────────────────────
+    /// Ask to pair; our user reads a PIN off our screen to the peer's user
+    PinPairRequest {
+        from: String,
+        to: String,
+        nonce: String,
+        public_key: String,
+        username: String,
+        /// The initiator's SPAKE2 message, blinded by the PIN
+        #[serde(default)]
+        spake: String,
+    },
+    /// The responder's SPAKE2 message and proof it derived the shared key from the PIN its
+    /// user typed (never the PIN itself)
+    PinPairResponse {
+        from: String,
+        to: String,
+        nonce: String,
+        public_key: String,
+        #[serde(default)]
+        spake: String,
+        proof: String,
+    },
+    /// The initiator's verdict; `confirm` proves it knew the PIN too
+    PinPairResult {
+        from: String,
+        to: String,
+        nonce: String,
+        accepted: bool,
+        confirm: Option<String>,
+    },
 }
 
 /// Peer connection state
@@ -282,33 +620,93 @@ pub struct PeerConnection {
     pub session_id: Option<String>,
 }
 
+/// What a peer told us in its Capabilities message
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerCapabilities {
+    pub version: u32,
+    pub features: Vec<String>,
+}
+
+impl PeerCapabilities {
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+fn capabilities_message(from: &str, to: &str, reply: bool) -> SignalingMessage {
+    SignalingMessage::Capabilities {
+        from: from.to_string(),
+        to: to.to_string(),
+        version: PROTOCOL_VERSION,
+        features: LOCAL_FEATURES.iter().map(|f| f.to_string()).collect(),
+        reply,
+    }
+}
+
+/// Outstanding ping() calls by ping timestamp; answered with the Pong's file port and clock
+type PendingPings = Arc<Mutex<HashMap<u64, Sender<(Option<u16>, Option<u64>)>>>>;
+
+/// What the Pong to one of our pings told us
+#[derive(Debug, Clone, Copy)]
+pub struct PingReply {
+    pub rtt: Duration,
+    /// File server port the peer advertised (None for older versions)
+    pub file_port: Option<u16>,
+    /// The peer's clock minus ours in ms, assuming the reply was made halfway through the round
+    /// trip (None for older versions)
+    pub clock_offset_ms: Option<i64>,
+}
+
+/// Current wall clock in ms since the epoch, as Ping and Pong carry it
+pub fn clock_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 /// Signaling server for LAN communication
 pub struct SignalingServer {
-    #[allow(dead_code)]
-    device_id: String,
+    device_id: RwLock<String>,
     socket: Arc<RwLock<Option<UdpSocket>>>,
     peers: Arc<RwLock<HashMap<String, PeerConnection>>>,
     event_sender: Sender<SignalingMessage>,
     event_receiver: Receiver<SignalingMessage>,
     running: Arc<RwLock<bool>>,
+    // Bumped on every start so consumers of a previous run can tell they are stale
+    generation: AtomicU64,
+    // Outstanding ping() calls keyed by the ping timestamp; the receive loop answers them
+    pending_pings: PendingPings,
+    // Stops the current run's receive task
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+    // Peers known to accept compressed messages (from their hello, or from receiving one)
+    compressing_peers: Arc<RwLock<HashSet<String>>>,
+    // Result of the handshake, per peer that answered it
+    capabilities: Arc<RwLock<HashMap<String, PeerCapabilities>>>,
 }
 
 impl SignalingServer {
     /// Create a new signaling server
     pub fn new(device_id: String) -> Self {
-        let (sender, receiver) = unbounded();
+        let (sender, receiver) = bounded(EVENT_QUEUE);
 
         SignalingServer {
-            device_id,
+            device_id: RwLock::new(device_id),
             socket: Arc::new(RwLock::new(None)),
             peers: Arc::new(RwLock::new(HashMap::new())),
             event_sender: sender,
             event_receiver: receiver,
             running: Arc::new(RwLock::new(false)),
+            generation: AtomicU64::new(0),
+            pending_pings: Arc::new(Mutex::new(HashMap::new())),
+            shutdown: Mutex::new(None),
+            compressing_peers: Arc::new(RwLock::new(HashSet::new())),
+            capabilities: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    /// Start the signaling server
+    /// Start the signaling server. Packets are read by an async task on the app's runtime
+    /// and handed to the app through a bounded queue.
     pub fn start(&self, port: u16) -> Result<u16, String> {
         // Bind to UDP socket
         let socket = UdpSocket::bind(format!("0.0.0.0:{}", port))
@@ -319,6 +717,8 @@ impl SignalingServer {
 
         socket.set_nonblocking(true).map_err(|e| e.to_string())?;
 
+        // A previous run's receive task must not keep reading its socket
+        self.shut_down_receiver();
         {
             let mut sock = self.socket.write().unwrap();
             *sock = Some(socket.try_clone().map_err(|e| e.to_string())?);
@@ -328,159 +728,73 @@ impl SignalingServer {
             let mut running = self.running.write().unwrap();
             *running = true;
         }
+        self.generation.fetch_add(1, Ordering::SeqCst);
 
-        // Start listener thread
-        let socket_clone = socket;
-        let event_sender = self.event_sender.clone();
-        let peers = Arc::clone(&self.peers);
-        let running = Arc::clone(&self.running);
-        let device_id = self.device_id.clone();
-
-        thread::spawn(move || {
-            let mut buf = [0u8; BUFFER_SIZE];
-
-            while *running.read().unwrap() {
-                match socket_clone.recv_from(&mut buf) {
-                    Ok((size, src)) => {
-                        if let Ok(text) = std::str::from_utf8(&buf[..size]) {
-                            if let Ok(msg) = serde_json::from_str::<SignalingMessage>(text) {
-                                // Update peer address
-                                let peer_id = match &msg {
-                                    SignalingMessage::Offer { from, .. } => Some(from.clone()),
-                                    SignalingMessage::Answer { from, .. } => Some(from.clone()),
-                                    SignalingMessage::IceCandidate { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::ConnectionRequest { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::Ping { from, .. } => Some(from.clone()),
-                                    SignalingMessage::ChatMessage { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::ProfileUpdate { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::GroupChatMessage { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::GroupCreated { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingChatMessage { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::GroupMemberAdded { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::GroupMemberRemoved { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::ScreenShareResponse { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::ScreenShareEnded { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingInvite { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingInviteResponse { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingOffer { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingAnswer { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingIceCandidate { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingChat { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingLeave { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingEnded { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingScreenShare { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingScreenShareInvite { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingRejoinRequest { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    SignalingMessage::MeetingParticipantList { from, .. } => {
-                                        Some(from.clone())
-                                    }
-                                    _ => None,
-                                };
-
-                                if let Some(id) = peer_id {
-                                    if id != device_id {
-                                        let mut peers_lock = peers.write().unwrap();
-
-                                        // If we already know this peer's address, DO NOT allow an incoming
-                                        // packet from a different source to overwrite it. This prevents
-                                        // a remote client from spoofing an existing peer id (for
-                                        // example: telling others that the host stopped sharing).
-                                        if let Some(existing) = peers_lock.get(&id) {
-                                            if existing.address != src {
-                                                // Possible spoofing attempt — ignore this message.
-                                                println!(
-                                                    "[Signaling] Ignoring message for '{}' from {} (expected {})",
-                                                    id, src, existing.address
-                                                );
-                                                // skip forwarding the message to the app
-                                                continue;
-                                            }
-                                        } else {
-                                            // First time seeing this peer id — record address
-                                            peers_lock.insert(
-                                                id.clone(),
-                                                PeerConnection {
-                                                    peer_id: id.clone(),
-                                                    address: src,
-                                                    state: ConnectionState::Disconnected,
-                                                    session_id: None,
-                                                },
-                                            );
-                                        }
-                                    }
-                                }
-
-                                // Forward validated message to application
-                                let _ = event_sender.send(msg);
-                            }
-                        }
-                    }
-                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                        // No data available, sleep briefly
-                        thread::sleep(Duration::from_millis(10));
-                    }
-                    Err(_) => {
-                        thread::sleep(Duration::from_millis(100));
-                    }
-                }
-            }
-        });
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        *self.shutdown.lock().unwrap() = Some(shutdown_tx);
+        let inbound = Inbound {
+            events: self.event_sender.clone(),
+            peers: Arc::clone(&self.peers),
+            device_id: self.device_id.read().unwrap().clone(),
+            pending_pings: Arc::clone(&self.pending_pings),
+            compressing_peers: Arc::clone(&self.compressing_peers),
+            capabilities: Arc::clone(&self.capabilities),
+        };
+        tauri::async_runtime::spawn(inbound.run(socket, shutdown_rx));
 
         Ok(actual_port)
     }
 
+    fn shut_down_receiver(&self) {
+        if let Some(tx) = self.shutdown.lock().unwrap().take() {
+            let _ = tx.send(());
+        }
+    }
+
     /// Stop the signaling server
-    #[allow(dead_code)]
     pub fn stop(&self) {
         let mut running = self.running.write().unwrap();
         *running = false;
+        self.shut_down_receiver();
+        // Release the port so a restart can bind it again
+        *self.socket.write().unwrap() = None;
+    }
+
+    /// Port the socket is bound to, if running
+    pub fn local_port(&self) -> Option<u16> {
+        let socket = self.socket.read().unwrap();
+        socket
+            .as_ref()
+            .and_then(|s| s.local_addr().ok())
+            .map(|a| a.port())
+    }
+
+    pub fn is_running(&self) -> bool {
+        *self.running.read().unwrap()
+    }
+
+    /// Identifies the current run; changes every time the server is (re)started
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Change the local device id (profile switch). Takes effect on the next start.
+    pub fn set_device_id(&self, device_id: &str) {
+        *self.device_id.write().unwrap() = device_id.to_string();
+    }
+
+    /// Record whether a peer accepts compressed messages (from its discovery hello)
+    pub fn set_peer_compression(&self, peer_id: &str, supported: bool) {
+        let mut peers = self.compressing_peers.write().unwrap();
+        if supported {
+            peers.insert(peer_id.to_string());
+        } else {
+            peers.remove(peer_id);
+        }
     }
 
-    /// Send a signaling message to a peer
+    /// Send a signaling message to a peer. Large messages are compressed when the peer
+    /// supports it.
     pub fn send_message(&self, peer_id: &str, message: &SignalingMessage) -> Result<(), String> {
         let socket = self.socket.read().unwrap();
         let socket = socket.as_ref().ok_or("Socket not initialized")?;
@@ -488,7 +802,8 @@ impl SignalingServer {
         let peers = self.peers.read().unwrap();
         let peer = peers.get(peer_id).ok_or("Peer not found")?;
 
-        let data = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+        let compress = self.compressing_peers.read().unwrap().contains(peer_id);
+        let data = encode(message, compress)?;
         socket
             .send_to(&data, peer.address)
             .map_err(|e| e.to_string())?;
@@ -496,8 +811,7 @@ impl SignalingServer {
         Ok(())
     }
 
-    /// Send a message to a specific address
-    #[allow(dead_code)]
+    /// Send a message to a specific address (always plain JSON: the receiver may be anyone)
     pub fn send_to_address(
         &self,
         addr: SocketAddr,
@@ -512,26 +826,70 @@ impl SignalingServer {
         Ok(())
     }
 
-    /// Register a peer address
+    /// Send a Ping to `addr` and wait for the Pong
+    pub fn ping(&self, addr: SocketAddr, timeout: Duration) -> Result<PingReply, String> {
+        let timestamp = clock_ms();
+        let (tx, rx) = bounded(1);
+        self.pending_pings.lock().unwrap().insert(timestamp, tx);
+
+        let started = Instant::now();
+        let ping = SignalingMessage::Ping {
+            from: self.device_id.read().unwrap().clone(),
+            timestamp,
+        };
+        let result = self
+            .send_to_address(addr, &ping)
+            .and_then(|_| rx.recv_timeout(timeout).map_err(|_| "No reply".to_string()))
+            .map(|(file_port, clock)| {
+                let rtt = started.elapsed();
+                let midpoint = timestamp as i64 + rtt.as_millis() as i64 / 2;
+                PingReply {
+                    rtt,
+                    file_port,
+                    clock_offset_ms: clock
+                        .and_then(|c| i64::try_from(c).ok())
+                        .and_then(|c| c.checked_sub(midpoint)),
+                }
+            });
+        self.pending_pings.lock().unwrap().remove(&timestamp);
+        result
+    }
+
+    /// Register a peer address. The first registration starts the capabilities handshake.
     pub fn register_peer(&self, peer_id: &str, ip: &str, port: u16) -> Result<(), String> {
         let addr: SocketAddr = format!("{}:{}", ip, port)
             .parse()
             .map_err(|e: std::net::AddrParseError| e.to_string())?;
+        lan_policy::check_peer_address(addr.ip())?;
 
-        let mut peers = self.peers.write().unwrap();
-        peers.insert(
-            peer_id.to_string(),
-            PeerConnection {
-                peer_id: peer_id.to_string(),
-                address: addr,
-                state: ConnectionState::Disconnected,
-                session_id: None,
-            },
-        );
+        let first_contact = self
+            .peers
+            .write()
+            .unwrap()
+            .insert(
+                peer_id.to_string(),
+                PeerConnection {
+                    peer_id: peer_id.to_string(),
+                    address: addr,
+                    state: ConnectionState::Disconnected,
+                    session_id: None,
+                },
+            )
+            .is_none();
+        if first_contact {
+            let local_id = self.device_id.read().unwrap().clone();
+            // Best effort: a peer that doesn't answer is simply treated as version 1
+            let _ = self.send_message(peer_id, &capabilities_message(&local_id, peer_id, false));
+        }
 
         Ok(())
     }
 
+    /// Handshake result for a peer; None until it answers (or if it predates the handshake)
+    pub fn peer_capabilities(&self, peer_id: &str) -> Option<PeerCapabilities> {
+        self.capabilities.read().unwrap().get(peer_id).cloned()
+    }
+
     /// Update peer connection state
     #[allow(dead_code)]
     pub fn update_peer_state(&self, peer_id: &str, state: ConnectionState) {
@@ -541,6 +899,17 @@ impl SignalingServer {
         }
     }
 
+    /// Hand a message to the app as if it had just arrived (held message requests, once accepted)
+    pub fn redeliver(&self, message: SignalingMessage) {
+        if self
+            .event_sender
+            .send_timeout(message, QUEUE_FULL_WAIT)
+            .is_err()
+        {
+            warn!("Signaling queue full, dropping a redelivered message");
+        }
+    }
+
     /// Get event receiver
     #[allow(dead_code)]
     pub fn get_event_receiver(&self) -> Receiver<SignalingMessage> {
@@ -554,6 +923,15 @@ impl SignalingServer {
         peers.get(peer_id).cloned()
     }
 
+    /// Every registered peer and its address
+    pub fn peer_addresses(&self) -> Vec<(String, SocketAddr)> {
+        let peers = self.peers.read().unwrap();
+        peers
+            .iter()
+            .map(|(id, p)| (id.clone(), p.address))
+            .collect()
+    }
+
     /// Get all connected peers
     #[allow(dead_code)]
     pub fn get_connected_peers(&self) -> Vec<String> {
@@ -566,6 +944,552 @@ impl SignalingServer {
     }
 }
 
+/// Serialize a message, deflating it when allowed and worth it
+fn encode(message: &SignalingMessage, compress: bool) -> Result<Vec<u8>, String> {
+    let mut value = serde_json::to_value(message).map_err(|e| e.to_string())?;
+    if let Value::Object(fields) = &mut value {
+        fields.insert(VERSION_FIELD.to_string(), PROTOCOL_VERSION.into());
+    }
+    let json = serde_json::to_vec(&value).map_err(|e| e.to_string())?;
+    if !compress || json.len() < COMPRESS_THRESHOLD {
+        return Ok(json);
+    }
+    let mut encoder = DeflateEncoder::new(COMPRESSED_MAGIC.to_vec(), Compression::default());
+    encoder.write_all(&json).map_err(|e| e.to_string())?;
+    let packed = encoder.finish().map_err(|e| e.to_string())?;
+    Ok(if packed.len() < json.len() {
+        packed
+    } else {
+        json
+    })
+}
+
+/// A datagram that parsed as JSON (short-lived, so the size difference doesn't matter)
+#[allow(clippy::large_enum_variant)]
+enum Decoded {
+    Message(SignalingMessage),
+    /// Names a sender and a type, but isn't a message this build can decode
+    Unsupported {
+        from: String,
+        message_type: String,
+        version: u32,
+    },
+}
+
+/// Compatibility shim between protocol versions: takes the envelope field off and rewrites
+/// messages from other versions into the shape this build expects. Versions 1 and 2 differ
+/// only by the envelope; a later change to an existing message gets its rewrite here instead
+/// of breaking older peers.
+fn open_envelope(value: &mut Value) -> u32 {
+    value
+        .as_object_mut()
+        .and_then(|fields| fields.remove(VERSION_FIELD))
+        .and_then(|v| v.as_u64())
+        .map_or(1, |v| v as u32)
+}
+
+/// Parse a datagram, plain or compressed. The flag tells whether it was compressed.
+/// Unknown fields are ignored; an unknown type comes back as Decoded::Unsupported.
+fn decode(data: &[u8]) -> Option<(Decoded, bool)> {
+    let (mut value, compressed) = match data.strip_prefix(COMPRESSED_MAGIC) {
+        None => (serde_json::from_slice::<Value>(data).ok()?, false),
+        Some(packed) => {
+            let mut json = Vec::new();
+            DeflateDecoder::new(packed)
+                .take(MAX_INFLATED + 1)
+                .read_to_end(&mut json)
+                .ok()?;
+            if json.len() as u64 > MAX_INFLATED {
+                return None;
+            }
+            (serde_json::from_slice::<Value>(&json).ok()?, true)
+        }
+    };
+    let version = open_envelope(&mut value);
+    let field = |name: &str| value.get(name).and_then(Value::as_str).map(str::to_string);
+    let (from, message_type) = (field("from"), field("type"));
+    let decoded = match serde_json::from_value(value) {
+        Ok(msg) => Decoded::Message(msg),
+        Err(_) => Decoded::Unsupported {
+            from: from?,
+            message_type: message_type?,
+            version,
+        },
+    };
+    Some((decoded, compressed))
+}
+
+/// Message ids handled in the last little while. Retries, the TCP fallback and relays can
+/// deliver the same message several times in quick succession; only the first is handled, so it
+/// is stored and shown once. Repeats of a chat message are still acknowledged each time (the
+/// sender retries because it missed an ack).
+pub struct DedupWindow {
+    window: Duration,
+    seen: HashMap<String, Instant>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl DedupWindow {
+    pub fn new(window: Duration) -> Self {
+        DedupWindow {
+            window,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// False when `msg` repeats one seen within the window. Messages without an id always pass.
+    pub fn first_sighting(&mut self, msg: &SignalingMessage, now: Instant) -> bool {
+        let Some(key) = dedup_key(msg) else {
+            return true;
+        };
+        while let Some((at, old)) = self.order.front() {
+            if now.duration_since(*at) < self.window && self.order.len() < DEDUP_CAPACITY {
+                break;
+            }
+            if self.seen.get(old) == Some(at) {
+                self.seen.remove(old);
+            }
+            self.order.pop_front();
+        }
+        if self.seen.contains_key(&key) {
+            return false;
+        }
+        self.seen.insert(key.clone(), now);
+        self.order.push_back((now, key));
+        true
+    }
+}
+
+/// What identifies a repeat of `msg`; None for kinds that may legitimately repeat
+fn dedup_key(msg: &SignalingMessage) -> Option<String> {
+    match msg {
+        SignalingMessage::ChatMessage { from, id, .. } => Some(format!("chat:{}:{}", from, id)),
+        SignalingMessage::GroupChatMessage { from, id, .. } => {
+            Some(format!("group:{}:{}", from, id))
+        }
+        SignalingMessage::MeetingChatMessage { from, id, .. } => {
+            Some(format!("meeting:{}:{}", from, id))
+        }
+        SignalingMessage::DeliveryAck {
+            from, message_id, ..
+        } => Some(format!("ack:{}:{}", from, message_id)),
+        _ => None,
+    }
+}
+
+/// Who a message claims to be from, for the address check in Inbound::accept
+fn claimed_sender(msg: &SignalingMessage) -> Option<String> {
+    match msg {
+        SignalingMessage::Offer { from, .. } => Some(from.clone()),
+        SignalingMessage::Answer { from, .. } => Some(from.clone()),
+        SignalingMessage::IceCandidate { from, .. } => Some(from.clone()),
+        SignalingMessage::ConnectionRequest { from, .. } => Some(from.clone()),
+        SignalingMessage::Ping { from, .. } => Some(from.clone()),
+        SignalingMessage::Capabilities { from, .. } => Some(from.clone()),
+        SignalingMessage::Unsupported { from, .. } => Some(from.clone()),
+        SignalingMessage::ChatMessage { from, .. } => Some(from.clone()),
+        SignalingMessage::FileTransferRequest { from, .. } => Some(from.clone()),
+        SignalingMessage::FileTransferResponse { from, .. } => Some(from.clone()),
+        SignalingMessage::FileChunkData { from, .. }
+        | SignalingMessage::FileTransferDone { from, .. }
+        | SignalingMessage::FileTransferStatus { from, .. } => Some(from.clone()),
+        SignalingMessage::ProfileUpdate { from, .. } => Some(from.clone()),
+        SignalingMessage::GroupChatMessage { from, .. } => Some(from.clone()),
+        SignalingMessage::GroupCreated { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingChatMessage { from, .. } => Some(from.clone()),
+        SignalingMessage::GroupMemberAdded { from, .. } => Some(from.clone()),
+        SignalingMessage::GroupMemberRemoved { from, .. } => Some(from.clone()),
+        SignalingMessage::GroupRoleChanged { from, .. } => Some(from.clone()),
+        SignalingMessage::ScreenShareResponse { from, .. } => Some(from.clone()),
+        SignalingMessage::ScreenShareEnded { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingInvite { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingInviteResponse { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingOffer { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingAnswer { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingIceCandidate { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingChat { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingLeave { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingEnded { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingScreenShare { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingScreenShareInvite { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingRejoinRequest { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingParticipantList { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingRecording { from, .. } => Some(from.clone()),
+        SignalingMessage::WhiteboardOp { from, .. } => Some(from.clone()),
+        SignalingMessage::MessageDeleted { from, .. } => Some(from.clone()),
+        SignalingMessage::ConversationCleared { from, .. } => Some(from.clone()),
+        SignalingMessage::RemoteControlRequest { from, .. } => Some(from.clone()),
+        SignalingMessage::RemoteControlResponse { from, .. } => Some(from.clone()),
+        SignalingMessage::RemoteInput { from, .. } => Some(from.clone()),
+        SignalingMessage::RemoteControlEnd { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingJoinRequest { from, .. } => Some(from.clone()),
+        SignalingMessage::MeetingJoinDecision { from, .. } => Some(from.clone()),
+        SignalingMessage::ScheduledMeetingInvite { from, .. } => Some(from.clone()),
+        SignalingMessage::ScheduledMeetingInviteAck { from, .. } => Some(from.clone()),
+        SignalingMessage::LinkRequest { from, .. } => Some(from.clone()),
+        SignalingMessage::LinkAccept { from, .. } => Some(from.clone()),
+        SignalingMessage::SyncRequest { from, .. } => Some(from.clone()),
+        SignalingMessage::SyncBatch { from, .. } => Some(from.clone()),
+        SignalingMessage::NoteSync { from, .. } => Some(from.clone()),
+        SignalingMessage::SettingsSync { from, .. } => Some(from.clone()),
+        SignalingMessage::PairIntroduction { from, .. } => Some(from.clone()),
+        SignalingMessage::NoteShare { from, .. } => Some(from.clone()),
+        SignalingMessage::PinPairRequest { from, .. }
+        | SignalingMessage::PinPairResponse { from, .. }
+        | SignalingMessage::PinPairResult { from, .. } => Some(from.clone()),
+        SignalingMessage::NativeMeetingOffer { from, .. }
+        | SignalingMessage::NativeMeetingAnswer { from, .. }
+        | SignalingMessage::NativeMeetingCandidate { from, .. }
+        | SignalingMessage::NativeMeetingLeave { from, .. } => Some(from.clone()),
+        _ => None,
+    }
+}
+
+/// What the receive task needs from the server
+struct Inbound {
+    events: Sender<SignalingMessage>,
+    peers: Arc<RwLock<HashMap<String, PeerConnection>>>,
+    device_id: String,
+    pending_pings: PendingPings,
+    compressing_peers: Arc<RwLock<HashSet<String>>>,
+    capabilities: Arc<RwLock<HashMap<String, PeerCapabilities>>>,
+}
+
+impl Inbound {
+    async fn run(self, socket: UdpSocket, mut shutdown: oneshot::Receiver<()>) {
+        let socket = match tokio::net::UdpSocket::from_std(socket) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Signaling receive task failed to start: {}", e);
+                return;
+            }
+        };
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        loop {
+            let (size, src) = tokio::select! {
+                // Fires on stop/restart, and when the server is dropped
+                _ = &mut shutdown => break,
+                received = socket.recv_from(&mut buf) => match received {
+                    Ok(r) => r,
+                    Err(_) => {
+                        tokio::time::sleep(ERROR_BACKOFF).await;
+                        continue;
+                    }
+                },
+            };
+            if !lan_policy::accepts(src.ip()) {
+                continue;
+            }
+            let (msg, compressed) = match decode(&buf[..size]) {
+                Some((Decoded::Message(msg), compressed)) => (msg, compressed),
+                Some((
+                    Decoded::Unsupported {
+                        from,
+                        message_type,
+                        version,
+                    },
+                    _,
+                )) => {
+                    self.refuse(&socket, src, from, message_type, version).await;
+                    continue;
+                }
+                None => continue,
+            };
+            if let Some(msg) = self.accept(msg, src) {
+                // A peer that compresses can also inflate
+                if compressed {
+                    if let Some(from) = claimed_sender(&msg) {
+                        self.compressing_peers.write().unwrap().insert(from);
+                    }
+                }
+                if let SignalingMessage::Capabilities {
+                    from,
+                    version,
+                    features,
+                    reply,
+                    ..
+                } = msg
+                {
+                    if !reply {
+                        let answer = capabilities_message(&self.device_id, &from, true);
+                        if let Ok(data) = encode(&answer, false) {
+                            let _ = socket.send_to(&data, src).await;
+                        }
+                    }
+                    self.record_capabilities(from, version, features);
+                    continue;
+                }
+                if let SignalingMessage::Unsupported {
+                    from,
+                    message_type,
+                    version,
+                    ..
+                } = &msg
+                {
+                    warn!(
+                        "Peer {} (protocol v{}) can't handle our {} messages",
+                        from, version, message_type
+                    );
+                    continue;
+                }
+                if !self.forward(msg).await {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Tell a known peer we couldn't decode its message. Nothing is sent to unknown addresses,
+    /// or in answer to an Unsupported we couldn't read, so two versions can't loop.
+    async fn refuse(
+        &self,
+        socket: &tokio::net::UdpSocket,
+        src: SocketAddr,
+        from: String,
+        message_type: String,
+        version: u32,
+    ) {
+        warn!(
+            "Dropped a {} message from {} (protocol v{})",
+            message_type, from, version
+        );
+        let known = self
+            .peers
+            .read()
+            .unwrap()
+            .get(&from)
+            .is_some_and(|p| p.address == src);
+        if !known || message_type == "Unsupported" {
+            return;
+        }
+        let answer = SignalingMessage::Unsupported {
+            from: self.device_id.clone(),
+            to: from,
+            message_type,
+            version: PROTOCOL_VERSION,
+        };
+        if let Ok(data) = encode(&answer, false) {
+            let _ = socket.send_to(&data, src).await;
+        }
+    }
+
+    fn record_capabilities(&self, from: String, version: u32, features: Vec<String>) {
+        let peer = PeerCapabilities { version, features };
+        {
+            let mut compressing = self.compressing_peers.write().unwrap();
+            if peer.supports(features::COMPRESSION) {
+                compressing.insert(from.clone());
+            } else {
+                compressing.remove(&from);
+            }
+        }
+        self.capabilities.write().unwrap().insert(from, peer);
+    }
+
+    /// Replies to our own ping() are consumed here; a message claiming a peer id we know at a
+    /// different address is dropped, so a remote client can't spoof an existing peer (for
+    /// example: telling others that the host stopped sharing).
+    fn accept(&self, msg: SignalingMessage, src: SocketAddr) -> Option<SignalingMessage> {
+        if let SignalingMessage::Pong {
+            timestamp,
+            file_port,
+            clock,
+            ..
+        } = &msg
+        {
+            if let Some(tx) = self.pending_pings.lock().unwrap().remove(timestamp) {
+                let _ = tx.send((*file_port, *clock));
+                return None;
+            }
+        }
+
+        if let Some(id) = claimed_sender(&msg) {
+            if id != self.device_id {
+                let mut peers_lock = self.peers.write().unwrap();
+                if let Some(existing) = peers_lock.get(&id) {
+                    if existing.address != src {
+                        warn!(
+                            "Ignoring message for '{}' from {} (expected {})",
+                            id, src, existing.address
+                        );
+                        return None;
+                    }
+                } else {
+                    // First time seeing this peer id — record address
+                    peers_lock.insert(
+                        id.clone(),
+                        PeerConnection {
+                            peer_id: id,
+                            address: src,
+                            state: ConnectionState::Disconnected,
+                            session_id: None,
+                        },
+                    );
+                }
+            }
+        }
+        Some(msg)
+    }
+
+    /// Queue a message for the app, waiting (without reading further packets) while the queue
+    /// is full. False once the app side is gone.
+    async fn forward(&self, msg: SignalingMessage) -> bool {
+        let msg = match self.events.try_send(msg) {
+            Ok(()) => return true,
+            Err(TrySendError::Disconnected(_)) => return false,
+            Err(TrySendError::Full(msg)) => msg,
+        };
+        let events = self.events.clone();
+        // Err(true): still full after the wait; Err(false): the app side is gone
+        let waited = tokio::task::spawn_blocking(move || {
+            events
+                .send_timeout(msg, QUEUE_FULL_WAIT)
+                .map_err(|e| matches!(e, SendTimeoutError::Timeout(_)))
+        })
+        .await;
+        match waited {
+            Ok(Err(true)) => {
+                warn!("Signaling queue full, dropping a message");
+                true
+            }
+            Ok(Err(false)) => false,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_window() {
+        let chat = |id: &str| SignalingMessage::ChatMessage {
+            from: "a".to_string(),
+            to: "b".to_string(),
+            id: id.to_string(),
+            content: "hi".to_string(),
+            message_type: "text".to_string(),
+            sender_name: "A".to_string(),
+            timestamp: "t".to_string(),
+            lamport: 1,
+            auto_reply: false,
+        };
+        let mut window = DedupWindow::new(Duration::from_secs(30));
+        let start = Instant::now();
+        assert!(window.first_sighting(&chat("m1"), start));
+        assert!(!window.first_sighting(&chat("m1"), start + Duration::from_secs(1)));
+        assert!(window.first_sighting(&chat("m2"), start + Duration::from_secs(1)));
+        let ack = SignalingMessage::DeliveryAck {
+            from: "a".to_string(),
+            to: "b".to_string(),
+            message_id: "m1".to_string(),
+        };
+        assert!(window.first_sighting(&ack, start));
+        assert!(!window.first_sighting(&ack, start));
+        // Kinds without an id are never held back
+        let ping = SignalingMessage::Ping {
+            from: "a".to_string(),
+            timestamp: 1,
+        };
+        assert!(window.first_sighting(&ping, start) && window.first_sighting(&ping, start));
+        // Past the window it's handled again
+        assert!(window.first_sighting(&chat("m1"), start + Duration::from_secs(31)));
+    }
+
+    fn request(file_name: String) -> SignalingMessage {
+        SignalingMessage::FileTransferRequest {
+            from: "a".to_string(),
+            to: "b".to_string(),
+            file_name,
+            file_size: 1,
+            file_type: "text/plain".to_string(),
+            transfer_id: "t".to_string(),
+            offer: None,
+        }
+    }
+
+    #[test]
+    fn test_compression_round_trip_and_fallback() {
+        let large = request("x".repeat(4000));
+        let packed = encode(&large, true).unwrap();
+        assert!(packed.starts_with(COMPRESSED_MAGIC));
+        assert!(packed.len() < 4000);
+        let Some((Decoded::Message(msg), compressed)) = decode(&packed) else {
+            panic!("didn't decode");
+        };
+        assert!(compressed);
+        assert_eq!(
+            serde_json::to_value(msg).unwrap(),
+            serde_json::to_value(&large).unwrap()
+        );
+
+        // Small messages and peers without support get plain JSON
+        let small = encode(&request("x".to_string()), true).unwrap();
+        assert_eq!(small.first(), Some(&b'{'));
+        let plain = encode(&large, false).unwrap();
+        assert!(matches!(decode(&plain), Some((Decoded::Message(_), false))));
+        assert!(decode(b"\0PZ1not deflate").is_none());
+    }
+
+    #[test]
+    fn test_capabilities_from_newer_peer() {
+        // A newer peer may list features we don't know, and older senders omit `reply`
+        let json = r#"{"type":"Capabilities","from":"a","to":"b","version":3,"features":["compression","tcp_fallback"]}"#;
+        let Some((
+            Decoded::Message(SignalingMessage::Capabilities {
+                version,
+                features: list,
+                reply,
+                ..
+            }),
+            false,
+        )) = decode(json.as_bytes())
+        else {
+            panic!("not a Capabilities message");
+        };
+        assert!(!reply);
+        let caps = PeerCapabilities {
+            version,
+            features: list,
+        };
+        assert!(caps.supports(features::COMPRESSION));
+        assert!(!caps.supports(features::ENCRYPTION));
+    }
+
+    #[test]
+    fn test_unknown_types_and_fields_from_newer_versions() {
+        let unknown = br#"{"type":"Hologram","v":3,"from":"a","to":"b","beam":1}"#;
+        let Some((
+            Decoded::Unsupported {
+                from,
+                message_type,
+                version,
+            },
+            _,
+        )) = decode(unknown)
+        else {
+            panic!("should be unsupported");
+        };
+        assert_eq!(
+            (from.as_str(), message_type.as_str(), version),
+            ("a", "Hologram", 3)
+        );
+
+        let extra_field = br#"{"type":"Ping","v":3,"from":"a","timestamp":1,"priority":"high"}"#;
+        assert!(matches!(
+            decode(extra_field),
+            Some((
+                Decoded::Message(SignalingMessage::Ping { timestamp: 1, .. }),
+                _
+            ))
+        ));
+        // Not even a sender: nothing to answer
+        assert!(decode(br#"{"type":"Hologram"}"#).is_none());
+    }
+}
+
 /*
 WEBRTC SIGNALING FLOW:
 
@@ -0,0 +1,289 @@
+// src-tauri/src/sounds.rs
+// Notification sounds: bundled tones, user-imported files and per-chat overrides
+
+use crate::commands::AppState;
+use crate::db::Database;
+use crate::device_sync;
+use rodio::source::{SineWave, Source};
+use rodio::{Decoder, OutputStream, Sink};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::State;
+use tracing::warn;
+
+const DEFAULT_SOUND: &str = "chime";
+const NO_SOUND: &str = "none";
+const CUSTOM_PREFIX: &str = "custom:";
+const SUPPORTED_EXTENSIONS: &[&str] = &["wav", "mp3", "ogg", "flac"];
+
+/// Built-in sounds are synthesized, so nothing has to be shipped as a resource.
+/// Each entry is (id, display name, [(frequency Hz, duration ms)]).
+type BundledSound = (&'static str, &'static str, &'static [(f32, u64)]);
+const BUNDLED: &[BundledSound] = &[
+    ("chime", "Chime", &[(880.0, 90), (1318.5, 160)]),
+    ("ping", "Ping", &[(1567.98, 120)]),
+    ("pop", "Pop", &[(440.0, 40), (660.0, 50)]),
+    ("knock", "Knock", &[(220.0, 60), (0.0, 80), (220.0, 60)]),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SoundInfo {
+    pub id: String,
+    pub name: String,
+    pub bundled: bool,
+}
+
+/// Imported sounds live next to the database, so each PINGO_INSTANCE gets its own set
+fn sounds_dir() -> PathBuf {
+    let dir = Database::get_db_path()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("sounds");
+    std::fs::create_dir_all(&dir).ok();
+    dir
+}
+
+/// Resolve a `custom:<file>` id to its path, refusing anything that escapes the sounds dir
+fn custom_path(sound_id: &str) -> Option<PathBuf> {
+    let file = sound_id.strip_prefix(CUSTOM_PREFIX)?;
+    if file.is_empty() || file.contains(['/', '\\']) || file.contains("..") {
+        return None;
+    }
+    Some(sounds_dir().join(file))
+}
+
+fn is_known_sound(sound_id: &str) -> bool {
+    sound_id == NO_SOUND
+        || BUNDLED.iter().any(|(id, _, _)| *id == sound_id)
+        || custom_path(sound_id).map(|p| p.exists()).unwrap_or(false)
+}
+
+/// Sound to play for a chat: its override if set, otherwise the global default
+pub fn sound_for_chat(db: &Database, chat_id: &str) -> String {
+    db.get_chat_sound(chat_id)
+        .ok()
+        .flatten()
+        .or_else(|| db.get_setting("notification_sound").ok().flatten())
+        .unwrap_or_else(|| DEFAULT_SOUND.to_string())
+}
+
+/// Play a sound without blocking the caller. The output stream is not Send, so it is
+/// opened on the playback thread and dropped once the sound finishes.
+pub fn play(sound_id: &str) {
+    if sound_id == NO_SOUND {
+        return;
+    }
+    let sound_id = sound_id.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = play_blocking(&sound_id) {
+            warn!("Failed to play sound {}: {}", sound_id, e);
+        }
+    });
+}
+
+fn play_blocking(sound_id: &str) -> Result<(), String> {
+    let (_stream, handle) =
+        OutputStream::try_default().map_err(|e| format!("No audio output: {}", e))?;
+    let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
+
+    if let Some((_, _, tones)) = BUNDLED.iter().find(|(id, _, _)| *id == sound_id) {
+        for &(freq, ms) in tones.iter() {
+            let tone = SineWave::new(freq)
+                .take_duration(Duration::from_millis(ms))
+                .amplify(if freq > 0.0 { 0.2 } else { 0.0 });
+            sink.append(tone);
+        }
+    } else {
+        let path = custom_path(sound_id).ok_or_else(|| "Unknown sound".to_string())?;
+        let file = File::open(&path).map_err(|e| e.to_string())?;
+        let source = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+        sink.append(source);
+    }
+    sink.sleep_until_end();
+    Ok(())
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn list_sounds() -> Vec<SoundInfo> {
+    let mut sounds: Vec<SoundInfo> = BUNDLED
+        .iter()
+        .map(|(id, name, _)| SoundInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            bundled: true,
+        })
+        .collect();
+
+    if let Ok(entries) = std::fs::read_dir(sounds_dir()) {
+        let mut custom: Vec<SoundInfo> = entries
+            .flatten()
+            .filter_map(|e| {
+                let file = e.file_name().to_string_lossy().to_string();
+                let name = Path::new(&file).file_stem()?.to_string_lossy().to_string();
+                Some(SoundInfo {
+                    id: format!("{}{}", CUSTOM_PREFIX, file),
+                    name,
+                    bundled: false,
+                })
+            })
+            .collect();
+        custom.sort_by(|a, b| a.name.cmp(&b.name));
+        sounds.extend(custom);
+    }
+    sounds
+}
+
+/// Copy a user-selected audio file into the sounds folder after checking it decodes
+#[tauri::command]
+pub fn import_sound(file_path: String) -> Result<SoundInfo, String> {
+    let src = PathBuf::from(&file_path);
+    let ext = src
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !SUPPORTED_EXTENSIONS.contains(&ext.as_str()) {
+        return Err(format!(
+            "Unsupported sound format (use {})",
+            SUPPORTED_EXTENSIONS.join(", ")
+        ));
+    }
+    let file = File::open(&src).map_err(|e| format!("Failed to open sound: {}", e))?;
+    Decoder::new(BufReader::new(file)).map_err(|e| format!("Not a playable sound: {}", e))?;
+
+    let stem = src
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sound".to_string());
+    let safe_stem: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    let dir = sounds_dir();
+    let mut dest = dir.join(format!("{}.{}", safe_stem, ext));
+    let mut n = 1;
+    while dest.exists() {
+        dest = dir.join(format!("{} ({}).{}", safe_stem, n, ext));
+        n += 1;
+    }
+    std::fs::copy(&src, &dest).map_err(|e| format!("Failed to import sound: {}", e))?;
+
+    let file_name = dest.file_name().unwrap().to_string_lossy().to_string();
+    Ok(SoundInfo {
+        id: format!("{}{}", CUSTOM_PREFIX, file_name),
+        name: dest.file_stem().unwrap().to_string_lossy().to_string(),
+        bundled: false,
+    })
+}
+
+/// Delete an imported sound; chats that used it fall back to the default sound
+#[tauri::command]
+pub fn delete_sound(state: State<AppState>, sound_id: String) -> Result<(), String> {
+    let path =
+        custom_path(&sound_id).ok_or_else(|| "Only imported sounds can be deleted".to_string())?;
+    std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    state
+        .db
+        .clear_chat_sounds_using(&sound_id)
+        .map_err(|e| e.to_string())?;
+    if state
+        .db
+        .get_setting("notification_sound")
+        .ok()
+        .flatten()
+        .as_deref()
+        == Some(sound_id.as_str())
+    {
+        state
+            .db
+            .set_setting("notification_sound", DEFAULT_SOUND)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn preview_sound(sound_id: String) -> Result<(), String> {
+    if !is_known_sound(&sound_id) {
+        return Err(format!("Unknown sound: {}", sound_id));
+    }
+    play(&sound_id);
+    Ok(())
+}
+
+/// Set the default notification sound ("none" disables sounds)
+#[tauri::command]
+pub fn set_default_sound(state: State<AppState>, sound_id: String) -> Result<(), String> {
+    if !is_known_sound(&sound_id) {
+        return Err(format!("Unknown sound: {}", sound_id));
+    }
+    state
+        .db
+        .set_setting("notification_sound", &sound_id)
+        .map_err(|e| e.to_string())?;
+    device_sync::push_settings(&state, &["notification_sound"]);
+    Ok(())
+}
+
+/// Override the sound for one chat (peer device_id or group_id); `None` clears the override
+#[tauri::command]
+pub fn set_chat_sound(
+    state: State<AppState>,
+    chat_id: String,
+    sound_id: Option<String>,
+) -> Result<(), String> {
+    if let Some(id) = &sound_id {
+        if !is_known_sound(id) {
+            return Err(format!("Unknown sound: {}", id));
+        }
+    }
+    state
+        .db
+        .set_chat_sound(&chat_id, sound_id.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the chat's override, or None when it uses the default
+#[tauri::command]
+pub fn get_chat_sound(state: State<AppState>, chat_id: String) -> Result<Option<String>, String> {
+    state.db.get_chat_sound(&chat_id).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_path_rejects_traversal() {
+        assert!(custom_path("custom:../pingo.db").is_none());
+        assert!(custom_path("custom:a/b.wav").is_none());
+        assert!(custom_path("custom:").is_none());
+        assert!(custom_path("chime").is_none());
+        assert!(custom_path("custom:bell.wav").is_some());
+    }
+
+    #[test]
+    fn test_sound_for_chat_resolution() {
+        let db = Database::new_in_memory().unwrap();
+        assert_eq!(sound_for_chat(&db, "peer-1"), DEFAULT_SOUND);
+        db.set_setting("notification_sound", "ping").unwrap();
+        assert_eq!(sound_for_chat(&db, "peer-1"), "ping");
+        db.set_chat_sound("peer-1", Some("pop")).unwrap();
+        assert_eq!(sound_for_chat(&db, "peer-1"), "pop");
+        assert_eq!(sound_for_chat(&db, "peer-2"), "ping");
+        db.set_chat_sound("peer-1", None).unwrap();
+        assert_eq!(sound_for_chat(&db, "peer-1"), "ping");
+    }
+}
@@ -0,0 +1,167 @@
+// src-tauri/src/status.rs
+// Custom status (emoji + text, optionally expiring). It's stored on the local user, announced
+// to peers with every discovery hello and carried by ProfileUpdate; a background timer clears
+// it once it expires so peers stop seeing it without the user doing anything.
+
+use crate::commands::AppState;
+use crate::db::{now, UserStatus};
+use chrono::{DateTime, Utc};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const MAX_TEXT_CHARS: usize = 100;
+const MAX_EMOJI_CHARS: usize = 16;
+const EXPIRY_CHECK: Duration = Duration::from_secs(30);
+const VISIBLE_KEY: &str = "discovery_visible";
+
+/// Whether we announce ourselves on the LAN (false: invisible mode)
+pub fn load_visibility(state: &AppState) -> bool {
+    state.db.get_setting(VISIBLE_KEY).ok().flatten().as_deref() != Some("false")
+}
+
+fn clean(value: Option<String>, max: usize) -> Option<String> {
+    value
+        .map(|v| v.trim().chars().take(max).collect::<String>())
+        .filter(|v| !v.is_empty())
+}
+
+/// Store and announce our status (None clears it)
+fn apply<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    status: Option<UserStatus>,
+) -> Result<(), String> {
+    state
+        .db
+        .set_user_status(&state.device_id(), status.as_ref())
+        .map_err(|e| e.to_string())?;
+    state.discovery.set_status(status.clone());
+    let _ = app.emit("status-changed", serde_json::json!({ "status": status }));
+    Ok(())
+}
+
+/// Clear our status once it expires, for the life of the app
+pub fn start_expiry_timer<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(EXPIRY_CHECK);
+        let state = app.state::<AppState>();
+        match state.db.clear_expired_status(&state.device_id(), &now()) {
+            Ok(true) => {
+                info!("Custom status expired");
+                if let Err(e) = apply(&app, &state, None) {
+                    warn!("Clearing expired status failed: {}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Status expiry check failed: {}", e),
+        }
+    });
+}
+
+// ============ COMMANDS ============
+
+/// Set our custom status. `expires_at` is RFC 3339 and must be in the future; with neither
+/// emoji nor text the status is cleared.
+#[tauri::command]
+pub fn set_status<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    emoji: Option<String>,
+    text: Option<String>,
+    expires_at: Option<String>,
+) -> Result<Option<UserStatus>, String> {
+    let emoji = clean(emoji, MAX_EMOJI_CHARS);
+    let text = clean(text, MAX_TEXT_CHARS);
+    if emoji.is_none() && text.is_none() {
+        apply(&app, &state, None)?;
+        return Ok(None);
+    }
+    let expires_at = match expires_at.filter(|t| !t.trim().is_empty()) {
+        Some(t) => {
+            let at = DateTime::parse_from_rfc3339(t.trim())
+                .map_err(|e| format!("Invalid expiry time: {}", e))?
+                .with_timezone(&Utc);
+            if at <= Utc::now() {
+                return Err("Expiry time is in the past".to_string());
+            }
+            // Stored in UTC so the expiry check can compare strings
+            Some(at.to_rfc3339())
+        }
+        None => None,
+    };
+    let status = UserStatus {
+        emoji,
+        text,
+        expires_at,
+    };
+    apply(&app, &state, Some(status.clone()))?;
+    Ok(Some(status))
+}
+
+#[tauri::command]
+pub fn clear_status<R: Runtime>(app: AppHandle<R>, state: State<AppState>) -> Result<(), String> {
+    apply(&app, &state, None)
+}
+
+/// Invisible mode when false: discovery keeps listening but stops announcing us
+#[tauri::command]
+pub fn set_visibility<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    visible: bool,
+) -> Result<(), String> {
+    state
+        .db
+        .set_setting(VISIBLE_KEY, if visible { "true" } else { "false" })
+        .map_err(|e| e.to_string())?;
+    state.discovery.set_visible(visible);
+    let _ = app.emit(
+        "visibility-changed",
+        serde_json::json!({ "visible": visible }),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_visibility(state: State<AppState>) -> bool {
+    state.discovery.is_visible()
+}
+
+/// Our current status; None when unset or expired
+#[tauri::command]
+pub fn get_status(state: State<AppState>) -> Result<Option<UserStatus>, String> {
+    state
+        .db
+        .get_user_status(&state.device_id())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{Database, UserStatus};
+    use chrono::{Duration as ChronoDuration, Utc};
+
+    #[test]
+    fn test_expired_status_is_cleared() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("me", "Me", None).unwrap();
+        let soon = Utc::now() + ChronoDuration::minutes(5);
+        let status = UserStatus {
+            emoji: Some("🌴".to_string()),
+            text: Some("Out".to_string()),
+            expires_at: Some(soon.to_rfc3339()),
+        };
+        db.set_user_status("me", Some(&status)).unwrap();
+        assert_eq!(db.get_user_status("me").unwrap(), Some(status));
+        assert!(!db
+            .clear_expired_status("me", &Utc::now().to_rfc3339())
+            .unwrap());
+
+        let later = (soon + ChronoDuration::minutes(1)).to_rfc3339();
+        assert!(db.clear_expired_status("me", &later).unwrap());
+        assert_eq!(db.get_user_status("me").unwrap(), None);
+    }
+}
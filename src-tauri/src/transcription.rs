@@ -0,0 +1,275 @@
+// src-tauri/src/transcription.rs
+// Voice message transcription: when configured, received audio attachments are run through an
+// external speech-to-text command (whisper.cpp's whisper-cli, or anything that prints the text
+// on stdout) in the background. The transcript is stored on the download row, indexed for
+// full-text search, and announced with "transcription-ready".
+
+use crate::commands::AppState;
+use crate::db::{AttachmentTextHit, Database, DownloadRecord};
+use crate::external_tool::{self, Tool};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "transcription";
+const AUDIO_EXTENSIONS: [&str; 9] = [
+    "ogg", "opus", "oga", "m4a", "mp3", "wav", "webm", "aac", "flac",
+];
+const MAX_SEARCH_RESULTS: i64 = 200;
+
+/// One transcription at a time; speech models are heavy
+static WORKER: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SttConfig {
+    pub enabled: bool,
+    /// Speech-to-text executable, e.g. "whisper-cli"
+    pub command: String,
+    /// e.g. ["-m", "models/ggml-base.bin", "-nt", "-np", "-f", "{file}"]
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    300
+}
+
+impl Default for SttConfig {
+    fn default() -> Self {
+        SttConfig {
+            enabled: false,
+            command: String::new(),
+            args: Vec::new(),
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TranscriptionReady<'a> {
+    download_id: &'a str,
+    message_id: Option<&'a str>,
+    transcript: &'a str,
+}
+
+pub fn load(db: &Database) -> SttConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+/// Voice messages and other audio attachments, by type or extension
+pub fn is_audio(record: &DownloadRecord) -> bool {
+    if record.file_type == "audio" || record.file_type.starts_with("audio/") {
+        return true;
+    }
+    Path::new(&record.file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// whisper.cpp prints "[00:00:00.000 --> 00:00:02.000]  text" unless told not to; keep the text
+fn clean_output(output: &str) -> String {
+    output
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            match line.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+                Some((stamp, text)) if stamp.contains("-->") => text.trim(),
+                _ => line,
+            }
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Run the speech-to-text command on `file` and return what it printed
+pub fn run(config: &SttConfig, file: &Path) -> Result<String, String> {
+    let args = external_tool::args_with_file(&config.args, file);
+    let output = Tool {
+        name: "Transcriber",
+        program: &config.command,
+        args: &args,
+        timeout_secs: config.timeout_secs,
+    }
+    .output(None)?;
+    Ok(clean_output(&output))
+}
+
+/// Transcribe a completed download, store the transcript and emit "transcription-ready"
+fn transcribe<R: Runtime>(app: &AppHandle<R>, db: &Database, id: &str) -> Result<String, String> {
+    let config = load(db);
+    if config.command.trim().is_empty() {
+        return Err("No transcriber is configured".to_string());
+    }
+    let record = db
+        .get_download(id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Unknown download")?;
+    if !is_audio(&record) {
+        return Err("Only audio attachments can be transcribed".to_string());
+    }
+    let path = match (&record.status[..], &record.path) {
+        ("complete", Some(path)) => path.clone(),
+        _ => return Err("The attachment hasn't been downloaded".to_string()),
+    };
+
+    let _running = WORKER.lock().unwrap_or_else(|e| e.into_inner());
+    let transcript = run(&config, Path::new(&path))?;
+    db.set_download_transcript(id, &transcript)
+        .map_err(|e| e.to_string())?;
+    info!(
+        "Transcribed {} ({} chars)",
+        record.file_name,
+        transcript.len()
+    );
+    let _ = app.emit(
+        "transcription-ready",
+        TranscriptionReady {
+            download_id: id,
+            message_id: record.message_id.as_deref(),
+            transcript: &transcript,
+        },
+    );
+    Ok(transcript)
+}
+
+/// Called once a download completes: audio is transcribed in the background when enabled
+pub fn queue<R: Runtime>(app: &AppHandle<R>, db: &Database, id: &str) {
+    let config = load(db);
+    if !config.enabled || config.command.trim().is_empty() {
+        return;
+    }
+    let Some(record) = db.get_download(id).ok().flatten() else {
+        return;
+    };
+    if !is_audio(&record) || record.transcript.is_some() {
+        return;
+    }
+    let app = app.clone();
+    let id = id.to_string();
+    std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        if let Err(e) = transcribe(&app, &state.db, &id) {
+            warn!("Transcribing {} failed: {}", id, e);
+        }
+    });
+}
+
+/// Turn what the user typed into an FTS5 query: every word must appear, as a prefix
+fn match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+// ============ COMMANDS ============
+
+/// Transcribe an audio attachment now (or again); returns the transcript
+#[tauri::command]
+pub async fn transcribe_attachment<R: Runtime>(
+    app: AppHandle<R>,
+    download_id: String,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        transcribe(&app, &state.db, &download_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Search transcripts and other text pulled out of attachments
+#[tauri::command]
+pub fn search_attachment_text(
+    state: State<AppState>,
+    query: String,
+    limit: Option<i64>,
+) -> Result<Vec<AttachmentTextHit>, String> {
+    let Some(query) = match_query(&query) else {
+        return Ok(Vec::new());
+    };
+    state
+        .db
+        .search_attachment_text(&query, limit.unwrap_or(50).clamp(1, MAX_SEARCH_RESULTS))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_transcription_config(state: State<AppState>) -> SttConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_transcription_config(state: State<AppState>, config: SttConfig) -> Result<(), String> {
+    if config.enabled && config.command.trim().is_empty() {
+        return Err("No transcriber command given".to_string());
+    }
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("Voice transcription enabled: {}", config.enabled);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::downloads;
+
+    #[test]
+    fn test_clean_output_strips_timestamps() {
+        let out = "\n[00:00:00.000 --> 00:00:02.500]   Hello there.\n[00:00:02.500 --> 00:00:04.000]  See you at noon.\n";
+        assert_eq!(clean_output(out), "Hello there. See you at noon.");
+        assert_eq!(clean_output("plain text\n"), "plain text");
+        assert_eq!(
+            match_query(" noon  \"x "),
+            Some("\"noon\"* \"\"\"x\"*".to_string())
+        );
+        assert_eq!(match_query("  "), None);
+    }
+
+    #[test]
+    fn test_transcript_is_searchable() {
+        let db = Database::new_in_memory().unwrap();
+        let id = downloads::begin(
+            &db,
+            None,
+            downloads::Source {
+                kind: "auto",
+                message_id: Some("m1"),
+                file_name: "voice.ogg",
+                file_type: "file",
+                ..Default::default()
+            },
+        );
+        assert!(is_audio(&db.get_download(&id).unwrap().unwrap()));
+        db.set_download_transcript(&id, "meet at the loading dock")
+            .unwrap();
+        assert_eq!(
+            db.get_download(&id).unwrap().unwrap().transcript.as_deref(),
+            Some("meet at the loading dock")
+        );
+        let hits = db
+            .search_attachment_text(&match_query("load").unwrap(), 10)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_id.as_deref(), Some("m1"));
+        assert!(db
+            .search_attachment_text(&match_query("parking").unwrap(), 10)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+// src-tauri/src/translation.rs
+// Message translation hook: translate_message sends a text message to a configured backend,
+// either a LibreTranslate server (often a local one) or an external command, and caches the
+// result per message and target language. Nothing is translated until a backend is configured;
+// message text leaves the app only for the backend the user chose.
+
+use crate::commands::AppState;
+use crate::db::{now, Database, MessageTranslation};
+use crate::external_tool::Tool;
+use crate::http_client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "translation";
+/// Stands for the target language in `args`
+const TARGET_PLACEHOLDER: &str = "{target}";
+const MAX_TEXT_CHARS: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    None,
+    /// POST {url}/translate with a LibreTranslate-style body
+    LibreTranslate,
+    /// Run `command` with `args`; the text goes in on stdin, the translation comes out on stdout
+    Command,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranslationConfig {
+    pub backend: Backend,
+    /// LibreTranslate base URL, e.g. "http://localhost:5000"
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Translator executable, e.g. "trans" (translate-shell) or "argos-translate"
+    #[serde(default)]
+    pub command: String,
+    /// e.g. ["-b", ":{target}"]
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+impl Default for TranslationConfig {
+    fn default() -> Self {
+        TranslationConfig {
+            backend: Backend::None,
+            url: String::new(),
+            api_key: None,
+            command: String::new(),
+            args: Vec::new(),
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+/// A translation next to the text it came from
+#[derive(Debug, Clone, Serialize)]
+pub struct TranslatedMessage {
+    pub message_id: String,
+    pub original: String,
+    pub translated: String,
+    pub target_lang: String,
+    pub source_lang: Option<String>,
+    /// Served from the cache rather than the backend
+    pub cached: bool,
+}
+
+pub fn load(db: &Database) -> TranslationConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+/// Language code like "de", "pt-BR" or "zh-Hant"
+fn normalize_lang(lang: &str) -> Result<String, String> {
+    let lang = lang.trim();
+    let valid = (2..=10).contains(&lang.len())
+        && lang.chars().all(|c| c.is_ascii_alphabetic() || c == '-')
+        && !lang.starts_with('-');
+    if !valid {
+        return Err(format!("Invalid language code: {}", lang));
+    }
+    Ok(lang.to_string())
+}
+
+fn build_args(config: &TranslationConfig, target: &str) -> Vec<String> {
+    config
+        .args
+        .iter()
+        .map(|a| a.replace(TARGET_PLACEHOLDER, target))
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct LibreDetected {
+    language: String,
+}
+
+#[derive(Deserialize)]
+struct LibreResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+    #[serde(rename = "detectedLanguage")]
+    detected_language: Option<LibreDetected>,
+}
+
+fn translate_http(
+    config: &TranslationConfig,
+    text: &str,
+    target: &str,
+) -> Result<(String, Option<String>), String> {
+    let url = format!("{}/translate", config.url.trim_end_matches('/'));
+    let body = serde_json::json!({
+        "q": text,
+        "source": "auto",
+        "target": target,
+        "format": "text",
+        "api_key": config.api_key,
+    });
+    let response = http_client::client()
+        .post(&url)
+        .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .map_err(|e| format!("Translation server unreachable: {}", e))?;
+    let status = response.status();
+    let text = response.text().map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        // LibreTranslate explains itself in {"error": "..."}
+        let detail = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|v| v["error"].as_str().map(String::from))
+            .unwrap_or_default();
+        return Err(format!("Translation server returned {} {}", status, detail)
+            .trim()
+            .to_string());
+    }
+    let parsed: LibreResponse = serde_json::from_str(&text)
+        .map_err(|e| format!("Unexpected translation server response: {}", e))?;
+    Ok((
+        parsed.translated_text,
+        parsed.detected_language.map(|d| d.language),
+    ))
+}
+
+fn translate_command(
+    config: &TranslationConfig,
+    text: &str,
+    target: &str,
+) -> Result<(String, Option<String>), String> {
+    let output = Tool {
+        name: "Translator",
+        program: &config.command,
+        args: &build_args(config, target),
+        timeout_secs: config.timeout_secs,
+    }
+    .output(Some(text))?;
+    let translated = output.trim().to_string();
+    if translated.is_empty() {
+        return Err("Translator returned nothing".to_string());
+    }
+    Ok((translated, None))
+}
+
+/// Translate a stored text message into `target_lang`, from the cache when it's been done before
+pub fn translate(
+    db: &Database,
+    message_id: &str,
+    target_lang: &str,
+) -> Result<TranslatedMessage, String> {
+    let target = normalize_lang(target_lang)?;
+    let (original, message_type) = db
+        .get_message_text(message_id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Message not found")?;
+    if message_type != "text" {
+        return Err("Only text messages can be translated".to_string());
+    }
+
+    if let Some(cached) = db
+        .get_translation(message_id, &target)
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(TranslatedMessage {
+            message_id: message_id.to_string(),
+            original,
+            translated: cached.text,
+            target_lang: target,
+            source_lang: cached.source_lang,
+            cached: true,
+        });
+    }
+
+    if original.chars().count() > MAX_TEXT_CHARS {
+        return Err(format!(
+            "Messages over {} characters aren't translated",
+            MAX_TEXT_CHARS
+        ));
+    }
+    let config = load(db);
+    let (translated, source_lang) = match config.backend {
+        Backend::None => return Err("No translation backend is configured".to_string()),
+        Backend::LibreTranslate => translate_http(&config, &original, &target)?,
+        Backend::Command => translate_command(&config, &original, &target)?,
+    };
+    let record = MessageTranslation {
+        message_id: message_id.to_string(),
+        lang: target.clone(),
+        text: translated.clone(),
+        source_lang: source_lang.clone(),
+        created_at: now(),
+    };
+    if let Err(e) = db.save_translation(&record) {
+        warn!("Caching translation of {} failed: {}", message_id, e);
+    }
+    Ok(TranslatedMessage {
+        message_id: message_id.to_string(),
+        original,
+        translated,
+        target_lang: target,
+        source_lang,
+        cached: false,
+    })
+}
+
+// ============ COMMANDS ============
+
+/// Translate a direct or group text message; returns the translation with the original
+#[tauri::command]
+pub async fn translate_message<R: Runtime>(
+    app: AppHandle<R>,
+    message_id: String,
+    target_lang: String,
+) -> Result<TranslatedMessage, String> {
+    // The backend can take seconds; keep it off the async runtime's workers
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        translate(&state.db, &message_id, &target_lang)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_translation_config(state: State<AppState>) -> TranslationConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_translation_config(
+    state: State<AppState>,
+    config: TranslationConfig,
+) -> Result<(), String> {
+    match config.backend {
+        Backend::LibreTranslate
+            if !(config.url.starts_with("http://") || config.url.starts_with("https://")) =>
+        {
+            return Err("Translation server URL must start with http:// or https://".to_string())
+        }
+        Backend::Command if config.command.trim().is_empty() => {
+            return Err("No translator command given".to_string())
+        }
+        _ => {}
+    }
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    let previous = load(&state.db);
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    // A different backend may translate differently; don't keep serving the old results
+    if previous.backend != config.backend
+        || previous.url != config.url
+        || previous.command != config.command
+        || previous.args != config.args
+    {
+        let _ = state.db.clear_translations();
+    }
+    info!("Translation backend: {:?}", config.backend);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_lang_and_args() {
+        assert_eq!(normalize_lang(" pt-BR ").unwrap(), "pt-BR");
+        assert!(normalize_lang("d").is_err());
+        assert!(normalize_lang("de; rm").is_err());
+
+        let config = TranslationConfig {
+            backend: Backend::Command,
+            command: "trans".to_string(),
+            args: vec!["-b".to_string(), ":{target}".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(build_args(&config, "fr"), vec!["-b", ":fr"]);
+    }
+
+    #[test]
+    fn test_translate_uses_cache() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("alice", "Alice", None).unwrap();
+        db.upsert_peer_as_user("bob", "Bob", None).unwrap();
+        db.import_messages(&[crate::db::Message {
+            id: "m1".to_string(),
+            sender_id: "alice".to_string(),
+            receiver_id: "bob".to_string(),
+            content: "Hallo".to_string(),
+            message_type: "text".to_string(),
+            file_path: None,
+            is_read: false,
+            is_delivered: true,
+            created_at: now(),
+            lamport: 0,
+            status: crate::delivery_status::MessageStatus::Delivered,
+        }])
+        .unwrap();
+        assert!(translate(&db, "missing", "en").is_err());
+        // No backend configured
+        assert!(translate(&db, "m1", "en").is_err());
+
+        db.save_translation(&MessageTranslation {
+            message_id: "m1".to_string(),
+            lang: "en".to_string(),
+            text: "Hello".to_string(),
+            source_lang: Some("de".to_string()),
+            created_at: now(),
+        })
+        .unwrap();
+        let result = translate(&db, "m1", "en").unwrap();
+        assert!(result.cached);
+        assert_eq!(
+            (result.original.as_str(), result.translated.as_str()),
+            ("Hallo", "Hello")
+        );
+
+        // Deleted messages aren't translated, and clearing the chat drops their translations
+        db.delete_message("m1").unwrap();
+        assert!(translate(&db, "m1", "en").is_err());
+        db.delete_all_messages_with_peer("bob", "alice").unwrap();
+        assert!(db.get_translation("m1", "en").unwrap().is_none());
+    }
+}
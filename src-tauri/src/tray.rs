@@ -1,12 +1,14 @@
 // src-tauri/src/tray.rs
 // System Tray handling for Pingo
 
+use crate::window_manager;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{
     menu::{Menu, MenuItem, PredefinedMenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     AppHandle, Emitter, Manager, Runtime,
 };
+use tracing::info;
 
 // Global state for notification mute
 pub static NOTIFICATIONS_MUTED: AtomicBool = AtomicBool::new(false);
@@ -15,12 +17,23 @@ pub static NOTIFICATIONS_MUTED: AtomicBool = AtomicBool::new(false);
 pub fn init_tray<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
     // Create menu items
     let open_item = MenuItem::with_id(app, "open", "Open Pingo", true, None::<&str>)?;
+    let quick_reply_item =
+        MenuItem::with_id(app, "quick_reply", "Quick Reply", true, None::<&str>)?;
     let mute_item = MenuItem::with_id(app, "mute", "Mute Notifications", true, None::<&str>)?;
     let separator = PredefinedMenuItem::separator(app)?;
     let exit_item = MenuItem::with_id(app, "exit", "Exit", true, None::<&str>)?;
 
     // Build menu
-    let menu = Menu::with_items(app, &[&open_item, &mute_item, &separator, &exit_item])?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &open_item,
+            &quick_reply_item,
+            &mute_item,
+            &separator,
+            &exit_item,
+        ],
+    )?;
 
     // Build tray icon - keep it alive by assigning to a name without underscore
     let _tray_icon = TrayIconBuilder::new()
@@ -36,6 +49,18 @@ pub fn init_tray<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::erro
                         let _ = window.set_focus();
                     }
                 }
+                "quick_reply" => {
+                    // Reply to whoever messaged last; fall back to the main window
+                    let opened = window_manager::last_incoming_peer()
+                        .map(|peer_id| window_manager::open_quick_reply(app, &peer_id).is_ok())
+                        .unwrap_or(false);
+                    if !opened {
+                        if let Some(window) = app.get_webview_window("main") {
+                            let _ = window.show();
+                            let _ = window.set_focus();
+                        }
+                    }
+                }
                 "mute" => {
                     let current = NOTIFICATIONS_MUTED.load(Ordering::SeqCst);
                     NOTIFICATIONS_MUTED.store(!current, Ordering::SeqCst);
@@ -64,7 +89,7 @@ pub fn init_tray<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::erro
         })
         .build(app)?;
 
-    println!("System tray initialized successfully");
+    info!("System tray initialized successfully");
     Ok(())
 }
 
@@ -0,0 +1,298 @@
+// src-tauri/src/trust.rs
+// Trusted peers (peers.is_trusted), set by QR pairing or by hand. Trust relaxes what the app
+// otherwise asks about: a trusted peer's files skip download approval, its encryption session
+// is set up as soon as it shows up, and group invitations can be limited to trusted peers.
+// The UI lists trusted peers in a "Verified" section. Trust is given to a device id together
+// with its identity key, so it survives session key rotation; a device that shows up under a
+// trusted id with another identity key (or none) loses the trust instead of inheriting it. Peers
+// that don't announce an identity key (older versions) can't be trusted.
+
+use crate::commands::AppState;
+use crate::crypto::{generate_checksum, CryptoManager};
+use crate::db::Database;
+use crate::discovery::PeerInfo;
+use crate::download_policy::PolicyAction;
+use crate::pairing;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "trust_policy";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrustPolicy {
+    /// Download a trusted peer's files without asking (blocked kinds stay blocked)
+    pub auto_accept_files: bool,
+    /// Establish the encryption session when a trusted peer is discovered
+    pub auto_sessions: bool,
+    /// Ignore group invitations from peers that aren't trusted
+    pub group_invites_trusted_only: bool,
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        TrustPolicy {
+            auto_accept_files: true,
+            auto_sessions: true,
+            group_invites_trusted_only: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrustState {
+    pub device_id: String,
+    pub trusted: bool,
+    /// Fingerprint of the key we know for the peer, to compare out of band
+    pub fingerprint: Option<String>,
+    pub has_session: bool,
+}
+
+pub fn load(db: &Database) -> TrustPolicy {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+pub fn is_trusted(db: &Database, device_id: &str) -> bool {
+    db.is_peer_trusted(device_id).unwrap_or(false)
+}
+
+/// Download policy decision for a file from `sender`, with trust applied
+pub fn download_action(db: &Database, sender: Option<&str>, action: PolicyAction) -> PolicyAction {
+    let trusted_sender = sender.is_some_and(|id| is_trusted(db, id));
+    if action == PolicyAction::Ask && trusted_sender && load(db).auto_accept_files {
+        PolicyAction::Auto
+    } else {
+        action
+    }
+}
+
+pub fn accepts_group_invite(db: &Database, from: &str) -> bool {
+    !load(db).group_invites_trusted_only || is_trusted(db, from)
+}
+
+/// The key trust in `peer` is tied to: its identity key, None when it doesn't announce one
+pub fn trust_key(peer: &PeerInfo) -> Option<&str> {
+    peer.identity.as_ref().map(|i| i.identity_key.as_str())
+}
+
+/// Check a peer that just showed up against the key it was trusted with (dropping the trust
+/// when it differs), and set up the encryption session when it's still trusted
+pub fn on_peer_seen(db: &Database, crypto: &CryptoManager, peer: &PeerInfo) {
+    let device_id = peer.device_id.as_str();
+    let Some(trusted_key) = db.get_trusted_key(device_id).ok().flatten() else {
+        return;
+    };
+    let matches = trust_key(peer)
+        .is_some_and(|k| trusted_key.eq_ignore_ascii_case(&generate_checksum(k.as_bytes())));
+    if !matches {
+        warn!(
+            "{} shows up with another key; it's no longer trusted",
+            device_id
+        );
+        if let Err(e) = db.set_peer_trusted(device_id, None) {
+            warn!("Failed to drop trust in {}: {}", device_id, e);
+        }
+        return;
+    }
+    let public_key = peer.public_key.as_str();
+    if crypto.has_session_with(device_id, public_key) || !load(db).auto_sessions {
+        return;
+    }
+    match crypto.establish_session(device_id, public_key) {
+        Ok(()) => info!("Established session with trusted peer {}", device_id),
+        Err(e) => warn!("Session with trusted peer {} failed: {}", device_id, e),
+    }
+}
+
+/// The key we'd trust: what the peer announces now, else what we stored for it
+fn known_key(state: &AppState, device_id: &str) -> Option<String> {
+    state
+        .discovery
+        .get_peer(device_id)
+        .map(|p| p.public_key)
+        .or_else(|| {
+            state
+                .db
+                .get_user(device_id)
+                .ok()
+                .flatten()
+                .and_then(|u| u.public_key)
+        })
+        .filter(|k| !k.is_empty())
+}
+
+/// The identity key the peer announces now (checked against its pin by discovery)
+fn identity_key(state: &AppState, device_id: &str) -> Option<String> {
+    state
+        .discovery
+        .get_peer(device_id)
+        .and_then(|p| p.identity)
+        .map(|i| i.identity_key)
+}
+
+/// The key to fingerprint: the peer's identity key when it announces one, since its session
+/// key rotates
+fn fingerprint_key(state: &AppState, device_id: &str) -> Option<String> {
+    identity_key(state, device_id).or_else(|| known_key(state, device_id))
+}
+
+fn trust_state(state: &AppState, device_id: &str) -> TrustState {
+    TrustState {
+        device_id: device_id.to_string(),
+        trusted: is_trusted(&state.db, device_id),
+        fingerprint: fingerprint_key(state, device_id).map(|k| pairing::fingerprint(&k)),
+        has_session: state.crypto.has_session(device_id),
+    }
+}
+
+/// Trust `peer_id` as the holder of identity key `key` (the key the user checked), or drop the
+/// trust when None. Only the key's checksum is kept; the trust ends when the peer shows up with another key.
+pub(crate) fn set_trusted<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    peer_id: &str,
+    key: Option<&str>,
+) -> Result<TrustState, String> {
+    let checksum = key.map(|k| generate_checksum(k.as_bytes()));
+    state
+        .db
+        .set_peer_trusted(peer_id, checksum.as_deref())
+        .map_err(|e| e.to_string())?;
+    if let Some(peer) = state.discovery.get_peer(peer_id) {
+        on_peer_seen(&state.db, &state.crypto, &peer);
+    }
+    let result = trust_state(state, peer_id);
+    let _ = app.emit("trust-changed", &result);
+    Ok(result)
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn trust_peer<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+) -> Result<TrustState, String> {
+    let key = identity_key(&state, &peer_id)
+        .ok_or("This peer doesn't announce an identity key, so it can't be trusted")?;
+    set_trusted(&app, &state, &peer_id, Some(&key))
+}
+
+#[tauri::command]
+pub fn untrust_peer<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+) -> Result<TrustState, String> {
+    set_trusted(&app, &state, &peer_id, None)
+}
+
+#[tauri::command]
+pub fn get_trust_state(state: State<AppState>, peer_id: String) -> TrustState {
+    trust_state(&state, &peer_id)
+}
+
+/// Device ids of every trusted peer, for the "Verified" section
+#[tauri::command]
+pub fn get_trusted_peers(state: State<AppState>) -> Result<Vec<String>, String> {
+    state.db.get_trusted_peer_ids().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_trust_policy(state: State<AppState>) -> TrustPolicy {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_trust_policy(state: State<AppState>, policy: TrustPolicy) -> Result<(), String> {
+    let json = serde_json::to_string(&policy).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_survives_caching_and_relaxes_approval() {
+        let db = Database::new_in_memory().unwrap();
+        db.upsert_peer_as_user("ana", "Ana", Some("key")).unwrap();
+        assert!(!is_trusted(&db, "ana"));
+        assert_eq!(
+            download_action(&db, Some("ana"), PolicyAction::Ask),
+            PolicyAction::Ask
+        );
+
+        // Trusted before ever being cached, then cached: the flag stays
+        let checksum = generate_checksum(b"key");
+        db.set_peer_trusted("ana", Some(&checksum)).unwrap();
+        db.cache_peer("ana", "Ana", "10.0.0.2", 7001, Some("key"))
+            .unwrap();
+        assert!(is_trusted(&db, "ana"));
+        assert_eq!(db.get_trusted_peer_ids().unwrap(), vec!["ana"]);
+        assert_eq!(
+            download_action(&db, Some("ana"), PolicyAction::Ask),
+            PolicyAction::Auto
+        );
+        assert_eq!(
+            download_action(&db, Some("ana"), PolicyAction::Never),
+            PolicyAction::Never
+        );
+
+        db.set_setting(
+            SETTING_KEY,
+            r#"{"auto_accept_files":true,"auto_sessions":false,"group_invites_trusted_only":true}"#,
+        )
+        .unwrap();
+        assert!(accepts_group_invite(&db, "ana"));
+        assert!(!accepts_group_invite(&db, "bob"));
+
+        // Trust follows the identity key: a rotated session key keeps it, no identity or
+        // another identity drops it
+        let ana = CryptoManager::new();
+        ana.set_device_id("ana");
+        let identity_key = ana.generate_identity();
+        let signed = |public_key: String| -> PeerInfo {
+            serde_json::from_value(serde_json::json!({
+                "device_id": "ana", "username": "Ana", "ip_address": "10.0.0.2", "port": 7001,
+                "public_key": public_key, "is_online": true, "identity": ana.identity_proof(),
+            }))
+            .unwrap()
+        };
+        let crypto = CryptoManager::new();
+        db.set_peer_trusted("ana", Some(&generate_checksum(identity_key.as_bytes())))
+            .unwrap();
+        let peer = signed(ana.generate_keypair());
+        on_peer_seen(&db, &crypto, &peer);
+        assert!(is_trusted(&db, "ana"));
+        on_peer_seen(&db, &crypto, &signed(ana.generate_keypair()));
+        assert!(is_trusted(&db, "ana"));
+        on_peer_seen(
+            &db,
+            &crypto,
+            &PeerInfo {
+                identity: None,
+                ..peer.clone()
+            },
+        );
+        assert!(!is_trusted(&db, "ana"));
+
+        // Trust given to a session key (older versions) doesn't survive either
+        db.set_peer_trusted("ana", Some(&generate_checksum(peer.public_key.as_bytes())))
+            .unwrap();
+        on_peer_seen(&db, &crypto, &peer);
+        assert!(!is_trusted(&db, "ana"));
+        assert_eq!(
+            download_action(&db, Some("ana"), PolicyAction::Ask),
+            PolicyAction::Ask
+        );
+    }
+}
@@ -0,0 +1,501 @@
+// src-tauri/src/video_prepare.rs
+// Video preparation before sending: prepare_video_for_send runs ffmpeg to scale a video down
+// to a preset (1080p / 720p / 480p, H.264 + AAC) and optionally trim it, writing the result next
+// to the files we serve. The webview then sends the prepared file like any other, which moves
+// it out of prepared/ (see take_prepared); cancelling drops it. ffmpeg is the one shipped next
+// to the app when there is one, else the configured or PATH binary.
+// Progress goes out as "video-prepare-progress".
+
+use crate::commands::AppState;
+use crate::db::{generate_id, Database};
+use crate::file_server;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tracing::{info, warn};
+
+const SETTING_KEY: &str = "video_transcode";
+#[cfg(windows)]
+const FFMPEG_NAME: &str = "ffmpeg.exe";
+#[cfg(not(windows))]
+const FFMPEG_NAME: &str = "ffmpeg";
+
+// job id -> cancel flag, for the jobs still running
+static JOBS: Mutex<Option<HashMap<String, Arc<AtomicBool>>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranscodeConfig {
+    /// ffmpeg to use; empty means the bundled one, else "ffmpeg" on PATH
+    #[serde(default)]
+    pub ffmpeg_path: String,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    1800
+}
+
+impl Default for TranscodeConfig {
+    fn default() -> Self {
+        TranscodeConfig {
+            ffmpeg_path: String::new(),
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Preset {
+    #[serde(rename = "1080p")]
+    P1080,
+    #[serde(rename = "720p")]
+    P720,
+    #[serde(rename = "480p")]
+    P480,
+}
+
+impl Preset {
+    /// (short side, CRF, audio bitrate)
+    fn params(self) -> (u32, u32, &'static str) {
+        match self {
+            Preset::P1080 => (1080, 26, "160k"),
+            Preset::P720 => (720, 28, "128k"),
+            Preset::P480 => (480, 30, "96k"),
+        }
+    }
+}
+
+/// Seconds to cut from the start, and where to stop (also in seconds of the original)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Trim {
+    #[serde(default)]
+    pub start: Option<f64>,
+    #[serde(default)]
+    pub end: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreparedVideo {
+    pub job_id: String,
+    pub path: String,
+    pub size: u64,
+    pub original_size: u64,
+    /// Length of the prepared video in seconds, when ffmpeg reported it
+    pub duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Progress<'a> {
+    job_id: &'a str,
+    source: &'a str,
+    /// 0-100; None while the length isn't known yet
+    percent: Option<u32>,
+    stage: &'a str,
+}
+
+pub fn load(db: &Database) -> TranscodeConfig {
+    db.get_setting(SETTING_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+/// The configured ffmpeg, else one shipped next to the executable, else PATH
+fn ffmpeg_binary(config: &TranscodeConfig) -> PathBuf {
+    if !config.ffmpeg_path.trim().is_empty() {
+        return PathBuf::from(config.ffmpeg_path.trim());
+    }
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(FFMPEG_NAME)))
+        .filter(|bundled| bundled.is_file())
+        .unwrap_or_else(|| PathBuf::from(FFMPEG_NAME))
+}
+
+fn validate_trim(trim: &Trim) -> Result<(), String> {
+    let start = trim.start.unwrap_or(0.0);
+    if !start.is_finite() || start < 0.0 {
+        return Err("Trim start must be zero or more seconds".to_string());
+    }
+    if let Some(end) = trim.end {
+        if !end.is_finite() || end <= start {
+            return Err("Trim end must come after the start".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn build_args(input: &Path, output: &Path, preset: Preset, trim: &Trim) -> Vec<String> {
+    let (side, crf, audio) = preset.params();
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-y".into()];
+    if let Some(start) = trim.start.filter(|s| *s > 0.0) {
+        args.extend(["-ss".into(), format!("{:.3}", start)]);
+    }
+    args.extend(["-i".into(), input.to_string_lossy().into_owned()]);
+    if let Some(end) = trim.end {
+        let length = end - trim.start.unwrap_or(0.0);
+        args.extend(["-t".into(), format!("{:.3}", length)]);
+    }
+    // Cap the short side, so portrait phone videos shrink as much as landscape ones
+    let scale = format!(
+        "scale='if(gt(iw,ih),-2,min({s},iw))':'if(gt(iw,ih),min({s},ih),-2)'",
+        s = side
+    );
+    args.extend([
+        "-vf".into(),
+        scale,
+        "-c:v".into(),
+        "libx264".into(),
+        "-preset".into(),
+        "veryfast".into(),
+        "-crf".into(),
+        crf.to_string(),
+        "-pix_fmt".into(),
+        "yuv420p".into(),
+        "-c:a".into(),
+        "aac".into(),
+        "-b:a".into(),
+        audio.into(),
+        "-movflags".into(),
+        "+faststart".into(),
+        "-progress".into(),
+        "pipe:1".into(),
+        "-nostats".into(),
+        output.to_string_lossy().into_owned(),
+    ]);
+    args
+}
+
+/// "  Duration: 00:01:23.45, start: ..." from ffmpeg's input summary, in seconds
+fn parse_duration(line: &str) -> Option<f64> {
+    let rest = line.trim().strip_prefix("Duration:")?;
+    parse_timestamp(rest.split(',').next()?.trim())
+}
+
+fn parse_timestamp(stamp: &str) -> Option<f64> {
+    let mut parts = stamp.split(':');
+    let h: f64 = parts.next()?.parse().ok()?;
+    let m: f64 = parts.next()?.parse().ok()?;
+    let s: f64 = parts.next()?.parse().ok()?;
+    Some(h * 3600.0 + m * 60.0 + s)
+}
+
+/// Seconds written so far, from a "-progress" line ("out_time_us=12345678")
+fn parse_progress(line: &str) -> Option<f64> {
+    let (key, value) = line.trim().split_once('=')?;
+    match key {
+        "out_time_us" | "out_time_ms" => value.parse::<f64>().ok().map(|us| us / 1_000_000.0),
+        _ => None,
+    }
+}
+
+fn percent(done: f64, total: Option<f64>) -> Option<u32> {
+    let total = total.filter(|t| *t > 0.0)?;
+    Some(((done / total) * 100.0).clamp(0.0, 99.0) as u32)
+}
+
+fn prepared_root(state: &AppState) -> PathBuf {
+    state.file_server.get_storage_dir().join("prepared")
+}
+
+fn prepared_dir(state: &AppState, job_id: &str) -> PathBuf {
+    prepared_root(state).join(job_id)
+}
+
+/// When `path` is a prepared video, move it next to the other files we send (named after
+/// `transfer_id`) and drop its job folder; any other path comes back as is
+pub fn take_prepared(state: &AppState, path: &Path, transfer_id: &str) -> Result<PathBuf, String> {
+    let Some(dir) = path.parent() else {
+        return Ok(path.to_path_buf());
+    };
+    if dir.parent() != Some(prepared_root(state).as_path()) {
+        return Ok(path.to_path_buf());
+    }
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("mp4");
+    let sent = state
+        .file_server
+        .get_storage_dir()
+        .join(format!("{}.{}", transfer_id, ext));
+    std::fs::rename(path, &sent).map_err(|e| format!("Can't move {}: {}", path.display(), e))?;
+    let _ = std::fs::remove_dir_all(dir);
+    Ok(sent)
+}
+
+/// Prepared videos still lying around were never sent; no job outlives the app
+pub fn clear_leftovers(state: &AppState) {
+    let root = prepared_root(state);
+    if root.is_dir() {
+        match std::fs::remove_dir_all(&root) {
+            Ok(()) => info!("Removed unsent prepared videos"),
+            Err(e) => warn!("Can't remove {}: {}", root.display(), e),
+        }
+    }
+}
+
+fn run<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    job_id: &str,
+    cancel: &AtomicBool,
+    source: &Path,
+    preset: Preset,
+    trim: &Trim,
+) -> Result<PreparedVideo, String> {
+    let original_size = std::fs::metadata(source)
+        .map_err(|e| format!("Can't read {}: {}", source.display(), e))?
+        .len();
+    let config = load(&state.db);
+    let dir = prepared_dir(state, job_id);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // Keep the name: it's what the receiver sees
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "video".to_string());
+    let output = dir.join(format!("{}.mp4", stem));
+
+    let mut child = Command::new(ffmpeg_binary(&config))
+        .args(build_args(source, &output, preset, trim))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("ffmpeg could not be started: {}", e))?;
+
+    // The input summary on stderr has the length; -progress on stdout has how far along it is.
+    // Both are read on the side so neither pipe can fill up and stall ffmpeg.
+    let source_duration = Arc::new(Mutex::new(None::<f64>));
+    let last_error = Arc::new(Mutex::new(String::new()));
+    let stderr = child.stderr.take();
+    let (duration_slot, error_slot) = (source_duration.clone(), last_error.clone());
+    std::thread::spawn(move || {
+        let Some(stderr) = stderr else {
+            return;
+        };
+        // Line by line, so the length is known as soon as the input summary is out
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if let Some(d) = parse_duration(&line) {
+                duration_slot.lock().unwrap().get_or_insert(d);
+            }
+            let line = line.trim();
+            if !line.is_empty() {
+                *error_slot.lock().unwrap() = line.to_string();
+            }
+        }
+    });
+    let written = Arc::new(Mutex::new(0.0f64));
+    let written_slot = written.clone();
+    let stdout = child.stdout.take();
+    std::thread::spawn(move || {
+        let Some(stdout) = stdout else {
+            return;
+        };
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(t) = parse_progress(&line) {
+                *written_slot.lock().unwrap() = t;
+            }
+        }
+    });
+
+    let source_name = source.to_string_lossy();
+    let emit = |percent: Option<u32>, stage: &str| {
+        let _ = app.emit(
+            "video-prepare-progress",
+            Progress {
+                job_id,
+                source: &source_name,
+                percent,
+                stage,
+            },
+        );
+    };
+    emit(Some(0), "started");
+
+    let deadline = Instant::now() + Duration::from_secs(config.timeout_secs.max(1));
+    let mut last_percent = None;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if cancel.load(Ordering::Relaxed) || Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                let _ = std::fs::remove_dir_all(&dir);
+                let cancelled = cancel.load(Ordering::Relaxed);
+                emit(None, if cancelled { "cancelled" } else { "failed" });
+                return Err(if cancelled {
+                    "Cancelled".to_string()
+                } else {
+                    "ffmpeg timed out".to_string()
+                });
+            }
+            Ok(None) => {
+                let total = match (trim.end, *source_duration.lock().unwrap()) {
+                    (Some(end), Some(d)) => Some(end.min(d) - trim.start.unwrap_or(0.0)),
+                    (Some(end), None) => Some(end - trim.start.unwrap_or(0.0)),
+                    (None, d) => d.map(|d| d - trim.start.unwrap_or(0.0)),
+                };
+                let now = percent(*written.lock().unwrap(), total);
+                if now != last_percent {
+                    last_percent = now;
+                    emit(now, "encoding");
+                }
+                std::thread::sleep(Duration::from_millis(250));
+            }
+            Err(e) => return Err(format!("ffmpeg failed: {}", e)),
+        }
+    };
+    if !status.success() {
+        let _ = std::fs::remove_dir_all(&dir);
+        emit(None, "failed");
+        let detail = last_error.lock().unwrap().clone();
+        return Err(match status.code() {
+            Some(code) if detail.is_empty() => format!("ffmpeg exited with code {}", code),
+            Some(code) => format!("ffmpeg exited with code {}: {}", code, detail),
+            None => "ffmpeg was terminated".to_string(),
+        });
+    }
+    let size = std::fs::metadata(&output)
+        .map_err(|e| format!("ffmpeg wrote no output: {}", e))?
+        .len();
+    let written = *written.lock().unwrap();
+    emit(Some(100), "done");
+    info!(
+        "Prepared {} for sending: {} -> {} bytes",
+        source.display(),
+        original_size,
+        size
+    );
+    Ok(PreparedVideo {
+        job_id: job_id.to_string(),
+        path: output.to_string_lossy().into_owned(),
+        size,
+        original_size,
+        duration: (written > 0.0).then_some(written),
+    })
+}
+
+// ============ COMMANDS ============
+
+/// Scale down and optionally trim a video with ffmpeg before it's sent; `preset` is "1080p",
+/// "720p" or "480p". Returns the prepared file, which the webview then sends like any file.
+#[tauri::command]
+pub async fn prepare_video_for_send<R: Runtime>(
+    app: AppHandle<R>,
+    path: String,
+    preset: Preset,
+    trim: Option<Trim>,
+) -> Result<PreparedVideo, String> {
+    let trim = trim.unwrap_or_default();
+    validate_trim(&trim)?;
+    let source = PathBuf::from(&path);
+    if !source.is_file() {
+        return Err(format!("Not a file: {}", path));
+    }
+    let job_id = generate_id();
+    let cancel = Arc::new(AtomicBool::new(false));
+    JOBS.lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(job_id.clone(), cancel.clone());
+
+    let job = job_id.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        run(&app, &state, &job, &cancel, &source, preset, &trim)
+    })
+    .await
+    .map_err(|e| e.to_string());
+    if let Some(jobs) = JOBS.lock().unwrap().as_mut() {
+        jobs.remove(&job_id);
+    }
+    let result = result?;
+    if let Err(e) = &result {
+        warn!("Preparing {} failed: {}", path, e);
+    }
+    result
+}
+
+/// Stop a prepare_video_for_send job, or drop the video it prepared when it already finished
+/// and won't be sent; returns false when there was nothing left to cancel
+#[tauri::command]
+pub fn cancel_video_prepare(state: State<AppState>, job_id: String) -> bool {
+    if let Some(cancel) = JOBS.lock().unwrap().as_ref().and_then(|j| j.get(&job_id)) {
+        cancel.store(true, Ordering::Relaxed);
+        return true;
+    }
+    if !file_server::is_plain_id(&job_id) {
+        return false;
+    }
+    std::fs::remove_dir_all(prepared_dir(&state, &job_id)).is_ok()
+}
+
+#[tauri::command]
+pub fn get_video_transcode_config(state: State<AppState>) -> TranscodeConfig {
+    load(&state.db)
+}
+
+#[tauri::command]
+pub fn set_video_transcode_config(
+    state: State<AppState>,
+    config: TranscodeConfig,
+) -> Result<(), String> {
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_setting(SETTING_KEY, &json)
+        .map_err(|e| e.to_string())?;
+    info!("ffmpeg: {}", ffmpeg_binary(&config).display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ffmpeg_output() {
+        assert_eq!(
+            parse_duration("  Duration: 00:01:23.50, start: 0.000000, bitrate: 17000 kb/s"),
+            Some(83.5)
+        );
+        assert_eq!(parse_duration("Stream #0:0: Video: h264"), None);
+        assert_eq!(parse_progress("out_time_us=12500000"), Some(12.5));
+        assert_eq!(parse_progress("progress=continue"), None);
+        assert_eq!(percent(12.5, Some(50.0)), Some(25));
+        assert_eq!(percent(60.0, Some(50.0)), Some(99));
+        assert_eq!(percent(1.0, None), None);
+    }
+
+    #[test]
+    fn test_trim_args() {
+        let trim = Trim {
+            start: Some(5.0),
+            end: Some(20.0),
+        };
+        assert!(validate_trim(&trim).is_ok());
+        assert!(validate_trim(&Trim {
+            start: Some(10.0),
+            end: Some(3.0)
+        })
+        .is_err());
+        let args = build_args(
+            Path::new("in.mov"),
+            Path::new("out.mp4"),
+            Preset::P720,
+            &trim,
+        );
+        let pos = |a: &str| args.iter().position(|x| x == a).unwrap();
+        assert!(pos("-ss") < pos("-i"));
+        assert_eq!(args[pos("-t") + 1], "15.000");
+        assert_eq!(args[pos("-crf") + 1], "28");
+        assert_eq!(args.last().unwrap(), "out.mp4");
+    }
+}
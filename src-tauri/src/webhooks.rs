@@ -0,0 +1,263 @@
+// src-tauri/src/webhooks.rs
+// Outgoing webhooks: POST incoming message events to user-configured URLs
+
+use crate::commands::AppState;
+use crate::db::{generate_id, now, Database, GroupMessage, Message, Webhook};
+use crate::http_client;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+use tracing::warn;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Delay before each retry; a delivery is attempted at most RETRY_DELAYS.len() + 1 times
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(2),
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+];
+
+/// What a message event carries for filter matching
+pub struct EventScope<'a> {
+    pub peer_id: &'a str,
+    pub group_id: Option<&'a str>,
+    pub message_type: &'a str,
+}
+
+#[derive(Deserialize)]
+pub struct WebhookInput {
+    pub id: Option<String>,
+    pub url: String,
+    pub peer_id: Option<String>,
+    pub group_id: Option<String>,
+    pub message_type: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct Payload<'a, T: Serialize> {
+    event: &'a str,
+    timestamp: String,
+    data: &'a T,
+}
+
+/// Empty filters match anything; a group filter never matches direct messages
+fn matches(hook: &Webhook, scope: &EventScope) -> bool {
+    fn accepts(filter: &Option<String>, value: Option<&str>) -> bool {
+        match filter.as_deref().filter(|f| !f.is_empty()) {
+            Some(f) => value == Some(f),
+            None => true,
+        }
+    }
+    hook.enabled
+        && accepts(&hook.peer_id, Some(scope.peer_id))
+        && accepts(&hook.group_id, scope.group_id)
+        && accepts(&hook.message_type, Some(scope.message_type))
+}
+
+/// POST once; Ok carries the HTTP status line recorded on the webhook
+fn post(url: &str, event: &str, body: &str) -> Result<String, String> {
+    let response = http_client::client()
+        .post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "Pingo-Webhook")
+        .header("X-Pingo-Event", event)
+        .body(body.to_string())
+        .send()
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.to_string())
+    } else {
+        Err(status.to_string())
+    }
+}
+
+fn deliver(db: &Database, hook: &Webhook, event: &str, body: &str) {
+    let mut result = post(&hook.url, event, body);
+    for delay in RETRY_DELAYS {
+        // 4xx means the endpoint rejected the payload; retrying won't help
+        if matches!(&result, Err(e) if !e.starts_with('4')) {
+            std::thread::sleep(delay);
+            result = post(&hook.url, event, body);
+        } else {
+            break;
+        }
+    }
+    let status = match result {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Webhook {} failed: {}", hook.url, e);
+            format!("error: {}", e)
+        }
+    };
+    let _ = db.set_webhook_result(&hook.id, &status);
+}
+
+/// Send `data` to every enabled webhook whose filters match, each on its own thread
+fn dispatch<T: Serialize>(db: &Arc<Database>, event: &str, scope: EventScope, data: &T) {
+    let hooks: Vec<Webhook> = match db.get_webhooks() {
+        Ok(hooks) => hooks.into_iter().filter(|h| matches(h, &scope)).collect(),
+        Err(_) => return,
+    };
+    if hooks.is_empty() {
+        return;
+    }
+    let body = match serde_json::to_string(&Payload {
+        event,
+        timestamp: now(),
+        data,
+    }) {
+        Ok(b) => b,
+        Err(_) => return,
+    };
+    for hook in hooks {
+        let db = Arc::clone(db);
+        let event = event.to_string();
+        let body = body.clone();
+        std::thread::spawn(move || deliver(&db, &hook, &event, &body));
+    }
+}
+
+pub fn message_received(db: &Arc<Database>, message: &Message) {
+    let scope = EventScope {
+        peer_id: &message.sender_id,
+        group_id: None,
+        message_type: &message.message_type,
+    };
+    dispatch(db, "message.received", scope, message);
+}
+
+pub fn group_message_received(db: &Arc<Database>, message: &GroupMessage) {
+    let scope = EventScope {
+        peer_id: &message.sender_id,
+        group_id: Some(&message.group_id),
+        message_type: &message.message_type,
+    };
+    dispatch(db, "group_message.received", scope, message);
+}
+
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn get_webhooks(state: State<AppState>) -> Result<Vec<Webhook>, String> {
+    state.db.get_webhooks().map_err(|e| e.to_string())
+}
+
+/// Create a webhook, or update it when `id` is set
+#[tauri::command]
+pub fn save_webhook(state: State<AppState>, input: WebhookInput) -> Result<Webhook, String> {
+    let url = input.url.trim().to_string();
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err("Webhook URL must start with http:// or https://".to_string());
+    }
+    let existing = match &input.id {
+        Some(id) => Some(
+            state
+                .db
+                .get_webhooks()
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .find(|w| &w.id == id)
+                .ok_or("Webhook not found")?,
+        ),
+        None => None,
+    };
+    let hook = Webhook {
+        id: input.id.unwrap_or_else(generate_id),
+        url,
+        peer_id: clean(input.peer_id),
+        group_id: clean(input.group_id),
+        message_type: clean(input.message_type),
+        enabled: input.enabled.unwrap_or(true),
+        created_at: existing
+            .as_ref()
+            .map(|w| w.created_at.clone())
+            .unwrap_or_else(now),
+        last_status: existing.as_ref().and_then(|w| w.last_status.clone()),
+        last_delivery_at: existing.and_then(|w| w.last_delivery_at),
+    };
+    state.db.save_webhook(&hook).map_err(|e| e.to_string())?;
+    Ok(hook)
+}
+
+#[tauri::command]
+pub fn delete_webhook(state: State<AppState>, id: String) -> Result<(), String> {
+    state.db.delete_webhook(&id).map_err(|e| e.to_string())
+}
+
+/// Send a single test event (no retries) and return the HTTP status
+#[tauri::command]
+pub fn test_webhook(state: State<AppState>, id: String) -> Result<String, String> {
+    let hook = state
+        .db
+        .get_webhooks()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|w| w.id == id)
+        .ok_or("Webhook not found")?;
+    let body = serde_json::to_string(&Payload {
+        event: "test",
+        timestamp: now(),
+        data: &serde_json::json!({ "message": "Test event from Pingo" }),
+    })
+    .map_err(|e| e.to_string())?;
+    let result = post(&hook.url, "test", &body);
+    let status = match &result {
+        Ok(s) => s.clone(),
+        Err(e) => format!("error: {}", e),
+    };
+    let _ = state.db.set_webhook_result(&hook.id, &status);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(peer: Option<&str>, group: Option<&str>, kind: Option<&str>) -> Webhook {
+        Webhook {
+            id: "w".into(),
+            url: "http://localhost".into(),
+            peer_id: peer.map(Into::into),
+            group_id: group.map(Into::into),
+            message_type: kind.map(Into::into),
+            enabled: true,
+            created_at: now(),
+            last_status: None,
+            last_delivery_at: None,
+        }
+    }
+
+    #[test]
+    fn test_filters_by_peer_group_and_type() {
+        let direct = EventScope {
+            peer_id: "p1",
+            group_id: None,
+            message_type: "text",
+        };
+        let group = EventScope {
+            peer_id: "p1",
+            group_id: Some("g1"),
+            message_type: "file",
+        };
+        assert!(matches(&hook(None, None, None), &direct));
+        assert!(matches(&hook(Some("p1"), None, None), &group));
+        assert!(!matches(&hook(Some("p2"), None, None), &direct));
+        assert!(!matches(&hook(None, Some("g1"), None), &direct));
+        assert!(matches(&hook(None, Some("g1"), Some("file")), &group));
+        assert!(!matches(&hook(None, None, Some("file")), &direct));
+
+        let mut disabled = hook(None, None, None);
+        disabled.enabled = false;
+        assert!(!matches(&disabled, &direct));
+    }
+}
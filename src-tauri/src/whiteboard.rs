@@ -0,0 +1,241 @@
+// src-tauri/src/whiteboard.rs
+// Shared meeting whiteboard. Each drawing operation is sent by the author's backend to every
+// participant in the meeting roster and kept in memory, so someone joining late can replay
+// the board. The final board can be saved as a PNG on the meeting's history record.
+
+use crate::commands::{send_to_peer, AppState};
+use crate::db::{now, MeetingRecord};
+use crate::profiles;
+use crate::signaling::SignalingMessage;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter, Runtime, State};
+use tracing::{info, warn};
+
+// Signaling is one UDP datagram per message; keep an op well inside it
+const MAX_POINTS: usize = 2000;
+const MAX_OPS: usize = 5000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WhiteboardOperation {
+    /// Freehand line through `points` ([x, y] in board coordinates)
+    Stroke {
+        points: Vec<[f32; 2]>,
+        color: String,
+        width: f32,
+    },
+    /// `shape` is rect, ellipse, line or arrow, spanning (x, y) to (x + w, y + h)
+    Shape {
+        shape: String,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color: String,
+        #[serde(default)]
+        fill: Option<String>,
+    },
+    Clear,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WhiteboardEntry {
+    pub from: String,
+    pub op: WhiteboardOperation,
+    pub at: String,
+}
+
+static BOARDS: OnceLock<Mutex<HashMap<String, Vec<WhiteboardEntry>>>> = OnceLock::new();
+
+fn boards() -> &'static Mutex<HashMap<String, Vec<WhiteboardEntry>>> {
+    BOARDS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn validate(op: &WhiteboardOperation) -> Result<(), String> {
+    match op {
+        WhiteboardOperation::Stroke { points, .. } if points.len() > MAX_POINTS => Err(format!(
+            "Stroke has {} points; split it (max {})",
+            points.len(),
+            MAX_POINTS
+        )),
+        WhiteboardOperation::Shape { shape, .. }
+            if !["rect", "ellipse", "line", "arrow"].contains(&shape.as_str()) =>
+        {
+            Err(format!("Unknown shape: {}", shape))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Add an op to a meeting's board; a clear drops everything before it
+fn record(meeting_id: &str, from: &str, op: WhiteboardOperation) -> WhiteboardEntry {
+    let entry = WhiteboardEntry {
+        from: from.to_string(),
+        op,
+        at: now(),
+    };
+    let mut all = boards().lock().unwrap();
+    let board = all.entry(meeting_id.to_string()).or_default();
+    if entry.op == WhiteboardOperation::Clear {
+        board.clear();
+    }
+    board.push(entry.clone());
+    if board.len() > MAX_OPS {
+        let excess = board.len() - MAX_OPS;
+        board.drain(..excess);
+    }
+    entry
+}
+
+/// Handle a WhiteboardOp from the signaling forwarder
+pub fn handle_message<R: Runtime>(
+    app: &AppHandle<R>,
+    from: &str,
+    meeting_id: &str,
+    op: &WhiteboardOperation,
+) {
+    if let Err(e) = validate(op) {
+        warn!("Whiteboard op from {}: {}", from, e);
+        return;
+    }
+    let entry = record(meeting_id, from, op.clone());
+    let _ = app.emit(
+        "whiteboard-op",
+        serde_json::json!({ "meeting_id": meeting_id, "entry": entry }),
+    );
+}
+
+// ============ COMMANDS ============
+
+/// Draw on the meeting whiteboard; the op goes to everyone in the meeting roster.
+/// Returns how many participants it was sent to.
+#[tauri::command]
+pub fn send_whiteboard_op(
+    state: State<AppState>,
+    meeting_id: String,
+    op: WhiteboardOperation,
+) -> Result<usize, String> {
+    validate(&op)?;
+    let local_id = state.device_id();
+    let roster = state
+        .meeting_rosters
+        .get(&meeting_id)
+        .ok_or("Unknown meeting")?;
+    record(&meeting_id, &local_id, op.clone());
+
+    let mut sent = 0;
+    for peer in roster.participants.iter().filter(|p| p.peer_id != local_id) {
+        let msg = SignalingMessage::WhiteboardOp {
+            from: local_id.clone(),
+            to: peer.peer_id.clone(),
+            meeting_id: meeting_id.clone(),
+            op: op.clone(),
+        };
+        match send_to_peer(&state, &peer.peer_id, &msg) {
+            Ok(()) => sent += 1,
+            Err(e) => warn!("Whiteboard op to {} failed: {}", peer.peer_id, e),
+        }
+    }
+    Ok(sent)
+}
+
+/// The board so far, oldest op first
+#[tauri::command]
+pub fn get_whiteboard(meeting_id: String) -> Vec<WhiteboardEntry> {
+    boards()
+        .lock()
+        .unwrap()
+        .get(&meeting_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Save the rendered board (base64 PNG or data URL) on the meeting's history record.
+/// Returns the file path.
+#[tauri::command]
+pub fn save_whiteboard(
+    state: State<AppState>,
+    meeting_id: String,
+    data: String,
+) -> Result<String, String> {
+    let b64 = data.split_once("base64,").map(|(_, b)| b).unwrap_or(&data);
+    let png = base64::engine::general_purpose::STANDARD
+        .decode(b64)
+        .map_err(|e| e.to_string())?;
+    if !png.starts_with(b"\x89PNG") {
+        return Err("Whiteboard image must be a PNG".to_string());
+    }
+    let dir = profiles::app_dir(&profiles::active_profile()).join("whiteboards");
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let safe_id: String = meeting_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    let path = dir.join(format!("{}.png", safe_id));
+    std::fs::write(&path, &png).map_err(|e| e.to_string())?;
+    let path = path.to_string_lossy().to_string();
+
+    // Meetings we only drew in may not be in history yet
+    let host_id = state
+        .meeting_rosters
+        .get(&meeting_id)
+        .map(|r| r.host_id)
+        .unwrap_or_else(|| state.device_id());
+    state
+        .db
+        .upsert_meeting(&MeetingRecord {
+            id: meeting_id.clone(),
+            host_id,
+            host_name: None,
+            started_at: now(),
+            ended_at: None,
+            whiteboard_path: None,
+        })
+        .map_err(|e| e.to_string())?;
+    state
+        .db
+        .set_meeting_whiteboard(&meeting_id, &path)
+        .map_err(|e| e.to_string())?;
+    info!("Saved whiteboard for meeting {}", meeting_id);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear_resets_board_and_ops_roundtrip() {
+        let stroke = WhiteboardOperation::Stroke {
+            points: vec![[0.0, 0.0], [10.0, 5.0]],
+            color: "#000".to_string(),
+            width: 2.0,
+        };
+        let json = serde_json::to_string(&stroke).unwrap();
+        assert!(json.contains("\"kind\":\"stroke\""));
+        assert_eq!(
+            serde_json::from_str::<WhiteboardOperation>(&json).unwrap(),
+            stroke
+        );
+
+        record("wb-test", "a", stroke.clone());
+        record("wb-test", "b", stroke);
+        assert_eq!(get_whiteboard("wb-test".to_string()).len(), 2);
+        record("wb-test", "a", WhiteboardOperation::Clear);
+        assert_eq!(get_whiteboard("wb-test".to_string()).len(), 1);
+
+        let bad = WhiteboardOperation::Shape {
+            shape: "star".to_string(),
+            x: 0.0,
+            y: 0.0,
+            w: 1.0,
+            h: 1.0,
+            color: "#000".to_string(),
+            fill: None,
+        };
+        assert!(validate(&bad).is_err());
+    }
+}
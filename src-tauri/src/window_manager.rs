@@ -0,0 +1,298 @@
+// src-tauri/src/window_manager.rs
+// Secondary windows managed by the backend (quick reply popup, pop-out chats)
+
+use crate::commands::AppState;
+use crate::db::Database;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::{
+    AppHandle, Emitter, Manager, PhysicalPosition, Runtime, State, WebviewUrl, WebviewWindow,
+    WebviewWindowBuilder, WindowEvent,
+};
+
+pub const QUICK_REPLY_LABEL: &str = "quick-reply";
+const QUICK_REPLY_WIDTH: f64 = 360.0;
+const QUICK_REPLY_HEIGHT: f64 = 220.0;
+// Gap between the popup and the screen edge / taskbar
+const TRAY_MARGIN: i32 = 12;
+const CHAT_WINDOW_WIDTH: f64 = 420.0;
+const CHAT_WINDOW_HEIGHT: f64 = 640.0;
+
+// Last peer that sent us a direct message — target of the tray "Quick Reply" item
+static LAST_INCOMING_PEER: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_last_incoming_peer(peer_id: &str) {
+    *LAST_INCOMING_PEER.lock().unwrap() = Some(peer_id.to_string());
+}
+
+pub fn last_incoming_peer() -> Option<String> {
+    LAST_INCOMING_PEER.lock().unwrap().clone()
+}
+
+pub fn clear_last_incoming_peer() {
+    *LAST_INCOMING_PEER.lock().unwrap() = None;
+}
+
+/// Returns true when the main window is visible and has keyboard focus
+pub fn is_main_window_focused<R: Runtime>(app: &AppHandle<R>) -> bool {
+    app.get_webview_window("main")
+        .map(|w| w.is_visible().unwrap_or(false) && w.is_focused().unwrap_or(false))
+        .unwrap_or(false)
+}
+
+/// Open (or retarget) the quick reply popup for a peer.
+/// The frontend reads the peer from the `#/quick-reply/<peer_id>` route on first load and
+/// listens for `quick-reply-target` when an already-open popup is reused.
+pub fn open_quick_reply<R: Runtime>(app: &AppHandle<R>, peer_id: &str) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(QUICK_REPLY_LABEL) {
+        let _ = app.emit_to(
+            QUICK_REPLY_LABEL,
+            "quick-reply-target",
+            serde_json::json!({ "peer_id": peer_id }),
+        );
+        position_near_tray(&window);
+        window.show().map_err(|e| e.to_string())?;
+        window.set_focus().map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    let url = WebviewUrl::App(format!("index.html#/quick-reply/{}", peer_id).into());
+    let window = WebviewWindowBuilder::new(app, QUICK_REPLY_LABEL, url)
+        .title("Pingo - Quick Reply")
+        .inner_size(QUICK_REPLY_WIDTH, QUICK_REPLY_HEIGHT)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .visible(false)
+        .build()
+        .map_err(|e| format!("Failed to create quick reply window: {}", e))?;
+
+    position_near_tray(&window);
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Close the quick reply popup if it is open
+pub fn close_quick_reply<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(QUICK_REPLY_LABEL) {
+        let _ = window.close();
+    }
+}
+
+/// Place the window in the corner where the system tray lives:
+/// top-right under the menu bar on macOS, bottom-right above the taskbar elsewhere.
+fn position_near_tray<R: Runtime>(window: &WebviewWindow<R>) {
+    let monitor = match window.primary_monitor() {
+        Ok(Some(m)) => m,
+        _ => return,
+    };
+    let area = monitor.work_area();
+    let size = match window.outer_size() {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+
+    let x = area.position.x + area.size.width as i32 - size.width as i32 - TRAY_MARGIN;
+    let y = if cfg!(target_os = "macos") {
+        area.position.y + TRAY_MARGIN
+    } else {
+        area.position.y + area.size.height as i32 - size.height as i32 - TRAY_MARGIN
+    };
+    let _ = window.set_position(PhysicalPosition::new(x, y));
+}
+
+// ============ POP-OUT CHAT WINDOWS ============
+
+/// Logical position and size of a pop-out chat window
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Pop-out chat windows currently open, keyed by peer_id, with their last known geometry
+pub struct ChatWindows {
+    windows: RwLock<HashMap<String, Option<WindowGeometry>>>,
+}
+
+impl ChatWindows {
+    pub fn new() -> Self {
+        ChatWindows {
+            windows: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_open(&self, peer_id: &str) -> bool {
+        self.windows.read().unwrap().contains_key(peer_id)
+    }
+
+    pub fn open_peers(&self) -> Vec<String> {
+        self.windows.read().unwrap().keys().cloned().collect()
+    }
+
+    fn insert(&self, peer_id: &str, geometry: Option<WindowGeometry>) {
+        self.windows
+            .write()
+            .unwrap()
+            .insert(peer_id.to_string(), geometry);
+    }
+
+    fn remove(&self, peer_id: &str) -> Option<WindowGeometry> {
+        self.windows.write().unwrap().remove(peer_id).flatten()
+    }
+
+    fn update_geometry(&self, peer_id: &str, update: impl FnOnce(&mut WindowGeometry)) {
+        if let Some(entry) = self.windows.write().unwrap().get_mut(peer_id) {
+            let mut g = entry.unwrap_or(WindowGeometry {
+                x: 0.0,
+                y: 0.0,
+                width: CHAT_WINDOW_WIDTH,
+                height: CHAT_WINDOW_HEIGHT,
+            });
+            update(&mut g);
+            *entry = Some(g);
+        }
+    }
+}
+
+impl Default for ChatWindows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn chat_window_label(peer_id: &str) -> String {
+    format!("chat-{}", peer_id)
+}
+
+fn geometry_setting_key(peer_id: &str) -> String {
+    format!("chat_window_geometry:{}", peer_id)
+}
+
+fn load_geometry(db: &Database, peer_id: &str) -> Option<WindowGeometry> {
+    db.get_setting(&geometry_setting_key(peer_id))
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+}
+
+fn save_geometry(db: &Database, peer_id: &str, geometry: &WindowGeometry) {
+    if let Ok(json) = serde_json::to_string(geometry) {
+        let _ = db.set_setting(&geometry_setting_key(peer_id), &json);
+    }
+}
+
+/// Forward a conversation event to the pop-out window for that peer, if one is open.
+/// Sent as a separate "chat-window-message" event so the pop-out doesn't also have to
+/// filter the global events meant for the main window.
+pub fn route_to_chat_window<R: Runtime, S: Serialize + Clone>(
+    app: &AppHandle<R>,
+    chat_windows: &ChatWindows,
+    peer_id: &str,
+    payload: S,
+) {
+    if chat_windows.is_open(peer_id) {
+        let _ = app.emit_to(chat_window_label(peer_id), "chat-window-message", payload);
+    }
+}
+
+// ============ COMMANDS ============
+
+#[tauri::command]
+pub fn open_quick_reply_window<R: Runtime>(
+    app: AppHandle<R>,
+    peer_id: String,
+) -> Result<(), String> {
+    open_quick_reply(&app, &peer_id)
+}
+
+#[tauri::command]
+pub fn close_quick_reply_window<R: Runtime>(app: AppHandle<R>) {
+    close_quick_reply(&app);
+}
+
+/// Open a conversation in its own window (or focus it if already open).
+/// Geometry is restored from the last time this chat was popped out.
+#[tauri::command]
+pub fn open_chat_window<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<AppState>,
+    peer_id: String,
+) -> Result<(), String> {
+    let label = chat_window_label(&peer_id);
+    if let Some(window) = app.get_webview_window(&label) {
+        window.show().map_err(|e| e.to_string())?;
+        window.set_focus().map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    let peer_name = state
+        .db
+        .get_user(&peer_id)
+        .ok()
+        .flatten()
+        .map(|u| u.username)
+        .unwrap_or_else(|| "Chat".to_string());
+    let geometry = load_geometry(&state.db, &peer_id);
+
+    let url = WebviewUrl::App(format!("index.html#/chat-window/{}", peer_id).into());
+    let mut builder = WebviewWindowBuilder::new(&app, &label, url)
+        .title(format!("Pingo - {}", peer_name))
+        .min_inner_size(320.0, 400.0);
+    builder = match geometry {
+        Some(g) => builder.inner_size(g.width, g.height).position(g.x, g.y),
+        None => builder.inner_size(CHAT_WINDOW_WIDTH, CHAT_WINDOW_HEIGHT),
+    };
+    let window = builder
+        .build()
+        .map_err(|e| format!("Failed to create chat window: {}", e))?;
+
+    state.chat_windows.insert(&peer_id, geometry);
+
+    let chat_windows = Arc::clone(&state.chat_windows);
+    let db = Arc::clone(&state.db);
+    let window_clone = window.clone();
+    window.on_window_event(move |event| match event {
+        WindowEvent::Moved(pos) => {
+            let scale = window_clone.scale_factor().unwrap_or(1.0);
+            let pos = pos.to_logical::<f64>(scale);
+            chat_windows.update_geometry(&peer_id, |g| {
+                g.x = pos.x;
+                g.y = pos.y;
+            });
+        }
+        WindowEvent::Resized(size) => {
+            let scale = window_clone.scale_factor().unwrap_or(1.0);
+            let size = size.to_logical::<f64>(scale);
+            chat_windows.update_geometry(&peer_id, |g| {
+                g.width = size.width;
+                g.height = size.height;
+            });
+        }
+        WindowEvent::Destroyed => {
+            if let Some(g) = chat_windows.remove(&peer_id) {
+                save_geometry(&db, &peer_id, &g);
+            }
+        }
+        _ => {}
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn close_chat_window<R: Runtime>(app: AppHandle<R>, peer_id: String) {
+    if let Some(window) = app.get_webview_window(&chat_window_label(&peer_id)) {
+        let _ = window.close();
+    }
+}
+
+#[tauri::command]
+pub fn get_open_chat_windows(state: State<AppState>) -> Vec<String> {
+    state.chat_windows.open_peers()
+}
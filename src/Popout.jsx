@@ -0,0 +1,21 @@
+// src/Popout.jsx
+// Root for the windows the backend opens on their own routes (quick reply popup, pop-out chats).
+// They skip AppProvider: no init_app, signaling or global listeners run a second time.
+
+import React from 'react';
+import { Routes, Route } from 'react-router-dom';
+import QuickReplyPage from './pages/quickReply';
+import ChatWindowPage from './pages/chatWindow';
+import './App.css';
+
+// Routes rendered by Popout instead of the main app
+export const isPopoutRoute = (hash) => /^#\/(quick-reply|chat-window)\//.test(hash);
+
+export default function Popout() {
+    return (
+        <Routes>
+            <Route path="/quick-reply/:peerId" element={<QuickReplyPage />} />
+            <Route path="/chat-window/:peerId" element={<ChatWindowPage />} />
+        </Routes>
+    );
+}
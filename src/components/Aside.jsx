@@ -1,15 +1,23 @@
 // src/components/Aside.jsx
 // Sidebar navigation with profile button and online badge
 
-import React, { useState } from 'react';
+import React, { useState, useEffect } from 'react';
 import { NavLink } from 'react-router-dom';
 import { useAppContext } from '../context/AppContext';
+import * as api from '../lib/api';
 import UserAvatar from './UserAvatar';
 import Profile from './Profile';
 
 export default function Aside() {
     const { localUser, peers, unreadCounts } = useAppContext();
     const [showProfile, setShowProfile] = useState(false);
+    const [visible, setVisible] = useState(true);
+
+    useEffect(() => {
+        api.getVisibility().then(v => setVisible(v !== false)).catch(() => {});
+        const unsub = api.onVisibilityChanged(({ visible }) => setVisible(visible));
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, []);
 
     const totalUnread = Object.values(unreadCounts || {}).reduce((a, b) => a + b, 0);
     const onlineCount = (peers || []).length;
@@ -48,8 +56,9 @@ export default function Aside() {
                     </NavLink>
 
                     {/* ── Profile button at bottom ── */}
-                    <button className="profile-btn" onClick={() => setShowProfile(true)} title="Profile">
+                    <button className="profile-btn" onClick={() => setShowProfile(true)} title={visible ? 'Profile' : 'Profile (invisible)'}>
                         <UserAvatar name={localUser?.username} size={34} avatarUrl={localUser?.avatar_path} />
+                        {!visible && <span className="status-dot offline" />}
                     </button>
                 </nav>
 
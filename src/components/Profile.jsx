@@ -1,8 +1,9 @@
 // src/components/Profile.jsx
 // Profile panel — slide-out or modal showing local user profile with edit
 
-import React, { useState, useRef, useCallback } from 'react';
+import React, { useState, useRef, useCallback, useEffect } from 'react';
 import { useAppContext } from '../context/AppContext';
+import * as api from '../lib/api';
 import UserAvatar from './UserAvatar';
 
 export default function Profile({ isOpen, onClose, peer }) {
@@ -11,11 +12,46 @@ export default function Profile({ isOpen, onClose, peer }) {
     const [username, setUsername] = useState('');
     const [bio, setBio] = useState('');
     const [designation, setDesignation] = useState('');
+    const [trust, setTrust] = useState(null);
+    const [pinPaired, setPinPaired] = useState(true);
+    const [pin, setPin] = useState(null);
     const fileRef = useRef(null);
 
     // If peer is provided, show their profile read-only; otherwise show local profile
     const user = peer || localUser;
     const isLocal = !peer;
+    const peerId = peer?.device_id || peer?.id;
+
+    useEffect(() => {
+        if (!isOpen || !peerId) return;
+        api.getTrustState(peerId).then(setTrust).catch(() => setTrust(null));
+        api.isPinPaired(peerId).then(setPinPaired).catch(() => setPinPaired(true));
+        const unsub = api.onPinPairingResult(({ peer_id, paired }) => {
+            if (peer_id !== peerId) return;
+            setPin(null);
+            if (paired) setPinPaired(true);
+        });
+        return () => {
+            unsub.then?.(fn => fn?.());
+            setPin(null);
+        };
+    }, [isOpen, peerId]);
+
+    const startPinPairing = async () => {
+        try {
+            setPin(await api.startPinPairing(peerId));
+        } catch (e) {
+            console.error('Failed to start PIN pairing:', e);
+        }
+    };
+
+    const toggleTrust = async () => {
+        try {
+            setTrust(await (trust?.trusted ? api.untrustPeer(peerId) : api.trustPeer(peerId)));
+        } catch (e) {
+            console.error('Failed to change trust:', e);
+        }
+    };
 
     const startEdit = () => {
         setUsername(localUser?.username || '');
@@ -115,6 +151,36 @@ export default function Profile({ isOpen, onClose, peer }) {
                                     </span>
                                 </div>
                             )}
+                            {trust && (
+                                <div className="profile-info-row">
+                                    <span className="profile-label">Verified</span>
+                                    <span className="profile-value">
+                                        {trust.trusted ? 'Yes' : 'No'}
+                                        {trust.fingerprint && (
+                                            <span style={{ display: 'block', fontSize: 11, fontFamily: 'monospace' }}>
+                                                {trust.fingerprint}
+                                            </span>
+                                        )}
+                                    </span>
+                                </div>
+                            )}
+                            {trust && (
+                                <button className="btn-secondary" onClick={toggleTrust} style={{ marginTop: 16 }}>
+                                    {trust.trusted ? 'Remove verification' : 'Mark as verified'}
+                                </button>
+                            )}
+                            {!isLocal && !pinPaired && (pin ? (
+                                <div className="profile-info-row" style={{ marginTop: 16 }}>
+                                    <span className="profile-label">Pairing PIN</span>
+                                    <span className="profile-value" style={{ fontSize: 20, fontFamily: 'monospace', letterSpacing: 4 }}>
+                                        {pin}
+                                    </span>
+                                </div>
+                            ) : (
+                                <button className="btn-primary" onClick={startPinPairing} style={{ marginTop: 16 }}>
+                                    Pair with PIN
+                                </button>
+                            ))}
                             {isLocal && (
                                 <button className="btn-primary" onClick={startEdit} style={{ marginTop: 16 }}>
                                     Edit Profile
@@ -6,7 +6,12 @@ import * as api from '../lib/api';
 import * as avatarCache from '../lib/avatarCache';
 import * as chatLogger from '../lib/chatLogger';
 import { compressDataUrlIfNeeded } from '../lib/avatarUtils';
-import { initNotifications, showMessageNotification, getLastNotification, clearNotificationHistory } from '../lib/notifications';
+import { initNotifications, getLastNotification, clearNotificationHistory } from '../lib/notifications';
+
+// Extended profile (directory) fields carried by users and ProfileUpdate messages
+const PROFILE_FIELDS = ['email', 'phone', 'department', 'pronouns', 'timezone'];
+const pickProfileFields = (src) =>
+    Object.fromEntries(PROFILE_FIELDS.filter(k => src?.[k] !== undefined).map(k => [k, src[k]]));
 
 // ═══════════════════════════════════════════════════════════════
 //  useApp  —  top-level app state
@@ -27,6 +32,7 @@ export function useApp() {
     const localUserRef = useRef(null);
     const deviceIdRef = useRef('');
     const fileServerPortRef = useRef(0);
+    const signalingPortRef = useRef(45678);
     // Track currently active chat peer — messages from this peer won't increment unread
     const activeChatPeerIdRef = useRef(null);
     const allUsersRef = useRef([]);
@@ -66,6 +72,15 @@ export function useApp() {
         }
     };
 
+    // Helper: peer_id -> last message, as the sidebar wants it (DB doesn't return message_type)
+    const toLastMessageMap = (rows) => {
+        const map = {};
+        rows.forEach(m => {
+            map[m.peer_id] = { ...m, message_type: m.message_type || inferMessageType(m.content) };
+        });
+        return map;
+    };
+
     // ─── Helper: broadcast our profile to a specific peer ────
     const broadcastProfileToPeer = useCallback(async (peerDeviceId) => {
         const lu = localUserRef.current;
@@ -86,6 +101,13 @@ export function useApp() {
                 // Fire-and-forget to avoid blocking the fast-path broadcast.
                 const fileId = `avatar_${did}`;
                 api.storeSharedFile(fileId, toSend, 'avatar.png').catch(() => { });
+            } else if (lu.avatar_path && lu.avatar_path.includes(`/file/avatar_${did}`) && port) {
+                // Processed avatar saved by the backend; peers fetch it from our file server
+                await api.sendSignalingMessage(peerDeviceId, {
+                    type: 'ProfileUpdate', from: did, to: peerDeviceId,
+                    username: lu.username || '', avatar_file_id: `avatar_${did}`, avatar_file_port: port,
+                    bio: lu.bio || '', designation: lu.designation || '',
+                });
             } else {
                 await api.sendSignalingMessage(peerDeviceId, {
                     type: 'ProfileUpdate', from: did, to: peerDeviceId,
@@ -141,21 +163,10 @@ export function useApp() {
                 return;
             }
 
-            const url = `http://${senderIp.split(':')[0]}:${info.port}/file/${info.fileId}`;
+            const url = api.peerFileUrl(senderIp, info.port, info.fileId, info.token);
             console.log('[Pingo] Auto-downloading file:', info.fileName, 'from', url);
-            api.autoDownloadFile(url, senderName, info.fileName || 'file', msgType, msg.id)
-                .then(async () => {
-                    // After download completes, read the file as a data URL and notify the chat UI
-                    // so it can display the preview immediately without waiting for the next render cycle
-                    try {
-                        const dataUrl = await api.readFileAsDataUrl(info.fileId);
-                        if (dataUrl && typeof window !== 'undefined') {
-                            window.dispatchEvent(new CustomEvent('pingo:file-downloaded', {
-                                detail: { fileId: info.fileId, dataUrl }
-                            }));
-                        }
-                    } catch { /* ignore — UI will lazy-load via effect */ }
-                })
+            // Queued in the backend download pool; the download-finished listener below picks it up
+            api.autoDownloadFile(url, senderName, info.fileName || 'file', msgType, msg.id, info.checksum || null)
                 .catch(e => console.warn('[Pingo] Auto-download failed:', e));
         } catch (e) { /* not JSON, skip */ }
     }, []);
@@ -190,12 +201,13 @@ export function useApp() {
                 }
 
                 // Start signaling
-                await api.startSignaling(45678);
+                const signalingPort = await api.startSignaling();
+                signalingPortRef.current = signalingPort;
                 api.appendDevLog && api.appendDevLog('[Pingo] startSignaling returned').catch(() => { });
 
                 // Start discovery
                 const username = user?.username || 'Pingo User';
-                await api.startDiscovery(username, 45678);
+                await api.startDiscovery(username, signalingPort);
                 api.appendDevLog && api.appendDevLog('[Pingo] startDiscovery returned').catch(() => { });
 
                 // Get file server port
@@ -245,28 +257,10 @@ export function useApp() {
                     }
                 } catch { /* ignore */ }
 
-                // Load last messages for sidebar preview
+                // Load last messages for sidebar preview (unread counts arrive on the backend event stream)
                 try {
                     const lm = await api.getLastMessages();
-                    if (lm) {
-                        const map = {};
-                        lm.forEach(m => {
-                            map[m.peer_id] = {
-                                ...m,
-                                // DB doesn't return message_type — infer it for preview
-                                message_type: m.message_type || inferMessageType(m.content),
-                            };
-                        });
-                        setLastMessages(map);
-                    }
-                } catch { /* ignore */ }
-
-                // Load unread counts
-                try {
-                    const total = await api.getUnreadCount();
-                    if (total) {
-                        // We need per-peer counts. Use allUsers to iterate.
-                    }
+                    if (lm) setLastMessages(toLastMessageMap(lm));
                 } catch { /* ignore */ }
 
                 await initNotifications();
@@ -284,22 +278,38 @@ export function useApp() {
     }, []);
 
     // ─── refresh last messages ────────────────────────────────
+    // Manual resync only; the backend pushes last messages after every write (see below)
     const refreshLastMessages = useCallback(async () => {
         try {
             const lm = await api.getLastMessages();
-            if (lm) {
-                const map = {};
-                lm.forEach(m => {
-                    map[m.peer_id] = {
-                        ...m,
-                        message_type: m.message_type || inferMessageType(m.content),
-                    };
-                });
-                setLastMessages(map);
-            }
+            if (lm) setLastMessages(toLastMessageMap(lm));
         } catch { /* ignore */ }
     }, []);
 
+    // ─── backend event stream ─────────────────────────────────
+    // Sidebar previews and unread badges follow the DB instead of being re-queried after each action
+    useEffect(() => {
+        if (!initialized) return;
+        const unsub = api.onBackendEvent(event => {
+            switch (event?.kind) {
+                case 'last_messages':
+                    setLastMessages(toLastMessageMap(event.items || []));
+                    break;
+                case 'unread_counts': {
+                    // The open chat is marked read when it's opened again, not per message
+                    const counts = { ...(event.by_peer || {}) };
+                    delete counts[activeChatPeerIdRef.current];
+                    setUnreadCounts(counts);
+                    break;
+                }
+                default:
+                    break;
+            }
+        });
+        api.refreshBackendSummaries().catch(() => { });
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, [initialized]);
+
     // Track rate limiting and deduplication for flush operations
     const lastFlushTimeRef = useRef(new Map()); // peerId -> timestamp
     const flushInProgressRef = useRef(new Set()); // peerIds currently flushing
@@ -356,14 +366,13 @@ export function useApp() {
                     console.warn('[Pingo] Failed to deliver pending message', p.id, err);
                 }
             }
-            refreshLastMessages().catch(() => { });
         } catch (err) {
             chatLogger.log('error', `flushPendingMessages error for ${peerDeviceId.slice(0, 8)}…`, { peerId: peerDeviceId, error: String(err) });
             console.warn('[Pingo] flushPendingMessages error:', err);
         } finally {
             flushInProgressRef.current.delete(peerDeviceId);
         }
-    }, [refreshLastMessages]);
+    }, []);
 
     // ─── discovery events ────────────────────────────────────
     useEffect(() => {
@@ -400,11 +409,12 @@ export function useApp() {
             flushPendingMessages(peer.device_id).catch(() => { /* ignore */ });
         }));
 
-        unsubs.push(api.onPeerUpdated(peer => {
+        // Presence changes arrive batched from the backend (unchanged hellos are dropped there)
+        const handlePeerUpdated = peer => {
             // ═══════════════════════════════════════════════════════════
             // CRITICAL FIX: Separate presence data from profile data
             //
-            // Problem: onPeerUpdated fires for presence updates (IP, port, connection)
+            // Problem: peer updates fire for presence updates (IP, port, connection)
             //          but doesn't include avatar data. If we set avatar_path:undefined,
             //          the UI renders nothing and avatars "disappear" 1-2 seconds later.
             //
@@ -416,7 +426,7 @@ export function useApp() {
 
             // Get existing user to preserve their profile data (avatar, bio, designation)
             const existingUser = peerMapRef.current.get(peer.device_id);
-            console.debug('[Pingo][debug] onPeerUpdated — id=%s incomingUsername=%s existingUsername=%s ip=%s port=%s', peer.device_id, peer.username, existingUser?.username, ip, port);
+            console.debug('[Pingo][debug] onPeersUpdated — id=%s incomingUsername=%s existingUsername=%s ip=%s port=%s', peer.device_id, peer.username, existingUser?.username, ip, port);
             const preservedAvatar = existingUser?.avatar_path || avatarCache.getLocalAvatarUrl(peer.device_id);
 
             // If this update includes a local avatar path (e.g., data: or local file), cache it immediately
@@ -426,7 +436,7 @@ export function useApp() {
 
             // If presence includes a username but we already have an authoritative one, suppress it (prevents flicker)
             if (peer.username && existingUser?.username && peer.username !== existingUser.username) {
-                console.debug('[Pingo][debug] onPeerUpdated: suppressed presence username update — incoming=%s existing=%s id=%s', peer.username, existingUser.username, peer.device_id);
+                console.debug('[Pingo][debug] onPeersUpdated: suppressed presence username update — incoming=%s existing=%s id=%s', peer.username, existingUser.username, peer.device_id);
             }
 
             // Update ONLY presence data, keep existing profile data — DO NOT overwrite authoritative username from presence.
@@ -444,11 +454,11 @@ export function useApp() {
                     // Accept first-seen presence username as a temporary display name and lock it
                     resolvedUsername = peer.username;
                     presenceUsernameLocked = true;
-                    console.debug('[Pingo][debug] onPeerUpdated: accepted presence-derived username=%s for id=%s', resolvedUsername, peer.device_id);
+                    console.debug('[Pingo][debug] onPeersUpdated: accepted presence-derived username=%s for id=%s', resolvedUsername, peer.device_id);
                 } else {
                     // Already locked to a presence-derived username — ignore changes
                     if (prev.username && prev.username !== peer.username) {
-                        console.debug('[Pingo][debug] onPeerUpdated: ignored changed presence username (locked) — incoming=%s locked=%s id=%s', peer.username, prev.username, peer.device_id);
+                        console.debug('[Pingo][debug] onPeersUpdated: ignored changed presence username (locked) — incoming=%s locked=%s id=%s', peer.username, prev.username, peer.device_id);
                     }
                 }
             }
@@ -460,6 +470,9 @@ export function useApp() {
                 username: resolvedUsername,
                 bio: prev.bio,
                 designation: prev.designation,
+                ...pickProfileFields(prev),
+                // Custom status rides along with every presence hello (null when cleared/expired)
+                status: peer.status ?? null,
                 is_online: true,
                 ip_address: ip,
                 port: port,
@@ -477,6 +490,7 @@ export function useApp() {
                 prev.avatar_path !== nextPeer.avatar_path ||
                 prev.public_key !== nextPeer.public_key ||
                 prev.username !== nextPeer.username ||
+                JSON.stringify(prev.status ?? null) !== JSON.stringify(nextPeer.status) ||
                 prev._presenceUsernameLocked !== nextPeer._presenceUsernameLocked;
 
             if (presenceChanged) {
@@ -505,7 +519,8 @@ export function useApp() {
                 // Peer just came online - flush pending messages
                 flushPendingMessages(peer.device_id).catch(() => { /* ignore */ });
             }
-        }));
+        };
+        unsubs.push(api.onPeersUpdated(list => (list || []).forEach(handlePeerUpdated)));
 
         unsubs.push(api.onPeerLost(data => {
             const id = data.device_id || data;
@@ -514,6 +529,12 @@ export function useApp() {
             setAllUsers(prev => prev.map(u => u.id === id ? { ...u, is_online: false } : u));
         }));
 
+        // A peer announced a new avatar version: drop our in-memory copy so the next
+        // ProfileUpdate re-downloads it instead of serving the stale one
+        unsubs.push(api.onPeerAvatarChanged(data => {
+            if (data?.device_id) avatarCache.invalidateCache(data.device_id);
+        }));
+
         // Listen for server-side deletion of a user and remove from local caches
         unsubs.push(api.onUserDeleted(payload => {
             const id = payload?.user_id || payload;
@@ -552,8 +573,6 @@ export function useApp() {
                         window.dispatchEvent(new CustomEvent('pingo:pending-delivered', { detail: { peerId, messageId } }));
                     }
                 } catch (e) { /* ignore */ }
-                // Refresh sidebar previews
-                refreshLastMessages().catch(() => { });
                 return;
             }
 
@@ -570,6 +589,8 @@ export function useApp() {
                     ...(msg.username && { username: msg.username }),
                     ...(msg.bio !== undefined && { bio: msg.bio }),
                     ...(msg.designation !== undefined && { designation: msg.designation }),
+                    ...pickProfileFields(msg),
+                    ...(msg.status !== undefined && { status: msg.status }),
                     // Clear the presence-lock flag so the new name sticks
                     _presenceUsernameLocked: false,
                 };
@@ -585,7 +606,7 @@ export function useApp() {
                         // Set it immediately for display, then cache in background
                         updated.avatar_path = msg.avatar_url;
                         avatarCache.invalidateCache(peerId); // force re-download
-                        avatarCache.cacheAvatarFromUrl(peerId, msg.avatar_url, msg.username || peerId)
+                        avatarCache.cacheAvatarFromUrl(peerId, msg.avatar_url, msg.username || peerId, msg.avatar_hash)
                             .then(localUrl => {
                                 if (localUrl) {
                                     const cached = peerMapRef.current.get(peerId);
@@ -618,6 +639,8 @@ export function useApp() {
                                 ...(msg.username && { username: msg.username }),
                                 ...(msg.bio !== undefined && { bio: msg.bio }),
                                 ...(msg.designation !== undefined && { designation: msg.designation }),
+                                ...pickProfileFields(msg),
+                                ...(msg.status !== undefined && { status: msg.status }),
                                 ...(updated.avatar_path && { avatar_path: updated.avatar_path }),
                             }
                             : u
@@ -632,6 +655,8 @@ export function useApp() {
                         avatar_path: updated.avatar_path,
                         bio: msg.bio,
                         designation: msg.designation,
+                        ...pickProfileFields(msg),
+                        ...(msg.status !== undefined && { status: msg.status }),
                     }];
                 });
             }
@@ -695,9 +720,6 @@ export function useApp() {
     useEffect(() => {
         if (!initialized) return;
         const unsub = api.onChatMessageReceived(msg => {
-            // Skip meeting chat messages — they are ephemeral and should NOT affect DM state.
-            if (msg.content?.startsWith('[MEETING_CHAT]')) return;
-
             chatLogger.log('receive', `Received ${msg.message_type || 'text'} from ${msg.sender_name || msg.sender_id?.slice(0, 8)}`, {
                 messageId: msg.id, senderId: msg.sender_id, senderName: msg.sender_name,
                 messageType: msg.message_type, contentLen: msg.content?.length,
@@ -721,19 +743,7 @@ export function useApp() {
                     ...prev,
                     [msg.sender_id]: (prev[msg.sender_id] || 0) + 1,
                 }));
-                // Show Windows notification (works when app is minimized/closed)
-                const preview = msg.message_type === 'text'
-                    ? msg.content
-                    : `Sent ${msg.message_type}`;
-
-                // Get sender's avatar from allUsers (use ref to avoid stale closure)
-                let senderAvatar = null;
-                const sender = allUsersRef.current.find(u => u.id === msg.sender_id);
-                if (sender?.avatar_path) {
-                    senderAvatar = sender.avatar_path;
-                }
-
-                showMessageNotification(msg.sender_name || 'User', preview, msg.sender_id, senderAvatar);
+                // Native notification is shown by the backend (respects mute / DND / per-chat mute)
             }
             // Auto-download file/image/video messages
             autoDownloadFileMessage(msg);
@@ -741,6 +751,56 @@ export function useApp() {
         return () => { unsub.then?.(fn => fn?.()); };
     }, [initialized, autoDownloadFileMessage]); // allUsers accessed via ref — no re-subscription needed
 
+    // ─── pooled downloads finishing ─────────────────────────
+    useEffect(() => {
+        // Read the finished file as a data URL and notify the chat UI so it can show the
+        // preview immediately without waiting for the next render cycle
+        const unsub = api.onDownloadFinished(async (data) => {
+            const { fileId, path, error } = data || {};
+            if (!fileId) return;
+            if (!path) {
+                console.warn('[Pingo] Auto-download failed:', error);
+                return;
+            }
+            try {
+                const dataUrl = await api.readFileAsDataUrl(fileId);
+                if (dataUrl && typeof window !== 'undefined') {
+                    window.dispatchEvent(new CustomEvent('pingo:file-downloaded', {
+                        detail: { fileId, dataUrl }
+                    }));
+                }
+            } catch { /* ignore — UI will lazy-load via effect */ }
+        });
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, []);
+
+    // ─── backend notification actions ───────────────────────
+    useEffect(() => {
+        const unsubOpen = api.onNotificationOpenChat(({ payload }) => {
+            // Group notifications only bring the window forward
+            if (!payload?.chat_id || payload.is_group) return;
+            window.dispatchEvent(new CustomEvent('pingo:open-chat', {
+                detail: { peerId: payload.chat_id }
+            }));
+        });
+        const unsubRead = api.onMessagesMarkedRead(({ payload }) => {
+            if (!payload?.peer_id) return;
+            setUnreadCounts(prev => ({ ...prev, [payload.peer_id]: 0 }));
+        });
+        return () => {
+            unsubOpen.then?.(fn => fn?.());
+            unsubRead.then?.(fn => fn?.());
+        };
+    }, []);
+
+    // ─── profile switch — restart the UI against the new profile ───
+    useEffect(() => {
+        // All frontend state belongs to the old profile; a reload re-runs init_app and
+        // restarts discovery/signaling with the new identity.
+        const unsub = api.onProfileSwitched(() => window.location.reload());
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, []);
+
     // ─── handle notification clicks — open specific chat ────
     useEffect(() => {
         const handleNotificationClick = (event) => {
@@ -778,6 +838,18 @@ export function useApp() {
         }
     }, []);
 
+    // ─── custom status (set here, or cleared by the backend on expiry) ─
+    useEffect(() => {
+        if (!initialized) return;
+        api.getStatus()
+            .then(status => setLocalUser(prev => prev ? { ...prev, status } : prev))
+            .catch(() => { /* ok */ });
+        const unsub = api.onStatusChanged(data => {
+            setLocalUser(prev => prev ? { ...prev, status: data?.status ?? null } : prev);
+        });
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, [initialized]);
+
     // ─── update user profile ─────────────────────────────────
     const updateProfile = useCallback(async (fields) => {
         if (!localUser) return;
@@ -787,8 +859,9 @@ export function useApp() {
             fields.avatar_path ?? localUser.avatar_path,
             fields.bio ?? localUser.bio,
             fields.designation ?? localUser.designation,
+            pickProfileFields(fields),
         );
-        if (updated) setLocalUser(updated);
+        if (updated) setLocalUser(prev => ({ ...updated, status: prev?.status ?? null }));
 
         // *** CRITICAL: Broadcast profile update to ALL peers immediately ***
         // This ensures peers get the new username/bio/designation synchronously
@@ -797,6 +870,7 @@ export function useApp() {
             (fields.username && fields.username !== localUser.username) ||
             (fields.bio && fields.bio !== localUser.bio) ||
             (fields.designation && fields.designation !== localUser.designation) ||
+            PROFILE_FIELDS.some(k => fields[k] !== undefined && fields[k] !== localUser[k]) ||
             (fields.avatar_path && fields.avatar_path !== localUser.avatar_path);
 
         if (hasChanges) {
@@ -839,7 +913,7 @@ export function useApp() {
 
         // Restart discovery with new username
         if (fields.username && fields.username !== localUser.username) {
-            try { await api.restartDiscovery(fields.username, 45678); } catch { /* ok */ }
+            try { await api.restartDiscovery(fields.username, signalingPortRef.current); } catch { /* ok */ }
             // Rename download folder for old username
             try { await api.renameUserDownloadFolder(oldUsername, fields.username); } catch { /* ok */ }
         }
@@ -853,45 +927,20 @@ export function useApp() {
         if (result) {
             setLocalUser(prev => prev ? { ...prev, avatar_path: result } : prev);
         }
-        // Broadcast avatar to online peers via signaling
+        // The backend stored the processed avatar on the file server; peers fetch it from there
         try {
             const onlinePeers = await api.getOnlinePeers();
-
-            if (imageData) {
-                // If we have a data URL, compress it if needed and broadcast avatar_url immediately
-                if (imageData.startsWith('data:')) {
-                    const compressed = await compressDataUrlIfNeeded(imageData, 64 * 1024).catch(() => imageData);
-                    if (onlinePeers) {
-                        for (const peer of onlinePeers) {
-                            try {
-                                await api.sendSignalingMessage(peer.device_id, {
-                                    type: 'ProfileUpdate', from: deviceId, to: peer.device_id,
-                                    username: localUser?.username || '', avatar_url: compressed,
-                                    bio: localUser?.bio || '', designation: localUser?.designation || '',
-                                });
-                            } catch (e) { /* ok */ }
-                        }
-                    }
-                    // Also store to file server in background
-                    const fileId = `avatar_${deviceId}`;
-                    api.storeSharedFile(fileId, compressed, 'avatar.png').catch(() => { });
-                } else {
-                    const port = fileServerPort || await api.getFileServerPort();
-                    if (port) {
-                        const fileId = `avatar_${deviceId}`;
-                        await api.storeSharedFile(fileId, imageData, 'avatar.png');
-                        if (onlinePeers) {
-                            for (const peer of onlinePeers) {
-                                try {
-                                    await api.sendSignalingMessage(peer.device_id, {
-                                        type: 'ProfileUpdate', from: deviceId, to: peer.device_id,
-                                        username: localUser?.username || '', avatar_file_id: fileId, avatar_file_port: port,
-                                        bio: localUser?.bio || '', designation: localUser?.designation || '',
-                                    });
-                                } catch { /* ok */ }
-                            }
-                        }
-                    }
+            const port = fileServerPort || await api.getFileServerPort();
+            if (result && port && onlinePeers) {
+                const fileId = `avatar_${deviceId}`;
+                for (const peer of onlinePeers) {
+                    try {
+                        await api.sendSignalingMessage(peer.device_id, {
+                            type: 'ProfileUpdate', from: deviceId, to: peer.device_id,
+                            username: localUser?.username || '', avatar_file_id: fileId, avatar_file_port: port,
+                            bio: localUser?.bio || '', designation: localUser?.designation || '',
+                        });
+                    } catch { /* ok */ }
                 }
             }
         } catch (e) {
@@ -951,7 +1000,7 @@ export function useChat() {
 
             // Annotate media messages so previews work after reload
             const port = await (async () => {
-                try { return await api.getFileServerPort(); } catch { return null; }
+                try { return await api.getLocalFileServerPort(); } catch { return null; }
             })();
 
             // Get our own device ID from backend (useful because this hook doesn't have access to useApp scope)
@@ -966,8 +1015,7 @@ export function useChat() {
                         const info = JSON.parse(m.content);
                         if (info && info.fileId) {
                             // Always use local file server (works for sent & auto-downloaded files)
-                            const p = info.port || port || 0;
-                            m._localDataUrl = `http://127.0.0.1:${p}/file/${info.fileId}`;
+                            m._localDataUrl = api.localFileUrl(port || 0, info.fileId);
                             m._fileName = info.fileName || m._fileName || 'file';
                             m._fileType = info.type || m._fileType || m.message_type;
                             m._fileId = info.fileId;
@@ -1034,6 +1082,25 @@ export function useChat() {
         return () => { unsub.then?.(fn => fn?.()); };
     }, []); // Empty deps — never re-subscribes. Uses ref for current peer ID.
 
+    // The peer deleted one of their messages for everyone
+    useEffect(() => {
+        const unsub = api.onMessageDeleted(data => {
+            const id = data?.message_id;
+            if (id) setMessages(prev => prev.filter(m => m.id !== id));
+        });
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, []);
+
+    // A conversation was cleared (by us, or by the peer with our policy's blessing)
+    useEffect(() => {
+        const unsub = api.onConversationCleared(data => {
+            if (data?.peer_id && data.peer_id === activePeerRef.current) {
+                setMessages(prev => prev.filter(m => m.created_at > data.cleared_at));
+            }
+        });
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, []);
+
     // Listen for pending-delivered events and update active chat messages
     useEffect(() => {
         if (typeof window === 'undefined') return;
@@ -1043,7 +1110,7 @@ export function useChat() {
                 const currentPeerId = activePeerRef.current;
                 if (!peerId || !messageId) return;
                 if (peerId === currentPeerId) {
-                    setMessages(prev => prev.map(m => m.id === messageId ? { ...m, is_delivered: true } : m));
+                    setMessages(prev => prev.map(m => m.id === messageId ? { ...m, is_delivered: true, status: m.status === 'read' ? 'read' : 'delivered' } : m));
                 }
             } catch (err) { /* ignore */ }
         };
@@ -1051,6 +1118,21 @@ export function useChat() {
         return () => window.removeEventListener('pingo:pending-delivered', handler);
     }, []); // Empty deps — uses ref
 
+    // Delivery state changes from the backend (sending, sent, failed, retrying, delivered, read)
+    useEffect(() => {
+        const unsub = api.onMessageStatusChanged(data => {
+            const { message_id: id, status } = data || {};
+            if (!id || !status) return;
+            setMessages(prev => prev.map(m => m.id === id ? {
+                ...m,
+                status,
+                is_delivered: m.is_delivered || status === 'delivered' || status === 'read',
+                is_read: m.is_read || status === 'read',
+            } : m));
+        });
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, []);
+
     // Send text message
     const sendText = useCallback(async (peerId, text, senderName) => {
         chatLogger.log('send', `Sending text to ${peerId.slice(0, 8)}…`, { peerId, textLen: text.length });
@@ -1080,8 +1162,12 @@ export function useChat() {
             // Store in file server
             await api.storeSharedFile(fileId, dataUrl, fileName);
             const port = await api.getFileServerPort();
+            // Receivers verify the download against this
+            const checksum = await api.getSharedFileChecksum(fileId).catch(() => null);
+            // ...and need this to fetch it from our LAN listener
+            const token = await api.getSharedFileToken(fileId).catch(() => null);
 
-            const fileInfo = JSON.stringify({ fileId, fileName, port, type: messageType });
+            const fileInfo = JSON.stringify({ fileId, fileName, port, type: messageType, checksum, token });
             const msg = await api.sendMessage(peerId, fileInfo, messageType);
             if (msg) {
                 // Use the original dataUrl directly for sender view (no HTTP needed)
@@ -1117,16 +1203,56 @@ export function useChat() {
         setMessages(prev => prev.filter(m => m.id !== messageId));
     }, []);
 
+    // Delete a message we sent here and on the peer's side
+    const deleteMsgForEveryone = useCallback(async (messageId) => {
+        await api.deleteForEveryone(messageId);
+        setMessages(prev => prev.filter(m => m.id !== messageId));
+    }, []);
+
+    // Multi-select actions: one backend call for the whole selection
+    const deleteMsgs = useCallback(async (ids) => {
+        await api.deleteMessages(ids);
+        const gone = new Set(ids);
+        setMessages(prev => prev.filter(m => !gone.has(m.id)));
+    }, []);
+
+    const markMsgsRead = useCallback(async (ids) => {
+        await api.markMessagesRead(ids);
+        const read = new Set(ids);
+        setMessages(prev => prev.map(m => read.has(m.id) ? { ...m, is_read: true } : m));
+    }, []);
+
+    const forwardMsgs = useCallback(async (ids, targetPeerId) => {
+        const result = await api.forwardMessages(ids, targetPeerId);
+        if (targetPeerId === activePeerRef.current && result?.messages?.length) {
+            setMessages(prev => [...prev, ...result.messages]);
+        }
+        return result;
+    }, []);
+
+    // Undo a delete (soft-deleted messages are kept for 30 days)
+    const restoreMsg = useCallback(async (messageId) => {
+        const restored = await api.restoreMessage(messageId);
+        if (restored && activePeerRef.current) await loadMessages(activePeerRef.current);
+        return restored;
+    }, [loadMessages]);
+
     // Delete all messages with peer
     const deleteAllMessages = useCallback(async (peerId) => {
         await api.deleteAllMessagesWithPeer(peerId);
         setMessages([]);
     }, []);
 
+    // Delete the history here and on the peer's side
+    const clearChatForBoth = useCallback(async (peerId) => {
+        return api.clearChatForBoth(peerId, true);
+    }, []);
+
     return {
         messages, loading, activePeer,
         selectPeer, updatePeerInfo, sendText, sendFile, loadMessages,
-        deleteMsg, deleteAllMessages, setMessages,
+        deleteMsg, deleteMsgForEveryone, restoreMsg, deleteMsgs, markMsgsRead, forwardMsgs,
+        deleteAllMessages, clearChatForBoth, setMessages,
     };
 }
 
@@ -1137,19 +1263,29 @@ export function useNotes() {
     const [notes, setNotes] = useState([]);
     const [loading, setLoading] = useState(true);
 
-    useEffect(() => {
-        (async () => {
-            try {
-                const all = await api.getAllNotes();
-                setNotes(all || []);
-            } catch (e) {
-                console.error('Failed to load notes:', e);
-            } finally {
-                setLoading(false);
-            }
-        })();
+    const load = useCallback(async () => {
+        try {
+            const all = await api.getAllNotes();
+            setNotes(all || []);
+        } catch (e) {
+            console.error('Failed to load notes:', e);
+        } finally {
+            setLoading(false);
+        }
     }, []);
 
+    useEffect(() => { load(); }, [load]);
+
+    // A linked device or a collaborator changed notes
+    useEffect(() => {
+        const unsubs = [
+            api.onNotesSynced(() => load()),
+            api.onNoteShared(() => load()),
+            api.onSharedNoteUpdated(() => load()),
+        ];
+        return () => { unsubs.forEach(u => u.then?.(fn => fn?.())); };
+    }, [load]);
+
     const save = useCallback(async (note) => {
         const saved = await api.saveNote(note);
         if (saved) setNotes(prev => {
@@ -1170,7 +1306,22 @@ export function useNotes() {
         setNotes(prev => prev.map(n => n.id === id ? { ...n, pinned: !n.pinned } : n));
     }, []);
 
-    return { notes, loading, save, remove, togglePin };
+    const share = useCallback(async (id, peerId) => {
+        const shared = await api.shareNote(id, peerId);
+        if (shared) setNotes(prev => prev.map(n => n.id === id ? shared : n));
+        return shared;
+    }, []);
+
+    const lock = useCallback(async (id, passphrase) => {
+        const locked = await api.lockNote(id, passphrase);
+        if (locked) setNotes(prev => prev.map(n => n.id === id ? locked : n));
+        return locked;
+    }, []);
+
+    // Returns the decrypted note without storing its content in state
+    const unlock = useCallback((id, passphrase) => api.unlockNote(id, passphrase), []);
+
+    return { notes, loading, save, remove, togglePin, share, lock, unlock };
 }
 
 // ═══════════════════════════════════════════════════════════════
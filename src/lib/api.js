@@ -24,11 +24,24 @@ async function listen(event, handler) {
 export const initApp = () => invoke('init_app');
 
 // ============ USER ============
-export const createUser = (username, avatarPath = null, bio = null, designation = null) =>
-    invoke('create_user', { input: { username, avatar_path: avatarPath, bio, designation } });
+// profile: { email, phone, department, pronouns, timezone } — omitted fields are kept, '' clears one
+export const createUser = (username, avatarPath = null, bio = null, designation = null, profile = {}) =>
+    invoke('create_user', { input: { username, avatar_path: avatarPath, bio, designation, ...profile } });
 export const getUser = (id) => invoke('get_user', { id });
-export const getAllUsers = () => invoke('get_all_users');
+// tag: only contacts carrying it (see contact tags)
+export const getAllUsers = (tag = null) => invoke('get_all_users', { tag });
 export const getLocalUser = () => invoke('get_local_user');
+// Custom status; expiresAt is an ISO timestamp or null. No emoji and no text clears it.
+export const setStatus = (emoji, text, expiresAt = null) => invoke('set_status', { emoji, text, expiresAt });
+export const clearStatus = () => invoke('clear_status');
+export const getStatus = () => invoke('get_status');
+// Invisible mode (visible = false): we still see peers and get messages, but aren't announced
+export const setVisibility = (visible) => invoke('set_visibility', { visible });
+export const getVisibility = () => invoke('get_visibility');
+// Off by default: signaling and file requests from public addresses are dropped
+export const getAllowWan = () => invoke('get_allow_wan');
+export const setAllowWan = (allow) => invoke('set_allow_wan', { allow });
+// imageData: data URL or base64; cropped/resized to a 256px PNG. Returns its file server URL
 export const saveAvatar = (imageData) => invoke('save_avatar', { imageData });
 export const deleteUser = (userId) => invoke('delete_user', { userId });
 
@@ -41,13 +54,45 @@ export const getMessagesPaginated = (peerId, before = null, limit = 50) =>
 export const getNewMessagesSince = (peerId, since) => invoke('get_new_messages_since', { peerId, since });
 export const markMessageRead = (messageId) => invoke('mark_message_read', { messageId });
 export const markMessagesReadFromPeer = (peerId) => invoke('mark_messages_read_from_peer', { peerId });
+// Polling fallbacks: unread counts are pushed on onBackendEvent ({ kind: 'unread_counts' })
 export const getUnreadCount = () => invoke('get_unread_count');
 export const getUnreadCountFromPeer = (peerId) => invoke('get_unread_count_from_peer', { peerId });
+// Initial load; later changes arrive as { kind: 'last_messages' }
 export const getLastMessages = () => invoke('get_last_messages');
-export const getSharedMedia = (peerId, mediaType = null) => invoke('get_shared_media', { peerId, mediaType });
-export const getUsersWithMessages = () => invoke('get_users_with_messages');
-export const deleteMessage = (messageId) => invoke('delete_message', { messageId });
-export const deleteAllMessagesWithPeer = (peerId) => invoke('delete_all_messages_with_peer', { peerId });
+// filter: { media_type?, sender_id?, since?, until?, limit? (default 100), before?, before_lamport?,
+// before_id? } where the before* cursor is the created_at, lamport and id of the last item loaded
+export const getSharedMedia = (peerId, mediaType = null, filter = null) =>
+    invoke('get_shared_media', { peerId, mediaType, filter });
+// [{ message_type, count }] for the same filters
+export const getSharedMediaSummary = (peerId, filter = null) => invoke('get_shared_media_summary', { peerId, filter });
+export const getUsersWithMessages = (tag = null) => invoke('get_users_with_messages', { tag });
+// Pass a peer id or a group id: { total, sent, received, media, by_hour, by_weekday, busiest_hour,
+// busiest_weekday, avg_response_secs_mine, avg_response_secs_theirs, ... }
+export const getChatStats = ({ peerId = null, groupId = null }) => invoke('get_chat_stats', { peerId, groupId });
+// secure: overwrite the message's files before deleting them and skip the undo window
+export const deleteMessage = (messageId, secure = false) => invoke('delete_message', { messageId, secure });
+export const restoreMessage = (messageId) => invoke('restore_message', { messageId });
+export const deleteForEveryone = (messageId) => invoke('delete_for_everyone', { messageId });
+export const deleteMessages = (ids) => invoke('delete_messages', { ids });
+export const markMessagesRead = (ids) => invoke('mark_messages_read', { ids });
+export const forwardMessages = (ids, target) => invoke('forward_messages', { ids, target });
+// One direct message to every contact tagged `tag`; `skipped` in the result lists contacts left out
+// because they need PIN pairing first
+export const broadcastToTag = (tag, content) => invoke('broadcast_to_tag', { tag, content });
+// Contact tags: lowercase letters, digits, '-' and '_'. getUserTags -> { userId: [tags] }
+export const addUserTag = (userId, tag) => invoke('add_user_tag', { userId, tag });
+export const removeUserTag = (userId, tag) => invoke('remove_user_tag', { userId, tag });
+export const tagUsers = (userIds, tag) => invoke('tag_users', { userIds, tag });
+export const getUserTags = () => invoke('get_user_tags');
+export const getAllTags = () => invoke('get_all_tags');
+export const onContactTagsChanged = (handler) => listen('contact-tags-changed', handler);
+export const deleteAllMessagesWithPeer = (peerId, secure = false) => invoke('delete_all_messages_with_peer', { peerId, secure });
+// Clears history on both sides; confirmed must be true. Resolves to whether the peer was reached.
+export const clearChatForBoth = (peerId, confirmed) => invoke('clear_chat_for_both', { peerId, confirmed });
+export const acceptConversationClear = (peerId, clearedAt) => invoke('accept_conversation_clear', { peerId, clearedAt });
+// 'honor' | 'ask' | 'ignore' — what to do when a peer clears the chat on both sides
+export const getConversationClearPolicy = () => invoke('get_conversation_clear_policy');
+export const setConversationClearPolicy = (policy) => invoke('set_conversation_clear_policy', { policy });
 
 // ============ CHAT RELAY ============
 export const relayChatMessage = (peerId, messageId, content, messageType = 'text', senderName = '') =>
@@ -63,33 +108,168 @@ export const getOnlinePeers = () => invoke('get_online_peers');
 export const restartDiscovery = (username, port) => invoke('restart_discovery', { username, port });
 
 // ============ SIGNALING ============
-export const startSignaling = (port = 45678) => invoke('start_signaling', { port });
+// Without a port, signaling uses the signaling_port setting; resolves to the port it bound
+export const startSignaling = (port = null) => invoke('start_signaling', { port });
 export const registerPeer = (peerId, ip, port) => invoke('register_peer', { peerId, ip, port });
+export const getPeerCapabilities = (peerId) => invoke('get_peer_capabilities', { peerId });
 export const sendSignalingMessage = (peerId, message) => invoke('send_signaling_message', { peerId, message });
 
 // ============ ENCRYPTION ============
 export const establishSession = (peerId, peerPublicKey) => invoke('establish_session', { peerId, peerPublicKey });
-export const encryptMessage = (peerId, message) => invoke('encrypt_message', { peerId, message });
-export const decryptMessage = (peerId, envelope) => invoke('decrypt_message', { peerId, envelope });
+// messageId is authenticated along with the ciphertext (a random one when omitted)
+export const encryptMessage = (peerId, message, messageId = null) => invoke('encrypt_message', { peerId, message, messageId });
+// With messageId, the envelope has to have been encrypted for that message
+export const decryptMessage = (peerId, envelope, messageId = null) => invoke('decrypt_message', { peerId, envelope, messageId });
 export const getPublicKey = () => invoke('get_public_key');
+export const getIdentityKey = () => invoke('get_identity_key');
+// After a contact reinstalled: accept the new identity key it announces
+export const forgetIdentityPin = (peerId) => invoke('forget_identity_pin', { peerId });
+export const rotateSessionKey = () => invoke('rotate_session_key');
 
 // ============ FILE SERVER ============
 export const storeSharedFile = (fileId, dataUrl, fileName) => invoke('store_shared_file', { fileId, dataUrl, fileName });
+export const getSharedFileChecksum = (fileId) => invoke('get_shared_file_checksum', { fileId });
+// LAN port, the one file messages tell peers about
 export const getFileServerPort = () => invoke('get_file_server_port');
+// Localhost-only port for this app's own file URLs (previews, avatars)
+export const getLocalFileServerPort = () => invoke('get_local_file_server_port');
+// Peers need this token to fetch a file we share; it goes in the file message as "token"
+export const getSharedFileToken = (fileId) => invoke('get_shared_file_token', { fileId });
+// URL of a file on a peer's file server, from a file message's port and token
+export const peerFileUrl = (ip, port, fileId, token = null) =>
+    `http://${ip.split(':')[0]}:${port}/file/${fileId}${token ? `?token=${encodeURIComponent(token)}` : ''}`;
+export const localFileUrl = (port, fileId) => `http://127.0.0.1:${port}/file/${fileId}`;
 
 /// Read file directly from disk as data URL (bypasses HTTP server)
 // Provide both camelCase and snake_case keys to be robust to argument-name mapping.
 export const readFileAsDataUrl = (fileId) => invoke('read_file_as_data_url', { fileId, file_id: fileId });
 
 // ============ FILE DOWNLOAD & MANAGEMENT ============
-export const autoDownloadFile = (url, senderName, fileName, fileType, messageId = null) =>
-    invoke('auto_download_file', { url, senderName, fileName, fileType, messageId });
+// Queues the download and resolves with its id; onDownloadFinished reports when it lands
+export const autoDownloadFile = (url, senderName, fileName, fileType, messageId = null, checksum = null) =>
+    invoke('auto_download_file', { url, senderName, fileName, fileType, messageId, checksum });
+// { id, fileId, fileName, messageId, path, error } — path on success, error after the last retry
+export const onDownloadFinished = (handler) => listen('download-finished', handler);
 export const openFileLocation = (path) => invoke('open_file_location', { path });
+// Rejects with 'Opening this file type needs confirmation' for executables; call again with confirmed = true
+export const openFile = (path, confirmed = false) => invoke('open_file', { path, confirmed });
 export const saveFileWithDialog = (url, defaultName) => invoke('save_file_with_dialog', { url, defaultName });
+// Every auto-download, save-as and received transfer, newest first.
+// filter: { status?: 'downloading'|'complete'|'failed'|'cancelled', kind?: 'auto'|'save_as'|'transfer', source_peer?, limit? }
+export const getDownloads = (filter = null) => invoke('get_downloads', { filter });
+export const retryDownload = (id) => invoke('retry_download', { id });
+export const approveDownload = (id, approve) => invoke('approve_download', { id, approve });
+export const getDownloadPolicy = () => invoke('get_download_policy');
+export const setDownloadPolicy = (policy) => invoke('set_download_policy', { policy });
+export const getScanConfig = () => invoke('get_scan_config');
+export const setScanConfig = (config) => invoke('set_scan_config', { config });
+// Translation hook: config { backend: 'none' | 'libretranslate' | 'command', url, api_key, command,
+// args ("{target}" stands for the language), timeout_secs }. translateMessage ->
+// { message_id, original, translated, target_lang, source_lang, cached }
+export const translateMessage = (messageId, targetLang) => invoke('translate_message', { messageId, targetLang });
+export const getTranslationConfig = () => invoke('get_translation_config');
+export const setTranslationConfig = (config) => invoke('set_translation_config', { config });
+// Voice transcription: config { enabled, command, args ("{file}" stands for the audio file),
+// timeout_secs }. Received audio is transcribed in the background; "transcription-ready" carries
+// { download_id, message_id, transcript }. Search hits are { download_id, message_id, kind,
+// file_name, path, snippet }; download_id is null for files we sent
+export const transcribeAttachment = (downloadId) => invoke('transcribe_attachment', { downloadId });
+export const searchAttachmentText = (query, limit) => invoke('search_attachment_text', { query, limit });
+export const getTranscriptionConfig = () => invoke('get_transcription_config');
+export const setTranscriptionConfig = (config) => invoke('set_transcription_config', { config });
+export const onTranscriptionReady = (handler) => listen('transcription-ready', handler);
+// Image OCR: config { enabled, command, args ("{file}" stands for the image), include_sent,
+// timeout_secs }. The text lands in searchAttachmentText with kind 'ocr'; "ocr-ready" carries
+// { download_id, message_id, text }
+export const extractImageText = (messageId) => invoke('extract_image_text', { messageId });
+export const getOcrConfig = () => invoke('get_ocr_config');
+export const setOcrConfig = (config) => invoke('set_ocr_config', { config });
+export const onOcrReady = (handler) => listen('ocr-ready', handler);
+// Outgoing image metadata: { strip, keep_orientation, keep_color_profile }; on by default, removes
+// EXIF/GPS, XMP, IPTC and comments from JPEG, PNG and WebP before they're shared
+export const getImageMetadataConfig = () => invoke('get_image_metadata_config');
+export const setImageMetadataConfig = (config) => invoke('set_image_metadata_config', { config });
+// Outgoing image compression: { mode: 'original' | 'balanced' | 'data_saver' }. A compressed image's
+// file message carries original: { fileId, token, size } for "download original"; for files shared
+// with storeSharedFile, getImageOriginal(fileId) returns the same (null when sent as is)
+export const getImageCompressionConfig = () => invoke('get_image_compression_config');
+export const setImageCompressionConfig = (config) => invoke('set_image_compression_config', { config });
+export const getImageOriginal = (fileId) => invoke('get_image_original', { fileId });
+// Video preparation with ffmpeg: preset '1080p' | '720p' | '480p', trim { start, end } in seconds.
+// Resolves to { job_id, path, size, original_size, duration }; send path with sendFile. Progress:
+// "video-prepare-progress" { job_id, source, percent, stage }. cancelVideoPrepare also drops a
+// finished job's video when it won't be sent.
+export const prepareVideoForSend = (path, preset, trim) => invoke('prepare_video_for_send', { path, preset, trim });
+export const cancelVideoPrepare = (jobId) => invoke('cancel_video_prepare', { jobId });
+export const getVideoTranscodeConfig = () => invoke('get_video_transcode_config');
+export const setVideoTranscodeConfig = (config) => invoke('set_video_transcode_config', { config });
+export const onVideoPrepareProgress = (handler) => listen('video-prepare-progress', handler);
+// First-page previews of PDF and Office documents. Resolves to a local URL of the PNG, or null when
+// there's none; peers fetch ours from http://{IP}:port/thumb/<fileId>?token=<token>.
+// Config: { enabled, pdftoppm, office, size, timeout_secs }
+export const getDocumentPreview = (fileId) => invoke('get_document_preview', { fileId });
+export const getPreviewConfig = () => invoke('get_preview_config');
+export const setPreviewConfig = (config) => invoke('set_preview_config', { config });
+// What's inside a shared .zip before downloading it (only its directory is fetched). Pass the file
+// message's url with the sender's IP filled in, or a fileId (ours, or a download awaiting approval).
+// Resolves to { entries: [{ name, size, compressed_size, is_dir, encrypted }], file_count,
+// total_size, archive_size, truncated }
+export const peekArchive = ({ url, fileId } = {}) => invoke('peek_archive', { url, fileId });
+// Link archiving (opt-in): pages linked from text messages are saved as plain HTML snapshots.
+// Snapshot: { id, message_id, url, title, excerpt, path, status: 'saved' | 'failed', error, created_at };
+// readLinkSnapshot returns the saved HTML (show it in a sandboxed iframe via srcdoc).
+// Config: { enabled, include_received, max_links, timeout_secs }
+export const getLinkSnapshots = (messageId) => invoke('get_link_snapshots', { messageId });
+export const readLinkSnapshot = (id) => invoke('read_link_snapshot', { id });
+export const snapshotLink = (messageId, url) => invoke('snapshot_link', { messageId, url });
+export const deleteLinkSnapshot = (id) => invoke('delete_link_snapshot', { id });
+export const getLinkSnapshotConfig = () => invoke('get_link_snapshot_config');
+export const setLinkSnapshotConfig = (config) => invoke('set_link_snapshot_config', { config });
+// Scheduled backups of the database and settings.
+// Config: { enabled, frequency: 'daily' | 'weekly', retention, destination } ('' = app data folder).
+// History entries: { id, started_at, finished_at, trigger: 'scheduled' | 'manual', status: 'ok' | 'failed',
+// path, size, error, remote, remote_error }; each run also emits "backup-finished" with its entry
+export const getBackupConfig = () => invoke('get_backup_config');
+export const setBackupConfig = (config) => invoke('set_backup_config', { config });
+export const runBackupNow = () => invoke('run_backup_now');
+export const getBackupHistory = (limit) => invoke('get_backup_history', { limit });
+export const onBackupFinished = (handler) => listen('backup-finished', handler);
+// Remote copies of each backup. Target: { kind: 'none' } | { kind: 'webdav', url, username } |
+// { kind: 's3', endpoint, region, bucket, prefix, access_key_id, path_style }; https only. The
+// password or secret key and the passphrase uploads are encrypted with go to the OS keystore; pass
+// null to keep the stored ones. getBackupTarget resolves to { target, has_secret, has_passphrase }.
+// Restoring (passphrase null: the stored one) replaces the database and stops networking: re-run
+// init on "database-restored" ({ name, previous })
+export const getBackupTarget = () => invoke('get_backup_target');
+export const setBackupTarget = (target, secret = null, passphrase = null) => invoke('set_backup_target', { target, secret, passphrase });
+export const listRemoteBackups = () => invoke('list_remote_backups');
+export const restoreRemoteBackup = (name, passphrase = null) => invoke('restore_remote_backup', { name, passphrase });
+export const onDatabaseRestored = (handler) => listen('database-restored', handler);
+// Peer clock offsets, measured over ping/pong every 15 minutes: { peer_id, offset_ms (theirs minus
+// ours), rtt_ms, measured_at, skewed }. Incoming timestamps from skewed peers are moved onto our clock.
+// Config: { compensate, tolerance_secs }. "peer-clock-skew" fires when a peer is first found skewed
+export const getPeerClocks = () => invoke('get_peer_clocks');
+export const measurePeerClock = (peerId) => invoke('measure_peer_clock', { peerId });
+export const getClockSkewConfig = () => invoke('get_clock_skew_config');
+export const setClockSkewConfig = (config) => invoke('set_clock_skew_config', { config });
+export const onPeerClockSkew = (handler) => listen('peer-clock-skew', handler);
+// Message delivery state: every message carries status, one of 'queued', 'sending', 'sent', 'retrying',
+// 'failed', 'delivered', 'read' (is_read/is_delivered follow it). Changes fire "message-status-changed"
+// { message_id, status }
+export const getMessageStatus = (messageId) => invoke('get_message_status', { messageId });
+export const onMessageStatusChanged = (handler) => listen('message-status-changed', handler);
+// Delivery queue retry policy: { max_attempts (0 = no limit), backoff_secs: [seconds after attempt 1, 2, …;
+// the last repeats], give_up: 'keep_queued' | 'mark_failed' }. retryMessage starts a message's attempts over
+export const getDeliveryRetryPolicy = () => invoke('get_delivery_retry_policy');
+export const setDeliveryRetryPolicy = (policy) => invoke('set_delivery_retry_policy', { policy });
+export const retryMessage = (messageId) => invoke('retry_message', { messageId });
+export const getStorageReserve = () => invoke('get_storage_reserve');
+export const setStorageReserve = (megabytes) => invoke('set_storage_reserve', { megabytes });
 export const renameUserDownloadFolder = (oldName, newName) => invoke('rename_user_download_folder', { oldName, newName });
 export const getPingoDownloadsBase = () => invoke('get_pingo_downloads_base');
-export const checkFileDownloaded = (senderName, fileName, fileType) =>
-    invoke('check_file_downloaded', { senderName, fileName, fileType });
+export const checkFileDownloaded = (senderName, fileName, fileType, senderId = null) =>
+    invoke('check_file_downloaded', { senderName, fileName, fileType, senderId });
+export const getDownloadFolderNames = () => invoke('get_download_folder_names');
 export const getLocalFileUrl = (fileId) => invoke('get_local_file_url', { fileId });
 // Find the raw file stored by the sender (shared_files dir, by fileId prefix)
 export const getSharedFilePath = (fileId) => invoke('get_shared_file_path', { fileId, file_id: fileId });
@@ -99,12 +279,39 @@ export const prepareFileSend = (filePath) => invoke('prepare_file_send', { fileP
 export const prepareFileReceive = (metadata) => invoke('prepare_file_receive', { metadata });
 export const getFileChunk = (transferId, chunkIndex) => invoke('get_file_chunk', { transferId, chunkIndex });
 export const receiveFileChunk = (chunk) => invoke('receive_file_chunk', { chunk });
+// Polling fallback: progress is pushed on onTransferProgress (and onBackendEvent { kind: 'transfer_progress' })
 export const getTransferProgress = (transferId) => invoke('get_transfer_progress', { transferId });
+// progress: { transfer_id, chunks_completed, total_chunks, bytes_transferred, total_bytes, percentage,
+//             is_sender, status: 'active'|'complete'|'failed'|'cancelled' }, for both directions
+export const onTransferProgress = (handler) => listen('transfer-progress', handler);
+// Incoming file offers wait for an answer; receiving is refused until one is accepted
+// request: { transfer_id, peer_id, sender_name, file_name, file_size, file_type, received_at }
+export const getFileTransferRequests = () => invoke('get_file_transfer_requests');
+export const respondToFileTransfer = (transferId, accept) => invoke('respond_to_file_transfer', { transferId, accept });
+export const onFileTransferRequested = (handler) => listen('file-transfer-requested', handler);
+export const onFileTransferRequestsChanged = (handler) => listen('file-transfer-requests-changed', handler);
+export const onFileTransferRequestExpired = (handler) => listen('file-transfer-request-expired', handler);
+// { peer_id, transfer_id, accepted }: the answer to a file we offered
+export const onFileTransferResponse = (handler) => listen('file-transfer-response', handler);
+// Send a file from disk in one call: the backend offers it, waits for acceptance and delivers it
+// (chunks over signaling for small files, HTTP for large ones). Returns the stored message.
+export const sendFileFromPath = (peerId, path) => invoke('send_file', { peerId, path });
+// { transfer_id, peer_id, message_id, transport: 'chunks'|'http',
+//   stage: 'requested'|'accepted'|'declined'|'sending'|'retrying'|'sent'|'complete'|'failed', error }
+export const onFileSendStatus = (handler) => listen('file-send-status', handler);
 export const getMissingChunks = (transferId) => invoke('get_missing_chunks', { transferId });
 export const completeTransfer = (transferId) => invoke('complete_transfer', { transferId });
 export const cancelTransfer = (transferId) => invoke('cancel_transfer', { transferId });
 
 // ============ SETTINGS ============
+// Typed settings: getConfig -> every known setting with defaults applied; updateConfig takes
+// only the fields to change, validates them and returns the new config
+export const getConfig = () => invoke('get_config');
+export const updateConfig = (partial) => invoke('update_config', { partial });
+// { changed: [setting names], config }
+export const onSettingsChanged = (handler) => listen('settings-changed', handler);
+// Fired when a linked device's newer values for synced settings were applied: { device_id, keys }
+export const onSettingsSynced = (handler) => listen('settings-synced', handler);
 export const setSetting = (key, value) => invoke('set_setting', { key, value });
 export const getSetting = (key) => invoke('get_setting', { key });
 export const getAllSettings = () => invoke('get_all_settings');
@@ -112,12 +319,183 @@ export const getAllSettings = () => invoke('get_all_settings');
 // ============ NOTIFICATIONS ============
 export const toggleNotificationsMute = () => invoke('toggle_notifications_mute');
 export const isNotificationsMuted = () => invoke('is_notifications_muted');
+export const setDoNotDisturb = (enabled) => invoke('set_do_not_disturb', { enabled });
+export const getDoNotDisturb = () => invoke('get_do_not_disturb');
+// chatId: peer device_id or group_id
+export const setChatMuted = (chatId, muted) => invoke('set_chat_muted', { chatId, muted });
+export const getChatMuted = (chatId) => invoke('get_chat_muted', { chatId });
+// Per-chat settings: key is 'nickname' | 'color' (#rrggbb) | 'wallpaper' | 'notifications'
+// ('default' | 'all' | 'muted'); a null value resets it. getChatSettings() without an id lists every chat's.
+export const setChatSetting = (chatId, key, value) => invoke('set_chat_setting', { chatId, key, value });
+export const getChatSettings = (chatId = null) => invoke('get_chat_settings', { chatId });
+export const onChatSettingsChanged = (handler) => listen('chat-settings-changed', handler);
+// Chat folders: a chat (peer device_id or group_id) can be in several folders. Folder unread totals
+// also arrive as by_folder in the unread_counts backend event.
+export const getChatFolders = () => invoke('get_chat_folders');
+export const createChatFolder = (name, color = null) => invoke('create_chat_folder', { name, color });
+export const updateChatFolder = (folderId, name, color = null) => invoke('update_chat_folder', { folderId, name, color });
+export const deleteChatFolder = (folderId) => invoke('delete_chat_folder', { folderId });
+export const assignChatToFolder = (chatId, folderId) => invoke('assign_chat_to_folder', { chatId, folderId });
+export const removeChatFromFolder = (chatId, folderId) => invoke('remove_chat_from_folder', { chatId, folderId });
+export const getChatsByFolder = (folderId) => invoke('get_chats_by_folder', { folderId });
+export const getChatFolderAssignments = () => invoke('get_chat_folder_assignments');
+export const onChatFoldersChanged = (handler) => listen('chat-folders-changed', handler);
+// Keyword alerts: matched as whole words, any case, even in muted chats
+export const getAlertKeywords = () => invoke('get_alert_keywords');
+export const addAlertKeyword = (keyword) => invoke('add_alert_keyword', { keyword });
+export const removeAlertKeyword = (id) => invoke('remove_alert_keyword', { id });
+export const notificationReply = (peerId) => invoke('notification_reply', { peerId });
+export const notificationMarkRead = (peerId) => invoke('notification_mark_read', { peerId });
+
+// ============ NOTIFICATION SOUNDS ============
+// sound ids: bundled ('chime', 'ping', ...), imported ('custom:<file>') or 'none'
+export const listSounds = () => invoke('list_sounds');
+export const importSound = (filePath) => invoke('import_sound', { filePath });
+export const deleteSound = (soundId) => invoke('delete_sound', { soundId });
+export const previewSound = (soundId) => invoke('preview_sound', { soundId });
+export const setDefaultSound = (soundId) => invoke('set_default_sound', { soundId });
+// soundId = null clears the chat override
+export const setChatSound = (chatId, soundId) => invoke('set_chat_sound', { chatId, soundId });
+export const getChatSound = (chatId) => invoke('get_chat_sound', { chatId });
 
 // ============ WINDOW ============
 export const minimizeToTray = () => invoke('minimize_to_tray');
 export const showWindow = () => invoke('show_window');
 export const isWindowVisible = () => invoke('is_window_visible');
 
+// ============ QUICK REPLY ============
+export const openQuickReplyWindow = (peerId) => invoke('open_quick_reply_window', { peerId });
+export const closeQuickReplyWindow = () => invoke('close_quick_reply_window');
+export const sendQuickReply = (peerId, content) => invoke('send_quick_reply', { peerId, content });
+
+// ============ POP-OUT CHAT WINDOWS ============
+export const openChatWindow = (peerId) => invoke('open_chat_window', { peerId });
+export const closeChatWindow = (peerId) => invoke('close_chat_window', { peerId });
+export const getOpenChatWindows = () => invoke('get_open_chat_windows');
+
+// ============ GLOBAL HOTKEYS ============
+// action: 'toggle_window' | 'quick_reply'; an empty accelerator disables the shortcut
+export const getHotkeys = () => invoke('get_hotkeys');
+export const setHotkey = (action, accelerator) => invoke('set_hotkey', { action, accelerator });
+export const resetHotkeys = () => invoke('reset_hotkeys');
+
+// ============ PROFILES ============
+export const listProfiles = () => invoke('list_profiles');
+export const getActiveProfile = () => invoke('get_active_profile');
+export const createProfile = (name) => invoke('create_profile', { name });
+export const switchProfile = (profileId) => invoke('switch_profile', { profileId });
+
+// ============ LINKED DEVICES ============
+export const createLinkCode = () => invoke('create_link_code');
+export const linkDevice = (peerId, code) => invoke('link_device', { peerId, code });
+export const getLinkedDevices = () => invoke('get_linked_devices');
+export const unlinkDevice = (deviceId) => invoke('unlink_device', { deviceId });
+export const syncLinkedDevices = () => invoke('sync_linked_devices');
+
+// ============ QR PAIRING ============
+// generatePairingQr -> { payload, image (PNG data URL), fingerprint }
+export const generatePairingQr = () => invoke('generate_pairing_qr');
+// pairFromQr -> { peer, fingerprint, verified }; verified (and trusted) only when the code carries an identity key
+export const pairFromQr = (payload) => invoke('pair_from_qr', { payload });
+// getIdentityQr -> { payload, image (PNG data URL), fingerprint }; verifyIdentityQr trusts the peer on a match
+export const getIdentityQr = () => invoke('get_identity_qr');
+export const verifyIdentityQr = (payload) => invoke('verify_identity_qr', { payload });
+
+// ============ TRUSTED PEERS ============
+// Trust state -> { device_id, trusted, fingerprint, has_session }; pairing also marks peers trusted
+export const trustPeer = (peerId) => invoke('trust_peer', { peerId });
+export const untrustPeer = (peerId) => invoke('untrust_peer', { peerId });
+export const getTrustState = (peerId) => invoke('get_trust_state', { peerId });
+export const getTrustedPeers = () => invoke('get_trusted_peers');
+// policy: { auto_accept_files, auto_sessions, group_invites_trusted_only }
+export const getTrustPolicy = () => invoke('get_trust_policy');
+export const setTrustPolicy = (policy) => invoke('set_trust_policy', { policy });
+export const onTrustChanged = (handler) => listen('trust-changed', handler);
+
+// ============ PRIVACY MODE / MESSAGE REQUESTS ============
+// With privacy mode on, messages, files and group invites from unknown peers are held as requests
+// request: { id, peer_id, sender_name, kind: 'message'|'file'|'group_invite', preview, created_at }
+export const getPrivacyMode = () => invoke('get_privacy_mode');
+export const setPrivacyMode = (enabled) => invoke('set_privacy_mode', { enabled });
+export const getMessageRequests = () => invoke('get_message_requests');
+export const acceptMessageRequest = (peerId) => invoke('accept_message_request', { peerId });
+export const declineMessageRequest = (peerId) => invoke('decline_message_request', { peerId });
+export const onMessageRequest = (handler) => listen('message-request', handler);
+export const onMessageRequestsChanged = (handler) => listen('message-requests-changed', handler);
+
+// ============ PIN PAIRING ============
+// When required, nothing is exchanged with a new peer until one side shows a PIN and the other enters it
+export const getRequirePinPairing = () => invoke('get_require_pin_pairing');
+export const setRequirePinPairing = (required) => invoke('set_require_pin_pairing', { required });
+export const isPinPaired = (peerId) => invoke('is_pin_paired', { peerId });
+// Returns the PIN to show to the peer's user
+export const startPinPairing = (peerId) => invoke('start_pin_pairing', { peerId });
+export const submitPairingPin = (peerId, pin) => invoke('submit_pairing_pin', { peerId, pin });
+export const cancelPinPairing = (peerId) => invoke('cancel_pin_pairing', { peerId });
+// { peer_id, username }: a peer asks to pair; prompt for the PIN on its screen
+export const onPinPairingRequested = (handler) => listen('pin-pairing-requested', handler);
+// { peer_id, paired, error }
+export const onPinPairingResult = (handler) => listen('pin-pairing-result', handler);
+
+// ============ HISTORY IMPORT ============
+// filePath: WhatsApp .txt/.zip export or Telegram result.json
+export const previewImport = (filePath) => invoke('preview_import', { filePath });
+export const commitImport = (importId, selfName, peerId) => invoke('commit_import', { importId, selfName, peerId });
+export const cancelImport = (importId) => invoke('cancel_import', { importId });
+
+// ============ AUTOMATION API ============
+// Opt-in localhost HTTP/WebSocket API; status: { enabled, running, port, token }
+export const getAutomationApi = () => invoke('get_automation_api');
+export const setAutomationApiEnabled = (enabled) => invoke('set_automation_api_enabled', { enabled });
+export const regenerateAutomationToken = () => invoke('regenerate_automation_token');
+
+// ============ WEBHOOKS ============
+// input: { id?, url, peer_id?, group_id?, message_type?, enabled? }; empty filters match everything
+export const getWebhooks = () => invoke('get_webhooks');
+export const saveWebhook = (input) => invoke('save_webhook', { input });
+export const deleteWebhook = (id) => invoke('delete_webhook', { id });
+export const testWebhook = (id) => invoke('test_webhook', { id });
+
+// ============ AUTO-RESPONDER ============
+// input: { id?, name?, pattern?, match_mode? ('any'|'contains'|'exact'|'starts_with'), peer_ids?, days? (1=Mon..7=Sun),
+//          start_time?/end_time? ('HH:MM'), active_from?/active_until? (ISO), response, cooldown_minutes?, enabled? }
+// response may use {name}, {message} and {until}
+export const getAutoReplyRules = () => invoke('get_auto_reply_rules');
+export const saveAutoReplyRule = (input) => invoke('save_auto_reply_rule', { input });
+export const deleteAutoReplyRule = (id) => invoke('delete_auto_reply_rule', { id });
+export const setAutoReplyRuleEnabled = (id, enabled) => invoke('set_auto_reply_rule_enabled', { id, enabled });
+
+// ============ LOGGING / DIAGNOSTICS ============
+// level: 'error' | 'warn' | 'info' | 'debug' | 'trace'
+export const getLogLevel = () => invoke('get_log_level');
+export const setLogLevel = (level) => invoke('set_log_level', { level });
+export const getRecentLogs = (limit) => invoke('get_recent_logs', { limit });
+// Returns { path, report }; path is a redacted zip in the downloads folder for bug reports
+export const generateDiagnosticsReport = () => invoke('generate_diagnostics_report');
+// Returns { peer_id, address, checks: [{ name, ok, detail, latency_ms }], diagnosis }
+export const testPeerConnectivity = (peerId) => invoke('test_peer_connectivity', { peerId });
+// Rebind sockets and resend queued messages; emits 'system-resumed' when done (also fired after sleep)
+export const reconnectNetwork = () => invoke('reconnect_network');
+export const onSystemResumed = (handler) => listen('system-resumed', handler);
+// Returns { ok, problems, path, last_recovery }; last_recovery is set after an automatic repair
+export const checkDatabase = () => invoke('check_database');
+// Rebuilds the database from what is readable; the damaged file is kept in <app dir>/corrupt/
+export const repairDatabase = () => invoke('repair_database');
+export const onDatabaseRecovered = (handler) => listen('database-recovered', handler);
+
+// ============ BACKEND EVENT STREAM ============
+// One typed stream instead of polling; event.kind is one of 'messages_changed' { peer_id },
+// 'unread_counts' { total, by_peer }, 'last_messages' { items }, 'transfer_progress' { progress },
+// 'message_status_changed' { message_id, status }
+export const onBackendEvent = (handler) => listen('backend-event', handler);
+// Push the current unread counts and last messages now (after subscribing)
+export const refreshBackendSummaries = () => invoke('refresh_backend_summaries');
+
+// ============ OUTBOUND HTTP ============
+// settings: { proxy_url?, no_proxy?, ca_cert_path? }; LAN addresses always bypass the proxy
+export const getHttpSettings = () => invoke('get_http_settings');
+export const setHttpSettings = (settings) => invoke('set_http_settings', { settings });
+
 // ============ UTILITY ============
 export const getDeviceId = () => invoke('get_device_id');
 export const generateUuid = () => invoke('generate_uuid');
@@ -133,10 +511,27 @@ export const saveNote = (input) => invoke('save_note', { input });
 export const getAllNotes = () => invoke('get_all_notes');
 export const deleteNote = (id) => invoke('delete_note', { id });
 export const toggleNotePin = (id) => invoke('toggle_note_pin', { id });
+export const lockNote = (noteId, passphrase) => invoke('lock_note', { noteId, passphrase });
+export const unlockNote = (noteId, passphrase) => invoke('unlock_note', { noteId, passphrase });
+export const removeNoteLock = (noteId, passphrase) => invoke('remove_note_lock', { noteId, passphrase });
+export const addNoteAttachment = (noteId, filePath) => invoke('add_note_attachment', { noteId, filePath });
+export const getNoteAttachments = (noteId) => invoke('get_note_attachments', { noteId });
+export const removeNoteAttachment = (attachmentId) => invoke('remove_note_attachment', { attachmentId });
+export const setNoteReminder = (noteId, remindAt, repeat) => invoke('set_note_reminder', { noteId, remindAt, repeat });
+export const getNoteReminders = () => invoke('get_note_reminders');
+export const clearNoteReminder = (noteId) => invoke('clear_note_reminder', { noteId });
+export const snoozeNoteReminder = (noteId, minutes) => invoke('snooze_note_reminder', { noteId, minutes });
+export const completeNoteReminder = (noteId) => invoke('complete_note_reminder', { noteId });
+export const onNoteReminder = (handler) => listen('note-reminder', handler);
+export const onNotificationOpenNote = (handler) => listen('notification-open-note', handler);
+export const shareNote = (noteId, peerId) => invoke('share_note', { noteId, peerId });
+export const onNoteShared = (handler) => listen('note-shared', handler);
+export const onSharedNoteUpdated = (handler) => listen('shared-note-updated', handler);
 
 // ============ GROUPS ============
-export const createGroup = (name, memberIds, memberNames) =>
-    invoke('create_group', { input: { name, member_ids: memberIds, member_names: memberNames } });
+// kind: 'group' or 'channel' (announcements: only admins post, members start muted)
+export const createGroup = (name, memberIds, memberNames, kind = 'group') =>
+    invoke('create_group', { input: { name, member_ids: memberIds, member_names: memberNames, kind } });
 export const getGroups = () => invoke('get_groups');
 export const getGroupMembers = (groupId) => invoke('get_group_members', { groupId });
 export const sendGroupMessage = (groupId, content, messageType = 'text') =>
@@ -146,16 +541,104 @@ export const deleteGroup = (groupId) => invoke('delete_group', { groupId });
 export const addGroupMember = (groupId, userId, username) => invoke('add_group_member', { groupId, userId, username });
 export const removeGroupMember = (groupId, userId) => invoke('remove_group_member', { groupId, userId });
 export const leaveGroup = (groupId) => invoke('leave_group', { groupId });
+// role: 'admin' or 'member'; admins only
+export const setGroupRole = (groupId, userId, role) => invoke('set_group_role', { groupId, userId, role });
 export const getAllUsersForGroup = () => invoke('get_all_users_for_group');
 
+// ============ MEETING ROSTER ============
+// { meeting_id, host_id, participants: [{ peer_id, username, joined_at }], waiting: [...] }
+export const getMeetingParticipants = (meetingId) => invoke('get_meeting_participants', { meetingId });
+// Waiting room (host only): { meeting_id, peer_id, username } per uninvited join request
+export const onMeetingJoinRequest = (handler) => listen('meeting-join-request', handler);
+export const decideMeetingJoin = (meetingId, peerId, approve) =>
+    invoke('decide_meeting_join', { meetingId, peerId, approve });
+
+// ============ MEETING HISTORY ============
+// Newest first: [{ id, host_id, host_name, started_at, ended_at, duration_secs, participants }]
+export const getMeetingHistory = (limit = 50) => invoke('get_meeting_history', { limit });
+
+// ============ SCHEDULED MEETINGS ============
+// startAt: ISO 8601; invitations are resent until offline participants come back
+export const scheduleMeeting = (title, startAt, participants, durationMinutes = 30) =>
+    invoke('schedule_meeting', { title, startAt, durationMinutes, participants });
+export const getScheduledMeetings = (includePast = false) => invoke('get_scheduled_meetings', { includePast });
+export const onMeetingScheduled = (handler) => listen('meeting-scheduled', handler);
+// Fired 5 minutes before a scheduled meeting starts
+export const onMeetingReminder = (handler) => listen('meeting-reminder', handler);
+export const onNotificationOpenMeeting = (handler) => listen('notification-open-meeting', handler);
+
+// ============ MEETING WHITEBOARD ============
+// op: { kind: 'stroke', points: [[x, y]…], color, width }
+//   | { kind: 'shape', shape: 'rect'|'ellipse'|'line'|'arrow', x, y, w, h, color, fill? }
+//   | { kind: 'clear' }
+export const sendWhiteboardOp = (meetingId, op) => invoke('send_whiteboard_op', { meetingId, op });
+// Replay for late joiners: [{ from, op, at }]
+export const getWhiteboard = (meetingId) => invoke('get_whiteboard', { meetingId });
+// data: PNG data URL of the rendered board; stored on the meeting's history entry
+export const saveWhiteboard = (meetingId, data) => invoke('save_whiteboard', { meetingId, data });
+export const onWhiteboardOp = (handler) => listen('whiteboard-op', handler);
+
+// ============ REMOTE CONTROL ============
+// The viewer of a screen share asks; the sharer must approve before any input is injected.
+// Requests only arrive with the allow_remote_control setting on and while setScreenSharing(true)
+export const requestRemoteControl = (peerId) => invoke('request_remote_control', { peerId });
+export const respondRemoteControl = (sessionId, allow) => invoke('respond_remote_control', { sessionId, allow });
+// event: { kind: 'mouse_move', x, y } (0..1 of the shared screen)
+//   | { kind: 'mouse_button', button: 'left'|'middle'|'right', action: 'press'|'release'|'click' }
+//   | { kind: 'scroll', dx, dy } | { kind: 'key', key: KeyboardEvent.key, action } | { kind: 'text', text }
+export const sendRemoteInput = (sessionId, event) => invoke('send_remote_input', { sessionId, event });
+// Either side can end a session; the sharer uses this to revoke control
+export const stopRemoteControl = (sessionId) => invoke('stop_remote_control', { sessionId });
+export const getRemoteControlSessions = () => invoke('get_remote_control_sessions');
+// Report our screen share starting/stopping; stopping ends sessions controlling this device
+export const setScreenSharing = (sharing) => invoke('set_screen_sharing', { sharing });
+export const onRemoteControlRequest = (handler) => listen('remote-control-request', handler);
+export const onRemoteControlResponse = (handler) => listen('remote-control-response', handler);
+export const onRemoteControlEnded = (handler) => listen('remote-control-ended', handler);
+
+// ============ MEETING RECORDING ============
+// Saved under downloads/Meeting recordings; displayIndex captures our own screen every 2s
+export const startMeetingRecording = (meetingId, participants, displayIndex = null) =>
+    invoke('start_meeting_recording', { meetingId, participants, displayIndex });
+// data: base64 PNG or data URL (e.g. a frame of a remote screen share)
+export const recordMeetingFrame = (meetingId, data) => invoke('record_meeting_frame', { meetingId, data });
+export const stopMeetingRecording = (meetingId) => invoke('stop_meeting_recording', { meetingId });
+export const getMeetingRecording = (meetingId) => invoke('get_meeting_recording', { meetingId });
+// { meeting_id, from, recording } — fired for our own recordings and other participants'
+export const onMeetingRecordingState = (handler) => listen('meeting-recording-state', handler);
+
+// ============ NATIVE MEETINGS ============
+// WebRTC runs in the backend; meetings are a mesh joined through the host
+export const createMeeting = () => invoke('create_meeting');
+export const joinMeeting = (meetingId, hostId) => invoke('join_meeting', { meetingId, hostId });
+export const leaveMeeting = (meetingId) => invoke('leave_meeting', { meetingId });
+export const getMeeting = (meetingId) => invoke('get_meeting', { meetingId });
+// kind: 'audio' (Opus) | 'video' (VP8); returns a track id
+export const sendTrack = (meetingId, kind) => invoke('send_track', { meetingId, kind });
+// data: base64 encoded frame
+export const writeTrackSample = (meetingId, trackId, data, durationMs) =>
+    invoke('write_track_sample', { meetingId, trackId, data, durationMs });
+export const sendMeetingData = (meetingId, data) => invoke('send_meeting_data', { meetingId, data });
+// servers: [{ urls: ['stun:…' | 'turn:…'], username?, credential? }]; TURN needs credentials
+export const getIceServers = () => invoke('get_ice_servers');
+export const setIceServers = (servers) => invoke('set_ice_servers', { servers });
+// Tests the given servers (or the saved ones): [{ url, ok, detail, latency_ms }]
+export const testIceServers = (servers = null) => invoke('test_ice_servers', { servers });
+export const onMeetingPeerState = (handler) => listen('meeting-peer-state', handler);
+export const onMeetingTrack = (handler) => listen('meeting-track', handler);
+export const onMeetingMedia = (handler) => listen('meeting-media', handler);
+export const onMeetingData = (handler) => listen('meeting-data', handler);
+
 // ============ AVATAR MANAGEMENT ============
-export const downloadAndCacheAvatar = (deviceId, remoteUrl, hintName = null) =>
+// avatarHash: the version peers announce; the download is skipped if we already cached it
+export const downloadAndCacheAvatar = (deviceId, remoteUrl, hintName = null, avatarHash = null) =>
     // Tauri requires args to match Rust function parameter names (camelCase). Provide both
     // camelCase and snake_case keys to remain robust across versions.
     invoke('download_and_cache_avatar', {
         deviceId: deviceId,
         remoteUrl: remoteUrl,
         hintName: hintName,
+        avatarHash: avatarHash,
         // Backwards-compatible keys
         device_id: deviceId,
         remote_url: remoteUrl,
@@ -173,17 +656,55 @@ export const registerLocalAvatar = (deviceId, filePath) =>
 
 // ============ DISCOVERY EVENTS ============
 export const onPeerDiscovered = (handler) => listen('peer-discovered', handler);
-export const onPeerUpdated = (handler) => listen('peer-updated', handler);
+// Payload is an array of peers whose presence actually changed, at most one batch per 500ms
+export const onPeersUpdated = (handler) => listen('peers-updated', handler);
 export const onPeerLost = (handler) => listen('peer-lost', handler);
+// { device_id, avatar_hash } — a peer announced an avatar we don't have cached yet
+export const onPeerAvatarChanged = (handler) => listen('peer-avatar-changed', handler);
+export const onDownloadApprovalNeeded = (handler) => listen('download-approval-needed', handler);
+export const onDownloadIntegrityFailed = (handler) => listen('download-integrity-failed', handler);
+export const onFileQuarantined = (handler) => listen('file-quarantined', handler);
+export const onStorageLow = (handler) => listen('storage-low', handler);
+// { status } — ours changed (set, cleared, or expired)
+export const onStatusChanged = (handler) => listen('status-changed', handler);
+// { visible }
+export const onVisibilityChanged = (handler) => listen('visibility-changed', handler);
 
 export const onSignalingMessage = (handler) => listen('signaling-message', handler);
 export const onChatMessageReceived = (handler) => listen('chat-message-received', handler);
+export const onMessageDeleted = (handler) => listen('message-deleted', handler);
+export const onMessagesBulkUpdated = (handler) => listen('messages-bulk-updated', handler);
+export const onConversationCleared = (handler) => listen('conversation-cleared', handler);
+export const onConversationClearRequested = (handler) => listen('conversation-clear-requested', handler);
 export const onUserDeleted = (handler) => listen('user-deleted', handler);
 export const onGroupCreated = (handler) => listen('group-created', handler);
 export const onGroupMessageReceived = (handler) => listen('group-message-received', handler);
+export const onChannelMessageReceived = (handler) => listen('channel-message-received', handler);
+export const onGroupRoleChanged = (handler) => listen('group-role-changed', handler);
 export const onMeetingChatReceived = (handler) => listen('meeting-chat-received', handler);
 export const onGroupMemberAdded = (handler) => listen('group-member-added', handler);
 export const onGroupMemberRemoved = (handler) => listen('group-member-removed', handler);
+export const onChatMessageSent = (handler) => listen('chat-message-sent', handler);
+export const onGroupMessageSent = (handler) => listen('group-message-sent', handler);
+// Fired in the quick reply popup when it is reused for a different peer: { peer_id }
+export const onQuickReplyTarget = (handler) => listen('quick-reply-target', handler);
+// Conversation events routed to a pop-out chat window (incoming and quick-reply messages)
+export const onChatWindowMessage = (handler) => listen('chat-window-message', handler);
+// Native notification clicked: { chat_id, is_group }
+export const onNotificationOpenChat = (handler) => listen('notification-open-chat', handler);
+// { chat_id, is_group, sender_name, keywords, preview }
+export const onKeywordAlert = (handler) => listen('keyword-alert', handler);
+// Messages from a peer marked read outside the chat view (notification action): { peer_id }
+export const onMessagesMarkedRead = (handler) => listen('messages-marked-read', handler);
+// Active profile changed; the backend has stopped networking and expects init_app again
+export const onProfileSwitched = (handler) => listen('profile-switched', handler);
+export const onDeviceLinked = (handler) => listen('device-linked', handler);
+export const onDeviceLinkFailed = (handler) => listen('device-link-failed', handler);
+export const onSyncComplete = (handler) => listen('sync-complete', handler);
+export const onNotesSynced = (handler) => listen('notes-synced', handler);
+export const onPeerPaired = (handler) => listen('peer-paired', handler);
+// Quick reply hotkey pressed with nobody to reply to — open the new-message composer
+export const onComposeNewMessage = (handler) => listen('compose-new-message', handler);
 // File download progress events from Rust (stage: 'downloading'|'saving'|'complete'|'error'|'cached')
 // payload: { fileId, fileName, stage, progress, localPath? }
 export const onFileDownloadProgress = (handler) => listen('file-download-progress', handler);
@@ -37,9 +37,10 @@ export function getLocalAvatarUrl(deviceId) {
  * @param {string} deviceId - User's device ID (unique key)
  * @param {string} remoteUrl - HTTP URL to fetch avatar from
  * @param {string} fileName - Optional name hint (username, email, etc for readability)
+ * @param {string} avatarHash - Optional content hash; the backend skips the download if it has this version
  * @returns {Promise<string>} Local file URL (e.g., app-file://path/to/avatars/user_abc123.png)
  */
-export async function cacheAvatarFromUrl(deviceId, remoteUrl, fileName = null) {
+export async function cacheAvatarFromUrl(deviceId, remoteUrl, fileName = null, avatarHash = null) {
     if (!deviceId || !remoteUrl) {
         console.warn('[Avatar] Invalid params: deviceId or remoteUrl missing');
         return null;
@@ -69,7 +70,7 @@ export async function cacheAvatarFromUrl(deviceId, remoteUrl, fileName = null) {
     // Create download promise
     const downloadPromise = (async () => {
         try {
-            const localUrl = await api.downloadAndCacheAvatar(deviceId, remoteUrl, fileName || deviceId);
+            const localUrl = await api.downloadAndCacheAvatar(deviceId, remoteUrl, fileName || deviceId, avatarHash);
             if (localUrl) {
                 avatarCache.set(deviceId, localUrl);
                 console.log(`[Avatar] Cached avatar for ${deviceId}: ${localUrl}`);
@@ -18,6 +18,8 @@ export const MSG = Object.freeze({
   SCREEN_SHARE_INVITE: 'MeetingScreenShareInvite',
   REJOIN_REQUEST:      'MeetingRejoinRequest',
   PARTICIPANT_LIST:    'MeetingParticipantList',
+  JOIN_REQUEST:        'MeetingJoinRequest',
+  JOIN_DECISION:       'MeetingJoinDecision',
 });
 
 // ─── Send helpers (typed, validated) ─────────────────────────
@@ -134,6 +136,18 @@ export async function sendMeetingRejoinRequest(peerId, from, meetingId, username
   });
 }
 
+// Join with just the meeting code; the host's backend holds us in its waiting room
+// until they answer with a MeetingJoinDecision
+export async function sendMeetingJoinRequest(peerId, from, meetingId, username) {
+  return api.sendSignalingMessage(peerId, {
+    type: MSG.JOIN_REQUEST,
+    from,
+    to: peerId,
+    meeting_id: meetingId,
+    username,
+  });
+}
+
 export async function sendMeetingParticipantList(peerId, from, meetingId, participants) {
   return api.sendSignalingMessage(peerId, {
     type: MSG.PARTICIPANT_LIST,
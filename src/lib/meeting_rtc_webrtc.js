@@ -9,6 +9,7 @@ import {
   sendMeetingScreenShare,
   meetingLog,
 } from './meeting_rtc_api';
+import { setScreenSharing } from './api';
 
 // ─── Constants ───────────────────────────────────────────────
 const ICE_SERVERS = [
@@ -317,6 +318,7 @@ export default class MeetingRTCManager {
       });
       this.localScreenStream = stream;
       this._log('Screen sharing started');
+      setScreenSharing(true).catch(() => { });
 
       // Add track ended handler
       stream.getVideoTracks()[0].onended = () => {
@@ -361,6 +363,7 @@ export default class MeetingRTCManager {
     if (this.localScreenStream) {
       this.localScreenStream.getTracks().forEach(t => t.stop());
       this.localScreenStream = null;
+      setScreenSharing(false).catch(() => { });
     }
 
     // Remove video senders from all peer connections
@@ -422,6 +425,7 @@ export default class MeetingRTCManager {
     if (this.localScreenStream) {
       this.localScreenStream.getTracks().forEach(t => t.stop());
       this.localScreenStream = null;
+      setScreenSharing(false).catch(() => { });
     }
     for (const [pid] of this.peerConnections) {
       this.cleanupPeer(pid);
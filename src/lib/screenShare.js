@@ -68,6 +68,7 @@ export class ScreenShareSession {
 
             this.state = ScreenShareState.SHARING;
             this.onStateChange?.(this.state);
+            api.setScreenSharing(true).catch(() => { });
 
             // Invite all peers
             for (const peerId of peerIds) {
@@ -200,6 +201,7 @@ export class ScreenShareSession {
         if (this.stream) {
             this.stream.getTracks().forEach(track => track.stop());
             this.stream = null;
+            if (this.isHost) api.setScreenSharing(false).catch(() => { });
         }
 
         this.state = ScreenShareState.IDLE;
@@ -33,6 +33,7 @@ export class WebRTCManager {
         this.onPeerDisconnected = null;
         this.onScreenShareReceived = null;
         this.onFileReceived = null;
+        this.outgoingTransfers = new Map(); // transferId -> total chunks
     }
 
     /**
@@ -44,6 +45,8 @@ export class WebRTCManager {
 
         // Listen for signaling messages from Rust backend
         api.onSignalingMessage(this.handleSignalingMessage.bind(this));
+        // The backend reports when the last chunk of an incoming file has been written
+        api.onTransferProgress(this.handleTransferProgress.bind(this));
     }
 
     /**
@@ -410,11 +413,17 @@ export class WebRTCManager {
             success,
         });
 
-        // Check if transfer complete
-        const progress = await api.getTransferProgress(message.transferId);
-        if (progress && progress.chunks_completed === progress.total_chunks) {
-            const verified = await api.completeTransfer(message.transferId);
-            this.onFileReceived?.(message.transferId, verified);
+    }
+
+    /**
+     * Verify an incoming file once all of its chunks are in
+     * @param {Object} progress - transfer-progress payload
+     */
+    async handleTransferProgress(progress) {
+        if (!progress || progress.is_sender || progress.status !== 'active') return;
+        if (progress.chunks_completed === progress.total_chunks) {
+            const verified = await api.completeTransfer(progress.transfer_id);
+            this.onFileReceived?.(progress.transfer_id, verified);
         }
     }
 
@@ -426,6 +435,7 @@ export class WebRTCManager {
     async sendFile(peerId, filePath) {
         // Prepare file
         const metadata = await api.prepareFileSend(filePath);
+        this.outgoingTransfers.set(metadata.transfer_id, metadata.total_chunks);
 
         // Send file request
         this.sendMessage(peerId, {
@@ -473,10 +483,11 @@ export class WebRTCManager {
      * @param {string} transferId 
      */
     async startFileSend(peerId, transferId) {
-        const progress = await api.getTransferProgress(transferId);
-        if (!progress) return;
+        const totalChunks = this.outgoingTransfers.get(transferId);
+        if (totalChunks === undefined) return;
+        this.outgoingTransfers.delete(transferId);
 
-        for (let i = 0; i < progress.total_chunks; i++) {
+        for (let i = 0; i < totalChunks; i++) {
             const chunk = await api.getFileChunk(transferId, i);
 
             this.sendMessage(peerId, {
@@ -505,6 +516,7 @@ export class WebRTCManager {
 
             // Store the local stream so we can negotiate when a peer accepts
             this.localScreenShares.set(peerId, stream);
+            api.setScreenSharing(true).catch(() => { });
 
             const connection = this.connections.get(peerId);
             if (connection) {
@@ -545,6 +557,9 @@ export class WebRTCManager {
         if (local) {
             local.getTracks().forEach(track => track.stop());
             this.localScreenShares.delete(peerId);
+            if (this.localScreenShares.size === 0) {
+                api.setScreenSharing(false).catch(() => { });
+            }
         }
     }
 
@@ -2,14 +2,22 @@ import React from "react";
 import ReactDOM from "react-dom/client";
 import { HashRouter as Router } from 'react-router-dom';
 import App from "./App";
+import Popout, { isPopoutRoute } from "./Popout";
 import { AppProvider } from "./context/AppContext";
 
+// Pop-out windows load their own route and must not initialise the app again
+const popout = isPopoutRoute(window.location.hash);
+
 ReactDOM.createRoot(document.getElementById("root")).render(
   <React.StrictMode>
     <Router>
-      <AppProvider>
-        <App />
-      </AppProvider>
+      {popout ? (
+        <Popout />
+      ) : (
+        <AppProvider>
+          <App />
+        </AppProvider>
+      )}
     </Router>
   </React.StrictMode>
 );
@@ -14,7 +14,7 @@ import * as api from '../lib/api';
 //  Helper to resolve file URL from message content
 // ═══════════════════════════════════════════════════════════════
 function resolveFileUrl(content, senderIp, localPort) {
-    // Message content for files is JSON: { fileId, fileName, port, type }
+    // Message content for files is JSON: { fileId, fileName, port, type, checksum }
     try {
         const info = JSON.parse(content);
         if (info.fileId) {
@@ -44,7 +44,7 @@ function resolveFileUrl(content, senderIp, localPort) {
 export default function ChatPage() {
     const {
         localUser, deviceId, peers, allUsers, unreadCounts,
-        lastMessages, clearUnread, refreshLastMessages, fileServerPort,
+        lastMessages, clearUnread, fileServerPort,
         flushPendingMessages, setActiveChatPeerId,
     } = useAppContext();
     const chat = useChat();
@@ -52,6 +52,10 @@ export default function ChatPage() {
 
     const [search, setSearch] = useState('');
     const [tab, setTab] = useState('dm'); // 'dm' | 'groups'
+    const [trustedIds, setTrustedIds] = useState(() => new Set());
+    const [pinRequest, setPinRequest] = useState(null);
+    const [fileRequests, setFileRequests] = useState([]);
+    const [pinInput, setPinInput] = useState('');
     const [showProfile, setShowProfile] = useState(null); // peer for profile view
     const [showNewGroup, setShowNewGroup] = useState(false);
     const [contextMenu, setContextMenu] = useState(null); // { x, y, messageId }
@@ -87,6 +91,70 @@ export default function ChatPage() {
         setTimeout(() => setToasts(prev => prev.filter(t => t.id !== id)), 3000);
     }, []);
 
+    // Trusted peers are listed in their own "Verified" section
+    useEffect(() => {
+        api.getTrustedPeers().then(ids => setTrustedIds(new Set(ids || []))).catch(() => {});
+        const unsub = api.onTrustChanged(({ device_id, trusted }) => {
+            setTrustedIds(prev => {
+                const next = new Set(prev);
+                if (trusted) next.add(device_id); else next.delete(device_id);
+                return next;
+            });
+        });
+        return () => { unsub.then?.(fn => fn?.()); };
+    }, []);
+
+    // PIN pairing: a peer asks us to type the PIN shown on its screen
+    useEffect(() => {
+        const unsubs = [
+            api.onPinPairingRequested((request) => {
+                setPinInput('');
+                setPinRequest(request);
+            }),
+            api.onPinPairingResult(({ peer_id, paired, error }) => {
+                setPinRequest(prev => (prev?.peer_id === peer_id ? null : prev));
+                if (paired) showToast('Paired', 'success');
+                else if (error) showToast(error, 'error');
+            }),
+        ];
+        return () => unsubs.forEach(u => u.then?.(fn => fn?.()));
+    }, [showToast]);
+
+    // Incoming file offers wait here until accepted or declined (they expire on their own)
+    useEffect(() => {
+        const refresh = () => api.getFileTransferRequests().then(r => setFileRequests(r || [])).catch(() => {});
+        refresh();
+        const unsubs = [
+            api.onFileTransferRequested(refresh),
+            api.onFileTransferRequestsChanged(refresh),
+            api.onFileTransferRequestExpired(({ file_name }) => showToast(`File offer expired: ${file_name}`, 'info')),
+        ];
+        return () => unsubs.forEach(u => u.then?.(fn => fn?.()));
+    }, [showToast]);
+
+    const handleFileRequest = async (transferId, accept) => {
+        try {
+            await api.respondToFileTransfer(transferId, accept);
+        } catch (e) {
+            showToast(String(e), 'error');
+        }
+        setFileRequests(prev => prev.filter(r => r.transfer_id !== transferId));
+    };
+
+    const handleSubmitPin = async () => {
+        try {
+            await api.submitPairingPin(pinRequest.peer_id, pinInput);
+        } catch (e) {
+            showToast(String(e), 'error');
+            setPinRequest(null);
+        }
+    };
+
+    const handleCancelPin = () => {
+        api.cancelPinPairing(pinRequest.peer_id).catch(() => {});
+        setPinRequest(null);
+    };
+
     // Peer IP lookup
     const peerIpMap = useMemo(() => {
         const map = {};
@@ -178,21 +246,14 @@ export default function ChatPage() {
             }
 
             // Try auto-downloading from remote peer (file will be stored locally)
-            const remoteUrl = `http://${senderIp.split(':')[0]}:${port}/file/${fileId}`;
+            const remoteUrl = api.peerFileUrl(senderIp, port, fileId, info.token);
             const senderName = msg.sender_name || resolveUsernameById(msg.sender_id) || 'Unknown';
             const fileType = msg.message_type || 'file';
 
             console.log('[Pingo] Auto-downloading file:', info.fileName, 'from', remoteUrl);
             try {
-                await api.autoDownloadFile(remoteUrl, senderName, info.fileName || 'file', fileType, msg.id);
-
-                // After download, read as data URL (not HTTP)
-                const dataUrl = await api.readFileAsDataUrl(fileId).catch(() => null);
-                if (dataUrl) {
-                    loadedFileIdsRef.current.add(fileId);
-                    setLoadedFileUrls(prev => ({ ...prev, [fileId]: dataUrl }));
-                    console.log('[Pingo] File downloaded and loaded directly');
-                }
+                // Queued in the backend; the pingo:file-downloaded listener shows it once it lands
+                await api.autoDownloadFile(remoteUrl, senderName, info.fileName || 'file', fileType, msg.id, info.checksum || null);
             } catch (dlErr) {
                 console.warn('[Pingo] Auto-download failed:', dlErr);
             }
@@ -235,7 +296,7 @@ export default function ChatPage() {
             });
         });
 
-        let list = Array.from(map.values());
+        let list = Array.from(map.values()).map(u => ({ ...u, trusted: trustedIds.has(u.device_id || u.id) }));
 
         // Sort: online first, then by last message time, then alphabetical
         list.sort((a, b) => {
@@ -251,7 +312,7 @@ export default function ChatPage() {
             list = list.filter(u => u.username?.toLowerCase().includes(q));
         }
         return list;
-    }, [allUsers, peers, deviceId, lastMessages, search]);
+    }, [allUsers, peers, deviceId, lastMessages, search, trustedIds]);
 
     // ─── Scroll to bottom on new messages ──────────────────
     useEffect(() => {
@@ -285,16 +346,15 @@ export default function ChatPage() {
         setShowGroupInfo(false);
         try {
             const msgs = await api.getGroupMessages(group.id, 200);
-            const port = fileServerPort || await api.getFileServerPort().catch(() => 0);
+            const port = await api.getLocalFileServerPort().catch(() => 0);
             // IMPROVED: Ensure chronological order and properly annotate media messages with local URLs
             const chronological = (msgs || []).slice().reverse().map(m => {
                 try {
                     if (m.message_type === 'image' || m.message_type === 'video' || m.message_type === 'file') {
                         const info = JSON.parse(m.content);
                         if (info && info.fileId) {
-                            const p = info.port || port || 0;
                             // Use local URL as primary for faster loading
-                            const localUrl = `http://127.0.0.1:${p}/file/${info.fileId}`;
+                            const localUrl = api.localFileUrl(port || 0, info.fileId);
                             m._localDataUrl = localUrl;
                             m._fileName = info.fileName || 'file';
                             m._fileType = info.type || m.message_type;
@@ -312,7 +372,7 @@ export default function ChatPage() {
             console.error('Failed to load group:', e);
             showToast('Failed to load group messages', 'error');
         }
-    }, [chat.selectPeer, setActiveChatPeerId, fileServerPort, showToast]);
+    }, [chat.selectPeer, setActiveChatPeerId, showToast]);
 
     // ─── Send DM text ──────────────────────────────────────
     const handleSendDm = useCallback(async () => {
@@ -320,8 +380,7 @@ export default function ChatPage() {
         if (!text || !chat.activePeer) return;
         setInputText('');
         await chat.sendText(chat.activePeer.device_id, text, localUser?.username || '');
-        refreshLastMessages();
-    }, [inputText, chat.activePeer, chat.sendText, localUser, refreshLastMessages]);
+    }, [inputText, chat.activePeer, chat.sendText, localUser]);
 
     // ─── Send group text ───────────────────────────────────
     const handleSendGroup = useCallback(async () => {
@@ -376,7 +435,8 @@ export default function ChatPage() {
                 const fileId = `gf_${Date.now()}_${Math.random().toString(36).slice(2, 8)}`;
                 await api.storeSharedFile(fileId, dataUrl, file.name);
                 const port = await api.getFileServerPort();
-                const fileInfo = JSON.stringify({ fileId, fileName: file.name, port, type: msgType });
+                const token = await api.getSharedFileToken(fileId).catch(() => null);
+                const fileInfo = JSON.stringify({ fileId, fileName: file.name, port, type: msgType, token });
                 const msg = await api.sendGroupMessage(activeGroup.id, fileInfo, msgType);
                 if (msg) {
                     // Use the original dataUrl for sender's immediate view (no HTTP needed)
@@ -461,7 +521,6 @@ export default function ChatPage() {
                 );
                 setUploadProgress(prev => ({ ...prev, [uploadId]: { name: file.name, progress: 100 } }));
                 setTimeout(() => setUploadProgress(prev => { const n = { ...prev }; delete n[uploadId]; return n; }), 1500);
-                refreshLastMessages();
             } catch (err) {
                 setUploadProgress(prev => ({ ...prev, [uploadId]: { name: file.name, progress: 0, error: true } }));
                 setTimeout(() => setUploadProgress(prev => { const n = { ...prev }; delete n[uploadId]; return n; }), 3000);
@@ -473,7 +532,7 @@ export default function ChatPage() {
             showToast('Failed to read file', 'error');
         };
         reader.readAsDataURL(file);
-    }, [chat.activePeer, chat.sendFile, localUser, refreshLastMessages, showToast]);
+    }, [chat.activePeer, chat.sendFile, localUser, showToast]);
 
     // ─── Dev helper: resend pending messages for active peer (manual test)
     const handleResendPending = useCallback(async () => {
@@ -497,13 +556,12 @@ export default function ChatPage() {
             await flushPendingMessages(peerId);
             console.log('[Pingo] flushPendingMessages completed for', peerId);
             showToast && showToast('Resend attempted', 'success');
-            refreshLastMessages().catch(() => { });
             if (chat.activePeer) chat.loadMessages(peerId).catch(() => { });
         } catch (err) {
             console.error('[Pingo] flushPendingMessages error', err);
             showToast && showToast('Resend failed', 'error');
         }
-    }, [chat.activePeer, chat.loadMessages, flushPendingMessages, refreshLastMessages, showToast]);
+    }, [chat.activePeer, chat.loadMessages, flushPendingMessages, showToast]);
 
     // ─── Drag & drop files ─────────────────────────────────
     const handleDrop = useCallback(async (e) => {
@@ -543,7 +601,6 @@ export default function ChatPage() {
                 );
                 setUploadProgress(prev => ({ ...prev, [uploadId]: { name: file.name, progress: 100 } }));
                 setTimeout(() => setUploadProgress(prev => { const n = { ...prev }; delete n[uploadId]; return n; }), 1500);
-                refreshLastMessages();
             } catch (err) {
                 setUploadProgress(prev => { const n = { ...prev }; delete n[uploadId]; return n; });
                 showToast(`Failed to send ${file.name}`, 'error');
@@ -551,7 +608,7 @@ export default function ChatPage() {
         };
         reader.onerror = () => setUploadProgress(prev => { const n = { ...prev }; delete n[uploadId]; return n; });
         reader.readAsDataURL(file);
-    }, [chat.activePeer, chat.sendFile, localUser, refreshLastMessages, showToast]);
+    }, [chat.activePeer, chat.sendFile, localUser, showToast]);
 
     // ─── Screenshot capture — uses native Rust backend ────
     const handleScreenshot = useCallback(async () => {
@@ -582,8 +639,7 @@ export default function ChatPage() {
             croppedDataUrl, 'screenshot.png', 'image',
             localUser?.username || '', getLocalIp(),
         );
-        refreshLastMessages();
-    }, [chat.activePeer, chat.sendFile, localUser, refreshLastMessages]);
+    }, [chat.activePeer, chat.sendFile, localUser]);
 
     // ─── Key handler ───────────────────────────────────────
     const handleKeyDown = (e) => {
@@ -603,16 +659,14 @@ export default function ChatPage() {
         if (!contextMenu?.message) return;
         await chat.deleteMsg(contextMenu.message.id);
         setContextMenu(null);
-        refreshLastMessages();
-    }, [contextMenu, chat.deleteMsg, refreshLastMessages]);
+    }, [contextMenu, chat.deleteMsg]);
 
     const handleClearAllChat = useCallback(async () => {
         const peerId = chat.activePeer?.device_id;
         if (!peerId) return;
         await chat.deleteAllMessages(peerId);
         setConfirmClearChat(false);
-        refreshLastMessages();
-    }, [chat.activePeer, chat.deleteAllMessages, refreshLastMessages]);
+    }, [chat.activePeer, chat.deleteAllMessages]);
 
     // ─── Download file (native save dialog) ───────────────
     const handleDownload = useCallback(async (url, fileName) => {
@@ -653,12 +707,12 @@ export default function ChatPage() {
                 // Fallback: check organized downloads with own username
                 if (!localPath) {
                     const senderName = localUser?.username || 'Me';
-                    localPath = await api.checkFileDownloaded(senderName, fileName, fileType).catch(() => null);
+                    localPath = await api.checkFileDownloaded(senderName, fileName, fileType, deviceId).catch(() => null);
                 }
             } else {
                 // For received files: check organized downloads folder
                 const senderName = msg.sender_name || resolveUsernameById(msg.sender_id) || 'Unknown';
-                localPath = await api.checkFileDownloaded(senderName, fileName, fileType).catch(() => null);
+                localPath = await api.checkFileDownloaded(senderName, fileName, fileType, msg.sender_id).catch(() => null);
                 // Fallback: check shared_files by fileId (auto-download saves there too)
                 if (!localPath && fileId) {
                     localPath = await api.getSharedFilePath(fileId).catch(() => null);
@@ -685,8 +739,8 @@ export default function ChatPage() {
 
     // ─── Listen for group messages in real-time ─────────
     useEffect(() => {
-        // Listen globally for all group messages (for last message tracking)
-        const globalUnsub = api.onGroupMessageReceived(msg => {
+        // Listen globally for all group and channel messages (for last message tracking)
+        const onMessage = msg => {
             // Update group last message for sidebar preview
             const previewContent = msg.message_type === 'text'
                 ? msg.content.substring(0, 40) + (msg.content.length > 40 ? '...' : '')
@@ -719,19 +773,9 @@ export default function ChatPage() {
                                 // If it's from another peer, trigger background download so the file
                                 // becomes available and `pingo:file-downloaded` will update loadedFileUrls
                                 if (msg.sender_id !== deviceId && senderIp && p) {
-                                    const remoteUrl = `http://${senderIp.split(':')[0]}:${p}/file/${info.fileId}`;
+                                    const remoteUrl = api.peerFileUrl(senderIp, p, info.fileId, info.token);
                                     const senderName = msg.sender_name || 'Unknown';
-                                    api.autoDownloadFile(remoteUrl, senderName, info.fileName || 'file', newMsg._fileType, msg.id)
-                                        .then(async () => {
-                                            try {
-                                                const dataUrl = await api.readFileAsDataUrl(info.fileId);
-                                                if (dataUrl && typeof window !== 'undefined') {
-                                                    window.dispatchEvent(new CustomEvent('pingo:file-downloaded', {
-                                                        detail: { fileId: info.fileId, dataUrl }
-                                                    }));
-                                                }
-                                            } catch { /* ignore */ }
-                                        })
+                                    api.autoDownloadFile(remoteUrl, senderName, info.fileName || 'file', newMsg._fileType, msg.id, info.checksum || null)
                                         .catch(() => { /* ignore */ });
                                 }
                             }
@@ -740,8 +784,9 @@ export default function ChatPage() {
                     return [...prev, newMsg];
                 });
             }
-        });
-        return () => { globalUnsub.then?.(fn => fn?.()); };
+        };
+        const unsubs = [api.onGroupMessageReceived(onMessage), api.onChannelMessageReceived(onMessage)];
+        return () => { unsubs.forEach(u => u.then?.(fn => fn?.())); };
     }, [activeGroup, peerIpMap, fileServerPort, deviceId]);
 
     // ─── Listen for group member changes in real-time ─────
@@ -779,18 +824,17 @@ export default function ChatPage() {
         const peerId = chat.activePeer.device_id;
         (async () => {
             try {
-                // Load all media messages from DB
-                const media = await api.getSharedMedia(peerId);
+                // Most recent page of media messages from DB
+                const media = await api.getSharedMedia(peerId, null, { limit: 200 });
                 if (media) {
                     // Annotate each with resolved URLs
-                    const port = fileServerPort || await api.getFileServerPort().catch(() => 0);
+                    const port = await api.getLocalFileServerPort().catch(() => 0);
                     const annotated = (media || []).map(m => {
                         try {
                             if (m.message_type === 'image' || m.message_type === 'video' || m.message_type === 'file') {
                                 const info = JSON.parse(m.content);
                                 if (info && info.fileId) {
-                                    const p = info.port || port || 0;
-                                    m._localDataUrl = `http://127.0.0.1:${p}/file/${info.fileId}`;
+                                    m._localDataUrl = api.localFileUrl(port || 0, info.fileId);
                                     m._fileName = info.fileName || 'file';
                                     m._fileType = info.type || m.message_type;
                                     m._fileId = info.fileId;
@@ -805,7 +849,7 @@ export default function ChatPage() {
                 console.warn('[Pingo] Failed to load shared media:', e);
             }
         })();
-    }, [showSharedMedia, chat.activePeer, fileServerPort]);
+    }, [showSharedMedia, chat.activePeer]);
 
     // ─── handle notification click — open specific chat ──────
     useEffect(() => {
@@ -883,18 +927,12 @@ export default function ChatPage() {
             if (!senderIp) senderIp = allUsers?.find(u => u.id === msg.sender_id)?.ip_address;
 
             if (senderIp && info.port) {
-                const remoteUrl = `http://${senderIp.split(':')[0]}:${info.port}/file/${fileId}`;
+                const remoteUrl = api.peerFileUrl(senderIp, info.port, fileId, info.token);
                 const senderName = msg.sender_name || 'Unknown';
                 const fileType = msg.message_type || 'file';
                 try {
-                    await api.autoDownloadFile(remoteUrl, senderName, info.fileName || 'file', fileType, msg.id);
-                    if (!cancelled) {
-                        const dataUrl = await api.readFileAsDataUrl(fileId).catch(() => null);
-                        if (dataUrl) {
-                            loadedFileIdsRef.current.add(fileId);
-                            setLoadedFileUrls(prev => ({ ...prev, [fileId]: dataUrl }));
-                        }
-                    }
+                    // Resolves once queued; pingo:file-downloaded fills loadedFileUrls when it lands
+                    await api.autoDownloadFile(remoteUrl, senderName, info.fileName || 'file', fileType, msg.id, info.checksum || null);
                 } catch { /* ignore — UI shows retry button */ }
             }
             loadingFilesRef.current.delete(fileId);
@@ -935,8 +973,8 @@ export default function ChatPage() {
         // ⚠ DO NOT add loadedFileUrls here — that would re-run on every file load (O(n²))
     }, [chat.messages, deviceId, peerIpMap, peers, allUsers]); // eslint-disable-line react-hooks/exhaustive-deps
 
-    // ─── Listen for completed downloads (emitted by useApp.js autoDownloadFileMessage) ─────
-    // When autoDownloadFileMessage finishes in the background, it dispatches this window event
+    // ─── Listen for completed downloads (dispatched by useApp.js on download-finished) ─────
+    // When a pooled download finishes in the background, useApp dispatches this window event
     // so we can update loadedFileUrls without waiting for the next chat.messages render cycle.
     useEffect(() => {
         if (typeof window === 'undefined') return;
@@ -1018,6 +1056,40 @@ export default function ChatPage() {
             || activePeer.avatar_path;
     }, [activePeer?.device_id, peers, allUsers]);
 
+    const verifiedUsers = userList.filter(u => u.trusted);
+    const otherUsers = userList.filter(u => !u.trusted);
+
+    const renderPeerItem = (u) => {
+        const uid = u.device_id || u.id;
+        const lm = lastMessages[uid];
+        const unread = unreadCounts[uid] || 0;
+        const isActive = activePeer?.device_id === uid && !activeGroup;
+        return (
+            <div
+                key={uid}
+                className={`peer-item ${isActive ? 'active' : ''}`}
+                onClick={() => selectDmPeer(u)}
+            >
+                <div className="peer-avatar-wrap">
+                    <UserAvatar name={u.username} size={38} avatarUrl={u.avatar_path} />
+                    <span className={`status-dot ${u.is_online ? 'online' : 'offline'}`} />
+                </div>
+                <div className="peer-info">
+                    <span className="peer-name">{u.username || 'Unknown'}</span>
+                    <span className="peer-preview">
+                        {lm ? ((lm.message_type === 'text' || !lm.message_type) ? lm.content?.slice(0, 40) : `📎 ${lm.message_type}`) : (u.is_online ? 'Online' : 'Offline')}
+                    </span>
+                </div>
+                <div className="peer-meta">
+                    {lm?.created_at && (
+                        <span className="peer-time">{formatTime(lm.created_at)}</span>
+                    )}
+                    {unread > 0 && <span className="unread-badge">{unread}</span>}
+                </div>
+            </div>
+        );
+    };
+
     return (
         <div className="chat-page">
             {/* ─── Sidebar ─────────────────────────────────── */}
@@ -1059,36 +1131,14 @@ export default function ChatPage() {
                                 </p>
                             </div>
                         ) : (
-                            userList.map(u => {
-                                const uid = u.device_id || u.id;
-                                const lm = lastMessages[uid];
-                                const unread = unreadCounts[uid] || 0;
-                                const isActive = activePeer?.device_id === uid && !activeGroup;
-                                return (
-                                    <div
-                                        key={uid}
-                                        className={`peer-item ${isActive ? 'active' : ''}`}
-                                        onClick={() => selectDmPeer(u)}
-                                    >
-                                        <div className="peer-avatar-wrap">
-                                            <UserAvatar name={u.username} size={38} avatarUrl={u.avatar_path} />
-                                            <span className={`status-dot ${u.is_online ? 'online' : 'offline'}`} />
-                                        </div>
-                                        <div className="peer-info">
-                                            <span className="peer-name">{u.username || 'Unknown'}</span>
-                                            <span className="peer-preview">
-                                                {lm ? ((lm.message_type === 'text' || !lm.message_type) ? lm.content?.slice(0, 40) : `📎 ${lm.message_type}`) : (u.is_online ? 'Online' : 'Offline')}
-                                            </span>
-                                        </div>
-                                        <div className="peer-meta">
-                                            {lm?.created_at && (
-                                                <span className="peer-time">{formatTime(lm.created_at)}</span>
-                                            )}
-                                            {unread > 0 && <span className="unread-badge">{unread}</span>}
-                                        </div>
-                                    </div>
-                                );
-                            })
+                            <>
+                                {verifiedUsers.length > 0 && <div className="peer-section-label">Verified</div>}
+                                {verifiedUsers.map(renderPeerItem)}
+                                {verifiedUsers.length > 0 && otherUsers.length > 0 && (
+                                    <div className="peer-section-label">Others</div>
+                                )}
+                                {otherUsers.map(renderPeerItem)}
+                            </>
                         )
                     ) : (
                         /* Groups tab */
@@ -1745,6 +1795,52 @@ export default function ChatPage() {
                 </div>
             )}
 
+            {/* ─── Incoming file offer ─────────────────────── */}
+            {fileRequests.length > 0 && !pinRequest && (
+                <div className="modal-overlay">
+                    <div className="confirm-dialog" onClick={e => e.stopPropagation()}>
+                        <h4>{fileRequests[0].sender_name || fileRequests[0].peer_id.slice(0, 12)} wants to send a file</h4>
+                        <p>
+                            {fileRequests[0].file_name} ({(fileRequests[0].file_size / 1024 / 1024).toFixed(1)} MB)
+                            {fileRequests.length > 1 && ` — ${fileRequests.length - 1} more waiting`}
+                        </p>
+                        <div className="modal-actions">
+                            <button className="btn-secondary" onClick={() => handleFileRequest(fileRequests[0].transfer_id, false)}>
+                                Decline
+                            </button>
+                            <button className="btn-primary" onClick={() => handleFileRequest(fileRequests[0].transfer_id, true)}>
+                                Accept
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+
+            {/* ─── PIN pairing prompt ──────────────────────── */}
+            {pinRequest && (
+                <div className="modal-overlay" onClick={handleCancelPin}>
+                    <div className="confirm-dialog" onClick={e => e.stopPropagation()}>
+                        <h4>Pair with {pinRequest.username || pinRequest.peer_id.slice(0, 12)}?</h4>
+                        <p>Enter the PIN shown on their screen.</p>
+                        <input
+                            inputMode="numeric"
+                            maxLength={6}
+                            autoFocus
+                            value={pinInput}
+                            onChange={e => setPinInput(e.target.value.replace(/\D/g, ''))}
+                            onKeyDown={e => { if (e.key === 'Enter' && pinInput.length === 6) handleSubmitPin(); }}
+                            style={{ width: '100%', marginBottom: 12, padding: '8px 12px', borderRadius: 8, border: '1px solid var(--border)', background: 'var(--bg-secondary)', fontFamily: 'monospace', letterSpacing: 4 }}
+                        />
+                        <div className="modal-actions">
+                            <button className="btn-secondary" onClick={handleCancelPin}>Cancel</button>
+                            <button className="btn-primary" disabled={pinInput.length !== 6} onClick={handleSubmitPin}>
+                                Pair
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+
             {/* ─── New group dialog ────────────────────────── */}
             {showNewGroup && (
                 <NewGroupDialog
@@ -0,0 +1,114 @@
+// src/pages/chatWindow.jsx
+// Pop-out chat window — one conversation in its own window. The backend routes that peer's
+// messages here as chat-window-message, so this page keeps no global listeners of its own.
+
+import React, { useState, useEffect, useRef } from 'react';
+import { useParams } from 'react-router-dom';
+import * as api from '../lib/api';
+
+const HISTORY = 100;
+
+export default function ChatWindowPage() {
+    const { peerId } = useParams();
+    const [peerName, setPeerName] = useState('');
+    const [messages, setMessages] = useState([]);
+    const [text, setText] = useState('');
+    const [error, setError] = useState(null);
+    const bottomRef = useRef(null);
+
+    const append = (msg) => {
+        if (!msg?.id) return;
+        setMessages(prev => prev.some(m => m.id === msg.id) ? prev : [...prev, msg]);
+    };
+
+    useEffect(() => {
+        api.getUser(peerId)
+            .then(user => {
+                setPeerName(user?.username || peerId);
+                document.title = `Pingo - ${user?.username || peerId}`;
+            })
+            .catch(() => setPeerName(peerId));
+        // Newest-first from the API; shown oldest-first
+        api.getMessages(peerId, HISTORY)
+            .then(msgs => setMessages((msgs || []).slice().reverse()))
+            .catch(e => setError(String(e)));
+        api.markMessagesReadFromPeer(peerId).catch(() => { });
+
+        let unlisten;
+        api.onChatWindowMessage((event) => {
+            append(event.payload);
+            if (event.payload?.sender_id === peerId) {
+                api.markMessagesReadFromPeer(peerId).catch(() => { });
+            }
+        }).then(fn => { unlisten = fn; });
+        return () => { if (unlisten) unlisten(); };
+    }, [peerId]);
+
+    useEffect(() => {
+        bottomRef.current?.scrollIntoView({ block: 'end' });
+    }, [messages]);
+
+    const send = async () => {
+        if (!text.trim()) return;
+        const content = text;
+        setText('');
+        setError(null);
+        try {
+            append(await api.sendMessage(peerId, content));
+        } catch (e) {
+            setText(content);
+            setError(String(e));
+        }
+    };
+
+    const onKeyDown = (e) => {
+        if (e.key === 'Enter' && !e.shiftKey) {
+            e.preventDefault();
+            send();
+        }
+    };
+
+    return (
+        <div className="chat-window">
+            <div className="chat-window-header">{peerName}</div>
+            <div className="chat-messages">
+                {messages.map(msg => {
+                    const isMine = msg.sender_id !== peerId;
+                    return (
+                        <div key={msg.id} className={`msg ${isMine ? 'msg-out' : 'msg-in'}`}>
+                            <div className="msg-bubble">
+                                <span className="msg-text">
+                                    {msg.message_type === 'text' ? msg.content : `[${msg.message_type}]`}
+                                </span>
+                                <span className="msg-time">{formatTime(msg.created_at)}</span>
+                            </div>
+                        </div>
+                    );
+                })}
+                <div ref={bottomRef} />
+            </div>
+            {error && <div className="chat-window-error">{error}</div>}
+            <div className="chat-window-input">
+                <textarea
+                    rows={2} placeholder={`Message ${peerName}…`}
+                    value={text} onChange={e => setText(e.target.value)} onKeyDown={onKeyDown}
+                    autoFocus
+                />
+                <button className="btn-primary" onClick={send} disabled={!text.trim()}>Send</button>
+            </div>
+        </div>
+    );
+}
+
+function formatTime(ts) {
+    if (!ts) return '';
+    try {
+        const d = new Date(ts);
+        if (d.toDateString() === new Date().toDateString()) {
+            return d.toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' });
+        }
+        return d.toLocaleDateString([], { month: 'short', day: 'numeric' });
+    } catch {
+        return '';
+    }
+}
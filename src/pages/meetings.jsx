@@ -20,7 +20,6 @@ import {
   sendMeetingEnded,
   sendMeetingScreenShareInvite,
   sendMeetingRejoinRequest,
-  sendMeetingParticipantList,
   broadcastMeeting,
   loadMeetingChat,
   saveMeetingChat,
@@ -304,11 +303,7 @@ export default function MeetingsPage() {
         });
         showToast(`${msg.username || peerNameMap[msg.from] || 'User'} joined`, 'success');
         await negotiateWithPeer(msg.from);
-        // Send back participant list
-        const currentParts = participantsRef.current.map(p => p.peerId);
-        try {
-          await sendMeetingParticipantList(msg.from, deviceId, meetingIdRef.current, currentParts);
-        } catch { }
+        // The backend answers with the participant list from its roster
         return;
       }
 
@@ -0,0 +1,89 @@
+// src/pages/quickReply.jsx
+// Quick reply popup — last few messages with one peer and a reply box. Enter sends (the backend
+// closes the popup), Escape dismisses it. The popup is reused: quick-reply-target retargets it.
+
+import React, { useState, useEffect, useRef } from 'react';
+import { useParams } from 'react-router-dom';
+import * as api from '../lib/api';
+
+const RECENT_MESSAGES = 5;
+
+export default function QuickReplyPage() {
+    const { peerId: routePeerId } = useParams();
+    const [peerId, setPeerId] = useState(routePeerId);
+    const [peerName, setPeerName] = useState('');
+    const [recent, setRecent] = useState([]);
+    const [text, setText] = useState('');
+    const [sending, setSending] = useState(false);
+    const [error, setError] = useState(null);
+    const inputRef = useRef(null);
+
+    useEffect(() => {
+        let unlisten;
+        api.onQuickReplyTarget((event) => {
+            if (event.payload?.peer_id) setPeerId(event.payload.peer_id);
+        }).then(fn => { unlisten = fn; });
+        return () => { if (unlisten) unlisten(); };
+    }, []);
+
+    useEffect(() => {
+        if (!peerId) return;
+        setText('');
+        setError(null);
+        api.getUser(peerId)
+            .then(user => setPeerName(user?.username || peerId))
+            .catch(() => setPeerName(peerId));
+        // Newest-first from the API; shown oldest-first
+        api.getMessages(peerId, RECENT_MESSAGES)
+            .then(msgs => setRecent((msgs || []).filter(m => m.message_type === 'text').reverse()))
+            .catch(() => setRecent([]));
+        inputRef.current?.focus();
+    }, [peerId]);
+
+    const send = async () => {
+        if (!text.trim() || sending) return;
+        setSending(true);
+        setError(null);
+        try {
+            await api.sendQuickReply(peerId, text);
+            setText('');
+        } catch (e) {
+            setError(String(e));
+        } finally {
+            setSending(false);
+        }
+    };
+
+    const onKeyDown = (e) => {
+        if (e.key === 'Enter' && !e.shiftKey) {
+            e.preventDefault();
+            send();
+        } else if (e.key === 'Escape') {
+            api.closeQuickReplyWindow();
+        }
+    };
+
+    return (
+        <div className="quick-reply">
+            <div className="quick-reply-header">
+                <span>{peerName}</span>
+                <button className="icon-btn" onClick={() => api.closeQuickReplyWindow()} title="Close">×</button>
+            </div>
+            <div className="quick-reply-recent">
+                {recent.map(msg => (
+                    <div key={msg.id} className={`msg ${msg.sender_id === peerId ? 'msg-in' : 'msg-out'}`}>
+                        <div className="msg-bubble">
+                            <span className="msg-text">{msg.content}</span>
+                        </div>
+                    </div>
+                ))}
+            </div>
+            {error && <div className="quick-reply-error">{error}</div>}
+            <textarea
+                ref={inputRef} rows={2} placeholder={`Reply to ${peerName}…`}
+                value={text} onChange={e => setText(e.target.value)} onKeyDown={onKeyDown}
+                disabled={sending} autoFocus
+            />
+        </div>
+    );
+}
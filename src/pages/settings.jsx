@@ -289,10 +289,102 @@ function ChatLogsSection() {
 }
 
 
+function PrivacySection() {
+    const [enabled, setEnabled] = useState(false);
+    const [visible, setVisible] = useState(true);
+    const [requirePin, setRequirePin] = useState(false);
+    const [requests, setRequests] = useState([]);
+
+    const refresh = useCallback(async () => {
+        try {
+            setEnabled(!!(await api.getPrivacyMode()));
+            setVisible((await api.getVisibility()) !== false);
+            setRequirePin(!!(await api.getRequirePinPairing()));
+            setRequests((await api.getMessageRequests()) || []);
+        } catch (e) {
+            console.error('Failed to load message requests:', e);
+        }
+    }, []);
+
+    useEffect(() => {
+        refresh();
+        const unsubs = [api.onMessageRequest(refresh), api.onMessageRequestsChanged(refresh)];
+        return () => unsubs.forEach(u => u.then?.(fn => fn?.()));
+    }, [refresh]);
+
+    const handleToggle = async () => {
+        await api.setPrivacyMode(!enabled);
+        setEnabled(!enabled);
+    };
+
+    const handleToggleRequirePin = async () => {
+        await api.setRequirePinPairing(!requirePin);
+        setRequirePin(!requirePin);
+    };
+
+    const handleToggleVisible = async () => {
+        await api.setVisibility(!visible);
+        setVisible(!visible);
+    };
+
+    // One entry per sender; accepting or declining applies to everything it sent
+    const bySender = requests.reduce((acc, r) => {
+        (acc[r.peer_id] = acc[r.peer_id] || []).push(r);
+        return acc;
+    }, {});
+
+    return (
+        <div>
+            <div className="settings-row clickable" onClick={handleToggleVisible}>
+                <span className="settings-label">Invisible on the network</span>
+                <div className={`toggle-switch ${!visible ? 'active' : ''}`}>
+                    <div className="toggle-knob" />
+                </div>
+            </div>
+            <p style={{ fontSize: 12, color: 'var(--text-muted)', margin: '8px 0 12px' }}>
+                You still see peers and receive messages from contacts who know your address, but you're not announced.
+            </p>
+            <div className="settings-row clickable" onClick={handleToggleRequirePin}>
+                <span className="settings-label">Require PIN pairing for new contacts</span>
+                <div className={`toggle-switch ${requirePin ? 'active' : ''}`}>
+                    <div className="toggle-knob" />
+                </div>
+            </div>
+            <p style={{ fontSize: 12, color: 'var(--text-muted)', margin: '8px 0 12px' }}>
+                Nothing is exchanged with a new peer until one of you shows a PIN (from their profile) and the other types it in.
+            </p>
+            <div className="settings-row clickable" onClick={handleToggle}>
+                <span className="settings-label">Hold messages from unknown peers</span>
+                <div className={`toggle-switch ${enabled ? 'active' : ''}`}>
+                    <div className="toggle-knob" />
+                </div>
+            </div>
+            <p style={{ fontSize: 12, color: 'var(--text-muted)', margin: '8px 0 12px' }}>
+                Messages, files and group invites from peers you haven't talked to wait here until you accept them.
+            </p>
+            {Object.entries(bySender).map(([peerId, items]) => (
+                <div key={peerId} className="settings-row">
+                    <span className="settings-label">
+                        {items[0].sender_name || peerId.slice(0, 12)}
+                        <span style={{ display: 'block', fontSize: 12, color: 'var(--text-muted)' }}>
+                            {items.length} held · {items[items.length - 1].preview}
+                        </span>
+                    </span>
+                    <div style={{ display: 'flex', gap: 8 }}>
+                        <button className="btn-sm btn-primary" onClick={() => api.acceptMessageRequest(peerId)}>Accept</button>
+                        <button className="btn-sm btn-secondary" onClick={() => api.declineMessageRequest(peerId)}>Decline</button>
+                    </div>
+                </div>
+            ))}
+        </div>
+    );
+}
+
 export default function SettingsPage() {
     const { localUser, updateProfile, deviceId } = useAppContext();
     const [settings, setSettings] = useState({});
     const [notifMuted, setNotifMuted] = useState(false);
+    const [allowWan, setAllowWan] = useState(false);
     const [saved, setSaved] = useState(false);
 
     useEffect(() => {
@@ -306,6 +398,7 @@ export default function SettingsPage() {
                 }
                 const muted = await api.isNotificationsMuted();
                 setNotifMuted(!!muted);
+                setAllowWan(!!(await api.getAllowWan()));
             } catch (e) {
                 console.error('Failed to load settings:', e);
             }
@@ -317,6 +410,11 @@ export default function SettingsPage() {
         setNotifMuted(result);
     }, []);
 
+    const handleToggleWan = useCallback(async () => {
+        await api.setAllowWan(!allowWan);
+        setAllowWan(!allowWan);
+    }, [allowWan]);
+
     const handleSetSetting = useCallback(async (key, value) => {
         await api.setSetting(key, value);
         setSettings(prev => ({ ...prev, [key]: value }));
@@ -361,6 +459,12 @@ export default function SettingsPage() {
                     </div>
                 </section>
 
+                {/* ── Privacy section ── */}
+                <section className="settings-section">
+                    <h3>Privacy</h3>
+                    <PrivacySection />
+                </section>
+
                 {/* ── Chat Logs section ── */}
                 <section className="settings-section">
                     <h3>Chat Logs</h3>
@@ -400,6 +504,15 @@ export default function SettingsPage() {
                         <span className="settings-label">File Server Port</span>
                         <span className="settings-value">18080 (HTTP)</span>
                     </div>
+                    <div className="settings-row clickable" onClick={handleToggleWan}>
+                        <span className="settings-label">Allow peers outside the local network</span>
+                        <div className={`toggle-switch ${allowWan ? 'active' : ''}`}>
+                            <div className="toggle-knob" />
+                        </div>
+                    </div>
+                    <p style={{ fontSize: 12, color: 'var(--text-muted)', margin: '8px 0 0' }}>
+                        Off: messages and file requests from public addresses are ignored, which keeps you safe on public Wi-Fi.
+                    </p>
                 </section>
 
                 {/* ── About section ── */}